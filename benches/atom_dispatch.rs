@@ -0,0 +1,72 @@
+//! Dispatches on the atoms of a decoded term, by name and by the ids of an `AtomSet`.
+//!
+//! Run with `cargo bench --bench atom_dispatch`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use eetf::{Atom, AtomSet, AtomTable, Decoder, Term};
+
+const NAMES: [&str; 6] = [
+    "ok",
+    "error",
+    "noreply",
+    "reply",
+    "inserted_at",
+    "updated_at",
+];
+
+/// A decoded list of `count` atoms of the set.
+fn atoms(count: usize, set: &AtomSet) -> Vec<Term> {
+    let atoms = (0..count)
+        .map(|i| Term::from(Atom::from(NAMES[i % NAMES.len()])))
+        .collect::<Vec<_>>();
+    let bytes = eetf::encode_to_vec(&Term::from(eetf::List::from(atoms))).unwrap();
+    let term = Decoder::new(&bytes[..])
+        .with_atom_table(set.table().clone())
+        .decode()
+        .unwrap();
+    let Term::List(list) = term else {
+        unreachable!()
+    };
+    list.elements
+}
+
+fn by_name(term: &Term) -> usize {
+    let Term::Atom(atom) = term else { return 0 };
+    match atom.as_str() {
+        "ok" => 1,
+        "error" => 2,
+        "noreply" => 3,
+        "reply" => 4,
+        "inserted_at" => 5,
+        "updated_at" => 6,
+        _ => 0,
+    }
+}
+
+fn by_id(term: &Term, set: &AtomSet) -> usize {
+    match term.atom_id(set) {
+        Some(id) => id + 1,
+        None => 0,
+    }
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("atom_dispatch");
+    let set = AtomSet::new(&AtomTable::new(), NAMES);
+    let terms = atoms(100_000, &set);
+    group.throughput(Throughput::Elements(100_000));
+    group.bench_function("by_name", |b| {
+        b.iter(|| black_box(&terms).iter().map(by_name).sum::<usize>())
+    });
+    group.bench_function("AtomSet", |b| {
+        b.iter(|| {
+            black_box(&terms)
+                .iter()
+                .map(|term| by_id(term, &set))
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
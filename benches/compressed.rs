@@ -0,0 +1,51 @@
+//! Compresses and decompresses a 20 MB term with the zlib backend of the build.
+//!
+//! Run with `cargo bench --bench compressed` for `libflate`, and with
+//! `cargo bench --bench compressed --features flate2` for `flate2`.
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use eetf::{Atom, Binary, List, Term, Tuple};
+
+/// Makes a list of `{event, Id, <<"payload Id">>}` terms which encodes to at least `size` bytes.
+fn make_term(size: usize) -> Term {
+    let mut elements = Vec::new();
+    let mut written = 0;
+    while written < size {
+        let id = elements.len() as i32;
+        let element = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("event")),
+            Term::from(id),
+            Term::from(Binary::from(format!("payload {}", id).into_bytes())),
+        ]));
+        written += element.encoded_size().unwrap();
+        elements.push(element);
+    }
+    Term::from(List::from(elements))
+}
+
+fn compressed(c: &mut Criterion) {
+    let term = make_term(20 << 20);
+    let mut compressed = Vec::new();
+    term.encode_compressed(&mut compressed).unwrap();
+    println!("compressed to {} bytes", compressed.len());
+
+    let mut group = c.benchmark_group("compressed");
+    group.throughput(Throughput::Bytes(term.encoded_size().unwrap() as u64));
+    group.sample_size(10);
+    group.bench_function("encode_compressed", |b| {
+        b.iter(|| {
+            let mut buf = Vec::with_capacity(compressed.len());
+            term.encode_compressed(&mut buf).unwrap();
+            buf
+        })
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| Term::decode(compressed.as_slice()).unwrap())
+    });
+    group.bench_function("decode_from_slice", |b| {
+        b.iter(|| eetf::decode_from_slice(&compressed).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, compressed);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Compares `Decoder::from_buf_read` with `Decoder::new` on a 50 MB file of small terms.
+//!
+//! Run with `cargo bench --bench decode_buf_read`.
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use eetf::{Atom, Binary, Decoder, Encoder, Term, Tuple};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+/// Writes `{event, Id, <<"payload">>}` terms until the file holds at least `size` bytes, and
+/// returns the number of terms.
+fn write_terms(path: &std::path::Path, size: usize) -> usize {
+    let mut writer = BufWriter::new(File::create(path).unwrap());
+    let (mut count, mut written) = (0, 0);
+    while written < size {
+        let term = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("event")),
+            Term::from(count as i32),
+            Term::from(Binary::from(b"payload".to_vec())),
+        ]));
+        let bytes = eetf::encode_to_vec(&term).unwrap();
+        Encoder::new(&mut writer).encode(&term).unwrap();
+        written += bytes.len();
+        count += 1;
+    }
+    writer.flush().unwrap();
+    count
+}
+
+fn decode(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("eetf-decode-buf-read-{}", std::process::id()));
+    let size = 50 << 20;
+    let count = write_terms(&path, size);
+
+    let mut group = c.benchmark_group("decode_file");
+    group.throughput(Throughput::Bytes(size as u64));
+    group.sample_size(10);
+    group.bench_function("Decoder::new", |b| {
+        b.iter(|| {
+            let mut decoder = Decoder::new(BufReader::new(File::open(&path).unwrap()));
+            for _ in 0..count {
+                decoder.decode_next().unwrap();
+            }
+        })
+    });
+    group.bench_function("Decoder::from_buf_read", |b| {
+        b.iter(|| {
+            let reader = BufReader::new(File::open(&path).unwrap());
+            let mut decoder = Decoder::from_buf_read(reader);
+            for _ in 0..count {
+                decoder.decode_next().unwrap();
+            }
+        })
+    });
+    group.finish();
+    std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
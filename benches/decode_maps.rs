@@ -0,0 +1,74 @@
+//! Decodes streams of small maps (the typical Elixir struct), and looks up their fields.
+//!
+//! Run with `cargo bench --bench decode_maps`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use eetf::{Atom, AtomMap, Decoder, Map, Term};
+
+/// A map with five atom keys.
+fn map(i: usize) -> Map {
+    let keys = ["id", "name", "email", "inserted_at", "updated_at"];
+    Map::from(
+        keys.iter()
+            .map(|&k| (Term::from(Atom::from(k)), Term::from(i)))
+            .collect::<std::collections::HashMap<_, _>>(),
+    )
+}
+
+/// A list of `count` maps with five atom keys each.
+fn maps(count: usize) -> Vec<u8> {
+    let maps = (0..count).map(|i| Term::from(map(i))).collect::<Vec<_>>();
+    eetf::encode_to_vec(&Term::from(eetf::List::from(maps))).unwrap()
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_maps");
+    let bytes = maps(100_000);
+    group.throughput(Throughput::Elements(100_000));
+    group.sample_size(20);
+    group.bench_function("Term::decode", |b| {
+        b.iter(|| Term::decode(black_box(&bytes[..])).unwrap())
+    });
+    group.bench_function("decode_from_slice", |b| {
+        b.iter(|| eetf::decode_from_slice(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+
+    // One map, as a `Map` and as an `AtomMap`.
+    let bytes = eetf::encode_to_vec(&Term::from(map(0))).unwrap();
+    let mut group = c.benchmark_group("decode_map");
+    group.bench_function("Term::decode", |b| {
+        b.iter(|| Term::decode(black_box(&bytes[..])).unwrap())
+    });
+    group.bench_function("decode_as::<AtomMap>", |b| {
+        b.iter(|| {
+            Decoder::new(black_box(&bytes[..]))
+                .decode_as::<AtomMap>()
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let map = map(0);
+    let atom_map = AtomMap::try_from(map.clone()).unwrap();
+    let key = Term::from(Atom::from("email"));
+    let mut group = c.benchmark_group("lookup_map");
+    group.bench_function("Map::get", |b| {
+        b.iter(|| map.map.get(black_box(&key)).unwrap())
+    });
+    // Looking up a `Map` by name needs a term to be made of the name.
+    group.bench_function("Map::get by name", |b| {
+        b.iter(|| {
+            let key = Term::from(Atom::from(black_box("email")));
+            map.map.get(&key).cloned().unwrap()
+        })
+    });
+    group.bench_function("AtomMap::get", |b| {
+        b.iter(|| atom_map.get(black_box("email")).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, lookup);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+//! Compares `decode_view` with the owned decoders.
+//!
+//! Run with `cargo bench --bench decode_view`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use eetf::{Atom, Binary, Term, Tuple};
+
+/// `{route, Key, [<<Payload>> × count]}` with `size`-byte payloads.
+fn message(count: usize, size: usize) -> Vec<u8> {
+    let payloads = (0..count)
+        .map(|i| Term::from(Binary::from(vec![i as u8; size])))
+        .collect::<Vec<_>>();
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("route")),
+        Term::from(Atom::from("some_key")),
+        Term::from(eetf::List::from(payloads)),
+    ]));
+    eetf::encode_to_vec(&term).unwrap()
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (count, size) in [(1, 64), (16, 4096), (4, 1 << 20)] {
+        let bytes = message(count, size);
+        let id = format!("{}x{}B", count, size);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("Term::decode", &id), &bytes, |b, bytes| {
+            b.iter(|| Term::decode(black_box(&bytes[..])).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decode_from_slice", &id),
+            &bytes,
+            |b, bytes| b.iter(|| eetf::decode_from_slice(black_box(bytes)).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("decode_view", &id), &bytes, |b, bytes| {
+            b.iter(|| eetf::decode_view(black_box(bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! Encodes one 1 MB payload into messages for 100 subscribers.
+//!
+//! Run with `cargo bench --bench encode_fanout` (with `--features bytes`, the cloned
+//! terms share the payload).
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use eetf::{Atom, Encoder, Term, Tuple};
+
+const SUBSCRIBERS: usize = 100;
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_fanout");
+    let payload = vec![7; 1 << 20];
+    let prefix = Term::from(Atom::from("data"));
+    let message = Term::from(Tuple::from(vec![
+        prefix.clone(),
+        Term::binary_from(payload.clone()),
+    ]));
+    let mut buf = Vec::with_capacity((1 << 20) + 64);
+    group.throughput(Throughput::Elements(SUBSCRIBERS as u64));
+    group.sample_size(20);
+    group.bench_function("clone_and_encode", |b| {
+        b.iter(|| {
+            for _ in 0..SUBSCRIBERS {
+                buf.clear();
+                black_box(message.clone()).encode(&mut buf).unwrap();
+            }
+        })
+    });
+    group.bench_function("encode_parts", |b| {
+        b.iter(|| {
+            for _ in 0..SUBSCRIBERS {
+                buf.clear();
+                Encoder::new(&mut buf)
+                    .encode_parts(black_box(&prefix), black_box(&payload))
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode);
+criterion_main!(benches);
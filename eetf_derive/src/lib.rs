@@ -0,0 +1,557 @@
+//! Derive macros for [eetf](https://docs.rs/eetf)'s `FromTerm` and `IntoTerm` traits.
+//!
+//! # Mapping
+//!
+//! - Structs with named fields are converted to maps keyed by field name atoms.
+//!   With `#[eetf(record = "name")]` they are converted to `{name, Field1, ...}` records instead.
+//! - Tuple structs are converted to tuples and unit structs to atoms.
+//! - Unit enum variants are converted to atoms and other variants to `{tag, Field1, ...}` tuples.
+//!
+//! Struct and variant names are converted to snake case atoms (e.g., `HttpError` to `http_error`).
+//!
+//! # Attributes
+//!
+//! Container attributes:
+//!
+//! - `#[eetf(record = "name")]`: converts a struct with named fields to a record tuple.
+//! - `#[eetf(keys = "atom" | "binary")]`: chooses the type of map keys (default: `atom`).
+//! - `#[eetf(rename = "name")]`: overrides the atom of a unit struct.
+//!
+//! Field attributes (structs with named fields only):
+//!
+//! - `#[eetf(rename = "name")]`: overrides the map key or record field name.
+//! - `#[eetf(skip)]`: never encodes the field and uses `Default::default()` when decoding.
+//! - `#[eetf(default)]`: uses `Default::default()` when the map key is missing.
+//!
+//! Variant attributes:
+//!
+//! - `#[eetf(rename = "name")]`: overrides the tag atom.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericParam, Generics, LitStr};
+
+/// Derives `eetf::IntoTerm`.
+#[proc_macro_derive(IntoTerm, attributes(eetf))]
+pub fn derive_into_term(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into_term(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `eetf::FromTerm`.
+#[proc_macro_derive(FromTerm, attributes(eetf))]
+pub fn derive_from_term(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_term(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyKind {
+    Atom,
+    Binary,
+}
+
+#[derive(Default)]
+struct ContainerAttrs {
+    record: Option<String>,
+    keys: Option<KeyKind>,
+    rename: Option<String>,
+}
+impl ContainerAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = ContainerAttrs::default();
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("eetf")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("record") {
+                    attrs.record = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("keys") {
+                    let lit = meta.value()?.parse::<LitStr>()?;
+                    attrs.keys = Some(match lit.value().as_str() {
+                        "atom" => KeyKind::Atom,
+                        "binary" => KeyKind::Binary,
+                        _ => {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "expected `keys = \"atom\"` or `keys = \"binary\"`",
+                            ))
+                        }
+                    });
+                } else if meta.path.is_ident("rename") {
+                    attrs.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("unknown eetf container attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let span = input.ident.span();
+        let named_struct = matches!(
+            input.data,
+            Data::Struct(syn::DataStruct {
+                fields: Fields::Named(_),
+                ..
+            })
+        );
+        let unit_struct = matches!(
+            input.data,
+            Data::Struct(syn::DataStruct {
+                fields: Fields::Unit,
+                ..
+            })
+        );
+        if attrs.record.is_some() && !named_struct {
+            return Err(syn::Error::new(
+                span,
+                "`record` is only supported on structs with named fields",
+            ));
+        }
+        if attrs.keys.is_some() && (!named_struct || attrs.record.is_some()) {
+            return Err(syn::Error::new(
+                span,
+                "`keys` is only supported on structs converted to maps",
+            ));
+        }
+        if attrs.rename.is_some() && !unit_struct {
+            return Err(syn::Error::new(
+                span,
+                "`rename` on a container is only supported on unit structs",
+            ));
+        }
+        Ok(attrs)
+    }
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    skip: bool,
+    default: bool,
+}
+impl FieldAttrs {
+    fn parse(field: &syn::Field, is_record: bool) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("eetf")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    attrs.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    attrs.skip = true;
+                } else if meta.path.is_ident("default") {
+                    attrs.default = true;
+                } else {
+                    return Err(meta.error("unknown eetf field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.skip && (attrs.rename.is_some() || attrs.default) {
+            return Err(syn::Error::new(
+                field.span(),
+                "`skip` cannot be combined with other field attributes",
+            ));
+        }
+        if attrs.default && is_record {
+            return Err(syn::Error::new(
+                field.span(),
+                "`default` is not supported on record fields",
+            ));
+        }
+        Ok(attrs)
+    }
+}
+
+fn parse_variant_rename(variant: &syn::Variant) -> syn::Result<Option<String>> {
+    let mut rename = None;
+    for attr in variant.attrs.iter().filter(|a| a.path().is_ident("eetf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown eetf variant attribute"))
+            }
+        })?;
+    }
+    for field in &variant.fields {
+        if let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("eetf")) {
+            return Err(syn::Error::new(
+                attr.span(),
+                "eetf field attributes are not supported in enum variants",
+            ));
+        }
+    }
+    Ok(rename)
+}
+
+struct NamedField<'a> {
+    ident: &'a syn::Ident,
+    name: String,
+    attrs: FieldAttrs,
+}
+
+fn named_fields(fields: &syn::FieldsNamed, is_record: bool) -> syn::Result<Vec<NamedField<'_>>> {
+    fields
+        .named
+        .iter()
+        .map(|f| {
+            let ident = f.ident.as_ref().expect("named field");
+            let attrs = FieldAttrs::parse(f, is_record)?;
+            let name = attrs
+                .rename
+                .clone()
+                .unwrap_or_else(|| unraw(&ident.to_string()));
+            Ok(NamedField { ident, name, attrs })
+        })
+        .collect()
+}
+
+fn expand_into_term(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = ContainerAttrs::parse(input)?;
+    let ident = &input.ident;
+    let body = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => {
+                let fields = named_fields(fields, attrs.record.is_some())?;
+                let fields = fields.iter().filter(|f| !f.attrs.skip);
+                if let Some(ref record) = attrs.record {
+                    let values = fields.map(|f| {
+                        let ident = f.ident;
+                        quote! { ::eetf::IntoTerm::into_term(self.#ident) }
+                    });
+                    quote! {
+                        ::eetf::Term::from(::eetf::Tuple::from(vec![
+                            ::eetf::Term::from(::eetf::Atom::from(#record)),
+                            #(#values),*
+                        ]))
+                    }
+                } else {
+                    let keys = attrs.keys.unwrap_or(KeyKind::Atom);
+                    let inserts = fields.map(|f| {
+                        let ident = f.ident;
+                        let key = key_term(&f.name, keys);
+                        quote! { map.insert(#key, ::eetf::IntoTerm::into_term(self.#ident)); }
+                    });
+                    quote! {
+                        let mut map = ::std::collections::HashMap::new();
+                        #(#inserts)*
+                        ::eetf::Term::from(::eetf::Map::from(map))
+                    }
+                }
+            }
+            Fields::Unnamed(ref fields) => {
+                let values = (0..fields.unnamed.len()).map(|i| {
+                    let index = syn::Index::from(i);
+                    quote! { ::eetf::IntoTerm::into_term(self.#index) }
+                });
+                quote! { ::eetf::Term::from(::eetf::Tuple::from(vec![#(#values),*])) }
+            }
+            Fields::Unit => {
+                let name = attrs
+                    .rename
+                    .unwrap_or_else(|| to_snake_case(&ident.to_string()));
+                quote! { ::eetf::Term::from(::eetf::Atom::from(#name)) }
+            }
+        },
+        Data::Enum(ref data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|v| {
+                    let variant = &v.ident;
+                    let tag = parse_variant_rename(v)?
+                        .unwrap_or_else(|| to_snake_case(&variant.to_string()));
+                    let bindings = (0..v.fields.len())
+                        .map(|i| format_ident!("f{}", i))
+                        .collect::<Vec<_>>();
+                    Ok(match v.fields {
+                        Fields::Unit => quote! {
+                            #ident::#variant => ::eetf::Term::from(::eetf::Atom::from(#tag))
+                        },
+                        Fields::Unnamed(_) => quote! {
+                            #ident::#variant(#(#bindings),*) => ::eetf::Term::from(::eetf::Tuple::from(vec![
+                                ::eetf::Term::from(::eetf::Atom::from(#tag)),
+                                #(::eetf::IntoTerm::into_term(#bindings)),*
+                            ]))
+                        },
+                        Fields::Named(ref fields) => {
+                            let names = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+                            quote! {
+                                #ident::#variant { #(#names: #bindings),* } => ::eetf::Term::from(::eetf::Tuple::from(vec![
+                                    ::eetf::Term::from(::eetf::Atom::from(#tag)),
+                                    #(::eetf::IntoTerm::into_term(#bindings)),*
+                                ]))
+                            }
+                        }
+                    })
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.span(),
+                "IntoTerm cannot be derived for unions",
+            ))
+        }
+    };
+
+    let generics = add_bounds(&input.generics, quote!(::eetf::IntoTerm));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::eetf::IntoTerm for #ident #ty_generics #where_clause {
+            fn into_term(self) -> ::eetf::Term {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_from_term(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = ContainerAttrs::parse(input)?;
+    let ident = &input.ident;
+    let type_name = ident.to_string();
+    let body = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => {
+                let fields = named_fields(fields, attrs.record.is_some())?;
+                if let Some(ref record) = attrs.record {
+                    let arity = 1 + fields.iter().filter(|f| !f.attrs.skip).count();
+                    let mut index = 0usize;
+                    let inits = fields.iter().map(|f| {
+                        let field = f.ident;
+                        if f.attrs.skip {
+                            return quote! { #field: ::std::default::Default::default() };
+                        }
+                        index += 1;
+                        let path = format!("{}.{}", type_name, f.name);
+                        quote! {
+                            #field: ::eetf::FromTerm::from_term(&tuple.elements[#index]).map_err(|e| {
+                                e.within(::eetf::convert::FromTermPathSegment::Field(#path.to_string()))
+                            })?
+                        }
+                    });
+                    quote! {
+                        let expected = || format!("record '{}' of arity {}", #record, #arity);
+                        let tuple = match *term {
+                            ::eetf::Term::Tuple(ref x) => x,
+                            _ => return Err(::eetf::FromTermError::new(expected(), term.variant_name())),
+                        };
+                        if tuple.elements.len() != #arity {
+                            return Err(::eetf::FromTermError::new(
+                                expected(),
+                                format!("Tuple of arity {}", tuple.elements.len()),
+                            ));
+                        }
+                        if tuple.elements[0] != ::eetf::Term::from(::eetf::Atom::from(#record)) {
+                            return Err(::eetf::FromTermError::new(
+                                expected(),
+                                format!("Tuple tagged with {}", tuple.elements[0]),
+                            ));
+                        }
+                        Ok(#ident { #(#inits),* })
+                    }
+                } else {
+                    let keys = attrs.keys.unwrap_or(KeyKind::Atom);
+                    let inits = fields.iter().map(|f| {
+                        let field = f.ident;
+                        if f.attrs.skip {
+                            return quote! { #field: ::std::default::Default::default() };
+                        }
+                        let key = key_term(&f.name, keys);
+                        let path = format!("{}.{}", type_name, f.name);
+                        let missing = if f.attrs.default {
+                            quote! { ::std::default::Default::default() }
+                        } else {
+                            let expected = format!("key {}", quote_key(&f.name, keys));
+                            quote! {
+                                return Err(::eetf::FromTermError::new(#expected, "nothing").within(
+                                    ::eetf::convert::FromTermPathSegment::Field(#path.to_string()),
+                                ))
+                            }
+                        };
+                        quote! {
+                            #field: match map.map.get(&#key) {
+                                Some(v) => ::eetf::FromTerm::from_term(v).map_err(|e| {
+                                    e.within(::eetf::convert::FromTermPathSegment::Field(#path.to_string()))
+                                })?,
+                                None => #missing,
+                            }
+                        }
+                    });
+                    quote! {
+                        let map = match *term {
+                            ::eetf::Term::Map(ref x) => x,
+                            _ => return Err(::eetf::FromTermError::unexpected::<Self>(term)),
+                        };
+                        Ok(#ident { #(#inits),* })
+                    }
+                }
+            }
+            Fields::Unnamed(ref fields) => {
+                let arity = fields.unnamed.len();
+                let inits = (0..arity).map(|i| {
+                    let path = format!("{}.{}", type_name, i);
+                    quote! {
+                        ::eetf::FromTerm::from_term(&tuple.elements[#i]).map_err(|e| {
+                            e.within(::eetf::convert::FromTermPathSegment::Field(#path.to_string()))
+                        })?
+                    }
+                });
+                quote! {
+                    let tuple = match *term {
+                        ::eetf::Term::Tuple(ref x) if x.elements.len() == #arity => x,
+                        _ => return Err(::eetf::FromTermError::new(
+                            format!("Tuple of arity {}", #arity),
+                            term.variant_name(),
+                        )),
+                    };
+                    Ok(#ident(#(#inits),*))
+                }
+            }
+            Fields::Unit => {
+                let name = attrs
+                    .rename
+                    .unwrap_or_else(|| to_snake_case(&ident.to_string()));
+                let expected = format!("atom '{}'", name);
+                quote! {
+                    if *term == ::eetf::Term::from(::eetf::Atom::from(#name)) {
+                        Ok(#ident)
+                    } else {
+                        Err(::eetf::FromTermError::new(#expected, term.to_string()))
+                    }
+                }
+            }
+        },
+        Data::Enum(ref data) => {
+            let mut atom_arms = Vec::new();
+            let mut tuple_arms = Vec::new();
+            for v in &data.variants {
+                let variant = &v.ident;
+                let tag =
+                    parse_variant_rename(v)?.unwrap_or_else(|| to_snake_case(&variant.to_string()));
+                let prefix = format!("{}::{}", type_name, variant);
+                let element = |i: usize, label: String| {
+                    let path = format!("{}.{}", prefix, label);
+                    let index = i + 1;
+                    quote! {
+                        ::eetf::FromTerm::from_term(&tuple.elements[#index]).map_err(|e| {
+                            e.within(::eetf::convert::FromTermPathSegment::Field(#path.to_string()))
+                        })?
+                    }
+                };
+                let arity = 1 + v.fields.len();
+                match v.fields {
+                    Fields::Unit => atom_arms.push(quote! { #tag => Ok(#ident::#variant) }),
+                    Fields::Unnamed(_) => {
+                        let values = (0..v.fields.len()).map(|i| element(i, i.to_string()));
+                        tuple_arms.push(quote! {
+                            (#tag, #arity) => Ok(#ident::#variant(#(#values),*))
+                        });
+                    }
+                    Fields::Named(ref fields) => {
+                        let values = fields.named.iter().enumerate().map(|(i, f)| {
+                            let field = f.ident.as_ref().unwrap();
+                            let value = element(i, unraw(&field.to_string()));
+                            quote! { #field: #value }
+                        });
+                        tuple_arms.push(quote! {
+                            (#tag, #arity) => Ok(#ident::#variant { #(#values),* })
+                        });
+                    }
+                }
+            }
+            quote! {
+                let unexpected = || ::eetf::FromTermError::new(#type_name, term.to_string());
+                match *term {
+                    ::eetf::Term::Atom(ref atom) => match atom.as_str() {
+                        #(#atom_arms,)*
+                        _ => Err(unexpected()),
+                    },
+                    ::eetf::Term::Tuple(ref tuple) => {
+                        let tag = match tuple.elements.first() {
+                            Some(::eetf::Term::Atom(ref atom)) => atom.as_str(),
+                            _ => return Err(unexpected()),
+                        };
+                        match (tag, tuple.elements.len()) {
+                            #(#tuple_arms,)*
+                            _ => Err(unexpected()),
+                        }
+                    }
+                    _ => Err(::eetf::FromTermError::unexpected::<Self>(term)),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.span(),
+                "FromTerm cannot be derived for unions",
+            ))
+        }
+    };
+
+    let generics = add_bounds(&input.generics, quote!(::eetf::FromTerm));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::eetf::FromTerm for #ident #ty_generics #where_clause {
+            #[allow(unreachable_code, clippy::needless_question_mark)]
+            fn from_term(term: &::eetf::Term) -> ::std::result::Result<Self, ::eetf::FromTermError> {
+                #body
+            }
+        }
+    })
+}
+
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut ty) = *param {
+            ty.bounds.push(syn::parse_quote!(#bound));
+        }
+    }
+    generics
+}
+
+fn key_term(name: &str, keys: KeyKind) -> TokenStream2 {
+    match keys {
+        KeyKind::Atom => quote! { ::eetf::Term::from(::eetf::Atom::from(#name)) },
+        KeyKind::Binary => {
+            quote! { ::eetf::Term::from(::eetf::Binary::from(#name.as_bytes())) }
+        }
+    }
+}
+
+fn quote_key(name: &str, keys: KeyKind) -> String {
+    match keys {
+        KeyKind::Atom => format!("'{}'", name),
+        KeyKind::Binary => format!("<<\"{}\">>", name),
+    }
+}
+
+fn unraw(name: &str) -> String {
+    name.trim_start_matches("r#").to_string()
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in unraw(name).char_indices() {
+        if c.is_uppercase() {
+            if i != 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
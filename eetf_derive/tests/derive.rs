@@ -0,0 +1,178 @@
+use eetf::convert::FromTermPathSegment;
+use eetf::{Atom, Binary, FromTerm, IntoTerm, Map, Term, Tuple};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+struct User {
+    id: u64,
+    name: String,
+    #[eetf(rename = "e-mail")]
+    email: String,
+    roles: Vec<Role>,
+    address: Address,
+    #[eetf(default)]
+    tags: Vec<Atom>,
+    #[eetf(skip)]
+    cached: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+#[eetf(record = "address")]
+struct Address {
+    street: String,
+    zip: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+enum Role {
+    Admin,
+    #[eetf(rename = "guest_user")]
+    Guest,
+    Member(u32),
+    TeamLead {
+        team: Atom,
+        size: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+#[eetf(keys = "binary")]
+struct Header {
+    content_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+struct Point(i32, i32);
+
+#[derive(Debug, Clone, PartialEq, FromTerm, IntoTerm)]
+struct KeepAlive;
+
+fn atom(name: &str) -> Term {
+    Term::from(Atom::from(name))
+}
+
+fn user() -> User {
+    User {
+        id: 10_000_000_000,
+        name: "joe".to_string(),
+        email: "joe@example.com".to_string(),
+        roles: vec![
+            Role::Admin,
+            Role::Guest,
+            Role::Member(3),
+            Role::TeamLead {
+                team: Atom::from("core"),
+                size: 5,
+            },
+        ],
+        address: Address {
+            street: "Main St.".to_string(),
+            zip: 12345,
+        },
+        tags: vec![Atom::from("vip")],
+        cached: None,
+    }
+}
+
+#[test]
+fn nested_struct_round_trip_works() {
+    let term = user().into_term();
+
+    let mut buf = Vec::new();
+    term.encode(&mut buf).unwrap();
+    let decoded = Term::decode(Cursor::new(&buf)).unwrap();
+    assert_eq!(User::from_term(&decoded), Ok(user()));
+
+    let map = match term {
+        Term::Map(ref x) => x,
+        _ => panic!(),
+    };
+    assert_eq!(map.map.len(), 6);
+    assert!(map.map.contains_key(&atom("e-mail")));
+    assert!(!map.map.contains_key(&atom("cached")));
+    assert_eq!(
+        map.map.get(&atom("address")),
+        Some(&Term::from(Tuple::from(vec![
+            atom("address"),
+            Term::from("Main St.".to_string()),
+            Term::from(12345)
+        ])))
+    );
+    assert_eq!(
+        map.map.get(&atom("roles")).unwrap().to_string(),
+        "['admin','guest_user',{'member',3},{'team_lead','core',5}]"
+    );
+}
+
+#[test]
+fn other_shapes_work() {
+    let header = Header {
+        content_type: "text/plain".to_string(),
+    };
+    let term = header.clone().into_term();
+    assert_eq!(
+        term,
+        Term::from(Map::from([(
+            Term::from(Binary::from("content_type".as_bytes())),
+            Term::from("text/plain".to_string())
+        )]))
+    );
+    assert_eq!(Header::from_term(&term), Ok(header));
+
+    let term = Point(1, -2).into_term();
+    assert_eq!(term.to_string(), "{1,-2}");
+    assert_eq!(Point::from_term(&term), Ok(Point(1, -2)));
+
+    assert_eq!(KeepAlive.into_term(), atom("keep_alive"));
+    assert_eq!(KeepAlive::from_term(&atom("keep_alive")), Ok(KeepAlive));
+}
+
+#[test]
+fn missing_default_key_works() {
+    let mut term = user().into_term();
+    if let Term::Map(ref mut x) = term {
+        x.map.remove(&atom("tags"));
+    }
+    let decoded = User::from_term(&term).unwrap();
+    assert!(decoded.tags.is_empty());
+}
+
+#[test]
+fn decode_errors_name_the_field() {
+    let mut term = user().into_term();
+    if let Term::Map(ref mut x) = term {
+        x.map.remove(&atom("name"));
+    }
+    let e = User::from_term(&term).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "expected key 'name', found nothing at field `User.name`"
+    );
+
+    let mut term = user().into_term();
+    if let Term::Map(ref mut x) = term {
+        x.map.insert(
+            atom("address"),
+            Term::from(Tuple::from(vec![
+                atom("address"),
+                Term::from("Main St.".to_string()),
+                atom("nowhere"),
+            ])),
+        );
+    }
+    let e = User::from_term(&term).unwrap_err();
+    assert_eq!(
+        e.path,
+        vec![
+            FromTermPathSegment::Field("Address.zip".to_string()),
+            FromTermPathSegment::Field("User.address".to_string())
+        ]
+    );
+    assert_eq!(
+        e.to_string(),
+        "expected u32, found Atom at field `Address.zip`, in field `User.address`"
+    );
+
+    let e = Role::from_term(&Term::from(Tuple::from(vec![atom("member")]))).unwrap_err();
+    assert_eq!(e.to_string(), "expected Role, found {'member'}");
+}
@@ -0,0 +1,10 @@
+use eetf::FromTerm;
+
+#[derive(FromTerm)]
+#[eetf(record = "user")]
+struct User {
+    #[eetf(default)]
+    name: String,
+}
+
+fn main() {}
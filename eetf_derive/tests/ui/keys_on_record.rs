@@ -0,0 +1,9 @@
+use eetf::FromTerm;
+
+#[derive(FromTerm)]
+#[eetf(record = "user", keys = "binary")]
+struct User {
+    name: String,
+}
+
+fn main() {}
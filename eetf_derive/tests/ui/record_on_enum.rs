@@ -0,0 +1,9 @@
+use eetf::IntoTerm;
+
+#[derive(IntoTerm)]
+#[eetf(record = "color")]
+enum Color {
+    Red,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use eetf::IntoTerm;
+
+#[derive(IntoTerm)]
+struct User {
+    #[eetf(skip, rename = "n")]
+    name: String,
+}
+
+fn main() {}
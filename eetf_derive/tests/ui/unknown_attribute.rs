@@ -0,0 +1,9 @@
+use eetf::IntoTerm;
+
+#[derive(IntoTerm)]
+struct User {
+    #[eetf(flatten)]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+//! Echoes the terms sent by an Erlang port.
+//!
+//! ```erlang
+//! Port = open_port({spawn, "target/debug/examples/port_echo"}, [{packet, 4}, binary]),
+//! Port ! {self(), {command, term_to_binary({hello, [1, 2, 3]})}},
+//! receive {Port, {data, Data}} -> binary_to_term(Data) end.
+//! ```
+use eetf::packet::{PacketSize, PacketTransport};
+use eetf::DecodeError;
+use std::io;
+
+fn main() {
+    let mut transport =
+        PacketTransport::new(io::stdin().lock(), io::stdout().lock(), PacketSize::Four);
+    loop {
+        match transport.recv_term() {
+            Ok(term) => transport.send_term(&term).expect("failed to send a term"),
+            Err(DecodeError::EndOfStream) => break,
+            Err(e) => panic!("failed to receive a term: {}", e),
+        }
+    }
+}
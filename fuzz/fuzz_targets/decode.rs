@@ -0,0 +1,14 @@
+//! Decodes arbitrary bytes with the slice, view and reader decoders.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let term = eetf::decode_from_slice(data);
+    let _ = eetf::decode_view(data);
+    let reader_term = eetf::Term::decode(data);
+    // The reader decoder may stop before the end of the input, but never disagrees.
+    if let Ok(term) = term {
+        assert_eq!(reader_term.unwrap(), term);
+    }
+});
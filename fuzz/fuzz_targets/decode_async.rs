@@ -0,0 +1,21 @@
+//! Decodes arbitrary bytes with the async decoder, which must agree with the reader decoder,
+//! also when it keeps the subterms as raw terms.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    for options in [
+        eetf::DecoderOptions::default(),
+        eetf::DecoderOptions::raw_depth(1),
+    ] {
+        let async_term = runtime.block_on(eetf::AsyncDecoder::with_options(data, options).decode());
+        match eetf::Term::decode_with_options(data, options) {
+            Ok(term) => assert_eq!(async_term.unwrap(), term),
+            Err(error) => assert!(async_term.is_err(), "{}", error),
+        }
+    }
+});
@@ -0,0 +1,29 @@
+//! Decodes compressed terms whose uncompressed size (the first 4 bytes) and payload are arbitrary.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io::Write;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let (size, payload) = data.split_at(4);
+    let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+    compressor.write_all(payload).unwrap();
+    let mut bytes = vec![131, 80];
+    bytes.extend_from_slice(size);
+    bytes.extend(compressor.finish().into_result().unwrap());
+
+    let term = eetf::decode_from_slice(&bytes);
+    let reader_term = eetf::Term::decode(&bytes[..]);
+    if let Ok(term) = term {
+        assert_eq!(reader_term.unwrap(), term);
+    }
+
+    // Not even zlib data.
+    let mut bytes = vec![131, 80];
+    bytes.extend_from_slice(data);
+    let _ = eetf::decode_from_slice(&bytes);
+    let _ = eetf::Term::decode(&bytes[..]);
+});
@@ -0,0 +1,26 @@
+//! Checks that decoded terms are stable under re-encoding.
+//!
+//! A decoded term may change once when it is re-encoded (e.g., a `LIST_EXT` of small
+//! integers becomes a byte list), but the re-decoded term must then re-encode to the
+//! same bytes and decode to an equal term.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(term) = eetf::decode_from_slice(data) else {
+        return;
+    };
+    let Ok(bytes) = eetf::encode_to_vec(&term) else {
+        return;
+    };
+    let mut reader_bytes = Vec::new();
+    term.encode(&mut reader_bytes).unwrap();
+    assert_eq!(reader_bytes, bytes);
+
+    let decoded = eetf::decode_from_slice(&bytes).unwrap();
+    assert_eq!(eetf::decode_view(&bytes).unwrap().to_owned(), decoded);
+    let reencoded = eetf::encode_to_vec(&decoded).unwrap();
+    assert_eq!(reencoded, bytes);
+    assert_eq!(eetf::decode_from_slice(&reencoded).unwrap(), decoded);
+});
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Runs every fuzz target over its corpus (the seeds and the regression entries of past
+# crashes), then fuzzes it for a few seconds (10 by default, or the first argument).
+#
+# Needs a nightly toolchain and cargo-fuzz (`cargo install cargo-fuzz`).
+set -eu
+cd "$(dirname "$0")"
+seconds="${1:-10}"
+for target in $(cargo +nightly fuzz list); do
+    echo "fuzzing $target for ${seconds}s"
+    cargo +nightly fuzz run "$target" -- \
+        -max_total_time="$seconds" -rss_limit_mb=2048 -malloc_limit_mb=1024
+done
@@ -0,0 +1,191 @@
+#ifndef EETF_H
+#define EETF_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of a fallible function.
+typedef enum eetf_status {
+  EETF_OK = 0,
+  // A pointer argument is NULL.
+  EETF_NULL_POINTER = 1,
+  // The input is not a valid term.
+  EETF_DECODE_ERROR = 2,
+  // The term cannot be encoded.
+  EETF_ENCODE_ERROR = 3,
+  // The term is not of the type the function expects.
+  EETF_WRONG_TYPE = 4,
+  // The index or value is out of range.
+  EETF_OUT_OF_RANGE = 5,
+} eetf_status;
+
+// Type of a term (see [`Term`]).
+typedef enum eetf_type {
+  // The term pointer is NULL.
+  EETF_TYPE_INVALID = 0,
+  EETF_TYPE_ATOM,
+  EETF_TYPE_FIX_INTEGER,
+  EETF_TYPE_BIG_INTEGER,
+  EETF_TYPE_FLOAT,
+  EETF_TYPE_PID,
+  EETF_TYPE_PORT,
+  EETF_TYPE_REFERENCE,
+  EETF_TYPE_EXTERNAL_FUN,
+  EETF_TYPE_INTERNAL_FUN,
+  EETF_TYPE_BINARY,
+  EETF_TYPE_BIT_BINARY,
+  EETF_TYPE_BYTE_LIST,
+  EETF_TYPE_LIST,
+  EETF_TYPE_IMPROPER_LIST,
+  EETF_TYPE_TUPLE,
+  EETF_TYPE_MAP,
+  EETF_TYPE_RAW,
+} eetf_type;
+
+// Term.
+typedef struct eetf_term eetf_term;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the message of the last error on the calling thread, or NULL if there is none.
+//
+// The message is valid until the next failing call on the same thread.
+const char *eetf_last_error_message(void);
+
+// Decodes the `len` bytes at `data` into a new term stored in `*out`.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, and `out` must be writable.
+enum eetf_status eetf_decode(const uint8_t *data, size_t len, struct eetf_term **out);
+
+// Encodes `term` into a new buffer stored in `*out` and `*len`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `out` and `len` must be writable.
+enum eetf_status eetf_encode(const struct eetf_term *term, uint8_t **out, size_t *len);
+
+// Releases a term returned by [`eetf_decode`]. NULL is ignored.
+//
+// # Safety
+//
+// `term` must be NULL or an owned term which has not been released yet.
+void eetf_term_free(struct eetf_term *term);
+
+// Releases a buffer returned by [`eetf_encode`]. NULL is ignored.
+//
+// # Safety
+//
+// `data` and `len` must be NULL or a buffer and its length which have not been released yet.
+void eetf_bytes_free(uint8_t *data, size_t len);
+
+// Returns the type of `term` ([`eetf_type::EETF_TYPE_INVALID`] if it is NULL).
+//
+// # Safety
+//
+// `term` must be NULL or a term obtained from this library.
+enum eetf_type eetf_term_type(const struct eetf_term *term);
+
+// Stores the UTF-8 name of an atom (not NUL-terminated) in `*name` and `*len`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `name` and `len` must be writable.
+enum eetf_status eetf_atom_name(const struct eetf_term *term, const uint8_t **name, size_t *len);
+
+// Stores the value of an integer in `*value`.
+//
+// Big integers are accepted if they fit in 64 bits.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `value` must be writable.
+enum eetf_status eetf_integer_value(const struct eetf_term *term, int64_t *value);
+
+// Stores the value of a float in `*value`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `value` must be writable.
+enum eetf_status eetf_float_value(const struct eetf_term *term, double *value);
+
+// Stores the bytes of a binary or a byte list in `*data` and `*len`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `data` and `len` must be writable.
+enum eetf_status eetf_binary_data(const struct eetf_term *term, const uint8_t **data, size_t *len);
+
+// Stores the number of elements of a tuple in `*size`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `size` must be writable.
+enum eetf_status eetf_tuple_size(const struct eetf_term *term, size_t *size);
+
+// Stores the `index`-th element of a tuple in `*element`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `element` must be writable.
+enum eetf_status eetf_tuple_get(const struct eetf_term *term,
+                                size_t index,
+                                const struct eetf_term **element);
+
+// Stores the number of elements of a list (excluding the tail of an improper list) in `*len`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `len` must be writable.
+enum eetf_status eetf_list_length(const struct eetf_term *term, size_t *len);
+
+// Stores the `index`-th element of a list in `*element`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `element` must be writable.
+enum eetf_status eetf_list_get(const struct eetf_term *term,
+                               size_t index,
+                               const struct eetf_term **element);
+
+// Stores the tail of an improper list in `*tail`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `tail` must be writable.
+enum eetf_status eetf_improper_list_tail(const struct eetf_term *term,
+                                         const struct eetf_term **tail);
+
+// Stores the number of entries of a map in `*size`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `size` must be writable.
+enum eetf_status eetf_map_size(const struct eetf_term *term, size_t *size);
+
+// Stores the key and the value of the `index`-th entry of a map in `*key` and `*value`.
+//
+// The entries are in an unspecified (but, for the same map, fixed) order. Finding an
+// entry takes time proportional to `index`.
+//
+// # Safety
+//
+// `term` must be a term obtained from this library, and `key` and `value` must be writable.
+enum eetf_status eetf_map_get(const struct eetf_term *term,
+                              size_t index,
+                              const struct eetf_term **key,
+                              const struct eetf_term **value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EETF_H */
@@ -0,0 +1,489 @@
+//! [proptest](https://docs.rs/proptest) strategies for terms.
+//!
+//! Every term type implements [`Arbitrary`], so `any::<Term>()` generates terms of every
+//! variant (other than [`Term::Raw`]). The terms are in the form the decoders produce, so
+//! they round-trip through the codecs: lists of small integers are byte lists, improper
+//! lists do not end in lists, and floats are finite.
+//!
+//! The size of the generated terms is limited by [`TermParams`]. Shrinking removes elements
+//! of lists, tuples and maps, and replaces nested terms by simpler ones.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::arbitrary::TermParams;
+//! use eetf::Term;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(term in any_with::<Term>(TermParams { depth: 2, ..TermParams::default() }))| {
+//!     let mut bytes = Vec::new();
+//!     term.encode(&mut bytes).unwrap();
+//!     prop_assert_eq!(Term::decode(&bytes[..]).unwrap(), term);
+//! });
+//! ```
+use super::*;
+use proptest::collection::{hash_map, vec};
+use proptest::prelude::*;
+
+/// Limits of the generated terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TermParams {
+    /// Maximum nesting depth of lists, tuples, maps and funs.
+    pub depth: u32,
+
+    /// Desired number of terms in total (see [`Strategy::prop_recursive`]).
+    pub size: u32,
+
+    /// Maximum number of elements of a list, tuple or map (and of free variables of a fun).
+    pub branch: u32,
+}
+impl Default for TermParams {
+    fn default() -> Self {
+        TermParams {
+            depth: 4,
+            size: 64,
+            branch: 8,
+        }
+    }
+}
+
+/// Returns a strategy for terms limited by `params`.
+pub fn arb_term(params: TermParams) -> BoxedStrategy<Term> {
+    arb_term_with(params, arb_leaf().boxed(), true)
+}
+
+/// Returns a strategy for terms limited by `params` whose leaves come from `leaf`, with
+/// internal funs only if `internal_funs` (for the formats which cannot hold them).
+pub(crate) fn arb_term_with(
+    params: TermParams,
+    leaf: BoxedStrategy<Term>,
+    internal_funs: bool,
+) -> BoxedStrategy<Term> {
+    let branch = params.branch as usize;
+    leaf.prop_recursive(params.depth, params.size, params.branch, move |inner| {
+        let mut compounds = vec![
+            vec(inner.clone(), 0..=branch).prop_map(list).boxed(),
+            vec(inner.clone(), 0..=branch)
+                .prop_map(|e| Term::from(Tuple::from(e)))
+                .boxed(),
+            hash_map(inner.clone(), inner.clone(), 0..=branch)
+                .prop_map(|m| Term::from(Map::from(m)))
+                .boxed(),
+            (vec(inner.clone(), 1..=branch.max(1)), inner.clone())
+                .prop_map(|x| Term::from(improper_list(x)))
+                .boxed(),
+        ];
+        if internal_funs {
+            compounds.push(
+                arb_internal_fun(vec(inner, 0..=branch))
+                    .prop_map(Term::from)
+                    .boxed(),
+            );
+        }
+        proptest::strategy::Union::new(compounds)
+    })
+    .boxed()
+}
+
+/// Returns a strategy for the terms of [`arb_term`] which hold no other terms.
+pub(crate) fn arb_leaf() -> impl Strategy<Value = Term> {
+    prop_oneof![
+        any::<Atom>().prop_map(Term::from),
+        any::<FixInteger>().prop_map(Term::from),
+        any::<BigInteger>().prop_map(Term::from),
+        any::<Float>().prop_map(Term::from),
+        any::<Pid>().prop_map(Term::from),
+        any::<Port>().prop_map(Term::from),
+        any::<Reference>().prop_map(Term::from),
+        any::<ExternalFun>().prop_map(Term::from),
+        any::<Binary>().prop_map(Term::from),
+        any::<BitBinary>().prop_map(Term::from),
+        any::<ByteList>().prop_map(Term::from),
+        Just(Term::from(List::nil())),
+    ]
+}
+
+/// Makes a list, which is a byte list if it consists of small integers (as it is encoded).
+fn list(elements: Vec<Term>) -> Term {
+    let to_byte = |e: &Term| match e {
+        Term::FixInteger(FixInteger { value }) => u8::try_from(*value).ok(),
+        _ => None,
+    };
+    let bytes = elements.iter().map(to_byte).collect::<Option<Vec<_>>>();
+    match bytes {
+        Some(bytes) if !bytes.is_empty() && bytes.len() <= u16::MAX as usize => {
+            Term::from(ByteList::from(bytes))
+        }
+        _ => Term::from(List::from(elements)),
+    }
+}
+
+/// Makes an improper list, wrapping a (proper or improper) list tail in a tuple as it
+/// would be encoded as a part of the list.
+fn improper_list((elements, last): (Vec<Term>, Term)) -> ImproperList {
+    let last = match last {
+        Term::List(_) | Term::ImproperList(_) | Term::ByteList(_) => {
+            Term::from(Tuple::from(vec![last]))
+        }
+        last => last,
+    };
+    ImproperList::from((elements, last))
+}
+
+fn arb_node() -> impl Strategy<Value = Atom> {
+    "[a-z][a-z0-9_]{0,7}@[a-z][a-z0-9.-]{0,7}".prop_map(Atom::from)
+}
+
+fn arb_internal_fun(
+    free_vars: impl Strategy<Value = Vec<Term>>,
+) -> impl Strategy<Value = InternalFun> {
+    let old = (any::<Atom>(), any::<Pid>(), any::<i32>(), any::<i32>());
+    let new = (
+        (any::<Atom>(), any::<u8>(), any::<Pid>(), any::<u32>()),
+        (any::<[u8; 16]>(), any::<i32>(), any::<i32>()),
+    );
+    (prop_oneof![old.prop_map(Ok), new.prop_map(Err)], free_vars).prop_map(|(fun, free_vars)| {
+        match fun {
+            Ok((module, pid, index, uniq)) => InternalFun::Old {
+                module,
+                pid,
+                free_vars,
+                index,
+                uniq,
+            },
+            Err(((module, arity, pid, index), (uniq, old_index, old_uniq))) => InternalFun::New {
+                module,
+                arity,
+                pid,
+                free_vars,
+                index,
+                uniq,
+                old_index,
+                old_uniq,
+            },
+        }
+    })
+}
+
+impl Arbitrary for Term {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        arb_term(params)
+    }
+}
+
+impl Arbitrary for Atom {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    /// Generates ASCII names (most of the time) and other UTF-8 names of up to 16 characters.
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![
+            3 => "[a-z][a-zA-Z0-9_@]{0,15}",
+            1 => "\\PC{0,16}",
+        ]
+        .prop_map(Atom::from)
+        .boxed()
+    }
+}
+
+impl Arbitrary for FixInteger {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![0..=255i32, any::<i32>()]
+            .prop_map(FixInteger::from)
+            .boxed()
+    }
+}
+
+impl Arbitrary for BigInteger {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    /// Generates integers of any sign with up to 64 bytes.
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (any::<bool>(), vec(any::<u8>(), 0..=64))
+            .prop_map(|(negative, bytes)| {
+                let sign = if negative {
+                    num::bigint::Sign::Minus
+                } else {
+                    num::bigint::Sign::Plus
+                };
+                BigInteger {
+                    value: BigInt::from_bytes_le(sign, &bytes),
+                }
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Float {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        any::<f64>()
+            .prop_filter("finite", |f| f.is_finite())
+            .prop_map(|value| Float { value })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Pid {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (arb_node(), any::<u32>(), any::<u32>(), any::<u32>())
+            .prop_map(|(node, id, serial, creation)| Pid {
+                node,
+                id,
+                serial,
+                creation,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Port {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (
+            arb_node(),
+            prop_oneof![any::<u32>().prop_map(u64::from), any::<u64>()],
+            any::<u32>(),
+        )
+            .prop_map(|(node, id, creation)| Port { node, id, creation })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Reference {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (arb_node(), vec(any::<u32>(), 1..=5), any::<u32>())
+            .prop_map(|(node, id, creation)| Reference { node, id, creation })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ExternalFun {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (any::<Atom>(), any::<Atom>(), any::<u8>())
+            .prop_map(|(module, function, arity)| ExternalFun {
+                module,
+                function,
+                arity,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for InternalFun {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        arb_internal_fun(vec(arb_term(params), 0..=params.branch as usize)).boxed()
+    }
+}
+
+impl Arbitrary for Binary {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        vec(any::<u8>(), 0..32).prop_map(Binary::from).boxed()
+    }
+}
+
+impl Arbitrary for BitBinary {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    /// Generates bit binaries with a last byte of 1 to 8 bits.
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        (vec(any::<u8>(), 1..32), 1u8..=8)
+            .prop_map(|(mut bytes, tail_bits_size)| {
+                if let Some(last) = bytes.last_mut() {
+                    *last = (u16::from(*last) & ((1 << tail_bits_size) - 1)) as u8;
+                }
+                BitBinary::from((bytes, tail_bits_size))
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ByteList {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        vec(any::<u8>(), 1..32).prop_map(ByteList::from).boxed()
+    }
+}
+
+impl Arbitrary for List {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    /// Generates lists of terms, which (unlike the lists in [`Term`]s) may consist of small
+    /// integers only.
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        vec(arb_term(params), 0..=params.branch as usize)
+            .prop_map(List::from)
+            .boxed()
+    }
+}
+
+impl Arbitrary for ImproperList {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        let elements = vec(arb_term(params), 1..=params.branch.max(1) as usize);
+        (elements, arb_term(params)).prop_map(improper_list).boxed()
+    }
+}
+
+impl Arbitrary for Tuple {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        vec(arb_term(params), 0..=params.branch as usize)
+            .prop_map(Tuple::from)
+            .boxed()
+    }
+}
+
+impl Arbitrary for Map {
+    type Parameters = TermParams;
+    type Strategy = BoxedStrategy<Self>;
+    /// Generates maps whose keys are terms of any type.
+    fn arbitrary_with(params: TermParams) -> Self::Strategy {
+        let term = arb_term(params);
+        hash_map(term.clone(), term, 0..=params.branch as usize)
+            .prop_map(Map::from)
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+
+    proptest! {
+        #[test]
+        fn terms_round_trip(term in any::<Term>()) {
+            let mut bytes = Vec::new();
+            term.encode(&mut bytes).unwrap();
+            prop_assert_eq!(&Term::decode(&bytes[..]).unwrap(), &term);
+            prop_assert_eq!(&decode_from_slice(&bytes).unwrap(), &term);
+            prop_assert_eq!(&decode_view(&bytes).unwrap().to_owned(), &term);
+            prop_assert_eq!(&encode_to_vec(&term).unwrap(), &bytes);
+            prop_assert_eq!(term.encoded_size().unwrap(), bytes.len());
+        }
+
+        #[test]
+        fn types_round_trip(
+            list in any::<ImproperList>(),
+            map in any::<Map>(),
+            fun in any::<InternalFun>(),
+        ) {
+            for term in [Term::from(list), Term::from(map), Term::from(fun)] {
+                let bytes = encode_to_vec(&term).unwrap();
+                prop_assert_eq!(decode_from_slice(&bytes).unwrap(), term);
+            }
+        }
+
+        #[test]
+        fn canonicalize_is_idempotent(term in any::<Term>(), byte_lists in any::<bool>()) {
+            let options = CanonicalizeOptions { byte_lists };
+            let canonical = term.clone().canonicalize(options);
+            prop_assert_eq!(&canonical.clone().canonicalize(options), &canonical);
+            prop_assert_eq!(canonical.erl_cmp(&term), core::cmp::Ordering::Equal);
+        }
+
+        #[test]
+        fn canonical_bytes_do_not_depend_on_insertion_order(
+            entries in proptest::collection::vec((any::<Term>(), any::<Term>()), 0..40),
+        ) {
+            let options = EncoderOptions {
+                deterministic: true,
+                ..EncoderOptions::default()
+            };
+            let encode = |entries: Vec<(Term, Term)>| {
+                let term = Term::from(Map::from(entries.into_iter().collect::<TermMap>()));
+                let mut bytes = Vec::new();
+                Encoder::with_options(&mut bytes, options)
+                    .encode(&term.canonicalize(CanonicalizeOptions::default()))
+                    .unwrap();
+                bytes
+            };
+            // Keys which are equal once canonicalized would make the last one win.
+            let entries = entries
+                .into_iter()
+                .map(|(k, v)| (k.canonicalize(CanonicalizeOptions::default()), v))
+                .collect::<TermMap>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let reversed = entries.iter().rev().cloned().collect();
+            prop_assert_eq!(encode(entries), encode(reversed));
+        }
+    }
+
+    #[test]
+    fn every_variant_is_generated() {
+        let mut runner = TestRunner::deterministic();
+        let strategy = any::<Term>();
+        let mut names = HashSet::new();
+        for _ in 0..2000 {
+            let mut stack = vec![strategy.new_tree(&mut runner).unwrap().current()];
+            while let Some(term) = stack.pop() {
+                names.insert(term.variant_name());
+                match term {
+                    Term::List(x) => stack.extend(x.elements),
+                    Term::ImproperList(x) => stack.extend(x.elements),
+                    Term::Tuple(x) => stack.extend(x.elements),
+                    Term::Map(x) => stack.extend(x.map.into_iter().flat_map(|(k, v)| [k, v])),
+                    Term::InternalFun(x) => match *x {
+                        InternalFun::Old { free_vars, .. } | InternalFun::New { free_vars, .. } => {
+                            stack.extend(free_vars)
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(names.len(), 16, "{:?}", names);
+    }
+
+    #[test]
+    fn shrinking_reduces_terms() {
+        let mut runner = TestRunner::deterministic();
+        let strategy = arb_term(TermParams::default());
+        // Shrink a large term towards the smallest term with at least 3 elements.
+        let count = |term: &Term| match term {
+            Term::List(x) => x.elements.len(),
+            Term::Tuple(x) => x.elements.len(),
+            _ => 0,
+        };
+        let mut tree = loop {
+            let tree = strategy.new_tree(&mut runner).unwrap();
+            if count(&tree.current()) > 5 {
+                break tree;
+            }
+        };
+        let mut failing = true;
+        loop {
+            let changed = if failing {
+                tree.simplify()
+            } else {
+                tree.complicate()
+            };
+            if !changed {
+                break;
+            }
+            failing = count(&tree.current()) >= 3;
+        }
+        assert_eq!(count(&tree.current()), 3);
+    }
+}
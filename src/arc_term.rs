@@ -0,0 +1,307 @@
+//! Terms whose containers share their elements (see [`ArcTerm`]).
+use super::*;
+use crate::codec_common::*;
+use crate::dist::DistFlags;
+use byteorder::{BigEndian, WriteBytesExt};
+
+/// Immutable term each of whose nodes is behind an [`Arc`], for sending the same term to many
+/// threads or tasks.
+///
+/// Cloning an `ArcTerm` or taking one of its elements (e.g., [`ArcTerm::tuple_element`]) shares
+/// the nodes instead of copying them, and [`ArcTerm::encode`] encodes it as the equivalent
+/// [`Term`] without converting it back.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{ArcTerm, Atom, Term, Tuple};
+///
+/// let term = Term::from(Tuple::from(vec![
+///     Term::from(Atom::from("config")),
+///     Term::from(Tuple::from(vec![Term::from(1), Term::from(2)])),
+/// ]));
+/// let shared = ArcTerm::from(&term);
+/// let inner = shared.tuple_element(1).unwrap();
+/// assert_eq!(Term::from(&inner), Term::from(Tuple::from(vec![Term::from(1), Term::from(2)])));
+/// assert_eq!(shared.encode_to_vec().unwrap(), eetf::encode_to_vec(&term).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArcTerm(Arc<ArcNode>);
+
+/// Node of an [`ArcTerm`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArcNode {
+    /// A term without elements (e.g., an atom or a binary), which is shared as a whole.
+    Leaf(Term),
+    List(Vec<ArcTerm>),
+    ImproperList(Vec<ArcTerm>, ArcTerm),
+    Tuple(Vec<ArcTerm>),
+    /// The entries of a map, in the order of iteration of the map it was made from.
+    Map(Vec<(ArcTerm, ArcTerm)>),
+}
+
+impl ArcTerm {
+    pub fn node(&self) -> &ArcNode {
+        &self.0
+    }
+
+    /// Returns the element `index` of a tuple.
+    pub fn tuple_element(&self, index: usize) -> Option<ArcTerm> {
+        match self.node() {
+            ArcNode::Tuple(elements) => elements.get(index).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Returns the element `index` of a (proper or improper) list.
+    pub fn list_element(&self, index: usize) -> Option<ArcTerm> {
+        match self.node() {
+            ArcNode::List(elements) | ArcNode::ImproperList(elements, _) => {
+                elements.get(index).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the map key `key` (which is looked up linearly).
+    pub fn map_get(&self, key: &Term) -> Option<ArcTerm> {
+        match self.node() {
+            ArcNode::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| Term::from(k) == *key)
+                .map(|(_, v)| v.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of `ArcTerm`s which share this node.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    /// Encodes the term (as [`Term::encode`] encodes the equivalent term).
+    pub fn encode<W: io::Write>(&self, writer: W) -> EncodeResult {
+        let mut encoder = Encoder::new(writer);
+        encoder.writer.write_u8(VERSION)?;
+        encoder.encode_arc_term(self)
+    }
+
+    pub fn encode_to_vec(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+impl From<Term> for ArcTerm {
+    fn from(term: Term) -> Self {
+        let node = match term {
+            Term::List(x) => ArcNode::List(x.elements.into_iter().map(ArcTerm::from).collect()),
+            Term::ImproperList(x) => ArcNode::ImproperList(
+                x.elements.into_iter().map(ArcTerm::from).collect(),
+                ArcTerm::from(*x.last),
+            ),
+            Term::Tuple(x) => ArcNode::Tuple(x.elements.into_iter().map(ArcTerm::from).collect()),
+            Term::Map(x) => ArcNode::Map(
+                x.map
+                    .into_iter()
+                    .map(|(k, v)| (ArcTerm::from(k), ArcTerm::from(v)))
+                    .collect(),
+            ),
+            term => ArcNode::Leaf(term),
+        };
+        ArcTerm(Arc::new(node))
+    }
+}
+impl From<&Term> for ArcTerm {
+    fn from(term: &Term) -> Self {
+        let node = match term {
+            Term::List(x) => ArcNode::List(x.elements.iter().map(ArcTerm::from).collect()),
+            Term::ImproperList(x) => ArcNode::ImproperList(
+                x.elements.iter().map(ArcTerm::from).collect(),
+                ArcTerm::from(&*x.last),
+            ),
+            Term::Tuple(x) => ArcNode::Tuple(x.elements.iter().map(ArcTerm::from).collect()),
+            Term::Map(x) => ArcNode::Map(
+                x.map
+                    .iter()
+                    .map(|(k, v)| (ArcTerm::from(k), ArcTerm::from(v)))
+                    .collect(),
+            ),
+            term => ArcNode::Leaf(term.clone()),
+        };
+        ArcTerm(Arc::new(node))
+    }
+}
+impl From<&ArcTerm> for Term {
+    fn from(term: &ArcTerm) -> Self {
+        let terms = |elements: &[ArcTerm]| elements.iter().map(Term::from).collect::<Vec<_>>();
+        match term.node() {
+            ArcNode::Leaf(x) => x.clone(),
+            ArcNode::List(x) => Term::from(List::from(terms(x))),
+            ArcNode::ImproperList(x, last) => Term::from(ImproperList {
+                elements: terms(x),
+                last: Box::new(Term::from(last)),
+            }),
+            ArcNode::Tuple(x) => Term::from(Tuple::from(terms(x))),
+            ArcNode::Map(x) => {
+                let map = x.iter().map(|(k, v)| (Term::from(k), Term::from(v)));
+                Term::from(Map::from(map.collect::<TermMap>()))
+            }
+        }
+    }
+}
+
+impl<W: io::Write> Encoder<W> {
+    fn encode_arc_term(&mut self, term: &ArcTerm) -> EncodeResult {
+        match term.node() {
+            ArcNode::Leaf(x) => self.encode_term(x),
+            ArcNode::List(x) => {
+                let to_byte = |e: &ArcTerm| match e.node() {
+                    ArcNode::Leaf(Term::FixInteger(i)) => u8::try_from(i.value).ok(),
+                    _ => None,
+                };
+                if !x.is_empty()
+                    && x.len() <= u16::MAX as usize
+                    && x.iter().all(|e| to_byte(e).is_some())
+                {
+                    let bytes: Vec<u8> = x.iter().map(|e| to_byte(e).unwrap()).collect();
+                    return self.encode_byte_list(&bytes);
+                }
+                if !x.is_empty() {
+                    self.encode_arc_elements(LIST_EXT, x, EncodePathSegment::ListElement)?;
+                }
+                self.encode_nil()
+            }
+            ArcNode::ImproperList(x, last) => {
+                self.encode_arc_elements(LIST_EXT, x, EncodePathSegment::ListElement)?;
+                self.encode_arc_term(last)
+                    .map_err(|e| e.within(EncodePathSegment::ListTail))
+            }
+            ArcNode::Tuple(x) if x.len() < 0x100 => {
+                self.writer.write_u8(SMALL_TUPLE_EXT)?;
+                self.writer.write_u8(x.len() as u8)?;
+                for (i, e) in x.iter().enumerate() {
+                    self.encode_arc_term(e)
+                        .map_err(|e| e.within(EncodePathSegment::TupleElement(i)))?;
+                }
+                Ok(())
+            }
+            ArcNode::Tuple(x) => {
+                self.encode_arc_elements(LARGE_TUPLE_EXT, x, EncodePathSegment::TupleElement)
+            }
+            ArcNode::Map(x) => {
+                if !self.options.map_tag {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(term),
+                        DistFlags::MAP_TAG,
+                    ));
+                }
+                self.writer.write_u8(MAP_EXT)?;
+                self.writer.write_u32::<BigEndian>(x.len() as u32)?;
+                for (k, v) in x {
+                    self.encode_arc_term(k)
+                        .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+                    self.encode_arc_term(v).map_err(|e| {
+                        let key = Term::from(k).to_string();
+                        e.within(EncodePathSegment::MapValue { key })
+                    })?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Encodes `tag`, the 4 byte length of `elements` and the elements.
+    fn encode_arc_elements(
+        &mut self,
+        tag: u8,
+        elements: &[ArcTerm],
+        segment: fn(usize) -> EncodePathSegment,
+    ) -> EncodeResult {
+        self.writer.write_u8(tag)?;
+        self.writer.write_u32::<BigEndian>(elements.len() as u32)?;
+        for (i, e) in elements.iter().enumerate() {
+            self.encode_arc_term(e).map_err(|e| e.within(segment(i)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Term {
+        Term::from(Map::from([
+            (
+                Term::from(Atom::from("listeners")),
+                Term::from(List::from(vec![
+                    Term::from(Tuple::from(vec![
+                        Term::from(Atom::from("http")),
+                        Term::from(8080),
+                    ])),
+                    Term::from(Binary::from(vec![0; 1000])),
+                ])),
+            ),
+            (
+                Term::from(Atom::from("bytes")),
+                Term::from(List::from(vec![Term::from(1), Term::from(2)])),
+            ),
+            (
+                Term::from(Atom::from("tail")),
+                Term::from(ImproperList {
+                    elements: vec![Term::from(1)],
+                    last: Box::new(Term::from(Atom::from("end"))),
+                }),
+            ),
+            (Term::from(Atom::from("nil")), Term::from(List::nil())),
+        ]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let shared = ArcTerm::from(config());
+        assert_eq!(Term::from(&shared), config());
+        assert_eq!(shared, ArcTerm::from(&config()));
+        assert_eq!(
+            shared.encode_to_vec().unwrap(),
+            encode_to_vec(&config()).unwrap()
+        );
+
+        let listeners = shared
+            .map_get(&Term::from(Atom::from("listeners")))
+            .unwrap();
+        let http = listeners.list_element(0).unwrap();
+        assert_eq!(
+            Term::from(&http.tuple_element(1).unwrap()),
+            Term::from(8080)
+        );
+        assert_eq!(http.tuple_element(2), None);
+    }
+
+    #[test]
+    fn fan_out_shares_the_nodes() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ArcTerm>();
+
+        let shared = ArcTerm::from(config());
+        let expected = encode_to_vec(&config()).unwrap();
+        let listeners = shared
+            .map_get(&Term::from(Atom::from("listeners")))
+            .unwrap();
+        assert_eq!(listeners.strong_count(), 2);
+
+        let clones: Vec<_> = (0..8).map(|_| shared.clone()).collect();
+        assert_eq!(shared.strong_count(), 9);
+        // The elements are shared by the clones rather than copied.
+        assert_eq!(listeners.strong_count(), 2);
+        let handles: Vec<_> = clones
+            .into_iter()
+            .map(|term| std::thread::spawn(move || term.encode_to_vec().unwrap()))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+        assert_eq!(shared.strong_count(), 1);
+    }
+}
@@ -1,622 +1,1442 @@
 use super::*;
-use crate::codec::Decoder;
-use crate::codec::Encoder;
 use crate::codec_common::*;
-use crate::convert::TryAsRef;
-use byteorder::BigEndian;
-use byteorder::WriteBytesExt;
+use crate::convert::{IntoTerm, TryAsRef};
+use crate::dist::{self, AtomCache, DistFlags};
+use crate::metrics::{CodecMetrics, Counting, Observer};
+use crate::progress::{Progress, Timeout, Tracked};
 use num::bigint::BigInt;
 use std::convert::From;
-use std::io;
-use std::io::Write;
 use std::str;
 use tokio::io::{AsyncWriteExt, AsyncReadExt};
 use async_recursion::async_recursion;
 
-pub struct AsyncDecoder<R> {
-    reader: R,
-    buf: Vec<u8>,
-}
-impl<R: tokio::io::AsyncRead + std::marker::Unpin  + std::marker::Send>   AsyncDecoder<R> {
-    pub fn new(reader: R) -> Self {
-        AsyncDecoder {
-            reader,
-            buf: Vec::new(),
-        }
-    }
-    pub async fn decode(mut self) -> DecodeResult {
-        let version = self.reader.read_u8().await?;
-        if version != VERSION {
-            return Err(DecodeError::UnsupportedVersion { version });
-        }
-        let tag = self.reader.read_u8().await?;
-        match tag {
-            COMPRESSED_TERM => self.decode_compressed_term().await,
-            DISTRIBUTION_HEADER => unimplemented!(),
-            _ => self.decode_term_with_tag(tag).await,
-        }
-    }
-    async fn decode_term(&mut self) -> DecodeResult {
-        let tag = self.reader.read_u8().await?;
-        self.decode_term_with_tag(tag).await
-    }
-    #[async_recursion]
-    async fn decode_term_with_tag(&mut self, tag: u8) -> DecodeResult {
-        match tag {
-            NEW_FLOAT_EXT => self.decode_new_float_ext().await,
-            BIT_BINARY_EXT => self.decode_bit_binary_ext().await,
-            ATOM_CACHE_REF => unimplemented!(),
-            SMALL_INTEGER_EXT => self.decode_small_integer_ext().await,
-            INTEGER_EXT => self.decode_integer_ext().await,
-            FLOAT_EXT => self.decode_float_ext().await,
-            ATOM_EXT => self.decode_atom_ext().await,
-            REFERENCE_EXT => self.decode_reference_ext().await,
-            PORT_EXT => self.decode_port_ext().await,
-            NEW_PORT_EXT => self.decode_new_port_ext().await,
-            V4_PORT_EXT => self.decode_v4_port_ext().await,
-            PID_EXT => self.decode_pid_ext().await,
-            NEW_PID_EXT => self.decode_new_pid_ext().await,
-            SMALL_TUPLE_EXT => self.decode_small_tuple_ext().await,
-            LARGE_TUPLE_EXT => self.decode_large_tuple_ext().await,
-            NIL_EXT => self.decode_nil_ext().await,
-            STRING_EXT => self.decode_string_ext().await,
-            LIST_EXT => self.decode_list_ext().await,
-            BINARY_EXT => self.decode_binary_ext().await,
-            SMALL_BIG_EXT => self.decode_small_big_ext().await,
-            LARGE_BIG_EXT => self.decode_large_big_ext().await,
-            NEW_FUN_EXT => self.decode_new_fun_ext().await,
-            EXPORT_EXT => self.decode_export_ext().await,
-            NEW_REFERENCE_EXT => self.decode_new_reference_ext().await,
-            SMALL_ATOM_EXT => self.decode_small_atom_ext().await,
-            MAP_EXT => self.decode_map_ext().await,
-            FUN_EXT => self.decode_fun_ext().await,
-            ATOM_UTF8_EXT => self.decode_atom_utf8_ext().await,
-            SMALL_ATOM_UTF8_EXT => self.decode_small_atom_utf8_ext().await,
-            NEWER_REFERENCE_EXT => self.decode_newer_reference_ext().await,
-            _ => Err(DecodeError::UnknownTag { tag }),
-        }
-    }
-    async fn decode_compressed_term(&mut self) -> DecodeResult {
-        unimplemented!()
-    }
-    #[allow(clippy::unnecessary_wraps)]
-    async fn decode_nil_ext(&mut self) -> DecodeResult {
-        Ok(Term::from(List::nil()))
-    }
-    async fn decode_string_ext(&mut self) -> DecodeResult {
-            let size = self.reader.read_u16().await? as usize;
-            let mut bytes = vec![0; size];
-            self.reader.read_exact(&mut bytes).await?;
-            Ok(Term::from(ByteList::from(bytes)))            
-    }
-    async fn decode_list_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u32().await? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
-            elements.push(self.decode_term().await?);
-        }
-        let last = self.decode_term().await?;
-        if last.try_as_ref().map(List::is_nil).unwrap_or(false) {
-            Ok(Term::from(List::from(elements)))
-        } else {
-            Ok(Term::from(ImproperList::from((elements, last))))
-        }
-    }
-    async fn decode_small_tuple_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u8().await? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
-            elements.push(self.decode_term().await?);
-        }
-        Ok(Term::from(Tuple::from(elements)))
-    }
-    async fn decode_large_tuple_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u32().await? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
-            elements.push(self.decode_term().await?);
-        }
-        Ok(Term::from(Tuple::from(elements)))
-    }
-    async fn decode_map_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u32().await? as usize;
-        let mut map = HashMap::<Term,Term>::new();
-        for _ in 0..count {
-            let k = self.decode_term().await?;
-            let v = self.decode_term().await?;
-            map.insert(k, v);
-        }
-        Ok(Term::from(Map::from(map)))
-    }
-    async fn decode_binary_ext(&mut self) -> DecodeResult {
-        let size = self.reader.read_u32().await? as usize;
-        let mut buf = vec![0; size];
-        self.reader.read_exact(&mut buf).await?;
-        Ok(Term::from(Binary::from(buf)))
-    }
-    async fn decode_bit_binary_ext(&mut self) -> DecodeResult {
-        let size = self.reader.read_u32().await? as usize;
-        let tail_bits_size = self.reader.read_u8().await?;
-        let mut buf = vec![0; size];
-        self.reader.read_exact(&mut buf).await?;
-        if !buf.is_empty() {
-            let last = buf[size - 1] >> (8 - tail_bits_size);
-            buf[size - 1] = last;
-        }
-        Ok(Term::from(BitBinary::from((buf, tail_bits_size))))
-    }
-    async fn decode_pid_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().await.and_then(aux::term_into_atom)?;
-        Ok(Term::from(Pid {
-            node,
-            id: self.reader.read_u32().await?,
-            serial: self.reader.read_u32().await?,
-            creation: self.reader.read_u8().await? as u32,
-        }))
-    }
-    async fn decode_new_pid_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().await.and_then(aux::term_into_atom)?;
-        Ok(Term::from(Pid {
-            node,
-            id: self.reader.read_u32().await?,
-            serial: self.reader.read_u32().await?,
-            creation: self.reader.read_u32().await?,
-        }))
-    }
-    async fn decode_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().await.and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
-        Ok(Term::from(Port {
-            node,
-            id: u64::from(self.reader.read_u32().await?),
-            creation: u32::from(self.reader.read_u8().await?),
-        }))
-    }
-    async fn decode_new_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().await.and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
-        Ok(Term::from(Port {
-            node,
-            id: u64::from(self.reader.read_u32().await?),
-            creation: self.reader.read_u32().await?,
-        }))
-    }
-    async fn decode_v4_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().await.and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
-        Ok(Term::from(Port {
-            node,
-            id: self.reader.read_u64().await?,
-            creation: self.reader.read_u32().await?,
-        }))
-    }
-    async fn decode_reference_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().await.and_then(aux::term_into_atom)?;
-        Ok(Term::from(Reference {
-            node,
-            id: vec![self.reader.read_u32().await?],
-            creation: u32::from(self.reader.read_u8().await?),
-        }))
-    }
-    async fn decode_new_reference_ext(&mut self) -> DecodeResult {
-        let id_count = self.reader.read_u16().await? as usize;
-        let node = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let creation = u32::from(self.reader.read_u8().await?);
-        let mut id = Vec::with_capacity(id_count);
-        for _ in 0..id_count {
-            id.push(self.reader.read_u32().await?);
-        }
-        Ok(Term::from(Reference { node, id, creation }))
-    }
-    async fn decode_newer_reference_ext(&mut self) -> DecodeResult {
-        let id_count = self.reader.read_u16().await? as usize;
-        let node = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let creation = self.reader.read_u32().await?;
-        let mut id = Vec::with_capacity(id_count);
-        for _ in 0..id_count {
-            id.push(self.reader.read_u32().await?);
-        }
-        Ok(Term::from(Reference { node, id, creation }))
-    }
-    async fn decode_export_ext(&mut self) -> DecodeResult {
-        let module = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let function = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let arity = self
-            .decode_term().await
-            .and_then(|t| aux::term_into_ranged_integer(t, 0..0xFF))? as u8;
-        Ok(Term::from(ExternalFun {
-            module,
-            function,
-            arity,
-        }))
-    }
-    async fn decode_fun_ext(&mut self) -> DecodeResult {
-        let num_free = self.reader.read_u32().await?;
-        let pid = self.decode_term().await.and_then(aux::term_into_pid)?;
-        let module = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let index = self.decode_term().await.and_then(aux::term_into_fix_integer)?;
-        let uniq = self.decode_term().await.and_then(aux::term_into_fix_integer)?;
-        let mut vars = Vec::with_capacity(num_free as usize);
-        for _ in 0..num_free {
-            vars.push(self.decode_term().await?);
+type DecodeFuture<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = DecodeResult> + Send + 'a>>;
+type LocalDecodeFuture<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = DecodeResult> + 'a>>;
+
+/// Defines a decoder and an encoder whose recursive futures are boxed as `$decode_future` and by
+/// `$recursion`, and which require `$send` of their reader and writer.
+macro_rules! async_codec {
+    (
+        $(#[$decoder_attr:meta])*
+        $decoder:ident,
+        $(#[$encoder_attr:meta])*
+        $encoder:ident,
+        $decode_future:ident,
+        $recursion:meta,
+        $($send:ident)?
+    ) => {
+        $(#[$decoder_attr])*
+        pub struct $decoder<R> {
+            reader: Tracked<R>,
+            buf: Vec<u8>,
+            options: DecoderOptions,
+            depth: usize,
+            atom_table: Option<AtomTable>,
+            metrics: Option<Arc<dyn CodecMetrics>>,
+            yield_interval: Option<usize>,
+            decoded: usize,
+            poisoned: bool,
         }
-        Ok(Term::from(InternalFun::Old {
-            module,
-            pid,
-            free_vars: vars,
-            index: index.value,
-            uniq: uniq.value,
-        }))
-    }
-    async fn decode_new_fun_ext(&mut self) -> DecodeResult {
-        let _size = self.reader.read_u32().await?;
-        let arity = self.reader.read_u8().await?;
-        let mut uniq = [0; 16];
-        self.reader.read_exact(&mut uniq).await?;
-        let index = self.reader.read_u32().await?;
-        let num_free = self.reader.read_u32().await?;
-        let module = self.decode_term().await.and_then(aux::term_into_atom)?;
-        let old_index = self.decode_term().await.and_then(aux::term_into_fix_integer)?;
-        let old_uniq = self.decode_term().await.and_then(aux::term_into_fix_integer)?;
-        let pid = self.decode_term().await.and_then(aux::term_into_pid)?;
-        let mut vars = Vec::with_capacity(num_free as usize);
-        for _ in 0..num_free {
-            vars.push(self.decode_term().await?);
+        impl<R: tokio::io::AsyncRead + std::marker::Unpin $(+ $send)?> $decoder<R> {
+            pub fn new(reader: R) -> Self {
+                Self::with_options(reader, DecoderOptions::default())
+            }
+            /// Makes a decoder with `options` (see [`Decoder::with_options`]).
+            ///
+            /// The async decoders do not report the anomalies of terms, so they fail with
+            /// [`DecodeError::UnsupportedOption`] with the [`strict`](DecoderOptions::strict)
+            /// option.
+            pub fn with_options(reader: R, options: DecoderOptions) -> Self {
+                $decoder {
+                    reader: Tracked::new(reader),
+                    buf: Vec::new(),
+                    options,
+                    depth: 0,
+                    atom_table: None,
+                    metrics: None,
+                    yield_interval: Some(DEFAULT_YIELD_INTERVAL),
+                    decoded: 0,
+                    poisoned: false,
+                }
+            }
+            /// Makes the decoder yield to the executor after decoding every `interval` terms
+            /// (including subterms), or never if it is `None`.
+            ///
+            /// A reader whose data is already buffered never makes the decoder wait, so without
+            /// yielding, decoding a large term would keep the other tasks of its worker thread
+            /// from running until it is done. The default interval is 1024 terms.
+            pub fn with_yield_interval(mut self, interval: Option<usize>) -> Self {
+                self.yield_interval = interval;
+                self
+            }
+            /// Makes the decoder intern the names of the decoded atoms in `table` (or, with the
+            /// [`safe`](DecoderOptions::safe) option, look them up in it).
+            pub fn with_atom_table(mut self, table: AtomTable) -> Self {
+                self.atom_table = Some(table);
+                self
+            }
+            /// Makes the decoder report the decoded term to `metrics` (see [`Decoder::with_metrics`]).
+            pub fn with_metrics(mut self, metrics: Arc<dyn CodecMetrics>) -> Self {
+                self.metrics = Some(metrics);
+                self
+            }
+            /// Makes the decoder fail once the term needs more than `limit` bytes to be read
+            /// (see [`Decoder::with_bytes_limit`]).
+            pub fn with_bytes_limit(mut self, limit: u64) -> Self {
+                self.reader.limit = Some(limit);
+                self
+            }
+            /// Makes the decoder call `callback` with its position whenever the position passes
+            /// a multiple of `every` bytes (see [`Decoder::with_progress`]).
+            ///
+            /// # Panics
+            ///
+            /// Panics if `every` is zero.
+            pub fn with_progress(
+                mut self,
+                every: u64,
+                callback: impl FnMut(u64) + Send + 'static,
+            ) -> Self {
+                self.reader.progress = Some(Progress::new(every, Box::new(callback)));
+                self
+            }
+            /// Returns the number of bytes which the decoder has read since it was made.
+            pub fn position(&self) -> u64 {
+                self.reader.position
+            }
+            /// Returns the limit set by [`with_bytes_limit`](Self::with_bytes_limit).
+            pub fn bytes_limit(&self) -> Option<u64> {
+                self.reader.limit
+            }
+            /// Returns the number of bytes which may still be read before the limit set by
+            /// [`with_bytes_limit`](Self::with_bytes_limit) is exceeded.
+            pub fn bytes_remaining(&self) -> Option<u64> {
+                self.reader.remaining()
+            }
+            /// Returns `true` if a decode did not complete successfully (it failed, or its
+            /// future was dropped) since the decoder was made or reset, so that the reader may
+            /// be left within a term.
+            ///
+            /// A poisoned decoder fails with [`DecodeError::Poisoned`] instead of reading from it.
+            pub fn is_poisoned(&self) -> bool {
+                self.poisoned
+            }
+            /// Clears the poisoning of the decoder, once the caller made the reader start at a
+            /// term again (see [`Decoder::reset`]), and discards the bytes which it read past
+            /// a compressed term.
+            pub fn reset(&mut self) {
+                self.poisoned = false;
+                self.depth = 0;
+                self.reader.unread.clear();
+            }
+            pub async fn decode(mut self) -> DecodeResult {
+                self.decode_next().await
+            }
+            /// Decodes the next term of the reader, reusing the buffers of the decoder.
+            ///
+            /// # Cancel safety
+            ///
+            /// If the future is dropped before it completes, the bytes which it read are
+            /// discarded with the partly decoded term, and the decoder is left poisoned (see
+            /// [`is_poisoned`](Self::is_poisoned)), as a failed decode leaves it.
+            pub async fn decode_next(&mut self) -> DecodeResult {
+                self.decode_timed(None).await
+            }
+            /// Decodes the next term as [`decode_next`](Self::decode_next) does, but fails with
+            /// [`DecodeError::Timeout`] once a read makes no progress for `idle`.
+            ///
+            /// The timeout restarts whenever bytes arrive, so a large term which arrives slowly
+            /// but steadily is still decoded. The decoder is left poisoned by a timeout, and
+            /// [`position`](Self::position) tells how many bytes were read.
+            pub async fn decode_with_timeout(&mut self, idle: std::time::Duration) -> DecodeResult {
+                self.decode_timed(Some(Timeout::new(Some(idle), None))).await
+            }
+            /// Decodes the next term as [`decode_next`](Self::decode_next) does, but fails with
+            /// [`DecodeError::Timeout`] if the term is not read by `deadline`.
+            pub async fn decode_with_deadline(
+                &mut self,
+                deadline: tokio::time::Instant,
+            ) -> DecodeResult {
+                self.decode_timed(Some(Timeout::new(None, Some(deadline)))).await
+            }
+            async fn decode_timed(&mut self, timeout: Option<Timeout>) -> DecodeResult {
+                if self.poisoned {
+                    return Err(DecodeError::Poisoned);
+                }
+                if self.options.strict {
+                    return Err(DecodeError::UnsupportedOption { option: "strict" });
+                }
+                self.poisoned = true;
+                // A dropped future may have left the depth of the subterm it was decoding.
+                self.depth = 0;
+                self.reader.timeout = timeout;
+                self.reader.timed_out = None;
+                let result = match Observer::decode(self.metrics.as_ref()) {
+                    None => self.decode_versioned().await,
+                    Some(observer) => self.decode_observed(observer).await,
+                };
+                let result = self.reader.check(result);
+                self.reader.timeout = None;
+                self.poisoned = result.is_err();
+                result
+            }
+            async fn decode_observed(&mut self, observer: Observer) -> DecodeResult {
+                let mut reader = Counting::new(&mut self.reader);
+                let mut decoder = $decoder {
+                    reader: Tracked::new(&mut reader),
+                    buf: std::mem::take(&mut self.buf),
+                    options: self.options,
+                    depth: 0,
+                    atom_table: self.atom_table.clone(),
+                    metrics: None,
+                    yield_interval: self.yield_interval,
+                    decoded: self.decoded,
+                    poisoned: false,
+                };
+                let result = observer.in_scope_async(decoder.decode_versioned()).await;
+                let (buf, decoded) = (decoder.buf, decoder.decoded);
+                // The bytes read past a compressed term go back to the reader of this decoder.
+                let mut unread = decoder.reader.unread;
+                reader.count -= unread.len() as u64;
+                let result = reader.inner.check(result);
+                observer.finish_decode(&result, reader.count);
+                self.reader.unread(unread.make_contiguous());
+                self.buf = buf;
+                self.decoded = decoded;
+                result
+            }
+            async fn decode_versioned(&mut self) -> DecodeResult {
+                let version = self.reader.read_u8().await?;
+                if version != VERSION {
+                    return Err(DecodeError::UnsupportedVersion { version });
+                }
+                let tag = self.reader.read_u8().await?;
+                match tag {
+                    COMPRESSED_TERM => {
+                        #[cfg(feature = "tracing")]
+                        instrument::record_compressed();
+                        self.decode_compressed_term().await
+                    }
+                    _ => self.decode_term_with_tag(tag).await,
+                }
+            }
+            /// Decodes the next term, which must be a binary or a byte list, by copying its bytes to `sink`
+            /// (see [`Decoder::decode_binary_into`]).
+            pub async fn decode_binary_into<W>(&mut self, sink: &mut W) -> Result<u64, DecodeError>
+            where
+                W: tokio::io::AsyncWrite + std::marker::Unpin + ?Sized,
+            {
+                let result = self.decode_binary_versioned_into(sink).await;
+                self.reader.check(result)
+            }
+            async fn decode_binary_versioned_into<W>(
+                &mut self,
+                sink: &mut W,
+            ) -> Result<u64, DecodeError>
+            where
+                W: tokio::io::AsyncWrite + std::marker::Unpin + ?Sized,
+            {
+                let version = self.reader.read_u8().await?;
+                if version != VERSION {
+                    return Err(DecodeError::UnsupportedVersion { version });
+                }
+                let tag = self.reader.read_u8().await?;
+                let size = match tag {
+                    BINARY_EXT => u64::from(self.reader.read_u32().await?),
+                    STRING_EXT => u64::from(self.reader.read_u16().await?),
+                    // The empty byte list.
+                    NIL_EXT => 0,
+                    _ => {
+                        let value = self.decode_term_with_tag(tag).await?;
+                        return Err(aux::unexpected_type(
+                            value,
+                            "Binary",
+                            self.options.keep_offending_term,
+                        ));
+                    }
+                };
+                let copied = tokio::io::copy(&mut (&mut self.reader).take(size), sink).await?;
+                if copied < size {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                Ok(copied)
+            }
+            async fn decode_term(&mut self) -> DecodeResult {
+                let tag = self.reader.read_u8().await?;
+                self.decode_term_with_tag(tag).await
+            }
+            async fn decode_atom(&mut self) -> Result<Atom, DecodeError> {
+                let tag = self.reader.read_u8().await?;
+                aux::check_atom_tag(tag)?;
+                self.decode_term_with_tag(tag)
+                    .await
+                    .and_then(|t| aux::term_into_atom(t, self.options.keep_offending_term))
+            }
+            async fn decode_term_with_tag(&mut self, tag: u8) -> DecodeResult {
+                if self.options.raw_depth.is_some_and(|d| self.depth >= d)
+                    && !aux::is_atom_tag(tag)
+                {
+                    return self.decode_raw_with_tag(tag).await;
+                }
+                aux::check_depth(self.depth)?;
+                self.decoded += 1;
+                if let Some(interval) = self.yield_interval {
+                    if self.decoded % interval.max(1) == 0 {
+                        YieldNow(false).await;
+                    }
+                }
+                self.depth += 1;
+                let result = self.decode_subterm_with_tag(tag).await;
+                self.depth -= 1;
+                result
+            }
+            // Boxed too, so that the state of the capture is not part of the state of every
+            // decoded term.
+            fn decode_raw_with_tag(&mut self, tag: u8) -> $decode_future<'_> {
+                Box::pin(async move {
+                    let mut bytes = Vec::new();
+                    self.capture_term_with_tag(tag, &mut bytes).await?;
+                    Ok(Term::from(Raw::from(bytes)))
+                })
+            }
+            // A plain function returning the boxed future of the tag, rather than an async one,
+            // so that the recursion does not keep the states of all the arms on the stack.
+            fn decode_subterm_with_tag(&mut self, tag: u8) -> $decode_future<'_> {
+                match tag {
+                    NEW_FLOAT_EXT => Box::pin(self.decode_new_float_ext()),
+                    BIT_BINARY_EXT => Box::pin(self.decode_bit_binary_ext()),
+                    ATOM_CACHE_REF => Box::pin(self.decode_atom_cache_ref()),
+                    SMALL_INTEGER_EXT => Box::pin(self.decode_small_integer_ext()),
+                    INTEGER_EXT => Box::pin(self.decode_integer_ext()),
+                    FLOAT_EXT => Box::pin(self.decode_float_ext()),
+                    ATOM_EXT => Box::pin(self.decode_atom_ext()),
+                    REFERENCE_EXT => Box::pin(self.decode_reference_ext()),
+                    PORT_EXT => Box::pin(self.decode_port_ext()),
+                    NEW_PORT_EXT => Box::pin(self.decode_new_port_ext()),
+                    V4_PORT_EXT => Box::pin(self.decode_v4_port_ext()),
+                    PID_EXT => Box::pin(self.decode_pid_ext()),
+                    NEW_PID_EXT => Box::pin(self.decode_new_pid_ext()),
+                    SMALL_TUPLE_EXT => Box::pin(self.decode_small_tuple_ext()),
+                    LARGE_TUPLE_EXT => Box::pin(self.decode_large_tuple_ext()),
+                    NIL_EXT => Box::pin(self.decode_nil_ext()),
+                    STRING_EXT => Box::pin(self.decode_string_ext()),
+                    LIST_EXT => Box::pin(self.decode_list_ext()),
+                    BINARY_EXT => Box::pin(self.decode_binary_ext()),
+                    SMALL_BIG_EXT => Box::pin(self.decode_small_big_ext()),
+                    LARGE_BIG_EXT => Box::pin(self.decode_large_big_ext()),
+                    NEW_FUN_EXT => Box::pin(self.decode_new_fun_ext()),
+                    EXPORT_EXT => Box::pin(self.decode_export_ext()),
+                    NEW_REFERENCE_EXT => Box::pin(self.decode_new_reference_ext()),
+                    SMALL_ATOM_EXT => Box::pin(self.decode_small_atom_ext()),
+                    MAP_EXT => Box::pin(self.decode_map_ext()),
+                    FUN_EXT => Box::pin(self.decode_fun_ext()),
+                    ATOM_UTF8_EXT => Box::pin(self.decode_atom_utf8_ext()),
+                    SMALL_ATOM_UTF8_EXT => Box::pin(self.decode_small_atom_utf8_ext()),
+                    NEWER_REFERENCE_EXT => Box::pin(self.decode_newer_reference_ext()),
+                    _ => Box::pin(async move { Err(DecodeError::UnknownTag { tag }) }),
+                }
+            }
+            async fn decode_compressed_term(&mut self) -> DecodeResult {
+                let max = self.options.max_uncompressed_size;
+                zlib::check_declared_size(max, self.reader.read_u32().await? as usize)?;
+                let body = zlib::inflate_async(&mut self.reader, max).await?;
+                let decoder = Decoder::with_options(&body[..], self.options);
+                let mut decoder = match self.atom_table.clone() {
+                    Some(table) => decoder.with_atom_table(table),
+                    None => decoder,
+                };
+                let term = decoder.decode_term()?;
+                let trailing = body.len() as u64 - decoder.position();
+                if trailing != 0 {
+                    return Err(DecodeError::InvalidData {
+                        message: format!("{} bytes follow the compressed term", trailing),
+                    });
+                }
+                Ok(term)
+            }
+            // The async decoders do not decode distribution headers, so no reference is known.
+            async fn decode_atom_cache_ref(&mut self) -> DecodeResult {
+                let index = self.reader.read_u8().await? as usize;
+                Err(DecodeError::UnknownAtomCacheRef { index })
+            }
+            #[allow(clippy::unnecessary_wraps)]
+            async fn decode_nil_ext(&mut self) -> DecodeResult {
+                Ok(Term::from(List::nil()))
+            }
+            async fn decode_string_ext(&mut self) -> DecodeResult {
+                let size = self.reader.read_u16().await? as usize;
+                read_chunked(&mut self.reader, &mut self.buf, size).await?;
+                Ok(Term::from(ByteList::from(self.buf.to_vec())))
+            }
+            async fn decode_list_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u32().await? as usize;
+                let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for _ in 0..count {
+                    elements.push(self.decode_term().await?);
+                }
+                let last = self.decode_term().await?;
+                if last.try_as_ref().map(List::is_nil).unwrap_or(false) {
+                    Ok(Term::from(List::from(elements)))
+                } else {
+                    Ok(Term::from(ImproperList::from((elements, last))))
+                }
+            }
+            async fn decode_small_tuple_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u8().await? as usize;
+                let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for _ in 0..count {
+                    elements.push(self.decode_term().await?);
+                }
+                Ok(Term::from(Tuple::from(elements)))
+            }
+            async fn decode_large_tuple_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u32().await? as usize;
+                let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for _ in 0..count {
+                    elements.push(self.decode_term().await?);
+                }
+                Ok(Term::from(Tuple::from(elements)))
+            }
+            async fn decode_map_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u32().await? as usize;
+                let mut map = TermMap::new();
+                for _ in 0..count {
+                    let k = self.decode_term().await?;
+                    let v = self.decode_term().await?;
+                    map.insert(k, v);
+                }
+                Ok(Term::from(Map::from(map)))
+            }
+            async fn decode_binary_ext(&mut self) -> DecodeResult {
+                let size = self.reader.read_u32().await? as usize;
+                let mut buf = Vec::new();
+                read_exact_bounded(&mut self.reader, &mut buf, size).await?;
+                Ok(Term::from(Binary::from(buf)))
+            }
+            async fn decode_bit_binary_ext(&mut self) -> DecodeResult {
+                let size = self.reader.read_u32().await? as usize;
+                let tail_bits_size = self.reader.read_u8().await?;
+                aux::check_tail_bits_size(size, tail_bits_size)?;
+                let mut buf = Vec::new();
+                read_exact_bounded(&mut self.reader, &mut buf, size).await?;
+                if let Some(last) = buf.last_mut() {
+                    *last >>= 8 - tail_bits_size;
+                }
+                Ok(Term::from(BitBinary::from((buf, tail_bits_size))))
+            }
+            async fn decode_pid_ext(&mut self) -> DecodeResult {
+                let node = self.decode_atom().await?;
+                Ok(Term::from(Pid {
+                    node,
+                    id: self.reader.read_u32().await?,
+                    serial: self.reader.read_u32().await?,
+                    creation: self.reader.read_u8().await? as u32,
+                }))
+            }
+            async fn decode_new_pid_ext(&mut self) -> DecodeResult {
+                let node = self.decode_atom().await?;
+                Ok(Term::from(Pid {
+                    node,
+                    id: self.reader.read_u32().await?,
+                    serial: self.reader.read_u32().await?,
+                    creation: self.reader.read_u32().await?,
+                }))
+            }
+            async fn decode_port_ext(&mut self) -> DecodeResult {
+                let node: Atom = self.decode_atom().await?;
+                Ok(Term::from(Port {
+                    node,
+                    id: u64::from(self.reader.read_u32().await?),
+                    creation: u32::from(self.reader.read_u8().await?),
+                }))
+            }
+            async fn decode_new_port_ext(&mut self) -> DecodeResult {
+                let node: Atom = self.decode_atom().await?;
+                Ok(Term::from(Port {
+                    node,
+                    id: u64::from(self.reader.read_u32().await?),
+                    creation: self.reader.read_u32().await?,
+                }))
+            }
+            async fn decode_v4_port_ext(&mut self) -> DecodeResult {
+                let node: Atom = self.decode_atom().await?;
+                Ok(Term::from(Port {
+                    node,
+                    id: self.reader.read_u64().await?,
+                    creation: self.reader.read_u32().await?,
+                }))
+            }
+            async fn decode_reference_ext(&mut self) -> DecodeResult {
+                let node = self.decode_atom().await?;
+                Ok(Term::from(Reference {
+                    node,
+                    id: vec![self.reader.read_u32().await?],
+                    creation: u32::from(self.reader.read_u8().await?),
+                }))
+            }
+            async fn decode_new_reference_ext(&mut self) -> DecodeResult {
+                let id_count = self.reader.read_u16().await? as usize;
+                let node = self.decode_atom().await?;
+                let creation = u32::from(self.reader.read_u8().await?);
+                let mut id = Vec::with_capacity(id_count);
+                for _ in 0..id_count {
+                    id.push(self.reader.read_u32().await?);
+                }
+                Ok(Term::from(Reference { node, id, creation }))
+            }
+            async fn decode_newer_reference_ext(&mut self) -> DecodeResult {
+                let id_count = self.reader.read_u16().await? as usize;
+                let node = self.decode_atom().await?;
+                let creation = self.reader.read_u32().await?;
+                let mut id = Vec::with_capacity(id_count);
+                for _ in 0..id_count {
+                    id.push(self.reader.read_u32().await?);
+                }
+                Ok(Term::from(Reference { node, id, creation }))
+            }
+            async fn decode_export_ext(&mut self) -> DecodeResult {
+                let keep = self.options.keep_offending_term;
+                let module = self.decode_atom().await?;
+                let function = self.decode_atom().await?;
+                let arity = self
+                    .decode_term().await
+                    .and_then(|t| aux::term_into_ranged_integer(t, 0..0xFF, keep))? as u8;
+                Ok(Term::from(ExternalFun {
+                    module,
+                    function,
+                    arity,
+                }))
+            }
+            async fn decode_fun_ext(&mut self) -> DecodeResult {
+                let keep = self.options.keep_offending_term;
+                let num_free = self.reader.read_u32().await?;
+                let pid = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_pid(t, keep))?;
+                let module = self.decode_atom().await?;
+                let index = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+                let uniq = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+                let mut vars = Vec::with_capacity((num_free as usize).min(MAX_PREALLOCATED_LEN));
+                for _ in 0..num_free {
+                    vars.push(self.decode_term().await?);
+                }
+                Ok(Term::from(InternalFun::Old {
+                    module,
+                    pid,
+                    free_vars: vars,
+                    index: index.value,
+                    uniq: uniq.value,
+                }))
+            }
+            async fn decode_new_fun_ext(&mut self) -> DecodeResult {
+                let keep = self.options.keep_offending_term;
+                let _size = self.reader.read_u32().await?;
+                let arity = self.reader.read_u8().await?;
+                let mut uniq = [0; 16];
+                self.reader.read_exact(&mut uniq).await?;
+                let index = self.reader.read_u32().await?;
+                let num_free = self.reader.read_u32().await?;
+                let module = self.decode_atom().await?;
+                let old_index = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+                let old_uniq = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+                let pid = self
+                    .decode_term()
+                    .await
+                    .and_then(|t| aux::term_into_pid(t, keep))?;
+                let mut vars = Vec::with_capacity((num_free as usize).min(MAX_PREALLOCATED_LEN));
+                for _ in 0..num_free {
+                    vars.push(self.decode_term().await?);
+                }
+                Ok(Term::from(InternalFun::New {
+                    module,
+                    arity,
+                    pid,
+                    free_vars: vars,
+                    index,
+                    uniq,
+                    old_index: old_index.value,
+                    old_uniq: old_uniq.value,
+                }))
+            }
+            async fn decode_new_float_ext(&mut self) -> DecodeResult {
+                let value = self.reader.read_f64().await?;
+                Ok(Term::from(Float::try_from(value)?))
+            }
+            async fn decode_float_ext(&mut self) -> DecodeResult {
+                let mut buf = [0; 31];
+                self.reader.read_exact(&mut buf).await?;
+                let float_str = str::from_utf8(&buf)
+                    .or_else(|e| aux::invalid_data_error(e.to_string()))?
+                    .trim_end_matches(0 as char);
+                let value = float_str
+                    .parse::<f32>()
+                    .or_else(|e| aux::invalid_data_error(e.to_string()))?;
+                Ok(Term::from(Float::try_from(value)?))
+            }
+            async fn decode_small_integer_ext(&mut self) -> DecodeResult {
+                let value = self.reader.read_u8().await?;
+                Ok(Term::from(FixInteger::from(i32::from(value))))
+            }
+            async fn decode_integer_ext(&mut self) -> DecodeResult {
+                let value = self.reader.read_i32().await?;
+                Ok(Term::from(FixInteger::from(value)))
+            }
+            async fn decode_small_big_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u8().await? as usize;
+                let sign = self.reader.read_u8().await?;
+                self.buf.resize(count, 0);
+                self.reader.read_exact(&mut self.buf).await?;
+                let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
+                Ok(Term::from(BigInteger { value }))
+            }
+            async fn decode_large_big_ext(&mut self) -> DecodeResult {
+                let count = self.reader.read_u32().await? as usize;
+                let sign = self.reader.read_u8().await?;
+                read_exact_bounded(&mut self.reader, &mut self.buf, count).await?;
+                let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
+                Ok(Term::from(BigInteger { value }))
+            }
+            fn make_atom(&self, name: &str) -> Result<Atom, DecodeError> {
+                atom_table::make_atom(self.atom_table.as_ref(), name, self.options.safe)
+            }
+            /// Reads the name of an atom, of `size` bytes of Latin-1 or UTF-8, and makes the atom.
+            async fn decode_atom_name(&mut self, size: usize, latin1: bool) -> DecodeResult {
+                let name = read_name(&mut self.reader, &mut self.buf, size, latin1).await?;
+                let atom = self.make_atom(&name)?;
+                self.buf = name.into_bytes();
+                Ok(Term::from(atom))
+            }
+            async fn decode_atom_ext(&mut self) -> DecodeResult {
+                let len = self.reader.read_u16().await?;
+                self.decode_atom_name(len as usize, true).await
+            }
+            async fn decode_small_atom_ext(&mut self) -> DecodeResult {
+                let len = self.reader.read_u8().await?;
+                self.decode_atom_name(len as usize, true).await
+            }
+            async fn decode_atom_utf8_ext(&mut self) -> DecodeResult {
+                let len = self.reader.read_u16().await?;
+                self.decode_atom_name(len as usize, false).await
+            }
+            async fn decode_small_atom_utf8_ext(&mut self) -> DecodeResult {
+                let len = self.reader.read_u8().await?;
+                self.decode_atom_name(len as usize, false).await
+            }
+            /// Copies the encoding of a term into `out` without decoding it (see
+            /// `Decoder::capture_term_with_tag`).
+            ///
+            /// Rather than recursing, it keeps the number of subterms left to capture of each
+            /// enclosing term, and the number of bytes which follow them.
+            async fn capture_term_with_tag(
+                &mut self,
+                mut tag: u8,
+                out: &mut Vec<u8>,
+            ) -> Result<(), DecodeError> {
+                let mut left: Vec<(usize, usize)> = Vec::new();
+                loop {
+                    aux::check_depth(self.depth + left.len())?;
+                    let (count, trailing) = self.capture_fields(tag, out).await?;
+                    if count > 0 {
+                        left.push((count, trailing));
+                    }
+                    loop {
+                        match left.last_mut() {
+                            None => return Ok(()),
+                            Some((0, trailing)) => {
+                                let trailing = *trailing;
+                                left.pop();
+                                self.capture_bytes(trailing, out).await?;
+                            }
+                            Some((count, _)) => {
+                                *count -= 1;
+                                break;
+                            }
+                        }
+                    }
+                    tag = self.reader.read_u8().await?;
+                }
+            }
+            /// Copies the tag and the fields of a term which precede its subterms into `out`,
+            /// and returns the number of its subterms and of the bytes which follow them.
+            async fn capture_fields(
+                &mut self,
+                tag: u8,
+                out: &mut Vec<u8>,
+            ) -> Result<(usize, usize), DecodeError> {
+                if tag == ATOM_CACHE_REF {
+                    self.decode_atom_cache_ref().await?;
+                }
+                out.push(tag);
+                let count = match tag {
+                    SMALL_TUPLE_EXT => self.capture_u8(out).await?,
+                    LARGE_TUPLE_EXT => self.capture_u32(out).await?,
+                    // The tail of a list, or the values of a map.
+                    LIST_EXT => self.capture_u32(out).await?.saturating_add(1),
+                    MAP_EXT => self.capture_u32(out).await?.saturating_mul(2),
+                    EXPORT_EXT => 3,
+                    FUN_EXT => self.capture_u32(out).await?.saturating_add(4),
+                    NEW_FUN_EXT => {
+                        // Without atom cache references, the size stays as it is.
+                        self.capture_bytes(4 + 1 + 16 + 4, out).await?;
+                        self.capture_u32(out).await?.saturating_add(4)
+                    }
+                    // The node, followed by the other fields.
+                    PID_EXT => return Ok((1, 9)),
+                    NEW_PID_EXT | V4_PORT_EXT => return Ok((1, 12)),
+                    PORT_EXT | REFERENCE_EXT => return Ok((1, 5)),
+                    NEW_PORT_EXT => return Ok((1, 8)),
+                    NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT => {
+                        let len = self.capture_u16(out).await?;
+                        let creation = if tag == NEW_REFERENCE_EXT { 1 } else { 4 };
+                        return Ok((1, creation + 4 * len));
+                    }
+                    _ => {
+                        self.capture_simple_term(tag, out).await?;
+                        0
+                    }
+                };
+                Ok((count, 0))
+            }
+            /// Captures the fields of a term which has no subterms.
+            async fn capture_simple_term(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+                match tag {
+                    SMALL_INTEGER_EXT => self.capture_bytes(1, out).await,
+                    INTEGER_EXT => self.capture_bytes(4, out).await,
+                    NEW_FLOAT_EXT => self.capture_bytes(8, out).await,
+                    FLOAT_EXT => self.capture_bytes(31, out).await,
+                    NIL_EXT => Ok(()),
+                    ATOM_EXT | ATOM_UTF8_EXT | STRING_EXT => {
+                        let len = self.capture_u16(out).await?;
+                        self.capture_bytes(len, out).await
+                    }
+                    SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => {
+                        let len = self.capture_u8(out).await?;
+                        self.capture_bytes(len, out).await
+                    }
+                    BINARY_EXT => {
+                        let len = self.capture_u32(out).await?;
+                        self.capture_bytes(len, out).await
+                    }
+                    BIT_BINARY_EXT | LARGE_BIG_EXT => {
+                        let len = self.capture_u32(out).await?;
+                        self.capture_bytes(1 + len, out).await
+                    }
+                    SMALL_BIG_EXT => {
+                        let len = self.capture_u8(out).await?;
+                        self.capture_bytes(1 + len, out).await
+                    }
+                    _ => Err(DecodeError::UnknownTag { tag }),
+                }
+            }
+            async fn capture_bytes(&mut self, len: usize, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+                // Grows `out` as the bytes arrive, as `len` may be bogus.
+                let read = (&mut self.reader).take(len as u64).read_to_end(out).await?;
+                if read < len {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                Ok(())
+            }
+            async fn capture_u8(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+                self.capture_bytes(1, out).await?;
+                Ok(out[out.len() - 1] as usize)
+            }
+            async fn capture_u16(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+                self.capture_bytes(2, out).await?;
+                Ok(u16::from_be_bytes([out[out.len() - 2], out[out.len() - 1]]) as usize)
+            }
+            async fn capture_u32(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+                self.capture_bytes(4, out).await?;
+                let n = out.len();
+                Ok(u32::from_be_bytes([out[n - 4], out[n - 3], out[n - 2], out[n - 1]]) as usize)
+            }
         }
-        Ok(Term::from(InternalFun::New {
-            module,
-            arity,
-            pid,
-            free_vars: vars,
-            index,
-            uniq,
-            old_index: old_index.value,
-            old_uniq: old_uniq.value,
-        }))
-    }
-    async fn decode_new_float_ext(&mut self) -> DecodeResult {
-        let value = self.reader.read_f64().await?;
-        Ok(Term::from(Float::try_from(value)?))
-    }
-    async fn decode_float_ext(&mut self) -> DecodeResult {
-        let mut buf = [0; 31];
-        self.reader.read_exact(&mut buf).await?;
-        let float_str = str::from_utf8(&buf)
-            .or_else(|e| aux::invalid_data_error(e.to_string()))?
-            .trim_end_matches(0 as char);
-        let value = float_str
-            .parse::<f32>()
-            .or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Float::try_from(value)?))
-    }
-    async fn decode_small_integer_ext(&mut self) -> DecodeResult {
-        let value = self.reader.read_u8().await?;
-        Ok(Term::from(FixInteger::from(i32::from(value))))
-    }
-    async fn decode_integer_ext(&mut self) -> DecodeResult {
-        let value = self.reader.read_i32().await?;
-        Ok(Term::from(FixInteger::from(value)))
-    }
-    async fn decode_small_big_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u8().await? as usize;
-        let sign = self.reader.read_u8().await?;
-        self.buf.resize(count, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
-        Ok(Term::from(BigInteger { value }))
-    }
-    async fn decode_large_big_ext(&mut self) -> DecodeResult {
-        let count = self.reader.read_u32().await? as usize;
-        let sign = self.reader.read_u8().await?;
-        self.buf.resize(count, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
-        Ok(Term::from(BigInteger { value }))
-    }
-    async fn decode_atom_ext(&mut self) -> DecodeResult {
-        let len = self.reader.read_u16().await?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let name = aux::latin1_bytes_to_string(&self.buf)?;
-        Ok(Term::from(Atom { name }))
-    }
-    async fn decode_small_atom_ext(&mut self) -> DecodeResult {
-        let len = self.reader.read_u8().await?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let name = aux::latin1_bytes_to_string(&self.buf)?;
-        Ok(Term::from(Atom { name }))
-    }
-    async fn decode_atom_utf8_ext(&mut self) -> DecodeResult {
-        let len = self.reader.read_u16().await?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let name = str::from_utf8(&self.buf).or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Atom::from(name)))
-    }
-    async fn decode_small_atom_utf8_ext(&mut self) -> DecodeResult {
-        let len = self.reader.read_u8().await?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf).await?;
-        let name = str::from_utf8(&self.buf).or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Atom::from(name)))
-    }
-}
 
-pub struct AsyncEncoder<W> {
-    writer: W,
-}
-impl<W: tokio::io::AsyncWrite + std::marker::Unpin + Send> AsyncEncoder<W> {
-    pub fn new(writer: W) -> Self {
-        AsyncEncoder { writer: writer}
-    }
-    pub async fn encode(mut self, term: &Term) -> EncodeResult {
-        self.writer.write_u8(VERSION).await?;
-        self.encode_term(term).await
-    }
-    
-    #[async_recursion]
-    async fn encode_term(&mut self, term: &Term) -> EncodeResult {
-        match *term {
-            Term::Atom(ref x) => self.encode_atom(x).await,
-            Term::FixInteger(ref x) => self.encode_fix_integer(x).await,
-            Term::BigInteger(ref x) => self.encode_big_integer(x).await,
-            Term::Float(ref x) => self.encode_float(x).await,
-            Term::Pid(ref x) => self.encode_pid(x).await,
-            Term::Port(ref x) => self.encode_port(x).await,
-            Term::Reference(ref x) => self.encode_reference(x).await,
-            Term::ExternalFun(ref x) => self.encode_external_fun(x).await,
-            Term::InternalFun(ref x) => self.encode_internal_fun(x).await,
-            Term::Binary(ref x) => self.encode_binary(x).await,
-            Term::BitBinary(ref x) => self.encode_bit_binary(x).await,
-            Term::List(ref x) => self.encode_list(x).await,
-            Term::ImproperList(ref x) => self.encode_improper_list(x).await,
-            Term::Tuple(ref x) => self.encode_tuple(x).await,
-            Term::Map(ref x) => self.encode_map(x).await,
-            Term::ByteList(ref x) => self.encode_byte_list(x.bytes.as_slice()).await
+        $(#[$encoder_attr])*
+        pub struct $encoder<W> {
+            writer: W,
+            atom_cache_refs: HashMap<Atom, u8>,
+            options: EncoderOptions,
+            metrics: Option<Arc<dyn CodecMetrics>>,
         }
-    }
-    async fn encode_nil(&mut self) -> EncodeResult {
-        self.writer.write_u8(NIL_EXT).await?;
-        Ok(())
-    }
-    async fn encode_list(&mut self, x: &List) -> EncodeResult {
-        let to_byte = |e: &Term| {
-            e.try_as_ref()
-                .and_then(|&FixInteger { value: i }| if i < 0x100 { Some(i as u8) } else { None })
-        };
-        if !x.elements.is_empty()
-            && x.elements.len() <= std::u16::MAX as usize
-            && x.elements.iter().all(|e| to_byte(e).is_some())
-        {
-            self.writer.write_u8(STRING_EXT).await?;
-            self.writer
-                .write_u16(x.elements.len() as u16).await?;
-            for b in x.elements.iter().map(|e| to_byte(e).unwrap()) {
-                self.writer.write_u8(b).await?;
-            }
-        } else {
-            if !x.is_nil() {
+        impl<W: tokio::io::AsyncWrite + std::marker::Unpin $(+ $send)?> $encoder<W> {
+            pub fn new(writer: W) -> Self {
+                Self::with_options(writer, EncoderOptions::default())
+            }
+            /// Makes an encoder with `options` (see [`Encoder::with_options`]).
+            pub fn with_options(writer: W, options: EncoderOptions) -> Self {
+                $encoder {
+                    writer,
+                    atom_cache_refs: HashMap::new(),
+                    options,
+                    metrics: None,
+                }
+            }
+            /// Makes the encoder report the encoded term to `metrics` (see [`Encoder::with_metrics`]).
+            pub fn with_metrics(mut self, metrics: Arc<dyn CodecMetrics>) -> Self {
+                self.metrics = Some(metrics);
+                self
+            }
+            pub async fn encode(self, term: &Term) -> EncodeResult {
+                let compressed = self.options.compressed;
+                let Some(observer) = Observer::encode(self.metrics.as_ref(), term, compressed) else {
+                    return self.encode_versioned(term).await;
+                };
+                let mut writer = Counting::new(self.writer);
+                let encoder = $encoder {
+                    writer: &mut writer,
+                    atom_cache_refs: self.atom_cache_refs,
+                    options: self.options,
+                    metrics: None,
+                };
+                let result = observer
+                    .in_scope_async(encoder.encode_versioned(term))
+                    .await;
+                observer.finish_encode(&result, writer.count);
+                result
+            }
+            async fn encode_versioned(mut self, term: &Term) -> EncodeResult {
+                if self.options.compressed {
+                    // The whole body is needed to deflate it, so it is encoded in memory.
+                    let mut bytes = Vec::new();
+                    Encoder::with_options(&mut bytes, self.options).encode(term)?;
+                    self.writer.write_all(&bytes).await?;
+                    return Ok(());
+                }
+                self.writer.write_u8(VERSION).await?;
+                self.encode_term(term).await
+            }
+            /// Encodes a distribution message (see [`Encoder::encode_distribution_message`]).
+            pub async fn encode_distribution_message(
+                &mut self,
+                control: &Term,
+                payload: Option<&Term>,
+                cache: &mut AtomCache,
+            ) -> EncodeResult {
+                let (header, refs) = dist::encode_distribution_header(control, payload, cache);
+                self.writer.write_all(&header).await?;
+                self.atom_cache_refs = refs;
+                let result = self.encode_distribution_terms(control, payload).await;
+                self.atom_cache_refs.clear();
+                result
+            }
+            async fn encode_distribution_terms(
+                &mut self,
+                control: &Term,
+                payload: Option<&Term>,
+            ) -> EncodeResult {
+                self.encode_term(control).await?;
+                if let Some(payload) = payload {
+                    self.encode_term(payload).await?;
+                }
+                Ok(())
+            }
+            /// Encodes the elements of `iter` as a list without collecting them into a [`List`]
+            /// (see [`Encoder::encode_list_from_iter`]).
+            pub async fn encode_list_from_iter<I>(&mut self, iter: I) -> EncodeResult
+            where
+                I: IntoIterator,
+                I::IntoIter: ExactSizeIterator,
+                I::Item: IntoTerm,
+            {
+                let iter = iter.into_iter();
+                let len = iter.len();
+                self.writer.write_u8(VERSION).await?;
+                if len > 0 {
+                    self.writer.write_u8(LIST_EXT).await?;
+                    self.writer.write_u32(len as u32).await?;
+                    for element in aux::exact(iter, len) {
+                        let (i, e) = element?;
+                        self.encode_term(&e.into_term())
+                            .await
+                            .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
+                    }
+                }
+                self.encode_nil().await
+            }
+            /// Encodes the elements of `iter` as a list, for iterators which do not know their
+            /// length (see [`Encoder::encode_list_from_buffered_iter`]).
+            pub async fn encode_list_from_buffered_iter<I>(&mut self, iter: I) -> EncodeResult
+            where
+                I: IntoIterator,
+                I::Item: IntoTerm,
+            {
+                let (len, body) = crate::codec::encode_buffered(iter, self.options)?;
+                self.writer.write_u8(VERSION).await?;
+                if len > 0 {
+                    self.writer.write_u8(LIST_EXT).await?;
+                    self.writer.write_u32(len as u32).await?;
+                    self.write_chunked(&body).await?;
+                }
+                self.encode_nil().await
+            }
+            /// Encodes the `(key, value)` pairs of `iter` as a map without collecting them into a
+            /// [`Map`] (see [`Encoder::encode_map_from_iter`]).
+            pub async fn encode_map_from_iter<I, K, V>(&mut self, iter: I) -> EncodeResult
+            where
+                I: IntoIterator<Item = (K, V)>,
+                I::IntoIter: ExactSizeIterator,
+                K: IntoTerm,
+                V: IntoTerm,
+            {
+                let iter = iter.into_iter();
+                if self.options.deterministic || !self.options.map_tag {
+                    let map = Map::from(
+                        iter.map(|(k, v)| (k.into_term(), v.into_term()))
+                            .collect::<HashMap<_, _>>(),
+                    );
+                    self.writer.write_u8(VERSION).await?;
+                    return self.encode_map(&map).await;
+                }
+                let len = iter.len();
+                self.writer.write_u8(VERSION).await?;
+                self.writer.write_u8(MAP_EXT).await?;
+                self.writer.write_u32(len as u32).await?;
+                for entry in aux::exact(iter, len) {
+                    let (_, (k, v)) = entry?;
+                    let k = k.into_term();
+                    self.encode_term(&k)
+                        .await
+                        .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+                    self.encode_term(&v.into_term())
+                        .await
+                        .map_err(|e| e.within_with(|| EncodePathSegment::MapValue { key: k.to_string() }))?;
+                }
+                Ok(())
+            }
+
+            #[$recursion]
+            async fn encode_term(&mut self, term: &Term) -> EncodeResult {
+                match *term {
+                    Term::Atom(ref x) => self.encode_atom(x).await,
+                    Term::FixInteger(ref x) => self.encode_fix_integer(x).await,
+                    Term::BigInteger(ref x) => self.encode_big_integer(x).await,
+                    Term::Float(ref x) => self.encode_float(x).await,
+                    Term::Pid(ref x) => self.encode_pid(x).await,
+                    Term::Port(ref x) => self.encode_port(x).await,
+                    Term::Reference(ref x) => self.encode_reference(x).await,
+                    Term::ExternalFun(ref x) => self.encode_external_fun(x).await,
+                    Term::InternalFun(ref x) => self.encode_internal_fun(x).await,
+                    Term::Binary(ref x) => self.encode_binary(x).await,
+                    Term::BitBinary(ref x) => self.encode_bit_binary(x).await,
+                    Term::List(ref x) => self.encode_list(x).await,
+                    Term::ImproperList(ref x) => self.encode_improper_list(x).await,
+                    Term::Tuple(ref x) => self.encode_tuple(x).await,
+                    Term::Map(ref x) => self.encode_map(x).await,
+                    Term::ByteList(ref x) => self.encode_byte_list(x.bytes.as_slice()).await,
+                    Term::Raw(ref x) => self.encode_raw(x).await,
+                }
+            }
+            async fn encode_raw(&mut self, x: &Raw) -> EncodeResult {
+                self.writer.write_all(&x.bytes).await?;
+                Ok(())
+            }
+            async fn encode_nil(&mut self) -> EncodeResult {
+                self.writer.write_u8(NIL_EXT).await?;
+                Ok(())
+            }
+            async fn encode_list(&mut self, x: &List) -> EncodeResult {
+                let to_byte = |e: &Term| {
+                    e.try_as_ref()
+                        .and_then(|&FixInteger { value: i }| u8::try_from(i).ok())
+                };
+                if self.options.string_ext
+                    && !x.elements.is_empty()
+                    && x.elements.len() <= u16::MAX as usize
+                    && x.elements.iter().all(|e| to_byte(e).is_some())
+                {
+                    self.writer.write_u8(STRING_EXT).await?;
+                    self.writer
+                        .write_u16(x.elements.len() as u16).await?;
+                    for b in x.elements.iter().map(|e| to_byte(e).unwrap()) {
+                        self.writer.write_u8(b).await?;
+                    }
+                } else {
+                    if !x.is_nil() {
+                        self.writer.write_u8(LIST_EXT).await?;
+                        self.writer
+                            .write_u32(x.elements.len() as u32).await?;
+                        for (i, e) in x.elements.iter().enumerate() {
+                            self.encode_term(e)
+                                .await
+                                .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
+                        }
+                    }
+                    self.encode_nil().await?;
+                }
+                Ok(())
+            }
+            async fn encode_improper_list(&mut self, x: &ImproperList) -> EncodeResult {
                 self.writer.write_u8(LIST_EXT).await?;
                 self.writer
                     .write_u32(x.elements.len() as u32).await?;
-                for e in &x.elements {
-                    self.encode_term(e).await?;
+                for (i, e) in x.elements.iter().enumerate() {
+                    self.encode_term(e)
+                        .await
+                        .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
                 }
+                self.encode_term(&x.last)
+                    .await
+                    .map_err(|e| e.within(EncodePathSegment::ListTail))?;
+                Ok(())
             }
-            self.encode_nil().await?;
-        }
-        Ok(())
-    }
-    async fn encode_improper_list(&mut self, x: &ImproperList) -> EncodeResult {
-        self.writer.write_u8(LIST_EXT).await?;
-        self.writer
-            .write_u32(x.elements.len() as u32).await?;
-        for e in &x.elements {
-            self.encode_term(e).await?;
-        }
-        self.encode_term(&x.last).await?;
-        Ok(())
-    }
-    async fn encode_tuple(&mut self, x: &Tuple) -> EncodeResult {
-        if x.elements.len() < 0x100 {
-            self.writer.write_u8(SMALL_TUPLE_EXT).await?;
-            self.writer.write_u8(x.elements.len() as u8).await?;
-        } else {
-            self.writer.write_u8(LARGE_TUPLE_EXT).await?;
-            self.writer
-                .write_u32(x.elements.len() as u32).await?;
-        }
-        for e in &x.elements {
-            self.encode_term(e).await?;
-        }
-        Ok(())
-    }
-    async fn encode_map(&mut self, x: &Map) -> EncodeResult {
-        self.writer.write_u8(MAP_EXT).await?;
-        self.writer.write_u32(x.map.len() as u32).await?;
-        for (k, v) in x.map.iter() {
-            self.encode_term(k).await?;
-            self.encode_term(v).await?;
-        }
-        Ok(())
-    }
-    async fn encode_byte_list(&mut self, x: &[u8]) -> EncodeResult{
-        self.writer.write_u8(STRING_EXT).await?;
-        self.writer.write_u16(x.len() as u16).await?;
-        self.writer.write_all(x).await?;
-        
-        Ok(())
-    }
-    async fn encode_binary(&mut self, x: &Binary) -> EncodeResult {
-        self.writer.write_u8(BINARY_EXT).await?;
-        self.writer.write_u32(x.bytes.len() as u32).await?;
-        self.writer.write_all(&x.bytes).await?;
-        Ok(())
-    }
-    async fn encode_bit_binary(&mut self, x: &BitBinary) -> EncodeResult {
-        self.writer.write_u8(BIT_BINARY_EXT).await?;
-        self.writer.write_u32(x.bytes.len() as u32).await?;
-        self.writer.write_u8(x.tail_bits_size).await?;
-        if !x.bytes.is_empty() {
-            self.writer.write_all(&x.bytes[0..x.bytes.len() - 1]).await?;
-            self.writer
-                .write_u8(x.bytes[x.bytes.len() - 1] << (8 - x.tail_bits_size)).await?;
-        }
-        Ok(())
-    }
-    async fn encode_float(&mut self, x: &Float) -> EncodeResult {
-        self.writer.write_u8(NEW_FLOAT_EXT).await?;
-        self.writer.write_f64(x.value).await?;
-        Ok(())
-    }
-    async fn encode_atom(&mut self, x: &Atom) -> EncodeResult {
-        if x.name.len() > 0xFFFF {
-            return Err(EncodeError::TooLongAtomName(x.clone()));
-        }
+            async fn encode_tuple(&mut self, x: &Tuple) -> EncodeResult {
+                if x.elements.len() < 0x100 {
+                    self.writer.write_u8(SMALL_TUPLE_EXT).await?;
+                    self.writer.write_u8(x.elements.len() as u8).await?;
+                } else {
+                    self.writer.write_u8(LARGE_TUPLE_EXT).await?;
+                    self.writer
+                        .write_u32(x.elements.len() as u32).await?;
+                }
+                for (i, e) in x.elements.iter().enumerate() {
+                    self.encode_term(e)
+                        .await
+                        .map_err(|e| e.within(EncodePathSegment::TupleElement(i)))?;
+                }
+                Ok(())
+            }
+            async fn encode_map(&mut self, x: &Map) -> EncodeResult {
+                if !self.options.map_tag {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(x.clone()),
+                        DistFlags::MAP_TAG,
+                    ));
+                }
+                self.writer.write_u8(MAP_EXT).await?;
+                self.writer.write_u32(x.map.len() as u32).await?;
+                for (k, v) in canonical::map_entries(x, self.options.deterministic) {
+                    self.encode_term(k)
+                        .await
+                        .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+                    self.encode_term(v)
+                        .await
+                        .map_err(|e| e.within_with(|| EncodePathSegment::MapValue { key: k.to_string() }))?;
+                }
+                Ok(())
+            }
+            async fn encode_byte_list(&mut self, x: &[u8]) -> EncodeResult{
+                if !self.options.string_ext {
+                    if !x.is_empty() {
+                        self.writer.write_u8(LIST_EXT).await?;
+                        self.writer.write_u32(x.len() as u32).await?;
+                        for &b in x {
+                            self.writer.write_u8(SMALL_INTEGER_EXT).await?;
+                            self.writer.write_u8(b).await?;
+                        }
+                    }
+                    return self.encode_nil().await;
+                }
+                self.writer.write_u8(STRING_EXT).await?;
+                self.writer.write_u16(x.len() as u16).await?;
+                self.writer.write_all(x).await?;
 
-        let is_ascii = x.name.as_bytes().iter().all(|&c| c < 0x80);
-        if is_ascii {
-            self.writer.write_u8(ATOM_EXT).await?;
-        } else {
-            self.writer.write_u8(ATOM_UTF8_EXT).await?;
-        }
-        self.writer.write_u16(x.name.len() as u16).await?;
-        self.writer.write_all(x.name.as_bytes()).await?;
-        Ok(())
-    }
-    async fn encode_fix_integer(&mut self, x: &FixInteger) -> EncodeResult {
-        if 0 <= x.value && x.value <= i32::from(std::u8::MAX) {
-            self.writer.write_u8(SMALL_INTEGER_EXT).await?;
-            self.writer.write_u8(x.value as u8).await?;
-        } else {
-            self.writer.write_u8(INTEGER_EXT).await?;
-            self.writer.write_i32(x.value as i32).await?;
-        }
-        Ok(())
-    }
-    async fn encode_big_integer(&mut self, x: &BigInteger) -> EncodeResult {
-        let (sign, bytes) = x.value.to_bytes_le();
+                Ok(())
+            }
+            async fn encode_binary(&mut self, x: &Binary) -> EncodeResult {
+                self.writer.write_u8(BINARY_EXT).await?;
+                self.writer.write_u32(x.bytes.len() as u32).await?;
+                self.write_chunked(&x.bytes).await
+            }
+            /// Writes `bytes` in chunks of at most `chunk_size` bytes, yielding to the executor between them
+            /// so that a large binary does not monopolize it when the writer is always ready.
+            async fn write_chunked(&mut self, bytes: &[u8]) -> EncodeResult {
+                for (i, chunk) in bytes.chunks(self.options.chunk_size.max(1)).enumerate() {
+                    if i > 0 {
+                        YieldNow(false).await;
+                    }
+                    self.writer.write_all(chunk).await?;
+                }
+                Ok(())
+            }
+            async fn encode_bit_binary(&mut self, x: &BitBinary) -> EncodeResult {
+                if !self.options.bit_binaries {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(x.clone()),
+                        DistFlags::BIT_BINARIES,
+                    ));
+                }
+                self.writer.write_u8(BIT_BINARY_EXT).await?;
+                self.writer.write_u32(x.bytes.len() as u32).await?;
+                self.writer.write_u8(x.tail_bits_size).await?;
+                if !x.bytes.is_empty() {
+                    self.write_chunked(&x.bytes[0..x.bytes.len() - 1]).await?;
+                    self.writer
+                        .write_u8(x.bytes[x.bytes.len() - 1] << (8 - x.tail_bits_size)).await?;
+                }
+                Ok(())
+            }
+            async fn encode_float(&mut self, x: &Float) -> EncodeResult {
+                if self.options.new_floats {
+                    self.writer.write_u8(NEW_FLOAT_EXT).await?;
+                    self.writer.write_f64(x.value).await?;
+                } else {
+                    let bytes = aux::float_ext_bytes(x.value)
+                        .ok_or_else(|| EncodeError::NonFiniteFloat(x.clone(), EncodePath::default()))?;
+                    self.writer.write_u8(FLOAT_EXT).await?;
+                    self.writer.write_all(&bytes).await?;
+                }
+                Ok(())
+            }
+            async fn encode_atom(&mut self, x: &Atom) -> EncodeResult {
+                if let Some(&index) = self.atom_cache_refs.get(x) {
+                    self.writer.write_u8(ATOM_CACHE_REF).await?;
+                    self.writer.write_u8(index).await?;
+                    return Ok(());
+                }
+                if x.name.len() > 0xFFFF {
+                    return Err(EncodeError::TooLongAtomName(
+                        x.clone(),
+                        EncodePath::default(),
+                    ));
+                }
 
-        if bytes.len() <= std::u8::MAX as usize {
-            self.writer.write_u8(SMALL_BIG_EXT).await?;
-            self.writer.write_u8(bytes.len() as u8).await?;
-        } else if bytes.len() <= std::u32::MAX as usize {
-            self.writer.write_u8(LARGE_BIG_EXT).await?;
-            self.writer.write_u32(bytes.len() as u32).await?;
-        } else {
-            return Err(EncodeError::TooLargeInteger(x.clone()));
-        }
+                let is_ascii = x.name.as_bytes().iter().all(|&c| c < 0x80);
+                if is_ascii {
+                    self.writer.write_u8(ATOM_EXT).await?;
+                } else if self.options.utf8_atoms {
+                    self.writer.write_u8(ATOM_UTF8_EXT).await?;
+                } else {
+                    let latin1 = aux::to_latin1(&x.name).ok_or_else(|| {
+                        aux::unsupported_by_peer(Term::from(x.clone()), DistFlags::UTF8_ATOMS)
+                    })?;
+                    self.writer.write_u8(ATOM_EXT).await?;
+                    self.writer.write_u16(latin1.len() as u16).await?;
+                    self.writer.write_all(&latin1).await?;
+                    return Ok(());
+                }
+                self.writer.write_u16(x.name.len() as u16).await?;
+                self.writer.write_all(x.name.as_bytes()).await?;
+                Ok(())
+            }
+            async fn encode_fix_integer(&mut self, x: &FixInteger) -> EncodeResult {
+                if 0 <= x.value && x.value <= i32::from(u8::MAX) {
+                    self.writer.write_u8(SMALL_INTEGER_EXT).await?;
+                    self.writer.write_u8(x.value as u8).await?;
+                } else {
+                    self.writer.write_u8(INTEGER_EXT).await?;
+                    self.writer.write_i32(x.value).await?;
+                }
+                Ok(())
+            }
+            async fn encode_big_integer(&mut self, x: &BigInteger) -> EncodeResult {
+                let (sign, bytes) = x.value.to_bytes_le();
 
-        self.writer.write_u8(aux::sign_to_byte(sign)).await?;
-        self.writer.write_all(&bytes).await?;
-        Ok(())
-    }
-    async fn encode_pid(&mut self, x: &Pid) -> EncodeResult {
-        self.writer.write_u8(NEW_PID_EXT).await?;
-        self.encode_atom(&x.node).await?;
-        self.writer.write_u32(x.id).await?;
-        self.writer.write_u32(x.serial).await?;
-        self.writer.write_u32(x.creation).await?;
-        Ok(())
-    }
-    async fn encode_port(&mut self, x: &Port) -> EncodeResult {
-        if (x.id >> 32) & 0xFFFFFFFF == 0 {
-            self.writer.write_u8(NEW_PORT_EXT).await?;
-            self.encode_atom(&x.node).await?;
-            self.writer.write_u32(x.id as u32).await?;
-            self.writer.write_u32(x.creation).await?;
-        } else {
-            self.writer.write_u8(V4_PORT_EXT).await?;
-            self.encode_atom(&x.node).await?;
-            self.writer.write_u64(x.id).await?;
-            self.writer.write_u32(x.creation).await?;
-        }
-        Ok(())
-    }
-    async fn encode_reference(&mut self, x: &Reference) -> EncodeResult {
-        self.writer.write_u8(NEWER_REFERENCE_EXT).await?;
-        if x.id.len() > std::u16::MAX as usize {
-            return Err(EncodeError::TooLargeReferenceId(x.clone()));
-        }
-        self.writer.write_u16(x.id.len() as u16).await?;
-        self.encode_atom(&x.node).await?;
-        self.writer.write_u32(x.creation).await?;
-        for n in &x.id {
-            self.writer.write_u32(*n).await?;
-        }
-        Ok(())
-    }
-    async fn encode_external_fun(&mut self, x: &ExternalFun) -> EncodeResult {
-        self.writer.write_u8(EXPORT_EXT).await?;
-        self.encode_atom(&x.module).await?;
-        self.encode_atom(&x.function).await?;
-        self.encode_fix_integer(&FixInteger::from(i32::from(x.arity))).await?;
-        Ok(())
-    }
-    async fn encode_internal_fun(&mut self, x: &InternalFun) -> EncodeResult {
-        match *x {
-            InternalFun::Old {
-                ref module,
-                ref pid,
-                ref free_vars,
-                index,
-                uniq,
-            } => {
-                self.writer.write_u8(FUN_EXT).await?;
-                self.writer.write_u32(free_vars.len() as u32).await?;
-                self.encode_pid(pid).await?;
-                self.encode_atom(module).await?;
-                self.encode_fix_integer(&FixInteger::from(index)).await?;
-                self.encode_fix_integer(&FixInteger::from(uniq)).await?;
-                for v in free_vars {
-                    self.encode_term(v).await?;
-                }
-            }
-            InternalFun::New {
-                ref module,
-                arity,
-                ref pid,
-                ref free_vars,
-                index,
-                ref uniq,
-                old_index,
-                old_uniq,
-            } => {
-                self.writer.write_u8(NEW_FUN_EXT).await?;
+                if bytes.len() <= u8::MAX as usize {
+                    self.writer.write_u8(SMALL_BIG_EXT).await?;
+                    self.writer.write_u8(bytes.len() as u8).await?;
+                } else if bytes.len() <= u32::MAX as usize {
+                    self.writer.write_u8(LARGE_BIG_EXT).await?;
+                    self.writer.write_u32(bytes.len() as u32).await?;
+                } else {
+                    return Err(EncodeError::TooLargeInteger(
+                        x.clone(),
+                        EncodePath::default(),
+                    ));
+                }
 
-                let mut buf = Vec::new();
-                {
-                    let mut tmp = Encoder::new(&mut buf);
-                    WriteBytesExt::write_u8(&mut tmp.writer, arity);
-                    AsyncWriteExt::write_all(&mut tmp.writer, uniq);
-                    WriteBytesExt::write_u32::<BigEndian>(&mut tmp.writer, index);
-                    WriteBytesExt::write_u32::<BigEndian>(&mut tmp.writer, free_vars.len() as u32);
-                    tmp.encode_atom(module);
-                    tmp.encode_fix_integer(&FixInteger::from(old_index));
-                    tmp.encode_fix_integer(&FixInteger::from(old_uniq));
-                    tmp.encode_pid(pid);
-                    for v in free_vars {
-                        tmp.encode_term(v);
+                self.writer.write_u8(aux::sign_to_byte(sign)).await?;
+                self.write_chunked(&bytes).await
+            }
+            async fn encode_pid(&mut self, x: &Pid) -> EncodeResult {
+                if !self.options.v4_nc {
+                    aux::check_range(aux::PID_ID_RANGE, u64::from(x.id))?;
+                    aux::check_range(aux::PID_SERIAL_RANGE, u64::from(x.serial))?;
+                }
+                if !self.options.big_creation {
+                    let creation = aux::small_creation(x.creation)?;
+                    self.writer.write_u8(PID_EXT).await?;
+                    self.encode_atom(&x.node).await?;
+                    self.writer.write_u32(x.id).await?;
+                    self.writer.write_u32(x.serial).await?;
+                    self.writer.write_u8(creation).await?;
+                    return Ok(());
+                }
+                self.writer.write_u8(NEW_PID_EXT).await?;
+                self.encode_atom(&x.node).await?;
+                self.writer.write_u32(x.id).await?;
+                self.writer.write_u32(x.serial).await?;
+                self.writer.write_u32(x.creation).await?;
+                Ok(())
+            }
+            async fn encode_port(&mut self, x: &Port) -> EncodeResult {
+                if (x.id >> 32) & 0xFFFFFFFF == 0 {
+                    if !self.options.v4_nc {
+                        aux::check_range(aux::PORT_ID_RANGE, x.id)?;
+                    }
+                    if !self.options.big_creation {
+                        let creation = aux::small_creation(x.creation)?;
+                        self.writer.write_u8(PORT_EXT).await?;
+                        self.encode_atom(&x.node).await?;
+                        self.writer.write_u32(x.id as u32).await?;
+                        self.writer.write_u8(creation).await?;
+                        return Ok(());
+                    }
+                    self.writer.write_u8(NEW_PORT_EXT).await?;
+                    self.encode_atom(&x.node).await?;
+                    self.writer.write_u32(x.id as u32).await?;
+                    self.writer.write_u32(x.creation).await?;
+                } else if !self.options.v4_nc {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(x.clone()),
+                        DistFlags::V4_NC,
+                    ));
+                } else {
+                    self.writer.write_u8(V4_PORT_EXT).await?;
+                    self.encode_atom(&x.node).await?;
+                    self.writer.write_u64(x.id).await?;
+                    self.writer.write_u32(x.creation).await?;
+                }
+                Ok(())
+            }
+            async fn encode_reference(&mut self, x: &Reference) -> EncodeResult {
+                if x.id.len() > u16::MAX as usize {
+                    return Err(EncodeError::TooLargeReferenceId(
+                        x.clone(),
+                        EncodePath::default(),
+                    ));
+                }
+                if !self.options.v4_nc {
+                    aux::check_range(aux::REFERENCE_LEN_RANGE, x.id.len() as u64)?;
+                    if let Some(&first) = x.id.first() {
+                        aux::check_range(aux::REFERENCE_WORD_RANGE, u64::from(first))?;
+                    }
+                }
+                if !self.options.big_creation {
+                    let creation = aux::small_creation(x.creation)?;
+                    self.writer.write_u8(NEW_REFERENCE_EXT).await?;
+                    self.writer.write_u16(x.id.len() as u16).await?;
+                    self.encode_atom(&x.node).await?;
+                    self.writer.write_u8(creation).await?;
+                    for n in &x.id {
+                        self.writer.write_u32(*n).await?;
+                    }
+                    return Ok(());
+                }
+                self.writer.write_u8(NEWER_REFERENCE_EXT).await?;
+                self.writer.write_u16(x.id.len() as u16).await?;
+                self.encode_atom(&x.node).await?;
+                self.writer.write_u32(x.creation).await?;
+                for n in &x.id {
+                    self.writer.write_u32(*n).await?;
+                }
+                Ok(())
+            }
+            async fn encode_external_fun(&mut self, x: &ExternalFun) -> EncodeResult {
+                if !self.options.export_ptr_tag {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(x.clone()),
+                        DistFlags::EXPORT_PTR_TAG,
+                    ));
+                }
+                self.writer.write_u8(EXPORT_EXT).await?;
+                self.encode_atom(&x.module).await?;
+                self.encode_atom(&x.function).await?;
+                self.encode_fix_integer(&FixInteger::from(i32::from(x.arity))).await?;
+                Ok(())
+            }
+            async fn encode_internal_fun(&mut self, x: &InternalFun) -> EncodeResult {
+                match *x {
+                    InternalFun::Old {
+                        ref module,
+                        ref pid,
+                        ref free_vars,
+                        index,
+                        uniq,
+                    } => {
+                        self.writer.write_u8(FUN_EXT).await?;
+                        self.writer.write_u32(free_vars.len() as u32).await?;
+                        self.encode_pid(pid).await?;
+                        self.encode_atom(module).await?;
+                        self.encode_fix_integer(&FixInteger::from(index)).await?;
+                        self.encode_fix_integer(&FixInteger::from(uniq)).await?;
+                        for (i, v) in free_vars.iter().enumerate() {
+                            self.encode_term(v)
+                                .await
+                                .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
+                        }
+                    }
+                    InternalFun::New {
+                        ref module,
+                        arity,
+                        ref pid,
+                        ref free_vars,
+                        index,
+                        ref uniq,
+                        old_index,
+                        old_uniq,
+                    } => {
+                        if !self.options.new_fun_tags {
+                            return Err(aux::unsupported_by_peer(
+                                Term::from(x.clone()),
+                                DistFlags::NEW_FUN_TAGS,
+                            ));
+                        }
+                        let size = codec::new_fun_size(x, self.options, &mut self.atom_cache_refs)?;
+                        self.writer.write_u8(NEW_FUN_EXT).await?;
+                        self.writer.write_u32(size).await?;
+                        self.writer.write_u8(arity).await?;
+                        self.writer.write_all(uniq).await?;
+                        self.writer.write_u32(index).await?;
+                        self.writer.write_u32(free_vars.len() as u32).await?;
+                        self.encode_atom(module).await?;
+                        self.encode_fix_integer(&FixInteger::from(old_index))
+                            .await?;
+                        self.encode_fix_integer(&FixInteger::from(old_uniq)).await?;
+                        self.encode_pid(pid).await?;
+                        for (i, v) in free_vars.iter().enumerate() {
+                            self.encode_term(v)
+                                .await
+                                .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
+                        }
                     }
                 }
-                self.writer.write_u32(4 + buf.len() as u32).await?;
-                self.writer.write_all(&buf).await?;
+                Ok(())
             }
         }
-        Ok(())
+    };
+}
+
+async_codec! {
+    /// Decoder of terms from an [`AsyncRead`](tokio::io::AsyncRead) which is `Send`, whose
+    /// futures are `Send`.
+    ///
+    /// As with [`Decoder`], the reader can be borrowed (e.g., the `ReadHalf` of a split
+    /// stream), and then borrowed again for the next term:
+    ///
+    /// ```
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// use eetf::{AsyncDecoder, Term};
+    ///
+    /// let (mut client, server) = tokio::io::duplex(64);
+    /// let (mut reader, _writer) = tokio::io::split(server);
+    /// Term::from(1).encode_async(&mut client).await.unwrap();
+    /// Term::from(2).encode_async(&mut client).await.unwrap();
+    /// assert_eq!(AsyncDecoder::new(&mut reader).decode().await.unwrap(), Term::from(1));
+    /// assert_eq!(AsyncDecoder::new(&mut reader).decode().await.unwrap(), Term::from(2));
+    /// # });
+    /// ```
+    ///
+    /// A compressed term is read by blocks, so the decoder may read past its end. It keeps
+    /// these bytes for [`decode_next`](AsyncDecoder::decode_next), and they are lost with the
+    /// decoder, so a stream which may hold compressed terms before other terms must be decoded
+    /// by a single decoder.
+    AsyncDecoder,
+    /// Encoder of terms into an [`AsyncWrite`](tokio::io::AsyncWrite) which is `Send`, whose
+    /// futures are `Send`, and which can be borrowed as the reader of an [`AsyncDecoder`].
+    AsyncEncoder,
+    DecodeFuture,
+    async_recursion,
+    Send
+}
+async_codec! {
+    /// [`AsyncDecoder`] for readers which are not `Send` (e.g., on a single-threaded runtime,
+    /// within a `tokio::task::LocalSet`).
+    LocalAsyncDecoder,
+    /// [`AsyncEncoder`] for writers which are not `Send` (e.g., on a single-threaded runtime,
+    /// within a `tokio::task::LocalSet`).
+    LocalAsyncEncoder,
+    LocalDecodeFuture,
+    async_recursion(?Send),
+}
+
+/// Reads `size` bytes into `buf` (replacing its contents), growing it as the bytes arrive
+/// once there are more than [`MAX_PREALLOCATED_LEN`].
+async fn read_exact_bounded<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    size: usize,
+) -> std::io::Result<()>
+where
+    R: tokio::io::AsyncRead + std::marker::Unpin,
+{
+    let mut len = size.min(MAX_PREALLOCATED_LEN);
+    buf.clear();
+    // Exactly, so that the buffer of a binary is not reallocated when it becomes a `Bytes`.
+    buf.reserve_exact(len);
+    buf.resize(len, 0);
+    reader.read_exact(buf).await?;
+    while len < size {
+        let start = len;
+        len = size.min(len * 2);
+        buf.reserve_exact(len - start);
+        buf.resize(len, 0);
+        reader.read_exact(&mut buf[start..]).await?;
     }
+    Ok(())
 }
 
+/// Reads `size` bytes into `buf` (replacing its contents) by chunks of [`aux::CHUNK_LEN`] (see
+/// `codec::read_chunked`).
+async fn read_chunked<R>(reader: &mut R, buf: &mut Vec<u8>, size: usize) -> std::io::Result<()>
+where
+    R: tokio::io::AsyncRead + std::marker::Unpin,
+{
+    buf.clear();
+    while buf.len() < size {
+        let start = buf.len();
+        buf.resize(size.min(start + aux::CHUNK_LEN), 0);
+        reader.read_exact(&mut buf[start..]).await?;
+    }
+    Ok(())
+}
+
+/// Reads a name of `size` bytes of Latin-1 or UTF-8 into a string made of the scratch buffer
+/// `buf`, by chunks which are converted or validated as they arrive (see `codec::read_name`).
+async fn read_name<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    size: usize,
+    latin1: bool,
+) -> std::io::Result<String>
+where
+    R: tokio::io::AsyncRead + std::marker::Unpin,
+{
+    let mut name = aux::take_name_buf(buf);
+    let mut chunk = [0; aux::CHUNK_LEN];
+    let (mut carried, mut remaining) = (0, size);
+    while remaining > 0 {
+        let len = remaining.min(aux::CHUNK_LEN - carried);
+        reader
+            .read_exact(&mut chunk[carried..carried + len])
+            .await?;
+        remaining -= len;
+        carried =
+            aux::push_name_chunk(&mut name, &mut chunk, carried + len, remaining == 0, latin1)?;
+    }
+    Ok(name)
+}
+
+/// Number of terms which the async decoders decode between yielding to the executor by default.
+const DEFAULT_YIELD_INTERVAL: usize = 1024;
+
+/// Future which returns `Pending` once (after waking its task) and then completes.
+struct YieldNow(bool);
+impl std::future::Future for YieldNow {
+    type Output = ();
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        if self.0 {
+            return std::task::Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        std::task::Poll::Pending
+    }
+}
@@ -0,0 +1,128 @@
+//! Maps whose keys are atoms (see [`AtomMap`]).
+use super::*;
+
+/// Map whose keys are atoms, the typical shape of records and Elixir structs.
+///
+/// Unlike a [`Map`], it hashes only the names of its keys, and is looked up by `&str`. A
+/// [`Decoder`] builds it without an intermediate [`Map`] (see [`Decoder::decode_as`]).
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{AtomMap, Decoder, Term};
+///
+/// // #{id => 1}
+/// let bytes = [131, 116, 0, 0, 0, 1, 119, 2, b'i', b'd', 97, 1];
+/// let map: AtomMap = Decoder::new(&bytes[..]).decode_as().unwrap();
+/// assert_eq!(map.get("id"), Some(&Term::from(1)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AtomMap {
+    pub map: HashMap<Atom, Term>,
+}
+impl AtomMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Term> {
+        self.map.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Term> {
+        self.map.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Inserts `value` under the atom `key`, returning the value it replaces.
+    pub fn insert(&mut self, key: impl Into<Atom>, value: impl Into<Term>) -> Option<Term> {
+        self.map.insert(key.into(), value.into())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Term> {
+        self.map.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Atom, &Term)> {
+        self.map.iter()
+    }
+}
+impl From<HashMap<Atom, Term>> for AtomMap {
+    fn from(map: HashMap<Atom, Term>) -> Self {
+        AtomMap { map }
+    }
+}
+impl FromIterator<(Atom, Term)> for AtomMap {
+    fn from_iter<I: IntoIterator<Item = (Atom, Term)>>(iter: I) -> Self {
+        AtomMap {
+            map: iter.into_iter().collect(),
+        }
+    }
+}
+impl IntoIterator for AtomMap {
+    type Item = (Atom, Term);
+    type IntoIter = <HashMap<Atom, Term> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+impl TryFrom<Map> for AtomMap {
+    type Error = NonAtomKeyError;
+
+    /// Fails with the first key (in the order of the map) which is not an atom.
+    fn try_from(map: Map) -> Result<Self, Self::Error> {
+        let non_atom = map
+            .map
+            .keys()
+            .find(|k| !matches!(k, Term::Atom(_)))
+            .cloned();
+        if let Some(key) = non_atom {
+            return Err(NonAtomKeyError { key, map });
+        }
+        Ok(map
+            .map
+            .into_iter()
+            .map(|(k, v)| match k {
+                Term::Atom(k) => (k, v),
+                _ => unreachable!(),
+            })
+            .collect())
+    }
+}
+impl From<AtomMap> for Map {
+    fn from(map: AtomMap) -> Self {
+        Map {
+            map: map
+                .map
+                .into_iter()
+                .map(|(k, v)| (Term::Atom(k), v))
+                .collect(),
+        }
+    }
+}
+impl From<AtomMap> for Term {
+    fn from(map: AtomMap) -> Self {
+        Term::Map(Map::from(map))
+    }
+}
+
+/// Error of converting a [`Map`] with a key which is not an atom into an [`AtomMap`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("the map key {key} is not an atom")]
+pub struct NonAtomKeyError {
+    pub key: Term,
+    /// The map, as it was.
+    pub map: Map,
+}
@@ -0,0 +1,440 @@
+//! Interning of atom names.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::{AtomTable, Decoder, Term};
+//!
+//! let table = AtomTable::new();
+//! let bytes = [131, 108, 0, 0, 0, 2, 119, 1, b'a', 119, 1, b'a', 106];
+//! let term = Decoder::new(&bytes[..]).with_atom_table(table.clone()).decode().unwrap();
+//! assert_eq!(table.len(), 1);
+//!
+//! let Term::List(list) = term else { unreachable!() };
+//! let (Term::Atom(a), Term::Atom(b)) = (&list.elements[0], &list.elements[1]) else { unreachable!() };
+//! assert!(std::sync::Arc::ptr_eq(&a.name, &b.name));
+//! ```
+use super::*;
+use std::collections::HashSet;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Set of atom names shared by the atoms it makes.
+///
+/// Clones of a table refer to the same set, so one table can be given to several decoders
+/// (also on other threads, e.g., to the decoders of all the connections of a server). Names
+/// which are already in the table are looked up under a shared lock.
+///
+/// A table made by [`AtomTable::with_max_len`] holds a bounded number of names, so that peers
+/// sending ever new atoms cannot make it grow without bound.
+#[derive(Debug, Clone, Default)]
+pub struct AtomTable {
+    names: Arc<RwLock<Names>>,
+    max_len: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Names {
+    /// The names, and whether each was looked up since the last eviction.
+    set: HashMap<Arc<str>, AtomicBool>,
+    /// The names which are never evicted (see [`AtomTable::pin`]).
+    pinned: HashSet<Arc<str>>,
+    bytes: usize,
+}
+
+impl AtomTable {
+    /// Makes an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes an empty table which holds at most `max_len` names.
+    ///
+    /// Once the table is full, adding a name evicts the names which were not looked up since the
+    /// previous eviction. If there are none, the new name is not added, and its atoms do not share
+    /// it (which only costs memory, as atoms are compared by name).
+    pub fn with_max_len(max_len: usize) -> Self {
+        AtomTable {
+            names: Arc::default(),
+            max_len: Some(max_len),
+        }
+    }
+
+    /// Returns an atom named `name`, which shares its name with the other atoms named so.
+    pub fn intern(&self, name: &str) -> Atom {
+        if let Some(atom) = self.read().get(name) {
+            return atom;
+        }
+        let mut names = self.write();
+        // Another thread may have added the name since the lookup.
+        if let Some(atom) = names.get(name) {
+            return atom;
+        }
+        if self.max_len.is_some_and(|max| names.set.len() >= max) {
+            names.evict();
+            if self.max_len.is_some_and(|max| names.set.len() >= max) {
+                return Atom::from(name);
+            }
+        }
+        let name: Arc<str> = Arc::from(name);
+        names.bytes += name.len();
+        names.set.insert(Arc::clone(&name), AtomicBool::new(false));
+        Atom::from(name)
+    }
+
+    /// Returns the atom named `name` if the name is in the table, without adding it.
+    pub fn get(&self, name: &str) -> Option<Atom> {
+        self.read().get(name)
+    }
+
+    /// Returns an atom named `name` like [`AtomTable::intern`], and keeps the name in the table
+    /// even if it is bounded, so that later atoms named so share it too.
+    pub fn pin(&self, name: &str) -> Atom {
+        let mut names = self.write();
+        let name = match names.set.get_key_value(name) {
+            Some((name, _)) => Arc::clone(name),
+            None => {
+                let name: Arc<str> = Arc::from(name);
+                names.bytes += name.len();
+                names.set.insert(Arc::clone(&name), AtomicBool::new(false));
+                name
+            }
+        };
+        names.pinned.insert(Arc::clone(&name));
+        Atom::from(name)
+    }
+
+    /// Returns the number of distinct names in the table.
+    pub fn len(&self) -> usize {
+        self.read().set.len()
+    }
+
+    /// Returns `true` if the table has no names.
+    pub fn is_empty(&self) -> bool {
+        self.read().set.is_empty()
+    }
+
+    /// Returns the total length of the names in the table, in bytes.
+    pub fn bytes(&self) -> usize {
+        self.read().bytes
+    }
+
+    // The set is never left half-updated, so a poisoned lock is still usable.
+    fn read(&self) -> RwLockReadGuard<'_, Names> {
+        self.names.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Names> {
+        self.names.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Makes the atom of a decoder with the atom table `table`, which only looks the name up with
+/// the [`safe`](DecoderOptions::safe) option.
+pub(crate) fn make_atom(
+    table: Option<&AtomTable>,
+    name: &str,
+    safe: bool,
+) -> Result<Atom, DecodeError> {
+    match table {
+        Some(table) if safe => table.get(name),
+        Some(table) => Some(table.intern(name)),
+        None if safe => None,
+        None => Some(Atom::from(name)),
+    }
+    .ok_or_else(|| DecodeError::UnknownAtom {
+        name: name.to_owned(),
+    })
+}
+
+impl Names {
+    fn get(&self, name: &str) -> Option<Atom> {
+        let (name, used) = self.set.get_key_value(name)?;
+        used.store(true, Ordering::Relaxed);
+        Some(Atom::from(Arc::clone(name)))
+    }
+
+    /// Removes the names which were not looked up since the last eviction.
+    fn evict(&mut self) {
+        let bytes = &mut self.bytes;
+        let pinned = &self.pinned;
+        self.set.retain(|name, used| {
+            let keep = used.swap(false, Ordering::Relaxed) || pinned.contains(name);
+            if !keep {
+                *bytes -= name.len();
+            }
+            keep
+        });
+    }
+}
+
+/// Numbering of a fixed set of atom names, so that atoms can be dispatched on by an integer
+/// instead of comparing their names.
+///
+/// The names are pinned in the table of the set, so the atoms decoded with that table (see
+/// [`Decoder::with_atom_table`]) share them, and [`AtomSet::id`] finds theirs by the address of
+/// their name: the names are hashed once when decoded, not at every dispatch.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{AtomSet, AtomTable, Decoder, Term};
+///
+/// const OK: usize = 0;
+/// const ERROR: usize = 1;
+/// let set = AtomSet::new(&AtomTable::new(), ["ok", "error"]);
+///
+/// let bytes = [131, 119, 5, b'e', b'r', b'r', b'o', b'r'];
+/// let term = Decoder::new(&bytes[..]).with_atom_table(set.table().clone()).decode().unwrap();
+/// match term.atom_id(&set) {
+///     Some(OK) => unreachable!(),
+///     Some(ERROR) => {}
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AtomSet {
+    table: AtomTable,
+    atoms: Vec<Atom>,
+    /// The ids by the addresses of the names in the table.
+    by_address: HashMap<usize, usize, BuildHasherDefault<AddressHasher>>,
+    by_name: HashMap<Arc<str>, usize>,
+}
+
+impl AtomSet {
+    /// Makes a set numbering `names` from zero, in order, which pins them in `table`.
+    ///
+    /// A name which is given more than once keeps its first id.
+    pub fn new<I>(table: &AtomTable, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut set = AtomSet {
+            table: table.clone(),
+            atoms: Vec::new(),
+            by_address: HashMap::default(),
+            by_name: HashMap::new(),
+        };
+        for name in names {
+            let atom = table.pin(name.as_ref());
+            let id = set.atoms.len();
+            if set.by_name.contains_key(&atom.name) {
+                continue;
+            }
+            set.by_address.insert(address(&atom), id);
+            set.by_name.insert(Arc::clone(&atom.name), id);
+            set.atoms.push(atom);
+        }
+        set
+    }
+
+    /// Returns the table of the set, to be given to the decoders.
+    pub fn table(&self) -> &AtomTable {
+        &self.table
+    }
+
+    /// Returns the id of `atom`, or `None` if it is not in the set.
+    ///
+    /// Atoms which do not share their name with the table (e.g., made by `Atom::from`) are
+    /// looked up by name instead.
+    pub fn id(&self, atom: &Atom) -> Option<usize> {
+        match self.by_address.get(&address(atom)) {
+            Some(id) => Some(*id),
+            None => self.by_name.get(&atom.name).copied(),
+        }
+    }
+
+    /// Returns the atom of `id`.
+    pub fn atom(&self, id: usize) -> Option<&Atom> {
+        self.atoms.get(id)
+    }
+
+    /// Returns the number of names in the set.
+    pub fn len(&self) -> usize {
+        self.atoms.len()
+    }
+
+    /// Returns `true` if the set has no names.
+    pub fn is_empty(&self) -> bool {
+        self.atoms.is_empty()
+    }
+}
+
+/// Names of the atoms made by [`Atom::from_static`], by the address and length of the static
+/// strings they were made from.
+type StaticNames = HashMap<(usize, usize), Arc<str>>;
+
+static STATIC_NAMES: OnceLock<RwLock<StaticNames>> = OnceLock::new();
+
+pub(crate) fn static_name(name: &'static str) -> Arc<str> {
+    let names = STATIC_NAMES.get_or_init(RwLock::default);
+    let key = (name.as_ptr() as usize, name.len());
+    if let Some(name) = names.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Arc::clone(name);
+    }
+    let mut names = names.write().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(names.entry(key).or_insert_with(|| Arc::from(name)))
+}
+
+/// Atom in a `static`, which is made on first use (see [`static_atoms!`](crate::static_atoms)).
+///
+/// It derefs to the [`Atom`], and compares equal to the atoms of the same name.
+pub struct StaticAtom {
+    name: &'static str,
+    atom: OnceLock<Atom>,
+}
+
+impl StaticAtom {
+    /// Makes a static atom named `name`.
+    pub const fn new(name: &'static str) -> Self {
+        StaticAtom {
+            name,
+            atom: OnceLock::new(),
+        }
+    }
+
+    /// Returns the atom.
+    pub fn get(&self) -> &Atom {
+        self.atom.get_or_init(|| Atom::from_static(self.name))
+    }
+}
+
+impl core::ops::Deref for StaticAtom {
+    type Target = Atom;
+    fn deref(&self) -> &Atom {
+        self.get()
+    }
+}
+
+impl fmt::Debug for StaticAtom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("StaticAtom").field(&self.name).finish()
+    }
+}
+
+impl fmt::Display for StaticAtom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl PartialEq<Atom> for StaticAtom {
+    fn eq(&self, other: &Atom) -> bool {
+        self.get() == other
+    }
+}
+
+impl PartialEq<StaticAtom> for Atom {
+    fn eq(&self, other: &StaticAtom) -> bool {
+        self == other.get()
+    }
+}
+
+impl From<&StaticAtom> for Atom {
+    fn from(atom: &StaticAtom) -> Self {
+        atom.get().clone()
+    }
+}
+
+impl From<&StaticAtom> for Term {
+    fn from(atom: &StaticAtom) -> Self {
+        Term::Atom(atom.get().clone())
+    }
+}
+
+/// Declares `static` [`StaticAtom`]s.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{static_atoms, Atom, Term};
+///
+/// static_atoms! {
+///     pub OK = "ok";
+///     ERROR = "error";
+/// }
+///
+/// assert_eq!(*OK, Atom::from("ok"));
+/// assert_eq!(Term::from(&ERROR), Term::from(Atom::from("error")));
+/// ```
+#[macro_export]
+macro_rules! static_atoms {
+    ($($vis:vis $name:ident = $value:expr;)*) => {
+        $(
+            $vis static $name: $crate::StaticAtom = $crate::StaticAtom::new($value);
+        )*
+    };
+}
+
+fn address(atom: &Atom) -> usize {
+    Arc::as_ptr(&atom.name) as *const u8 as usize
+}
+
+/// Hasher of addresses, which only need their bits mixed.
+#[derive(Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0.rotate_left(8) ^ u64::from(b)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        }
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.0 = (n as u64)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            .rotate_left(29);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_atoms_share_names() {
+        let table = AtomTable::new();
+        let a = table.intern("foo");
+        let b = table.clone().intern("foo");
+        assert!(Arc::ptr_eq(&a.name, &b.name));
+        assert_eq!(a, Atom::from("foo"));
+        assert_eq!(table.intern("bar").as_str(), "bar");
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.bytes(), 6);
+    }
+
+    #[test]
+    fn bounded_table_keeps_used_names() {
+        let table = AtomTable::with_max_len(100);
+        let ok = table.intern("ok");
+        for i in 0..10_000 {
+            table.intern(&format!("flood{}", i));
+            assert!(Arc::ptr_eq(&table.intern("ok").name, &ok.name));
+            assert!(table.len() <= 100);
+        }
+        let bytes: usize = table.read().set.keys().map(|n| n.len()).sum();
+        assert_eq!(table.bytes(), bytes);
+    }
+
+    #[test]
+    fn bounded_table_keeps_pinned_names() {
+        let table = AtomTable::with_max_len(100);
+        let set = AtomSet::new(&table, ["ok", "error", "ok"]);
+        assert_eq!(set.len(), 2);
+        for i in 0..10_000 {
+            table.intern(&format!("flood{}", i));
+        }
+        let ok = table.intern("ok");
+        assert!(Arc::ptr_eq(&ok.name, &set.atom(0).unwrap().name));
+        assert_eq!(set.id(&ok), Some(0));
+        assert_eq!(set.id(&Atom::from("error")), Some(1));
+        assert_eq!(set.id(&table.intern("flood1")), None);
+    }
+}
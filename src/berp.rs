@@ -0,0 +1,188 @@
+//! BERP framing (i.e., a 4 byte big-endian length followed by an encoded term).
+//!
+//! BERT-RPC and other Erlang-adjacent services exchange multiple BERPs per connection.
+//! Each frame must contain exactly one term.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::{berp, Atom, Term};
+//!
+//! let mut buf = Vec::new();
+//! berp::write_message(&mut buf, &Term::from(Atom::from("foo"))).unwrap();
+//! berp::write_message(&mut buf, &Term::from(1)).unwrap();
+//!
+//! let mut reader = &buf[..];
+//! assert_eq!(berp::read_message(&mut reader).unwrap(), Some(Term::from(Atom::from("foo"))));
+//! assert_eq!(berp::read_message(&mut reader).unwrap(), Some(Term::from(1)));
+//! assert_eq!(berp::read_message(&mut reader).unwrap(), None);
+//! ```
+use super::*;
+use crate::packet::{decode_frame, encode_frame, read_frame};
+use std::io::{Read, Write};
+
+/// The default maximum frame size of [`read_message`] and [`write_message`] (i.e., `u32::MAX`).
+pub const DEFAULT_MAX_FRAME_SIZE: usize = u32::MAX as usize;
+
+/// Reads a BERP, returning `None` if the reader ends at a frame boundary.
+pub fn read_message<R: Read>(reader: R) -> Result<Option<Term>, DecodeError> {
+    read_message_with_max(reader, DEFAULT_MAX_FRAME_SIZE)
+}
+
+/// Reads a BERP whose frame is at most `max` bytes long.
+pub fn read_message_with_max<R: Read>(
+    mut reader: R,
+    max: usize,
+) -> Result<Option<Term>, DecodeError> {
+    read_frame(&mut reader, 4, max)?
+        .map(|frame| decode_frame(&frame, DecoderOptions::default()))
+        .transpose()
+}
+
+/// Writes a BERP.
+pub fn write_message<W: Write>(writer: W, term: &Term) -> Result<(), EncodeError> {
+    write_message_with_max(writer, term, DEFAULT_MAX_FRAME_SIZE)
+}
+
+/// Writes a BERP whose frame is at most `max` bytes long.
+pub fn write_message_with_max<W: Write>(
+    mut writer: W,
+    term: &Term,
+    max: usize,
+) -> Result<(), EncodeError> {
+    let frame = encode_frame(
+        term,
+        4,
+        max.min(DEFAULT_MAX_FRAME_SIZE),
+        EncoderOptions::default(),
+    )?;
+    writer.write_all(&frame)?;
+    Ok(())
+}
+
+/// Reads a BERP from an async reader (see [`read_message_with_max`]).
+#[cfg(feature = "tokio-async")]
+pub async fn read_message_async<R>(reader: &mut R, max: usize) -> Result<Option<Term>, DecodeError>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut prefix = [0; 4];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]).await? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            n => read += n,
+        }
+    }
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > max {
+        return Err(DecodeError::TooLargeFrame { len, max });
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    decode_frame(&frame, DecoderOptions::default()).map(Some)
+}
+
+/// Writes a BERP to an async writer (see [`write_message_with_max`]).
+#[cfg(feature = "tokio-async")]
+pub async fn write_message_async<W>(
+    writer: &mut W,
+    term: &Term,
+    max: usize,
+) -> Result<(), EncodeError>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+
+    let frame = encode_frame(
+        term,
+        4,
+        max.min(DEFAULT_MAX_FRAME_SIZE),
+        EncoderOptions::default(),
+    )?;
+    writer.write_all(&frame).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &Term::from(Atom::from("foo"))).unwrap();
+        write_message(&mut buf, &Term::from(List::nil())).unwrap();
+        buf
+    }
+
+    #[test]
+    fn back_to_back_frames_work() {
+        let buf = frames();
+        assert_eq!(&buf[..4], [0, 0, 0, 7]);
+        let mut reader = &buf[..];
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(Term::from(Atom::from("foo")))
+        );
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(Term::from(List::nil()))
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        // trailing junk within the frame
+        let buf = [0, 0, 0, 4, 131, 97, 1, 0xFF];
+        assert!(matches!(
+            read_message(&buf[..]),
+            Err(DecodeError::TrailingBytes { count: 1 })
+        ));
+
+        // truncated frame
+        let buf = [0, 0, 0, 4, 131, 97];
+        assert!(matches!(read_message(&buf[..]), Err(DecodeError::Io(_))));
+
+        // too large frame
+        let buf = frames();
+        assert!(matches!(
+            read_message_with_max(&buf[..], 6),
+            Err(DecodeError::TooLargeFrame { len: 7, max: 6 })
+        ));
+        assert!(matches!(
+            write_message_with_max(Vec::new(), &Term::from(Atom::from("foo")), 6),
+            Err(EncodeError::TooLargeFrame { len: 7, max: 6 })
+        ));
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_frames_work() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut buf = Vec::new();
+            let term = Term::from(Atom::from("foo"));
+            write_message_async(&mut buf, &term, 100).await.unwrap();
+            assert_eq!(buf, frames()[..11]);
+            buf.extend_from_slice(&[0, 0, 0, 4, 131, 97, 1, 0]);
+
+            let mut reader = &buf[..];
+            assert_eq!(
+                read_message_async(&mut reader, 100).await.unwrap(),
+                Some(term)
+            );
+            assert!(matches!(
+                read_message_async(&mut reader, 100).await,
+                Err(DecodeError::TrailingBytes { count: 1 })
+            ));
+            assert_eq!(read_message_async(&mut reader, 100).await.unwrap(), None);
+        });
+    }
+}
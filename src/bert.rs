@@ -0,0 +1,279 @@
+//! BERT conventions.
+//!
+//! BERT represents values which ETF lacks as tuples tagged with the atom `bert`
+//! (e.g., `{bert, nil}` and `{bert, dict, [{Key, Value}]}`).
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::bert::BertValue;
+//! use eetf::{Atom, Term, Tuple};
+//!
+//! let term = BertValue::Bool(true).to_term();
+//! assert_eq!(term.to_string(), "{'bert','true'}");
+//! assert_eq!(BertValue::from_term(&term), BertValue::Bool(true));
+//!
+//! // Other terms are left untouched.
+//! let term = Term::from(Atom::from("true"));
+//! assert_eq!(BertValue::from_term(&term), BertValue::Term(term));
+//! ```
+//!
+//! # Reference
+//!
+//! - [BERT and BERT-RPC 1.0 Specification](https://bert-rpc.org/)
+use super::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BERT: &str = "bert";
+
+/// Value following the BERT conventions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BertValue {
+    /// `{bert, nil}`
+    Nil,
+
+    /// `{bert, true}` or `{bert, false}`
+    Bool(bool),
+
+    /// `{bert, dict, [{Key, Value}]}`
+    Dict(Vec<(Term, Term)>),
+
+    /// `{bert, time, MegaSeconds, Seconds, MicroSeconds}`
+    Time(SystemTime),
+
+    /// `{bert, regex, Source, Options}`
+    Regex { source: Vec<u8>, options: Vec<Term> },
+
+    /// Any other term.
+    Term(Term),
+}
+impl BertValue {
+    /// Recognizes the BERT conventions in `term`.
+    ///
+    /// Terms which are not (well-formed) BERT tuples become [`BertValue::Term`].
+    pub fn from_term(term: &Term) -> Self {
+        Self::try_from_bert_tuple(term).unwrap_or_else(|| BertValue::Term(term.clone()))
+    }
+
+    /// Converts the value to its term.
+    pub fn to_term(&self) -> Term {
+        let bert = || Term::from(Atom::from(BERT));
+        let atom = |name: &str| Term::from(Atom::from(name));
+        let elements = match self {
+            BertValue::Nil => vec![bert(), atom("nil")],
+            BertValue::Bool(b) => vec![bert(), atom(if *b { "true" } else { "false" })],
+            BertValue::Dict(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(k, v)| Term::from(Tuple::from(vec![k.clone(), v.clone()])))
+                    .collect::<Vec<_>>();
+                vec![bert(), atom("dict"), Term::from(List::from(entries))]
+            }
+            BertValue::Time(time) => {
+                let (mega, sec, micro) = to_timestamp(*time);
+                vec![
+                    bert(),
+                    atom("time"),
+                    Term::from(mega),
+                    Term::from(sec),
+                    Term::from(micro),
+                ]
+            }
+            BertValue::Regex { source, options } => vec![
+                bert(),
+                atom("regex"),
+                Term::from(Binary::from(source.clone())),
+                Term::from(List::from(options.clone())),
+            ],
+            BertValue::Term(term) => return term.clone(),
+        };
+        Term::from(Tuple::from(elements))
+    }
+
+    /// Makes a dict holding the entries of `map`.
+    pub fn dict_from_map(map: &Map) -> Self {
+        BertValue::Dict(
+            map.map
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )
+    }
+
+    /// Returns the entries of a dict as a map (later duplicate keys win).
+    pub fn to_map(&self) -> Option<Map> {
+        match self {
+            BertValue::Dict(entries) => Some(Map::from(
+                entries.iter().cloned().collect::<HashMap<_, _>>(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Returns the time of a time value.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        match self {
+            BertValue::Time(time) => Some(*time),
+            _ => None,
+        }
+    }
+
+    fn try_from_bert_tuple(term: &Term) -> Option<Self> {
+        let elements = match term {
+            Term::Tuple(x) => x.elements.as_slice(),
+            _ => return None,
+        };
+        let name = |term: &Term| match term {
+            Term::Atom(x) => Some(x.name.clone()),
+            _ => None,
+        };
+        match elements {
+            [tag, kind, rest @ ..] if &*name(tag)? == BERT => match (&*name(kind)?, rest) {
+                ("nil", []) => Some(BertValue::Nil),
+                ("true", []) => Some(BertValue::Bool(true)),
+                ("false", []) => Some(BertValue::Bool(false)),
+                ("dict", [entries]) => list_elements(entries)?
+                    .iter()
+                    .map(|entry| match entry {
+                        Term::Tuple(x) if x.elements.len() == 2 => {
+                            Some((x.elements[0].clone(), x.elements[1].clone()))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .map(BertValue::Dict),
+                ("time", [mega, sec, micro]) => {
+                    from_timestamp(integer(mega)?, integer(sec)?, integer(micro)?)
+                        .map(BertValue::Time)
+                }
+                ("regex", [source, options]) => Some(BertValue::Regex {
+                    source: match source {
+                        Term::Binary(x) => x.bytes.to_vec(),
+                        _ => return None,
+                    },
+                    options: list_elements(options)?,
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+impl From<Term> for BertValue {
+    fn from(term: Term) -> Self {
+        BertValue::from_term(&term)
+    }
+}
+impl From<BertValue> for Term {
+    fn from(value: BertValue) -> Self {
+        value.to_term()
+    }
+}
+impl From<SystemTime> for BertValue {
+    fn from(time: SystemTime) -> Self {
+        BertValue::Time(time)
+    }
+}
+
+fn list_elements(term: &Term) -> Option<Vec<Term>> {
+    match term {
+        Term::List(x) => Some(x.elements.clone()),
+        Term::ByteList(x) => Some(x.bytes.iter().map(|&b| Term::from(i32::from(b))).collect()),
+        _ => None,
+    }
+}
+
+fn integer(term: &Term) -> Option<u64> {
+    match term {
+        Term::FixInteger(x) => u64::try_from(x.value).ok(),
+        _ => None,
+    }
+}
+
+/// Converts `time` to `{MegaSeconds, Seconds, MicroSeconds}` (times before the epoch are clamped to it).
+fn to_timestamp(time: SystemTime) -> (i32, i32, i32) {
+    let d = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+    (
+        (secs / 1_000_000) as i32,
+        (secs % 1_000_000) as i32,
+        d.subsec_micros() as i32,
+    )
+}
+
+fn from_timestamp(mega: u64, sec: u64, micro: u64) -> Option<SystemTime> {
+    let secs = mega.checked_mul(1_000_000)?.checked_add(sec)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs) + Duration::from_micros(micro))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn round_trip(value: BertValue, expected: &str) {
+        let term = value.to_term();
+        assert_eq!(term.to_string(), expected);
+        let mut buf = Vec::new();
+        term.encode(&mut buf).unwrap();
+        let term = Term::decode(std::io::Cursor::new(&buf)).unwrap();
+        assert_eq!(BertValue::from_term(&term), value);
+    }
+
+    #[test]
+    fn conventions_work() {
+        round_trip(BertValue::Nil, "{'bert','nil'}");
+        round_trip(BertValue::Bool(true), "{'bert','true'}");
+        round_trip(BertValue::Bool(false), "{'bert','false'}");
+        round_trip(
+            BertValue::Dict(vec![(atom("name"), Term::from(Binary::from(&b"Tom"[..])))]),
+            "{'bert','dict',[{'name',<<84,111,109>>}]}",
+        );
+        round_trip(BertValue::Dict(Vec::new()), "{'bert','dict',[]}");
+        let time = UNIX_EPOCH + Duration::new(1_255_295_581, 446_228_000);
+        round_trip(BertValue::Time(time), "{'bert','time',1255,295581,446228}");
+        round_trip(
+            BertValue::Regex {
+                source: b"^c(a*)t$".to_vec(),
+                options: vec![atom("caseless")],
+            },
+            "{'bert','regex',<<94,99,40,97,42,41,116,36>>,['caseless']}",
+        );
+    }
+
+    #[test]
+    fn helpers_work() {
+        let map = Map::from([(atom("a"), Term::from(1))]);
+        let dict = BertValue::dict_from_map(&map);
+        assert_eq!(dict.to_map(), Some(map));
+        assert_eq!(BertValue::Nil.to_map(), None);
+
+        let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        assert_eq!(BertValue::from(time).to_system_time(), Some(time));
+    }
+
+    #[test]
+    fn other_terms_pass_through() {
+        let terms = [
+            atom("nil"),
+            Term::from(Tuple::from(vec![atom("ok"), Term::from(1)])),
+            Term::from(Tuple::from(vec![atom("bert"), atom("unknown")])),
+            Term::from(Tuple::from(vec![atom("bert"), atom("nil"), atom("extra")])),
+            Term::from(Tuple::from(vec![
+                atom("bert"),
+                atom("time"),
+                Term::from(-1),
+                Term::from(0),
+                Term::from(0),
+            ])),
+        ];
+        for term in terms {
+            let value = BertValue::from_term(&term);
+            assert_eq!(value, BertValue::Term(term.clone()));
+            assert_eq!(value.to_term(), term);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+//! BERT-RPC messages.
+//!
+//! A client sends `{call, Module, Function, Arguments}` (or `cast`) in a BERP and the
+//! server answers with `{reply, Result}`, `{noreply}` or
+//! `{error, {Type, Code, Class, Detail, Backtrace}}`.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::bert_rpc::BertRpc;
+//! use eetf::Term;
+//!
+//! let call = BertRpc::call("calc", "add", vec![Term::from(1), Term::from(2)]);
+//! assert_eq!(call.to_term().to_string(), "{'call','calc','add',[1,2]}");
+//! assert_eq!(BertRpc::from_term(&call.to_term()).unwrap(), call);
+//! ```
+//!
+//! # Reference
+//!
+//! - [BERT and BERT-RPC 1.0 Specification](https://bert-rpc.org/)
+use super::*;
+use crate::berp;
+use std::io::{Read, Write};
+
+/// Errors which can occur when parsing BERT-RPC messages or issuing calls.
+#[derive(Debug, thiserror::Error)]
+pub enum BertRpcError {
+    #[error("malformed BERT-RPC message: expected {expected}, found {value}")]
+    Malformed { expected: &'static str, value: Term },
+
+    #[error("unexpected BERT-RPC response {0:?}")]
+    UnexpectedResponse(Box<BertRpc>),
+
+    #[error("{error_type} error {code} ({class}): {detail}")]
+    Remote {
+        error_type: Atom,
+        code: i32,
+        class: String,
+        detail: String,
+        backtrace: Vec<String>,
+    },
+
+    #[error("the connection was closed before a response")]
+    Closed,
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+/// BERT-RPC message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BertRpc {
+    /// `{call, Module, Function, Arguments}`
+    Call {
+        module: Atom,
+        function: Atom,
+        args: Vec<Term>,
+    },
+
+    /// `{cast, Module, Function, Arguments}`
+    Cast {
+        module: Atom,
+        function: Atom,
+        args: Vec<Term>,
+    },
+
+    /// `{reply, Result}`
+    Reply(Term),
+
+    /// `{noreply}`
+    NoReply,
+
+    /// `{error, {Type, Code, Class, Detail, Backtrace}}`
+    Error {
+        /// `protocol`, `server`, `user` or `proxy`.
+        error_type: Atom,
+        code: i32,
+        class: String,
+        detail: String,
+        backtrace: Vec<String>,
+    },
+
+    /// `{info, Command, Options}`
+    Info { command: Atom, options: Vec<Term> },
+}
+impl BertRpc {
+    /// Makes a call.
+    pub fn call(module: &str, function: &str, args: Vec<Term>) -> Self {
+        BertRpc::Call {
+            module: Atom::from(module),
+            function: Atom::from(function),
+            args,
+        }
+    }
+
+    /// Makes a cast.
+    pub fn cast(module: &str, function: &str, args: Vec<Term>) -> Self {
+        BertRpc::Cast {
+            module: Atom::from(module),
+            function: Atom::from(function),
+            args,
+        }
+    }
+
+    /// Converts the message to its tuple.
+    pub fn to_term(&self) -> Term {
+        let atom = |name: &str| Term::from(Atom::from(name));
+        let binary = |s: &str| Term::from(Binary::from(s.as_bytes()));
+        let elements = match self {
+            BertRpc::Call {
+                module,
+                function,
+                args,
+            } => vec![
+                atom("call"),
+                Term::from(module.clone()),
+                Term::from(function.clone()),
+                Term::from(List::from(args.clone())),
+            ],
+            BertRpc::Cast {
+                module,
+                function,
+                args,
+            } => vec![
+                atom("cast"),
+                Term::from(module.clone()),
+                Term::from(function.clone()),
+                Term::from(List::from(args.clone())),
+            ],
+            BertRpc::Reply(result) => vec![atom("reply"), result.clone()],
+            BertRpc::NoReply => vec![atom("noreply")],
+            BertRpc::Error {
+                error_type,
+                code,
+                class,
+                detail,
+                backtrace,
+            } => {
+                let backtrace = backtrace.iter().map(|s| binary(s)).collect::<Vec<_>>();
+                vec![
+                    atom("error"),
+                    Term::from(Tuple::from(vec![
+                        Term::from(error_type.clone()),
+                        Term::from(*code),
+                        binary(class),
+                        binary(detail),
+                        Term::from(List::from(backtrace)),
+                    ])),
+                ]
+            }
+            BertRpc::Info { command, options } => vec![
+                atom("info"),
+                Term::from(command.clone()),
+                Term::from(List::from(options.clone())),
+            ],
+        };
+        Term::from(Tuple::from(elements))
+    }
+
+    /// Parses a message.
+    pub fn from_term(term: &Term) -> Result<Self, BertRpcError> {
+        let elements = match term {
+            Term::Tuple(x) if !x.elements.is_empty() => x.elements.as_slice(),
+            _ => return Err(malformed("a non-empty tuple", term)),
+        };
+        let kind = atom(&elements[0])?;
+        match (kind.as_str(), &elements[1..]) {
+            ("call", [module, function, args]) => Ok(BertRpc::Call {
+                module: atom(module)?,
+                function: atom(function)?,
+                args: list(args)?,
+            }),
+            ("cast", [module, function, args]) => Ok(BertRpc::Cast {
+                module: atom(module)?,
+                function: atom(function)?,
+                args: list(args)?,
+            }),
+            ("reply", [result]) => Ok(BertRpc::Reply(result.clone())),
+            ("noreply", []) => Ok(BertRpc::NoReply),
+            ("error", [error]) => match error {
+                Term::Tuple(x) if x.elements.len() == 5 => {
+                    let e = &x.elements;
+                    Ok(BertRpc::Error {
+                        error_type: atom(&e[0])?,
+                        code: match &e[1] {
+                            Term::FixInteger(x) => x.value,
+                            other => return Err(malformed("an error code", other)),
+                        },
+                        class: string(&e[2])?,
+                        detail: string(&e[3])?,
+                        backtrace: list(&e[4])?.iter().map(string).collect::<Result<_, _>>()?,
+                    })
+                }
+                other => Err(malformed("{Type, Code, Class, Detail, Backtrace}", other)),
+            },
+            ("info", [command, options]) => Ok(BertRpc::Info {
+                command: atom(command)?,
+                options: list(options)?,
+            }),
+            _ => Err(malformed(
+                "a call, cast, reply, noreply, error or info message",
+                term,
+            )),
+        }
+    }
+
+    /// Converts a response to the result of a call.
+    fn into_result(self) -> Result<Term, BertRpcError> {
+        match self {
+            BertRpc::Reply(result) => Ok(result),
+            BertRpc::Error {
+                error_type,
+                code,
+                class,
+                detail,
+                backtrace,
+            } => Err(BertRpcError::Remote {
+                error_type,
+                code,
+                class,
+                detail,
+                backtrace,
+            }),
+            other => Err(BertRpcError::UnexpectedResponse(Box::new(other))),
+        }
+    }
+}
+
+/// Calls `module:function(args...)` over `stream`, returning the reply.
+///
+/// A `{error, ...}` response becomes [`BertRpcError::Remote`].
+pub fn call<S: Read + Write>(
+    stream: &mut S,
+    module: &str,
+    function: &str,
+    args: Vec<Term>,
+) -> Result<Term, BertRpcError> {
+    berp::write_message(
+        &mut *stream,
+        &BertRpc::call(module, function, args).to_term(),
+    )?;
+    stream.flush().map_err(EncodeError::from)?;
+    let response = berp::read_message(&mut *stream)?.ok_or(BertRpcError::Closed)?;
+    BertRpc::from_term(&response)?.into_result()
+}
+
+/// Calls `module:function(args...)` over an async `stream` (see [`call`]).
+#[cfg(feature = "tokio-async")]
+pub async fn call_async<S>(
+    stream: &mut S,
+    module: &str,
+    function: &str,
+    args: Vec<Term>,
+) -> Result<Term, BertRpcError>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+
+    let request = BertRpc::call(module, function, args).to_term();
+    berp::write_message_async(stream, &request, berp::DEFAULT_MAX_FRAME_SIZE).await?;
+    stream.flush().await.map_err(EncodeError::from)?;
+    let response = berp::read_message_async(stream, berp::DEFAULT_MAX_FRAME_SIZE)
+        .await?
+        .ok_or(BertRpcError::Closed)?;
+    BertRpc::from_term(&response)?.into_result()
+}
+
+fn malformed(expected: &'static str, value: &Term) -> BertRpcError {
+    BertRpcError::Malformed {
+        expected,
+        value: value.clone(),
+    }
+}
+
+fn atom(term: &Term) -> Result<Atom, BertRpcError> {
+    match term {
+        Term::Atom(x) => Ok(x.clone()),
+        _ => Err(malformed("an atom", term)),
+    }
+}
+
+fn list(term: &Term) -> Result<Vec<Term>, BertRpcError> {
+    match term {
+        Term::List(x) => Ok(x.elements.clone()),
+        Term::ByteList(x) => Ok(x.bytes.iter().map(|&b| Term::from(i32::from(b))).collect()),
+        _ => Err(malformed("a list", term)),
+    }
+}
+
+fn string(term: &Term) -> Result<String, BertRpcError> {
+    match term {
+        Term::Binary(x) => {
+            String::from_utf8(x.bytes.to_vec()).map_err(|_| malformed("a UTF-8 binary", term))
+        }
+        _ => Err(malformed("a binary", term)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory stream which reads `input` and records the written bytes.
+    struct Stream {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+    impl Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+    impl Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn call_is_encoded() {
+        let call = BertRpc::call(
+            "nat",
+            "add",
+            vec![Term::from(Atom::from("a")), Term::from(2)],
+        );
+        let mut buf = Vec::new();
+        berp::write_message(&mut buf, &call.to_term()).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            0, 0, 0, 34,
+            131, 104, 4,
+            100, 0, 4, b'c', b'a', b'l', b'l',
+            100, 0, 3, b'n', b'a', b't',
+            100, 0, 3, b'a', b'd', b'd',
+            108, 0, 0, 0, 2, 100, 0, 1, b'a', 97, 2, 106,
+        ];
+        assert_eq!(buf, expected);
+        assert_eq!(BertRpc::from_term(&call.to_term()).unwrap(), call);
+
+        // Arguments which are small integers are decoded from STRING_EXT.
+        let call = BertRpc::cast("nat", "add", vec![Term::from(1), Term::from(2)]);
+        let mut buf = Vec::new();
+        call.to_term().encode(&mut buf).unwrap();
+        let term = Term::decode(io::Cursor::new(&buf)).unwrap();
+        assert_eq!(BertRpc::from_term(&term).unwrap(), call);
+    }
+
+    #[test]
+    fn responses_are_decoded() {
+        // {reply, 3}
+        let mut stream = Stream {
+            input: io::Cursor::new(vec![
+                0, 0, 0, 13, 131, 104, 2, 100, 0, 5, b'r', b'e', b'p', b'l', b'y', 97, 3,
+            ]),
+            output: Vec::new(),
+        };
+        let result = call(
+            &mut stream,
+            "nat",
+            "add",
+            vec![Term::from(1), Term::from(2)],
+        )
+        .unwrap();
+        assert_eq!(result, Term::from(3));
+        let request = berp::read_message(&stream.output[..]).unwrap().unwrap();
+        assert_eq!(
+            BertRpc::from_term(&request).unwrap(),
+            BertRpc::call("nat", "add", vec![Term::from(1), Term::from(2)])
+        );
+
+        // {error, {server, 2, <<"UnknownFunction">>, <<"function 'foo' not found">>, [<<"line 1">>]}}
+        let error = BertRpc::Error {
+            error_type: Atom::from("server"),
+            code: 2,
+            class: "UnknownFunction".to_owned(),
+            detail: "function 'foo' not found".to_owned(),
+            backtrace: vec!["line 1".to_owned()],
+        };
+        assert_eq!(
+            error.to_term().to_string(),
+            "{'error',{'server',2,<<85,110,107,110,111,119,110,70,117,110,99,116,105,111,110>>,\
+             <<102,117,110,99,116,105,111,110,32,39,102,111,111,39,32,110,111,116,32,102,111,117,110,100>>,\
+             [<<108,105,110,101,32,49>>]}}"
+        );
+        let mut input = Vec::new();
+        berp::write_message(&mut input, &error.to_term()).unwrap();
+        let mut stream = Stream {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+        };
+        match call(&mut stream, "nat", "foo", Vec::new()) {
+            Err(BertRpcError::Remote {
+                error_type,
+                code,
+                class,
+                ..
+            }) => {
+                assert_eq!(
+                    (error_type.as_str(), code, class.as_str()),
+                    ("server", 2, "UnknownFunction")
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // The connection is closed.
+        let mut stream = Stream {
+            input: io::Cursor::new(Vec::new()),
+            output: Vec::new(),
+        };
+        assert!(matches!(
+            call(&mut stream, "nat", "foo", Vec::new()),
+            Err(BertRpcError::Closed)
+        ));
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        let atom = |name: &str| Term::from(Atom::from(name));
+        let tuple = |elements: Vec<Term>| Term::from(Tuple::from(elements));
+        let malformed = [
+            atom("reply"),
+            tuple(vec![atom("call"), atom("nat")]),
+            tuple(vec![
+                atom("call"),
+                atom("nat"),
+                Term::from(1),
+                List::nil().into(),
+            ]),
+            tuple(vec![
+                atom("error"),
+                tuple(vec![atom("server"), Term::from(2)]),
+            ]),
+            tuple(vec![atom("unknown")]),
+        ];
+        for term in malformed {
+            assert!(
+                matches!(
+                    BertRpc::from_term(&term),
+                    Err(BertRpcError::Malformed { .. })
+                ),
+                "{}",
+                term
+            );
+        }
+        let info = BertRpc::Info {
+            command: Atom::from("cache"),
+            options: vec![tuple(vec![
+                atom("validation"),
+                Term::from(Binary::from(&b"etag"[..])),
+            ])],
+        };
+        assert_eq!(BertRpc::from_term(&info.to_term()).unwrap(), info);
+        assert!(matches!(
+            BertRpc::NoReply.into_result(),
+            Err(BertRpcError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_call_works() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (mut client, mut server) = tokio::io::duplex(1024);
+            let server = async move {
+                let request = berp::read_message_async(&mut server, 1024)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    BertRpc::from_term(&request).unwrap(),
+                    BertRpc::call("nat", "add", vec![Term::from(1), Term::from(2)])
+                );
+                let reply = BertRpc::Reply(Term::from(3)).to_term();
+                berp::write_message_async(&mut server, &reply, 1024)
+                    .await
+                    .unwrap();
+            };
+            let (result, ()) = tokio::join!(
+                call_async(
+                    &mut client,
+                    "nat",
+                    "add",
+                    vec![Term::from(1), Term::from(2)]
+                ),
+                server
+            );
+            assert_eq!(result.unwrap(), Term::from(3));
+        });
+    }
+}
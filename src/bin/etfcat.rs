@@ -0,0 +1,305 @@
+//! Inspects and converts files of version-prefixed terms (e.g., wire captures).
+//!
+//! Every input (a file, or stdin if there are none or for `-`) is read as a sequence of
+//! terms, each of which is printed in Erlang syntax unless another output is selected.
+//!
+//! The exit status is 0 on success, 1 if a `--path` is not found, 2 for invalid
+//! arguments, 65 if an input does not decode (or a term does not encode), and 74 if an
+//! input cannot be read or the output cannot be written.
+use clap::Parser;
+use eetf::json::JsonOptions;
+use eetf::{DecodeError, Decoder, EncodeError, Term};
+use std::fmt::Write as _;
+use std::io::{self, Cursor, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Inspects and converts files of version-prefixed terms of the Erlang External Term Format.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Files to read (`-` for stdin, which is read if there are none).
+    files: Vec<PathBuf>,
+
+    /// Prints the terms in Erlang syntax over multiple lines.
+    #[arg(long, group = "output")]
+    pretty: bool,
+
+    /// Prints the terms as JSON.
+    #[arg(long, group = "output")]
+    json: bool,
+
+    /// Prints the depth, the number of atoms and the size of the largest binary of each term.
+    #[arg(long, group = "output")]
+    stats: bool,
+
+    /// Writes the terms compressed.
+    #[arg(long, group = "output")]
+    recompress: bool,
+
+    /// Writes the terms uncompressed.
+    #[arg(long, group = "output")]
+    decompress: bool,
+
+    /// Replaces each term with its subterm at a dot separated path (e.g., `http.port`).
+    ///
+    /// A segment selects the value of a map key (an atom, a binary or an integer), the element
+    /// of a tuple or a list at a (zero-based) index, or the value of a key in a list of pairs.
+    #[arg(long)]
+    path: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("failed to read {input}: {source}")]
+    Read { input: String, source: io::Error },
+
+    #[error("failed to write the output: {0}")]
+    Write(#[from] io::Error),
+
+    #[error("{input}: failed to decode the term at byte {offset}: {source}")]
+    Decode {
+        input: String,
+        offset: u64,
+        source: DecodeError,
+    },
+
+    #[error("failed to encode a term: {0}")]
+    Encode(#[from] EncodeError),
+
+    #[error("{input}: path {path} is not found in the term at byte {offset}")]
+    PathNotFound {
+        input: String,
+        offset: u64,
+        path: String,
+    },
+}
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::PathNotFound { .. } => ExitCode::from(1),
+            CliError::Decode { .. } | CliError::Encode(_) => ExitCode::from(65),
+            CliError::Read { .. } | CliError::Write(_) => ExitCode::from(74),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let result = run(&args, &mut out).and_then(|()| Ok(out.flush()?));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Write(e)) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("etfcat: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+fn run(args: &Args, out: &mut impl Write) -> Result<(), CliError> {
+    let stdin = [PathBuf::from("-")];
+    let files = if args.files.is_empty() {
+        &stdin[..]
+    } else {
+        &args.files
+    };
+    for file in files {
+        let input = file.display().to_string();
+        let bytes = if input == "-" {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+        } else {
+            std::fs::read(file)
+        };
+        let bytes = bytes.map_err(|source| CliError::Read {
+            input: input.clone(),
+            source,
+        })?;
+
+        let mut reader = Cursor::new(&bytes[..]);
+        while (reader.position() as usize) < bytes.len() {
+            let offset = reader.position();
+            let term = Decoder::new(&mut reader)
+                .decode()
+                .map_err(|source| CliError::Decode {
+                    input: input.clone(),
+                    offset,
+                    source,
+                })?;
+            let term = match &args.path {
+                None => &term,
+                Some(path) => select(&term, path).ok_or_else(|| CliError::PathNotFound {
+                    input: input.clone(),
+                    offset,
+                    path: path.clone(),
+                })?,
+            };
+            output(args, term, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn output(args: &Args, term: &Term, out: &mut impl Write) -> Result<(), CliError> {
+    if args.pretty {
+        let mut s = String::new();
+        pretty(term, 0, &mut s);
+        writeln!(out, "{}", s)?;
+    } else if args.json {
+        writeln!(out, "{}", term.to_json(&JsonOptions::new()))?;
+    } else if args.stats {
+        let stats = Stats::of(term);
+        writeln!(
+            out,
+            "depth: {}, atoms: {}, largest binary: {} bytes",
+            stats.depth, stats.atoms, stats.largest_binary
+        )?;
+    } else if args.recompress {
+        term.encode_compressed(out)?;
+    } else if args.decompress {
+        term.encode(out)?;
+    } else {
+        writeln!(out, "{}", term)?;
+    }
+    Ok(())
+}
+
+/// Returns the subterm of `term` at `path` (see [`Args::path`]).
+fn select<'a>(term: &'a Term, path: &str) -> Option<&'a Term> {
+    path.split('.').try_fold(term, |term, segment| {
+        let index = segment.parse::<usize>().ok();
+        match term {
+            Term::Map(map) => map.map.iter().find_map(|(k, v)| {
+                let found = match k {
+                    Term::Atom(atom) => atom.as_str() == segment,
+                    Term::Binary(binary) => binary.bytes[..] == *segment.as_bytes(),
+                    Term::FixInteger(n) => segment.parse() == Ok(n.value),
+                    _ => false,
+                };
+                found.then_some(v)
+            }),
+            Term::Tuple(tuple) => tuple.elements.get(index?),
+            Term::List(list) => match index {
+                Some(i) => list.elements.get(i),
+                None => list.elements.iter().find_map(|e| match e {
+                    Term::Tuple(pair) => match &pair.elements[..] {
+                        [Term::Atom(k), v] if k.as_str() == segment => Some(v),
+                        _ => None,
+                    },
+                    _ => None,
+                }),
+            },
+            _ => None,
+        }
+    })
+}
+
+const WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+/// Writes `term` to `out`, putting the elements of containers which do not fit in [`WIDTH`]
+/// on their own lines.
+fn pretty(term: &Term, indent: usize, out: &mut String) {
+    let flat = term.to_string();
+    if indent + flat.len() <= WIDTH {
+        out.push_str(&flat);
+        return;
+    }
+    let (open, close, items, tail): (_, _, Vec<_>, _) = match term {
+        Term::Tuple(x) => (
+            "{",
+            "}",
+            x.elements.iter().map(|e| (None, e)).collect(),
+            None,
+        ),
+        Term::List(x) => (
+            "[",
+            "]",
+            x.elements.iter().map(|e| (None, e)).collect(),
+            None,
+        ),
+        Term::ImproperList(x) => (
+            "[",
+            "]",
+            x.elements.iter().map(|e| (None, e)).collect(),
+            Some(&*x.last),
+        ),
+        Term::Map(x) => (
+            "#{",
+            "}",
+            x.map.iter().map(|(k, v)| (Some(k), v)).collect(),
+            None,
+        ),
+        _ => {
+            out.push_str(&flat);
+            return;
+        }
+    };
+    let inner = indent + INDENT;
+    out.push_str(open);
+    for (i, (key, value)) in items.iter().enumerate() {
+        let _ = write!(out, "\n{:inner$}", "");
+        if let Some(key) = key {
+            let _ = write!(out, "{} => ", key);
+        }
+        pretty(value, inner, out);
+        if i + 1 < items.len() {
+            out.push(',');
+        }
+    }
+    if let Some(tail) = tail {
+        let _ = write!(out, "\n{:inner$}| ", "");
+        pretty(tail, inner + 2, out);
+    }
+    let _ = write!(out, "\n{:indent$}{}", "", close);
+}
+
+/// Structural summary of a term.
+#[derive(Debug, Default)]
+struct Stats {
+    depth: usize,
+    atoms: usize,
+    largest_binary: usize,
+}
+impl Stats {
+    fn of(term: &Term) -> Self {
+        let mut stats = Stats::default();
+        stats.depth = stats.visit(term);
+        stats
+    }
+
+    /// Counts the atoms and binaries of `term`, and returns its depth.
+    fn visit(&mut self, term: &Term) -> usize {
+        let children: Vec<&Term> = match term {
+            Term::Atom(_) => {
+                self.atoms += 1;
+                Vec::new()
+            }
+            Term::Binary(x) => {
+                self.largest_binary = self.largest_binary.max(x.bytes.len());
+                Vec::new()
+            }
+            Term::BitBinary(x) => {
+                self.largest_binary = self.largest_binary.max(x.bytes.len());
+                Vec::new()
+            }
+            Term::List(x) => x.elements.iter().collect(),
+            Term::ImproperList(x) => x.elements.iter().chain([&*x.last]).collect(),
+            Term::Tuple(x) => x.elements.iter().collect(),
+            Term::Map(x) => x.map.iter().flat_map(|(k, v)| [k, v]).collect(),
+            Term::InternalFun(x) => match &**x {
+                eetf::InternalFun::Old { free_vars, .. }
+                | eetf::InternalFun::New { free_vars, .. } => free_vars.iter().collect(),
+            },
+            _ => Vec::new(),
+        };
+        1 + children
+            .into_iter()
+            .map(|child| self.visit(child))
+            .max()
+            .unwrap_or(0)
+    }
+}
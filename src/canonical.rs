@@ -0,0 +1,489 @@
+//! Canonical forms and the term order of Erlang.
+use super::*;
+use alloc::borrow::Cow;
+use core::cmp::Ordering;
+
+/// Options of [`Term::canonicalize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalizeOptions {
+    /// Whether strings are byte lists, as the decoders make them (rather than lists of integers).
+    ///
+    /// Either way, empty strings are empty lists.
+    pub byte_lists: bool,
+}
+impl CanonicalizeOptions {
+    /// Makes the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Default for CanonicalizeOptions {
+    fn default() -> Self {
+        CanonicalizeOptions { byte_lists: true }
+    }
+}
+
+/// Compound term whose subterms are being canonicalized.
+enum Frame {
+    List,
+    ImproperList,
+    Tuple,
+    /// The subterms are the keys and the values of the entries, alternately.
+    Map,
+}
+
+impl Term {
+    /// Rewrites the term into the canonical form of the terms which only differ in
+    /// representation, so that they are equal (and hash equally) afterwards.
+    ///
+    /// Big integers which fit a [`FixInteger`] become one, bit strings of whole bytes become
+    /// binaries, strings become byte lists or lists of
+    /// integers (see [`CanonicalizeOptions::byte_lists`]), and the entries of maps are inserted
+    /// in the term order of their keys (see [`Term::erl_cmp`]). Improper lists, and the free
+    /// variables of funs, are left alone. The term is rewritten without recursion.
+    ///
+    /// Encoding canonical terms with [`EncoderOptions::deterministic`] makes the same bytes for
+    /// terms which only differ in representation or in the insertion order of their maps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eetf::{BigInteger, ByteList, CanonicalizeOptions, List, Term};
+    ///
+    /// let a = Term::from(List::from(vec![Term::from(104), Term::from(105)]));
+    /// let b = Term::from(ByteList::from("hi"));
+    /// assert_ne!(a, b);
+    ///
+    /// let options = CanonicalizeOptions::default();
+    /// assert_eq!(a.canonicalize(options), b.canonicalize(options));
+    /// assert_eq!(
+    ///     Term::from(BigInteger::from(7)).canonicalize(options),
+    ///     Term::from(7)
+    /// );
+    /// ```
+    pub fn canonicalize(self, options: CanonicalizeOptions) -> Term {
+        // Each frame holds the canonicalized subterms and the rest of the subterms.
+        let mut stack: Vec<(Frame, Vec<Term>, alloc::vec::IntoIter<Term>)> = Vec::new();
+        let mut next = self;
+        loop {
+            let (frame, children) = match next {
+                Term::List(x) if !x.elements.is_empty() => (Frame::List, x.elements),
+                Term::ImproperList(x) => {
+                    let mut children = x.elements;
+                    children.push(*x.last);
+                    (Frame::ImproperList, children)
+                }
+                Term::Tuple(x) if !x.elements.is_empty() => (Frame::Tuple, x.elements),
+                Term::Map(x) if !x.map.is_empty() => {
+                    let children = x.map.into_iter().flat_map(|(k, v)| [k, v]).collect();
+                    (Frame::Map, children)
+                }
+                term => {
+                    let mut done = canonical_leaf(term, options);
+                    // Completes the compound terms whose last subterm is done.
+                    loop {
+                        let Some((_, children, rest)) = stack.last_mut() else {
+                            return done;
+                        };
+                        children.push(done);
+                        if let Some(term) = rest.next() {
+                            next = term;
+                            break;
+                        }
+                        let (frame, children, _) = stack.pop().expect("unreachable");
+                        done = canonical_compound(frame, children, options);
+                    }
+                    continue;
+                }
+            };
+            let mut rest = children.into_iter();
+            next = rest.next().expect("unreachable");
+            stack.push((frame, Vec::with_capacity(rest.len() + 1), rest));
+        }
+    }
+
+    /// Compares the terms in the term order of Erlang (e.g., as `lists:sort/1` orders them).
+    ///
+    /// Numbers are compared by value, and an integer comes before an equal float. Pids, ports,
+    /// references and funs, which Erlang orders by internals of the runtime, are ordered by their
+    /// fields. Terms which only differ in representation (e.g., a byte list and a list of the
+    /// same integers) are equal.
+    pub fn erl_cmp(&self, other: &Term) -> Ordering {
+        let class = order_class(self).cmp(&order_class(other));
+        if class != Ordering::Equal {
+            return class;
+        }
+        match (self, other) {
+            (Term::Atom(a), Term::Atom(b)) => a.name.cmp(&b.name),
+            (Term::Reference(a), Term::Reference(b)) => {
+                (&a.node.name, a.creation, &a.id).cmp(&(&b.node.name, b.creation, &b.id))
+            }
+            (Term::ExternalFun(a), Term::ExternalFun(b)) => (
+                &a.module.name,
+                &a.function.name,
+                a.arity,
+            )
+                .cmp(&(&b.module.name, &b.function.name, b.arity)),
+            (Term::ExternalFun(_), Term::InternalFun(_)) => Ordering::Less,
+            (Term::InternalFun(_), Term::ExternalFun(_)) => Ordering::Greater,
+            (Term::InternalFun(a), Term::InternalFun(b)) => compare_funs(a, b),
+            (Term::Port(a), Term::Port(b)) => {
+                (&a.node.name, a.id, a.creation).cmp(&(&b.node.name, b.id, b.creation))
+            }
+            (Term::Pid(a), Term::Pid(b)) => (&a.node.name, a.serial, a.id, a.creation).cmp(&(
+                &b.node.name,
+                b.serial,
+                b.id,
+                b.creation,
+            )),
+            (Term::Tuple(a), Term::Tuple(b)) => a
+                .elements
+                .len()
+                .cmp(&b.elements.len())
+                .then_with(|| compare_terms(&a.elements, &b.elements)),
+            (Term::Map(a), Term::Map(b)) => compare_maps(a, b),
+            (Term::Binary(_) | Term::BitBinary(_), Term::Binary(_) | Term::BitBinary(_)) => {
+                bits(self).cmp(&bits(other))
+            }
+            (Term::Raw(a), Term::Raw(b)) => a.bytes.cmp(&b.bytes),
+            _ => match order_class(self) {
+                NUMBER => compare_numbers(self, other),
+                LIST => compare_lists(self, other),
+                // Empty lists.
+                _ => Ordering::Equal,
+            },
+        }
+    }
+}
+
+impl Map {
+    /// Returns the entries in the term order of their keys (see [`Term::erl_cmp`]).
+    pub fn sorted_entries(&self) -> Vec<(&Term, &Term)> {
+        let mut entries = self.map.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.erl_cmp(b));
+        entries
+    }
+}
+
+/// Returns the entries of `map` in the order the encoders write them in.
+#[cfg(feature = "std")]
+pub(crate) fn map_entries(map: &Map, sorted: bool) -> impl Iterator<Item = (&Term, &Term)> {
+    let unsorted = (!sorted).then(|| map.map.iter());
+    let sorted = if sorted {
+        map.sorted_entries()
+    } else {
+        Vec::new()
+    };
+    unsorted.into_iter().flatten().chain(sorted)
+}
+
+fn canonical_leaf(term: Term, options: CanonicalizeOptions) -> Term {
+    match term {
+        Term::BigInteger(x) => match i32::try_from(&x.value) {
+            Ok(value) => Term::from(value),
+            Err(_) => Term::BigInteger(x),
+        },
+        Term::BitBinary(x) if x.bit_len() % 8 == 0 => {
+            let (bytes, _) = x.to_padded_bytes();
+            Term::from(Binary::from(bytes))
+        }
+        Term::ByteList(x) if x.bytes.is_empty() => Term::from(List::nil()),
+        Term::ByteList(x) if !options.byte_lists => {
+            let elements = x.bytes.iter().map(|&b| Term::from(i32::from(b)));
+            Term::from(List::from(elements.collect::<Vec<_>>()))
+        }
+        term => term,
+    }
+}
+
+fn canonical_compound(frame: Frame, mut children: Vec<Term>, options: CanonicalizeOptions) -> Term {
+    match frame {
+        Frame::List => {
+            let bytes = options.byte_lists.then(|| {
+                children
+                    .iter()
+                    .map(|e| match *e {
+                        Term::FixInteger(FixInteger { value }) => u8::try_from(value).ok(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            });
+            match bytes.flatten() {
+                Some(bytes) => Term::from(ByteList::from(bytes)),
+                None => Term::from(List::from(children)),
+            }
+        }
+        Frame::ImproperList => {
+            let last = children.pop().expect("unreachable");
+            Term::from(ImproperList::from((children, last)))
+        }
+        Frame::Tuple => Term::from(Tuple::from(children)),
+        Frame::Map => {
+            let mut entries = Vec::with_capacity(children.len() / 2);
+            let mut children = children.into_iter();
+            while let (Some(k), Some(v)) = (children.next(), children.next()) {
+                entries.push((k, v));
+            }
+            entries.sort_by(|(a, _), (b, _)| a.erl_cmp(b));
+            Term::from(Map::from(entries.into_iter().collect::<TermMap>()))
+        }
+    }
+}
+
+const NUMBER: u8 = 0;
+const LIST: u8 = 9;
+
+/// Returns the rank of the type of the term in the term order
+/// (`number < atom < reference < fun < port < pid < tuple < map < nil < list < bit string`).
+fn order_class(term: &Term) -> u8 {
+    match term {
+        Term::FixInteger(_) | Term::BigInteger(_) | Term::Float(_) => NUMBER,
+        Term::Atom(_) => 1,
+        Term::Reference(_) => 2,
+        Term::ExternalFun(_) | Term::InternalFun(_) => 3,
+        Term::Port(_) => 4,
+        Term::Pid(_) => 5,
+        Term::Tuple(_) => 6,
+        Term::Map(_) => 7,
+        Term::List(x) if x.is_nil() => 8,
+        Term::ByteList(x) if x.bytes.is_empty() => 8,
+        Term::List(_) | Term::ByteList(_) | Term::ImproperList(_) => LIST,
+        Term::Binary(_) | Term::BitBinary(_) => 10,
+        Term::Raw(_) => 11,
+    }
+}
+
+fn compare_terms(a: &[Term], b: &[Term]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.erl_cmp(b))
+        .find(|o| o.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+fn compare_numbers(a: &Term, b: &Term) -> Ordering {
+    let integer = |t: &Term| match t {
+        Term::FixInteger(x) => Some(BigInt::from(x.value)),
+        Term::BigInteger(x) => Some(x.value.clone()),
+        _ => None,
+    };
+    let float = |t: &Term| match t {
+        Term::FixInteger(x) => f64::from(x.value),
+        Term::BigInteger(x) => num::ToPrimitive::to_f64(&x.value).unwrap_or(f64::NAN),
+        Term::Float(x) => x.value,
+        _ => unreachable!(),
+    };
+    match (integer(a), integer(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (a_int, b_int) => float(a)
+            .total_cmp(&float(b))
+            // An integer comes before an equal float.
+            .then_with(|| b_int.is_none().cmp(&a_int.is_none()).reverse()),
+    }
+}
+
+fn compare_maps(a: &Map, b: &Map) -> Ordering {
+    let (a, b) = (a.sorted_entries(), b.sorted_entries());
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| {
+            a.iter()
+                .zip(&b)
+                .map(|((a, _), (b, _))| a.erl_cmp(b))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+        .then_with(|| {
+            a.iter()
+                .zip(&b)
+                .map(|((_, a), (_, b))| a.erl_cmp(b))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+}
+
+/// Returns the elements and the tail (`None` for an empty list) of a list.
+fn list_parts(term: &Term) -> (Cow<'_, [Term]>, Option<&Term>) {
+    match term {
+        Term::List(x) => (Cow::Borrowed(&x.elements), None),
+        Term::ByteList(x) => {
+            let elements = x.bytes.iter().map(|&b| Term::from(i32::from(b)));
+            (Cow::Owned(elements.collect()), None)
+        }
+        Term::ImproperList(x) => (Cow::Borrowed(&x.elements), Some(&x.last)),
+        _ => unreachable!(),
+    }
+}
+
+fn compare_lists(a: &Term, b: &Term) -> Ordering {
+    let ((a, a_tail), (b, b_tail)) = (list_parts(a), list_parts(b));
+    let nil = Term::from(List::nil());
+    let elements = a.iter().zip(b.iter()).map(|(a, b)| a.erl_cmp(b));
+    if let Some(ordering) = elements.clone().find(|o| o.is_ne()) {
+        return ordering;
+    }
+    // The shorter list continues with its tail, where the other continues with a list.
+    match a.len().cmp(&b.len()) {
+        Ordering::Equal => a_tail.unwrap_or(&nil).erl_cmp(b_tail.unwrap_or(&nil)),
+        Ordering::Less => order_class(a_tail.unwrap_or(&nil)).cmp(&LIST),
+        Ordering::Greater => LIST.cmp(&order_class(b_tail.unwrap_or(&nil))),
+    }
+}
+
+fn compare_funs(a: &InternalFun, b: &InternalFun) -> Ordering {
+    let key = |f: &InternalFun| match *f {
+        InternalFun::Old {
+            ref module,
+            index,
+            uniq,
+            ..
+        } => (
+            module.name.clone(),
+            false,
+            i64::from(index),
+            i64::from(uniq),
+        ),
+        InternalFun::New {
+            ref module,
+            index,
+            old_uniq,
+            ..
+        } => (
+            module.name.clone(),
+            true,
+            i64::from(index),
+            i64::from(old_uniq),
+        ),
+    };
+    fn free_vars(f: &InternalFun) -> &[Term] {
+        match f {
+            InternalFun::Old { free_vars, .. } | InternalFun::New { free_vars, .. } => free_vars,
+        }
+    }
+    key(a)
+        .cmp(&key(b))
+        .then_with(|| compare_terms(free_vars(a), free_vars(b)))
+}
+
+/// Returns the bits of a bit string (see [`BitBinary::to_padded_bytes`]).
+fn bits(term: &Term) -> (Cow<'_, [u8]>, usize) {
+    match term {
+        Term::Binary(x) => (Cow::Borrowed(&x.bytes[..]), x.bytes.len() * 8),
+        Term::BitBinary(x) => {
+            let (bytes, len) = x.to_padded_bytes();
+            (Cow::Owned(bytes), len)
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn list<const N: usize>(elements: [Term; N]) -> Term {
+        Term::from(List::from(elements.to_vec()))
+    }
+
+    #[test]
+    fn canonicalize_rewrites_representations() {
+        let options = CanonicalizeOptions::default();
+        let term = Term::from(Tuple::from(vec![
+            Term::from(BigInteger::from(-3)),
+            Term::from(BigInteger::from(1u64 << 40)),
+            list([Term::from(104), Term::from(BigInteger::from(105))]),
+            Term::from(ByteList::from("")),
+            Term::from(ImproperList::from((vec![Term::from(1)], Term::from(2)))),
+            Term::from(BitBinary::from((vec![1, 2], 8))),
+        ]));
+        let expected = Term::from(Tuple::from(vec![
+            Term::from(-3),
+            Term::from(BigInteger::from(1u64 << 40)),
+            Term::from(ByteList::from("hi")),
+            Term::from(List::nil()),
+            Term::from(ImproperList::from((vec![Term::from(1)], Term::from(2)))),
+            Term::from(Binary::from(vec![1, 2])),
+        ]));
+        assert_eq!(term.canonicalize(options), expected);
+
+        let options = CanonicalizeOptions { byte_lists: false };
+        assert_eq!(
+            Term::from(ByteList::from("hi")).canonicalize(options),
+            list([Term::from(104), Term::from(105)])
+        );
+        let mixed = list([Term::from(1), atom("a")]);
+        assert_eq!(
+            mixed.clone().canonicalize(CanonicalizeOptions::default()),
+            mixed
+        );
+    }
+
+    #[test]
+    fn canonicalize_sorts_map_entries() {
+        let entries = (0..40).map(|i| (Term::from(i), atom("v")));
+        let forward = Map::from(entries.clone().collect::<TermMap>());
+        let backward = Map::from(entries.rev().collect::<TermMap>());
+        let Term::Map(x) = Term::from(backward).canonicalize(CanonicalizeOptions::default()) else {
+            panic!()
+        };
+        assert_eq!(x, forward);
+        let keys = x.sorted_entries().into_iter().map(|(k, _)| k.clone());
+        assert_eq!(
+            keys.collect::<Vec<_>>(),
+            (0..40).map(Term::from).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn canonicalize_deep_terms() {
+        // Deeper terms would overflow the stack when compared and dropped in debug builds.
+        let mut term = Term::from(1);
+        for _ in 0..500 {
+            term = list([Term::from(Tuple::from(vec![term])), Term::from(300)]);
+        }
+        let canonical = term.clone().canonicalize(CanonicalizeOptions::default());
+        assert_eq!(canonical, term);
+    }
+
+    #[test]
+    fn erl_cmp_follows_the_term_order() {
+        let pid = Term::from(Pid::new("a@host", 1, 0, 0));
+        let sorted = [
+            Term::from(-1),
+            Term::from(1),
+            Term::from(Float::try_from(1.0).unwrap()),
+            Term::from(Float::try_from(1.5).unwrap()),
+            Term::from(BigInteger::from(1u64 << 40)),
+            atom("a"),
+            atom("b"),
+            pid,
+            Term::from(Tuple::from(vec![atom("z")])),
+            Term::from(Tuple::from(vec![atom("a"), atom("a")])),
+            Term::from(Map::from([(atom("a"), Term::from(1))])),
+            Term::from(List::nil()),
+            list([Term::from(1)]),
+            Term::from(ByteList::from("ab")),
+            Term::from(ImproperList::from((vec![Term::from(98)], Term::from(1)))),
+            Term::from(BitBinary::from((vec![0], 1))),
+            Term::from(Binary::from(vec![1])),
+            Term::from(Binary::from(vec![1, 0])),
+        ];
+        for (i, a) in sorted.iter().enumerate() {
+            for (j, b) in sorted.iter().enumerate() {
+                assert_eq!(a.erl_cmp(b), i.cmp(&j), "{} vs {}", a, b);
+            }
+        }
+        assert_eq!(
+            list([Term::from(97)]).erl_cmp(&Term::from(ByteList::from("a"))),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Term::from(ByteList::from("")).erl_cmp(&Term::from(List::nil())),
+            Ordering::Equal
+        );
+    }
+}
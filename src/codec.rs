@@ -1,48 +1,734 @@
 use super::*;
 use codec_common::*;
-use crate::convert::TryAsRef;
+use crate::convert::{FromTerm, IntoTerm, TryAsRef};
+use crate::dist::{
+    self, AtomCache, AtomCacheRef, DistFlags, DistHeader, DistMessage, ATOM_CACHE_SEGMENT_SIZE,
+};
+use crate::fidelity::{self, Fallback, WireLayout};
+use crate::metrics::{CodecMetrics, Counting, Observer};
+use crate::progress::{Progress, Tracked};
+use crate::projection::{Projected, Projection};
+use crate::report::{self, DecodeReport, ReportPathSegment, Reporter, WarningKind};
+use crate::zlib::{self, Zlib};
 use byteorder::BigEndian;
 use byteorder::ReadBytesExt;
 use byteorder::WriteBytesExt;
-use libflate::zlib;
 use num::bigint::BigInt;
 use std::convert::From;
-use std::io;
-use std::io::Write;
+use std::io::{self, Read};
 use std::str;
 
+/// Decoder of terms from a reader.
+///
+/// The decoder takes its reader by value, but `&mut R` is a reader too, so a reader which is
+/// owned by something else can be borrowed for each term:
+///
+/// ```
+/// use eetf::{Decoder, Term};
+/// use std::io::Cursor;
+///
+/// let mut reader = Cursor::new(vec![131, 97, 1, 131, 97, 2]);
+/// assert_eq!(Decoder::new(&mut reader).decode().unwrap(), Term::from(1));
+/// assert_eq!(Decoder::new(&mut reader).decode().unwrap(), Term::from(2));
+/// ```
 pub struct Decoder<R> {
-    reader: R,
+    reader: Tracked<R>,
     buf: Vec<u8>,
+    atom_cache_refs: Vec<Atom>,
+    options: DecoderOptions,
+    depth: usize,
+    atom_table: Option<AtomTable>,
+    metrics: Option<Arc<dyn CodecMetrics>>,
+    layout: Option<WireLayout>,
+    report: Option<Reporter>,
+    poisoned: bool,
 }
 impl<R: io::Read> Decoder<R> {
     pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecoderOptions::default())
+    }
+    /// Makes a decoder whose scratch buffer (for atoms and big integers) has room for `capacity` bytes.
+    pub fn with_capacity(reader: R, capacity: usize) -> Self {
+        let mut decoder = Self::new(reader);
+        decoder.buf.reserve(capacity);
+        decoder
+    }
+    pub fn with_options(reader: R, options: DecoderOptions) -> Self {
         Decoder {
-            reader,
+            reader: Tracked::new(reader),
             buf: Vec::new(),
+            atom_cache_refs: Vec::new(),
+            options,
+            depth: 0,
+            atom_table: None,
+            metrics: None,
+            layout: None,
+            report: None,
+            poisoned: false,
         }
     }
+    /// Makes the decoder intern the names of the decoded atoms in `table`.
+    pub fn with_atom_table(mut self, table: AtomTable) -> Self {
+        self.atom_table = Some(table);
+        self
+    }
+    /// Makes the decoder report each decoded term to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn CodecMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    /// Makes the decoder fail with [`DecodeError::LimitExceeded`] once a term needs more than
+    /// `limit` bytes in total (over all the terms it decodes) to be read.
+    pub fn with_bytes_limit(mut self, limit: u64) -> Self {
+        self.reader.limit = Some(limit);
+        self
+    }
+    /// Makes the decoder call `callback` with its [position](Decoder::position) whenever the
+    /// position passes a multiple of `every` bytes (once per read, even if a read passes several).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is zero.
+    ///
+    /// ```
+    /// use eetf::{Decoder, Term};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let bytes = [131, 107, 0, 4, 1, 2, 3, 4];
+    /// let positions = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = positions.clone();
+    /// let decoder = Decoder::new(&bytes[..])
+    ///     .with_progress(4, move |position| sink.lock().unwrap().push(position));
+    /// decoder.decode().unwrap();
+    /// assert_eq!(*positions.lock().unwrap(), [4, 8]);
+    /// ```
+    pub fn with_progress(mut self, every: u64, callback: impl FnMut(u64) + Send + 'static) -> Self {
+        self.reader.progress = Some(Progress::new(every, Box::new(callback)));
+        self
+    }
+    /// Returns the number of bytes which the decoder has read (or seeked over) since it was made.
+    ///
+    /// After a term was decoded, this is the offset of the end of the term in the input.
+    pub fn position(&self) -> u64 {
+        self.reader.position
+    }
+    /// Returns the limit set by [`Decoder::with_bytes_limit`].
+    pub fn bytes_limit(&self) -> Option<u64> {
+        self.reader.limit
+    }
+    /// Returns the number of bytes which may still be read before the limit set by
+    /// [`Decoder::with_bytes_limit`] is exceeded.
+    pub fn bytes_remaining(&self) -> Option<u64> {
+        self.reader.remaining()
+    }
     pub fn decode(mut self) -> DecodeResult {
+        self.decode_next()
+    }
+    /// Decodes the next term of the reader, reusing the buffers of the decoder.
+    ///
+    /// A failed decode leaves the reader at an unknown position within the term, so it poisons
+    /// the decoder (see [`Decoder::is_poisoned`]).
+    pub fn decode_next(&mut self) -> DecodeResult {
+        if self.options.strict && self.report.is_none() {
+            return self.decode_with_report().map(|(term, _)| term);
+        }
+        self.guarded(|decoder| match Observer::decode(decoder.metrics.as_ref()) {
+            None => decoder.decode_versioned(),
+            Some(observer) => decoder.decode_observed(observer),
+        })
+    }
+    /// Returns `true` if a decode (or skip) failed since the decoder was made or reset.
+    ///
+    /// A poisoned decoder fails with [`DecodeError::Poisoned`] instead of reading from a position
+    /// which is likely within a term.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+    /// Clears the poisoning of the decoder, once the caller made the reader start at a term
+    /// again (e.g., by skipping to the next frame of a framed stream).
+    pub fn reset(&mut self) {
+        self.poisoned = false;
+        self.depth = 0;
+    }
+    /// Runs a public decode, unless the decoder is poisoned, and poisons it if the decode fails.
+    fn guarded<T>(
+        &mut self,
+        decode: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError> {
+        if self.poisoned {
+            return Err(DecodeError::Poisoned);
+        }
+        let result = decode(self);
+        let result = self.reader.check(result);
+        self.poisoned = result.is_err();
+        result
+    }
+    /// Decodes the next term of the reader as a `T`, which may skip building the [`Term`] (e.g.,
+    /// for an [`AtomMap`]).
+    pub fn decode_as<T: DecodeAs>(&mut self) -> Result<T, DecodeError> {
+        T::decode_as(self)
+    }
+    /// Decodes the next term of the reader, and records the tags it was encoded with, so that
+    /// [`Encoder::encode_with_layout`] can reproduce them (see [`fidelity`](crate::fidelity)).
+    pub fn decode_with_layout(&mut self) -> Result<(Term, WireLayout), DecodeError> {
+        self.layout = Some(WireLayout::default());
+        let result = self.decode_next();
+        let layout = self.layout.take().unwrap_or_default();
+        result.map(|term| (term, layout))
+    }
+    /// Decodes the next term of the reader, and reports its anomalies, such as deprecated tags
+    /// or duplicate map keys (see [`report`](crate::report)).
+    ///
+    /// With the [`strict`](DecoderOptions::strict) option, an anomaly of a kind which it rejects
+    /// fails the decode with [`DecodeError::Rejected`] instead.
+    pub fn decode_with_report(&mut self) -> Result<(Term, DecodeReport), DecodeError> {
+        self.report = Some(Reporter::default());
+        let result = self.decode_next();
+        let report = self.report.take().unwrap_or_default();
+        result.map(|term| (term, report.finish()))
+    }
+    fn decode_observed(&mut self, observer: Observer) -> DecodeResult {
+        let base = self.report.as_ref().map_or(0, |report| report.base);
+        let position = self.reader.position;
+        let mut decoder = Decoder {
+            reader: Tracked::new(Counting::new(&mut self.reader)),
+            buf: std::mem::take(&mut self.buf),
+            atom_cache_refs: std::mem::take(&mut self.atom_cache_refs),
+            options: self.options,
+            depth: self.depth,
+            atom_table: self.atom_table.take(),
+            metrics: None,
+            layout: self.layout.take(),
+            report: self
+                .report
+                .take()
+                .map(|report| report.with_base(base + position)),
+            poisoned: false,
+        };
+        let result = observer.in_scope(|| decoder.decode_versioned());
+        observer.finish_decode(&result, decoder.reader.inner.count);
+        self.buf = decoder.buf;
+        self.atom_cache_refs = decoder.atom_cache_refs;
+        self.atom_table = decoder.atom_table;
+        self.layout = decoder.layout;
+        self.report = decoder.report.map(|report| report.with_base(base));
+        result
+    }
+    /// Returns `true` if no option observes or rewrites the subterms, which need the whole
+    /// term otherwise.
+    fn is_plain(&self) -> bool {
+        self.metrics.is_none()
+            && self.layout.is_none()
+            && self.report.is_none()
+            && !self.options.strict
+            && self.options.raw_depth != Some(0)
+    }
+    /// Decodes a map with atom keys (see [`DecodeAs`] for [`AtomMap`]).
+    fn decode_atom_map(&mut self) -> Result<AtomMap, DecodeError> {
+        let keep = self.options.keep_offending_term;
+        if !self.is_plain() {
+            return self
+                .decode_next()
+                .and_then(|term| atom_map_from_term(term, keep));
+        }
+        self.guarded(|decoder| {
+            let version = decoder.reader.read_u8()?;
+            if version != VERSION {
+                return Err(DecodeError::UnsupportedVersion { version });
+            }
+            match decoder.reader.read_u8()? {
+                MAP_EXT => {
+                    decoder.depth += 1;
+                    let result = decoder.decode_atom_map_ext();
+                    decoder.depth -= 1;
+                    result
+                }
+                COMPRESSED_TERM => decoder
+                    .decode_compressed_term()
+                    .and_then(|term| atom_map_from_term(term, keep)),
+                tag => decoder
+                    .decode_term_with_tag(tag)
+                    .and_then(|term| atom_map_from_term(term, keep)),
+            }
+        })
+    }
+    /// Decodes the entries of a map into an [`AtomMap`], or fails with the first key which is not
+    /// an atom once all of them are decoded.
+    fn decode_atom_map_ext(&mut self) -> Result<AtomMap, DecodeError> {
+        let count = self.reader.read_u32::<BigEndian>()? as usize;
+        let mut map = HashMap::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+        let mut non_atom = None;
+        for _ in 0..count {
+            let k = self.decode_term()?;
+            let v = self.decode_term()?;
+            match k {
+                Term::Atom(k) => {
+                    map.insert(k, v);
+                }
+                k => {
+                    non_atom.get_or_insert(k);
+                }
+            }
+        }
+        match non_atom {
+            Some(value) => Err(aux::unexpected_type(
+                value,
+                "atom",
+                self.options.keep_offending_term,
+            )),
+            None => Ok(AtomMap::from(map)),
+        }
+    }
+    /// Decodes the next term, which must be a map, by giving its entries to `f` in the order
+    /// they are encoded, so that they can be converted or stored without building a [`Map`]
+    /// (see also [`DecodeAs`] for `HashMap`).
+    ///
+    /// Once `f` fails, the remaining entries are still decoded, but not given to it, and its
+    /// error is returned, so that the decoder may be [reset](Decoder::reset) to decode the next
+    /// term. A term of another type fails with [`DecodeError::UnexpectedType`].
+    ///
+    /// ```
+    /// use eetf::{Atom, Decoder, Map, Term};
+    ///
+    /// let map = Map::from([(Term::from(Atom::from("a")), Term::from(1))]);
+    /// let mut bytes = Vec::new();
+    /// Term::from(map).encode(&mut bytes).unwrap();
+    ///
+    /// let mut entries = Vec::new();
+    /// Decoder::new(&bytes[..])
+    ///     .decode_map_with(|k, v| {
+    ///         entries.push((k.to_string(), v));
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(entries, [("'a'".to_owned(), Term::from(1))]);
+    /// ```
+    pub fn decode_map_with<F>(&mut self, mut f: F) -> Result<(), DecodeError>
+    where
+        F: FnMut(Term, Term) -> Result<(), DecodeError>,
+    {
+        let keep = self.options.keep_offending_term;
+        if !self.is_plain() {
+            return self
+                .decode_next()
+                .and_then(|term| map_entries_with(term, keep, f));
+        }
+        self.guarded(|decoder| {
+            let version = decoder.reader.read_u8()?;
+            if version != VERSION {
+                return Err(DecodeError::UnsupportedVersion { version });
+            }
+            match decoder.reader.read_u8()? {
+                MAP_EXT => {
+                    decoder.depth += 1;
+                    let result = decoder.decode_map_ext_with(&mut f);
+                    decoder.depth -= 1;
+                    result
+                }
+                COMPRESSED_TERM => decoder
+                    .decode_compressed_term()
+                    .and_then(|term| map_entries_with(term, keep, f)),
+                tag => decoder
+                    .decode_term_with_tag(tag)
+                    .and_then(|term| map_entries_with(term, keep, f)),
+            }
+        })
+    }
+    fn decode_map_ext_with<F>(&mut self, f: &mut F) -> Result<(), DecodeError>
+    where
+        F: FnMut(Term, Term) -> Result<(), DecodeError>,
+    {
+        let count = self.reader.read_u32::<BigEndian>()?;
+        let mut result = Ok(());
+        for _ in 0..count {
+            let k = self.decode_term()?;
+            let v = self.decode_term()?;
+            if result.is_ok() {
+                result = f(k, v);
+            }
+        }
+        result
+    }
+    fn decode_versioned(&mut self) -> DecodeResult {
         let version = self.reader.read_u8()?;
         if version != VERSION {
             return Err(DecodeError::UnsupportedVersion { version });
         }
         let tag = self.reader.read_u8()?;
         match tag {
-            COMPRESSED_TERM => self.decode_compressed_term(),
-            DISTRIBUTION_HEADER => unimplemented!(),
+            COMPRESSED_TERM => {
+                #[cfg(feature = "tracing")]
+                instrument::record_compressed();
+                self.decode_compressed_term()
+            }
             _ => self.decode_term_with_tag(tag),
         }
     }
-    fn decode_term(&mut self) -> DecodeResult {
+    /// Decodes the next term, which must be a binary or a byte list, by copying its bytes to `sink`.
+    ///
+    /// Returns the number of copied bytes. The bytes are copied in chunks, so the binary is never
+    /// held in memory as a whole. A term of another type is decoded (and thus consumed) in full and
+    /// reported as [`DecodeError::UnexpectedType`], after which the decoder may be
+    /// [`reset`](Decoder::reset) to decode the next term (unless the term was compressed).
+    pub fn decode_binary_into(&mut self, sink: &mut impl io::Write) -> Result<u64, DecodeError> {
+        self.guarded(|decoder| decoder.decode_binary_versioned_into(sink))
+    }
+    fn decode_binary_versioned_into(
+        &mut self,
+        sink: &mut impl io::Write,
+    ) -> Result<u64, DecodeError> {
+        let version = self.reader.read_u8()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion { version });
+        }
+        let tag = self.reader.read_u8()?;
+        if tag != COMPRESSED_TERM {
+            return self.decode_binary_with_tag_into(tag, sink);
+        }
+        let max = self.options.max_uncompressed_size;
+        zlib::check_declared_size(max, self.reader.read_u32::<BigEndian>()? as usize)?;
+        let zlib_decoder = zlib::Limited::new(zlib::Backend::decoder(&mut self.reader)?, max);
+        let mut decoder = Decoder::with_options(zlib_decoder, self.options);
+        decoder.buf = std::mem::take(&mut self.buf);
+        decoder.atom_table = self.atom_table.clone();
+        let result = decoder
+            .reader
+            .read_u8()
+            .map_err(DecodeError::from)
+            .and_then(|tag| decoder.decode_binary_with_tag_into(tag, sink))
+            .and_then(|n| finish_compressed(&mut decoder.reader).map(|()| n));
+        self.buf = decoder.buf;
+        decoder.reader.inner.check(result)
+    }
+    fn decode_binary_with_tag_into(
+        &mut self,
+        tag: u8,
+        sink: &mut impl io::Write,
+    ) -> Result<u64, DecodeError> {
+        let size = match tag {
+            BINARY_EXT => u64::from(self.reader.read_u32::<BigEndian>()?),
+            STRING_EXT => u64::from(self.reader.read_u16::<BigEndian>()?),
+            // The empty byte list.
+            NIL_EXT => 0,
+            _ => {
+                let value = self.decode_term_with_tag(tag)?;
+                let keep = self.options.keep_offending_term;
+                return Err(aux::unexpected_type(value, "Binary", keep));
+            }
+        };
+        let copied = io::copy(&mut io::Read::take(&mut self.reader, size), sink)?;
+        if copied < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(copied)
+    }
+    /// Skips the next term without decoding it, by reading and discarding its bytes.
+    ///
+    /// See [`Decoder::skip_term_fast`] for readers which can seek.
+    pub fn skip_term(&mut self) -> Result<(), DecodeError> {
+        self.guarded(|decoder| decoder.skip_versioned(discard))
+    }
+    fn skip_versioned(&mut self, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        let version = self.reader.read_u8()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion { version });
+        }
+        let tag = self.reader.read_u8()?;
+        if tag != COMPRESSED_TERM {
+            return self.skip_term_with_tag(tag, skip);
+        }
+        // The end of the compressed term is only known by inflating it.
+        let max = self.options.max_uncompressed_size;
+        zlib::check_declared_size(max, self.reader.read_u32::<BigEndian>()? as usize)?;
+        let mut zlib_decoder = zlib::Limited::new(zlib::Backend::decoder(&mut self.reader)?, max);
+        let result = io::copy(&mut zlib_decoder, &mut io::sink());
+        zlib_decoder.check(result.map(|_| ()).map_err(DecodeError::from))
+    }
+    /// Decodes the parts of the next term selected by `projection`, and skips the others
+    /// without buffering them (see [`projection`](crate::projection)).
+    ///
+    /// The subterms of a compressed term are inflated to be skipped.
+    pub fn decode_projected(&mut self, projection: &Projection) -> Result<Projected, DecodeError> {
+        self.guarded(|decoder| {
+            let version = decoder.reader.read_u8()?;
+            if version != VERSION {
+                return Err(DecodeError::UnsupportedVersion { version });
+            }
+            match decoder.reader.read_u8()? {
+                COMPRESSED_TERM => decoder.project_compressed_term(projection),
+                tag => decoder.project_term_with_tag(tag, projection),
+            }
+        })
+    }
+    fn project_compressed_term(
+        &mut self,
+        projection: &Projection,
+    ) -> Result<Projected, DecodeError> {
+        let max = self.options.max_uncompressed_size;
+        zlib::check_declared_size(max, self.reader.read_u32::<BigEndian>()? as usize)?;
+        let zlib_decoder = zlib::Limited::new(zlib::Backend::decoder(&mut self.reader)?, max);
+        let mut decoder = Decoder::with_options(zlib_decoder, self.options);
+        decoder.buf = std::mem::take(&mut self.buf);
+        decoder.atom_table = self.atom_table.clone();
+        let result = decoder
+            .reader
+            .read_u8()
+            .map_err(DecodeError::from)
+            .and_then(|tag| decoder.project_term_with_tag(tag, projection))
+            .and_then(|projected| finish_compressed(&mut decoder.reader).map(|()| projected));
+        self.buf = decoder.buf;
+        decoder.reader.inner.check(result)
+    }
+    fn project_term_with_tag(
+        &mut self,
+        tag: u8,
+        projection: &Projection,
+    ) -> Result<Projected, DecodeError> {
+        match projection {
+            Projection::Decode => self.decode_term_with_tag(tag).map(Projected::Term),
+            Projection::Skip => {
+                let start = self.reader.position - 1;
+                self.skip_term_with_tag(tag, discard)?;
+                Ok(Projected::Skipped {
+                    tag,
+                    len: self.reader.position - start,
+                })
+            }
+            Projection::CaptureRaw => {
+                let mut bytes = Vec::new();
+                self.capture_term_with_tag(tag, &mut bytes)?;
+                Ok(Projected::Raw(Raw::from(bytes)))
+            }
+            Projection::Elements { .. } | Projection::Values { .. } => {
+                aux::check_depth(self.depth)?;
+                self.depth += 1;
+                let result = self.project_compound(tag, projection);
+                self.depth -= 1;
+                result
+            }
+        }
+    }
+    fn project_compound(
+        &mut self,
+        tag: u8,
+        projection: &Projection,
+    ) -> Result<Projected, DecodeError> {
+        let keep = self.options.keep_offending_term;
+        match (tag, projection) {
+            (SMALL_TUPLE_EXT | LARGE_TUPLE_EXT, Projection::Elements { by_index, other }) => {
+                let count = if tag == SMALL_TUPLE_EXT {
+                    self.reader.read_u8()? as usize
+                } else {
+                    self.reader.read_u32::<BigEndian>()? as usize
+                };
+                let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for i in 0..count {
+                    let tag = self.reader.read_u8()?;
+                    let projection = by_index.get(&i).unwrap_or(other);
+                    elements.push(self.project_term_with_tag(tag, projection)?);
+                }
+                Ok(Projected::Tuple(elements))
+            }
+            (LIST_EXT, Projection::Elements { by_index, other }) => {
+                let count = self.reader.read_u32::<BigEndian>()? as usize;
+                let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for i in 0..count {
+                    let tag = self.reader.read_u8()?;
+                    let projection = by_index.get(&i).unwrap_or(other);
+                    elements.push(self.project_term_with_tag(tag, projection)?);
+                }
+                let tail = match self.reader.read_u8()? {
+                    NIL_EXT => None,
+                    tag => Some(Box::new(self.project_term_with_tag(tag, other)?)),
+                };
+                Ok(Projected::List { elements, tail })
+            }
+            (NIL_EXT, Projection::Elements { .. }) => Ok(Projected::List {
+                elements: Vec::new(),
+                tail: None,
+            }),
+            (STRING_EXT, Projection::Elements { .. }) => {
+                self.decode_subterm_with_tag(tag).map(Projected::Term)
+            }
+            (MAP_EXT, Projection::Values { by_key, other }) => {
+                let count = self.reader.read_u32::<BigEndian>()? as usize;
+                let mut entries = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+                for _ in 0..count {
+                    let key = self.decode_term()?;
+                    let tag = self.reader.read_u8()?;
+                    let projection = by_key.get(&key).unwrap_or(other);
+                    let value = self.project_term_with_tag(tag, projection)?;
+                    entries.push((key, value));
+                }
+                Ok(Projected::Map(entries))
+            }
+            (_, Projection::Values { .. }) => {
+                let value = self.decode_subterm_with_tag(tag)?;
+                Err(aux::unexpected_type(value, "Map", keep))
+            }
+            _ => {
+                let value = self.decode_subterm_with_tag(tag)?;
+                Err(aux::unexpected_type(value, "Tuple or List", keep))
+            }
+        }
+    }
+    /// Decodes a distribution message (i.e., the data following the 4 byte length of a packet).
+    ///
+    /// The new entries of the distribution header are stored in `cache`, and the other
+    /// entries are resolved against it. The reader must end at the end of the message,
+    /// because the payload is optional.
+    pub fn decode_distribution_message(
+        &mut self,
+        cache: &mut AtomCache,
+    ) -> Result<DistMessage, DecodeError> {
+        self.guarded(|decoder| decoder.decode_versioned_distribution_message(cache))
+    }
+    fn decode_versioned_distribution_message(
+        &mut self,
+        cache: &mut AtomCache,
+    ) -> Result<DistMessage, DecodeError> {
+        let version = self.reader.read_u8()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion { version });
+        }
+        let tag = self.reader.read_u8()?;
+        if tag != DISTRIBUTION_HEADER {
+            return Err(DecodeError::UnexpectedTag {
+                tag,
+                expected: DISTRIBUTION_HEADER,
+            });
+        }
+        let header = self.decode_distribution_header(cache)?;
+        self.atom_cache_refs = header
+            .atom_cache_refs
+            .iter()
+            .map(|r| r.atom.clone())
+            .collect();
+        let result = self.decode_distribution_terms();
+        self.atom_cache_refs.clear();
+        let (control, payload) = result?;
+        Ok(DistMessage {
+            header,
+            control,
+            payload,
+        })
+    }
+    fn decode_distribution_header(
+        &mut self,
+        cache: &mut AtomCache,
+    ) -> Result<DistHeader, DecodeError> {
+        let count = self.reader.read_u8()? as usize;
+        if count == 0 {
+            return Ok(DistHeader {
+                atom_cache_refs: Vec::new(),
+                long_atoms: false,
+            });
+        }
+
+        // Each reference has a half byte of flags, and the half byte following them holds the LongAtoms flag.
+        let mut flags = [0; 128];
+        let flags = &mut flags[..count / 2 + 1];
+        self.reader.read_exact(flags)?;
+        let half_byte = |i: usize| (flags[i / 2] >> ((i % 2) * 4)) & 0x0F;
+        let long_atoms = half_byte(count) & 0x01 != 0;
+
+        let mut atom_cache_refs = Vec::with_capacity(count);
+        for i in 0..count {
+            let new_entry = half_byte(i) & 0x08 != 0;
+            let segment_index = (half_byte(i) & 0x07) as usize;
+            let internal_segment_index = self.reader.read_u8()? as usize;
+            let cache_index = segment_index * ATOM_CACHE_SEGMENT_SIZE + internal_segment_index;
+            let atom = if new_entry {
+                let len = if long_atoms {
+                    self.reader.read_u16::<BigEndian>()? as usize
+                } else {
+                    self.reader.read_u8()? as usize
+                };
+                let name = read_name(&mut self.reader, &mut self.buf, len, false)?;
+                let atom = self.make_atom(&name)?;
+                self.buf = name.into_bytes();
+                cache.set(cache_index, atom.clone());
+                atom
+            } else {
+                cache
+                    .get(cache_index)
+                    .cloned()
+                    .ok_or(DecodeError::EmptyAtomCacheEntry { index: cache_index })?
+            };
+            atom_cache_refs.push(AtomCacheRef {
+                cache_index,
+                new_entry,
+                atom,
+            });
+        }
+        Ok(DistHeader {
+            atom_cache_refs,
+            long_atoms,
+        })
+    }
+    fn decode_distribution_terms(&mut self) -> Result<(Term, Option<Term>), DecodeError> {
+        let control = self.decode_distribution_term()?;
+        let mut tag = [0];
+        let payload = if self.reader.read(&mut tag)? == 0 {
+            None
+        } else {
+            Some(self.decode_distribution_term_with_tag(tag[0])?)
+        };
+        Ok((control, payload))
+    }
+    fn decode_distribution_term(&mut self) -> DecodeResult {
+        let tag = self.reader.read_u8()?;
+        self.decode_distribution_term_with_tag(tag)
+    }
+    fn decode_distribution_term_with_tag(&mut self, tag: u8) -> DecodeResult {
+        // The terms following a distribution header have no version number,
+        // but some implementations write one anyway.
+        if tag == VERSION {
+            self.decode_term()
+        } else {
+            self.decode_term_with_tag(tag)
+        }
+    }
+    pub(crate) fn decode_term(&mut self) -> DecodeResult {
         let tag = self.reader.read_u8()?;
         self.decode_term_with_tag(tag)
     }
-    fn decode_term_with_tag(&mut self, tag: u8) -> DecodeResult {
+    /// Decodes a term which must be an atom (e.g., the node of a pid).
+    ///
+    /// The tag is checked first, so that nested non-atoms cannot recurse without bound.
+    fn decode_atom(&mut self) -> Result<Atom, DecodeError> {
+        let keep = self.options.keep_offending_term;
+        let tag = self.reader.read_u8()?;
+        aux::check_atom_tag(tag)?;
+        self.decode_term_with_tag(tag)
+            .and_then(|t| aux::term_into_atom(t, keep))
+    }
+    pub(crate) fn decode_term_with_tag(&mut self, tag: u8) -> DecodeResult {
+        if self.options.raw_depth.is_some_and(|d| self.depth >= d) && !aux::is_atom_tag(tag) {
+            let mut bytes = Vec::new();
+            self.capture_term_with_tag(tag, &mut bytes)?;
+            return Ok(Term::from(Raw::from(bytes)));
+        }
+        aux::check_depth(self.depth)?;
+        if let Some(layout) = self.layout.as_mut() {
+            layout.tags.push(tag);
+        }
+        if let Some(report) = self.report.as_mut() {
+            report.start = report.base + self.reader.position - 1;
+            if report::is_deprecated_tag(tag) {
+                self.warn(WarningKind::DeprecatedTag { tag })?;
+            }
+        }
+        self.depth += 1;
+        let result = self.decode_subterm_with_tag(tag);
+        self.depth -= 1;
+        result
+    }
+    fn decode_subterm_with_tag(&mut self, tag: u8) -> DecodeResult {
         match tag {
             NEW_FLOAT_EXT => self.decode_new_float_ext(),
             BIT_BINARY_EXT => self.decode_bit_binary_ext(),
-            ATOM_CACHE_REF => unimplemented!(),
+            ATOM_CACHE_REF => self.decode_atom_cache_ref(),
             SMALL_INTEGER_EXT => self.decode_small_integer_ext(),
             INTEGER_EXT => self.decode_integer_ext(),
             FLOAT_EXT => self.decode_float_ext(),
@@ -74,28 +760,307 @@ impl<R: io::Read> Decoder<R> {
         }
     }
     fn decode_compressed_term(&mut self) -> DecodeResult {
-        let _uncompressed_size = self.reader.read_u32::<BigEndian>()? as usize;
-        let zlib_decoder = zlib::Decoder::new(&mut self.reader)?;
-        let mut decoder = Decoder::new(zlib_decoder);
-        decoder.decode_term()
+        let max = self.options.max_uncompressed_size;
+        zlib::check_declared_size(max, self.reader.read_u32::<BigEndian>()? as usize)?;
+        let zlib_decoder = zlib::Limited::new(zlib::Backend::decoder(&mut self.reader)?, max);
+        let mut decoder = Decoder::with_options(zlib_decoder, self.options);
+        decoder.buf = std::mem::take(&mut self.buf);
+        decoder.atom_table = self.atom_table.clone();
+        decoder.layout = self.layout.take().map(|layout| WireLayout {
+            compressed: true,
+            ..layout
+        });
+        // The offsets within the term are those in its inflated body.
+        let base = self.report.as_ref().map_or(0, |report| report.base);
+        decoder.report = self.report.take().map(|report| report.with_base(0));
+        let result = decoder
+            .decode_term()
+            .and_then(|term| finish_compressed(&mut decoder.reader).map(|()| term));
+        self.buf = decoder.buf;
+        self.layout = decoder.layout;
+        self.report = decoder.report.map(|report| report.with_base(base));
+        decoder.reader.inner.check(result)
+    }
+    /// Copies the encoding of a term into `out` without decoding it.
+    ///
+    /// Atom cache references are replaced by the atoms, so that `out` stands on its own.
+    fn capture_term_with_tag(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        aux::check_depth(self.depth)?;
+        self.depth += 1;
+        let result = self.capture_subterm_with_tag(tag, out);
+        self.depth -= 1;
+        result
+    }
+    fn capture_subterm_with_tag(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let keep = self.options.keep_offending_term;
+        if tag == ATOM_CACHE_REF {
+            let atom = aux::term_into_atom(self.decode_atom_cache_ref()?, keep)?;
+            if let Ok(len) = u8::try_from(atom.name.len()) {
+                out.push(SMALL_ATOM_UTF8_EXT);
+                out.push(len);
+            } else {
+                out.push(ATOM_UTF8_EXT);
+                out.extend_from_slice(&(atom.name.len() as u16).to_be_bytes());
+            }
+            out.extend_from_slice(atom.name.as_bytes());
+            return Ok(());
+        }
+        out.push(tag);
+        // The compound terms are captured by methods of their own, which keeps the frame of
+        // this recursive method small.
+        match tag {
+            SMALL_TUPLE_EXT | LARGE_TUPLE_EXT => self.capture_tuple(tag, out),
+            LIST_EXT | MAP_EXT => self.capture_list_or_map(tag, out),
+            EXPORT_EXT => self.capture_terms(3, out),
+            FUN_EXT => self.capture_fun(out),
+            NEW_FUN_EXT => self.capture_new_fun(out),
+            _ => self.capture_simple_term(tag, out),
+        }
+    }
+    fn capture_tuple(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let arity = if tag == SMALL_TUPLE_EXT {
+            self.capture_u8(out)?
+        } else {
+            self.capture_u32(out)?
+        };
+        self.capture_terms(arity, out)
+    }
+    fn capture_list_or_map(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let len = self.capture_u32(out)?;
+        self.capture_terms(len, out)?;
+        // The tail of a list, or the values of a map.
+        self.capture_terms(if tag == LIST_EXT { 1 } else { len }, out)
+    }
+    fn capture_fun(&mut self, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let num_free = self.capture_u32(out)?;
+        self.capture_terms(4 + num_free, out)
+    }
+    fn capture_new_fun(&mut self, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        // The size covers the whole fun, which may change with the atom cache references.
+        let start = out.len();
+        self.reader.read_u32::<BigEndian>()?;
+        out.extend_from_slice(&[0; 4]);
+        self.capture_bytes(1 + 16 + 4, out)?;
+        let num_free = self.capture_u32(out)?;
+        self.capture_terms(4 + num_free, out)?;
+        let size = (out.len() - start) as u32;
+        out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+        Ok(())
+    }
+    /// Captures a term whose only subterm (if any) is an atom.
+    fn capture_simple_term(&mut self, tag: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        match tag {
+            SMALL_INTEGER_EXT => self.capture_bytes(1, out),
+            INTEGER_EXT => self.capture_bytes(4, out),
+            NEW_FLOAT_EXT => self.capture_bytes(8, out),
+            FLOAT_EXT => self.capture_bytes(31, out),
+            NIL_EXT => Ok(()),
+            ATOM_EXT | ATOM_UTF8_EXT | STRING_EXT => {
+                let len = self.capture_u16(out)?;
+                self.capture_bytes(len, out)
+            }
+            SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => {
+                let len = self.capture_u8(out)?;
+                self.capture_bytes(len, out)
+            }
+            BINARY_EXT => {
+                let len = self.capture_u32(out)?;
+                self.capture_bytes(len, out)
+            }
+            BIT_BINARY_EXT | LARGE_BIG_EXT => {
+                let len = self.capture_u32(out)?;
+                self.capture_bytes(1 + len, out)
+            }
+            SMALL_BIG_EXT => {
+                let len = self.capture_u8(out)?;
+                self.capture_bytes(1 + len, out)
+            }
+            PID_EXT => {
+                self.capture_terms(1, out)?;
+                self.capture_bytes(9, out)
+            }
+            NEW_PID_EXT | V4_PORT_EXT => {
+                self.capture_terms(1, out)?;
+                self.capture_bytes(12, out)
+            }
+            PORT_EXT | REFERENCE_EXT => {
+                self.capture_terms(1, out)?;
+                self.capture_bytes(5, out)
+            }
+            NEW_PORT_EXT => {
+                self.capture_terms(1, out)?;
+                self.capture_bytes(8, out)
+            }
+            NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT => {
+                let len = self.capture_u16(out)?;
+                self.capture_terms(1, out)?;
+                let creation = if tag == NEW_REFERENCE_EXT { 1 } else { 4 };
+                self.capture_bytes(creation + 4 * len, out)
+            }
+            _ => Err(DecodeError::UnknownTag { tag }),
+        }
+    }
+    fn capture_terms(&mut self, count: usize, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        for _ in 0..count {
+            let tag = self.reader.read_u8()?;
+            self.capture_term_with_tag(tag, out)?;
+        }
+        Ok(())
+    }
+    fn capture_bytes(&mut self, len: usize, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        // Grows `out` as the bytes arrive, as `len` may be bogus.
+        let read = io::Read::read_to_end(&mut io::Read::take(&mut self.reader, len as u64), out)?;
+        if read < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+    fn capture_u8(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+        self.capture_bytes(1, out)?;
+        Ok(out[out.len() - 1] as usize)
+    }
+    fn capture_u16(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+        self.capture_bytes(2, out)?;
+        Ok(u16::from_be_bytes([out[out.len() - 2], out[out.len() - 1]]) as usize)
+    }
+    fn capture_u32(&mut self, out: &mut Vec<u8>) -> Result<usize, DecodeError> {
+        self.capture_bytes(4, out)?;
+        let n = out.len();
+        Ok(u32::from_be_bytes([out[n - 4], out[n - 3], out[n - 2], out[n - 1]]) as usize)
+    }
+    fn skip_term_with_tag(&mut self, tag: u8, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        aux::check_depth(self.depth)?;
+        self.depth += 1;
+        let result = self.skip_subterm_with_tag(tag, skip);
+        self.depth -= 1;
+        result
+    }
+    fn skip_subterm_with_tag(&mut self, tag: u8, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        match tag {
+            SMALL_TUPLE_EXT => {
+                let arity = self.reader.read_u8()? as usize;
+                self.skip_terms(arity, skip)
+            }
+            LARGE_TUPLE_EXT => {
+                let arity = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_terms(arity, skip)
+            }
+            LIST_EXT | MAP_EXT => {
+                let len = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_terms(len, skip)?;
+                // The tail of a list, or the values of a map.
+                self.skip_terms(if tag == LIST_EXT { 1 } else { len }, skip)
+            }
+            EXPORT_EXT => self.skip_terms(3, skip),
+            FUN_EXT => {
+                let num_free = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_terms(4, skip)?;
+                self.skip_terms(num_free, skip)
+            }
+            NEW_FUN_EXT => {
+                // The size covers the whole fun, including itself.
+                let size = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_bytes(size.saturating_sub(4), skip)
+            }
+            // The index of the atom.
+            ATOM_CACHE_REF => self.skip_bytes(1, skip),
+            _ => self.skip_simple_term(tag, skip),
+        }
+    }
+    /// Skips a term whose only subterm (if any) is an atom.
+    fn skip_simple_term(&mut self, tag: u8, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        match tag {
+            SMALL_INTEGER_EXT => self.skip_bytes(1, skip),
+            INTEGER_EXT => self.skip_bytes(4, skip),
+            NEW_FLOAT_EXT => self.skip_bytes(8, skip),
+            FLOAT_EXT => self.skip_bytes(31, skip),
+            NIL_EXT => Ok(()),
+            ATOM_EXT | ATOM_UTF8_EXT | STRING_EXT => {
+                let len = self.reader.read_u16::<BigEndian>()? as usize;
+                self.skip_bytes(len, skip)
+            }
+            SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => {
+                let len = self.reader.read_u8()? as usize;
+                self.skip_bytes(len, skip)
+            }
+            BINARY_EXT => {
+                let len = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_bytes(len, skip)
+            }
+            BIT_BINARY_EXT | LARGE_BIG_EXT => {
+                let len = self.reader.read_u32::<BigEndian>()? as usize;
+                self.skip_bytes(1 + len, skip)
+            }
+            SMALL_BIG_EXT => {
+                let len = self.reader.read_u8()? as usize;
+                self.skip_bytes(1 + len, skip)
+            }
+            PID_EXT => {
+                self.skip_terms(1, skip)?;
+                self.skip_bytes(9, skip)
+            }
+            NEW_PID_EXT | V4_PORT_EXT => {
+                self.skip_terms(1, skip)?;
+                self.skip_bytes(12, skip)
+            }
+            PORT_EXT | REFERENCE_EXT => {
+                self.skip_terms(1, skip)?;
+                self.skip_bytes(5, skip)
+            }
+            NEW_PORT_EXT => {
+                self.skip_terms(1, skip)?;
+                self.skip_bytes(8, skip)
+            }
+            NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT => {
+                let len = self.reader.read_u16::<BigEndian>()? as usize;
+                self.skip_terms(1, skip)?;
+                let creation = if tag == NEW_REFERENCE_EXT { 1 } else { 4 };
+                self.skip_bytes(creation + 4 * len, skip)
+            }
+            _ => Err(DecodeError::UnknownTag { tag }),
+        }
+    }
+    fn skip_terms(&mut self, count: usize, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        for _ in 0..count {
+            let tag = self.reader.read_u8()?;
+            self.skip_term_with_tag(tag, skip)?;
+        }
+        Ok(())
+    }
+    fn skip_bytes(&mut self, len: usize, skip: SkipBytes<R>) -> Result<(), DecodeError> {
+        Ok(skip(&mut self.reader, len as u64)?)
+    }
+    fn decode_atom_cache_ref(&mut self) -> DecodeResult {
+        let index = self.reader.read_u8()? as usize;
+        self.atom_cache_refs
+            .get(index)
+            .cloned()
+            .map(Term::from)
+            .ok_or(DecodeError::UnknownAtomCacheRef { index })
     }
     #[allow(clippy::unnecessary_wraps)]
     fn decode_nil_ext(&mut self) -> DecodeResult {
         Ok(Term::from(List::nil()))
     }
     fn decode_string_ext(&mut self) -> DecodeResult {
-            let size = self.reader.read_u16::<BigEndian>()? as usize;
-            let mut bytes = vec![0; size];
-            self.reader.read_exact(&mut bytes)?;
-            Ok(Term::from(ByteList::from(bytes)))            
+        let size = self.reader.read_u16::<BigEndian>()? as usize;
+        read_chunked(&mut self.reader, &mut self.buf, size)?;
+        if self.report.is_some() && !report::is_printable(&self.buf) {
+            self.warn(WarningKind::NonPrintableString)?;
+        }
+        Ok(Term::from(ByteList::from(self.buf.to_vec())))
     }
     fn decode_list_ext(&mut self) -> DecodeResult {
         let count = self.reader.read_u32::<BigEndian>()? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
+        let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+        for i in 0..count {
+            self.enter(ReportPathSegment::ListElement(i));
             elements.push(self.decode_term()?);
+            self.leave();
         }
+        self.enter(ReportPathSegment::ListTail);
         let last = self.decode_term()?;
+        self.leave();
         if last.try_as_ref().map(List::is_nil).unwrap_or(false) {
             Ok(Term::from(List::from(elements)))
         } else {
@@ -104,49 +1069,74 @@ impl<R: io::Read> Decoder<R> {
     }
     fn decode_small_tuple_ext(&mut self) -> DecodeResult {
         let count = self.reader.read_u8()? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
+        let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+        for i in 0..count {
+            self.enter(ReportPathSegment::TupleElement(i));
             elements.push(self.decode_term()?);
+            self.leave();
         }
         Ok(Term::from(Tuple::from(elements)))
     }
     fn decode_large_tuple_ext(&mut self) -> DecodeResult {
         let count = self.reader.read_u32::<BigEndian>()? as usize;
-        let mut elements = Vec::with_capacity(count);
-        for _ in 0..count {
+        let mut elements = Vec::with_capacity(count.min(MAX_PREALLOCATED_LEN));
+        for i in 0..count {
+            self.enter(ReportPathSegment::TupleElement(i));
             elements.push(self.decode_term()?);
+            self.leave();
         }
         Ok(Term::from(Tuple::from(elements)))
     }
     fn decode_map_ext(&mut self) -> DecodeResult {
         let count = self.reader.read_u32::<BigEndian>()? as usize;
-        let mut map = HashMap::<Term,Term>::new();
-        for _ in 0..count {
+        let mut map = TermMap::new();
+        // The keys are recorded in the order of the maps, which is the order they are encoded in.
+        let slot = self.layout.as_mut().map(|layout| {
+            layout.map_keys.push(Vec::new());
+            layout.map_keys.len() - 1
+        });
+        let mut keys = Vec::new();
+        for i in 0..count {
+            let position = self.reader.position;
+            let start = self.report.as_ref().map(|report| report.base + position);
+            self.enter(ReportPathSegment::MapKey(i));
             let k = self.decode_term()?;
+            if let Some(start) = start.filter(|_| map.contains_key(&k)) {
+                self.warn_at(start, WarningKind::DuplicateMapKey { key: k.clone() })?;
+            }
+            self.leave();
+            self.enter_with(|| ReportPathSegment::MapValue { key: k.to_string() });
             let v = self.decode_term()?;
+            self.leave();
+            if slot.is_some() {
+                keys.push(k.clone());
+            }
             map.insert(k, v);
         }
+        if let (Some(slot), Some(layout)) = (slot, self.layout.as_mut()) {
+            layout.map_keys[slot] = keys;
+        }
         Ok(Term::from(Map::from(map)))
     }
     fn decode_binary_ext(&mut self) -> DecodeResult {
         let size = self.reader.read_u32::<BigEndian>()? as usize;
-        let mut buf = vec![0; size];
-        self.reader.read_exact(&mut buf)?;
+        let mut buf = Vec::new();
+        read_exact_bounded(&mut self.reader, &mut buf, size)?;
         Ok(Term::from(Binary::from(buf)))
     }
     fn decode_bit_binary_ext(&mut self) -> DecodeResult {
         let size = self.reader.read_u32::<BigEndian>()? as usize;
         let tail_bits_size = self.reader.read_u8()?;
-        let mut buf = vec![0; size];
-        self.reader.read_exact(&mut buf)?;
-        if !buf.is_empty() {
-            let last = buf[size - 1] >> (8 - tail_bits_size);
-            buf[size - 1] = last;
+        aux::check_tail_bits_size(size, tail_bits_size)?;
+        let mut buf = Vec::new();
+        read_exact_bounded(&mut self.reader, &mut buf, size)?;
+        if let Some(last) = buf.last_mut() {
+            *last >>= 8 - tail_bits_size;
         }
         Ok(Term::from(BitBinary::from((buf, tail_bits_size))))
     }
     fn decode_pid_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().and_then(aux::term_into_atom)?;
+        let node = self.decode_atom()?;
         Ok(Term::from(Pid {
             node,
             id: self.reader.read_u32::<BigEndian>()?,
@@ -155,7 +1145,7 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_new_pid_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().and_then(aux::term_into_atom)?;
+        let node = self.decode_atom()?;
         Ok(Term::from(Pid {
             node,
             id: self.reader.read_u32::<BigEndian>()?,
@@ -164,12 +1154,7 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
+        let node: Atom = self.decode_atom()?;
         Ok(Term::from(Port {
             node,
             id: u64::from(self.reader.read_u32::<BigEndian>()?),
@@ -177,12 +1162,7 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_new_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
+        let node: Atom = self.decode_atom()?;
         Ok(Term::from(Port {
             node,
             id: u64::from(self.reader.read_u32::<BigEndian>()?),
@@ -190,12 +1170,7 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_v4_port_ext(&mut self) -> DecodeResult {
-        let node: Atom = self.decode_term().and_then(|t| {
-            t.try_into().map_err(|t| DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
-        })?;
+        let node: Atom = self.decode_atom()?;
         Ok(Term::from(Port {
             node,
             id: self.reader.read_u64::<BigEndian>()?,
@@ -203,7 +1178,7 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_reference_ext(&mut self) -> DecodeResult {
-        let node = self.decode_term().and_then(aux::term_into_atom)?;
+        let node = self.decode_atom()?;
         Ok(Term::from(Reference {
             node,
             id: vec![self.reader.read_u32::<BigEndian>()?],
@@ -212,7 +1187,7 @@ impl<R: io::Read> Decoder<R> {
     }
     fn decode_new_reference_ext(&mut self) -> DecodeResult {
         let id_count = self.reader.read_u16::<BigEndian>()? as usize;
-        let node = self.decode_term().and_then(aux::term_into_atom)?;
+        let node = self.decode_atom()?;
         let creation = u32::from(self.reader.read_u8()?);
         let mut id = Vec::with_capacity(id_count);
         for _ in 0..id_count {
@@ -222,7 +1197,7 @@ impl<R: io::Read> Decoder<R> {
     }
     fn decode_newer_reference_ext(&mut self) -> DecodeResult {
         let id_count = self.reader.read_u16::<BigEndian>()? as usize;
-        let node = self.decode_term().and_then(aux::term_into_atom)?;
+        let node = self.decode_atom()?;
         let creation = self.reader.read_u32::<BigEndian>()?;
         let mut id = Vec::with_capacity(id_count);
         for _ in 0..id_count {
@@ -231,11 +1206,12 @@ impl<R: io::Read> Decoder<R> {
         Ok(Term::from(Reference { node, id, creation }))
     }
     fn decode_export_ext(&mut self) -> DecodeResult {
-        let module = self.decode_term().and_then(aux::term_into_atom)?;
-        let function = self.decode_term().and_then(aux::term_into_atom)?;
-        let arity = self
-            .decode_term()
-            .and_then(|t| aux::term_into_ranged_integer(t, 0..0xFF))? as u8;
+        let keep = self.options.keep_offending_term;
+        let module = self.decode_atom()?;
+        let function = self.decode_atom()?;
+        let arity =
+            self.decode_term()
+                .and_then(|t| aux::term_into_ranged_integer(t, 0..0xFF, keep))? as u8;
         Ok(Term::from(ExternalFun {
             module,
             function,
@@ -243,12 +1219,19 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_fun_ext(&mut self) -> DecodeResult {
+        let keep = self.options.keep_offending_term;
         let num_free = self.reader.read_u32::<BigEndian>()?;
-        let pid = self.decode_term().and_then(aux::term_into_pid)?;
-        let module = self.decode_term().and_then(aux::term_into_atom)?;
-        let index = self.decode_term().and_then(aux::term_into_fix_integer)?;
-        let uniq = self.decode_term().and_then(aux::term_into_fix_integer)?;
-        let mut vars = Vec::with_capacity(num_free as usize);
+        let pid = self
+            .decode_term()
+            .and_then(|t| aux::term_into_pid(t, keep))?;
+        let module = self.decode_atom()?;
+        let index = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let uniq = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let mut vars = Vec::with_capacity((num_free as usize).min(MAX_PREALLOCATED_LEN));
         for _ in 0..num_free {
             vars.push(self.decode_term()?);
         }
@@ -261,17 +1244,24 @@ impl<R: io::Read> Decoder<R> {
         }))
     }
     fn decode_new_fun_ext(&mut self) -> DecodeResult {
+        let keep = self.options.keep_offending_term;
         let _size = self.reader.read_u32::<BigEndian>()?;
         let arity = self.reader.read_u8()?;
         let mut uniq = [0; 16];
         self.reader.read_exact(&mut uniq)?;
         let index = self.reader.read_u32::<BigEndian>()?;
         let num_free = self.reader.read_u32::<BigEndian>()?;
-        let module = self.decode_term().and_then(aux::term_into_atom)?;
-        let old_index = self.decode_term().and_then(aux::term_into_fix_integer)?;
-        let old_uniq = self.decode_term().and_then(aux::term_into_fix_integer)?;
-        let pid = self.decode_term().and_then(aux::term_into_pid)?;
-        let mut vars = Vec::with_capacity(num_free as usize);
+        let module = self.decode_atom()?;
+        let old_index = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let old_uniq = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let pid = self
+            .decode_term()
+            .and_then(|t| aux::term_into_pid(t, keep))?;
+        let mut vars = Vec::with_capacity((num_free as usize).min(MAX_PREALLOCATED_LEN));
         for _ in 0..num_free {
             vars.push(self.decode_term()?);
         }
@@ -299,7 +1289,11 @@ impl<R: io::Read> Decoder<R> {
         let value = float_str
             .parse::<f32>()
             .or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Float::try_from(value)?))
+        let float = Float::try_from(value)?;
+        if let Some(layout) = self.layout.as_mut() {
+            layout.floats.push((buf, float.value));
+        }
+        Ok(Term::from(float))
     }
     fn decode_small_integer_ext(&mut self) -> DecodeResult {
         let value = self.reader.read_u8()?;
@@ -307,6 +1301,9 @@ impl<R: io::Read> Decoder<R> {
     }
     fn decode_integer_ext(&mut self) -> DecodeResult {
         let value = self.reader.read_i32::<BigEndian>()?;
+        if self.report.is_some() && (0..=255).contains(&value) {
+            self.warn(WarningKind::NonMinimalInteger { tag: INTEGER_EXT })?;
+        }
         Ok(Term::from(FixInteger::from(value)))
     }
     fn decode_small_big_ext(&mut self) -> DecodeResult {
@@ -315,57 +1312,652 @@ impl<R: io::Read> Decoder<R> {
         self.buf.resize(count, 0);
         self.reader.read_exact(&mut self.buf)?;
         let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
+        self.check_big_integer(SMALL_BIG_EXT, &value)?;
         Ok(Term::from(BigInteger { value }))
     }
     fn decode_large_big_ext(&mut self) -> DecodeResult {
         let count = self.reader.read_u32::<BigEndian>()? as usize;
         let sign = self.reader.read_u8()?;
-        self.buf.resize(count, 0);
-        self.reader.read_exact(&mut self.buf)?;
+        read_exact_bounded(&mut self.reader, &mut self.buf, count)?;
         let value = BigInt::from_bytes_le(aux::byte_to_sign(sign)?, &self.buf);
+        self.check_big_integer(LARGE_BIG_EXT, &value)?;
         Ok(Term::from(BigInteger { value }))
     }
+    fn make_atom(&self, name: &str) -> Result<Atom, DecodeError> {
+        atom_table::make_atom(self.atom_table.as_ref(), name, self.options.safe)
+    }
+    /// Reads the name of an atom, of `size` bytes of Latin-1 or UTF-8, and makes the atom.
+    fn decode_atom_name(&mut self, size: usize, latin1: bool) -> DecodeResult {
+        let name = read_name(&mut self.reader, &mut self.buf, size, latin1)?;
+        let atom = self.make_atom(&name)?;
+        let long = match self.report {
+            Some(_) => report::long_atom_chars(&name),
+            None => None,
+        };
+        self.buf = name.into_bytes();
+        if let Some(chars) = long {
+            self.warn(WarningKind::LongAtom { chars })?;
+        }
+        Ok(Term::from(atom))
+    }
+    /// Reports a big integer which fits a smaller tag, or has leading zero bytes.
+    fn check_big_integer(&mut self, tag: u8, value: &BigInt) -> Result<(), DecodeError> {
+        if self.report.is_none() {
+            return Ok(());
+        }
+        let fits_i32 = i32::try_from(value).is_ok();
+        let leading_zero = self.buf.last() == Some(&0);
+        let small = tag == LARGE_BIG_EXT && self.buf.len() <= usize::from(u8::MAX);
+        if fits_i32 || leading_zero || small {
+            self.warn(WarningKind::NonMinimalInteger { tag })?;
+        }
+        Ok(())
+    }
+    /// Reports an anomaly of the subterm being decoded, if the decoder reports them.
+    fn warn(&mut self, kind: WarningKind) -> Result<(), DecodeError> {
+        match self.report.as_ref() {
+            Some(report) => self.warn_at(report.start, kind),
+            None => Ok(()),
+        }
+    }
+    fn warn_at(&mut self, offset: u64, kind: WarningKind) -> Result<(), DecodeError> {
+        match self.report.as_mut() {
+            Some(report) => report.warn(offset, kind, self.options.strict),
+            None => Ok(()),
+        }
+    }
+    fn enter(&mut self, segment: ReportPathSegment) {
+        self.enter_with(|| segment)
+    }
+    fn enter_with(&mut self, segment: impl FnOnce() -> ReportPathSegment) {
+        if let Some(report) = self.report.as_mut() {
+            report.enter(segment());
+        }
+    }
+    fn leave(&mut self) {
+        if let Some(report) = self.report.as_mut() {
+            report.leave();
+        }
+    }
     fn decode_atom_ext(&mut self) -> DecodeResult {
         let len = self.reader.read_u16::<BigEndian>()?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf)?;
-        let name = aux::latin1_bytes_to_string(&self.buf)?;
-        Ok(Term::from(Atom { name }))
+        self.decode_atom_name(len as usize, true)
     }
     fn decode_small_atom_ext(&mut self) -> DecodeResult {
         let len = self.reader.read_u8()?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf)?;
-        let name = aux::latin1_bytes_to_string(&self.buf)?;
-        Ok(Term::from(Atom { name }))
+        self.decode_atom_name(len as usize, true)
     }
     fn decode_atom_utf8_ext(&mut self) -> DecodeResult {
         let len = self.reader.read_u16::<BigEndian>()?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf)?;
-        let name = str::from_utf8(&self.buf).or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Atom::from(name)))
+        self.decode_atom_name(len as usize, false)
     }
     fn decode_small_atom_utf8_ext(&mut self) -> DecodeResult {
         let len = self.reader.read_u8()?;
-        self.buf.resize(len as usize, 0);
-        self.reader.read_exact(&mut self.buf)?;
-        let name = str::from_utf8(&self.buf).or_else(|e| aux::invalid_data_error(e.to_string()))?;
-        Ok(Term::from(Atom::from(name)))
+        self.decode_atom_name(len as usize, false)
+    }
+}
+
+/// Type which [`Decoder::decode_as`] decodes.
+///
+/// It is implemented for [`Term`], and for [`AtomMap`], which is decoded without building an
+/// intermediate [`Map`]. An implementation for another type can decode a term with
+/// [`Decoder::decode_next`] and convert it.
+pub trait DecodeAs: Sized {
+    fn decode_as<R: io::Read>(decoder: &mut Decoder<R>) -> Result<Self, DecodeError>;
+}
+impl DecodeAs for Term {
+    fn decode_as<R: io::Read>(decoder: &mut Decoder<R>) -> Result<Self, DecodeError> {
+        decoder.decode_next()
+    }
+}
+/// Fails with [`DecodeError::UnexpectedType`] if the term is not a map, or if one of its keys
+/// (the first one in the input) is not an atom. Either way the whole term is read, so the
+/// decoder can be [reset](Decoder::reset) to decode the next one.
+impl DecodeAs for AtomMap {
+    fn decode_as<R: io::Read>(decoder: &mut Decoder<R>) -> Result<Self, DecodeError> {
+        decoder.decode_atom_map()
+    }
+}
+
+/// Sequence of elements which [`Encoder::encode_sequence_as_list`] encodes as a list, without
+/// collecting them into a [`List`] first.
+///
+/// It is implemented for slices, arrays, `Vec`s and `VecDeque`s of [`IntoTerm`] types, for
+/// [`List`] and [`ImproperList`], and for iterators wrapped in an [`IterSequence`].
+///
+/// The implementations for slices, arrays, `Vec`s and `VecDeque`s clone each element before
+/// converting it with [`IntoTerm::into_term`], one at a time. That is free for integers, but
+/// the elements of a `Vec<(Atom, Term)>` are deep clones of each `Term`, so a [`List`] should
+/// be encoded as it is.
+pub trait ToTermSequence {
+    /// Returns the number of elements which [`ToTermSequence::encode_elements`] gives, which
+    /// `LIST_EXT` starts with.
+    fn element_count(&self) -> usize;
+
+    /// Gives the elements to `sink` in order.
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult;
+
+    /// Returns the tail of an improper list, which follows the elements.
+    fn tail(&self) -> Option<Term> {
+        None
+    }
+}
+
+/// Encoder of the elements of a [`ToTermSequence`].
+///
+/// It fails with [`EncodeError::IteratorLengthMismatch`] if it is given more elements than
+/// [`ToTermSequence::element_count`].
+pub trait ElementSink {
+    fn encode_element(&mut self, element: &Term) -> EncodeResult;
+}
+
+/// Sink writing the elements of a list of `len` elements.
+struct ListElements<'a, W> {
+    encoder: &'a mut Encoder<W>,
+    len: usize,
+    count: usize,
+}
+impl<W: io::Write> ElementSink for ListElements<'_, W> {
+    fn encode_element(&mut self, element: &Term) -> EncodeResult {
+        if self.count == self.len {
+            return Err(EncodeError::IteratorLengthMismatch { len: self.len });
+        }
+        let i = self.count;
+        self.count += 1;
+        self.encoder
+            .encode_term(element)
+            .map_err(|e| e.within(EncodePathSegment::ListElement(i)))
+    }
+}
+
+/// [`ToTermSequence`] of the elements of an iterator, which is cloned to be iterated.
+///
+/// ```
+/// use eetf::{Encoder, IterSequence};
+///
+/// let squares = IterSequence((1..4).map(|i| i * i));
+/// let mut bytes = Vec::new();
+/// Encoder::new(&mut bytes).encode_sequence_as_list(&squares).unwrap();
+/// assert_eq!(eetf::decode_from_slice(&bytes).unwrap().to_string(), "[1,4,9]");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IterSequence<I>(pub I);
+impl<I> ToTermSequence for IterSequence<I>
+where
+    I: Clone + ExactSizeIterator,
+    I::Item: IntoTerm,
+{
+    fn element_count(&self) -> usize {
+        self.0.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self.0
+            .clone()
+            .try_for_each(|e| sink.encode_element(&e.into_term()))
+    }
+}
+
+impl<T: Clone + IntoTerm> ToTermSequence for [T] {
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self.iter()
+            .try_for_each(|e| sink.encode_element(&e.clone().into_term()))
+    }
+}
+impl<T: Clone + IntoTerm, const N: usize> ToTermSequence for [T; N] {
+    fn element_count(&self) -> usize {
+        N
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self[..].encode_elements(sink)
+    }
+}
+impl<T: Clone + IntoTerm> ToTermSequence for Vec<T> {
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self[..].encode_elements(sink)
+    }
+}
+impl<T: Clone + IntoTerm> ToTermSequence for std::collections::VecDeque<T> {
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self.iter()
+            .try_for_each(|e| sink.encode_element(&e.clone().into_term()))
+    }
+}
+impl ToTermSequence for List {
+    fn element_count(&self) -> usize {
+        self.elements.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self.elements
+            .iter()
+            .try_for_each(|e| sink.encode_element(e))
+    }
+}
+impl ToTermSequence for ImproperList {
+    fn element_count(&self) -> usize {
+        self.elements.len()
+    }
+    fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+        self.elements
+            .iter()
+            .try_for_each(|e| sink.encode_element(e))
+    }
+    fn tail(&self) -> Option<Term> {
+        Some((*self.last).clone())
+    }
+}
+
+/// Decodes a map whose keys and values are converted with [`FromTerm`] as they are decoded (see
+/// [`Decoder::decode_map_with`]), so that the entries are never stored as terms.
+///
+/// A key or a value which does not convert fails with [`DecodeError::UnexpectedType`] once the
+/// whole map is read.
+///
+/// ```
+/// use eetf::{Binary, Decoder, Map, Term};
+/// use std::collections::HashMap;
+///
+/// let key = Term::from(Binary::from(&b"pi"[..]));
+/// let map = Map::from([(key, Term::try_from(3.14).unwrap())]);
+/// let mut bytes = Vec::new();
+/// Term::from(map).encode(&mut bytes).unwrap();
+///
+/// let decoded: HashMap<String, f64> = Decoder::new(&bytes[..]).decode_as().unwrap();
+/// assert_eq!(decoded["pi"], 3.14);
+/// ```
+impl<K, V, S> DecodeAs for HashMap<K, V, S>
+where
+    K: FromTerm + Eq + Hash,
+    V: FromTerm,
+    S: std::hash::BuildHasher + Default,
+{
+    fn decode_as<R: io::Read>(decoder: &mut Decoder<R>) -> Result<Self, DecodeError> {
+        let keep = decoder.options.keep_offending_term;
+        let mut map = HashMap::default();
+        decoder.decode_map_with(|k, v| {
+            let key = K::from_term(&k).map_err(|e| aux::unexpected_type(k, &e.expected, keep))?;
+            let value = V::from_term(&v).map_err(|e| aux::unexpected_type(v, &e.expected, keep))?;
+            map.insert(key, value);
+            Ok(())
+        })?;
+        Ok(map)
+    }
+}
+
+/// Gives the entries of `term` to `f`, if it is a map.
+fn map_entries_with<F>(term: Term, keep: bool, mut f: F) -> Result<(), DecodeError>
+where
+    F: FnMut(Term, Term) -> Result<(), DecodeError>,
+{
+    match term {
+        Term::Map(map) => map.map.into_iter().try_for_each(|(k, v)| f(k, v)),
+        value => Err(aux::unexpected_type(value, "Map", keep)),
+    }
+}
+
+fn atom_map_from_term(term: Term, keep: bool) -> Result<AtomMap, DecodeError> {
+    match term {
+        Term::Map(map) => {
+            AtomMap::try_from(map).map_err(|e| aux::unexpected_type(e.key, "atom", keep))
+        }
+        value => Err(aux::unexpected_type(value, "AtomMap", keep)),
+    }
+}
+
+impl<R: io::Read + io::Seek> Decoder<R> {
+    /// Skips the next term as [`Decoder::skip_term`] does, but seeks over the bytes of large
+    /// binaries, strings, big integers and funs instead of reading them.
+    ///
+    /// Compressed terms are still inflated, as the end of their zlib stream is not known
+    /// otherwise. As seeking past the end of the input succeeds, a term which is cut short
+    /// within such bytes is only noticed by the next read.
+    pub fn skip_term_fast(&mut self) -> Result<(), DecodeError> {
+        self.guarded(|decoder| decoder.skip_versioned(seek_over))
+    }
+    /// Skips the next term, which has no version byte, as [`Decoder::skip_term_fast`] does.
+    pub(crate) fn skip_subterm_fast(&mut self) -> Result<(), DecodeError> {
+        let tag = self.reader.read_u8()?;
+        let result = self.skip_term_with_tag(tag, seek_over);
+        self.reader.check(result)
+    }
+}
+
+impl<R: io::BufRead> Decoder<BufReadSource<R>> {
+    /// Makes a decoder which reads the tags, lengths and other short fields of the terms from the
+    /// buffer of `reader` instead of reading them one by one.
+    ///
+    /// It decodes the same terms (and fails with the same errors) as [`Decoder::new`].
+    pub fn from_buf_read(reader: R) -> Self {
+        Decoder::new(BufReadSource(reader))
+    }
+}
+
+/// Reader of a decoder made by [`Decoder::from_buf_read`].
+#[derive(Debug)]
+pub struct BufReadSource<R>(R);
+impl<R> BufReadSource<R> {
+    pub fn into_inner(self) -> R {
+        self.0
+    }
+}
+impl<R: io::BufRead> io::Read for BufReadSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.0.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.0.consume(len);
+        Ok(len)
+    }
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let available = self.0.fill_buf()?;
+        if let Some(bytes) = available.get(..buf.len()) {
+            buf.copy_from_slice(bytes);
+            self.0.consume(buf.len());
+            return Ok(());
+        }
+        // The bytes straddle the end of the buffer (or do not fit in it).
+        self.0.read_exact(buf)
     }
 }
 
+/// Encoder of terms into a writer, which can be borrowed (`&mut W`) as the reader of a
+/// [`Decoder`].
+///
+/// The encoder writes each field of a term separately and does not buffer them, so a writer
+/// which makes a system call per write (e.g., a [`File`](std::fs::File) or a
+/// [`TcpStream`](std::net::TcpStream)) should be wrapped in an [`io::BufWriter`], as
+/// [`Encoder::buffered`] does.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Atom, Encoder, Term};
+///
+/// let mut encoder = Encoder::buffered(Vec::new(), 8 * 1024);
+/// encoder.encode_next(&Term::from(Atom::from("a"))).unwrap();
+/// encoder.encode_next(&Term::from(Atom::from("b"))).unwrap();
+/// let bytes = encoder.into_inner().into_inner().unwrap();
+/// assert_eq!(bytes, [131, 100, 0, 1, 97, 131, 100, 0, 1, 98]);
+/// ```
 pub struct Encoder<W> {
     pub(crate) writer: W,
+    pub(crate) atom_cache_refs: HashMap<Atom, u8>,
+    pub(crate) options: EncoderOptions,
+    metrics: Option<Arc<dyn CodecMetrics>>,
 }
 impl<W: io::Write> Encoder<W> {
     pub fn new(writer: W) -> Self {
-        Encoder { writer }
+        Self::with_options(writer, EncoderOptions::default())
+    }
+    pub fn with_options(writer: W, options: EncoderOptions) -> Self {
+        Encoder {
+            writer,
+            atom_cache_refs: HashMap::new(),
+            options,
+            metrics: None,
+        }
+    }
+    /// Makes the encoder report each encoded term to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn CodecMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    pub fn encode(self, term: &Term) -> EncodeResult {
+        if self.options.compressed {
+            return self.encode_compressed(term);
+        }
+        match Observer::encode(self.metrics.as_ref(), term, false) {
+            None => self.encode_versioned(term),
+            Some(observer) => self.encode_observed(observer, |e| e.encode_versioned(term)),
+        }
+    }
+    /// Encodes the next term into the writer, keeping the encoder (and its writer) for more.
+    pub fn encode_next(&mut self, term: &Term) -> EncodeResult {
+        let mut encoder = Encoder::with_options(&mut self.writer, self.options);
+        encoder.metrics = self.metrics.clone();
+        encoder.encode(term)
+    }
+    /// Flushes the writer (e.g., the buffer of [`Encoder::buffered`]).
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
     }
-    pub fn encode(mut self, term: &Term) -> EncodeResult {
+    /// Returns the writer, which is not flushed.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+    fn encode_versioned(mut self, term: &Term) -> EncodeResult {
         self.writer.write_u8(VERSION)?;
         self.encode_term(term)
     }
+    /// Encodes `term` as a `COMPRESSED_TERM` (i.e., as `term_to_binary(Term, [compressed])` does).
+    pub fn encode_compressed(self, term: &Term) -> EncodeResult {
+        match Observer::encode(self.metrics.as_ref(), term, true) {
+            None => self.encode_compressed_versioned(term),
+            Some(observer) => {
+                self.encode_observed(observer, |e| e.encode_compressed_versioned(term))
+            }
+        }
+    }
+    fn encode_compressed_versioned(mut self, term: &Term) -> EncodeResult {
+        let mut body = Vec::new();
+        Encoder::with_options(&mut body, self.options).encode_term(term)?;
+        write_compressed(&mut self.writer, &body)
+    }
+    /// Encodes `term` with the tags of `layout`, which [`Decoder::decode_with_layout`] recorded
+    /// when decoding it, so that a term which is left unchanged is encoded into the same bytes.
+    ///
+    /// `on_fallback` is told each departure from the layout (see [`fidelity`](crate::fidelity)).
+    pub fn encode_with_layout(
+        mut self,
+        term: &Term,
+        layout: &WireLayout,
+        mut on_fallback: impl FnMut(Fallback),
+    ) -> EncodeResult {
+        fidelity::encode(
+            &mut self.writer,
+            term,
+            layout,
+            self.options,
+            &mut on_fallback,
+        )
+    }
+    /// Runs `encode` with an encoder which counts the bytes written to this one's writer.
+    fn encode_observed(
+        self,
+        observer: Observer,
+        encode: impl FnOnce(Encoder<&mut Counting<W>>) -> EncodeResult,
+    ) -> EncodeResult {
+        let mut writer = Counting::new(self.writer);
+        let encoder = Encoder {
+            writer: &mut writer,
+            atom_cache_refs: self.atom_cache_refs,
+            options: self.options,
+            metrics: None,
+        };
+        let result = observer.in_scope(|| encode(encoder));
+        observer.finish_encode(&result, writer.count);
+        result
+    }
+    /// Encodes the 2-tuple `{prefix, <<payload>>}` without building it as a term.
+    ///
+    /// This saves copying `payload` into a [`Binary`] for every message it is sent in.
+    pub fn encode_parts(mut self, prefix: &Term, payload: &[u8]) -> EncodeResult {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| EncodeError::TooLargeBinary { len: payload.len() })?;
+        self.writer.write_u8(VERSION)?;
+        self.writer.write_u8(SMALL_TUPLE_EXT)?;
+        self.writer.write_u8(2)?;
+        self.encode_term(prefix)?;
+        self.writer.write_u8(BINARY_EXT)?;
+        self.writer.write_u32::<BigEndian>(len)?;
+        self.writer.write_all(payload)?;
+        Ok(())
+    }
+    /// Encodes the elements of `iter` as a list, converting each one when it is written rather
+    /// than collecting them into a [`List`] first.
+    ///
+    /// `LIST_EXT` starts with the number of elements, so the iterator must know it (see
+    /// [`Encoder::encode_list_from_buffered_iter`] for iterators which do not). The list is
+    /// always encoded as a `LIST_EXT`, even if its elements are bytes.
+    ///
+    /// ```
+    /// use eetf::{Encoder, List, Term};
+    ///
+    /// let mut bytes = Vec::new();
+    /// Encoder::new(&mut bytes).encode_list_from_iter(1..4).unwrap();
+    /// let list = List::from(vec![Term::from(1), Term::from(2), Term::from(3)]);
+    /// assert_eq!(eetf::decode_from_slice(&bytes).unwrap(), Term::from(list));
+    /// ```
+    pub fn encode_list_from_iter<I>(&mut self, iter: I) -> EncodeResult
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: IntoTerm,
+    {
+        let mut iter = iter.into_iter();
+        self.encode_list_with(iter.len(), None, |sink| {
+            iter.try_for_each(|e| sink.encode_element(&e.into_term()))
+        })
+    }
+    /// Encodes the elements of `sequence` as a list (improper if it has a tail), as
+    /// [`Encoder::encode_list_from_iter`] does.
+    ///
+    /// ```
+    /// use eetf::{Encoder, List, Term};
+    /// use std::collections::VecDeque;
+    ///
+    /// let queue = VecDeque::from([1, 2, 3]);
+    /// let mut bytes = Vec::new();
+    /// Encoder::new(&mut bytes).encode_sequence_as_list(&queue).unwrap();
+    /// let list = List::from(vec![Term::from(1), Term::from(2), Term::from(3)]);
+    /// assert_eq!(eetf::decode_from_slice(&bytes).unwrap(), Term::from(list));
+    /// ```
+    pub fn encode_sequence_as_list<S>(&mut self, sequence: &S) -> EncodeResult
+    where
+        S: ToTermSequence + ?Sized,
+    {
+        let tail = sequence.tail();
+        self.encode_list_with(sequence.element_count(), tail.as_ref(), |sink| {
+            sequence.encode_elements(sink)
+        })
+    }
+    /// Encodes a list of `len` elements, which `elements` gives to a sink, and of `tail`.
+    fn encode_list_with<F>(&mut self, len: usize, tail: Option<&Term>, elements: F) -> EncodeResult
+    where
+        F: FnOnce(&mut dyn ElementSink) -> EncodeResult,
+    {
+        self.writer.write_u8(VERSION)?;
+        if len > 0 {
+            self.writer.write_u8(LIST_EXT)?;
+            self.writer.write_u32::<BigEndian>(len as u32)?;
+        }
+        let mut sink = ListElements {
+            encoder: self,
+            len,
+            count: 0,
+        };
+        elements(&mut sink)?;
+        if sink.count < len {
+            return Err(EncodeError::IteratorLengthMismatch { len });
+        }
+        match tail {
+            Some(tail) => self
+                .encode_term(tail)
+                .map_err(|e| e.within(EncodePathSegment::ListTail)),
+            None => self.encode_nil(),
+        }
+    }
+    /// Encodes the elements of `iter` as a list as [`Encoder::encode_list_from_iter`] does, for
+    /// iterators which do not know their length.
+    ///
+    /// The elements are encoded into a buffer until the length is known, so the memory used is
+    /// the size of their encoding rather than the size of the terms.
+    pub fn encode_list_from_buffered_iter<I>(&mut self, iter: I) -> EncodeResult
+    where
+        I: IntoIterator,
+        I::Item: IntoTerm,
+    {
+        let (len, body) = encode_buffered(iter, self.options)?;
+        self.writer.write_u8(VERSION)?;
+        if len > 0 {
+            self.writer.write_u8(LIST_EXT)?;
+            self.writer.write_u32::<BigEndian>(len as u32)?;
+            self.writer.write_all(&body)?;
+        }
+        self.encode_nil()
+    }
+    /// Encodes the `(key, value)` pairs of `iter` as a map, without collecting them into a
+    /// [`Map`] first.
+    ///
+    /// The entries are written in the order of the iterator, so the keys should be unique.
+    /// With [`EncoderOptions::deterministic`], the entries are collected and sorted after all.
+    pub fn encode_map_from_iter<I, K, V>(&mut self, iter: I) -> EncodeResult
+    where
+        I: IntoIterator<Item = (K, V)>,
+        I::IntoIter: ExactSizeIterator,
+        K: IntoTerm,
+        V: IntoTerm,
+    {
+        let iter = iter.into_iter();
+        if self.options.deterministic || !self.options.map_tag {
+            let map = Map::from(
+                iter.map(|(k, v)| (k.into_term(), v.into_term()))
+                    .collect::<HashMap<_, _>>(),
+            );
+            self.writer.write_u8(VERSION)?;
+            return self.encode_map(&map);
+        }
+        let len = iter.len();
+        self.writer.write_u8(VERSION)?;
+        self.writer.write_u8(MAP_EXT)?;
+        self.writer.write_u32::<BigEndian>(len as u32)?;
+        for entry in aux::exact(iter, len) {
+            let (_, (k, v)) = entry?;
+            let k = k.into_term();
+            self.encode_term(&k)
+                .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+            self.encode_term(&v.into_term()).map_err(|e| {
+                e.within_with(|| EncodePathSegment::MapValue { key: k.to_string() })
+            })?;
+        }
+        Ok(())
+    }
+    /// Encodes a distribution message (i.e., the data following the 4 byte length of a packet).
+    ///
+    /// The atoms to be cached are assigned entries of `cache` (evicting the atoms
+    /// which occupied them), and are encoded as `ATOM_CACHE_REF`s.
+    pub fn encode_distribution_message(
+        &mut self,
+        control: &Term,
+        payload: Option<&Term>,
+        cache: &mut AtomCache,
+    ) -> EncodeResult {
+        let (header, refs) = dist::encode_distribution_header(control, payload, cache);
+        self.writer.write_all(&header)?;
+        self.atom_cache_refs = refs;
+        let result = self.encode_distribution_terms(control, payload);
+        self.atom_cache_refs.clear();
+        result
+    }
+    fn encode_distribution_terms(
+        &mut self,
+        control: &Term,
+        payload: Option<&Term>,
+    ) -> EncodeResult {
+        self.encode_term(control)?;
+        if let Some(payload) = payload {
+            self.encode_term(payload)?;
+        }
+        Ok(())
+    }
     pub(crate) fn encode_term(&mut self, term: &Term) -> EncodeResult {
         match *term {
             Term::Atom(ref x) => self.encode_atom(x),
@@ -383,9 +1975,14 @@ impl<W: io::Write> Encoder<W> {
             Term::ImproperList(ref x) => self.encode_improper_list(x),
             Term::Tuple(ref x) => self.encode_tuple(x),
             Term::Map(ref x) => self.encode_map(x),
-            Term::ByteList(ref x) => self.encode_byte_list(x.bytes.as_slice())
+            Term::ByteList(ref x) => self.encode_byte_list(x.bytes.as_slice()),
+            Term::Raw(ref x) => self.encode_raw(x),
         }
     }
+    fn encode_raw(&mut self, x: &Raw) -> EncodeResult {
+        self.writer.write_all(&x.bytes)?;
+        Ok(())
+    }
     pub(crate) fn encode_nil(&mut self) -> EncodeResult {
         self.writer.write_u8(NIL_EXT)?;
         Ok(())
@@ -393,10 +1990,11 @@ impl<W: io::Write> Encoder<W> {
     pub(crate) fn encode_list(&mut self, x: &List) -> EncodeResult {
         let to_byte = |e: &Term| {
             e.try_as_ref()
-                .and_then(|&FixInteger { value: i }| if i < 0x100 { Some(i as u8) } else { None })
+                .and_then(|&FixInteger { value: i }| u8::try_from(i).ok())
         };
-        if !x.elements.is_empty()
-            && x.elements.len() <= std::u16::MAX as usize
+        if self.options.string_ext
+            && !x.elements.is_empty()
+            && x.elements.len() <= u16::MAX as usize
             && x.elements.iter().all(|e| to_byte(e).is_some())
         {
             self.writer.write_u8(STRING_EXT)?;
@@ -410,8 +2008,9 @@ impl<W: io::Write> Encoder<W> {
                 self.writer.write_u8(LIST_EXT)?;
                 self.writer
                     .write_u32::<BigEndian>(x.elements.len() as u32)?;
-                for e in &x.elements {
-                    self.encode_term(e)?;
+                for (i, e) in x.elements.iter().enumerate() {
+                    self.encode_term(e)
+                        .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
                 }
             }
             self.encode_nil()?;
@@ -422,10 +2021,12 @@ impl<W: io::Write> Encoder<W> {
         self.writer.write_u8(LIST_EXT)?;
         self.writer
             .write_u32::<BigEndian>(x.elements.len() as u32)?;
-        for e in &x.elements {
-            self.encode_term(e)?;
+        for (i, e) in x.elements.iter().enumerate() {
+            self.encode_term(e)
+                .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
         }
-        self.encode_term(&x.last)?;
+        self.encode_term(&x.last)
+            .map_err(|e| e.within(EncodePathSegment::ListTail))?;
         Ok(())
     }
     pub(crate) fn encode_tuple(&mut self, x: &Tuple) -> EncodeResult {
@@ -437,21 +2038,42 @@ impl<W: io::Write> Encoder<W> {
             self.writer
                 .write_u32::<BigEndian>(x.elements.len() as u32)?;
         }
-        for e in &x.elements {
-            self.encode_term(e)?;
+        for (i, e) in x.elements.iter().enumerate() {
+            self.encode_term(e)
+                .map_err(|e| e.within(EncodePathSegment::TupleElement(i)))?;
         }
         Ok(())
     }
     pub(crate) fn encode_map(&mut self, x: &Map) -> EncodeResult {
+        if !self.options.map_tag {
+            return Err(aux::unsupported_by_peer(
+                Term::from(x.clone()),
+                DistFlags::MAP_TAG,
+            ));
+        }
         self.writer.write_u8(MAP_EXT)?;
         self.writer.write_u32::<BigEndian>(x.map.len() as u32)?;
-        for (k, v) in x.map.iter() {
-            self.encode_term(k)?;
-            self.encode_term(v)?;
+        for (k, v) in canonical::map_entries(x, self.options.deterministic) {
+            self.encode_term(k)
+                .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+            self.encode_term(v).map_err(|e| {
+                e.within_with(|| EncodePathSegment::MapValue { key: k.to_string() })
+            })?;
         }
         Ok(())
     }
-    pub(crate) fn encode_byte_list(&mut self, x: &[u8]) -> EncodeResult{
+    pub(crate) fn encode_byte_list(&mut self, x: &[u8]) -> EncodeResult {
+        if !self.options.string_ext {
+            if !x.is_empty() {
+                self.writer.write_u8(LIST_EXT)?;
+                self.writer.write_u32::<BigEndian>(x.len() as u32)?;
+                for &b in x {
+                    self.writer.write_u8(SMALL_INTEGER_EXT)?;
+                    self.writer.write_u8(b)?;
+                }
+            }
+            return self.encode_nil();
+        }
         self.writer.write_u8(STRING_EXT)?;
         self.writer.write_u16::<BigEndian>(x.len() as u16)?;
         self.writer.write_all(x)?;
@@ -465,6 +2087,12 @@ impl<W: io::Write> Encoder<W> {
         Ok(())
     }
     pub(crate) fn encode_bit_binary(&mut self, x: &BitBinary) -> EncodeResult {
+        if !self.options.bit_binaries {
+            return Err(aux::unsupported_by_peer(
+                Term::from(x.clone()),
+                DistFlags::BIT_BINARIES,
+            ));
+        }
         self.writer.write_u8(BIT_BINARY_EXT)?;
         self.writer.write_u32::<BigEndian>(x.bytes.len() as u32)?;
         self.writer.write_u8(x.tail_bits_size)?;
@@ -476,51 +2104,101 @@ impl<W: io::Write> Encoder<W> {
         Ok(())
     }
     pub(crate) fn encode_float(&mut self, x: &Float) -> EncodeResult {
-        self.writer.write_u8(NEW_FLOAT_EXT)?;
-        self.writer.write_f64::<BigEndian>(x.value)?;
+        if self.options.new_floats {
+            self.writer.write_u8(NEW_FLOAT_EXT)?;
+            self.writer.write_f64::<BigEndian>(x.value)?;
+        } else {
+            let bytes = aux::float_ext_bytes(x.value)
+                .ok_or_else(|| EncodeError::NonFiniteFloat(x.clone(), EncodePath::default()))?;
+            self.writer.write_u8(FLOAT_EXT)?;
+            self.writer.write_all(&bytes)?;
+        }
         Ok(())
     }
     pub(crate) fn encode_atom(&mut self, x: &Atom) -> EncodeResult {
+        if let Some(&index) = self.atom_cache_refs.get(x) {
+            self.writer.write_u8(ATOM_CACHE_REF)?;
+            self.writer.write_u8(index)?;
+            return Ok(());
+        }
         if x.name.len() > 0xFFFF {
-            return Err(EncodeError::TooLongAtomName(x.clone()));
+            return Err(EncodeError::TooLongAtomName(
+                x.clone(),
+                EncodePath::default(),
+            ));
         }
 
         let is_ascii = x.name.as_bytes().iter().all(|&c| c < 0x80);
         if is_ascii {
             self.writer.write_u8(ATOM_EXT)?;
-        } else {
+        } else if self.options.utf8_atoms {
             self.writer.write_u8(ATOM_UTF8_EXT)?;
+        } else {
+            let latin1 = aux::to_latin1(&x.name).ok_or_else(|| {
+                aux::unsupported_by_peer(Term::from(x.clone()), DistFlags::UTF8_ATOMS)
+            })?;
+            self.writer.write_u8(ATOM_EXT)?;
+            self.writer.write_u16::<BigEndian>(latin1.len() as u16)?;
+            self.writer.write_all(&latin1)?;
+            return Ok(());
         }
         self.writer.write_u16::<BigEndian>(x.name.len() as u16)?;
         self.writer.write_all(x.name.as_bytes())?;
         Ok(())
     }
     pub(crate) fn encode_fix_integer(&mut self, x: &FixInteger) -> EncodeResult {
-        if 0 <= x.value && x.value <= i32::from(std::u8::MAX) {
+        if 0 <= x.value && x.value <= i32::from(u8::MAX) {
             self.writer.write_u8(SMALL_INTEGER_EXT)?;
             self.writer.write_u8(x.value as u8)?;
         } else {
             self.writer.write_u8(INTEGER_EXT)?;
-            self.writer.write_i32::<BigEndian>(x.value as i32)?;
+            self.writer.write_i32::<BigEndian>(x.value)?;
         }
         Ok(())
     }
     pub(crate) fn encode_big_integer(&mut self, x: &BigInteger) -> EncodeResult {
-        let (sign, bytes) = x.value.to_bytes_le();
-        if bytes.len() <= std::u8::MAX as usize {
+        // The digits are written from the words of the magnitude, without collecting its
+        // bytes (zero still has one digit).
+        let len = x.value.bits().div_ceil(8).max(1);
+        if len <= u64::from(u8::MAX) {
             self.writer.write_u8(SMALL_BIG_EXT)?;
-            self.writer.write_u8(bytes.len() as u8)?;
-        } else if bytes.len() <= std::u32::MAX as usize {
+            self.writer.write_u8(len as u8)?;
+        } else if len <= u64::from(u32::MAX) {
             self.writer.write_u8(LARGE_BIG_EXT)?;
-            self.writer.write_u32::<BigEndian>(bytes.len() as u32)?;
+            self.writer.write_u32::<BigEndian>(len as u32)?;
         } else {
-            return Err(EncodeError::TooLargeInteger(x.clone()));
+            return Err(EncodeError::TooLargeInteger(
+                x.clone(),
+                EncodePath::default(),
+            ));
+        }
+        self.writer.write_u8(aux::sign_to_byte(x.value.sign()))?;
+        let mut remaining = len as usize;
+        for word in x.value.magnitude().iter_u64_digits() {
+            let bytes = word.to_le_bytes();
+            let n = remaining.min(bytes.len());
+            self.writer.write_all(&bytes[..n])?;
+            remaining -= n;
+        }
+        if remaining > 0 {
+            self.writer.write_all(&[0])?;
         }
-        self.writer.write_u8(aux::sign_to_byte(sign))?;
-        self.writer.write_all(&bytes)?;
         Ok(())
     }
     pub(crate) fn encode_pid(&mut self, x: &Pid) -> EncodeResult {
+        if !self.options.v4_nc {
+            aux::check_range(aux::PID_ID_RANGE, u64::from(x.id))?;
+            aux::check_range(aux::PID_SERIAL_RANGE, u64::from(x.serial))?;
+        }
+        if !self.options.big_creation {
+            let creation = aux::small_creation(x.creation)?;
+            self.writer.write_u8(PID_EXT)?;
+            self.encode_atom(&x.node)?;
+            self.writer.write_u32::<BigEndian>(x.id)?;
+            self.writer.write_u32::<BigEndian>(x.serial)?;
+            self.writer.write_u8(creation)?;
+            return Ok(());
+        }
         self.writer.write_u8(NEW_PID_EXT)?;
         self.encode_atom(&x.node)?;
         self.writer.write_u32::<BigEndian>(x.id)?;
@@ -530,10 +2208,26 @@ impl<W: io::Write> Encoder<W> {
     }
     pub(crate) fn encode_port(&mut self, x: &Port) -> EncodeResult {
         if (x.id >> 32) & 0xFFFFFFFF == 0 {
+            if !self.options.v4_nc {
+                aux::check_range(aux::PORT_ID_RANGE, x.id)?;
+            }
+            if !self.options.big_creation {
+                let creation = aux::small_creation(x.creation)?;
+                self.writer.write_u8(PORT_EXT)?;
+                self.encode_atom(&x.node)?;
+                self.writer.write_u32::<BigEndian>(x.id as u32)?;
+                self.writer.write_u8(creation)?;
+                return Ok(());
+            }
             self.writer.write_u8(NEW_PORT_EXT)?;
             self.encode_atom(&x.node)?;
             self.writer.write_u32::<BigEndian>(x.id as u32)?;
             self.writer.write_u32::<BigEndian>(x.creation)?;
+        } else if !self.options.v4_nc {
+            return Err(aux::unsupported_by_peer(
+                Term::from(x.clone()),
+                DistFlags::V4_NC,
+            ));
         } else {
             self.writer.write_u8(V4_PORT_EXT)?;
             self.encode_atom(&x.node)?;
@@ -543,10 +2237,30 @@ impl<W: io::Write> Encoder<W> {
         Ok(())
     }
     pub(crate) fn encode_reference(&mut self, x: &Reference) -> EncodeResult {
-        self.writer.write_u8(NEWER_REFERENCE_EXT)?;
-        if x.id.len() > std::u16::MAX as usize {
-            return Err(EncodeError::TooLargeReferenceId(x.clone()));
+        if x.id.len() > u16::MAX as usize {
+            return Err(EncodeError::TooLargeReferenceId(
+                x.clone(),
+                EncodePath::default(),
+            ));
         }
+        if !self.options.v4_nc {
+            aux::check_range(aux::REFERENCE_LEN_RANGE, x.id.len() as u64)?;
+            if let Some(&first) = x.id.first() {
+                aux::check_range(aux::REFERENCE_WORD_RANGE, u64::from(first))?;
+            }
+        }
+        if !self.options.big_creation {
+            let creation = aux::small_creation(x.creation)?;
+            self.writer.write_u8(NEW_REFERENCE_EXT)?;
+            self.writer.write_u16::<BigEndian>(x.id.len() as u16)?;
+            self.encode_atom(&x.node)?;
+            self.writer.write_u8(creation)?;
+            for n in &x.id {
+                self.writer.write_u32::<BigEndian>(*n)?;
+            }
+            return Ok(());
+        }
+        self.writer.write_u8(NEWER_REFERENCE_EXT)?;
         self.writer.write_u16::<BigEndian>(x.id.len() as u16)?;
         self.encode_atom(&x.node)?;
         self.writer.write_u32::<BigEndian>(x.creation)?;
@@ -556,6 +2270,12 @@ impl<W: io::Write> Encoder<W> {
         Ok(())
     }
     pub(crate) fn encode_external_fun(&mut self, x: &ExternalFun) -> EncodeResult {
+        if !self.options.export_ptr_tag {
+            return Err(aux::unsupported_by_peer(
+                Term::from(x.clone()),
+                DistFlags::EXPORT_PTR_TAG,
+            ));
+        }
         self.writer.write_u8(EXPORT_EXT)?;
         self.encode_atom(&x.module)?;
         self.encode_atom(&x.function)?;
@@ -577,41 +2297,261 @@ impl<W: io::Write> Encoder<W> {
                 self.encode_atom(module)?;
                 self.encode_fix_integer(&FixInteger::from(index))?;
                 self.encode_fix_integer(&FixInteger::from(uniq))?;
-                for v in free_vars {
-                    self.encode_term(v)?;
+                for (i, v) in free_vars.iter().enumerate() {
+                    self.encode_term(v)
+                        .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
                 }
             }
-            InternalFun::New {
-                ref module,
-                arity,
-                ref pid,
-                ref free_vars,
-                index,
-                ref uniq,
-                old_index,
-                old_uniq,
-            } => {
-                self.writer.write_u8(NEW_FUN_EXT)?;
-
-                let mut buf = Vec::new();
-                {
-                    let mut tmp = Encoder::new(&mut buf);
-                    tmp.writer.write_u8(arity)?;
-                    tmp.writer.write_all(uniq)?;
-                    tmp.writer.write_u32::<BigEndian>(index)?;
-                    tmp.writer.write_u32::<BigEndian>(free_vars.len() as u32)?;
-                    tmp.encode_atom(module)?;
-                    tmp.encode_fix_integer(&FixInteger::from(old_index))?;
-                    tmp.encode_fix_integer(&FixInteger::from(old_uniq))?;
-                    tmp.encode_pid(pid)?;
-                    for v in free_vars {
-                        tmp.encode_term(v)?;
-                    }
+            InternalFun::New { .. } => {
+                if !self.options.new_fun_tags {
+                    return Err(aux::unsupported_by_peer(
+                        Term::from(x.clone()),
+                        DistFlags::NEW_FUN_TAGS,
+                    ));
                 }
-                self.writer.write_u32::<BigEndian>(4 + buf.len() as u32)?;
-                self.writer.write_all(&buf)?;
+                let size = new_fun_size(x, self.options, &mut self.atom_cache_refs)?;
+                self.writer.write_u8(NEW_FUN_EXT)?;
+                self.writer.write_u32::<BigEndian>(size)?;
+                self.encode_new_fun_body(x)?;
+            }
+        }
+        Ok(())
+    }
+    /// Encodes the fields of `NEW_FUN_EXT` following the size.
+    fn encode_new_fun_body(&mut self, x: &InternalFun) -> EncodeResult {
+        if let InternalFun::New {
+            ref module,
+            arity,
+            ref pid,
+            ref free_vars,
+            index,
+            ref uniq,
+            old_index,
+            old_uniq,
+        } = *x
+        {
+            self.writer.write_u8(arity)?;
+            self.writer.write_all(uniq)?;
+            self.writer.write_u32::<BigEndian>(index)?;
+            self.writer.write_u32::<BigEndian>(free_vars.len() as u32)?;
+            self.encode_atom(module)?;
+            self.encode_fix_integer(&FixInteger::from(old_index))?;
+            self.encode_fix_integer(&FixInteger::from(old_uniq))?;
+            self.encode_pid(pid)?;
+            for (i, v) in free_vars.iter().enumerate() {
+                self.encode_term(v)
+                    .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
             }
         }
         Ok(())
     }
 }
+
+/// Writes `body` (the encoding of a term) as a `COMPRESSED_TERM`.
+pub(crate) fn write_compressed<W: io::Write>(writer: &mut W, body: &[u8]) -> EncodeResult {
+    let len = u32::try_from(body.len()).map_err(|_| EncodeError::TooLargeFrame {
+        len: body.len(),
+        max: u32::MAX as usize,
+    })?;
+    writer.write_u8(VERSION)?;
+    writer.write_u8(COMPRESSED_TERM)?;
+    writer.write_u32::<BigEndian>(len)?;
+    let mut zlib_encoder = zlib::Backend::encoder(writer)?;
+    io::Write::write_all(&mut zlib_encoder, body)?;
+    zlib::Backend::finish(zlib_encoder)?;
+    Ok(())
+}
+
+impl<W: io::Write> Encoder<io::BufWriter<W>> {
+    /// Makes an encoder which writes to `writer` through a buffer of `capacity` bytes.
+    ///
+    /// The buffer is written out when it fills up, by [`Encoder::flush`], and when the
+    /// [`io::BufWriter`] returned by [`Encoder::into_inner`] is flushed or dropped (which would
+    /// ignore errors).
+    pub fn buffered(writer: W, capacity: usize) -> Self {
+        Encoder::new(io::BufWriter::with_capacity(capacity, writer))
+    }
+}
+
+/// Encodes the elements of `iter` one after another into a buffer, returning their number and
+/// the buffer.
+pub(crate) fn encode_buffered<I>(
+    iter: I,
+    options: EncoderOptions,
+) -> Result<(usize, Vec<u8>), EncodeError>
+where
+    I: IntoIterator,
+    I::Item: IntoTerm,
+{
+    let mut body = Vec::new();
+    let mut encoder = Encoder::with_options(&mut body, options);
+    let mut len = 0;
+    for e in iter {
+        encoder
+            .encode_term(&e.into_term())
+            .map_err(|e| e.within(EncodePathSegment::ListElement(len)))?;
+        len += 1;
+    }
+    Ok((len, body))
+}
+
+/// Reads the rest of the zlib stream of a compressed term (i.e., its checksum), so that
+/// the next term can be read after it.
+fn finish_compressed<R: io::Read>(zlib_decoder: &mut R) -> Result<(), DecodeError> {
+    let trailing = io::copy(zlib_decoder, &mut io::sink())?;
+    if trailing != 0 {
+        return Err(DecodeError::InvalidData {
+            message: format!("{} bytes follow the compressed term", trailing),
+        });
+    }
+    Ok(())
+}
+
+/// Skips the given number of bytes of a reader.
+type SkipBytes<R> = fn(&mut Tracked<R>, u64) -> io::Result<()>;
+
+/// Bytes which [`Decoder::skip_term_fast`] reads rather than seeks over, so that the buffers of
+/// readers such as `BufReader` (which seeking discards) are not thrown away for short fields.
+const MIN_SEEK_LEN: u64 = 64 * 1024;
+
+/// Reads and discards `len` bytes.
+fn discard<R: io::Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    if io::copy(&mut io::Read::take(reader, len), &mut io::sink())? < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(())
+}
+
+/// Seeks over `len` bytes, or discards them if there are only a few.
+fn seek_over<R: io::Read + io::Seek>(reader: &mut Tracked<R>, len: u64) -> io::Result<()> {
+    if len < MIN_SEEK_LEN {
+        return discard(reader, len);
+    }
+    reader.seek_forward(len)
+}
+
+/// Reads `size` bytes into `buf` (replacing its contents), growing it as the bytes arrive
+/// once there are more than [`MAX_PREALLOCATED_LEN`].
+fn read_exact_bounded<R: io::Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    size: usize,
+) -> io::Result<()> {
+    let mut len = size.min(MAX_PREALLOCATED_LEN);
+    buf.clear();
+    // Exactly, so that the buffer of a binary is not reallocated when it becomes a `Bytes`.
+    buf.reserve_exact(len);
+    buf.resize(len, 0);
+    reader.read_exact(buf)?;
+    while len < size {
+        let start = len;
+        len = size.min(len * 2);
+        buf.reserve_exact(len - start);
+        buf.resize(len, 0);
+        reader.read_exact(&mut buf[start..])?;
+    }
+    Ok(())
+}
+
+/// Reads `size` bytes into `buf` (replacing its contents) by chunks of [`aux::CHUNK_LEN`], so
+/// that it only grows as the bytes arrive.
+fn read_chunked<R: io::Read>(reader: &mut R, buf: &mut Vec<u8>, size: usize) -> io::Result<()> {
+    buf.clear();
+    while buf.len() < size {
+        let start = buf.len();
+        buf.resize(size.min(start + aux::CHUNK_LEN), 0);
+        reader.read_exact(&mut buf[start..])?;
+    }
+    Ok(())
+}
+
+/// Reads a name of `size` bytes of Latin-1 or UTF-8 into a string made of the scratch buffer
+/// `buf`, by chunks which are converted or validated as they arrive, so that an invalid or
+/// truncated name fails before the memory for the rest of it is taken.
+fn read_name<R: io::Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    size: usize,
+    latin1: bool,
+) -> io::Result<String> {
+    let mut name = aux::take_name_buf(buf);
+    let mut chunk = [0; aux::CHUNK_LEN];
+    let (mut carried, mut remaining) = (0, size);
+    while remaining > 0 {
+        let len = remaining.min(aux::CHUNK_LEN - carried);
+        reader.read_exact(&mut chunk[carried..carried + len])?;
+        remaining -= len;
+        carried =
+            aux::push_name_chunk(&mut name, &mut chunk, carried + len, remaining == 0, latin1)?;
+    }
+    Ok(name)
+}
+
+/// Writer which only counts the written bytes.
+#[derive(Default)]
+struct ByteCounter(usize);
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the number of bytes which [`Term::encode`] writes for `term`.
+pub(crate) fn encoded_size(term: &Term) -> Result<usize, EncodeError> {
+    let mut counter = ByteCounter::default();
+    Encoder::new(&mut counter).encode(term)?;
+    Ok(counter.0)
+}
+
+/// Encodes `term` into `buf` as [`Term::encode`] does, returning the number of written bytes.
+///
+/// The bytes are written directly into the slice, so that nothing is allocated, whether or not
+/// the slice is large enough. If it is too small, the error tells the size of the encoding
+/// (see [`Term::encoded_size`]), and the contents of the slice are unspecified.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Atom, EncodeError, Term};
+///
+/// let term = Term::from(Atom::from("foo"));
+/// let mut buf = [0; 16];
+/// let len = eetf::encode_into(&term, &mut buf).unwrap();
+/// assert_eq!(buf[..len], [131, 100, 0, 3, 102, 111, 111]);
+///
+/// let error = eetf::encode_into(&term, &mut buf[..4]).unwrap_err();
+/// assert!(matches!(error, EncodeError::BufferTooSmall { needed: 7, available: 4 }));
+/// ```
+pub fn encode_into(term: &Term, buf: &mut [u8]) -> Result<usize, EncodeError> {
+    let available = buf.len();
+    let mut writer = buf;
+    match Encoder::new(&mut writer).encode(term) {
+        Ok(()) => Ok(available - writer.len()),
+        Err(EncodeError::Io(e)) if e.kind() == io::ErrorKind::WriteZero => {
+            Err(EncodeError::BufferTooSmall {
+                needed: encoded_size(term)?,
+                available,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the size field of `NEW_FUN_EXT` for `fun` (which includes the field itself),
+/// computed by encoding the fun without storing the bytes.
+pub(crate) fn new_fun_size(
+    fun: &InternalFun,
+    options: EncoderOptions,
+    atom_cache_refs: &mut HashMap<Atom, u8>,
+) -> Result<u32, EncodeError> {
+    let mut counter = Encoder::with_options(ByteCounter::default(), options);
+    counter.atom_cache_refs = std::mem::take(atom_cache_refs);
+    let result = counter.encode_new_fun_body(fun);
+    *atom_cache_refs = counter.atom_cache_refs;
+    result?;
+    Ok(4 + counter.writer.0 as u32)
+}
@@ -1,18 +1,10 @@
 use super::*;
-use crate::convert::TryAsRef;
-use byteorder::BigEndian;
-use byteorder::ReadBytesExt;
-use byteorder::WriteBytesExt;
-use libflate::zlib;
-use num::bigint::BigInt;
-use std::convert::From;
-use std::io;
-use std::io::Write;
-use std::str;
+use crate::dist::DistFlags;
 
 /// Errors which can occur when decoding a term
 #[derive(Debug, thiserror::Error)]
 pub enum DecodeError {
+    #[cfg(feature = "std")]
     #[error("I/O error")]
     Io(#[from] io::Error),
 
@@ -22,33 +14,609 @@ pub enum DecodeError {
     #[error("unknown tag {tag}")]
     UnknownTag { tag: u8 },
 
-    #[error("{value} is not a {expected}")]
-    UnexpectedType { value: Term, expected: String },
+    /// The term (summarized by `found`, which keeps it only with the
+    /// [`keep_offending_term`](DecoderOptions::keep_offending_term) option) has another type than
+    /// `expected`.
+    #[error("{found} is not a {expected}")]
+    UnexpectedType {
+        found: Box<TermSummary>,
+        expected: String,
+    },
 
     #[error("{value} is out of range {range:?}")]
     OutOfRange {
         value: i32,
-        range: std::ops::Range<i32>,
+        range: core::ops::Range<i32>,
     },
 
     #[error("tried to convert non-finite float")]
     NonFiniteFloat,
+
+    #[error("unexpected tag {tag} (expected {expected})")]
+    UnexpectedTag { tag: u8, expected: u8 },
+
+    #[error("atom cache reference {index} is not in the distribution header")]
+    UnknownAtomCacheRef { index: usize },
+
+    #[error("atom cache entry {index} is empty")]
+    EmptyAtomCacheEntry { index: usize },
+
+    #[error("the stream ended at a frame boundary")]
+    EndOfStream,
+
+    #[error("frame of {len} bytes exceeds the maximum of {max} bytes")]
+    TooLargeFrame { len: usize, max: usize },
+
+    #[error("{count} trailing bytes follow the term in the frame")]
+    TrailingBytes { count: usize },
+
+    #[error("the input ended in the middle of a term")]
+    UnexpectedEof,
+
+    #[error("invalid data: {message}")]
+    InvalidData { message: String },
+
+    #[cfg(not(feature = "std"))]
+    #[error("compressed terms cannot be decoded without the std feature")]
+    CompressedTerm,
+
+    #[error("compressed terms cannot be decoded as views")]
+    CompressedView,
+
+    /// The decoders are recursive, so they reject terms nested deeper than
+    /// [`MAX_DECODE_DEPTH`] levels instead of overflowing the stack.
+    #[error("the term is nested deeper than {max} levels")]
+    TooDeep { max: usize },
+
+    /// A limit of [`DecoderOptions`] (named by `limit`) was exceeded.
+    #[error("the {limit} limit of {max} is exceeded")]
+    LimitExceeded { limit: &'static str, max: usize },
+
+    /// An earlier decode failed within a term, and the decoder was not reset since.
+    #[cfg(feature = "std")]
+    #[error("the decoder is poisoned by an earlier error")]
+    Poisoned,
+
+    /// The [`safe`](DecoderOptions::safe) option rejects an atom whose name is not in the atom
+    /// table of the decoder.
+    #[cfg(feature = "std")]
+    #[error("the atom {name:?} is not in the atom table")]
+    UnknownAtom { name: String },
+
+    /// The [`strict`](DecoderOptions::strict) option rejects an anomaly of the term.
+    #[cfg(feature = "std")]
+    #[error("the term is rejected: {warning}")]
+    Rejected { warning: crate::report::Warning },
+
+    /// The decoder does not honor an option which is set, such as
+    /// [`strict`](DecoderOptions::strict) in the async decoders.
+    #[cfg(feature = "std")]
+    #[error("the decoder does not support the {option} option")]
+    UnsupportedOption { option: &'static str },
+
+    #[cfg(feature = "std")]
+    #[error("the file {} is empty", .path.display())]
+    EmptyFile { path: std::path::PathBuf },
+
+    /// The timeout of `AsyncDecoder::decode_with_timeout` (or its deadline) expired after
+    /// `bytes_consumed` bytes were read.
+    #[cfg(feature = "tokio-async")]
+    #[error("the decoder timed out after reading {bytes_consumed} bytes in {elapsed:?}")]
+    Timeout {
+        bytes_consumed: u64,
+        elapsed: core::time::Duration,
+    },
+}
+
+/// Maximum number of characters of [`TermSummary::preview`].
+const PREVIEW_CHARS: usize = 64;
+
+/// Bounded description of a term which failed to decode as the expected type.
+///
+/// A decoder may find any term, e.g., a binary of many megabytes, where it expects an atom, so
+/// the error describes it by its type, its size and the beginning of its text. The term itself
+/// is only kept with the [`keep_offending_term`](DecoderOptions::keep_offending_term) option.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Binary, Term, TermSummary};
+///
+/// let summary = TermSummary::new(&Term::from(Binary::from(vec![0; 1000])));
+/// assert_eq!(summary.type_name, "Binary");
+/// assert_eq!(summary.size, Some(1006));
+/// assert!(summary.truncated);
+/// assert_eq!(summary.value(), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermSummary {
+    /// The variant of the term (see [`Term::variant_name`]).
+    pub type_name: &'static str,
+
+    /// The number of bytes of the encoded term (see [`Term::encoded_size`]), which is not
+    /// computed without the `std` feature.
+    pub size: Option<usize>,
+
+    /// The text of the term (see [`fmt::Display`] for [`Term`]), cut to 64 characters.
+    pub preview: String,
+
+    /// `true` if the preview is not the whole text of the term.
+    pub truncated: bool,
+
+    value: Option<Box<Term>>,
+}
+impl TermSummary {
+    /// Summarizes `term`, without keeping it.
+    pub fn new(term: &Term) -> Self {
+        let mut preview = Preview {
+            text: String::new(),
+            chars: 0,
+            truncated: false,
+        };
+        // The preview fails the formatting once it is full.
+        let _ = fmt::write(&mut preview, format_args!("{}", term));
+        TermSummary {
+            type_name: term.variant_name(),
+            #[cfg(feature = "std")]
+            size: crate::codec::encoded_size(term).ok(),
+            #[cfg(not(feature = "std"))]
+            size: None,
+            preview: preview.text,
+            truncated: preview.truncated,
+            value: None,
+        }
+    }
+
+    /// Summarizes `term`, keeping it.
+    pub fn keeping(term: Term) -> Self {
+        let summary = Self::new(&term);
+        TermSummary {
+            value: Some(Box::new(term)),
+            ..summary
+        }
+    }
+
+    /// Returns the summarized term, if it is kept.
+    pub fn value(&self) -> Option<&Term> {
+        self.value.as_deref()
+    }
+
+    pub fn into_value(self) -> Option<Term> {
+        self.value.map(|value| *value)
+    }
+}
+impl fmt::Display for TermSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.preview)?;
+        if self.truncated {
+            write!(f, "…")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writer of the first [`PREVIEW_CHARS`] characters of a text.
+struct Preview {
+    text: String,
+    chars: usize,
+    truncated: bool,
+}
+impl fmt::Write for Preview {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.chars == PREVIEW_CHARS {
+                self.truncated = true;
+                return Err(fmt::Error);
+            }
+            self.text.push(c);
+            self.chars += 1;
+        }
+        Ok(())
+    }
 }
 
 /// Errors which can occur when encoding a term
 #[derive(Debug, thiserror::Error)]
 pub enum EncodeError {
+    #[cfg(feature = "std")]
     #[error("I/O error")]
     Io(#[from] io::Error),
 
-    #[error("too long atom name: {} bytes", .0.name.len())]
-    TooLongAtomName(Atom),
+    #[error("too long atom name: {} bytes{}", .0.name.len(), .1)]
+    TooLongAtomName(Atom, EncodePath),
+
+    #[error("too large integer value: {} bytes required to encode{}", .0.value.to_bytes_le().1.len(), .1)]
+    TooLargeInteger(BigInteger, EncodePath),
+
+    #[error("too large reference ID: {} bytes required to encode{}", .0.id.len() * 4, .1)]
+    TooLargeReferenceId(Reference, EncodePath),
+
+    #[error("non-finite float {} cannot be encoded as FLOAT_EXT{}", .0.value, .1)]
+    NonFiniteFloat(Float, EncodePath),
+
+    #[error("binary of {len} bytes exceeds the maximum of {} bytes", u32::MAX)]
+    TooLargeBinary { len: usize },
+
+    #[error("frame of {len} bytes exceeds the maximum of {max} bytes")]
+    TooLargeFrame { len: usize, max: usize },
+
+    /// The slice given to [`encode_into`](crate::encode_into) is shorter than the encoding.
+    #[error("the encoding needs {needed} bytes, but the buffer has {available}")]
+    BufferTooSmall { needed: usize, available: usize },
+
+    #[error("{value} cannot be encoded without {flag:?}")]
+    UnsupportedByPeer { value: Term, flag: DistFlags },
+
+    /// A field of a pid, port or reference is out of the range which the peers without
+    /// `profile` decode (e.g., the ID of a pid above 15 bits without [`DistFlags::V4_NC`]).
+    #[error("{field} {value} exceeds {max}, the maximum without {profile:?}")]
+    ValueOutOfRangeForProfile {
+        field: &'static str,
+        value: u64,
+        max: u64,
+        profile: DistFlags,
+    },
+
+    /// The iterator given to [`Encoder::encode_list_from_iter`] (or to a similar method) did not
+    /// yield as many elements as its length said, so the length was already written wrongly.
+    #[error("the iterator did not yield the {len} elements of its length")]
+    IteratorLengthMismatch { len: usize },
+}
+
+impl EncodeError {
+    /// Returns the path to the subterm which failed to encode, if the error has one.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn path(&self) -> Option<&EncodePath> {
+        match self {
+            EncodeError::TooLongAtomName(_, path)
+            | EncodeError::TooLargeInteger(_, path)
+            | EncodeError::TooLargeReferenceId(_, path)
+            | EncodeError::NonFiniteFloat(_, path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Records that the error occurred within `segment` of an outer term.
+    pub(crate) fn within(self, segment: EncodePathSegment) -> Self {
+        self.within_with(|| segment)
+    }
+
+    /// Records that the error occurred within the segment returned by `segment`, which is only
+    /// called if the error has a path (e.g., not for an I/O error).
+    pub(crate) fn within_with(mut self, segment: impl FnOnce() -> EncodePathSegment) -> Self {
+        match self {
+            EncodeError::TooLongAtomName(_, ref mut path)
+            | EncodeError::TooLargeInteger(_, ref mut path)
+            | EncodeError::TooLargeReferenceId(_, ref mut path)
+            | EncodeError::NonFiniteFloat(_, ref mut path) => path.segments.push(segment()),
+            _ => {}
+        }
+        self
+    }
+}
+
+/// A step in the path from an encoded term to the subterm which failed to encode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodePathSegment {
+    ListElement(usize),
+    ListTail,
+    TupleElement(usize),
+    MapKey,
+    MapValue { key: String },
+    FreeVar(usize),
+}
+impl fmt::Display for EncodePathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodePathSegment::ListElement(i) => write!(f, "list[{}]", i),
+            EncodePathSegment::ListTail => write!(f, "list tail"),
+            EncodePathSegment::TupleElement(i) => write!(f, "tuple[{}]", i),
+            EncodePathSegment::MapKey => write!(f, "map key"),
+            EncodePathSegment::MapValue { ref key } => write!(f, "map value for key {}", key),
+            EncodePathSegment::FreeVar(i) => write!(f, "fun free var[{}]", i),
+        }
+    }
+}
+
+/// Path from an encoded term to the subterm which failed to encode.
+///
+/// The path is built while the error propagates, so it costs nothing unless encoding fails.
+/// It is displayed outermost step first (e.g., ` at tuple[3] → map value for key 'labels' → list[1021]`),
+/// and is empty if the failed term is the encoded term itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodePath {
+    /// The steps, innermost first.
+    pub segments: Vec<EncodePathSegment>,
+}
+impl fmt::Display for EncodePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().rev().enumerate() {
+            if i == 0 {
+                write!(f, " at {}", segment)?;
+            } else {
+                write!(f, " → {}", segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options of an encoder.
+///
+/// Every tag is allowed by default. Disabling a tag makes the encoder fall back to the
+/// older tag for the same term if there is one, and fail with
+/// [`EncodeError::UnsupportedByPeer`] otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Encoder, EncoderOptions, Term};
+///
+/// let options = EncoderOptions::new().with_new_floats(false);
+/// let mut bytes = Vec::new();
+/// Encoder::with_options(&mut bytes, options)
+///     .encode(&Term::try_from(1.5).unwrap())
+///     .unwrap();
+/// assert_eq!(bytes[1], 99); // FLOAT_EXT
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderOptions {
+    /// Whether floats are `NEW_FLOAT_EXT` (rather than `FLOAT_EXT`).
+    pub new_floats: bool,
+
+    /// Whether atoms may be `ATOM_UTF8_EXT` (rather than Latin-1 `ATOM_EXT` only).
+    pub utf8_atoms: bool,
+
+    /// Whether maps (`MAP_EXT`) may be encoded.
+    pub map_tag: bool,
+
+    /// Whether pids, ports and references have 32 bit creations (`NEW_PID_EXT`, etc.), rather
+    /// than 2 bit ones.
+    pub big_creation: bool,
+
+    /// Whether ports may have 64 bit IDs (`V4_PORT_EXT`) and references may have up to 5 ID words.
+    ///
+    /// Without it, the IDs of pids, ports and references must fit the narrower fields which the
+    /// older nodes decode (e.g., 15 bits for the ID of a pid), or the encoder fails with
+    /// [`EncodeError::ValueOutOfRangeForProfile`].
+    pub v4_nc: bool,
+
+    /// Whether external funs (`EXPORT_EXT`) may be encoded.
+    pub export_ptr_tag: bool,
+
+    /// Whether bit binaries (`BIT_BINARY_EXT`) may be encoded.
+    pub bit_binaries: bool,
+
+    /// Whether new funs (`NEW_FUN_EXT`) may be encoded.
+    pub new_fun_tags: bool,
+
+    /// Whether the entries of maps are written in the term order of their keys (see
+    /// [`Term::erl_cmp`]), so that equal maps make the same bytes whatever their insertion
+    /// order. It is off by default, as sorting takes time; see also [`Term::canonicalize`].
+    pub deterministic: bool,
+
+    /// Maximum number of bytes of a binary or a big integer which the async encoder writes at once
+    /// (it yields to the executor between the chunks).
+    pub chunk_size: usize,
+
+    /// Whether `encode` writes terms compressed with zlib, as `term_to_binary(Term, [compressed])`
+    /// does (see [`Encoder::encode_compressed`](crate::Encoder::encode_compressed)). It is off by
+    /// default.
+    pub compressed: bool,
+
+    /// Whether lists of integers in `0..=255` (and [`ByteList`]s) are `STRING_EXT`, as Erlang
+    /// writes them, rather than `LIST_EXT`s of `SMALL_INTEGER_EXT`, which peers that only decode
+    /// plain lists may need.
+    pub string_ext: bool,
+}
+impl EncoderOptions {
+    /// Makes the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the options which only allow the tags supported by a peer with the negotiated `flags`.
+    pub fn for_peer(flags: DistFlags) -> Self {
+        EncoderOptions {
+            new_floats: flags.contains(DistFlags::NEW_FLOATS),
+            utf8_atoms: flags.contains(DistFlags::UTF8_ATOMS),
+            map_tag: flags.contains(DistFlags::MAP_TAG),
+            big_creation: flags.contains(DistFlags::BIG_CREATION),
+            v4_nc: flags.contains(DistFlags::V4_NC),
+            export_ptr_tag: flags.contains(DistFlags::EXPORT_PTR_TAG),
+            bit_binaries: flags.contains(DistFlags::BIT_BINARIES),
+            new_fun_tags: flags.contains(DistFlags::NEW_FUN_TAGS),
+            deterministic: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compressed: false,
+            string_ext: true,
+        }
+    }
+
+    /// Sets [`EncoderOptions::new_floats`].
+    pub fn with_new_floats(mut self, new_floats: bool) -> Self {
+        self.new_floats = new_floats;
+        self
+    }
+
+    /// Sets [`EncoderOptions::utf8_atoms`].
+    pub fn with_utf8_atoms(mut self, utf8_atoms: bool) -> Self {
+        self.utf8_atoms = utf8_atoms;
+        self
+    }
+
+    /// Sets [`EncoderOptions::map_tag`].
+    pub fn with_map_tag(mut self, map_tag: bool) -> Self {
+        self.map_tag = map_tag;
+        self
+    }
+
+    /// Sets [`EncoderOptions::big_creation`].
+    pub fn with_big_creation(mut self, big_creation: bool) -> Self {
+        self.big_creation = big_creation;
+        self
+    }
+
+    /// Sets [`EncoderOptions::v4_nc`].
+    pub fn with_v4_nc(mut self, v4_nc: bool) -> Self {
+        self.v4_nc = v4_nc;
+        self
+    }
+
+    /// Sets [`EncoderOptions::export_ptr_tag`].
+    pub fn with_export_ptr_tag(mut self, export_ptr_tag: bool) -> Self {
+        self.export_ptr_tag = export_ptr_tag;
+        self
+    }
+
+    /// Sets [`EncoderOptions::bit_binaries`].
+    pub fn with_bit_binaries(mut self, bit_binaries: bool) -> Self {
+        self.bit_binaries = bit_binaries;
+        self
+    }
+
+    /// Sets [`EncoderOptions::new_fun_tags`].
+    pub fn with_new_fun_tags(mut self, new_fun_tags: bool) -> Self {
+        self.new_fun_tags = new_fun_tags;
+        self
+    }
+
+    /// Sets [`EncoderOptions::deterministic`].
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Sets [`EncoderOptions::chunk_size`].
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets [`EncoderOptions::compressed`].
+    pub fn with_compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Sets [`EncoderOptions::string_ext`].
+    pub fn with_string_ext(mut self, string_ext: bool) -> Self {
+        self.string_ext = string_ext;
+        self
+    }
+}
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        EncoderOptions {
+            new_floats: true,
+            utf8_atoms: true,
+            map_tag: true,
+            big_creation: true,
+            v4_nc: true,
+            export_ptr_tag: true,
+            bit_binaries: true,
+            new_fun_tags: true,
+            deterministic: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compressed: false,
+            string_ext: true,
+        }
+    }
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options of a decoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Depth (the decoded term being at depth 0) from which subterms are kept as [`Raw`] terms
+    /// instead of being decoded. Atoms are always decoded.
+    pub raw_depth: Option<usize>,
 
-    #[error("too large integer value: {} bytes required to encode", .0.value.to_bytes_le().1.len())]
-    TooLargeInteger(BigInteger),
+    /// Maximum number of bytes which a compressed term may inflate to ([`None`] by default, for no
+    /// limit). A few kilobytes of zlib stream can inflate to gigabytes, so decoders of untrusted
+    /// input should set it, e.g., to 16 MiB or to the largest term they expect.
+    ///
+    /// `libflate` inflates a whole deflate block before the limit is checked, so a crafted block
+    /// still takes as much memory as it inflates to. With the `flate2` feature, inflating stops
+    /// at the limit, as it does in the async decoders whatever the feature.
+    pub max_uncompressed_size: Option<usize>,
 
-    #[error("too large reference ID: {} bytes required to encode", .0.id.len() * 4)]
-    TooLargeReferenceId(Reference),
+    /// Makes [`Decoder`](crate::Decoder) fail with `DecodeError::Rejected` on the anomalies which
+    /// [`Decoder::decode_with_report`](crate::Decoder::decode_with_report) would report as
+    /// warnings of a kind for which `WarningKind::is_strict_error` is `true` (see
+    /// [`report`](crate::report)). The slice decoders ignore it, and the async decoders fail
+    /// with `DecodeError::UnsupportedOption`.
+    pub strict: bool,
+
+    /// Makes the decoders fail with `DecodeError::UnknownAtom` on an atom whose name is not in
+    /// their atom table (see [`Decoder::with_atom_table`](crate::Decoder::with_atom_table)),
+    /// as `binary_to_term(Bin, [safe])` fails on atoms which do not exist, so that untrusted input
+    /// cannot add names to the table. Without a table, every atom fails. The slice decoders
+    /// ignore it.
+    pub safe: bool,
+
+    /// Keeps the whole term of a [`DecodeError::UnexpectedType`] in its [`TermSummary`] (see
+    /// [`TermSummary::value`]). It is off by default, as the term may be arbitrarily large.
+    pub keep_offending_term: bool,
+
+    /// Decodes terms which do not start with the version number (131), as some framing layers
+    /// strip it. Only [`decode_frame_with_options`](crate::decode_frame_with_options) and
+    /// [`decode_from_slice_with_options`](crate::decode_from_slice_with_options) honor it.
+    pub versionless: bool,
+}
+impl DecoderOptions {
+    /// Makes the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the options which keep the subterms at `depth` and below as [`Raw`] terms.
+    ///
+    /// With a depth of 1, decoding `{route, Payload}` yields the atom `route` and the
+    /// original encoding of `Payload`.
+    pub fn raw_depth(depth: usize) -> Self {
+        DecoderOptions {
+            raw_depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    /// Sets [`DecoderOptions::raw_depth`].
+    pub fn with_raw_depth(mut self, depth: usize) -> Self {
+        self.raw_depth = Some(depth);
+        self
+    }
+
+    /// Sets [`DecoderOptions::max_uncompressed_size`].
+    pub fn with_max_uncompressed_size(mut self, max: usize) -> Self {
+        self.max_uncompressed_size = Some(max);
+        self
+    }
+
+    /// Sets [`DecoderOptions::strict`].
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets [`DecoderOptions::safe`].
+    pub fn with_safe(mut self, safe: bool) -> Self {
+        self.safe = safe;
+        self
+    }
+
+    /// Sets [`DecoderOptions::keep_offending_term`].
+    pub fn with_keep_offending_term(mut self, keep: bool) -> Self {
+        self.keep_offending_term = keep;
+        self
+    }
+
+    /// Sets [`DecoderOptions::versionless`].
+    pub fn with_versionless(mut self, versionless: bool) -> Self {
+        self.versionless = versionless;
+        self
+    }
 }
 
 pub type DecodeResult = Result<Term, DecodeError>;
@@ -60,6 +628,7 @@ pub(crate) const DISTRIBUTION_HEADER: u8 = 68;
 pub(crate) const NEW_FLOAT_EXT: u8 = 70;
 pub(crate) const BIT_BINARY_EXT: u8 = 77;
 pub(crate) const COMPRESSED_TERM: u8 = 80;
+#[cfg(feature = "std")]
 pub(crate) const ATOM_CACHE_REF: u8 = 82;
 pub(crate) const NEW_PID_EXT: u8 = 88;
 pub(crate) const NEW_PORT_EXT: u8 = 89;
@@ -89,38 +658,149 @@ pub(crate) const ATOM_UTF8_EXT: u8 = 118;
 pub(crate) const SMALL_ATOM_UTF8_EXT: u8 = 119;
 pub(crate) const V4_PORT_EXT: u8 = 120;
 
+/// Maximum number of bytes or elements which the stream decoders allocate up front for a
+/// length read from the input, so that a bogus length in a short input cannot exhaust memory.
+#[cfg(feature = "std")]
+pub(crate) const MAX_PREALLOCATED_LEN: usize = 64 * 1024;
+
+/// Maximum nesting depth of the terms which the decoders accept (see [`DecodeError::TooDeep`]).
+pub const MAX_DECODE_DEPTH: usize = 512;
+
 pub(crate) mod aux {
+    #[cfg(not(feature = "std"))]
+    use alloc::boxed::Box;
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+    use core::ops::Range;
     use num::bigint::Sign;
+    #[cfg(feature = "std")]
     use std::io;
-    use std::ops::Range;
-    use std::str;
-
-    pub fn term_into_atom(t: crate::Term) -> Result<crate::Atom, super::DecodeError> {
-        t.try_into()
-            .map_err(|t| super::DecodeError::UnexpectedType {
-                value: t,
-                expected: "Atom".to_string(),
-            })
+
+    /// Checks the tail bits size of a bit binary of `len` bytes the way the VM does
+    /// (it is 0 exactly for empty bit binaries).
+    pub fn check_tail_bits_size(len: usize, tail_bits_size: u8) -> Result<(), super::DecodeError> {
+        if (tail_bits_size == 0) != (len == 0) || tail_bits_size > 8 {
+            return Err(super::DecodeError::InvalidData {
+                message: format!(
+                    "a bit binary of {} bytes cannot have {} tail bits",
+                    len, tail_bits_size
+                ),
+            });
+        }
+        Ok(())
     }
-    pub fn term_into_pid(t: crate::Term) -> Result<crate::Pid, super::DecodeError> {
-        t.try_into()
-            .map_err(|t| super::DecodeError::UnexpectedType {
-                value: t,
-                expected: "Pid".to_string(),
-            })
+    /// Fails if a subterm at `depth` would be nested too deeply.
+    pub fn check_depth(depth: usize) -> Result<(), super::DecodeError> {
+        if depth >= super::MAX_DECODE_DEPTH {
+            return Err(super::DecodeError::TooDeep {
+                max: super::MAX_DECODE_DEPTH,
+            });
+        }
+        Ok(())
     }
-    pub fn term_into_fix_integer(t: crate::Term) -> Result<crate::FixInteger, super::DecodeError> {
-        t.try_into()
-            .map_err(|t| super::DecodeError::UnexpectedType {
-                value: t,
-                expected: "FixInteger".to_string(),
+    pub fn is_atom_tag(tag: u8) -> bool {
+        #[cfg(feature = "std")]
+        if tag == super::ATOM_CACHE_REF {
+            return true;
+        }
+        matches!(
+            tag,
+            super::ATOM_EXT
+                | super::SMALL_ATOM_EXT
+                | super::ATOM_UTF8_EXT
+                | super::SMALL_ATOM_UTF8_EXT
+        )
+    }
+    /// Rejects the tag of a term which must be an atom before the term is decoded.
+    pub fn check_atom_tag(tag: u8) -> Result<(), super::DecodeError> {
+        if is_atom_tag(tag) {
+            Ok(())
+        } else {
+            Err(super::DecodeError::UnexpectedTag {
+                tag,
+                expected: super::SMALL_ATOM_UTF8_EXT,
             })
+        }
+    }
+    /// Returns the `len` elements of `iter` with their indices, followed by an error if there
+    /// are fewer or more of them.
+    #[cfg(feature = "std")]
+    pub fn exact<I: Iterator>(iter: I, len: usize) -> Exact<I> {
+        Exact {
+            iter,
+            len,
+            count: 0,
+        }
+    }
+    #[cfg(feature = "std")]
+    pub struct Exact<I> {
+        iter: I,
+        len: usize,
+        count: usize,
+    }
+    #[cfg(feature = "std")]
+    impl<I: Iterator> Iterator for Exact<I> {
+        type Item = Result<(usize, I::Item), super::EncodeError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.count > self.len {
+                return None;
+            }
+            let index = self.count;
+            self.count += 1;
+            match self.iter.next() {
+                Some(e) if index < self.len => Some(Ok((index, e))),
+                None if index == self.len => None,
+                _ => {
+                    self.count = self.len + 1;
+                    Some(Err(super::EncodeError::IteratorLengthMismatch {
+                        len: self.len,
+                    }))
+                }
+            }
+        }
+    }
+    /// Makes a [`super::DecodeError::UnexpectedType`], which keeps `value` only if `keep` (see
+    /// [`super::DecoderOptions::keep_offending_term`]).
+    pub fn unexpected_type(value: crate::Term, expected: &str, keep: bool) -> super::DecodeError {
+        super::DecodeError::UnexpectedType {
+            found: Box::new(if keep {
+                super::TermSummary::keeping(value)
+            } else {
+                super::TermSummary::new(&value)
+            }),
+            expected: expected.to_string(),
+        }
+    }
+    pub fn term_into_atom(t: crate::Term, keep: bool) -> Result<crate::Atom, super::DecodeError> {
+        match t {
+            crate::Term::Atom(x) => Ok(x),
+            t => Err(unexpected_type(t, "Atom", keep)),
+        }
+    }
+    pub fn term_into_pid(t: crate::Term, keep: bool) -> Result<crate::Pid, super::DecodeError> {
+        match t {
+            crate::Term::Pid(x) => Ok(x),
+            t => Err(unexpected_type(t, "Pid", keep)),
+        }
+    }
+    pub fn term_into_fix_integer(
+        t: crate::Term,
+        keep: bool,
+    ) -> Result<crate::FixInteger, super::DecodeError> {
+        match t {
+            crate::Term::FixInteger(x) => Ok(x),
+            t => Err(unexpected_type(t, "FixInteger", keep)),
+        }
     }
     pub fn term_into_ranged_integer(
         t: crate::Term,
         range: Range<i32>,
+        keep: bool,
     ) -> Result<i32, super::DecodeError> {
-        term_into_fix_integer(t).and_then(|i| {
+        term_into_fix_integer(t, keep).and_then(|i| {
             let n = i.value;
             if range.start <= n && n <= range.end {
                 Ok(n)
@@ -129,18 +809,70 @@ pub(crate) mod aux {
             }
         })
     }
+    #[cfg(feature = "std")]
     pub fn invalid_data_error<T>(message: String) -> io::Result<T> {
         Err(io::Error::new(io::ErrorKind::InvalidData, message))
     }
-    pub fn other_error<T>(message: String) -> io::Result<T> {
-        Err(io::Error::new(io::ErrorKind::Other, message))
+    /// Size of the chunks in which the decoders read strings and the names of atoms, so that
+    /// the memory for them is only taken as their bytes arrive.
+    #[cfg(feature = "std")]
+    pub const CHUNK_LEN: usize = 1024;
+
+    /// Turns the scratch buffer of a decoder into an empty string, keeping its capacity for
+    /// a name read by chunks (see [`push_name_chunk`]).
+    #[cfg(feature = "std")]
+    pub fn take_name_buf(buf: &mut Vec<u8>) -> String {
+        buf.clear();
+        String::from_utf8(core::mem::take(buf)).unwrap_or_default()
     }
-    pub fn latin1_bytes_to_string(buf: &[u8]) -> io::Result<String> {
-        // FIXME: Supports Latin1 characters
-        str::from_utf8(buf)
-            .or_else(|e| other_error(e.to_string()))
-            .map(ToString::to_string)
+
+    /// Appends the first `len` bytes of `chunk` to `name`, converting them from Latin-1 or
+    /// checking that they are UTF-8.
+    ///
+    /// The bytes of a UTF-8 character split at the end of the chunk, unless it is the `last`,
+    /// are moved to its start, to be completed by the next chunk; their number is returned.
+    #[cfg(feature = "std")]
+    pub fn push_name_chunk(
+        name: &mut String,
+        chunk: &mut [u8],
+        len: usize,
+        last: bool,
+        latin1: bool,
+    ) -> io::Result<usize> {
+        if latin1 {
+            // Latin-1 code points are the first 256 Unicode scalar values.
+            name.extend(chunk[..len].iter().map(|&b| char::from(b)));
+            return Ok(0);
+        }
+        let error = match core::str::from_utf8(&chunk[..len]) {
+            Ok(s) => {
+                name.push_str(s);
+                return Ok(0);
+            }
+            Err(e) => e,
+        };
+        let valid = error.valid_up_to();
+        let index = name.len() + valid;
+        match error.error_len() {
+            None if !last => {}
+            None => {
+                return invalid_data_error(format!(
+                    "incomplete utf-8 byte sequence from index {}",
+                    index
+                ))
+            }
+            Some(n) => {
+                return invalid_data_error(format!(
+                    "invalid utf-8 sequence of {} bytes from index {}",
+                    n, index
+                ))
+            }
+        }
+        name.push_str(core::str::from_utf8(&chunk[..valid]).expect("unreachable"));
+        chunk.copy_within(valid..len, 0);
+        Ok(len - valid)
     }
+    #[cfg(feature = "std")]
     pub fn byte_to_sign(b: u8) -> io::Result<Sign> {
         match b {
             0 => Ok(Sign::Plus),
@@ -148,6 +880,102 @@ pub(crate) mod aux {
             _ => invalid_data_error(format!("A sign value must be 0 or 1: value={}", b)),
         }
     }
+    #[cfg(feature = "std")]
+    pub fn unsupported_by_peer(
+        value: crate::Term,
+        flag: crate::dist::DistFlags,
+    ) -> super::EncodeError {
+        super::EncodeError::UnsupportedByPeer { value, flag }
+    }
+    /// Range of a field of pids, ports or references which is narrower for the peers without
+    /// `flag`.
+    ///
+    /// The ranges are those of the tags which the encoders write for such peers, as the current
+    /// external term format specifies them: `PORT_EXT` and `NEW_PORT_EXT` have 28 significant bits
+    /// of ID (rather than the 18 bits of the ports of older releases), and `NEW_REFERENCE_EXT` has
+    /// up to 3 words of ID, of which the first has 18 significant bits (rather than the single
+    /// word of `REFERENCE_EXT`, which the encoders never write).
+    #[cfg(feature = "std")]
+    pub struct LegacyRange {
+        field: &'static str,
+        max: u64,
+        flag: crate::dist::DistFlags,
+    }
+    #[cfg(feature = "std")]
+    pub const CREATION_RANGE: LegacyRange = LegacyRange {
+        field: "creation",
+        max: 3,
+        flag: crate::dist::DistFlags::BIG_CREATION,
+    };
+    #[cfg(feature = "std")]
+    pub const PID_ID_RANGE: LegacyRange = LegacyRange {
+        field: "pid ID",
+        max: (1 << 15) - 1,
+        flag: crate::dist::DistFlags::V4_NC,
+    };
+    #[cfg(feature = "std")]
+    pub const PID_SERIAL_RANGE: LegacyRange = LegacyRange {
+        field: "pid serial",
+        max: (1 << 13) - 1,
+        flag: crate::dist::DistFlags::V4_NC,
+    };
+    #[cfg(feature = "std")]
+    pub const PORT_ID_RANGE: LegacyRange = LegacyRange {
+        field: "port ID",
+        max: (1 << 28) - 1,
+        flag: crate::dist::DistFlags::V4_NC,
+    };
+    #[cfg(feature = "std")]
+    pub const REFERENCE_LEN_RANGE: LegacyRange = LegacyRange {
+        field: "number of words of reference ID",
+        max: 3,
+        flag: crate::dist::DistFlags::V4_NC,
+    };
+    #[cfg(feature = "std")]
+    pub const REFERENCE_WORD_RANGE: LegacyRange = LegacyRange {
+        field: "first word of reference ID",
+        max: (1 << 18) - 1,
+        flag: crate::dist::DistFlags::V4_NC,
+    };
+    #[cfg(feature = "std")]
+    pub fn check_range(range: LegacyRange, value: u64) -> Result<(), super::EncodeError> {
+        if value <= range.max {
+            return Ok(());
+        }
+        Err(super::EncodeError::ValueOutOfRangeForProfile {
+            field: range.field,
+            value,
+            max: range.max,
+            profile: range.flag,
+        })
+    }
+    /// Returns the creation of `PID_EXT`, `PORT_EXT` and `NEW_REFERENCE_EXT`, of which only 2
+    /// bits are significant.
+    #[cfg(feature = "std")]
+    pub fn small_creation(creation: u32) -> Result<u8, super::EncodeError> {
+        check_range(CREATION_RANGE, u64::from(creation))?;
+        Ok(creation as u8)
+    }
+    #[cfg(feature = "std")]
+    pub fn to_latin1(s: &str) -> Option<Vec<u8>> {
+        s.chars().map(|c| u8::try_from(c).ok()).collect()
+    }
+    #[cfg(feature = "std")]
+    /// Formats `value` as `FLOAT_EXT` does (i.e., `"%.20e"` padded with zeros to 31 bytes).
+    ///
+    /// Returns `None` if `value` is not finite, as `FLOAT_EXT` cannot represent it.
+    pub fn float_ext_bytes(value: f64) -> Option<[u8; 31]> {
+        if !value.is_finite() {
+            return None;
+        }
+        let s = format!("{:.20e}", value);
+        let (mantissa, exponent) = s.split_once('e').expect("unreachable");
+        let exponent: i32 = exponent.parse().expect("unreachable");
+        let s = format!("{}e{:+03}", mantissa, exponent);
+        let mut buf = [0; 31];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
+        Some(buf)
+    }
     pub fn sign_to_byte(sign: Sign) -> u8 {
         if sign == Sign::Minus {
             1
@@ -1,5 +1,7 @@
 use super::*;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
+use std::fmt;
 
 pub trait TryAsRef<T> {
     fn try_as_ref(&self) -> Option<&T>;
@@ -39,6 +41,7 @@ impl_term_try_as_ref!(ImproperList);
 impl_term_try_as_ref!(Tuple);
 impl_term_try_as_ref!(Map);
 impl_term_try_as_ref!(ByteList);
+impl_term_try_as_ref!(Raw);
 
 macro_rules! impl_term_try_into {
     ($to:ident) => {
@@ -90,6 +93,7 @@ impl_term_try_into!(ImproperList);
 impl_term_try_into!(Tuple);
 impl_term_try_into!(Map);
 impl_term_try_into!(ByteList);
+impl_term_try_into!(Raw);
 
 pub trait AsOption {
     fn as_option(&self) -> Option<&Self>;
@@ -201,3 +205,431 @@ impl num::bigint::ToBigUint for Term {
         }
     }
 }
+
+/// Conversion into a `Term`.
+///
+/// This is the intended customization point for converting user types into terms.
+/// Unlike `From`, it can be implemented by downstream crates for their own types and
+/// is picked up by the `Vec`, `HashMap`, and tuple adapters.
+///
+/// Every type for which `Term: From<T>` holds implements this trait.
+pub trait IntoTerm {
+    fn into_term(self) -> Term;
+}
+impl<T> IntoTerm for T
+where
+    Term: From<T>,
+{
+    fn into_term(self) -> Term {
+        Term::from(self)
+    }
+}
+impl<T: IntoTerm> IntoTerm for Vec<T> {
+    fn into_term(self) -> Term {
+        Term::from(List::from(
+            self.into_iter()
+                .map(IntoTerm::into_term)
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
+impl<K: IntoTerm, V: IntoTerm, S> IntoTerm for HashMap<K, V, S> {
+    fn into_term(self) -> Term {
+        Term::from(Map::from(
+            self.into_iter()
+                .map(|(k, v)| (k.into_term(), v.into_term()))
+                .collect::<HashMap<_, _>>(),
+        ))
+    }
+}
+
+/// Conversion from a `Term`.
+///
+/// This is the intended customization point for converting terms into user types.
+/// It is implemented for every term variant type, the primitive types,
+/// and `Vec`, `HashMap`, and tuples of types implementing it.
+pub trait FromTerm: Sized {
+    fn from_term(term: &Term) -> Result<Self, FromTermError>;
+}
+impl<T> FromTerm for T
+where
+    T: Clone,
+    Term: TryAsRef<T>,
+{
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        term.try_as_ref()
+            .cloned()
+            .ok_or_else(|| FromTermError::unexpected::<T>(term))
+    }
+}
+impl<T: FromTerm> FromTerm for Vec<T> {
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        let from_element =
+            |(i, e)| T::from_term(e).map_err(|e| e.within(FromTermPathSegment::ListElement(i)));
+        match *term {
+            Term::List(ref x) => x.elements.iter().enumerate().map(from_element).collect(),
+            Term::ByteList(ref x) => x
+                .bytes
+                .iter()
+                .map(|&b| Term::from(b))
+                .collect::<Vec<_>>()
+                .iter()
+                .enumerate()
+                .map(from_element)
+                .collect(),
+            _ => Err(FromTermError::unexpected::<Self>(term)),
+        }
+    }
+}
+impl<K, V, S> FromTerm for HashMap<K, V, S>
+where
+    K: FromTerm + Eq + Hash,
+    V: FromTerm,
+    S: std::hash::BuildHasher + Default,
+{
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        let map: &Map = term
+            .try_as_ref()
+            .ok_or_else(|| FromTermError::unexpected::<Self>(term))?;
+        map.map
+            .iter()
+            .map(|(k, v)| {
+                let key = K::from_term(k).map_err(|e| e.within(FromTermPathSegment::MapKey))?;
+                let value = V::from_term(v)
+                    .map_err(|e| e.within(FromTermPathSegment::MapValue { key: k.to_string() }))?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+impl List {
+    /// Converts each element, failing at the first one which does not convert (the error's path
+    /// starts with its index).
+    ///
+    /// ```
+    /// use eetf::convert::FromTermPathSegment;
+    /// use eetf::{List, Term};
+    ///
+    /// let list = List::from(vec![Term::from(1), Term::from(2)]);
+    /// assert_eq!(list.try_map::<u8>(), Ok(vec![1, 2]));
+    ///
+    /// let list = List::from(vec![Term::from(1), Term::from(300)]);
+    /// let e = list.try_map::<u8>().unwrap_err();
+    /// assert_eq!(e.path, vec![FromTermPathSegment::ListElement(1)]);
+    /// ```
+    pub fn try_map<T: FromTerm>(&self) -> Result<Vec<T>, FromTermError> {
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                T::from_term(e).map_err(|e| e.within(FromTermPathSegment::ListElement(i)))
+            })
+            .collect()
+    }
+
+    /// Converts the first element, if there is one.
+    pub fn first_as<T: FromTerm>(&self) -> Result<Option<T>, FromTermError> {
+        self.elements
+            .first()
+            .map(|e| T::from_term(e).map_err(|e| e.within(FromTermPathSegment::ListElement(0))))
+            .transpose()
+    }
+
+    /// Returns the elements of a list of `{Key, Value}` tuples (e.g., a proplist) as pairs, each
+    /// element which is not a 2-tuple being an error.
+    pub fn pairs(&self) -> impl Iterator<Item = Result<(&Term, &Term), FromTermError>> {
+        self.elements.iter().enumerate().map(|(i, e)| match e {
+            Term::Tuple(x) if x.elements.len() == 2 => Ok((&x.elements[0], &x.elements[1])),
+            _ => {
+                let actual = match e {
+                    Term::Tuple(x) => format!("Tuple of arity {}", x.elements.len()),
+                    _ => describe(e),
+                };
+                Err(FromTermError::new("Tuple of arity 2", actual)
+                    .within(FromTermPathSegment::ListElement(i)))
+            }
+        })
+    }
+
+    /// Groups the elements by their [variant names](Term::variant_name), keeping their order
+    /// within each group (e.g., to split the flags of an options list from its tuples).
+    ///
+    /// ```
+    /// use eetf::{Atom, List, Term, Tuple};
+    ///
+    /// let debug = Term::from(Atom::from("debug"));
+    /// let timeout = Term::from(Tuple::from(vec![Term::from(Atom::from("timeout")), Term::from(5)]));
+    /// let options = List::from(vec![debug.clone(), timeout.clone()]);
+    /// let groups = options.partition_by_variant();
+    /// assert_eq!(groups["Atom"], [&debug]);
+    /// assert_eq!(groups["Tuple"], [&timeout]);
+    /// ```
+    pub fn partition_by_variant(&self) -> BTreeMap<&'static str, Vec<&Term>> {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for e in &self.elements {
+            groups.entry(e.variant_name()).or_default().push(e);
+        }
+        groups
+    }
+}
+
+impl List {
+    /// Makes a list of floats, failing at the first value which is not finite.
+    ///
+    /// ```
+    /// use eetf::List;
+    ///
+    /// let list = List::from_f64_slice(&[1.5, 2.5]).unwrap();
+    /// assert_eq!(list.try_to_f64_vec(), Ok(vec![1.5, 2.5]));
+    ///
+    /// let e = List::from_f64_slice(&[1.5, f64::NAN]).unwrap_err();
+    /// assert_eq!(e.to_string(), "element 1 of the slice (NaN) is not a finite float");
+    /// ```
+    pub fn from_f64_slice(values: &[f64]) -> Result<Self, NonFiniteFloatError> {
+        let mut elements = Vec::with_capacity(values.len());
+        for (index, &value) in values.iter().enumerate() {
+            if !value.is_finite() {
+                return Err(NonFiniteFloatError { index, value });
+            }
+            elements.push(Term::Float(Float { value }));
+        }
+        Ok(List::from(elements))
+    }
+
+    /// Returns the values of a list of floats, failing at the first element which is not a float
+    /// (the error's path starts with its index).
+    pub fn try_to_f64_vec(&self) -> Result<Vec<f64>, FromTermError> {
+        let mut values = Vec::with_capacity(self.elements.len());
+        for (i, e) in self.elements.iter().enumerate() {
+            match *e {
+                Term::Float(ref x) => values.push(x.value),
+                _ => {
+                    return Err(FromTermError::unexpected::<f64>(e)
+                        .within(FromTermPathSegment::ListElement(i)))
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Error of [`List::from_f64_slice`], which names the value which is not finite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonFiniteFloatError {
+    /// The index of the value in the slice.
+    pub index: usize,
+
+    /// The value (an infinity or a NaN).
+    pub value: f64,
+}
+impl fmt::Display for NonFiniteFloatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "element {} of the slice ({}) is not a finite float",
+            self.index, self.value
+        )
+    }
+}
+impl std::error::Error for NonFiniteFloatError {}
+
+/// Conversion of a Rust tuple into a tuple term, which reads better than
+/// [`IntoTerm::into_term`] at the end of a tuple expression.
+///
+/// ```
+/// use eetf::{Atom, IntoTermTuple, Term, Tuple};
+///
+/// let term = (Atom::from("ok"), 1).into_term_tuple();
+/// assert_eq!(term, Term::from(eetf::tuple!(Atom::from("ok"), 1)));
+/// ```
+pub trait IntoTermTuple {
+    fn into_term_tuple(self) -> Term;
+}
+
+macro_rules! impl_tuple_conversions {
+    ($arity:expr; $($name:ident : $index:tt),*) => {
+        impl<$($name: IntoTerm),*> IntoTerm for ($($name,)*) {
+            fn into_term(self) -> Term {
+                Term::from(Tuple::from(vec![$(self.$index.into_term()),*]))
+            }
+        }
+        impl<$($name: IntoTerm),*> IntoTermTuple for ($($name,)*) {
+            fn into_term_tuple(self) -> Term {
+                self.into_term()
+            }
+        }
+        impl<$($name: FromTerm),*> FromTerm for ($($name,)*) {
+            fn from_term(term: &Term) -> Result<Self, FromTermError> {
+                let tuple: &Tuple = term
+                    .try_as_ref()
+                    .filter(|t: &&Tuple| t.elements.len() == $arity)
+                    .ok_or_else(|| {
+                        FromTermError::new(format!("Tuple of arity {}", $arity), describe(term))
+                    })?;
+                Ok(($($name::from_term(&tuple.elements[$index])
+                    .map_err(|e| e.within(FromTermPathSegment::TupleElement($index)))?,)*))
+            }
+        }
+    };
+}
+impl_tuple_conversions!(1; A: 0);
+impl_tuple_conversions!(2; A: 0, B: 1);
+impl_tuple_conversions!(3; A: 0, B: 1, C: 2);
+impl_tuple_conversions!(4; A: 0, B: 1, C: 2, D: 3);
+impl_tuple_conversions!(5; A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_tuple_conversions!(6; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+macro_rules! impl_integer_from_term {
+    ($($to:ident => $big_to:ident),*) => {
+        $(impl FromTerm for $to {
+            fn from_term(term: &Term) -> Result<Self, FromTermError> {
+                let value = match *term {
+                    Term::FixInteger(ref x) => $to::try_from(x.value).ok(),
+                    Term::BigInteger(ref x) => num::traits::ToPrimitive::$big_to(&x.value),
+                    _ => return Err(FromTermError::unexpected::<Self>(term)),
+                };
+                value.ok_or_else(|| {
+                    FromTermError::new(stringify!($to), format!("{} (out of range)", term))
+                })
+            }
+        })*
+    };
+}
+impl_integer_from_term!(
+    i8 => to_i8, u8 => to_u8, i16 => to_i16, u16 => to_u16, i32 => to_i32, u32 => to_u32,
+    i64 => to_i64, u64 => to_u64, i128 => to_i128, u128 => to_u128, isize => to_isize,
+    usize => to_usize
+);
+impl FromTerm for f64 {
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        let x: &Float = term
+            .try_as_ref()
+            .ok_or_else(|| FromTermError::unexpected::<Self>(term))?;
+        Ok(x.value)
+    }
+}
+impl FromTerm for f32 {
+    /// Accepts floats which `f32` represents exactly (see [`Float::to_f32`] for rounding).
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        let x: &Float = term
+            .try_as_ref()
+            .ok_or_else(|| FromTermError::unexpected::<Self>(term))?;
+        x.to_f32(FloatPrecisionLoss::Error)
+            .ok_or_else(|| FromTermError::new("f32", format!("{} (precision loss)", term)))
+    }
+}
+impl FromTerm for bool {
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        match term.try_as_ref() {
+            Some(Atom { name }) if &**name == "true" => Ok(true),
+            Some(Atom { name }) if &**name == "false" => Ok(false),
+            _ => Err(FromTermError::unexpected::<Self>(term)),
+        }
+    }
+}
+impl FromTerm for String {
+    /// Accepts UTF-8 encoded `Binary` and `ByteList` terms (and nil as the empty string).
+    fn from_term(term: &Term) -> Result<Self, FromTermError> {
+        let bytes: &[u8] = match *term {
+            Term::Binary(ref x) => &x.bytes,
+            Term::ByteList(ref x) => &x.bytes,
+            Term::List(ref x) if x.is_nil() => return Ok(String::new()),
+            _ => return Err(FromTermError::unexpected::<Self>(term)),
+        };
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| FromTermError::new("String", format!("{} (invalid UTF-8)", term)))
+    }
+}
+
+/// A step in the path from a root term to the subterm which failed to convert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromTermPathSegment {
+    ListElement(usize),
+    TupleElement(usize),
+    MapKey,
+    MapValue { key: String },
+    Field(String),
+}
+impl fmt::Display for FromTermPathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FromTermPathSegment::ListElement(i) => write!(f, "element {} of list", i),
+            FromTermPathSegment::TupleElement(i) => write!(f, "element {} of tuple", i),
+            FromTermPathSegment::MapKey => write!(f, "key of map"),
+            FromTermPathSegment::MapValue { ref key } => write!(f, "value for key {} of map", key),
+            FromTermPathSegment::Field(ref name) => write!(f, "field `{}`", name),
+        }
+    }
+}
+
+/// Error which can occur when converting a term with `FromTerm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromTermError {
+    /// What the conversion expected (usually a type name).
+    pub expected: String,
+
+    /// What was actually found (usually a term variant name).
+    pub actual: String,
+
+    /// The path to the failed subterm, innermost step first.
+    pub path: Vec<FromTermPathSegment>,
+}
+impl FromTermError {
+    pub fn new<E: Into<String>, A: Into<String>>(expected: E, actual: A) -> Self {
+        FromTermError {
+            expected: expected.into(),
+            actual: actual.into(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Makes an error saying that `T` was expected but `term` was found.
+    pub fn unexpected<T>(term: &Term) -> Self {
+        FromTermError::new(short_type_name::<T>(), describe(term))
+    }
+
+    /// Records that the error occurred within `segment` of an outer term.
+    pub fn within(mut self, segment: FromTermPathSegment) -> Self {
+        self.path.push(segment);
+        self
+    }
+}
+impl fmt::Display for FromTermError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.actual)?;
+        for (i, segment) in self.path.iter().enumerate() {
+            if i == 0 {
+                write!(f, " at {}", segment)?;
+            } else {
+                write!(f, ", in {}", segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for FromTermError {}
+
+fn describe(term: &Term) -> String {
+    term.variant_name().to_string()
+}
+
+/// Returns the name of `T` without module paths (e.g., `Vec<Atom>`).
+fn short_type_name<T>() -> String {
+    let full = std::any::type_name::<T>();
+    let mut name = String::with_capacity(full.len());
+    let mut segment_start = 0;
+    for (i, c) in full.char_indices() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            if c == ':' {
+                segment_start = i + 1;
+            }
+            continue;
+        }
+        name.push_str(&full[segment_start..i]);
+        name.push(c);
+        segment_start = i + c.len_utf8();
+    }
+    name.push_str(&full[segment_start..]);
+    name
+}
@@ -0,0 +1,791 @@
+//! Deserialize Rust values from terms with serde.
+//!
+//! This is the inverse of the mapping described in [`ser`](crate::ser).
+//! In addition, the following terms are accepted:
+//!
+//! - both `nil` and `undefined` as `()` and `None`
+//! - atoms and `ByteList`s as strings
+//! - `ByteList`s, and `BitBinary`s made of whole bytes, as bytes (`serde_bytes`)
+//! - `ByteList`s as sequences of `u8`
+//! - lists and tuples interchangeably as sequences
+//!
+//! The expected enum and key representations are set with [`DeserializeOptions`].
+//!
+//! # Examples
+//!
+//! ```
+//! use serde::Deserialize;
+//! use eetf::{Atom, FixInteger, Map, Term};
+//!
+//! #[derive(Debug, PartialEq, Deserialize)]
+//! struct Point {
+//!     x: i32,
+//! }
+//!
+//! let term = Term::from(Map::from([(
+//!     Term::from(Atom::from("x")),
+//!     Term::from(FixInteger::from(1)),
+//! )]));
+//! assert_eq!(eetf::from_term::<Point>(term).unwrap(), Point { x: 1 });
+//! ```
+use super::*;
+use crate::convert::FromTermPathSegment;
+use crate::ser::{EnumRepr, KeyRepr};
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use num::traits::ToPrimitive;
+use std::vec;
+
+/// Errors which can occur when deserializing a value.
+#[derive(Debug, thiserror::Error)]
+pub enum DeserializeError {
+    #[error("{0}")]
+    Message(String),
+
+    #[error(transparent)]
+    Mismatch(#[from] FromTermError),
+
+    #[error("failed to decode the input bytes")]
+    Decode(#[from] DecodeError),
+}
+impl DeserializeError {
+    fn within(self, segment: FromTermPathSegment) -> Self {
+        match self {
+            DeserializeError::Mismatch(e) => DeserializeError::Mismatch(e.within(segment)),
+            e => e,
+        }
+    }
+}
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeserializeError::Message(msg.to_string())
+    }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        DeserializeError::Mismatch(FromTermError::new(exp.to_string(), unexp.to_string()))
+    }
+
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        DeserializeError::Mismatch(FromTermError::new(exp.to_string(), unexp.to_string()))
+    }
+}
+
+/// Options for deserialization.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeserializeOptions {
+    enum_repr: EnumRepr,
+    key_repr: KeyRepr,
+    lenient_keys: bool,
+}
+impl DeserializeOptions {
+    /// Makes the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the expected representation of enum variants (default: [`EnumRepr::Atom`]).
+    pub fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+
+    /// Sets the expected representation of struct field names (default: [`KeyRepr::Atom`]).
+    pub fn key_repr(mut self, repr: KeyRepr) -> Self {
+        self.key_repr = repr;
+        self
+    }
+
+    /// If `true`, struct field names (and map enum tags) may be either atoms or binaries
+    /// regardless of `key_repr` (default: `false`).
+    pub fn lenient_keys(mut self, lenient: bool) -> Self {
+        self.lenient_keys = lenient;
+        self
+    }
+
+    fn check_key(&self, key: &Term) -> Result<(), DeserializeError> {
+        if self.lenient_keys || self.key_repr.matches(key) {
+            Ok(())
+        } else {
+            let expected = match self.key_repr {
+                KeyRepr::Atom => "atom key",
+                KeyRepr::Binary => "binary key",
+            };
+            Err(DeserializeError::Mismatch(FromTermError::new(
+                expected,
+                key.to_string(),
+            )))
+        }
+    }
+}
+
+/// Deserializes a `T` from `term`.
+pub fn from_term<T: DeserializeOwned>(term: Term) -> Result<T, DeserializeError> {
+    from_term_with_options(term, &DeserializeOptions::default())
+}
+
+/// Deserializes a `T` from `term` using `options`.
+pub fn from_term_with_options<T: DeserializeOwned>(
+    term: Term,
+    options: &DeserializeOptions,
+) -> Result<T, DeserializeError> {
+    T::deserialize(Deserializer::with_options(term, *options))
+}
+
+/// Deserializes a `T` from bytes in the external term format.
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DeserializeError> {
+    from_bytes_with_options(bytes, &DeserializeOptions::default())
+}
+
+/// Deserializes a `T` from bytes in the external term format using `options`.
+pub fn from_bytes_with_options<T: DeserializeOwned>(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<T, DeserializeError> {
+    from_term_with_options(Term::decode(io::Cursor::new(bytes))?, options)
+}
+
+/// Deserializer whose input is an owned `Term`.
+///
+/// Owning the term allows strings and bytes to be moved out without copying.
+#[derive(Debug)]
+pub struct Deserializer {
+    term: Term,
+    options: DeserializeOptions,
+}
+impl Deserializer {
+    pub fn new(term: Term) -> Self {
+        Self::with_options(term, DeserializeOptions::default())
+    }
+
+    pub fn with_options(term: Term, options: DeserializeOptions) -> Self {
+        Deserializer { term, options }
+    }
+
+    fn mismatch(&self, expected: &str) -> DeserializeError {
+        DeserializeError::Mismatch(FromTermError::new(expected, self.term.to_string()))
+    }
+
+    fn into_enum(self) -> Result<EnumDeserializer, DeserializeError> {
+        let options = self.options;
+        let enum_deserializer = |variant, payload| EnumDeserializer {
+            variant,
+            payload,
+            options,
+        };
+        match (options.enum_repr, self.term) {
+            (EnumRepr::Atom, term @ Term::Atom(_)) => Ok(enum_deserializer(term, Payload::None)),
+            (EnumRepr::Atom, Term::Tuple(x))
+                if x.elements.len() >= 2 && matches!(x.elements[0], Term::Atom(_)) =>
+            {
+                let mut elements = x.elements;
+                let variant = elements.remove(0);
+                Ok(enum_deserializer(variant, Payload::Flat(elements)))
+            }
+            (EnumRepr::TaggedTuple, Term::Tuple(x))
+                if matches!(x.elements.first(), Some(Term::Atom(_))) =>
+            {
+                let mut elements = x.elements;
+                let variant = elements.remove(0);
+                let payload = if elements.is_empty() {
+                    Payload::None
+                } else {
+                    Payload::Flat(elements)
+                };
+                Ok(enum_deserializer(variant, payload))
+            }
+            (EnumRepr::ExternallyTaggedMap, Term::Map(x)) if x.map.len() == 1 => {
+                let (variant, value) = x.map.into_iter().next().expect("never fails");
+                options.check_key(&variant)?;
+                Ok(enum_deserializer(variant, Payload::Nested(value)))
+            }
+            (repr, term) => {
+                let expected = match repr {
+                    EnumRepr::Atom => "atom or tagged tuple",
+                    EnumRepr::TaggedTuple => "tagged tuple",
+                    EnumRepr::ExternallyTaggedMap => "single-entry map",
+                };
+                Err(Deserializer::with_options(term, options).mismatch(expected))
+            }
+        }
+    }
+}
+
+fn is_unit_atom(term: &Term) -> bool {
+    match term {
+        Term::Atom(a) => a.as_str() == "nil" || a.as_str() == "undefined",
+        _ => false,
+    }
+}
+
+fn byte_terms(bytes: Vec<u8>) -> Vec<Term> {
+    bytes.into_iter().map(Term::from).collect()
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let options = self.options;
+        match self.term {
+            Term::Atom(a) => match a.as_str() {
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                "nil" | "undefined" => visitor.visit_unit(),
+                _ => visitor.visit_str(&a.name),
+            },
+            Term::FixInteger(x) => visitor.visit_i32(x.value),
+            Term::BigInteger(ref x) => {
+                if let Some(v) = x.value.to_i64() {
+                    visitor.visit_i64(v)
+                } else if let Some(v) = x.value.to_u64() {
+                    visitor.visit_u64(v)
+                } else if let Some(v) = x.value.to_i128() {
+                    visitor.visit_i128(v)
+                } else if let Some(v) = x.value.to_u128() {
+                    visitor.visit_u128(v)
+                } else {
+                    Err(self.mismatch("integer within 128 bits"))
+                }
+            }
+            Term::Float(x) => visitor.visit_f64(x.value),
+            Term::Binary(x) => match String::from_utf8(bytes_into_vec(x.bytes)) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Term::ByteList(x) => {
+                visitor.visit_seq(SeqDeserializer::new(byte_terms(x.bytes), options))
+            }
+            Term::List(x) => visitor.visit_seq(SeqDeserializer::new(x.elements, options)),
+            Term::Tuple(x) => visitor.visit_seq(SeqDeserializer::new(x.elements, options)),
+            Term::Map(x) => visitor.visit_map(MapDeserializer::new(x.map, options, false)),
+            _ => Err(self.mismatch("term supported by serde")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.term {
+            Term::Atom(a) => visitor.visit_str(&a.name),
+            Term::ByteList(x) => match String::from_utf8(x.bytes) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.term {
+            // The buffer is moved out of the term, so no copy is made.
+            Term::Binary(x) => visitor.visit_byte_buf(bytes_into_vec(x.bytes)),
+            Term::ByteList(x) => visitor.visit_byte_buf(x.bytes),
+            Term::BitBinary(x) if x.tail_bits_size == 8 => {
+                visitor.visit_byte_buf(bytes_into_vec(x.bytes))
+            }
+            Term::BitBinary(x) if x.tail_bits_size == 0 => {
+                let mut bytes = bytes_into_vec(x.bytes);
+                bytes.pop();
+                visitor.visit_byte_buf(bytes)
+            }
+            Term::BitBinary(ref x) => Err(self.mismatch(&format!(
+                "bitstring of whole bytes (got {} tail bits)",
+                x.tail_bits_size
+            ))),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if is_unit_atom(&self.term) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if is_unit_atom(&self.term) {
+            visitor.visit_unit()
+        } else {
+            Err(self.mismatch("'nil' or 'undefined'"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.term {
+            Term::Map(x) => visitor.visit_map(MapDeserializer::new(x.map, self.options, true)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.into_enum()?)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
+        seq tuple tuple_struct map
+    }
+}
+impl<'de> IntoDeserializer<'de, DeserializeError> for Term {
+    type Deserializer = Deserializer;
+    fn into_deserializer(self) -> Deserializer {
+        Deserializer::new(self)
+    }
+}
+
+struct SeqDeserializer {
+    elements: vec::IntoIter<Term>,
+    index: usize,
+    options: DeserializeOptions,
+}
+impl SeqDeserializer {
+    fn new(elements: Vec<Term>, options: DeserializeOptions) -> Self {
+        SeqDeserializer {
+            elements: elements.into_iter(),
+            index: 0,
+            options,
+        }
+    }
+}
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let Some(term) = self.elements.next() else {
+            return Ok(None);
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(Deserializer::with_options(term, self.options))
+            .map(Some)
+            .map_err(|e| e.within(FromTermPathSegment::ListElement(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapDeserializer {
+    entries: crate::term_map::IntoIter,
+    value: Option<(String, Term)>,
+    options: DeserializeOptions,
+    is_struct: bool,
+}
+impl MapDeserializer {
+    fn new(map: TermMap, options: DeserializeOptions, is_struct: bool) -> Self {
+        MapDeserializer {
+            entries: map.into_iter(),
+            value: None,
+            options,
+            is_struct,
+        }
+    }
+}
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        if self.is_struct {
+            self.options
+                .check_key(&key)
+                .map_err(|e| e.within(FromTermPathSegment::MapKey))?;
+        }
+        let name = match &key {
+            Term::Atom(a) => a.name.to_string(),
+            k => k.to_string(),
+        };
+        self.value = Some((name, value));
+        seed.deserialize(Deserializer::with_options(key, self.options))
+            .map(Some)
+            .map_err(|e| e.within(FromTermPathSegment::MapKey))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| DeserializeError::Message("value is missing".to_string()))?;
+        seed.deserialize(Deserializer::with_options(value, self.options))
+            .map_err(|e| e.within(FromTermPathSegment::MapValue { key }))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// The payload of an enum variant.
+enum Payload {
+    /// Unit variant in a tuple representation.
+    None,
+
+    /// The elements following the tag of a tagged tuple.
+    Flat(Vec<Term>),
+
+    /// The value of a single-entry map.
+    Nested(Term),
+}
+
+struct EnumDeserializer {
+    variant: Term,
+    payload: Payload,
+    options: DeserializeOptions,
+}
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = DeserializeError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), Self::Error> {
+        let variant = seed.deserialize(Deserializer::with_options(self.variant, self.options))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                payload: self.payload,
+                options: self.options,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    payload: Payload,
+    options: DeserializeOptions,
+}
+impl VariantDeserializer {
+    fn single(
+        self,
+        expected: &str,
+    ) -> Result<(Deserializer, FromTermPathSegment), DeserializeError> {
+        let options = self.options;
+        match self.payload {
+            Payload::Flat(mut values) if values.len() == 1 => Ok((
+                Deserializer::with_options(values.pop().expect("never fails"), options),
+                FromTermPathSegment::TupleElement(1),
+            )),
+            Payload::Nested(value) => Ok((
+                Deserializer::with_options(value, options),
+                FromTermPathSegment::MapValue {
+                    key: "variant".to_string(),
+                },
+            )),
+            _ => Err(self.mismatch(expected)),
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> DeserializeError {
+        let actual = match &self.payload {
+            Payload::None => "unit variant".to_string(),
+            Payload::Flat(values) => format!("variant with {} values", values.len()),
+            Payload::Nested(value) => value.to_string(),
+        };
+        DeserializeError::Mismatch(FromTermError::new(expected, actual))
+    }
+}
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.payload {
+            Payload::None => Ok(()),
+            Payload::Nested(ref value) if is_unit_atom(value) => Ok(()),
+            _ => Err(self.mismatch("unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let (deserializer, segment) = self.single("newtype variant")?;
+        seed.deserialize(deserializer)
+            .map_err(|e| e.within(segment))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.payload {
+            Payload::Flat(values) => visitor.visit_seq(SeqDeserializer::new(values, self.options)),
+            Payload::Nested(value) => de::Deserializer::deserialize_seq(
+                Deserializer::with_options(value, self.options),
+                visitor,
+            ),
+            Payload::None => Err(self.mismatch("tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (deserializer, segment) = self.single("struct variant")?;
+        de::Deserializer::deserialize_struct(deserializer, "", fields, visitor)
+            .map_err(|e| e.within(segment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ser::{
+        to_bytes, to_bytes_with_options, to_term, to_term_with_options, SerializeOptions,
+    };
+    use ::serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+        score: f64,
+        nickname: Option<String>,
+        role: Role,
+        location: (i32, i32),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Guest,
+        Admin(u8),
+        Custom(String, bool),
+        Scoped { scope: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper(i64);
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    #[test]
+    fn round_trip_works() {
+        for role in [
+            Role::Guest,
+            Role::Admin(3),
+            Role::Custom("ops".to_string(), true),
+            Role::Scoped {
+                scope: "billing".to_string(),
+            },
+        ] {
+            let user = User {
+                id: u64::MAX,
+                name: "joe".to_string(),
+                tags: vec!["a".to_string(), "b".to_string()],
+                score: 1.5,
+                nickname: None,
+                role,
+                location: (-1, 2),
+            };
+            let bytes = to_bytes(&user).unwrap();
+            assert_eq!(from_bytes::<User>(&bytes).unwrap(), user);
+        }
+
+        assert_eq!(
+            from_term::<Wrapper>(to_term(&Wrapper(i64::MIN)).unwrap()).unwrap(),
+            Wrapper(i64::MIN)
+        );
+        assert_eq!(
+            from_term::<i128>(to_term(&i128::MAX).unwrap()).unwrap(),
+            i128::MAX
+        );
+    }
+
+    #[test]
+    fn mismatch_reports_path() {
+        let term = Term::from(Map::from([(
+            atom("tags"),
+            Term::from(List::from(vec![Term::from(1)])),
+        )]));
+
+        #[derive(Debug, Deserialize)]
+        struct Tags {
+            #[allow(dead_code)]
+            tags: Vec<String>,
+        }
+        let e = from_term::<Tags>(term).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expected a string, found integer `1` at element 0 of list, in value for key tags of map"
+        );
+    }
+
+    #[test]
+    fn enum_and_key_repr_matrix_works() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Account {
+            owner: String,
+            role: Role,
+        }
+
+        let roles = [
+            Role::Guest,
+            Role::Admin(3),
+            Role::Custom("ops".to_string(), true),
+            Role::Scoped {
+                scope: "billing".to_string(),
+            },
+        ];
+        for enum_repr in [
+            EnumRepr::Atom,
+            EnumRepr::TaggedTuple,
+            EnumRepr::ExternallyTaggedMap,
+        ] {
+            for key_repr in [KeyRepr::Atom, KeyRepr::Binary] {
+                let ser_options = SerializeOptions::new()
+                    .enum_repr(enum_repr)
+                    .key_repr(key_repr);
+                let de_options = DeserializeOptions::new()
+                    .enum_repr(enum_repr)
+                    .key_repr(key_repr);
+                for role in &roles {
+                    let account = Account {
+                        owner: "joe".to_string(),
+                        role: from_term(to_term(role).unwrap()).unwrap(),
+                    };
+                    let bytes = to_bytes_with_options(&account, &ser_options).unwrap();
+                    let decoded: Account = from_bytes_with_options(&bytes, &de_options)
+                        .unwrap_or_else(|e| panic!("{:?}/{:?}: {}", enum_repr, key_repr, e));
+                    assert_eq!(decoded, account);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_keys_work() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Point {
+            x: i32,
+        }
+
+        let binary_keys = SerializeOptions::new().key_repr(KeyRepr::Binary);
+        let term = to_term_with_options(&Point { x: 1 }, &binary_keys).unwrap();
+        assert!(from_term::<Point>(term.clone()).is_err());
+
+        let lenient = DeserializeOptions::new().lenient_keys(true);
+        assert_eq!(
+            from_term_with_options::<Point>(term, &lenient).unwrap(),
+            Point { x: 1 }
+        );
+        assert_eq!(
+            from_term_with_options::<Point>(to_term(&Point { x: 2 }).unwrap(), &lenient).unwrap(),
+            Point { x: 2 }
+        );
+    }
+
+    #[test]
+    fn large_byte_buffers_are_binaries_and_not_copied() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Blob {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        let payload = vec![7; 1024 * 1024];
+        let bytes = to_bytes(&serde_bytes::Bytes::new(&payload)).unwrap();
+        assert_eq!(bytes[1], 109); // BINARY_EXT
+        assert_eq!(bytes.len(), 1 + 1 + 4 + payload.len());
+
+        let blob = Blob { data: payload };
+        let term = to_term(&blob).unwrap();
+        let Term::Map(map) = term else {
+            panic!("{}", term)
+        };
+        let Some(Term::Binary(binary)) = map.map.get(&atom("data")) else {
+            panic!("data is not a binary")
+        };
+        assert_eq!(binary.bytes.len(), blob.data.len());
+
+        let decoded = Term::decode(io::Cursor::new(&bytes)).unwrap();
+        let Term::Binary(ref binary) = decoded else {
+            panic!("{}", decoded)
+        };
+        let ptr = binary.bytes.as_ptr();
+        #[cfg(not(feature = "bytes"))]
+        let capacity = binary.bytes.capacity();
+        let buf = from_term::<serde_bytes::ByteBuf>(decoded)
+            .unwrap()
+            .into_vec();
+        assert_eq!(buf.as_ptr(), ptr);
+        #[cfg(not(feature = "bytes"))]
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn byte_like_terms_are_accepted_as_bytes() {
+        let expected = serde_bytes::ByteBuf::from(vec![1, 2, 3]);
+        for term in [
+            Term::from(Binary::from(vec![1, 2, 3])),
+            Term::from(ByteList::from(vec![1, 2, 3])),
+            Term::from(BitBinary::from((vec![1, 2, 3], 8))),
+            Term::from(BitBinary::from((vec![1, 2, 3, 0], 0))),
+        ] {
+            assert_eq!(from_term::<serde_bytes::ByteBuf>(term).unwrap(), expected);
+        }
+        assert!(
+            from_term::<serde_bytes::ByteBuf>(Term::from(BitBinary::from((vec![1, 2, 3], 4))))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! Merging of maps and differences between terms.
+use super::*;
+use crate::redact::key_name;
+
+/// How [`Map::merge`] combines the values of the keys which both maps have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The values of the other map win (as in `maps:merge/2`).
+    Shallow,
+
+    /// Nested maps are merged recursively, and the other map wins for the other values.
+    Deep,
+
+    /// As `Deep`, except that the elements of the lists of the other map are appended to the
+    /// lists of this one.
+    AppendLists,
+}
+
+impl Map {
+    /// Returns the entries of `other` merged into the entries of this map.
+    ///
+    /// Nested maps are merged without recursion, so maps of any depth can be merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eetf::{Atom, Map, MergeStrategy, Term};
+    ///
+    /// let key = |name| Term::from(Atom::from(name));
+    /// let db = |pool| Term::from(Map::from([(key("host"), key("localhost")), (key("pool"), pool)]));
+    /// let defaults = Map::from([(key("db"), db(Term::from(10)))]);
+    /// let overlay = Map::from([(key("db"), Term::from(Map::from([(key("pool"), Term::from(20))])))]);
+    ///
+    /// let merged = defaults.merge(&overlay, MergeStrategy::Deep);
+    /// assert_eq!(merged, Map::from([(key("db"), db(Term::from(20)))]));
+    /// assert_eq!(defaults.merge(&overlay, MergeStrategy::Shallow), overlay);
+    /// ```
+    pub fn merge(&self, other: &Map, strategy: MergeStrategy) -> Map {
+        let mut map = self.map.clone();
+        if strategy == MergeStrategy::Shallow {
+            for (k, v) in other.map.iter() {
+                map.insert(k.clone(), v.clone());
+            }
+            return Map { map };
+        }
+
+        // The maps being merged, each with the rest of the other map's entries, and the key
+        // whose nested maps are being merged in the next frame.
+        let mut stack = vec![(map, other.map.iter(), None)];
+        loop {
+            let (merged, entries, nested) = stack.last_mut().expect("unreachable");
+            let Some((k, v)) = entries.next() else {
+                let (map, _, _) = stack.pop().expect("unreachable");
+                match stack.last_mut() {
+                    None => return Map { map },
+                    Some((merged, _, nested)) => {
+                        let key = nested.take().expect("unreachable");
+                        merged.insert(key, Term::from(Map { map }));
+                    }
+                }
+                continue;
+            };
+            match (merged.remove(k), v) {
+                (Some(Term::Map(old)), Term::Map(new)) => {
+                    *nested = Some(k.clone());
+                    stack.push((old.map, new.map.iter(), None));
+                }
+                (Some(Term::List(mut old)), Term::List(new))
+                    if strategy == MergeStrategy::AppendLists =>
+                {
+                    old.elements.extend_from_slice(&new.elements);
+                    merged.insert(k.clone(), Term::from(old));
+                }
+                _ => {
+                    merged.insert(k.clone(), v.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Differences between two terms, as found by [`Term::diff`].
+///
+/// It displays one change per line (e.g., `~ db.pool: 10 -> 20`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TermDiff {
+    /// The changes, in the order of their paths in the terms.
+    pub changes: Vec<TermChange>,
+}
+impl TermDiff {
+    /// Returns `true` if the terms are equal.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+impl fmt::Display for TermDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, change) in self.changes.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// A subterm which differs between two terms.
+///
+/// A path is made of map keys (named as in [`RedactPolicy::paths`]) and the indices of tuple or
+/// list elements. It is empty for the terms themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermChange {
+    /// The key is only in the new map.
+    Added { path: Vec<String>, value: Term },
+
+    /// The key is only in the old map.
+    Removed { path: Vec<String>, value: Term },
+
+    /// The subterm has another value.
+    Changed {
+        path: Vec<String>,
+        old: Term,
+        new: Term,
+    },
+}
+impl TermChange {
+    pub fn path(&self) -> &[String] {
+        match self {
+            TermChange::Added { path, .. }
+            | TermChange::Removed { path, .. }
+            | TermChange::Changed { path, .. } => path,
+        }
+    }
+}
+impl fmt::Display for TermChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = match self.path() {
+            [] => ".".to_owned(),
+            path => path.join("."),
+        };
+        match self {
+            TermChange::Added { value, .. } => write!(f, "+ {}: {}", path, value),
+            TermChange::Removed { value, .. } => write!(f, "- {}: {}", path, value),
+            TermChange::Changed { old, new, .. } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+impl Term {
+    /// Returns the differences from this term to `other` (e.g., to log what changed between
+    /// two snapshots of a configuration).
+    ///
+    /// Maps are compared by key, and tuples and lists of the same length by element. Other
+    /// subterms (including lists of other lengths) are changed as a whole. The terms are
+    /// walked without recursion, so terms of any depth can be compared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eetf::{Atom, Map, Term};
+    ///
+    /// let config = |pool| Term::from(Map::from([(Term::from(Atom::from("pool")), Term::from(pool))]));
+    /// let diff = config(10).diff(&config(20));
+    /// assert_eq!(diff.to_string(), "~ pool: 10 -> 20");
+    /// ```
+    pub fn diff(&self, other: &Term) -> TermDiff {
+        // The segments of the paths, each with the index of its parent segment.
+        let mut segments: Vec<(usize, String)> = Vec::new();
+        let path = |segments: &[(usize, String)], mut i: usize| {
+            let mut path = Vec::new();
+            while i != usize::MAX {
+                path.push(segments[i].1.clone());
+                i = segments[i].0;
+            }
+            path.reverse();
+            path
+        };
+        let mut changes = Vec::new();
+        let mut stack = vec![(usize::MAX, Some(self), Some(other))];
+        while let Some((at, old, new)) = stack.pop() {
+            let (old, new) = match (old, new) {
+                (Some(old), Some(new)) => (old, new),
+                (Some(value), None) => {
+                    let path = path(&segments, at);
+                    let value = value.clone();
+                    changes.push(TermChange::Removed { path, value });
+                    continue;
+                }
+                (None, Some(value)) => {
+                    let path = path(&segments, at);
+                    let value = value.clone();
+                    changes.push(TermChange::Added { path, value });
+                    continue;
+                }
+                (None, None) => unreachable!(),
+            };
+            // The subterms are pushed in reverse, so that they are compared in order.
+            match (old, new) {
+                (Term::Map(old), Term::Map(new)) => {
+                    let mut keys = old
+                        .map
+                        .keys()
+                        .chain(new.map.keys().filter(|k| !old.map.contains_key(k)))
+                        .map(|k| (key_name(k).unwrap_or_else(|| k.to_string()), k))
+                        .collect::<Vec<_>>();
+                    keys.sort_by(|(a, _), (b, _)| b.cmp(a));
+                    for (name, k) in keys {
+                        segments.push((at, name));
+                        stack.push((segments.len() - 1, old.map.get(k), new.map.get(k)));
+                    }
+                }
+                (Term::Tuple(Tuple { elements: old }), Term::Tuple(Tuple { elements: new }))
+                | (Term::List(List { elements: old }), Term::List(List { elements: new }))
+                    if old.len() == new.len() =>
+                {
+                    for (i, (old, new)) in old.iter().zip(new).enumerate().rev() {
+                        segments.push((at, i.to_string()));
+                        stack.push((segments.len() - 1, Some(old), Some(new)));
+                    }
+                }
+                // Compound terms are compared by their subterms, so that each is compared once.
+                _ if old == new => {}
+                _ => changes.push(TermChange::Changed {
+                    path: path(&segments, at),
+                    old: old.clone(),
+                    new: new.clone(),
+                }),
+            }
+        }
+        TermDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn map<const N: usize>(entries: [(&str, Term); N]) -> Term {
+        Term::from(Map::from(entries.map(|(k, v)| (atom(k), v))))
+    }
+
+    fn list<const N: usize>(elements: [Term; N]) -> Term {
+        Term::from(List::from(elements.to_vec()))
+    }
+
+    fn merge(a: Term, b: Term, strategy: MergeStrategy) -> Term {
+        let (Term::Map(a), Term::Map(b)) = (a, b) else {
+            panic!()
+        };
+        Term::from(a.merge(&b, strategy))
+    }
+
+    #[test]
+    fn deep_merge_overrides_nested_keys() {
+        let defaults = map([
+            ("name", atom("app")),
+            (
+                "db",
+                map([
+                    ("host", atom("localhost")),
+                    (
+                        "pool",
+                        map([("size", Term::from(10)), ("timeout", Term::from(5000))]),
+                    ),
+                ]),
+            ),
+            ("tags", list([atom("a")])),
+        ]);
+        let overlay = map([
+            ("db", map([("pool", map([("size", Term::from(20))]))])),
+            ("tags", list([atom("b")])),
+        ]);
+        let expected = |tags| {
+            map([
+                ("name", atom("app")),
+                (
+                    "db",
+                    map([
+                        ("host", atom("localhost")),
+                        (
+                            "pool",
+                            map([("size", Term::from(20)), ("timeout", Term::from(5000))]),
+                        ),
+                    ]),
+                ),
+                ("tags", tags),
+            ])
+        };
+        assert_eq!(
+            merge(defaults.clone(), overlay.clone(), MergeStrategy::Deep),
+            expected(list([atom("b")]))
+        );
+        assert_eq!(
+            merge(
+                defaults.clone(),
+                overlay.clone(),
+                MergeStrategy::AppendLists
+            ),
+            expected(list([atom("a"), atom("b")]))
+        );
+        assert_eq!(
+            merge(defaults, overlay.clone(), MergeStrategy::Shallow),
+            map([
+                ("name", atom("app")),
+                ("db", map([("pool", map([("size", Term::from(20))]))])),
+                ("tags", list([atom("b")])),
+            ])
+        );
+    }
+
+    #[test]
+    fn diff_reports_changed_paths() {
+        let v1 = map([
+            ("name", atom("app")),
+            (
+                "db",
+                map([("host", atom("localhost")), ("pool", Term::from(10))]),
+            ),
+            ("nodes", list([atom("a"), atom("b")])),
+            ("debug", atom("false")),
+        ]);
+        let v2 = map([
+            ("name", atom("app")),
+            ("db", map([("host", atom("db1")), ("pool", Term::from(10))])),
+            ("nodes", list([atom("a"), atom("c")])),
+            ("trace", atom("true")),
+        ]);
+        let diff = v1.diff(&v2);
+        assert_eq!(
+            diff.changes,
+            [
+                TermChange::Changed {
+                    path: vec!["db".to_owned(), "host".to_owned()],
+                    old: atom("localhost"),
+                    new: atom("db1"),
+                },
+                TermChange::Removed {
+                    path: vec!["debug".to_owned()],
+                    value: atom("false"),
+                },
+                TermChange::Changed {
+                    path: vec!["nodes".to_owned(), "1".to_owned()],
+                    old: atom("b"),
+                    new: atom("c"),
+                },
+                TermChange::Added {
+                    path: vec!["trace".to_owned()],
+                    value: atom("true"),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "~ db.host: 'localhost' -> 'db1'\n- debug: 'false'\n~ nodes.1: 'b' -> 'c'\n+ trace: 'true'"
+        );
+        assert!(v1.diff(&v1).is_empty());
+        assert_eq!(
+            Term::from(1).diff(&Term::from(2)).to_string(),
+            "~ .: 1 -> 2"
+        );
+    }
+
+    #[test]
+    fn deep_terms_are_merged_and_compared() {
+        let (mut a, mut b) = (map([("x", Term::from(1))]), map([("y", Term::from(2))]));
+        // Deeper terms would overflow the stack when they are cloned or dropped.
+        for _ in 0..1000 {
+            a = map([("x", a)]);
+            b = map([("x", b)]);
+        }
+        let merged = merge(a.clone(), b.clone(), MergeStrategy::Deep);
+        let changes = a.diff(&merged).changes;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path().len(), 1001);
+        assert!(matches!(changes[0], TermChange::Added { .. }));
+        assert_eq!(b.diff(&merged).changes.len(), 1);
+    }
+}
@@ -0,0 +1,694 @@
+//! Erlang distribution protocol messages.
+//!
+//! # Reference
+//!
+//! - [Distribution Header](https://www.erlang.org/doc/apps/erts/erl_ext_dist.html#distribution-header)
+use super::*;
+use crate::codec_common::DISTRIBUTION_HEADER;
+#[cfg(feature = "std")]
+use crate::codec_common::VERSION;
+
+#[cfg(feature = "distribution")]
+pub mod handshake;
+#[cfg(feature = "std")]
+pub mod local_node;
+#[cfg(feature = "distribution")]
+pub mod node;
+
+/// The number of segments of an atom cache.
+pub const ATOM_CACHE_SEGMENTS: usize = 8;
+
+/// The number of entries in each segment of an atom cache.
+pub const ATOM_CACHE_SEGMENT_SIZE: usize = 256;
+
+/// The number of entries of an atom cache.
+pub const ATOM_CACHE_SIZE: usize = ATOM_CACHE_SEGMENTS * ATOM_CACHE_SEGMENT_SIZE;
+
+/// The maximum number of atom cache references in a distribution header.
+pub const MAX_ATOM_CACHE_REFS: usize = 255;
+
+bitflags::bitflags! {
+    /// Distribution capability flags exchanged in the handshake.
+    ///
+    /// The negotiated flags (i.e., the intersection of both sides' flags) decide which
+    /// tags may be sent to the peer (see [`crate::EncoderOptions::for_peer`]).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct DistFlags: u64 {
+        const PUBLISHED = 0x01;
+        const ATOM_CACHE = 0x02;
+        const EXTENDED_REFERENCES = 0x04;
+        const DIST_MONITOR = 0x08;
+        const FUN_TAGS = 0x10;
+        const DIST_MONITOR_NAME = 0x20;
+        const HIDDEN_ATOM_CACHE = 0x40;
+        const NEW_FUN_TAGS = 0x80;
+        const EXTENDED_PIDS_PORTS = 0x100;
+        const EXPORT_PTR_TAG = 0x200;
+        const BIT_BINARIES = 0x400;
+        const NEW_FLOATS = 0x800;
+        const UNICODE_IO = 0x1000;
+        const DIST_HDR_ATOM_CACHE = 0x2000;
+        const SMALL_ATOM_TAGS = 0x4000;
+        const UTF8_ATOMS = 0x10000;
+        const MAP_TAG = 0x20000;
+        const BIG_CREATION = 0x40000;
+        const SEND_SENDER = 0x80000;
+        const BIG_SEQTRACE_LABELS = 0x100000;
+        const EXIT_PAYLOAD = 0x400000;
+        const FRAGMENTS = 0x800000;
+        const HANDSHAKE_23 = 0x1000000;
+        const UNLINK_ID = 0x2000000;
+        const MANDATORY_25_DIGEST = 0x4000000;
+        const SPAWN = 1 << 32;
+        const NAME_ME = 1 << 33;
+        const V4_NC = 1 << 34;
+        const ALIAS = 1 << 35;
+        const LOCAL_EXT = 1 << 36;
+        const ALTACT_SIG = 1 << 37;
+    }
+}
+impl DistFlags {
+    /// Returns the flags which OTP 26 and later require of every peer.
+    pub const fn mandatory() -> Self {
+        Self::EXTENDED_REFERENCES
+            .union(Self::FUN_TAGS)
+            .union(Self::NEW_FUN_TAGS)
+            .union(Self::EXTENDED_PIDS_PORTS)
+            .union(Self::EXPORT_PTR_TAG)
+            .union(Self::BIT_BINARIES)
+            .union(Self::NEW_FLOATS)
+            .union(Self::UTF8_ATOMS)
+            .union(Self::MAP_TAG)
+            .union(Self::BIG_CREATION)
+            .union(Self::HANDSHAKE_23)
+            .union(Self::UNLINK_ID)
+            .union(Self::V4_NC)
+    }
+}
+impl Default for DistFlags {
+    /// Returns the mandatory flags, the distribution header atom cache, and process monitoring.
+    fn default() -> Self {
+        Self::mandatory() | Self::DIST_HDR_ATOM_CACHE | Self::DIST_MONITOR
+    }
+}
+
+/// Atom cache of a distribution connection.
+///
+/// Each direction of a connection has its own cache, which is populated by
+/// the new cache entries of the distribution headers sent in that direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtomCache {
+    entries: Vec<Option<Atom>>,
+}
+impl AtomCache {
+    /// Makes an empty cache.
+    pub fn new() -> Self {
+        AtomCache {
+            entries: vec![None; ATOM_CACHE_SIZE],
+        }
+    }
+
+    /// Returns the atom at `index` (i.e., `SegmentIndex * 256 + InternalSegmentIndex`).
+    pub fn get(&self, index: usize) -> Option<&Atom> {
+        self.entries.get(index).and_then(Option::as_ref)
+    }
+
+    /// Returns the atom at `internal_index` of segment `segment`.
+    pub fn resolve(&self, segment: u8, internal_index: u8) -> Option<&Atom> {
+        self.get(usize::from(segment) * ATOM_CACHE_SEGMENT_SIZE + usize::from(internal_index))
+    }
+
+    /// Sets the atom at `index`, returning the previous one.
+    ///
+    /// This is how a receiver records the new entries of a header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`ATOM_CACHE_SIZE`].
+    pub fn set(&mut self, index: usize, atom: Atom) -> Option<Atom> {
+        self.entries[index].replace(atom)
+    }
+
+    /// Assigns `atom` its entry (see [`AtomCache::slot_for`]), overwriting any other atom there.
+    ///
+    /// The returned reference is a new entry unless the atom was already cached.
+    pub fn insert(&mut self, atom: &Atom) -> CacheRef {
+        let index = Self::slot_for(atom);
+        let new_entry = self.get(index) != Some(atom);
+        if new_entry {
+            self.set(index, atom.clone());
+        }
+        CacheRef {
+            segment: (index / ATOM_CACHE_SEGMENT_SIZE) as u8,
+            internal_index: (index % ATOM_CACHE_SEGMENT_SIZE) as u8,
+            new_entry,
+        }
+    }
+
+    /// Selects the atoms to be referenced by the header of a message, and inserts them.
+    ///
+    /// `atoms` are in encoding order and may repeat. At most [`MAX_ATOM_CACHE_REFS`] atoms are selected,
+    /// and an atom is skipped if its entry is already taken by an earlier atom of the message,
+    /// since both could not be referenced at once. Skipped atoms are encoded inline.
+    pub fn insert_message_atoms<'a, I>(&mut self, atoms: I) -> Vec<(&'a Atom, CacheRef)>
+    where
+        I: IntoIterator<Item = &'a Atom>,
+    {
+        let mut selected = Vec::new();
+        let mut seen = HashSet::new();
+        let mut used_slots = HashSet::new();
+        for atom in atoms {
+            if selected.len() == MAX_ATOM_CACHE_REFS {
+                break;
+            }
+            if atom.name.len() > 0xFFFF || !seen.insert(atom) {
+                continue;
+            }
+            if !used_slots.insert(Self::slot_for(atom)) {
+                continue;
+            }
+            selected.push((atom, self.insert(atom)));
+        }
+        selected
+    }
+
+    /// Returns the index of the entry to which an encoder assigns `atom`.
+    ///
+    /// The index is derived from a hash of the atom name, so an atom evicts any other atom with the same index.
+    pub fn slot_for(atom: &Atom) -> usize {
+        // hashpjw
+        let mut h: u32 = 0;
+        for &b in atom.name.as_bytes() {
+            h = (h << 4).wrapping_add(u32::from(b));
+            let g = h & 0xF000_0000;
+            if g != 0 {
+                h ^= g >> 24;
+                h ^= g;
+            }
+        }
+        h as usize % ATOM_CACHE_SIZE
+    }
+}
+impl Default for AtomCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entry of an atom cache assigned to an atom by [`AtomCache::insert`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheRef {
+    pub segment: u8,
+    pub internal_index: u8,
+
+    /// Whether the entry was created or replaced (i.e., the header must carry the atom text).
+    pub new_entry: bool,
+}
+impl CacheRef {
+    /// Returns the index of the entry in the atom cache.
+    pub fn index(&self) -> usize {
+        usize::from(self.segment) * ATOM_CACHE_SEGMENT_SIZE + usize::from(self.internal_index)
+    }
+
+    /// Returns the half byte of flags of this reference in a distribution header.
+    pub fn flags(&self) -> u8 {
+        if self.new_entry {
+            0x08 | self.segment
+        } else {
+            self.segment
+        }
+    }
+}
+
+/// Atom cache reference of a distribution header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtomCacheRef {
+    /// The index of the atom in the atom cache.
+    pub cache_index: usize,
+
+    /// Whether the header carried the atom text (i.e., created or replaced the cache entry).
+    pub new_entry: bool,
+
+    /// The atom.
+    pub atom: Atom,
+}
+
+/// Distribution header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistHeader {
+    /// The atom cache references; `ATOM_CACHE_REF` entries in the terms index this list.
+    pub atom_cache_refs: Vec<AtomCacheRef>,
+
+    /// Whether the atom texts have 2 byte lengths.
+    pub long_atoms: bool,
+}
+
+/// Distribution message (i.e., a distribution header followed by a control message and an optional payload).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistMessage {
+    pub header: DistHeader,
+    pub control: Term,
+    pub payload: Option<Term>,
+}
+
+/// Selects the atoms of a message to be cached, updates `cache`, and encodes the distribution header.
+///
+/// Returns the header (including the version number) and the atom cache reference index of each selected atom.
+#[cfg(feature = "std")]
+pub(crate) fn encode_distribution_header(
+    control: &Term,
+    payload: Option<&Term>,
+    cache: &mut AtomCache,
+) -> (Vec<u8>, HashMap<Atom, u8>) {
+    let mut atoms = Vec::new();
+    collect_atoms(control, &mut atoms);
+    if let Some(payload) = payload {
+        collect_atoms(payload, &mut atoms);
+    }
+    let selected = cache.insert_message_atoms(atoms);
+
+    let mut buf = vec![VERSION];
+    write_header(&selected, &mut buf);
+    let refs = selected
+        .iter()
+        .enumerate()
+        .map(|(i, &(atom, _))| (atom.clone(), i as u8))
+        .collect();
+    (buf, refs)
+}
+
+/// Writes a distribution header (without the version number) referencing `refs`.
+///
+/// # Panics
+///
+/// Panics if there are more than [`MAX_ATOM_CACHE_REFS`] references, or if an atom is longer than 65535 bytes.
+pub fn write_header(refs: &[(&Atom, CacheRef)], buf: &mut Vec<u8>) {
+    assert!(refs.len() <= MAX_ATOM_CACHE_REFS);
+    buf.push(DISTRIBUTION_HEADER);
+    buf.push(refs.len() as u8);
+    if refs.is_empty() {
+        return;
+    }
+
+    let long_atoms = refs
+        .iter()
+        .any(|(atom, r)| r.new_entry && atom.name.len() > 0xFF);
+    let mut flags = vec![0u8; refs.len() / 2 + 1];
+    let mut set_half_byte = |i: usize, half_byte: u8| flags[i / 2] |= half_byte << ((i % 2) * 4);
+    for (i, (_, r)) in refs.iter().enumerate() {
+        set_half_byte(i, r.flags());
+    }
+    set_half_byte(refs.len(), u8::from(long_atoms));
+    buf.extend_from_slice(&flags);
+
+    for (atom, r) in refs {
+        buf.push(r.internal_index);
+        if r.new_entry {
+            let name = atom.name.as_bytes();
+            if long_atoms {
+                let len = u16::try_from(name.len()).expect("too long atom");
+                buf.extend_from_slice(&len.to_be_bytes());
+            } else {
+                buf.push(name.len() as u8);
+            }
+            buf.extend_from_slice(name);
+        }
+    }
+}
+
+/// Collects the atoms of `term` in encoding order.
+#[cfg(feature = "std")]
+fn collect_atoms<'a>(term: &'a Term, atoms: &mut Vec<&'a Atom>) {
+    match term {
+        Term::Atom(x) => atoms.push(x),
+        Term::Pid(x) => atoms.push(&x.node),
+        Term::Port(x) => atoms.push(&x.node),
+        Term::Reference(x) => atoms.push(&x.node),
+        Term::ExternalFun(x) => {
+            atoms.push(&x.module);
+            atoms.push(&x.function);
+        }
+        Term::InternalFun(x) => {
+            let (module, pid, free_vars) = match **x {
+                InternalFun::Old {
+                    ref module,
+                    ref pid,
+                    ref free_vars,
+                    ..
+                } => (module, pid, free_vars),
+                InternalFun::New {
+                    ref module,
+                    ref pid,
+                    ref free_vars,
+                    ..
+                } => (module, pid, free_vars),
+            };
+            atoms.push(module);
+            atoms.push(&pid.node);
+            for v in free_vars {
+                collect_atoms(v, atoms);
+            }
+        }
+        Term::List(x) => x.elements.iter().for_each(|e| collect_atoms(e, atoms)),
+        Term::ImproperList(x) => {
+            x.elements.iter().for_each(|e| collect_atoms(e, atoms));
+            collect_atoms(&x.last, atoms);
+        }
+        Term::Tuple(x) => x.elements.iter().for_each(|e| collect_atoms(e, atoms)),
+        Term::Map(x) => x.map.iter().for_each(|(k, v)| {
+            collect_atoms(k, atoms);
+            collect_atoms(v, atoms);
+        }),
+        Term::FixInteger(_)
+        | Term::BigInteger(_)
+        | Term::Float(_)
+        | Term::Binary(_)
+        | Term::BitBinary(_)
+        | Term::ByteList(_)
+        | Term::Raw(_) => {}
+    }
+}
+
+/// Control message of a distribution message.
+///
+/// Messages which are not listed here are kept as [`ControlMessage::Other`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlMessage {
+    /// `{1, FromPid, ToPid}`
+    Link {
+        from: Pid,
+        to: Pid,
+    },
+
+    /// `{2, Unused, ToPid}` followed by the message.
+    Send {
+        to: Pid,
+    },
+
+    /// `{3, FromPid, ToPid, Reason}`
+    Exit {
+        from: Pid,
+        to: Pid,
+        reason: Term,
+    },
+
+    /// `{6, FromPid, Unused, ToName}` followed by the message.
+    RegSend {
+        from: Pid,
+        to_name: Atom,
+    },
+
+    /// `{7, FromPid, ToPid}`
+    GroupLeader {
+        from: Pid,
+        to: Pid,
+    },
+
+    /// `{8, FromPid, ToPid, Reason}`
+    Exit2 {
+        from: Pid,
+        to: Pid,
+        reason: Term,
+    },
+
+    /// `{19, FromPid, ToProc, Ref}`
+    MonitorP {
+        from: Pid,
+        to_proc: Term,
+        reference: Reference,
+    },
+
+    /// `{20, FromPid, ToProc, Ref}`
+    DemonitorP {
+        from: Pid,
+        to_proc: Term,
+        reference: Reference,
+    },
+
+    /// `{21, FromProc, ToPid, Ref, Reason}`
+    MonitorPExit {
+        from_proc: Term,
+        to: Pid,
+        reference: Reference,
+        reason: Term,
+    },
+
+    /// `{22, FromPid, ToPid}` followed by the message.
+    SendSender {
+        from: Pid,
+        to: Pid,
+    },
+
+    /// `{35, Id, FromPid, ToPid}`
+    UnlinkId {
+        id: Term,
+        from: Pid,
+        to: Pid,
+    },
+
+    /// `{36, Id, FromPid, ToPid}`
+    UnlinkIdAck {
+        id: Term,
+        from: Pid,
+        to: Pid,
+    },
+
+    Other(Term),
+}
+impl ControlMessage {
+    /// Converts the message to its tuple.
+    pub fn to_term(&self) -> Term {
+        let op = |n: i32| Term::from(n);
+        let pid = |x: &Pid| Term::from(x.clone());
+        let unused = || Term::from(Atom::from(""));
+        let elements = match self {
+            ControlMessage::Link { from, to } => vec![op(1), pid(from), pid(to)],
+            ControlMessage::Send { to } => vec![op(2), unused(), pid(to)],
+            ControlMessage::Exit { from, to, reason } => {
+                vec![op(3), pid(from), pid(to), reason.clone()]
+            }
+            ControlMessage::RegSend { from, to_name } => {
+                vec![op(6), pid(from), unused(), Term::from(to_name.clone())]
+            }
+            ControlMessage::GroupLeader { from, to } => vec![op(7), pid(from), pid(to)],
+            ControlMessage::Exit2 { from, to, reason } => {
+                vec![op(8), pid(from), pid(to), reason.clone()]
+            }
+            ControlMessage::MonitorP {
+                from,
+                to_proc,
+                reference,
+            } => vec![
+                op(19),
+                pid(from),
+                to_proc.clone(),
+                Term::from(reference.clone()),
+            ],
+            ControlMessage::DemonitorP {
+                from,
+                to_proc,
+                reference,
+            } => vec![
+                op(20),
+                pid(from),
+                to_proc.clone(),
+                Term::from(reference.clone()),
+            ],
+            ControlMessage::MonitorPExit {
+                from_proc,
+                to,
+                reference,
+                reason,
+            } => vec![
+                op(21),
+                from_proc.clone(),
+                pid(to),
+                Term::from(reference.clone()),
+                reason.clone(),
+            ],
+            ControlMessage::SendSender { from, to } => vec![op(22), pid(from), pid(to)],
+            ControlMessage::UnlinkId { id, from, to } => {
+                vec![op(35), id.clone(), pid(from), pid(to)]
+            }
+            ControlMessage::UnlinkIdAck { id, from, to } => {
+                vec![op(36), id.clone(), pid(from), pid(to)]
+            }
+            ControlMessage::Other(x) => return x.clone(),
+        };
+        Term::from(Tuple::from(elements))
+    }
+
+    /// Converts a tuple to the message, which is [`ControlMessage::Other`] if it is not of a listed form.
+    pub fn from_term(term: Term) -> Self {
+        Self::try_from_tuple(&term).unwrap_or(ControlMessage::Other(term))
+    }
+
+    fn try_from_tuple(term: &Term) -> Option<Self> {
+        let elements = match term {
+            Term::Tuple(x) => &x.elements,
+            _ => return None,
+        };
+        let pid = |i: usize| match elements.get(i) {
+            Some(Term::Pid(x)) => Some(x.clone()),
+            _ => None,
+        };
+        let reference = |i: usize| match elements.get(i) {
+            Some(Term::Reference(x)) => Some((**x).clone()),
+            _ => None,
+        };
+        let term = |i: usize| elements.get(i).cloned();
+        let op = match elements.first() {
+            Some(Term::FixInteger(x)) => x.value,
+            _ => return None,
+        };
+        let message = match (op, elements.len()) {
+            (1, 3) => ControlMessage::Link {
+                from: pid(1)?,
+                to: pid(2)?,
+            },
+            (2, 3) => ControlMessage::Send { to: pid(2)? },
+            (3, 4) => ControlMessage::Exit {
+                from: pid(1)?,
+                to: pid(2)?,
+                reason: term(3)?,
+            },
+            (6, 4) => ControlMessage::RegSend {
+                from: pid(1)?,
+                to_name: match term(3)? {
+                    Term::Atom(x) => x,
+                    _ => return None,
+                },
+            },
+            (7, 3) => ControlMessage::GroupLeader {
+                from: pid(1)?,
+                to: pid(2)?,
+            },
+            (8, 4) => ControlMessage::Exit2 {
+                from: pid(1)?,
+                to: pid(2)?,
+                reason: term(3)?,
+            },
+            (19, 4) => ControlMessage::MonitorP {
+                from: pid(1)?,
+                to_proc: term(2)?,
+                reference: reference(3)?,
+            },
+            (20, 4) => ControlMessage::DemonitorP {
+                from: pid(1)?,
+                to_proc: term(2)?,
+                reference: reference(3)?,
+            },
+            (21, 5) => ControlMessage::MonitorPExit {
+                from_proc: term(1)?,
+                to: pid(2)?,
+                reference: reference(3)?,
+                reason: term(4)?,
+            },
+            (22, 3) => ControlMessage::SendSender {
+                from: pid(1)?,
+                to: pid(2)?,
+            },
+            (35, 4) => ControlMessage::UnlinkId {
+                id: term(1)?,
+                from: pid(2)?,
+                to: pid(3)?,
+            },
+            (36, 4) => ControlMessage::UnlinkIdAck {
+                id: term(1)?,
+                from: pid(2)?,
+                to: pid(3)?,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colliding_atoms() -> (Atom, Atom) {
+        let name = |i: usize| format!("a{}", i);
+        let slot = |i: usize| AtomCache::slot_for(&Atom::from(name(i)));
+        (0..)
+            .flat_map(|i| (0..i).map(move |j| (j, i)))
+            .find(|&(j, i)| slot(j) == slot(i))
+            .map(|(j, i)| (Atom::from(name(j)), Atom::from(name(i))))
+            .unwrap()
+    }
+
+    #[test]
+    fn insert_and_lookup_work() {
+        let mut cache = AtomCache::new();
+        let foo = Atom::from("foo");
+        let r = cache.insert(&foo);
+        assert!(r.new_entry);
+        assert_eq!(r.index(), AtomCache::slot_for(&foo));
+        assert_eq!(cache.get(r.index()), Some(&foo));
+        assert_eq!(cache.resolve(r.segment, r.internal_index), Some(&foo));
+        assert_eq!(r.flags(), 0x08 | r.segment);
+
+        let r = cache.insert(&foo);
+        assert!(!r.new_entry);
+        assert_eq!(r.flags(), r.segment);
+        assert_eq!(cache.get(ATOM_CACHE_SIZE), None);
+    }
+
+    #[test]
+    fn colliding_atoms_overwrite_each_other() {
+        let (a, b) = colliding_atoms();
+        let mut cache = AtomCache::new();
+        let ra = cache.insert(&a);
+        let rb = cache.insert(&b);
+        assert_eq!(ra.index(), rb.index());
+        assert!(rb.new_entry);
+        assert_eq!(cache.get(ra.index()), Some(&b));
+        assert!(cache.insert(&a).new_entry);
+        assert_eq!(cache.get(ra.index()), Some(&a));
+
+        // Only the first one of colliding atoms is referenced by a message.
+        let selected = cache.insert_message_atoms([&b, &a, &b]);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, &b);
+        assert_eq!(cache.get(ra.index()), Some(&b));
+    }
+
+    #[test]
+    fn message_atoms_are_limited() {
+        let atoms = (0..1000)
+            .map(|i| Atom::from(format!("atom_{}", i)))
+            .collect::<Vec<_>>();
+        let mut cache = AtomCache::new();
+        let selected = cache.insert_message_atoms(&atoms);
+        assert_eq!(selected.len(), MAX_ATOM_CACHE_REFS);
+        let slots = selected
+            .iter()
+            .map(|(_, r)| r.index())
+            .collect::<HashSet<_>>();
+        assert_eq!(slots.len(), MAX_ATOM_CACHE_REFS);
+        assert!(selected
+            .iter()
+            .all(|(atom, r)| cache.get(r.index()) == Some(*atom)));
+    }
+
+    #[test]
+    fn write_header_works() {
+        let mut cache = AtomCache::new();
+        let (foo, bar) = (Atom::from("foo"), Atom::from("bar"));
+        cache.insert(&bar);
+        let refs = cache.insert_message_atoms([&foo, &bar]);
+        let mut buf = Vec::new();
+        write_header(&refs, &mut buf);
+
+        let (f, b) = (refs[0].1, refs[1].1);
+        let mut expected = vec![68, 2, f.flags() | (b.flags() << 4), 0];
+        expected.extend_from_slice(&[f.internal_index, 3, b'f', b'o', b'o']);
+        expected.push(b.internal_index);
+        assert_eq!(buf, expected);
+
+        let mut buf = Vec::new();
+        write_header(&[], &mut buf);
+        assert_eq!(buf, [68, 0]);
+    }
+}
@@ -0,0 +1,461 @@
+//! Distribution handshake.
+//!
+//! Both sides send their node name and capability flags, and then prove that they
+//! share the magic cookie by answering each other's challenge with
+//! `MD5(Cookie ++ integer_to_list(Challenge))`.
+//!
+//! Only the handshake introduced in OTP 23 (i.e., `DistFlags::HANDSHAKE_23` with
+//! 64 bit flags and creation in the name and challenge messages) is supported.
+//!
+//! # Reference
+//!
+//! - [Distribution Handshake](https://www.erlang.org/doc/apps/erts/erl_dist_protocol.html#distribution-handshake)
+use super::local_node::LocalNode;
+use super::*;
+use md5::{Digest, Md5};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SEND_NAME: u8 = b'N';
+const OLD_SEND_NAME: u8 = b'n';
+const SEND_STATUS: u8 = b's';
+const SEND_CHALLENGE: u8 = b'N';
+const CHALLENGE_REPLY: u8 = b'r';
+const CHALLENGE_ACK: u8 = b'a';
+
+/// Errors which can occur during a handshake.
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error("unexpected handshake message tag {tag} (expected {expected})")]
+    UnexpectedMessage { tag: u8, expected: u8 },
+
+    #[error("the peer does not support the OTP 23 handshake")]
+    UnsupportedVersion,
+
+    #[error("the peer refused the connection (status {0:?})")]
+    Refused(String),
+
+    #[error("the peer lacks the mandatory flags {missing:?}")]
+    MissingFlags { missing: DistFlags },
+
+    #[error("the peer's digest does not match the cookie")]
+    DigestMismatch,
+
+    #[error("invalid handshake message: {0}")]
+    InvalidMessage(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Options of a handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeOptions {
+    /// The full name of this node (e.g., `foo@localhost`).
+    pub node_name: NodeName,
+
+    pub cookie: String,
+
+    /// The capability flags of this node.
+    pub flags: DistFlags,
+
+    /// The creation of this node (e.g., [`crate::epmd::EpmdRegistration::creation`]).
+    pub creation: u32,
+
+    challenge: Option<u32>,
+}
+impl HandshakeOptions {
+    /// Makes options with [`DEFAULT_FLAGS`] and creation `0`.
+    pub fn new<T: Into<NodeName>>(node_name: T, cookie: &str) -> Self {
+        HandshakeOptions {
+            node_name: node_name.into(),
+            cookie: cookie.to_owned(),
+            flags: DistFlags::default(),
+            creation: 0,
+            challenge: None,
+        }
+    }
+
+    /// Sets the capability flags (which are extended by [`DistFlags::mandatory`]).
+    pub fn flags(mut self, flags: DistFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the creation.
+    pub fn creation(mut self, creation: u32) -> Self {
+        self.creation = creation;
+        self
+    }
+
+    fn our_flags(&self) -> DistFlags {
+        self.flags | DistFlags::mandatory()
+    }
+
+    fn our_challenge(&self) -> u32 {
+        self.challenge.unwrap_or_else(rand::random)
+    }
+}
+
+/// Connection to a peer which has completed the handshake.
+#[derive(Debug)]
+pub struct Connection<S> {
+    pub(super) stream: S,
+    pub(super) local: LocalNode,
+    peer_name: String,
+    peer_creation: u32,
+    flags: DistFlags,
+    pub(super) send_cache: AtomCache,
+    pub(super) recv_cache: AtomCache,
+}
+impl<S> Connection<S> {
+    /// Returns the name of this node.
+    pub fn node_name(&self) -> &NodeName {
+        self.local.name()
+    }
+
+    /// Returns the creation of this node.
+    pub fn creation(&self) -> u32 {
+        self.local.creation()
+    }
+
+    /// Returns this node, which mints the pids and references of the control messages (see
+    /// [`Connection::monitor`]).
+    pub fn local_node(&self) -> &LocalNode {
+        &self.local
+    }
+
+    /// Returns the name of the peer.
+    pub fn peer_name(&self) -> &str {
+        &self.peer_name
+    }
+
+    /// Returns the creation of the peer.
+    pub fn peer_creation(&self) -> u32 {
+        self.peer_creation
+    }
+
+    /// Returns the flags supported by both sides.
+    pub fn flags(&self) -> DistFlags {
+        self.flags
+    }
+
+    /// Returns the underlying stream, over which 4 byte length prefixed distribution messages follow.
+    pub fn into_stream(self) -> S {
+        self.stream
+    }
+}
+
+/// Performs the handshake as the connecting side (node A).
+pub async fn connect<S>(
+    mut stream: S,
+    options: HandshakeOptions,
+) -> Result<Connection<S>, HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut send_name = vec![SEND_NAME];
+    send_name.extend_from_slice(&options.our_flags().bits().to_be_bytes());
+    send_name.extend_from_slice(&options.creation.to_be_bytes());
+    write_name(&mut send_name, options.node_name.as_str())?;
+    write_message(&mut stream, &send_name).await?;
+
+    let status = read_message(&mut stream).await?;
+    let status = expect_tag(&status, SEND_STATUS)?;
+    match status {
+        b"ok" | b"ok_simultaneous" => {}
+        _ => {
+            return Err(HandshakeError::Refused(
+                String::from_utf8_lossy(status).into_owned(),
+            ))
+        }
+    }
+
+    let challenge = read_message(&mut stream).await?;
+    let mut reader = expect_tag(&challenge, SEND_CHALLENGE)?;
+    let peer_flags = DistFlags::from_bits_retain(read_u64(&mut reader)?);
+    let peer_challenge = read_u32(&mut reader)?;
+    let peer_creation = read_u32(&mut reader)?;
+    let peer_name = read_name(&mut reader)?;
+    let flags = negotiate(options.our_flags(), peer_flags)?;
+
+    let our_challenge = options.our_challenge();
+    let mut reply = vec![CHALLENGE_REPLY];
+    reply.extend_from_slice(&our_challenge.to_be_bytes());
+    reply.extend_from_slice(&digest(&options.cookie, peer_challenge));
+    write_message(&mut stream, &reply).await?;
+
+    let ack = read_message(&mut stream).await?;
+    if expect_tag(&ack, CHALLENGE_ACK)? != digest(&options.cookie, our_challenge) {
+        return Err(HandshakeError::DigestMismatch);
+    }
+
+    Ok(Connection {
+        stream,
+        local: LocalNode::new(options.node_name, options.creation).for_peer(flags),
+        peer_name,
+        peer_creation,
+        flags,
+        send_cache: AtomCache::new(),
+        recv_cache: AtomCache::new(),
+    })
+}
+
+/// Performs the handshake as the accepting side (node B).
+pub async fn accept<S>(
+    mut stream: S,
+    options: HandshakeOptions,
+) -> Result<Connection<S>, HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let send_name = read_message(&mut stream).await?;
+    if send_name.first() == Some(&OLD_SEND_NAME) {
+        write_message(&mut stream, b"snot_allowed").await?;
+        return Err(HandshakeError::UnsupportedVersion);
+    }
+    let mut reader = expect_tag(&send_name, SEND_NAME)?;
+    let peer_flags = DistFlags::from_bits_retain(read_u64(&mut reader)?);
+    let peer_creation = read_u32(&mut reader)?;
+    let peer_name = read_name(&mut reader)?;
+    let flags = match negotiate(options.our_flags(), peer_flags) {
+        Ok(flags) => flags,
+        Err(e) => {
+            write_message(&mut stream, b"snot_allowed").await?;
+            return Err(e);
+        }
+    };
+    write_message(&mut stream, b"sok").await?;
+
+    let our_challenge = options.our_challenge();
+    let mut challenge = vec![SEND_CHALLENGE];
+    challenge.extend_from_slice(&options.our_flags().bits().to_be_bytes());
+    challenge.extend_from_slice(&our_challenge.to_be_bytes());
+    challenge.extend_from_slice(&options.creation.to_be_bytes());
+    write_name(&mut challenge, options.node_name.as_str())?;
+    write_message(&mut stream, &challenge).await?;
+
+    let reply = read_message(&mut stream).await?;
+    let mut reader = expect_tag(&reply, CHALLENGE_REPLY)?;
+    let peer_challenge = read_u32(&mut reader)?;
+    if reader != digest(&options.cookie, our_challenge) {
+        return Err(HandshakeError::DigestMismatch);
+    }
+
+    let mut ack = vec![CHALLENGE_ACK];
+    ack.extend_from_slice(&digest(&options.cookie, peer_challenge));
+    write_message(&mut stream, &ack).await?;
+
+    Ok(Connection {
+        stream,
+        local: LocalNode::new(options.node_name, options.creation).for_peer(flags),
+        peer_name,
+        peer_creation,
+        flags,
+        send_cache: AtomCache::new(),
+        recv_cache: AtomCache::new(),
+    })
+}
+
+/// Computes `MD5(Cookie ++ integer_to_list(Challenge))`.
+pub fn digest(cookie: &str, challenge: u32) -> [u8; 16] {
+    let mut md5 = Md5::new();
+    md5.update(cookie.as_bytes());
+    md5.update(challenge.to_string().as_bytes());
+    md5.finalize().into()
+}
+
+fn negotiate(our_flags: DistFlags, peer_flags: DistFlags) -> Result<DistFlags, HandshakeError> {
+    let missing = DistFlags::mandatory() - peer_flags;
+    if !missing.is_empty() {
+        return Err(HandshakeError::MissingFlags { missing });
+    }
+    Ok(our_flags & peer_flags)
+}
+
+async fn write_message<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    message: &[u8],
+) -> Result<(), HandshakeError> {
+    let len = u16::try_from(message.len())
+        .map_err(|_| HandshakeError::InvalidMessage("too long message".to_owned()))?;
+    let mut buf = Vec::with_capacity(2 + message.len());
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(message);
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_message<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>, HandshakeError> {
+    let len = stream.read_u16().await? as usize;
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+fn expect_tag(message: &[u8], expected: u8) -> Result<&[u8], HandshakeError> {
+    match message.split_first() {
+        Some((&tag, rest)) if tag == expected => Ok(rest),
+        Some((&tag, _)) => Err(HandshakeError::UnexpectedMessage { tag, expected }),
+        None => Err(HandshakeError::InvalidMessage("empty message".to_owned())),
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut &[u8]) -> Result<[u8; N], HandshakeError> {
+    if reader.len() < N {
+        return Err(HandshakeError::InvalidMessage(
+            "truncated message".to_owned(),
+        ));
+    }
+    let (bytes, rest) = reader.split_at(N);
+    *reader = rest;
+    Ok(bytes.try_into().expect("unreachable"))
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32, HandshakeError> {
+    read_array(reader).map(u32::from_be_bytes)
+}
+
+fn read_u64(reader: &mut &[u8]) -> Result<u64, HandshakeError> {
+    read_array(reader).map(u64::from_be_bytes)
+}
+
+fn read_name(reader: &mut &[u8]) -> Result<String, HandshakeError> {
+    let len = read_array(reader).map(u16::from_be_bytes)? as usize;
+    if reader.len() < len {
+        return Err(HandshakeError::InvalidMessage(
+            "truncated node name".to_owned(),
+        ));
+    }
+    let (name, rest) = reader.split_at(len);
+    *reader = rest;
+    String::from_utf8(name.to_vec()).map_err(|e| HandshakeError::InvalidMessage(e.to_string()))
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) -> Result<(), HandshakeError> {
+    let len = u16::try_from(name.len())
+        .map_err(|_| HandshakeError::InvalidMessage("too long node name".to_owned()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(name.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn digest_works() {
+        // md5("secret123") computed independently
+        assert_eq!(
+            digest("secret", 123),
+            [
+                0x5d, 0x78, 0x45, 0xac, 0x6e, 0xe7, 0xcf, 0xff, 0xaf, 0xc5, 0xfe, 0x5f, 0x35, 0xcf,
+                0x66, 0x6d
+            ]
+        );
+    }
+
+    #[test]
+    fn handshake_works() {
+        block_on(async {
+            let (a, b) = tokio::io::duplex(1024);
+            let a_options = HandshakeOptions::new("a@localhost", "secret").creation(1);
+            let b_options = HandshakeOptions::new("b@localhost", "secret")
+                .flags(DistFlags::default() - DistFlags::DIST_HDR_ATOM_CACHE)
+                .creation(2);
+            let (a, b) = tokio::join!(connect(a, a_options), accept(b, b_options));
+            let (a, b) = (a.unwrap(), b.unwrap());
+            assert_eq!(a.peer_name(), "b@localhost");
+            assert_eq!(a.peer_creation(), 2);
+            assert_eq!(b.peer_name(), "a@localhost");
+            assert_eq!(b.peer_creation(), 1);
+            assert!(a.local_node().owns(&a.pid()));
+            assert!(!b.local_node().owns(&a.local_node().make_pid()));
+            assert_eq!(
+                a.flags(),
+                DistFlags::default() - DistFlags::DIST_HDR_ATOM_CACHE
+            );
+            assert_eq!(a.flags(), b.flags());
+        });
+    }
+
+    #[test]
+    fn wrong_cookie_is_rejected() {
+        block_on(async {
+            let (a, b) = tokio::io::duplex(1024);
+            let (a, b) = tokio::join!(
+                connect(a, HandshakeOptions::new("a@localhost", "secret")),
+                accept(b, HandshakeOptions::new("b@localhost", "other"))
+            );
+            assert!(matches!(b, Err(HandshakeError::DigestMismatch)));
+            // The accepting side closes the connection without acknowledging.
+            assert!(matches!(a, Err(HandshakeError::Io(_))));
+        });
+    }
+
+    #[test]
+    fn missing_flags_are_rejected() {
+        block_on(async {
+            let (mut a, b) = tokio::io::duplex(1024);
+            let mut send_name = vec![SEND_NAME];
+            send_name.extend_from_slice(&DistFlags::HANDSHAKE_23.bits().to_be_bytes());
+            send_name.extend_from_slice(&[0, 0, 0, 0, 0, 1, b'a']);
+            write_message(&mut a, &send_name).await.unwrap();
+            let result = accept(b, HandshakeOptions::new("b@localhost", "secret")).await;
+            assert!(matches!(result, Err(HandshakeError::MissingFlags { .. })));
+            assert_eq!(read_message(&mut a).await.unwrap(), b"snot_allowed");
+        });
+    }
+
+    /// Replays the messages of a peer node (assembled following the protocol specification)
+    /// with both challenges fixed.
+    #[test]
+    fn fixture_replay_works() {
+        #[rustfmt::skip]
+        let peer_messages: &[&[u8]] = &[
+            b"sok",
+            &[
+                b'N',
+                0x00, 0x00, 0x00, 0x0F, 0x03, 0xDF, 0x7F, 0xBD, // flags
+                0x8A, 0x4E, 0x38, 0x6F, // challenge 2320382063
+                0x65, 0x53, 0xF1, 0x00, // creation
+                0x00, 0x0B, b'b', b'@', b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't',
+            ],
+        ];
+        block_on(async {
+            let (a, mut peer) = tokio::io::duplex(1024);
+            let mut options = HandshakeOptions::new("a@localhost", "secret");
+            options.challenge = Some(42);
+            let handle = tokio::spawn(async move { connect(a, options).await });
+
+            let send_name = read_message(&mut peer).await.unwrap();
+            assert_eq!(&send_name[..1], b"N");
+            assert_eq!(&send_name[13..], b"\x00\x0Ba@localhost");
+            for message in peer_messages {
+                write_message(&mut peer, message).await.unwrap();
+            }
+            let reply = read_message(&mut peer).await.unwrap();
+            let mut expected = vec![b'r', 0, 0, 0, 42];
+            expected.extend_from_slice(&digest("secret", 2320382063));
+            assert_eq!(reply, expected);
+            let mut ack = vec![b'a'];
+            ack.extend_from_slice(&digest("secret", 42));
+            write_message(&mut peer, &ack).await.unwrap();
+
+            let connection = handle.await.unwrap().unwrap();
+            assert_eq!(connection.peer_creation(), 1700000000);
+            assert_eq!(
+                connection.flags(),
+                DistFlags::default() & DistFlags::from_bits_retain(0x0000_000F_03DF_7FBD)
+            );
+        });
+    }
+}
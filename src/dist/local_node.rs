@@ -0,0 +1,232 @@
+//! Pids, ports and references minted by the local node.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::dist::local_node::LocalNode;
+//!
+//! let node = LocalNode::new("foo@localhost", 3);
+//! let (a, b) = (node.make_pid(), node.make_pid());
+//! assert_ne!(a, b);
+//! assert!(node.owns(&a));
+//! assert!(!LocalNode::new("foo@localhost", 4).owns(&a));
+//! ```
+use super::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Significant bits of the ID and serial of a pid, of the ID of a port, and of the first word
+/// of the ID of a reference, for peers without [`DistFlags::V4_NC`].
+const OLD_PID_ID_BITS: u32 = 15;
+const OLD_PID_SERIAL_BITS: u32 = 13;
+const OLD_PORT_ID_BITS: u32 = 28;
+const OLD_REFERENCE_FIRST_WORD_BITS: u32 = 18;
+
+/// Number of words of the IDs of the minted references.
+const REFERENCE_ID_LEN: usize = 3;
+
+/// Identity of the local node (its name and creation), which mints its pids, ports and
+/// references.
+///
+/// The IDs are taken from counters, which clones share, so that a node used from several
+/// threads or connections never mints the same identifier twice (until a counter wraps around
+/// the IDs which the peer can decode). An ID is never 0: `<Node.0.0>` is the pid of
+/// [`Connection::pid`](super::handshake::Connection::pid).
+#[derive(Debug, Clone)]
+pub struct LocalNode {
+    name: NodeName,
+    creation: u32,
+    v4_nc: bool,
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    pids: AtomicU64,
+    ports: AtomicU64,
+    references: AtomicU64,
+}
+
+impl LocalNode {
+    /// Makes a node whose identifiers are decoded by peers with [`DistFlags::V4_NC`] (see
+    /// [`LocalNode::for_peer`]).
+    pub fn new<T: Into<NodeName>>(name: T, creation: u32) -> Self {
+        LocalNode {
+            name: name.into(),
+            creation,
+            v4_nc: true,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Returns the node (sharing its counters) which mints identifiers within the ranges
+    /// decoded by a peer with the negotiated `flags`.
+    ///
+    /// Without [`DistFlags::V4_NC`], the peer only decodes 15 bits of the ID and 13 bits of the
+    /// serial of a pid, 28 bits of the ID of a port, and 18 bits of the first word of a
+    /// reference. The creation is kept as it is, so a peer without [`DistFlags::BIG_CREATION`]
+    /// needs it to fit in 2 bits (see [`crate::EncoderOptions::for_peer`]).
+    pub fn for_peer(&self, flags: DistFlags) -> Self {
+        LocalNode {
+            v4_nc: flags.contains(DistFlags::V4_NC),
+            ..self.clone()
+        }
+    }
+
+    pub fn name(&self) -> &NodeName {
+        &self.name
+    }
+
+    pub fn creation(&self) -> u32 {
+        self.creation
+    }
+
+    /// Mints a pid.
+    pub fn make_pid(&self) -> Pid {
+        let (id_bits, serial_bits) = if self.v4_nc {
+            (32, 32)
+        } else {
+            (OLD_PID_ID_BITS, OLD_PID_SERIAL_BITS)
+        };
+        let n = next(&self.counters.pids, id_bits + serial_bits);
+        let id = (n & mask(id_bits)) as u32;
+        let serial = (n >> id_bits) as u32;
+        Pid::new(self.name.clone(), id, serial, self.creation)
+    }
+
+    /// Mints a port.
+    pub fn make_port(&self) -> Port {
+        let bits = if self.v4_nc { 64 } else { OLD_PORT_ID_BITS };
+        let id = next(&self.counters.ports, bits);
+        Port::new(self.name.clone(), id, self.creation)
+    }
+
+    /// Mints a reference, whose ID has 3 words.
+    pub fn make_ref(&self) -> Reference {
+        let first_bits = if self.v4_nc {
+            32
+        } else {
+            OLD_REFERENCE_FIRST_WORD_BITS
+        };
+        let n = next(&self.counters.references, 64);
+        let id = vec![
+            (n & mask(first_bits)) as u32,
+            (n >> first_bits) as u32,
+            (u128::from(n) >> (first_bits + 32)) as u32,
+        ];
+        debug_assert_eq!(id.len(), REFERENCE_ID_LEN);
+        Reference::new(self.name.clone(), id, self.creation)
+    }
+
+    /// Returns `true` if `pid` is of this node (i.e., has its name and creation).
+    pub fn owns(&self, pid: &Pid) -> bool {
+        pid.node.as_str() == self.name.as_str() && pid.creation == self.creation
+    }
+}
+impl PartialEq for LocalNode {
+    /// Nodes are equal if they mint identifiers of the same node within the same ranges,
+    /// whether or not they share their counters.
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.creation == other.creation && self.v4_nc == other.v4_nc
+    }
+}
+impl Eq for LocalNode {}
+
+fn mask(bits: u32) -> u64 {
+    u64::MAX >> (64 - bits)
+}
+
+/// Takes the next value of `counter`, in `1..2^bits`.
+fn next(counter: &AtomicU64, bits: u32) -> u64 {
+    counter.fetch_add(1, Ordering::Relaxed) % mask(bits) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec_common::EncoderOptions;
+    use std::collections::HashSet;
+
+    #[test]
+    fn ids_are_unique_across_threads() {
+        let node = LocalNode::new("foo@localhost", 1);
+        let ids = std::thread::scope(|s| {
+            let threads = (0..8)
+                .map(|_| {
+                    let node = node.clone();
+                    s.spawn(move || {
+                        (0..1000)
+                            .map(|_| (node.make_pid(), node.make_port(), node.make_ref()))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .flat_map(|t| t.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(ids.len(), 8000);
+        let pids = ids
+            .iter()
+            .map(|(p, _, _)| (p.id, p.serial))
+            .collect::<HashSet<_>>();
+        let ports = ids.iter().map(|(_, p, _)| p.id).collect::<HashSet<_>>();
+        let refs = ids
+            .iter()
+            .map(|(_, _, r)| r.id.clone())
+            .collect::<HashSet<_>>();
+        assert_eq!((pids.len(), ports.len(), refs.len()), (8000, 8000, 8000));
+        assert!(!pids.contains(&(0, 0)));
+    }
+
+    #[test]
+    fn ownership_is_checked() {
+        let node = LocalNode::new("foo@localhost", 7);
+        let pid = node.make_pid();
+        assert_eq!((pid.node.as_str(), pid.creation), ("foo@localhost", 7));
+        assert!(node.owns(&pid));
+        assert!(node.for_peer(DistFlags::empty()).owns(&pid));
+        assert!(!node.owns(&Pid::new("bar@localhost", pid.id, pid.serial, 7)));
+        assert!(!node.owns(&Pid::new("foo@localhost", pid.id, pid.serial, 8)));
+    }
+
+    #[test]
+    fn old_peers_decode_the_ids() {
+        let flags = DistFlags::mandatory() - DistFlags::V4_NC - DistFlags::BIG_CREATION;
+        let node = LocalNode::new("foo@localhost", 3).for_peer(flags);
+        let options = EncoderOptions::for_peer(flags);
+
+        // Close to the end of the ranges, so that the counters wrap around.
+        let pid_ids = 1 << (OLD_PID_ID_BITS + OLD_PID_SERIAL_BITS);
+        node.counters.pids.store(pid_ids - 3, Ordering::Relaxed);
+        node.counters
+            .ports
+            .store((1 << OLD_PORT_ID_BITS) - 3, Ordering::Relaxed);
+        node.counters
+            .references
+            .store(u64::MAX - 3, Ordering::Relaxed);
+        let mut pids = HashSet::new();
+        for _ in 0..4 {
+            let pid = node.make_pid();
+            assert!(pid.id < 1 << OLD_PID_ID_BITS, "{:?}", pid);
+            assert!(pid.serial < 1 << OLD_PID_SERIAL_BITS, "{:?}", pid);
+            assert!(pids.insert((pid.id, pid.serial)));
+            let port = node.make_port();
+            assert!(port.id < 1 << OLD_PORT_ID_BITS, "{:?}", port);
+            let reference = node.make_ref();
+            assert!(reference.id[0] < 1 << OLD_REFERENCE_FIRST_WORD_BITS);
+            assert_eq!(reference.id.len(), REFERENCE_ID_LEN);
+
+            for term in [Term::from(pid), Term::from(port), Term::from(reference)] {
+                let mut buf = Vec::new();
+                crate::Encoder::with_options(&mut buf, options)
+                    .encode(&term)
+                    .unwrap();
+                assert_eq!(Term::decode(io::Cursor::new(&buf)).unwrap(), term);
+            }
+        }
+        // The pids wrapped around, skipping <0.0.0>.
+        assert!(pids.contains(&(1, 0)));
+    }
+}
@@ -0,0 +1,216 @@
+//! Distribution nodes exchanging messages with other (e.g., Erlang) nodes.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use eetf::dist::node::Node;
+//! use eetf::{Atom, Term};
+//! use tokio::io::{AsyncRead, AsyncWrite};
+//!
+//! // `stream` is connected to the port of the peer node (e.g., found by `eetf::epmd::port_please`).
+//! async fn hello<S>(stream: S) -> Result<(), eetf::Error>
+//! where
+//!     S: AsyncRead + AsyncWrite + Unpin,
+//! {
+//!     let node = Node::new("bar@localhost", "secret");
+//!     let mut connection = node.connect(stream).await?;
+//!     connection
+//!         .send_reg("logger", &Term::from(Atom::from("hello")))
+//!         .await?;
+//!     let (control, message) = connection.recv().await?;
+//!     Ok(())
+//! }
+//! ```
+use super::handshake::{self, Connection, HandshakeOptions};
+use super::local_node::LocalNode;
+use super::*;
+use crate::codec::{Decoder, Encoder};
+use crate::codec_common::EncoderOptions;
+use crate::Error;
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const PASS_THROUGH: u8 = 112;
+
+/// Local node which connects to, or accepts connections from, other nodes.
+///
+/// Its connections share its [`LocalNode`], so that the pids and references minted for any of
+/// them are unique.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    options: HandshakeOptions,
+    local: LocalNode,
+}
+impl Node {
+    /// Makes a node named `name` (e.g., `foo@localhost`).
+    pub fn new<T: Into<NodeName>>(name: T, cookie: &str) -> Self {
+        let options = HandshakeOptions::new(name, cookie);
+        Node {
+            local: LocalNode::new(options.node_name.clone(), options.creation),
+            options,
+        }
+    }
+
+    /// Sets the capability flags (see [`HandshakeOptions::flags`]).
+    pub fn flags(mut self, flags: DistFlags) -> Self {
+        self.options = self.options.flags(flags);
+        self
+    }
+
+    /// Sets the creation.
+    pub fn creation(mut self, creation: u32) -> Self {
+        self.options = self.options.creation(creation);
+        self.local = LocalNode::new(self.options.node_name.clone(), creation);
+        self
+    }
+
+    /// Returns the name of the node.
+    pub fn name(&self) -> &NodeName {
+        &self.options.node_name
+    }
+
+    /// Makes a pid of this node.
+    pub fn pid(&self, id: u32, serial: u32) -> Pid {
+        Pid::new(self.name().clone(), id, serial, self.options.creation)
+    }
+
+    /// Returns the minter of the pids, ports and references of this node.
+    pub fn local_node(&self) -> &LocalNode {
+        &self.local
+    }
+
+    /// Connects to the node at the other end of `stream`.
+    pub async fn connect<S>(&self, stream: S) -> Result<Connection<S>, Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let connection = handshake::connect(stream, self.options.clone()).await?;
+        Ok(self.share_local_node(connection))
+    }
+
+    /// Accepts the connection of the node at the other end of `stream`.
+    pub async fn accept<S>(&self, stream: S) -> Result<Connection<S>, Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let connection = handshake::accept(stream, self.options.clone()).await?;
+        Ok(self.share_local_node(connection))
+    }
+
+    fn share_local_node<S>(&self, mut connection: Connection<S>) -> Connection<S> {
+        connection.local = self.local.for_peer(connection.flags());
+        connection
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    /// Returns the pid on behalf of which [`Connection::send_reg`] sends messages (i.e., `<Node.0.0>`).
+    pub fn pid(&self) -> Pid {
+        Pid::new(self.node_name().clone(), 0, 0, self.creation())
+    }
+
+    /// Sends `message` to the process `to`.
+    pub async fn send(&mut self, to: Pid, message: &Term) -> Result<(), Error> {
+        self.send_control(&ControlMessage::Send { to }, Some(message))
+            .await
+    }
+
+    /// Sends `message` to the process registered as `name` on the peer.
+    pub async fn send_reg(&mut self, name: &str, message: &Term) -> Result<(), Error> {
+        let control = ControlMessage::RegSend {
+            from: self.pid(),
+            to_name: Atom::from(name),
+        };
+        self.send_control(&control, Some(message)).await
+    }
+
+    /// Monitors the process `to_proc` (a pid, or the name of a registered process) on behalf of
+    /// [`Connection::pid`], returning the reference of the monitor.
+    pub async fn monitor(&mut self, to_proc: Term) -> Result<Reference, Error> {
+        let reference = self.local_node().make_ref();
+        let control = ControlMessage::MonitorP {
+            from: self.pid(),
+            to_proc,
+            reference: reference.clone(),
+        };
+        self.send_control(&control, None).await?;
+        Ok(reference)
+    }
+
+    /// Removes the monitor of `to_proc` made by [`Connection::monitor`].
+    pub async fn demonitor(&mut self, to_proc: Term, reference: Reference) -> Result<(), Error> {
+        let control = ControlMessage::DemonitorP {
+            from: self.pid(),
+            to_proc,
+            reference,
+        };
+        self.send_control(&control, None).await
+    }
+
+    /// Sends a control message and an optional payload.
+    pub async fn send_control(
+        &mut self,
+        control: &ControlMessage,
+        payload: Option<&Term>,
+    ) -> Result<(), Error> {
+        let control = control.to_term();
+        let options = EncoderOptions::for_peer(self.flags());
+        let mut buf = vec![0; 4];
+        if self.flags().contains(DistFlags::DIST_HDR_ATOM_CACHE) {
+            Encoder::with_options(&mut buf, options).encode_distribution_message(
+                &control,
+                payload,
+                &mut self.send_cache,
+            )?;
+        } else {
+            buf.push(PASS_THROUGH);
+            Encoder::with_options(&mut buf, options).encode(&control)?;
+            if let Some(payload) = payload {
+                Encoder::with_options(&mut buf, options).encode(payload)?;
+            }
+        }
+        let len = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+        self.stream.write_all(&buf).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Sends a tick (i.e., an empty packet) to keep the connection alive.
+    pub async fn tick(&mut self) -> Result<(), Error> {
+        self.stream.write_all(&[0; 4]).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Receives a control message and its payload.
+    ///
+    /// Ticks of the peer are answered with ticks, so a connection which is kept
+    /// receiving stays alive.
+    pub async fn recv(&mut self) -> Result<(ControlMessage, Option<Term>), Error> {
+        loop {
+            let len = self.stream.read_u32().await? as usize;
+            if len == 0 {
+                self.tick().await?;
+                continue;
+            }
+            let mut buf = vec![0; len];
+            self.stream.read_exact(&mut buf).await?;
+            let (control, payload) = if buf[0] == PASS_THROUGH {
+                let mut reader = Cursor::new(&buf[1..]);
+                let control = Decoder::new(&mut reader).decode()?;
+                let payload = if (reader.position() as usize) < len - 1 {
+                    Some(Decoder::new(&mut reader).decode()?)
+                } else {
+                    None
+                };
+                (control, payload)
+            } else {
+                let message = Decoder::new(Cursor::new(&buf))
+                    .decode_distribution_message(&mut self.recv_cache)?;
+                (message.control, message.payload)
+            };
+            return Ok((ControlMessage::from_term(control), payload));
+        }
+    }
+}
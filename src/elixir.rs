@@ -0,0 +1,503 @@
+//! Helpers for Elixir structs.
+//!
+//! Elixir structs are encoded as maps carrying a `:__struct__` key whose value
+//! is the module atom (e.g. `'Elixir.MyApp.User'`).
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::elixir::ElixirStruct;
+//! use eetf::{Binary, Term};
+//!
+//! let term = ElixirStruct::new("MyApp.User")
+//!     .field("name", Binary::from("joe".as_bytes()))
+//!     .into_term();
+//!
+//! let user = ElixirStruct::from_term(&term).unwrap();
+//! assert_eq!(user.module_name(), "Elixir.MyApp.User");
+//! assert_eq!(user.short_module_name(), "MyApp.User");
+//! assert_eq!(user.get("name"), Some(&Term::from(Binary::from("joe".as_bytes()))));
+//! ```
+use super::*;
+use crate::convert::TryAsRef;
+
+/// The key under which Elixir stores the module of a struct.
+pub const STRUCT_KEY: &str = "__struct__";
+
+/// The prefix of every Elixir module atom.
+pub const MODULE_PREFIX: &str = "Elixir.";
+
+/// An Elixir struct.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ElixirStruct {
+    module: Atom,
+    fields: HashMap<Term, Term>,
+}
+impl ElixirStruct {
+    /// Makes a new struct with no fields.
+    ///
+    /// The `Elixir.` prefix is added to `module` if it is missing.
+    pub fn new(module: &str) -> Self {
+        let module = if module.starts_with(MODULE_PREFIX) {
+            Atom::from(module)
+        } else {
+            Atom::from(format!("{}{}", MODULE_PREFIX, module))
+        };
+        ElixirStruct {
+            module,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Adds (or replaces) a field.
+    pub fn field<V>(mut self, name: &str, value: V) -> Self
+    where
+        Term: From<V>,
+    {
+        self.fields
+            .insert(Term::Atom(Atom::from(name)), Term::from(value));
+        self
+    }
+
+    /// Interprets `term` as an Elixir struct.
+    ///
+    /// Returns `None` if `term` is not a map or has no atom `__struct__` key.
+    pub fn from_term(term: &Term) -> Option<Self> {
+        let map: &Map = term.try_as_ref()?;
+        let module: &Atom = map
+            .map
+            .get(&Term::from(Atom::from(STRUCT_KEY)))?
+            .try_as_ref()?;
+        let fields = map
+            .map
+            .iter()
+            .filter(|(k, _)| !is_struct_key(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        Some(ElixirStruct {
+            module: module.clone(),
+            fields,
+        })
+    }
+
+    /// Returns the module atom name, including the `Elixir.` prefix.
+    pub fn module_name(&self) -> &str {
+        &self.module.name
+    }
+
+    /// Returns the module name without the `Elixir.` prefix.
+    pub fn short_module_name(&self) -> &str {
+        self.module
+            .name
+            .strip_prefix(MODULE_PREFIX)
+            .unwrap_or(&self.module.name)
+    }
+
+    /// Returns the fields of the struct (the `__struct__` key is not included).
+    pub fn fields(&self) -> &HashMap<Term, Term> {
+        &self.fields
+    }
+
+    /// Returns the value of the field whose key is the atom `name`.
+    pub fn get(&self, name: &str) -> Option<&Term> {
+        self.fields.get(&Term::from(Atom::from(name)))
+    }
+
+    /// Returns `true` if this is a struct of the module `module` (with or without the prefix).
+    pub fn is(&self, module: &str) -> bool {
+        self.module_name() == module || self.short_module_name() == module
+    }
+
+    /// Returns `true` if this is a `DateTime` struct.
+    pub fn is_date_time(&self) -> bool {
+        self.module_name() == "Elixir.DateTime"
+    }
+
+    /// Returns `true` if this is a `NaiveDateTime` struct.
+    pub fn is_naive_date_time(&self) -> bool {
+        self.module_name() == "Elixir.NaiveDateTime"
+    }
+
+    /// Converts the struct into a map term.
+    pub fn into_term(self) -> Term {
+        Term::from(Map::from(self))
+    }
+}
+impl From<ElixirStruct> for Map {
+    fn from(x: ElixirStruct) -> Self {
+        let mut map = x.fields;
+        map.insert(Term::from(Atom::from(STRUCT_KEY)), Term::from(x.module));
+        Map::from(map)
+    }
+}
+impl From<ElixirStruct> for Term {
+    fn from(x: ElixirStruct) -> Self {
+        x.into_term()
+    }
+}
+
+fn is_struct_key(term: &Term) -> bool {
+    TryAsRef::<Atom>::try_as_ref(term)
+        .map(|a| a.as_str() == STRUCT_KEY)
+        .unwrap_or(false)
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_support {
+    use super::*;
+    use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike, Utc};
+    use num::traits::ToPrimitive;
+
+    const CALENDAR_ISO: &str = "Elixir.Calendar.ISO";
+
+    impl ElixirStruct {
+        /// Converts a `NaiveDateTime` struct (or the wall time of a `DateTime` struct).
+        pub fn to_naive_date_time(&self) -> Option<NaiveDateTime> {
+            if !self.is_naive_date_time() && !self.is_date_time() {
+                return None;
+            }
+            let int = |name| self.get(name).and_then(|t| t.to_i64());
+            let (micros, _precision) = self.microsecond()?;
+            NaiveDate::from_ymd_opt(
+                int("year")?.to_i32()?,
+                int("month")?.to_u32()?,
+                int("day")?.to_u32()?,
+            )?
+            .and_hms_micro_opt(
+                int("hour")?.to_u32()?,
+                int("minute")?.to_u32()?,
+                int("second")?.to_u32()?,
+                micros,
+            )
+        }
+
+        /// Converts a `DateTime` struct into UTC, applying its `utc_offset` and `std_offset`.
+        pub fn to_date_time_utc(&self) -> Option<DateTime<Utc>> {
+            if !self.is_date_time() {
+                return None;
+            }
+            let offset = |name| self.get(name).and_then(|t| t.to_i64());
+            let offset = offset("utc_offset")? + offset("std_offset")?;
+            let naive = self.to_naive_date_time()? - TimeDelta::try_seconds(offset)?;
+            Some(naive.and_utc())
+        }
+
+        fn microsecond(&self) -> Option<(u32, u32)> {
+            let tuple: &Tuple = self.get("microsecond")?.try_as_ref()?;
+            match tuple.elements.as_slice() {
+                [value, precision] => Some((value.to_u32()?, precision.to_u32()?)),
+                _ => None,
+            }
+        }
+
+        fn with_date_time_fields(self, naive: &NaiveDateTime) -> Self {
+            self.field("calendar", Atom::from(CALENDAR_ISO))
+                .field("year", naive.year())
+                .field("month", naive.month() as i32)
+                .field("day", naive.day() as i32)
+                .field("hour", naive.hour() as i32)
+                .field("minute", naive.minute() as i32)
+                .field("second", naive.second() as i32)
+                .field("microsecond", tuple!((naive.nanosecond() / 1000) as i32, 6))
+        }
+    }
+    impl From<NaiveDateTime> for ElixirStruct {
+        fn from(x: NaiveDateTime) -> Self {
+            ElixirStruct::new("NaiveDateTime").with_date_time_fields(&x)
+        }
+    }
+    impl From<DateTime<Utc>> for ElixirStruct {
+        fn from(x: DateTime<Utc>) -> Self {
+            let utc = |s: &str| Binary::from(s.as_bytes());
+            ElixirStruct::new("DateTime")
+                .with_date_time_fields(&x.naive_utc())
+                .field("time_zone", utc("Etc/UTC"))
+                .field("zone_abbr", utc("UTC"))
+                .field("utc_offset", 0)
+                .field("std_offset", 0)
+        }
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+pub use decimal_support::{DecimalConvention, DecimalError};
+
+#[cfg(feature = "bigdecimal")]
+mod decimal_support {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use num::bigint::{BigInt, Sign, ToBigInt};
+    use num::traits::ToPrimitive;
+
+    /// The shape of the terms made by [`Term::from_decimal`].
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum DecimalConvention {
+        /// A `%Decimal{sign: Sign, coef: Coef, exp: Exp}` struct.
+        #[default]
+        Struct,
+
+        /// A `{decimal, Sign, Coef, Exp}` tuple.
+        Tuple,
+    }
+
+    /// Error of [`Term::to_decimal`].
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum DecimalError {
+        #[error("the term is neither a Decimal struct nor a decimal tuple")]
+        NotADecimal,
+
+        /// The field is missing, or is not a sign (1 or -1), a non-negative coefficient, or an
+        /// integer exponent.
+        #[error("the {field} of the decimal is invalid")]
+        InvalidField { field: &'static str },
+
+        /// The decimal is `inf` (or `-inf`), which `BigDecimal` cannot represent.
+        #[error("the decimal is infinite")]
+        Infinite { negative: bool },
+
+        #[error("the decimal is NaN")]
+        NaN,
+
+        /// The exponent is not within `-i64::MAX..=i64::MAX`, the range of the scales of
+        /// `BigDecimal`.
+        #[error("the exponent {exp} of the decimal is out of range")]
+        ExponentOutOfRange { exp: String },
+    }
+
+    impl Term {
+        /// Converts an Elixir `Decimal` struct, or a `{decimal, Sign, Coef, Exp}` tuple, whose
+        /// value is `Sign * Coef * 10 ^ Exp`.
+        ///
+        /// `BigDecimal` has no negative zero, so `-0` (a sign of -1 with a coefficient of 0)
+        /// converts to 0. The special values `inf` and `NaN` fail.
+        ///
+        /// ```
+        /// use bigdecimal::BigDecimal;
+        /// use eetf::elixir::DecimalConvention;
+        /// use eetf::Term;
+        /// use std::str::FromStr;
+        ///
+        /// let price = BigDecimal::from_str("-12.50").unwrap();
+        /// let term = Term::from_decimal(&price, DecimalConvention::Struct);
+        /// assert_eq!(term.to_decimal().unwrap(), price);
+        /// ```
+        pub fn to_decimal(&self) -> Result<BigDecimal, DecimalError> {
+            let (sign, coef, exp) = decimal_fields(self)?;
+            let negative = match sign.to_i64() {
+                Some(1) => false,
+                Some(-1) => true,
+                _ => return Err(DecimalError::InvalidField { field: "sign" }),
+            };
+            let coef = match coef {
+                Term::Atom(atom) if atom.as_str() == "inf" => {
+                    return Err(DecimalError::Infinite { negative })
+                }
+                Term::Atom(atom) if atom.as_str() == "NaN" => return Err(DecimalError::NaN),
+                coef => coef
+                    .to_bigint()
+                    .filter(|coef| coef.sign() != Sign::Minus)
+                    .ok_or(DecimalError::InvalidField { field: "coef" })?,
+            };
+            let exp = exp
+                .to_bigint()
+                .ok_or(DecimalError::InvalidField { field: "exp" })?;
+            let scale = (-&exp)
+                .to_i64()
+                .filter(|&scale| scale != i64::MIN)
+                .ok_or_else(|| DecimalError::ExponentOutOfRange {
+                    exp: exp.to_string(),
+                })?;
+            let coef = if negative { -coef } else { coef };
+            Ok(BigDecimal::new(coef, scale))
+        }
+
+        /// Makes a decimal term of `value` in the shape of `convention`, with the digits and
+        /// scale of `value` as its coefficient and exponent (so that trailing zeros are kept).
+        pub fn from_decimal(value: &BigDecimal, convention: DecimalConvention) -> Term {
+            let (coef, scale) = value.as_bigint_and_exponent();
+            let sign = if coef.sign() == Sign::Minus { -1 } else { 1 };
+            let coef = integer_term(coef.magnitude().to_bigint().unwrap_or_default());
+            let exp = Term::from(-i128::from(scale));
+            match convention {
+                DecimalConvention::Struct => ElixirStruct::new("Decimal")
+                    .field("sign", sign)
+                    .field("coef", coef)
+                    .field("exp", exp)
+                    .into_term(),
+                DecimalConvention::Tuple => {
+                    Term::from(tuple!(Atom::from("decimal"), sign, coef, exp))
+                }
+            }
+        }
+    }
+
+    /// Returns the sign, coefficient and exponent of a decimal struct or tuple.
+    fn decimal_fields(term: &Term) -> Result<(Term, Term, Term), DecimalError> {
+        if let Term::Tuple(tuple) = term {
+            return match tuple.elements.as_slice() {
+                [Term::Atom(tag), sign, coef, exp] if tag.as_str() == "decimal" => {
+                    Ok((sign.clone(), coef.clone(), exp.clone()))
+                }
+                _ => Err(DecimalError::NotADecimal),
+            };
+        }
+        let decimal = ElixirStruct::from_term(term)
+            .filter(|s| s.is("Decimal"))
+            .ok_or(DecimalError::NotADecimal)?;
+        let field = |field| {
+            decimal
+                .get(field)
+                .cloned()
+                .ok_or(DecimalError::InvalidField { field })
+        };
+        Ok((field("sign")?, field("coef")?, field("exp")?))
+    }
+
+    fn integer_term(value: BigInt) -> Term {
+        match value.to_i32() {
+            Some(value) => Term::from(value),
+            None => Term::from(BigInteger { value }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn struct_round_trip_works() {
+        let term = ElixirStruct::new("MyApp.User")
+            .field("name", Binary::from("joe".as_bytes()))
+            .field("age", 30)
+            .into_term();
+
+        let map: &Map = term.try_as_ref().unwrap();
+        assert_eq!(
+            map.map.get(&Term::from(Atom::from("__struct__"))),
+            Some(&Term::from(Atom::from("Elixir.MyApp.User")))
+        );
+
+        let mut buf = Vec::new();
+        term.encode(&mut buf).unwrap();
+        let decoded = Term::decode(io::Cursor::new(&buf)).unwrap();
+
+        let user = ElixirStruct::from_term(&decoded).unwrap();
+        assert_eq!(user.module_name(), "Elixir.MyApp.User");
+        assert_eq!(user.short_module_name(), "MyApp.User");
+        assert!(user.is("MyApp.User"));
+        assert_eq!(user.fields().len(), 2);
+        assert_eq!(user.get("age"), Some(&Term::from(30)));
+        assert_eq!(user.into_term(), term);
+    }
+
+    #[test]
+    fn non_struct_is_rejected() {
+        let map = Term::from(Map::from([(
+            Term::from(Atom::from("name")),
+            Term::from(Atom::from("joe")),
+        )]));
+        assert_eq!(ElixirStruct::from_term(&map), None);
+        assert_eq!(ElixirStruct::from_term(&Term::from(1)), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn date_time_conversion_works() {
+        use chrono::{DateTime, NaiveDate, Utc};
+
+        let naive = NaiveDate::from_ymd_opt(2024, 2, 29)
+            .unwrap()
+            .and_hms_micro_opt(12, 34, 56, 789)
+            .unwrap();
+        let s = ElixirStruct::from(naive);
+        assert!(s.is_naive_date_time());
+        assert_eq!(s.to_naive_date_time(), Some(naive));
+
+        let utc = DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc);
+        let s = ElixirStruct::from_term(&ElixirStruct::from(utc).into_term()).unwrap();
+        assert!(s.is_date_time());
+        assert_eq!(s.to_date_time_utc(), Some(utc));
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn decimal_conversion_works() {
+        use bigdecimal::BigDecimal;
+        use num::bigint::BigInt;
+        use std::str::FromStr;
+
+        let values = [
+            BigDecimal::from_str("-12.50").unwrap(),
+            BigDecimal::from_str("123456789012345678901234567890e-40").unwrap(),
+            BigDecimal::new(BigInt::from(7), i64::MAX),
+            BigDecimal::new(BigInt::from(-7), -i64::MAX),
+        ];
+        for value in &values {
+            for convention in [DecimalConvention::Struct, DecimalConvention::Tuple] {
+                let term = Term::from_decimal(value, convention);
+                let mut buf = Vec::new();
+                term.encode(&mut buf).unwrap();
+                let decoded = Term::decode(io::Cursor::new(&buf)).unwrap();
+                let decimal = decoded.to_decimal().unwrap();
+                assert_eq!(
+                    decimal.as_bigint_and_exponent(),
+                    value.as_bigint_and_exponent()
+                );
+            }
+        }
+
+        let s = ElixirStruct::from_term(&Term::from_decimal(&values[0], DecimalConvention::Struct))
+            .unwrap();
+        assert!(s.is("Decimal"));
+        assert_eq!(s.get("sign"), Some(&Term::from(-1)));
+        assert_eq!(s.get("coef"), Some(&Term::from(1250)));
+        assert_eq!(s.get("exp"), Some(&Term::from(-2)));
+
+        // -0 is 0.
+        let term = Term::from(tuple!(Atom::from("decimal"), -1, 0, 3));
+        assert_eq!(term.to_decimal().unwrap(), BigDecimal::from(0));
+        let term = Term::from_decimal(&BigDecimal::from(0), DecimalConvention::Tuple);
+        assert_eq!(term, Term::from(tuple!(Atom::from("decimal"), 1, 0, 0)));
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn special_decimals_are_rejected() {
+        let decimal = |sign: i32, coef: Term, exp: Term| {
+            ElixirStruct::new("Decimal")
+                .field("sign", sign)
+                .field("coef", coef)
+                .field("exp", exp)
+                .into_term()
+        };
+
+        let inf = decimal(-1, Term::from(Atom::from("inf")), Term::from(0));
+        assert_eq!(
+            inf.to_decimal(),
+            Err(DecimalError::Infinite { negative: true })
+        );
+        let nan = decimal(1, Term::from(Atom::from("NaN")), Term::from(0));
+        assert_eq!(nan.to_decimal(), Err(DecimalError::NaN));
+
+        let exp = Term::from(i128::from(i64::MAX) + 1);
+        assert_eq!(
+            decimal(1, Term::from(1), exp).to_decimal(),
+            Err(DecimalError::ExponentOutOfRange {
+                exp: "9223372036854775808".to_owned()
+            })
+        );
+        assert_eq!(
+            decimal(1, Term::from(-1), Term::from(0)).to_decimal(),
+            Err(DecimalError::InvalidField { field: "coef" })
+        );
+        assert_eq!(
+            decimal(2, Term::from(1), Term::from(0)).to_decimal(),
+            Err(DecimalError::InvalidField { field: "sign" })
+        );
+        assert_eq!(
+            ElixirStruct::new("Money").into_term().to_decimal(),
+            Err(DecimalError::NotADecimal)
+        );
+    }
+}
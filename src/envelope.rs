@@ -0,0 +1,373 @@
+//! Versioned messages, `{msg, Version, Kind, Payload}`, and their dispatch to handlers.
+//!
+//! An [`Envelope`] tags a payload with its kind (an atom) and the version of its format, so
+//! that the services exchanging it can evolve the format of each kind on their own. A
+//! [`Router`] passes each envelope to the handler registered for its kind and version, and
+//! [`Router::serve`] does so for each term received by a [`PacketTransport`], sending back the
+//! replies of the handlers.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::envelope::{Envelope, EnvelopeError, Router};
+//! use eetf::Term;
+//!
+//! let mut router = Router::new()
+//!     .route("ping", 1..=2, |envelope: Envelope| Ok(Some(envelope.payload)))
+//!     .route("log", 1.., |_| Ok(None));
+//!
+//! let ping = Envelope::new("ping", 2, Term::from(7));
+//! assert_eq!(ping.to_term().to_string(), "{'msg',2,'ping',7}");
+//! assert_eq!(router.dispatch_term(&ping.to_term()).unwrap(), Some(Term::from(7)));
+//!
+//! let ping = Envelope::new("ping", 3, Term::from(7));
+//! assert!(matches!(
+//!     router.dispatch(ping),
+//!     Err(EnvelopeError::UnsupportedVersion { version: 3, .. })
+//! ));
+//! ```
+use super::*;
+use crate::convert::FromTerm;
+use crate::packet::PacketTransport;
+use std::io::{Read, Write};
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+/// Error returned by a handler of a [`Router`].
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors which can occur when parsing or dispatching envelopes.
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("malformed envelope: expected {expected}, found {value}")]
+    Malformed { expected: &'static str, value: Term },
+
+    #[error("no handler for messages of kind {kind}")]
+    UnknownKind { kind: Atom },
+
+    /// The router has handlers for the kind, but not for its version.
+    #[error("unsupported version {version} of messages of kind {kind} (supported: {})",
+        describe_versions(.supported))]
+    UnsupportedVersion {
+        kind: Atom,
+        version: u32,
+        /// The versions of the handlers of the kind, in the order of their registration.
+        supported: Vec<RangeInclusive<u32>>,
+    },
+
+    #[error("the handler of version {version} of {kind} failed")]
+    Handler {
+        kind: Atom,
+        version: u32,
+        #[source]
+        source: HandlerError,
+    },
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+fn describe_versions(versions: &[RangeInclusive<u32>]) -> String {
+    versions
+        .iter()
+        .map(|v| format!("{}..={}", v.start(), v.end()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Message of a kind, in a version of its format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub version: u32,
+    pub kind: Atom,
+    pub payload: Term,
+}
+impl Envelope {
+    pub fn new(kind: impl Into<Atom>, version: u32, payload: impl Into<Term>) -> Self {
+        Envelope {
+            version,
+            kind: kind.into(),
+            payload: payload.into(),
+        }
+    }
+
+    /// Converts the envelope to its tuple, `{msg, Version, Kind, Payload}`.
+    pub fn to_term(&self) -> Term {
+        Term::from(tuple!(
+            Atom::from("msg"),
+            self.version,
+            self.kind.clone(),
+            self.payload.clone()
+        ))
+    }
+
+    /// Parses an envelope, which must be a tuple of exactly four elements: the atom `msg`, a
+    /// version which fits in `u32`, an atom and the payload.
+    pub fn from_term(term: &Term) -> Result<Self, EnvelopeError> {
+        let elements = match term {
+            Term::Tuple(x) => x.elements.as_slice(),
+            _ => return Err(malformed("{msg, Version, Kind, Payload}", term)),
+        };
+        match elements {
+            [Term::Atom(tag), version, kind, payload] if tag.as_str() == "msg" => Ok(Envelope {
+                version: u32::from_term(version)
+                    .map_err(|_| malformed("a version (u32)", version))?,
+                kind: match kind {
+                    Term::Atom(x) => x.clone(),
+                    _ => return Err(malformed("a kind (atom)", kind)),
+                },
+                payload: payload.clone(),
+            }),
+            _ => Err(malformed("{msg, Version, Kind, Payload}", term)),
+        }
+    }
+}
+impl From<Envelope> for Term {
+    fn from(envelope: Envelope) -> Self {
+        envelope.to_term()
+    }
+}
+
+fn malformed(expected: &'static str, value: &Term) -> EnvelopeError {
+    EnvelopeError::Malformed {
+        expected,
+        value: value.clone(),
+    }
+}
+
+type Handler<'a> = Box<dyn FnMut(Envelope) -> Result<Option<Term>, HandlerError> + 'a>;
+
+struct Route<'a> {
+    kind: Atom,
+    versions: RangeInclusive<u32>,
+    handler: Handler<'a>,
+}
+
+/// Dispatcher of envelopes to handlers registered per kind and range of versions.
+///
+/// A handler returns the reply to the envelope, if any.
+#[derive(Default)]
+pub struct Router<'a> {
+    routes: Vec<Route<'a>>,
+}
+impl<'a> Router<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for the envelopes of `kind` whose version is within `versions`.
+    ///
+    /// If several handlers accept an envelope, the first one registered handles it.
+    pub fn route<F>(
+        mut self,
+        kind: impl Into<Atom>,
+        versions: impl RangeBounds<u32>,
+        handler: F,
+    ) -> Self
+    where
+        F: FnMut(Envelope) -> Result<Option<Term>, HandlerError> + 'a,
+    {
+        self.routes.push(Route {
+            kind: kind.into(),
+            versions: to_inclusive(versions),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Passes `envelope` to its handler, returning the reply of the handler.
+    pub fn dispatch(&mut self, envelope: Envelope) -> Result<Option<Term>, EnvelopeError> {
+        let mut supported = Vec::new();
+        for route in self.routes.iter_mut().filter(|r| r.kind == envelope.kind) {
+            if !route.versions.contains(&envelope.version) {
+                supported.push(route.versions.clone());
+                continue;
+            }
+            let (kind, version) = (envelope.kind.clone(), envelope.version);
+            return (route.handler)(envelope).map_err(|source| EnvelopeError::Handler {
+                kind,
+                version,
+                source,
+            });
+        }
+        if supported.is_empty() {
+            return Err(EnvelopeError::UnknownKind {
+                kind: envelope.kind,
+            });
+        }
+        Err(EnvelopeError::UnsupportedVersion {
+            kind: envelope.kind,
+            version: envelope.version,
+            supported,
+        })
+    }
+
+    /// Parses `term` as an envelope and passes it to its handler (see [`Router::dispatch`]).
+    pub fn dispatch_term(&mut self, term: &Term) -> Result<Option<Term>, EnvelopeError> {
+        self.dispatch(Envelope::from_term(term)?)
+    }
+
+    /// Dispatches the terms received by `transport` until its stream ends, and sends the
+    /// replies of the handlers.
+    ///
+    /// Stops at the first error, e.g., a term which is not an envelope or which has no
+    /// handler.
+    pub fn serve<R: Read, W: Write>(
+        &mut self,
+        transport: &mut PacketTransport<R, W>,
+    ) -> Result<(), EnvelopeError> {
+        loop {
+            let term = match transport.recv_term() {
+                Ok(term) => term,
+                Err(DecodeError::EndOfStream) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(reply) = self.dispatch_term(&term)? {
+                transport.send_term(&reply)?;
+            }
+        }
+    }
+}
+impl fmt::Debug for Router<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.routes.iter().map(|r| (&r.kind, &r.versions)))
+            .finish()
+    }
+}
+
+/// Converts `versions` to an inclusive range, which is empty (`1..=0`) if `versions` is.
+fn to_inclusive(versions: impl RangeBounds<u32>) -> RangeInclusive<u32> {
+    let start = match versions.start_bound() {
+        Bound::Included(&start) => Some(start),
+        Bound::Excluded(&start) => start.checked_add(1),
+        Bound::Unbounded => Some(0),
+    };
+    let end = match versions.end_bound() {
+        Bound::Included(&end) => Some(end),
+        Bound::Excluded(&end) => end.checked_sub(1),
+        Bound::Unbounded => Some(u32::MAX),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) => start..=end,
+        _ => RangeInclusive::new(1, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PacketSize;
+
+    fn router<'a>(calls: &'a std::cell::RefCell<Vec<&'static str>>) -> Router<'a> {
+        Router::new()
+            .route("ping", 1..=1, move |e: Envelope| {
+                calls.borrow_mut().push("ping v1");
+                Ok(Some(e.payload))
+            })
+            .route("ping", 2..4, move |_| {
+                calls.borrow_mut().push("ping v2");
+                Ok(None)
+            })
+            .route("log", .., move |_| Err("disk full".into()))
+    }
+
+    #[test]
+    fn envelope_round_trip_works() {
+        let envelope = Envelope::new("ping", u32::MAX, Term::from(Atom::from("hi")));
+        let mut buf = Vec::new();
+        envelope.to_term().encode(&mut buf).unwrap();
+        let term = Term::decode(io::Cursor::new(&buf)).unwrap();
+        assert_eq!(Envelope::from_term(&term).unwrap(), envelope);
+
+        for term in [
+            tuple!(Atom::from("msg"), 1, Atom::from("ping")),
+            tuple!(Atom::from("msg"), 1, Atom::from("ping"), 0, 0),
+            tuple!(Atom::from("message"), 1, Atom::from("ping"), 0),
+            tuple!(Atom::from("msg"), -1, Atom::from("ping"), 0),
+            tuple!(Atom::from("msg"), 1, Binary::from(&b"ping"[..]), 0),
+        ] {
+            let term = Term::from(term);
+            assert!(
+                matches!(
+                    Envelope::from_term(&term),
+                    Err(EnvelopeError::Malformed { .. })
+                ),
+                "{}",
+                term
+            );
+        }
+    }
+
+    #[test]
+    fn envelopes_are_dispatched() {
+        let calls = std::cell::RefCell::new(Vec::new());
+        let mut router = router(&calls);
+
+        let reply = router.dispatch(Envelope::new("ping", 1, Term::from(5)));
+        assert_eq!(reply.unwrap(), Some(Term::from(5)));
+        let reply = router.dispatch(Envelope::new("ping", 3, Term::from(5)));
+        assert_eq!(reply.unwrap(), None);
+        assert_eq!(*calls.borrow(), ["ping v1", "ping v2"]);
+
+        match router.dispatch(Envelope::new("pong", 1, Term::from(5))) {
+            Err(EnvelopeError::UnknownKind { kind }) => assert_eq!(kind, Atom::from("pong")),
+            other => panic!("{:?}", other),
+        }
+        let error = router
+            .dispatch(Envelope::new("ping", 4, Term::from(5)))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unsupported version 4 of messages of kind 'ping' (supported: 1..=1, 2..=3)"
+        );
+        match router.dispatch(Envelope::new("log", 0, Term::from(5))) {
+            Err(EnvelopeError::Handler { source, .. }) => {
+                assert_eq!(source.to_string(), "disk full")
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(calls.borrow().len(), 2);
+    }
+
+    #[test]
+    fn transport_is_served() {
+        let calls = std::cell::RefCell::new(Vec::new());
+        let mut input = Vec::new();
+        let mut sender = PacketTransport::new(&[][..], &mut input, PacketSize::Four);
+        for envelope in [
+            Envelope::new("ping", 1, Term::from(1)),
+            Envelope::new("ping", 2, Term::from(2)),
+            Envelope::new("ping", 1, Term::from(3)),
+        ] {
+            sender.send_term(&envelope.to_term()).unwrap();
+        }
+
+        let mut output = Vec::new();
+        let mut transport = PacketTransport::new(&input[..], &mut output, PacketSize::Four);
+        router(&calls).serve(&mut transport).unwrap();
+        assert_eq!(*calls.borrow(), ["ping v1", "ping v2", "ping v1"]);
+
+        let mut receiver = PacketTransport::new(&output[..], io::sink(), PacketSize::Four);
+        assert_eq!(receiver.recv_term().unwrap(), Term::from(1));
+        assert_eq!(receiver.recv_term().unwrap(), Term::from(3));
+        assert!(matches!(
+            receiver.recv_term(),
+            Err(DecodeError::EndOfStream)
+        ));
+
+        // An unknown kind stops the server.
+        let mut input = Vec::new();
+        let mut sender = PacketTransport::new(&[][..], &mut input, PacketSize::Four);
+        sender
+            .send_term(&Envelope::new("pong", 1, Term::from(1)).to_term())
+            .unwrap();
+        let mut transport = PacketTransport::new(&input[..], io::sink(), PacketSize::Four);
+        assert!(matches!(
+            router(&calls).serve(&mut transport),
+            Err(EnvelopeError::UnknownKind { .. })
+        ));
+    }
+}
@@ -0,0 +1,398 @@
+//! Client of EPMD (the Erlang Port Mapper Daemon).
+//!
+//! EPMD listens on port 4369 of each host and maps the names of the nodes running
+//! there to their distribution ports.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), eetf::epmd::EpmdError> {
+//! // Looks up `foo@localhost`.
+//! let info = eetf::epmd::port_please("localhost", "foo").await?;
+//! println!("listening on {}", info.port);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Reference
+//!
+//! - [EPMD Protocol](https://www.erlang.org/doc/apps/erts/erl_dist_protocol.html#epmd-protocol)
+use super::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The default port of EPMD.
+pub const EPMD_PORT: u16 = 4369;
+
+/// The highest distribution protocol version registered by [`register_node`].
+pub const HIGHEST_VERSION: u16 = 6;
+
+/// The lowest distribution protocol version registered by [`register_node`].
+pub const LOWEST_VERSION: u16 = 5;
+
+const ALIVE2_X_RESP: u8 = 118;
+const PORT2_RESP: u8 = 119;
+const ALIVE2_REQ: u8 = 120;
+const ALIVE2_RESP: u8 = 121;
+const PORT_PLEASE2_REQ: u8 = 122;
+const NAMES_REQ: u8 = 110;
+
+/// Errors which can occur when talking to EPMD.
+#[derive(Debug, thiserror::Error)]
+pub enum EpmdError {
+    #[error("node {name:?} is not registered")]
+    NodeNotFound { name: String },
+
+    #[error("registration of node {name:?} was refused (result {result})")]
+    RegistrationRefused { name: String, result: u8 },
+
+    #[error("unexpected response tag {tag} (expected {expected})")]
+    UnexpectedResponse { tag: u8, expected: u8 },
+
+    #[error("unknown node type {node_type}")]
+    UnknownNodeType { node_type: u8 },
+
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("{0} is too long")]
+    TooLong(&'static str),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Node type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NodeType {
+    #[default]
+    Normal,
+    Hidden,
+}
+impl NodeType {
+    fn to_u8(self) -> u8 {
+        match self {
+            NodeType::Normal => 77,
+            NodeType::Hidden => 72,
+        }
+    }
+    fn from_u8(node_type: u8) -> Result<Self, EpmdError> {
+        match node_type {
+            77 => Ok(NodeType::Normal),
+            72 => Ok(NodeType::Hidden),
+            _ => Err(EpmdError::UnknownNodeType { node_type }),
+        }
+    }
+}
+
+/// Registration of a node in EPMD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    /// The name of the node (i.e., the part preceding `@`).
+    pub name: String,
+
+    /// The port on which the node accepts distribution connections.
+    pub port: u16,
+
+    pub node_type: NodeType,
+
+    /// The transport protocol (`0` is TCP/IPv4).
+    pub protocol: u8,
+
+    pub highest_version: u16,
+    pub lowest_version: u16,
+    pub extra: Vec<u8>,
+}
+impl NodeInfo {
+    /// Makes the registration of a normal TCP node of the supported protocol versions.
+    pub fn new(name: &str, port: u16) -> Self {
+        NodeInfo {
+            name: name.to_owned(),
+            port,
+            node_type: NodeType::Normal,
+            protocol: 0,
+            highest_version: HIGHEST_VERSION,
+            lowest_version: LOWEST_VERSION,
+            extra: Vec::new(),
+        }
+    }
+}
+
+/// Client of the EPMD of a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpmdClient {
+    host: String,
+    port: u16,
+}
+impl EpmdClient {
+    /// Makes a client of the EPMD of `host`.
+    pub fn new(host: &str) -> Self {
+        EpmdClient {
+            host: host.to_owned(),
+            port: EPMD_PORT,
+        }
+    }
+
+    /// Sets the port of EPMD (the default is [`EPMD_PORT`]).
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Looks up the node named `node_name` (i.e., the part of the node name preceding `@`).
+    pub async fn port_please(&self, node_name: &str) -> Result<NodeInfo, EpmdError> {
+        let mut request = vec![PORT_PLEASE2_REQ];
+        request.extend_from_slice(node_name.as_bytes());
+        let mut stream = self.request(&request).await?;
+
+        expect_tag(&mut stream, PORT2_RESP).await?;
+        if stream.read_u8().await? != 0 {
+            return Err(EpmdError::NodeNotFound {
+                name: node_name.to_owned(),
+            });
+        }
+        let port = stream.read_u16().await?;
+        let node_type = NodeType::from_u8(stream.read_u8().await?)?;
+        let protocol = stream.read_u8().await?;
+        let highest_version = stream.read_u16().await?;
+        let lowest_version = stream.read_u16().await?;
+        let name = read_bytes(&mut stream).await?;
+        let name =
+            String::from_utf8(name).map_err(|e| EpmdError::InvalidResponse(e.to_string()))?;
+        let extra = read_bytes(&mut stream).await?;
+        Ok(NodeInfo {
+            name,
+            port,
+            node_type,
+            protocol,
+            highest_version,
+            lowest_version,
+            extra,
+        })
+    }
+
+    /// Returns the names and ports of the nodes registered in EPMD.
+    pub async fn names(&self) -> Result<Vec<(String, u16)>, EpmdError> {
+        let mut stream = self.request(&[NAMES_REQ]).await?;
+        let _epmd_port = stream.read_u32().await?;
+        let mut text = String::new();
+        stream.read_to_string(&mut text).await?;
+        text.lines().map(parse_names_line).collect()
+    }
+
+    /// Registers `info`.
+    ///
+    /// The node stays registered until the returned registration is dropped.
+    pub async fn register(&self, info: &NodeInfo) -> Result<EpmdRegistration, EpmdError> {
+        let mut request = vec![ALIVE2_REQ];
+        request.extend_from_slice(&info.port.to_be_bytes());
+        request.push(info.node_type.to_u8());
+        request.push(info.protocol);
+        request.extend_from_slice(&info.highest_version.to_be_bytes());
+        request.extend_from_slice(&info.lowest_version.to_be_bytes());
+        write_bytes(&mut request, info.name.as_bytes(), "node name")?;
+        write_bytes(&mut request, &info.extra, "extra")?;
+        let mut stream = self.request(&request).await?;
+
+        let tag = stream.read_u8().await?;
+        let result = stream.read_u8().await?;
+        if result != 0 {
+            return Err(EpmdError::RegistrationRefused {
+                name: info.name.clone(),
+                result,
+            });
+        }
+        let creation = match tag {
+            ALIVE2_X_RESP => stream.read_u32().await?,
+            ALIVE2_RESP => u32::from(stream.read_u16().await?),
+            _ => {
+                return Err(EpmdError::UnexpectedResponse {
+                    tag,
+                    expected: ALIVE2_X_RESP,
+                })
+            }
+        };
+        Ok(EpmdRegistration { stream, creation })
+    }
+
+    async fn request(&self, request: &[u8]) -> Result<TcpStream, EpmdError> {
+        let len = u16::try_from(request.len()).map_err(|_| EpmdError::TooLong("request"))?;
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let mut buf = Vec::with_capacity(2 + request.len());
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(request);
+        stream.write_all(&buf).await?;
+        Ok(stream)
+    }
+}
+
+/// Registration of a node, which lasts as long as this value (i.e., the connection to EPMD).
+#[derive(Debug)]
+pub struct EpmdRegistration {
+    stream: TcpStream,
+    creation: u32,
+}
+impl EpmdRegistration {
+    /// Returns the creation of the node, which distinguishes its incarnations.
+    pub fn creation(&self) -> u32 {
+        self.creation
+    }
+
+    /// Returns the connection to EPMD.
+    pub fn stream(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+/// Looks up the node named `node_name` in the EPMD of `host` (see [`EpmdClient::port_please`]).
+pub async fn port_please(host: &str, node_name: &str) -> Result<NodeInfo, EpmdError> {
+    EpmdClient::new(host).port_please(node_name).await
+}
+
+/// Returns the nodes registered in the EPMD of `host` (see [`EpmdClient::names`]).
+pub async fn names(host: &str) -> Result<Vec<(String, u16)>, EpmdError> {
+    EpmdClient::new(host).names().await
+}
+
+/// Registers the node named `name` listening on `port` in the local EPMD (see [`EpmdClient::register`]).
+pub async fn register_node(name: &str, port: u16) -> Result<EpmdRegistration, EpmdError> {
+    EpmdClient::new("localhost")
+        .register(&NodeInfo::new(name, port))
+        .await
+}
+
+async fn expect_tag(stream: &mut TcpStream, expected: u8) -> Result<(), EpmdError> {
+    let tag = stream.read_u8().await?;
+    if tag != expected {
+        return Err(EpmdError::UnexpectedResponse { tag, expected });
+    }
+    Ok(())
+}
+
+async fn read_bytes(stream: &mut TcpStream) -> Result<Vec<u8>, EpmdError> {
+    let len = stream.read_u16().await? as usize;
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8], what: &'static str) -> Result<(), EpmdError> {
+    let len = u16::try_from(bytes.len()).map_err(|_| EpmdError::TooLong(what))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Parses `name foo at port 1234`.
+fn parse_names_line(line: &str) -> Result<(String, u16), EpmdError> {
+    let invalid = || EpmdError::InvalidResponse(format!("invalid NAMES line {:?}", line));
+    let rest = line.strip_prefix("name ").ok_or_else(invalid)?;
+    let (name, port) = rest.rsplit_once(" at port ").ok_or_else(invalid)?;
+    let port = port.trim().parse().map_err(|_| invalid())?;
+    Ok((name.to_owned(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Accepts a connection, checks its request and sends `response`, returning the connection.
+    async fn mock_epmd(
+        expected_request: Vec<u8>,
+        response: Vec<u8>,
+    ) -> (EpmdClient, tokio::task::JoinHandle<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let len = stream.read_u16().await.unwrap() as usize;
+            let mut request = vec![0; len];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request, expected_request);
+            stream.write_all(&response).await.unwrap();
+            stream
+        });
+        (EpmdClient::new("127.0.0.1").port(port), handle)
+    }
+
+    #[test]
+    fn port_please_works() {
+        block_on(async {
+            let mut response = vec![PORT2_RESP, 0, 0x1F, 0x90, 77, 0, 0, 6, 0, 5, 0, 3];
+            response.extend_from_slice(b"foo");
+            response.extend_from_slice(&[0, 0]);
+            let (client, server) = mock_epmd(b"zfoo".to_vec(), response).await;
+            let info = client.port_please("foo").await.unwrap();
+            assert_eq!(info, NodeInfo::new("foo", 8080));
+            drop(server.await.unwrap());
+
+            let (client, server) = mock_epmd(b"zbar".to_vec(), vec![PORT2_RESP, 1]).await;
+            assert!(matches!(
+                client.port_please("bar").await,
+                Err(EpmdError::NodeNotFound { name }) if name == "bar"
+            ));
+            drop(server.await.unwrap());
+        });
+    }
+
+    #[test]
+    fn names_works() {
+        block_on(async {
+            let mut response = vec![0, 0, 0x11, 0x11];
+            response.extend_from_slice(b"name foo at port 8080\nname bar at port 8081\n");
+            let (client, server) = mock_epmd(vec![NAMES_REQ], response).await;
+            // The server closes the connection to end the response.
+            tokio::spawn(async move { drop(server.await.unwrap()) });
+            assert_eq!(
+                client.names().await.unwrap(),
+                [("foo".to_owned(), 8080), ("bar".to_owned(), 8081)]
+            );
+        });
+    }
+
+    #[test]
+    fn register_works() {
+        block_on(async {
+            let mut request = vec![ALIVE2_REQ, 0x1F, 0x90, 72, 0, 0, 6, 0, 5, 0, 3];
+            request.extend_from_slice(b"foo");
+            request.extend_from_slice(&[0, 0]);
+            let response = vec![ALIVE2_X_RESP, 0, 0x65, 0x53, 0xF1, 0x00];
+            let (client, server) = mock_epmd(request.clone(), response).await;
+            let mut info = NodeInfo::new("foo", 8080);
+            info.node_type = NodeType::Hidden;
+            let registration = client.register(&info).await.unwrap();
+            assert_eq!(registration.creation(), 1700000000);
+
+            // Dropping the registration closes the connection.
+            let mut server = server.await.unwrap();
+            drop(registration);
+            assert_eq!(server.read(&mut [0]).await.unwrap(), 0);
+
+            let (client, server) = mock_epmd(request, vec![ALIVE2_RESP, 1, 0, 0]).await;
+            assert!(matches!(
+                client.register(&info).await,
+                Err(EpmdError::RegistrationRefused { result: 1, .. })
+            ));
+            drop(server.await.unwrap());
+        });
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(parse_names_line("foo 8080").is_err());
+        assert!(parse_names_line("name foo at port x").is_err());
+        assert_eq!(
+            parse_names_line("name foo at port 1").unwrap(),
+            ("foo".to_owned(), 1)
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Error type of the high-level functions.
+use super::*;
+use crate::node_name::NodeNameError;
+
+/// Errors of the high-level functions (e.g., [`read_term_file`](crate::read_term_file)),
+/// which may fail in more than one way.
+///
+/// The low-level APIs return their specific error types, which convert into this one.
+/// The display of an error names the failed step, and [`source`](core::error::Error::source)
+/// returns the specific error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to encode a term")]
+    Encode(#[from] EncodeError),
+
+    #[error("failed to decode a term")]
+    Decode(#[from] DecodeError),
+
+    #[error("failed to parse a node name")]
+    Parse(#[from] NodeNameError),
+
+    #[cfg(feature = "std")]
+    #[error("I/O error")]
+    Io(#[from] io::Error),
+
+    #[cfg(feature = "epmd")]
+    #[error("EPMD request failed")]
+    Epmd(#[from] crate::epmd::EpmdError),
+
+    #[cfg(feature = "distribution")]
+    #[error("distribution handshake failed")]
+    Handshake(#[from] crate::dist::handshake::HandshakeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::error::Error as _;
+
+    fn messages(error: Error) -> (String, String) {
+        let source = error.source().expect("no source").to_string();
+        (error.to_string(), source)
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            messages(Error::from(EncodeError::TooLongAtomName(
+                Atom::from("a"),
+                EncodePath::default()
+            ))),
+            (
+                "failed to encode a term".to_string(),
+                "too long atom name: 1 bytes".to_string()
+            )
+        );
+        assert_eq!(
+            messages(Error::from(DecodeError::UnknownTag { tag: 1 })),
+            (
+                "failed to decode a term".to_string(),
+                "unknown tag 1".to_string()
+            )
+        );
+        assert_eq!(
+            messages(Error::from(NodeName::parse("foo").unwrap_err())),
+            (
+                "failed to parse a node name".to_string(),
+                "node name \"foo\" has no '@'".to_string()
+            )
+        );
+        #[cfg(feature = "std")]
+        assert_eq!(
+            messages(Error::from(io::Error::other("disk full"))),
+            ("I/O error".to_string(), "disk full".to_string())
+        );
+        #[cfg(feature = "epmd")]
+        assert_eq!(
+            messages(Error::from(crate::epmd::EpmdError::NodeNotFound {
+                name: "foo".to_string()
+            })),
+            (
+                "EPMD request failed".to_string(),
+                "node \"foo\" is not registered".to_string()
+            )
+        );
+        #[cfg(feature = "distribution")]
+        assert_eq!(
+            messages(Error::from(
+                crate::dist::handshake::HandshakeError::DigestMismatch
+            )),
+            (
+                "distribution handshake failed".to_string(),
+                "the peer's digest does not match the cookie".to_string()
+            )
+        );
+    }
+}
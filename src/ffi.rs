@@ -0,0 +1,596 @@
+//! C ABI for decoding and encoding terms.
+//!
+//! The declarations are in `include/eetf.h` (generated by `cbindgen`). To get a shared
+//! library, build the crate with `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! # Ownership
+//!
+//! - [`eetf_decode`] returns a term owned by the caller, which must be released with
+//!   [`eetf_term_free`].
+//! - The terms returned by the accessors (e.g., [`eetf_tuple_get`]) and the data they
+//!   point to are borrowed from the term they were obtained from. They must not be
+//!   freed, and are valid as long as the owned term is.
+//! - [`eetf_encode`] returns a buffer owned by the caller, which must be released with
+//!   [`eetf_bytes_free`].
+//!
+//! # Errors
+//!
+//! The functions which can fail return an [`eetf_status`]. On failure, a description of
+//! the error can be obtained with [`eetf_last_error_message`] on the same thread.
+#![allow(non_camel_case_types)]
+
+use super::*;
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+/// Result of a fallible function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum eetf_status {
+    EETF_OK = 0,
+    /// A pointer argument is NULL.
+    EETF_NULL_POINTER = 1,
+    /// The input is not a valid term.
+    EETF_DECODE_ERROR = 2,
+    /// The term cannot be encoded.
+    EETF_ENCODE_ERROR = 3,
+    /// The term is not of the type the function expects.
+    EETF_WRONG_TYPE = 4,
+    /// The index or value is out of range.
+    EETF_OUT_OF_RANGE = 5,
+}
+use eetf_status::*;
+
+/// Type of a term (see [`Term`]).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum eetf_type {
+    /// The term pointer is NULL.
+    EETF_TYPE_INVALID = 0,
+    EETF_TYPE_ATOM,
+    EETF_TYPE_FIX_INTEGER,
+    EETF_TYPE_BIG_INTEGER,
+    EETF_TYPE_FLOAT,
+    EETF_TYPE_PID,
+    EETF_TYPE_PORT,
+    EETF_TYPE_REFERENCE,
+    EETF_TYPE_EXTERNAL_FUN,
+    EETF_TYPE_INTERNAL_FUN,
+    EETF_TYPE_BINARY,
+    EETF_TYPE_BIT_BINARY,
+    EETF_TYPE_BYTE_LIST,
+    EETF_TYPE_LIST,
+    EETF_TYPE_IMPROPER_LIST,
+    EETF_TYPE_TUPLE,
+    EETF_TYPE_MAP,
+    EETF_TYPE_RAW,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: eetf_status, message: impl ToString) -> eetf_status {
+    // Interior NUL bytes (e.g., from atom names) would truncate the message, so they are dropped.
+    let message = message.to_string().replace('\0', "");
+    let message = CString::new(message).expect("unreachable");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+    status
+}
+
+fn wrong_type(term: &Term, expected: &str) -> eetf_status {
+    fail(
+        EETF_WRONG_TYPE,
+        format!("expected {}, found {}", expected, term.variant_name()),
+    )
+}
+
+unsafe fn term_ref<'a>(term: *const Term) -> Result<&'a Term, eetf_status> {
+    term.as_ref()
+        .ok_or_else(|| fail(EETF_NULL_POINTER, "the term is NULL"))
+}
+
+fn check_out<T>(out: *mut T) -> Result<(), eetf_status> {
+    if out.is_null() {
+        Err(fail(EETF_NULL_POINTER, "an output pointer is NULL"))
+    } else {
+        Ok(())
+    }
+}
+
+fn status(result: Result<(), eetf_status>) -> eetf_status {
+    match result {
+        Ok(()) => EETF_OK,
+        Err(status) => status,
+    }
+}
+
+/// Writes a borrowed byte slice to `data` and `len`.
+unsafe fn write_bytes(
+    bytes: &[u8],
+    data: *mut *const u8,
+    len: *mut usize,
+) -> Result<(), eetf_status> {
+    check_out(data)?;
+    check_out(len)?;
+    *data = bytes.as_ptr();
+    *len = bytes.len();
+    Ok(())
+}
+
+/// Returns the message of the last error on the calling thread, or NULL if there is none.
+///
+/// The message is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn eetf_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Decodes the `len` bytes at `data` into a new term stored in `*out`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_decode(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut Term,
+) -> eetf_status {
+    status((|| {
+        check_out(out)?;
+        if data.is_null() {
+            return Err(fail(EETF_NULL_POINTER, "the input is NULL"));
+        }
+        let bytes = std::slice::from_raw_parts(data, len);
+        let term = Term::decode(bytes).map_err(|e| fail(EETF_DECODE_ERROR, e))?;
+        *out = Box::into_raw(Box::new(term));
+        Ok(())
+    })())
+}
+
+/// Encodes `term` into a new buffer stored in `*out` and `*len`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `out` and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_encode(
+    term: *const Term,
+    out: *mut *mut u8,
+    len: *mut usize,
+) -> eetf_status {
+    status((|| {
+        let term = term_ref(term)?;
+        check_out(out)?;
+        check_out(len)?;
+        let mut buf = Vec::new();
+        term.encode(&mut buf)
+            .map_err(|e| fail(EETF_ENCODE_ERROR, e))?;
+        let buf = buf.into_boxed_slice();
+        *len = buf.len();
+        *out = Box::into_raw(buf) as *mut u8;
+        Ok(())
+    })())
+}
+
+/// Releases a term returned by [`eetf_decode`]. NULL is ignored.
+///
+/// # Safety
+///
+/// `term` must be NULL or an owned term which has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_term_free(term: *mut Term) {
+    if !term.is_null() {
+        drop(Box::from_raw(term));
+    }
+}
+
+/// Releases a buffer returned by [`eetf_encode`]. NULL is ignored.
+///
+/// # Safety
+///
+/// `data` and `len` must be NULL or a buffer and its length which have not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Returns the type of `term` ([`eetf_type::EETF_TYPE_INVALID`] if it is NULL).
+///
+/// # Safety
+///
+/// `term` must be NULL or a term obtained from this library.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_term_type(term: *const Term) -> eetf_type {
+    use eetf_type::*;
+
+    match term.as_ref() {
+        None => EETF_TYPE_INVALID,
+        Some(Term::Atom(_)) => EETF_TYPE_ATOM,
+        Some(Term::FixInteger(_)) => EETF_TYPE_FIX_INTEGER,
+        Some(Term::BigInteger(_)) => EETF_TYPE_BIG_INTEGER,
+        Some(Term::Float(_)) => EETF_TYPE_FLOAT,
+        Some(Term::Pid(_)) => EETF_TYPE_PID,
+        Some(Term::Port(_)) => EETF_TYPE_PORT,
+        Some(Term::Reference(_)) => EETF_TYPE_REFERENCE,
+        Some(Term::ExternalFun(_)) => EETF_TYPE_EXTERNAL_FUN,
+        Some(Term::InternalFun(_)) => EETF_TYPE_INTERNAL_FUN,
+        Some(Term::Binary(_)) => EETF_TYPE_BINARY,
+        Some(Term::BitBinary(_)) => EETF_TYPE_BIT_BINARY,
+        Some(Term::ByteList(_)) => EETF_TYPE_BYTE_LIST,
+        Some(Term::List(_)) => EETF_TYPE_LIST,
+        Some(Term::ImproperList(_)) => EETF_TYPE_IMPROPER_LIST,
+        Some(Term::Tuple(_)) => EETF_TYPE_TUPLE,
+        Some(Term::Map(_)) => EETF_TYPE_MAP,
+        Some(Term::Raw(_)) => EETF_TYPE_RAW,
+    }
+}
+
+/// Stores the UTF-8 name of an atom (not NUL-terminated) in `*name` and `*len`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `name` and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_atom_name(
+    term: *const Term,
+    name: *mut *const u8,
+    len: *mut usize,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Atom(x) => write_bytes(x.name.as_bytes(), name, len),
+        other => Err(wrong_type(other, "Atom")),
+    })())
+}
+
+/// Stores the value of an integer in `*value`.
+///
+/// Big integers are accepted if they fit in 64 bits.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_integer_value(term: *const Term, value: *mut i64) -> eetf_status {
+    use num::ToPrimitive;
+
+    status((|| {
+        let n = match term_ref(term)? {
+            Term::FixInteger(x) => i64::from(x.value),
+            Term::BigInteger(x) => x
+                .value
+                .to_i64()
+                .ok_or_else(|| fail(EETF_OUT_OF_RANGE, format!("{} does not fit in 64 bits", x)))?,
+            other => return Err(wrong_type(other, "FixInteger or BigInteger")),
+        };
+        check_out(value)?;
+        *value = n;
+        Ok(())
+    })())
+}
+
+/// Stores the value of a float in `*value`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_float_value(term: *const Term, value: *mut f64) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Float(x) => {
+            check_out(value)?;
+            *value = x.value;
+            Ok(())
+        }
+        other => Err(wrong_type(other, "Float")),
+    })())
+}
+
+/// Stores the bytes of a binary or a byte list in `*data` and `*len`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `data` and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_binary_data(
+    term: *const Term,
+    data: *mut *const u8,
+    len: *mut usize,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Binary(x) => write_bytes(&x.bytes, data, len),
+        Term::ByteList(x) => write_bytes(&x.bytes, data, len),
+        other => Err(wrong_type(other, "Binary or ByteList")),
+    })())
+}
+
+/// Stores the number of elements of a tuple in `*size`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `size` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_tuple_size(term: *const Term, size: *mut usize) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Tuple(x) => {
+            check_out(size)?;
+            *size = x.elements.len();
+            Ok(())
+        }
+        other => Err(wrong_type(other, "Tuple")),
+    })())
+}
+
+/// Stores the `index`-th element of a tuple in `*element`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `element` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_tuple_get(
+    term: *const Term,
+    index: usize,
+    element: *mut *const Term,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Tuple(x) => write_element(&x.elements, index, element),
+        other => Err(wrong_type(other, "Tuple")),
+    })())
+}
+
+/// Stores the number of elements of a list (excluding the tail of an improper list) in `*len`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_list_length(term: *const Term, len: *mut usize) -> eetf_status {
+    status((|| {
+        let n = match term_ref(term)? {
+            Term::List(x) => x.elements.len(),
+            Term::ImproperList(x) => x.elements.len(),
+            other => return Err(wrong_type(other, "List or ImproperList")),
+        };
+        check_out(len)?;
+        *len = n;
+        Ok(())
+    })())
+}
+
+/// Stores the `index`-th element of a list in `*element`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `element` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_list_get(
+    term: *const Term,
+    index: usize,
+    element: *mut *const Term,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::List(x) => write_element(&x.elements, index, element),
+        Term::ImproperList(x) => write_element(&x.elements, index, element),
+        other => Err(wrong_type(other, "List or ImproperList")),
+    })())
+}
+
+/// Stores the tail of an improper list in `*tail`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `tail` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_improper_list_tail(
+    term: *const Term,
+    tail: *mut *const Term,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::ImproperList(x) => {
+            check_out(tail)?;
+            *tail = &*x.last;
+            Ok(())
+        }
+        other => Err(wrong_type(other, "ImproperList")),
+    })())
+}
+
+/// Stores the number of entries of a map in `*size`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `size` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_map_size(term: *const Term, size: *mut usize) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Map(x) => {
+            check_out(size)?;
+            *size = x.map.len();
+            Ok(())
+        }
+        other => Err(wrong_type(other, "Map")),
+    })())
+}
+
+/// Stores the key and the value of the `index`-th entry of a map in `*key` and `*value`.
+///
+/// The entries are in an unspecified (but, for the same map, fixed) order. Finding an
+/// entry takes time proportional to `index`.
+///
+/// # Safety
+///
+/// `term` must be a term obtained from this library, and `key` and `value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn eetf_map_get(
+    term: *const Term,
+    index: usize,
+    key: *mut *const Term,
+    value: *mut *const Term,
+) -> eetf_status {
+    status((|| match term_ref(term)? {
+        Term::Map(x) => {
+            check_out(key)?;
+            check_out(value)?;
+            let (k, v) = x
+                .map
+                .iter()
+                .nth(index)
+                .ok_or_else(|| out_of_range(index, x.map.len()))?;
+            *key = k;
+            *value = v;
+            Ok(())
+        }
+        other => Err(wrong_type(other, "Map")),
+    })())
+}
+
+unsafe fn write_element(
+    elements: &[Term],
+    index: usize,
+    element: *mut *const Term,
+) -> Result<(), eetf_status> {
+    check_out(element)?;
+    let e = elements
+        .get(index)
+        .ok_or_else(|| out_of_range(index, elements.len()))?;
+    *element = e;
+    Ok(())
+}
+
+fn out_of_range(index: usize, len: usize) -> eetf_status {
+    fail(
+        EETF_OUT_OF_RANGE,
+        format!("index {} is out of range for {} elements", index, len),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn last_error() -> String {
+        let message = eetf_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    fn decode(bytes: &[u8]) -> *mut Term {
+        let mut term = ptr::null_mut();
+        assert_eq!(
+            unsafe { eetf_decode(bytes.as_ptr(), bytes.len(), &mut term) },
+            EETF_OK
+        );
+        term
+    }
+
+    #[test]
+    fn decode_and_access_works() {
+        // {foo, [1, 2 | <<"b">>], #{3.5 => "ab"}}
+        let term = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("foo")),
+            Term::from(ImproperList::from((
+                vec![Term::from(1), Term::from(2)],
+                Term::from(Binary::from(&b"b"[..])),
+            ))),
+            Term::from(Map::from([(
+                Term::from(Float::try_from(3.5).unwrap()),
+                Term::from(ByteList::from("ab")),
+            )])),
+        ]));
+        let mut bytes = Vec::new();
+        term.encode(&mut bytes).unwrap();
+        let root = decode(&bytes);
+        unsafe {
+            assert_eq!(eetf_term_type(root), eetf_type::EETF_TYPE_TUPLE);
+            let mut size = 0;
+            assert_eq!(eetf_tuple_size(root, &mut size), EETF_OK);
+            assert_eq!(size, 3);
+
+            let mut atom = ptr::null();
+            assert_eq!(eetf_tuple_get(root, 0, &mut atom), EETF_OK);
+            let (mut name, mut len) = (ptr::null(), 0);
+            assert_eq!(eetf_atom_name(atom, &mut name, &mut len), EETF_OK);
+            assert_eq!(std::slice::from_raw_parts(name, len), b"foo");
+
+            let mut list = ptr::null();
+            assert_eq!(eetf_tuple_get(root, 1, &mut list), EETF_OK);
+            assert_eq!(eetf_term_type(list), eetf_type::EETF_TYPE_IMPROPER_LIST);
+            assert_eq!(eetf_list_length(list, &mut len), EETF_OK);
+            assert_eq!(len, 2);
+            let mut element = ptr::null();
+            assert_eq!(eetf_list_get(list, 1, &mut element), EETF_OK);
+            let mut n = 0;
+            assert_eq!(eetf_integer_value(element, &mut n), EETF_OK);
+            assert_eq!(n, 2);
+            let mut tail = ptr::null();
+            assert_eq!(eetf_improper_list_tail(list, &mut tail), EETF_OK);
+            let mut data = ptr::null();
+            assert_eq!(eetf_binary_data(tail, &mut data, &mut len), EETF_OK);
+            assert_eq!(std::slice::from_raw_parts(data, len), b"b");
+
+            let mut map = ptr::null();
+            assert_eq!(eetf_tuple_get(root, 2, &mut map), EETF_OK);
+            assert_eq!(eetf_map_size(map, &mut size), EETF_OK);
+            assert_eq!(size, 1);
+            let (mut key, mut value) = (ptr::null(), ptr::null());
+            assert_eq!(eetf_map_get(map, 0, &mut key, &mut value), EETF_OK);
+            let mut f = 0.0;
+            assert_eq!(eetf_float_value(key, &mut f), EETF_OK);
+            assert_eq!(f, 3.5);
+            assert_eq!(eetf_binary_data(value, &mut data, &mut len), EETF_OK);
+            assert_eq!(std::slice::from_raw_parts(data, len), b"ab");
+
+            // Re-encoding gives an owned buffer.
+            let (mut out, mut out_len) = (ptr::null_mut(), 0);
+            assert_eq!(eetf_encode(root, &mut out, &mut out_len), EETF_OK);
+            assert_eq!(std::slice::from_raw_parts(out, out_len), bytes.as_slice());
+            eetf_bytes_free(out, out_len);
+
+            eetf_term_free(root);
+        }
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let mut term = ptr::null_mut();
+        let truncated = [131, 100, 0, 3, 102];
+        let status = unsafe { eetf_decode(truncated.as_ptr(), truncated.len(), &mut term) };
+        assert_eq!(status, EETF_DECODE_ERROR);
+        assert!(term.is_null());
+        assert_eq!(last_error(), "I/O error");
+
+        let root = decode(&[131, 104, 1, 97, 1]); // {1}
+        unsafe {
+            let mut element = ptr::null();
+            assert_eq!(eetf_tuple_get(root, 1, &mut element), EETF_OUT_OF_RANGE);
+            assert_eq!(last_error(), "index 1 is out of range for 1 elements");
+            assert_eq!(eetf_tuple_get(root, 0, ptr::null_mut()), EETF_NULL_POINTER);
+
+            let (mut name, mut len) = (ptr::null(), 0);
+            assert_eq!(eetf_atom_name(root, &mut name, &mut len), EETF_WRONG_TYPE);
+            assert_eq!(last_error(), "expected Atom, found Tuple");
+            assert_eq!(eetf_term_type(ptr::null()), eetf_type::EETF_TYPE_INVALID);
+            assert_eq!(eetf_tuple_size(ptr::null(), &mut len), EETF_NULL_POINTER);
+
+            eetf_term_free(root);
+            eetf_term_free(ptr::null_mut());
+            eetf_bytes_free(ptr::null_mut(), 0);
+        }
+
+        let big = decode(&[131, 110, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]); // 1 bsl 64
+        let mut n = 0;
+        assert_eq!(
+            unsafe { eetf_integer_value(big, &mut n) },
+            EETF_OUT_OF_RANGE
+        );
+        unsafe { eetf_term_free(big) };
+    }
+}
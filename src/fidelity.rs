@@ -0,0 +1,509 @@
+//! Round trips which reproduce the wire format of decoded terms.
+//!
+//! A term does not record the tags it was decoded from, so encoding it again picks the tags of
+//! the [`EncoderOptions`] (e.g., `NEW_PID_EXT` for a pid received as `PID_EXT`). A proxy or a
+//! tool which rewrites a few fields of a term, and must leave the rest of its bytes alone, can
+//! instead decode it with [`Decoder::decode_with_layout`] and encode it with
+//! [`Encoder::encode_with_layout`], which reuses the recorded [`WireLayout`]: the tags, the
+//! order of the map keys and the text of the `FLOAT_EXT` floats.
+//!
+//! A subterm whose value no longer fits its recorded tag (e.g., `SMALL_INTEGER_EXT` for 256) is
+//! encoded with a tag which fits, and a subterm which no longer matches the layout (e.g., a map
+//! replaced by a list) ends the replay, so that the rest of the term is encoded as by
+//! [`Encoder::encode`]. Either way the callback of `encode_with_layout` is told a [`Fallback`].
+//! The recorded tags are written regardless of the `EncoderOptions`, which only apply to the
+//! subterms encoded anew.
+//!
+//! Not reproduced are atom cache references, which are encoded as plain atoms, the encoding of
+//! big integers with leading zero bytes, and the zlib stream of a compressed term, which is
+//! compressed again (and reported as [`Fallback::Recompressed`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::{Decoder, Encoder, Term};
+//!
+//! // A pid in the `PID_EXT` format, whose node is an `ATOM_EXT`.
+//! let bytes = [131, 103, 100, 0, 1, 97, 0, 0, 0, 1, 0, 0, 0, 0, 1];
+//! let (term, layout) = Decoder::new(&bytes[..]).decode_with_layout().unwrap();
+//!
+//! let mut encoded = Vec::new();
+//! term.encode(&mut encoded).unwrap();
+//! assert_ne!(encoded, bytes);
+//!
+//! let mut encoded = Vec::new();
+//! Encoder::new(&mut encoded)
+//!     .encode_with_layout(&term, &layout, |fallback| panic!("{:?}", fallback))
+//!     .unwrap();
+//! assert_eq!(encoded, bytes);
+//! ```
+use super::*;
+use codec_common::*;
+use num::bigint::BigInt;
+use num::ToPrimitive;
+use std::io;
+
+/// Wire format of a decoded term, recorded by [`Decoder::decode_with_layout`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WireLayout {
+    /// Tags of the subterms in pre-order, except for those decoded as [`Raw`] terms.
+    pub(crate) tags: Vec<u8>,
+    /// Keys of the maps in the order of the wire, in the pre-order of the maps.
+    pub(crate) map_keys: Vec<Vec<Term>>,
+    /// Text and decoded value of the `FLOAT_EXT` floats, in pre-order.
+    pub(crate) floats: Vec<([u8; 31], f64)>,
+    pub(crate) compressed: bool,
+}
+impl WireLayout {
+    /// Returns the number of recorded subterms.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Returns `true` if the term was a `COMPRESSED_TERM`.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+/// Departure from a [`WireLayout`] when encoding a term with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// The subterm no longer fits its recorded tag, so it is encoded with another one.
+    Tag { tag: u8, variant: &'static str },
+
+    /// The subterm no longer matches the layout (e.g., its variant or the keys of a map
+    /// changed), so it and the rest of the term are encoded with the encoder options.
+    ///
+    /// `tag` is `None` if the layout has no more subterms.
+    Mismatch {
+        tag: Option<u8>,
+        variant: &'static str,
+    },
+
+    /// The term was compressed, and is compressed again by the zlib backend of this crate.
+    Recompressed,
+}
+
+pub(crate) fn encode<W: io::Write>(
+    writer: &mut W,
+    term: &Term,
+    layout: &WireLayout,
+    options: EncoderOptions,
+    on_fallback: &mut dyn FnMut(Fallback),
+) -> EncodeResult {
+    let mut replay = Replay {
+        layout,
+        options,
+        on_fallback,
+        out: Vec::new(),
+        tags: 0,
+        maps: 0,
+        floats: 0,
+        aligned: true,
+    };
+    replay.term(term)?;
+    if layout.compressed {
+        (replay.on_fallback)(Fallback::Recompressed);
+        return codec::write_compressed(writer, &replay.out);
+    }
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&replay.out)?;
+    Ok(())
+}
+
+/// Encoder of a term which follows a layout from the start of the term.
+///
+/// The term is encoded into `out`, so that the size of a `NEW_FUN_EXT` can be filled in after
+/// its body.
+struct Replay<'a> {
+    layout: &'a WireLayout,
+    options: EncoderOptions,
+    on_fallback: &'a mut dyn FnMut(Fallback),
+    out: Vec<u8>,
+    tags: usize,
+    maps: usize,
+    floats: usize,
+    aligned: bool,
+}
+impl Replay<'_> {
+    fn term(&mut self, term: &Term) -> EncodeResult {
+        if let Term::Raw(x) = term {
+            self.out.extend_from_slice(&x.bytes);
+            return Ok(());
+        }
+        if !self.aligned {
+            return self.encode(term);
+        }
+        let Some(&tag) = self.layout.tags.get(self.tags) else {
+            return self.mismatch(None, term);
+        };
+        self.tags += 1;
+        match (term, tag) {
+            (
+                Term::Atom(x),
+                ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT | ATOM_CACHE_REF,
+            ) => self.atom(tag, x, term),
+            (
+                Term::FixInteger(x),
+                SMALL_INTEGER_EXT | INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT,
+            ) => self.integer(tag, &BigInt::from(x.value), term),
+            (
+                Term::BigInteger(x),
+                SMALL_INTEGER_EXT | INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT,
+            ) => self.integer(tag, &x.value, term),
+            (Term::Float(x), NEW_FLOAT_EXT) => {
+                self.out.push(NEW_FLOAT_EXT);
+                self.out.extend_from_slice(&x.value.to_be_bytes());
+                Ok(())
+            }
+            (Term::Float(x), FLOAT_EXT) => self.float_ext(x, term),
+            (Term::Pid(x), PID_EXT | NEW_PID_EXT) => self.pid(tag, x, term),
+            (Term::Port(x), PORT_EXT | NEW_PORT_EXT | V4_PORT_EXT) => self.port(tag, x, term),
+            (Term::Reference(x), REFERENCE_EXT | NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT) => {
+                self.reference(tag, x, term)
+            }
+            (Term::ExternalFun(x), EXPORT_EXT) => {
+                self.out.push(EXPORT_EXT);
+                self.atom_term(&x.module)?;
+                self.atom_term(&x.function)?;
+                self.term(&Term::from(i32::from(x.arity)))
+            }
+            (Term::InternalFun(x), FUN_EXT | NEW_FUN_EXT)
+                if (tag == FUN_EXT) == matches!(**x, InternalFun::Old { .. }) =>
+            {
+                self.fun(x)
+            }
+            (Term::Binary(_), BINARY_EXT) | (Term::BitBinary(_), BIT_BINARY_EXT) => {
+                let options = EncoderOptions {
+                    bit_binaries: true,
+                    ..self.options
+                };
+                Encoder::with_options(&mut self.out, options).encode_term(term)
+            }
+            (Term::List(x), NIL_EXT) if x.is_nil() => {
+                self.out.push(NIL_EXT);
+                Ok(())
+            }
+            (Term::List(x), LIST_EXT) => {
+                self.elements(LIST_EXT, &x.elements, EncodePathSegment::ListElement)?;
+                self.term(&Term::from(List::nil()))
+                    .map_err(|e| e.within(EncodePathSegment::ListTail))
+            }
+            (Term::ImproperList(x), LIST_EXT) => {
+                self.elements(LIST_EXT, &x.elements, EncodePathSegment::ListElement)?;
+                self.term(&x.last)
+                    .map_err(|e| e.within(EncodePathSegment::ListTail))
+            }
+            (Term::ByteList(x), STRING_EXT) => match u16::try_from(x.bytes.len()) {
+                Ok(len) => {
+                    self.out.push(STRING_EXT);
+                    self.out.extend_from_slice(&len.to_be_bytes());
+                    self.out.extend_from_slice(&x.bytes);
+                    Ok(())
+                }
+                Err(_) => self.unfit(tag, term),
+            },
+            (Term::Tuple(x), SMALL_TUPLE_EXT) if x.elements.len() > u8::MAX as usize => {
+                self.report_unfit(tag, term);
+                self.elements(
+                    LARGE_TUPLE_EXT,
+                    &x.elements,
+                    EncodePathSegment::TupleElement,
+                )
+            }
+            (Term::Tuple(x), SMALL_TUPLE_EXT | LARGE_TUPLE_EXT) => {
+                self.elements(tag, &x.elements, EncodePathSegment::TupleElement)
+            }
+            (Term::Map(x), MAP_EXT) => self.map(x, term),
+            _ => self.mismatch(Some(tag), term),
+        }
+    }
+
+    /// Encodes `term` as [`Encoder::encode`] does.
+    fn encode(&mut self, term: &Term) -> EncodeResult {
+        Encoder::with_options(&mut self.out, self.options).encode_term(term)
+    }
+
+    fn mismatch(&mut self, tag: Option<u8>, term: &Term) -> EncodeResult {
+        let variant = term.variant_name();
+        (self.on_fallback)(Fallback::Mismatch { tag, variant });
+        self.aligned = false;
+        self.encode(term)
+    }
+
+    fn report_unfit(&mut self, tag: u8, term: &Term) {
+        let variant = term.variant_name();
+        (self.on_fallback)(Fallback::Tag { tag, variant });
+    }
+
+    /// Encodes a term which has no subterms with the tag of the encoder options.
+    fn unfit(&mut self, tag: u8, term: &Term) -> EncodeResult {
+        self.report_unfit(tag, term);
+        self.encode(term)
+    }
+
+    /// Encodes a pid, a port or a reference with the tag of the encoder options, skipping the
+    /// recorded tag of its node.
+    fn unfit_identifier(&mut self, tag: u8, term: &Term) -> EncodeResult {
+        self.tags += 1;
+        self.unfit(tag, term)
+    }
+
+    fn atom_term(&mut self, x: &Atom) -> EncodeResult {
+        self.term(&Term::from(x.clone()))
+    }
+
+    fn atom(&mut self, tag: u8, x: &Atom, term: &Term) -> EncodeResult {
+        let latin1;
+        let bytes = match tag {
+            ATOM_EXT | SMALL_ATOM_EXT => match aux::to_latin1(&x.name) {
+                Some(bytes) => {
+                    latin1 = bytes;
+                    &latin1[..]
+                }
+                None => return self.unfit(tag, term),
+            },
+            ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => x.name.as_bytes(),
+            _ => return self.unfit(tag, term),
+        };
+        if matches!(tag, SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT) {
+            let Ok(len) = u8::try_from(bytes.len()) else {
+                return self.unfit(tag, term);
+            };
+            self.out.push(tag);
+            self.out.push(len);
+        } else {
+            let Ok(len) = u16::try_from(bytes.len()) else {
+                return self.unfit(tag, term);
+            };
+            self.out.push(tag);
+            self.out.extend_from_slice(&len.to_be_bytes());
+        }
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn integer(&mut self, tag: u8, value: &BigInt, term: &Term) -> EncodeResult {
+        match tag {
+            SMALL_INTEGER_EXT => match value.to_u8() {
+                Some(value) => self.out.extend_from_slice(&[tag, value]),
+                None => return self.unfit(tag, term),
+            },
+            INTEGER_EXT => match value.to_i32() {
+                Some(value) => {
+                    self.out.push(tag);
+                    self.out.extend_from_slice(&value.to_be_bytes());
+                }
+                None => return self.unfit(tag, term),
+            },
+            _ => {
+                let (sign, bytes) = value.to_bytes_le();
+                if tag == SMALL_BIG_EXT {
+                    let Ok(len) = u8::try_from(bytes.len()) else {
+                        return self.unfit(tag, term);
+                    };
+                    self.out.extend_from_slice(&[tag, len]);
+                } else {
+                    let Ok(len) = u32::try_from(bytes.len()) else {
+                        return self.unfit(tag, term);
+                    };
+                    self.out.push(tag);
+                    self.out.extend_from_slice(&len.to_be_bytes());
+                }
+                self.out.push(aux::sign_to_byte(sign));
+                self.out.extend_from_slice(&bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn float_ext(&mut self, x: &Float, term: &Term) -> EncodeResult {
+        let recorded = self.layout.floats.get(self.floats);
+        self.floats += 1;
+        let bytes = match recorded {
+            // The text is reused while it decodes to the value, which it may not do exactly
+            // (as `FLOAT_EXT` is decoded as an `f32`).
+            Some(&(bytes, value)) if value == x.value => bytes,
+            _ => match aux::float_ext_bytes(x.value) {
+                Some(bytes) => bytes,
+                None => return self.unfit(FLOAT_EXT, term),
+            },
+        };
+        self.out.push(FLOAT_EXT);
+        self.out.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn pid(&mut self, tag: u8, x: &Pid, term: &Term) -> EncodeResult {
+        let creation = if tag == PID_EXT {
+            match u8::try_from(x.creation) {
+                Ok(creation) => creation.to_be_bytes().to_vec(),
+                Err(_) => return self.unfit_identifier(tag, term),
+            }
+        } else {
+            x.creation.to_be_bytes().to_vec()
+        };
+        self.out.push(tag);
+        self.atom_term(&x.node)?;
+        self.out.extend_from_slice(&x.id.to_be_bytes());
+        self.out.extend_from_slice(&x.serial.to_be_bytes());
+        self.out.extend_from_slice(&creation);
+        Ok(())
+    }
+
+    fn port(&mut self, tag: u8, x: &Port, term: &Term) -> EncodeResult {
+        let id = match u32::try_from(x.id) {
+            _ if tag == V4_PORT_EXT => x.id.to_be_bytes().to_vec(),
+            Ok(id) => id.to_be_bytes().to_vec(),
+            Err(_) => return self.unfit_identifier(tag, term),
+        };
+        let creation = if tag == PORT_EXT {
+            match u8::try_from(x.creation) {
+                Ok(creation) => creation.to_be_bytes().to_vec(),
+                Err(_) => return self.unfit_identifier(tag, term),
+            }
+        } else {
+            x.creation.to_be_bytes().to_vec()
+        };
+        self.out.push(tag);
+        self.atom_term(&x.node)?;
+        self.out.extend_from_slice(&id);
+        self.out.extend_from_slice(&creation);
+        Ok(())
+    }
+
+    fn reference(&mut self, tag: u8, x: &Reference, term: &Term) -> EncodeResult {
+        let small_creation = u8::try_from(x.creation);
+        let (Ok(len), true) = (
+            u16::try_from(x.id.len()),
+            tag == NEWER_REFERENCE_EXT || small_creation.is_ok(),
+        ) else {
+            return self.unfit_identifier(tag, term);
+        };
+        if tag == REFERENCE_EXT {
+            let [id] = x.id[..] else {
+                return self.unfit_identifier(tag, term);
+            };
+            self.out.push(tag);
+            self.atom_term(&x.node)?;
+            self.out.extend_from_slice(&id.to_be_bytes());
+            self.out.push(x.creation as u8);
+            return Ok(());
+        }
+        self.out.push(tag);
+        self.out.extend_from_slice(&len.to_be_bytes());
+        self.atom_term(&x.node)?;
+        match small_creation {
+            Ok(creation) if tag == NEW_REFERENCE_EXT => self.out.push(creation),
+            _ => self.out.extend_from_slice(&x.creation.to_be_bytes()),
+        }
+        for n in &x.id {
+            self.out.extend_from_slice(&n.to_be_bytes());
+        }
+        Ok(())
+    }
+
+    fn fun(&mut self, x: &InternalFun) -> EncodeResult {
+        let free_vars = match *x {
+            InternalFun::Old {
+                ref module,
+                ref pid,
+                ref free_vars,
+                index,
+                uniq,
+            } => {
+                self.out.push(FUN_EXT);
+                self.out
+                    .extend_from_slice(&(free_vars.len() as u32).to_be_bytes());
+                self.term(&Term::from(pid.clone()))?;
+                self.atom_term(module)?;
+                self.term(&Term::from(index))?;
+                self.term(&Term::from(uniq))?;
+                free_vars
+            }
+            InternalFun::New {
+                ref module,
+                arity,
+                ref pid,
+                ref free_vars,
+                index,
+                ref uniq,
+                old_index,
+                old_uniq,
+            } => {
+                self.out.push(NEW_FUN_EXT);
+                let start = self.out.len();
+                // The size, which is filled in once the rest is encoded.
+                self.out.extend_from_slice(&[0; 4]);
+                self.out.push(arity);
+                self.out.extend_from_slice(uniq);
+                self.out.extend_from_slice(&index.to_be_bytes());
+                self.out
+                    .extend_from_slice(&(free_vars.len() as u32).to_be_bytes());
+                self.atom_term(module)?;
+                self.term(&Term::from(old_index))?;
+                self.term(&Term::from(old_uniq))?;
+                self.term(&Term::from(pid.clone()))?;
+                self.free_vars(free_vars)?;
+                let size = (self.out.len() - start) as u32;
+                self.out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+                return Ok(());
+            }
+        };
+        self.free_vars(free_vars)
+    }
+
+    fn free_vars(&mut self, free_vars: &[Term]) -> EncodeResult {
+        for (i, v) in free_vars.iter().enumerate() {
+            self.term(v)
+                .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
+        }
+        Ok(())
+    }
+
+    /// Encodes the header of a list or a tuple, and its elements.
+    fn elements(
+        &mut self,
+        tag: u8,
+        elements: &[Term],
+        segment: fn(usize) -> EncodePathSegment,
+    ) -> EncodeResult {
+        self.out.push(tag);
+        if tag == SMALL_TUPLE_EXT {
+            self.out.push(elements.len() as u8);
+        } else {
+            self.out
+                .extend_from_slice(&(elements.len() as u32).to_be_bytes());
+        }
+        for (i, e) in elements.iter().enumerate() {
+            self.term(e).map_err(|e| e.within(segment(i)))?;
+        }
+        Ok(())
+    }
+
+    fn map(&mut self, x: &Map, term: &Term) -> EncodeResult {
+        let keys = self.layout.map_keys.get(self.maps);
+        self.maps += 1;
+        let Some(keys) = keys.filter(|keys| {
+            keys.len() == x.map.len() && keys.iter().all(|k| x.map.get(k).is_some())
+        }) else {
+            return self.mismatch(Some(MAP_EXT), term);
+        };
+        self.out.push(MAP_EXT);
+        self.out
+            .extend_from_slice(&(keys.len() as u32).to_be_bytes());
+        for k in keys {
+            let v = x.map.get(k).expect("unreachable");
+            self.term(k)
+                .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+            self.term(v)
+                .map_err(|e| e.within(EncodePathSegment::MapValue { key: k.to_string() }))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+//! Reading and writing of term files (e.g., the files of `file:write_file(Path, term_to_binary(Term))`).
+use super::*;
+use crate::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Options of [`Term::to_file`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Writes the term as a `COMPRESSED_TERM` (default: `false`).
+    pub compressed: bool,
+
+    /// Flushes the file and its directory to the disk before returning (default: `true`).
+    pub fsync: bool,
+}
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            compressed: false,
+            fsync: true,
+        }
+    }
+}
+
+impl Term {
+    /// Reads the term which occupies the whole of the file at `path`.
+    ///
+    /// Compressed terms are decompressed. An empty file is rejected with
+    /// [`DecodeError::EmptyFile`], and a truncated one with [`DecodeError::UnexpectedEof`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use eetf::{Term, WriteOptions};
+    ///
+    /// let term = Term::from_file("state.etf")?;
+    /// term.to_file("state.etf", WriteOptions::default())?;
+    /// # Ok::<(), eetf::Error>(())
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Term, Error> {
+        let path = path.as_ref();
+        decode_file_bytes(path, &fs::read(path)?)
+    }
+
+    /// Writes the term to the file at `path`, replacing the file if it exists.
+    ///
+    /// The term is written to a temporary file in the same directory which is then renamed to
+    /// `path`, so readers (and a crash) see either the old or the new file but never a part of one.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, options: WriteOptions) -> Result<(), Error> {
+        let bytes = encode_file_bytes(self, options)?;
+        write_atomically(path.as_ref(), &bytes, options.fsync, || Ok(()))?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`Term::from_file`].
+    #[cfg(feature = "tokio-async")]
+    pub async fn from_file_async<P: AsRef<Path>>(path: P) -> Result<Term, Error> {
+        let path = path.as_ref();
+        decode_file_bytes(path, &tokio::fs::read(path).await?)
+    }
+
+    /// Asynchronous version of [`Term::to_file`].
+    #[cfg(feature = "tokio-async")]
+    pub async fn to_file_async<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: WriteOptions,
+    ) -> Result<(), Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let bytes = encode_file_bytes(self, options)?;
+        let path = path.as_ref();
+        let temp = temp_path(path)?;
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp).await?;
+            file.write_all(&bytes).await?;
+            if options.fsync {
+                file.sync_all().await?;
+            }
+            drop(file);
+            tokio::fs::rename(&temp, path).await
+        }
+        .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp).await;
+            result?;
+        }
+        #[cfg(unix)]
+        if options.fsync {
+            tokio::fs::File::open(parent_dir(path))
+                .await?
+                .sync_all()
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the term which occupies the whole of the file at `path` (see [`Term::from_file`]).
+///
+/// # Examples
+///
+/// ```no_run
+/// let term = eetf::read_term_file("state.etf")?;
+/// println!("{}", term);
+/// # Ok::<(), eetf::Error>(())
+/// ```
+pub fn read_term_file<P: AsRef<Path>>(path: P) -> Result<Term, Error> {
+    Term::from_file(path)
+}
+
+/// Writes `term` to the file at `path`, replacing the file if it exists (see [`Term::to_file`]).
+pub fn write_term_file<P: AsRef<Path>>(path: P, term: &Term) -> Result<(), Error> {
+    term.to_file(path, WriteOptions::default())
+}
+
+fn decode_file_bytes(path: &Path, bytes: &[u8]) -> Result<Term, Error> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EmptyFile {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+    Ok(decode_from_slice(bytes)?)
+}
+
+fn encode_file_bytes(term: &Term, options: WriteOptions) -> Result<Vec<u8>, EncodeError> {
+    let mut bytes = Vec::new();
+    if options.compressed {
+        term.encode_compressed(&mut bytes)?;
+    } else {
+        term.encode(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it to `path`.
+///
+/// `before_rename` is called once the temporary file is complete (tests use it to interrupt
+/// the write).
+fn write_atomically(
+    path: &Path,
+    bytes: &[u8],
+    fsync: bool,
+    before_rename: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    let temp = temp_path(path)?;
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        if fsync {
+            file.sync_all()?;
+        }
+        drop(file);
+        before_rename()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+    if fsync {
+        sync_dir(path)?;
+    }
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let mut temp = std::ffi::OsString::from(".");
+    temp.push(name);
+    temp.push(format!(".tmp{}", std::process::id()));
+    Ok(path.with_file_name(temp))
+}
+
+/// Flushes the directory entry of `path` (which is not possible on Windows).
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    File::open(parent_dir(path))?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("eetf-file-{}-{}.etf", name, std::process::id()))
+    }
+
+    fn state() -> Term {
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("state")),
+            Term::from(Binary::from(vec![1, 2, 3])),
+        ]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_file("round-trip");
+        let term = state();
+        write_term_file(&path, &term).unwrap();
+        assert_eq!(read_term_file(&path).unwrap(), term);
+
+        std::fs::write(&path, [131, 1]).unwrap();
+        assert!(matches!(
+            read_term_file(&path),
+            Err(Error::Decode(DecodeError::UnknownTag { tag: 1 }))
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(read_term_file(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let path = temp_file("compressed");
+        let term = Term::from(List::from(vec![state(); 100]));
+        let options = WriteOptions {
+            compressed: true,
+            fsync: false,
+        };
+        term.to_file(&path, options).unwrap();
+        assert_eq!(fs::read(&path).unwrap()[..2], [131, 80]);
+        assert_eq!(Term::from_file(&path).unwrap(), term);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_files_are_distinct_from_truncated_ones() {
+        let path = temp_file("empty");
+        fs::write(&path, []).unwrap();
+        assert!(matches!(
+            Term::from_file(&path),
+            Err(Error::Decode(DecodeError::EmptyFile { path: ref p })) if *p == path
+        ));
+
+        let mut bytes = encode_to_vec(&state()).unwrap();
+        bytes.pop();
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            Term::from_file(&path),
+            Err(Error::Decode(DecodeError::UnexpectedEof))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_writes_keep_the_old_file() {
+        let path = temp_file("interrupted");
+        state().to_file(&path, WriteOptions::default()).unwrap();
+
+        let bytes = encode_to_vec(&Term::from(Atom::from("new"))).unwrap();
+        let crash = || Err(io::Error::other("crash"));
+        assert!(write_atomically(&path, &bytes, true, crash).is_err());
+        assert_eq!(Term::from_file(&path).unwrap(), state());
+        assert!(!temp_path(&path).unwrap().exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_round_trip() {
+        let path = temp_file("async");
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                state()
+                    .to_file_async(&path, WriteOptions::default())
+                    .await
+                    .unwrap();
+                assert_eq!(Term::from_file_async(&path).await.unwrap(), state());
+            });
+        fs::remove_file(&path).unwrap();
+    }
+}
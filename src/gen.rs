@@ -0,0 +1,227 @@
+//! `gen_server` call and cast messages.
+//!
+//! A call is `{'$gen_call', {From, Tag}, Request}` and is answered by `{Tag, Reply}`.
+//! `Tag` is either the monitor reference of the call or, since OTP 24, the improper
+//! list `[alias | Ref]` where `Ref` doubles as a process alias.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::gen::{parse_reply, GenCall};
+//! use eetf::{Atom, Pid, Reference, Term, Tuple};
+//!
+//! let from = Pid::new("a@localhost", 85, 0, 1);
+//! let reference = Reference::from(("a@localhost", vec![1, 2, 3]));
+//! let call = GenCall::new(from, reference.clone(), Term::from(Atom::from("ping"))).alias(true);
+//! let request = call.to_term();
+//!
+//! // The server replies with `{Tag, Reply}`.
+//! let reply = call.reply(Term::from(Atom::from("pong")));
+//! assert_eq!(parse_reply(&reply, &reference), Some(&Term::from(Atom::from("pong"))));
+//! # assert_eq!(GenCall::try_from_term(&request), Some(call));
+//! ```
+use super::*;
+
+const GEN_CALL: &str = "$gen_call";
+const GEN_CAST: &str = "$gen_cast";
+const ALIAS: &str = "alias";
+
+/// `{'$gen_call', {From, Tag}, Request}` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenCall {
+    pub from: Pid,
+    pub reference: Reference,
+
+    /// Whether the tag is `[alias | Ref]` rather than `Ref`.
+    pub alias: bool,
+
+    pub request: Term,
+}
+impl GenCall {
+    /// Makes a new call tagged with `reference`.
+    pub fn new(from: Pid, reference: Reference, request: Term) -> Self {
+        GenCall {
+            from,
+            reference,
+            alias: false,
+            request,
+        }
+    }
+
+    /// Sets whether the call is tagged with `[alias | Ref]` (as `gen:call/4` does since OTP 24).
+    pub fn alias(mut self, alias: bool) -> Self {
+        self.alias = alias;
+        self
+    }
+
+    /// Returns the `Tag` of the call.
+    pub fn tag(&self) -> Term {
+        let reference = Term::from(self.reference.clone());
+        if self.alias {
+            Term::from(ImproperList::from((
+                vec![Term::from(Atom::from(ALIAS))],
+                reference,
+            )))
+        } else {
+            reference
+        }
+    }
+
+    /// Converts the call to a `{'$gen_call', {From, Tag}, Request}` term.
+    pub fn to_term(&self) -> Term {
+        let from = Term::from(Tuple::from(vec![Term::from(self.from.clone()), self.tag()]));
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from(GEN_CALL)),
+            from,
+            self.request.clone(),
+        ]))
+    }
+
+    /// Makes the `{Tag, Reply}` message answering the call.
+    pub fn reply(&self, reply: Term) -> Term {
+        Term::from(Tuple::from(vec![self.tag(), reply]))
+    }
+
+    /// Parses a `{'$gen_call', {From, Tag}, Request}` term.
+    pub fn try_from_term(term: &Term) -> Option<Self> {
+        let elements = tagged_elements(term, GEN_CALL, 3)?;
+        let from = match &elements[1] {
+            Term::Tuple(x) if x.elements.len() == 2 => &x.elements,
+            _ => return None,
+        };
+        let pid = match &from[0] {
+            Term::Pid(x) => x.clone(),
+            _ => return None,
+        };
+        let (reference, alias) = parse_tag(&from[1])?;
+        Some(GenCall {
+            from: pid,
+            reference: reference.clone(),
+            alias,
+            request: elements[2].clone(),
+        })
+    }
+}
+
+/// `{'$gen_cast', Request}` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenCast {
+    pub request: Term,
+}
+impl GenCast {
+    /// Makes a new cast.
+    pub fn new(request: Term) -> Self {
+        GenCast { request }
+    }
+
+    /// Converts the cast to a `{'$gen_cast', Request}` term.
+    pub fn to_term(&self) -> Term {
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from(GEN_CAST)),
+            self.request.clone(),
+        ]))
+    }
+
+    /// Parses a `{'$gen_cast', Request}` term.
+    pub fn try_from_term(term: &Term) -> Option<Self> {
+        let elements = tagged_elements(term, GEN_CAST, 2)?;
+        Some(GenCast::new(elements[1].clone()))
+    }
+}
+
+/// Returns the `Reply` of a `{Tag, Reply}` message if `Tag` is `reference` or `[alias | reference]`.
+pub fn parse_reply<'a>(term: &'a Term, reference: &Reference) -> Option<&'a Term> {
+    match term {
+        Term::Tuple(x) if x.elements.len() == 2 => {
+            let (tag, _) = parse_tag(&x.elements[0])?;
+            if tag == reference {
+                Some(&x.elements[1])
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Parses `Ref` or `[alias | Ref]`, returning the reference and whether it is an alias.
+fn parse_tag(term: &Term) -> Option<(&Reference, bool)> {
+    match term {
+        Term::Reference(x) => Some((x, false)),
+        Term::ImproperList(x) => match (x.elements.as_slice(), &*x.last) {
+            ([Term::Atom(a)], Term::Reference(r)) if a.as_str() == ALIAS => Some((r, true)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn tagged_elements<'a>(term: &'a Term, tag: &str, arity: usize) -> Option<&'a [Term]> {
+    match term {
+        Term::Tuple(x) if x.elements.len() == arity => match &x.elements[0] {
+            Term::Atom(a) if a.as_str() == tag => Some(&x.elements),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn reference(id: u32) -> Reference {
+        Reference::from(("a@localhost", vec![id, 2, 3]))
+    }
+
+    #[test]
+    fn call_round_trip_works() {
+        let from = Pid::new("a@localhost", 85, 0, 1);
+        let request = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("get")),
+            Term::from(1),
+        ]));
+        for alias in [false, true] {
+            let call = GenCall::new(from.clone(), reference(1), request.clone()).alias(alias);
+            let mut buf = Vec::new();
+            call.to_term().encode(&mut buf).unwrap();
+            let term = Term::decode(Cursor::new(&buf)).unwrap();
+            assert_eq!(GenCall::try_from_term(&term), Some(call.clone()));
+            assert_eq!(GenCast::try_from_term(&term), None);
+
+            let reply = call.reply(Term::from(42));
+            assert_eq!(parse_reply(&reply, &reference(1)), Some(&Term::from(42)));
+        }
+
+        let alias_tag = GenCall::new(from, reference(1), request).alias(true).tag();
+        assert_eq!(alias_tag.to_string(), "['alias'|#Ref<'a@localhost'.1.2.3>]");
+    }
+
+    #[test]
+    fn cast_round_trip_works() {
+        let cast = GenCast::new(Term::from(Atom::from("stop")));
+        assert_eq!(cast.to_term().to_string(), "{'$gen_cast','stop'}");
+        assert_eq!(GenCast::try_from_term(&cast.to_term()), Some(cast));
+    }
+
+    #[test]
+    fn unrelated_replies_are_rejected() {
+        let tag = |r| Term::from(r);
+        let reply = |tag| Term::from(Tuple::from(vec![tag, Term::from(Atom::from("ok"))]));
+        assert_eq!(parse_reply(&reply(tag(reference(2))), &reference(1)), None);
+        let wrong_alias = Term::from(ImproperList::from((
+            vec![Term::from(Atom::from("monitor"))],
+            tag(reference(1)),
+        )));
+        assert_eq!(parse_reply(&reply(wrong_alias), &reference(1)), None);
+        assert_eq!(
+            parse_reply(&Term::from(Atom::from("ok")), &reference(1)),
+            None
+        );
+        assert_eq!(
+            GenCall::try_from_term(&GenCast::new(Term::from(1)).to_term()),
+            None
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! `tracing` spans of the codecs.
+//!
+//! Every decoded or encoded term gets a `DEBUG` span (`eetf::decode` or `eetf::encode`) with the
+//! fields `bytes`, `variant` (of the top-level term), `compressed` and `elapsed_us`, and a
+//! `DEBUG` event is emitted within the span if the term fails to decode or encode. Nothing is
+//! computed unless the span is enabled.
+use super::*;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Returns the span of a decoded term, or `None` if it is disabled.
+pub(crate) fn decode_span() -> Option<Span> {
+    let span = tracing::debug_span!(
+        "eetf::decode",
+        bytes = Empty,
+        variant = Empty,
+        compressed = false,
+        elapsed_us = Empty
+    );
+    (!span.is_disabled()).then_some(span)
+}
+
+/// Returns the span of an encoded term, or `None` if it is disabled.
+pub(crate) fn encode_span(term: &Term, compressed: bool) -> Option<Span> {
+    let span = tracing::debug_span!(
+        "eetf::encode",
+        bytes = Empty,
+        variant = Empty,
+        compressed,
+        elapsed_us = Empty
+    );
+    if span.is_disabled() {
+        return None;
+    }
+    span.record("variant", variant(term));
+    Some(span)
+}
+
+/// Marks the current span (which is the span of the decoded term if it is enabled) as compressed.
+pub(crate) fn record_compressed() {
+    Span::current().record("compressed", true);
+}
+
+/// Records the outcome of decoding `bytes` bytes since `start`.
+pub(crate) fn record_decode(span: &Span, result: &DecodeResult, bytes: u64, start: Instant) {
+    span.record("bytes", bytes);
+    span.record("elapsed_us", elapsed_us(start));
+    match result {
+        Ok(term) => {
+            span.record("variant", variant(term));
+        }
+        Err(error) => span.in_scope(|| {
+            tracing::debug!(offset = bytes, %error, "failed to decode a term");
+        }),
+    }
+}
+
+/// Records the outcome of encoding `bytes` bytes since `start`.
+pub(crate) fn record_encode(span: &Span, result: &EncodeResult, bytes: u64, start: Instant) {
+    span.record("bytes", bytes);
+    span.record("elapsed_us", elapsed_us(start));
+    if let Err(error) = result {
+        span.in_scope(|| match error.path() {
+            Some(path) => tracing::debug!(offset = bytes, %path, %error, "failed to encode a term"),
+            None => tracing::debug!(offset = bytes, %error, "failed to encode a term"),
+        });
+    }
+}
+
+fn elapsed_us(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
+
+fn variant(term: &Term) -> &'static str {
+    match term {
+        Term::Atom(_) => "Atom",
+        Term::FixInteger(_) => "FixInteger",
+        Term::BigInteger(_) => "BigInteger",
+        Term::Float(_) => "Float",
+        Term::Pid(_) => "Pid",
+        Term::Port(_) => "Port",
+        Term::Reference(_) => "Reference",
+        Term::ExternalFun(_) => "ExternalFun",
+        Term::InternalFun(_) => "InternalFun",
+        Term::Binary(_) => "Binary",
+        Term::BitBinary(_) => "BitBinary",
+        Term::ByteList(_) => "ByteList",
+        Term::List(_) => "List",
+        Term::ImproperList(_) => "ImproperList",
+        Term::Tuple(_) => "Tuple",
+        Term::Map(_) => "Map",
+        Term::Raw(_) => "Raw",
+    }
+}
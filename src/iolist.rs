@@ -0,0 +1,170 @@
+//! Flattening of iolists (see [`Term::iolist_to_bytes`]).
+use super::*;
+
+/// A step in the path from an iolist to the element which is not allowed in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IolistPathSegment {
+    ListElement(usize),
+    ListTail,
+}
+impl fmt::Display for IolistPathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IolistPathSegment::ListElement(i) => write!(f, "element {} of list", i),
+            IolistPathSegment::ListTail => write!(f, "tail of list"),
+        }
+    }
+}
+
+/// Error of flattening a term which is not an iolist, as `erlang:iolist_to_binary/1` fails
+/// with `badarg`.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{found} is not allowed in an iolist{}", describe_path(.path))]
+pub struct IolistError {
+    pub found: TermSummary,
+
+    /// The path to the element, innermost step first (empty if the term itself is neither a
+    /// list nor a binary).
+    pub path: Vec<IolistPathSegment>,
+}
+
+fn describe_path(path: &[IolistPathSegment]) -> String {
+    let mut text = String::new();
+    for (i, segment) in path.iter().enumerate() {
+        let separator = if i == 0 { " at " } else { ", in " };
+        text.push_str(separator);
+        text.push_str(&segment.to_string());
+    }
+    text
+}
+
+impl Term {
+    /// Returns the bytes of an iolist, as `erlang:iolist_to_binary/1`.
+    ///
+    /// An iolist is a binary, or a list (proper or not) whose elements are integers in
+    /// `0..=255`, binaries and iolists, and whose tail is a binary or nil. A
+    /// [`BitBinary`] is allowed if it is made of whole bytes, but a [`Term::Raw`] is not.
+    ///
+    /// The nested lists are flattened without recursion, so iolists of any depth can be
+    /// flattened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eetf::{Binary, ImproperList, IolistPathSegment, List, Term};
+    ///
+    /// // [$h, [<<"el">>, "l"] | <<"o">>]
+    /// let nested = List::from(vec![
+    ///     Term::from(Binary::from(&b"el"[..])),
+    ///     Term::from(List::from(vec![Term::from(108)])),
+    /// ]);
+    /// let iolist = Term::from(ImproperList::from((
+    ///     vec![Term::from(104), Term::from(nested)],
+    ///     Term::from(Binary::from(&b"o"[..])),
+    /// )));
+    /// assert_eq!(iolist.iolist_to_bytes().unwrap(), b"hello");
+    ///
+    /// let error = Term::from(List::from(vec![Term::from(256)])).iolist_to_bytes().unwrap_err();
+    /// assert_eq!(error.path, [IolistPathSegment::ListElement(0)]);
+    /// assert_eq!(error.to_string(), "256 is not allowed in an iolist at element 0 of list");
+    /// ```
+    pub fn iolist_to_bytes(&self) -> Result<Vec<u8>, IolistError> {
+        let mut bytes = Vec::new();
+        flatten(self, |chunk| bytes.extend_from_slice(chunk))?;
+        Ok(bytes)
+    }
+
+    /// Returns `true` if the term is an iolist (see [`Term::iolist_to_bytes`]).
+    pub fn is_iolist(&self) -> bool {
+        flatten(self, |_| ()).is_ok()
+    }
+}
+
+/// List being flattened.
+struct Level<'a> {
+    elements: &'a [Term],
+    next: usize,
+    tail: Option<&'a Term>,
+    /// The step from the enclosing list (`None` for the outermost list).
+    segment: Option<IolistPathSegment>,
+}
+impl<'a> Level<'a> {
+    /// Returns the level of `term`, if it is a list which is not a byte list.
+    fn of(term: &'a Term, segment: Option<IolistPathSegment>) -> Option<Self> {
+        let (elements, tail) = match term {
+            Term::List(list) => (&list.elements[..], None),
+            Term::ImproperList(list) => (&list.elements[..], Some(&*list.last)),
+            _ => return None,
+        };
+        Some(Level {
+            elements,
+            next: 0,
+            tail,
+            segment,
+        })
+    }
+}
+
+/// Gives the bytes of the iolist `term` to `sink`, chunk by chunk.
+fn flatten(term: &Term, mut sink: impl FnMut(&[u8])) -> Result<(), IolistError> {
+    let mut stack = Vec::new();
+    if let Some(bytes) = binary_bytes(term) {
+        sink(bytes);
+    } else if let Term::ByteList(list) = term {
+        sink(&list.bytes);
+    } else {
+        stack.push(Level::of(term, None).ok_or_else(|| not_allowed(term, &[], None))?);
+    }
+    while let Some(level) = stack.last_mut() {
+        let (element, segment) = if level.next < level.elements.len() {
+            level.next += 1;
+            let i = level.next - 1;
+            (&level.elements[i], IolistPathSegment::ListElement(i))
+        } else if let Some(tail) = level.tail.take() {
+            // A tail may be nil or a binary, or (as `[A | [B]]` is `[A, B]`) another list.
+            (tail, IolistPathSegment::ListTail)
+        } else {
+            stack.pop();
+            continue;
+        };
+        if let Some(bytes) = binary_bytes(element) {
+            sink(bytes);
+            continue;
+        }
+        match element {
+            Term::FixInteger(FixInteger { value }) if segment != IolistPathSegment::ListTail => {
+                match u8::try_from(*value) {
+                    Ok(byte) => sink(&[byte]),
+                    Err(_) => return Err(not_allowed(element, &stack, Some(segment))),
+                }
+            }
+            Term::ByteList(list) => sink(&list.bytes),
+            _ => match Level::of(element, Some(segment)) {
+                Some(level) => stack.push(level),
+                None => return Err(not_allowed(element, &stack, Some(segment))),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Returns the bytes of a binary, or of a bitstring of whole bytes.
+fn binary_bytes(term: &Term) -> Option<&[u8]> {
+    match term {
+        Term::Binary(x) => Some(&x.bytes),
+        Term::BitBinary(x) if x.tail_bits_size == 8 => Some(&x.bytes),
+        _ => None,
+    }
+}
+
+fn not_allowed(term: &Term, stack: &[Level], segment: Option<IolistPathSegment>) -> IolistError {
+    IolistError {
+        found: TermSummary::new(term),
+        path: stack
+            .iter()
+            .filter_map(|level| level.segment)
+            .chain(segment)
+            .rev()
+            .collect(),
+    }
+}
@@ -0,0 +1,518 @@
+//! Conversion between terms and `serde_json::Value`s.
+//!
+//! | Term                   | JSON                                                   |
+//! |------------------------|--------------------------------------------------------|
+//! | `true` / `false` atoms | `true` / `false`                                       |
+//! | the null atom (`nil`)  | `null`                                                 |
+//! | other atoms            | strings (prefixed with the atom sigil, if any)         |
+//! | `FixInteger`           | numbers                                                |
+//! | `BigInteger`           | see [`BigIntegerRepr`]                                 |
+//! | `Float`                | numbers                                                |
+//! | `Binary`               | see [`BinaryRepr`]                                     |
+//! | `Tuple`                | see [`TupleRepr`]                                      |
+//! | `List`, `ByteList`     | arrays                                                 |
+//! | `Map`                  | objects, or `{"map": [[Key, Value], ...]}` (see below) |
+//! | other terms            | strings formatted by `Display` (lossy)                 |
+//!
+//! A map becomes an object if all of its keys are atoms or binaries that are
+//! written as strings, and if the object could not be mistaken for one of the
+//! marker objects (`{"tuple": ...}`, `{"base64": ...}`, `{"bigint": ...}`, and
+//! `{"map": ...}`); otherwise it is written as a list of key/value pairs.
+//!
+//! With [`JsonOptions::reversible`], `Term::from_json(&term.to_json(&opts), &opts)`
+//! reconstructs the original term for every term made of atoms, integers
+//! (`BigInteger`s outside of the `FixInteger` range), floats, binaries,
+//! tuples, (non-byte) lists and maps.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::json::JsonOptions;
+//! use eetf::{Atom, Term, Tuple};
+//!
+//! let term = Term::from(Tuple::from(vec![
+//!     Term::from(Atom::from("ok")),
+//!     Term::from(1),
+//! ]));
+//!
+//! let options = JsonOptions::reversible();
+//! let json = term.to_json(&options);
+//! assert_eq!(json.to_string(), r#"{"tuple":[":ok",1]}"#);
+//! assert_eq!(Term::from_json(&json, &options).unwrap(), term);
+//!
+//! assert_eq!(term.to_json(&JsonOptions::new()).to_string(), r#"["ok",1]"#);
+//! ```
+use super::*;
+use base64::Engine;
+use num::traits::ToPrimitive;
+use serde_json::{Number, Value};
+
+const TUPLE_MARKER: &str = "tuple";
+const BASE64_MARKER: &str = "base64";
+const BIGINT_MARKER: &str = "bigint";
+const MAP_MARKER: &str = "map";
+const MARKERS: [&str; 4] = [TUPLE_MARKER, BASE64_MARKER, BIGINT_MARKER, MAP_MARKER];
+
+/// Errors which can occur when converting a JSON value into a term.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum JsonError {
+    #[error("invalid {marker:?} marker object: {value}")]
+    InvalidMarker { marker: &'static str, value: String },
+
+    #[error("invalid base64 string: {0:?}")]
+    InvalidBase64(String),
+
+    #[error("invalid big integer string: {0:?}")]
+    InvalidBigInteger(String),
+}
+
+/// How binaries are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryRepr {
+    /// A string if the binary is UTF-8, `{"base64": "..."}` otherwise.
+    #[default]
+    Text,
+
+    /// Always a plain base64 string (lossy).
+    Base64,
+}
+
+/// How big integers are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BigIntegerRepr {
+    /// A number; integers which do not fit in 64 bits become floats (lossy).
+    #[default]
+    Number,
+
+    /// A decimal string (lossy).
+    String,
+
+    /// A decimal string wrapped in `{"bigint": "..."}`.
+    Tagged,
+}
+
+/// How tuples are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TupleRepr {
+    /// An array (lossy; arrays are converted into lists).
+    #[default]
+    Array,
+
+    /// An array wrapped in `{"tuple": [...]}`.
+    Tagged,
+}
+
+/// Options for converting between terms and JSON values.
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    atom_sigil: Option<String>,
+    null_atom: String,
+    binary_repr: BinaryRepr,
+    big_integer_repr: BigIntegerRepr,
+    tuple_repr: TupleRepr,
+}
+impl JsonOptions {
+    /// Makes the default (human-readable and lossy) options.
+    pub fn new() -> Self {
+        JsonOptions {
+            atom_sigil: None,
+            null_atom: "nil".to_string(),
+            binary_repr: BinaryRepr::default(),
+            big_integer_repr: BigIntegerRepr::default(),
+            tuple_repr: TupleRepr::default(),
+        }
+    }
+
+    /// Makes options with which the conversion can be reversed.
+    ///
+    /// Atoms are prefixed with `:`, and big integers and tuples are tagged.
+    pub fn reversible() -> Self {
+        Self::new()
+            .atom_sigil(Some(":"))
+            .big_integer_repr(BigIntegerRepr::Tagged)
+            .tuple_repr(TupleRepr::Tagged)
+    }
+
+    /// Sets the prefix of the strings made from atoms (default: `None`).
+    ///
+    /// Without a sigil, atoms and binaries can not be told apart and strings are converted into binaries.
+    pub fn atom_sigil(mut self, sigil: Option<&str>) -> Self {
+        self.atom_sigil = sigil.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Sets the atom which corresponds to `null` (default: `nil`).
+    pub fn null_atom(mut self, name: &str) -> Self {
+        self.null_atom = name.to_string();
+        self
+    }
+
+    /// Sets how binaries are represented (default: [`BinaryRepr::Text`]).
+    pub fn binary_repr(mut self, repr: BinaryRepr) -> Self {
+        self.binary_repr = repr;
+        self
+    }
+
+    /// Sets how big integers are represented (default: [`BigIntegerRepr::Number`]).
+    pub fn big_integer_repr(mut self, repr: BigIntegerRepr) -> Self {
+        self.big_integer_repr = repr;
+        self
+    }
+
+    /// Sets how tuples are represented (default: [`TupleRepr::Array`]).
+    pub fn tuple_repr(mut self, repr: TupleRepr) -> Self {
+        self.tuple_repr = repr;
+        self
+    }
+
+    fn atom_to_string(&self, atom: &Atom) -> String {
+        match self.atom_sigil {
+            None => atom.name.to_string(),
+            Some(ref sigil) => format!("{}{}", sigil, atom.name),
+        }
+    }
+
+    /// Returns the string of a UTF-8 binary, unless it could be mistaken for an atom.
+    fn binary_as_str<'a>(&self, binary: &'a Binary) -> Option<&'a str> {
+        let s = std::str::from_utf8(&binary.bytes).ok()?;
+        match self.atom_sigil {
+            Some(ref sigil) if s.starts_with(sigil.as_str()) => None,
+            _ => Some(s),
+        }
+    }
+
+    fn str_to_term(&self, s: &str) -> Term {
+        match self.atom_sigil {
+            Some(ref sigil) if s.starts_with(sigil.as_str()) => {
+                Term::from(Atom::from(&s[sigil.len()..]))
+            }
+            _ => Term::from(Binary::from(s.as_bytes())),
+        }
+    }
+
+    /// Returns the object key for `key`, if the key can be represented by a plain string.
+    fn key_to_string(&self, key: &Term) -> Option<String> {
+        match key {
+            Term::Atom(x) => Some(self.atom_to_string(x)),
+            Term::Binary(x) if self.binary_repr == BinaryRepr::Text => {
+                self.binary_as_str(x).map(ToOwned::to_owned)
+            }
+            _ => None,
+        }
+    }
+}
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn marker(name: &str, value: Value) -> Value {
+    Value::Object([(name.to_string(), value)].into_iter().collect())
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+impl Term {
+    /// Converts the term into a JSON value.
+    pub fn to_json(&self, options: &JsonOptions) -> Value {
+        match self {
+            Term::Atom(x) if x.as_str() == "true" => Value::Bool(true),
+            Term::Atom(x) if x.as_str() == "false" => Value::Bool(false),
+            Term::Atom(x) if x.as_str() == options.null_atom => Value::Null,
+            Term::Atom(x) => Value::String(options.atom_to_string(x)),
+            Term::FixInteger(x) => Value::from(x.value),
+            Term::BigInteger(x) => big_integer_to_json(x, options),
+            Term::Float(x) => Number::from_f64(x.value)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            Term::Binary(x) => match options.binary_repr {
+                BinaryRepr::Text => match options.binary_as_str(x) {
+                    Some(s) => Value::String(s.to_string()),
+                    None => marker(BASE64_MARKER, Value::String(base64_encode(&x.bytes))),
+                },
+                BinaryRepr::Base64 => Value::String(base64_encode(&x.bytes)),
+            },
+            Term::ByteList(x) => Value::Array(x.bytes.iter().map(|&b| Value::from(b)).collect()),
+            Term::List(x) => Value::Array(x.elements.iter().map(|e| e.to_json(options)).collect()),
+            Term::Tuple(x) => {
+                let elements =
+                    Value::Array(x.elements.iter().map(|e| e.to_json(options)).collect());
+                match options.tuple_repr {
+                    TupleRepr::Array => elements,
+                    TupleRepr::Tagged => marker(TUPLE_MARKER, elements),
+                }
+            }
+            Term::Map(x) => map_to_json(x, options),
+            _ => Value::String(self.to_string()),
+        }
+    }
+
+    /// Converts a JSON value into a term.
+    pub fn from_json(value: &Value, options: &JsonOptions) -> Result<Self, JsonError> {
+        Ok(match value {
+            Value::Null => Term::from(Atom::from(options.null_atom.as_str())),
+            Value::Bool(b) => Term::from(*b),
+            Value::Number(n) => number_to_term(n),
+            Value::String(s) => options.str_to_term(s),
+            Value::Array(elements) => Term::from(List::from(
+                elements
+                    .iter()
+                    .map(|e| Term::from_json(e, options))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Value::Object(object) => {
+                if object.len() == 1 {
+                    let (key, value) = object.iter().next().expect("never fails");
+                    if let Some(term) = marker_to_term(key, value, options)? {
+                        return Ok(term);
+                    }
+                }
+                let map = object
+                    .iter()
+                    .map(|(k, v)| Ok((options.str_to_term(k), Term::from_json(v, options)?)))
+                    .collect::<Result<TermMap, JsonError>>()?;
+                Term::from(Map::from(map))
+            }
+        })
+    }
+}
+
+fn big_integer_to_json(x: &BigInteger, options: &JsonOptions) -> Value {
+    match options.big_integer_repr {
+        BigIntegerRepr::Number => {
+            if let Some(v) = x.value.to_i64() {
+                Value::from(v)
+            } else if let Some(v) = x.value.to_u64() {
+                Value::from(v)
+            } else {
+                x.value
+                    .to_f64()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .unwrap_or_else(|| Value::String(x.value.to_string()))
+            }
+        }
+        BigIntegerRepr::String => Value::String(x.value.to_string()),
+        BigIntegerRepr::Tagged => marker(BIGINT_MARKER, Value::String(x.value.to_string())),
+    }
+}
+
+fn map_to_json(x: &Map, options: &JsonOptions) -> Value {
+    let keys = x
+        .map
+        .keys()
+        .map(|k| options.key_to_string(k))
+        .collect::<Option<Vec<_>>>();
+    match keys {
+        Some(keys) if !(keys.len() == 1 && MARKERS.contains(&keys[0].as_str())) => Value::Object(
+            keys.into_iter()
+                .zip(x.map.values())
+                .map(|(k, v)| (k, v.to_json(options)))
+                .collect(),
+        ),
+        _ => {
+            let pairs = x
+                .map
+                .iter()
+                .map(|(k, v)| Value::Array(vec![k.to_json(options), v.to_json(options)]))
+                .collect();
+            marker(MAP_MARKER, Value::Array(pairs))
+        }
+    }
+}
+
+fn number_to_term(n: &Number) -> Term {
+    if let Some(v) = n.as_i64() {
+        match i32::try_from(v) {
+            Ok(v) => Term::from(v),
+            Err(_) => Term::from(BigInteger::from(v)),
+        }
+    } else if let Some(v) = n.as_u64() {
+        Term::from(BigInteger::from(v))
+    } else {
+        Term::from(Float {
+            value: n.as_f64().expect("never fails"),
+        })
+    }
+}
+
+fn marker_to_term(
+    key: &str,
+    value: &Value,
+    options: &JsonOptions,
+) -> Result<Option<Term>, JsonError> {
+    let invalid = |marker| JsonError::InvalidMarker {
+        marker,
+        value: value.to_string(),
+    };
+    Ok(Some(match key {
+        TUPLE_MARKER => {
+            let elements = value.as_array().ok_or_else(|| invalid(TUPLE_MARKER))?;
+            Term::from(Tuple::from(
+                elements
+                    .iter()
+                    .map(|e| Term::from_json(e, options))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ))
+        }
+        BASE64_MARKER => {
+            let s = value.as_str().ok_or_else(|| invalid(BASE64_MARKER))?;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(s)
+                .map_err(|_| JsonError::InvalidBase64(s.to_string()))?;
+            Term::from(Binary::from(bytes))
+        }
+        BIGINT_MARKER => {
+            let s = value.as_str().ok_or_else(|| invalid(BIGINT_MARKER))?;
+            let value = s
+                .parse::<BigInt>()
+                .map_err(|_| JsonError::InvalidBigInteger(s.to_string()))?;
+            Term::from(BigInteger { value })
+        }
+        MAP_MARKER => {
+            let pairs = value.as_array().ok_or_else(|| invalid(MAP_MARKER))?;
+            let map = pairs
+                .iter()
+                .map(|pair| match pair.as_array().map(Vec::as_slice) {
+                    Some([k, v]) => {
+                        Ok((Term::from_json(k, options)?, Term::from_json(v, options)?))
+                    }
+                    _ => Err(invalid(MAP_MARKER)),
+                })
+                .collect::<Result<TermMap, _>>()?;
+            Term::from(Map::from(map))
+        }
+        _ => return Ok(None),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn binary(bytes: &[u8]) -> Term {
+        Term::from(Binary::from(bytes))
+    }
+
+    fn sample() -> Term {
+        Term::from(Map::from([
+            (atom("name"), binary(b"joe")),
+            (atom("tag"), binary(b":not_an_atom")),
+            (atom("raw"), binary(&[0, 159, 146, 150])),
+            (atom("active"), atom("true")),
+            (atom("nickname"), atom("nil")),
+            (
+                atom("ids"),
+                Term::from(List::from(vec![
+                    Term::from(1),
+                    Term::from(BigInteger::from(u64::MAX)),
+                    Term::from(BigInteger {
+                        value: BigInt::from(u64::MAX) * BigInt::from(u64::MAX),
+                    }),
+                    Term::from(Float::try_from(1.5).unwrap()),
+                ])),
+            ),
+            (
+                atom("pair"),
+                Term::from(Tuple::from(vec![atom("ok"), Term::from(-1)])),
+            ),
+            (
+                atom("nested"),
+                Term::from(Map::from([(
+                    Term::from(1),
+                    Term::from(Map::from([(binary(b"tuple"), Term::from(2))])),
+                )])),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn reversible_round_trip_works() {
+        let options = JsonOptions::reversible();
+        let term = sample();
+        let json = term.to_json(&options);
+        assert_eq!(json[":name"], "joe");
+        assert_eq!(json[":pair"], serde_json::json!({"tuple": [":ok", -1]}));
+        assert_eq!(
+            json[":nested"],
+            serde_json::json!({"map": [[1, {"map": [["tuple", 2]]}]]})
+        );
+        assert_eq!(Term::from_json(&json, &options).unwrap(), term);
+
+        let text = serde_json::to_string(&json).unwrap();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(Term::from_json(&parsed, &options).unwrap(), term);
+    }
+
+    #[test]
+    fn lossy_conversion_works() {
+        let options = JsonOptions::new();
+        let term = Term::from(Map::from([
+            (atom("name"), atom("joe")),
+            (atom("active"), atom("false")),
+            (atom("nickname"), atom("nil")),
+            (
+                atom("pair"),
+                Term::from(Tuple::from(vec![atom("ok"), Term::from(1)])),
+            ),
+            (atom("big"), Term::from(BigInteger::from(u64::MAX))),
+            (atom("raw"), binary(&[255])),
+        ]));
+        let json = term.to_json(&options);
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "joe",
+                "active": false,
+                "nickname": null,
+                "pair": ["ok", 1],
+                "big": u64::MAX,
+                "raw": {"base64": "/w=="},
+            })
+        );
+
+        let back = Term::from_json(&json, &options).unwrap();
+        let map: &Map = crate::convert::TryAsRef::try_as_ref(&back).unwrap();
+        assert_eq!(map.map[&binary(b"name")], binary(b"joe"));
+        assert_eq!(
+            map.map[&binary(b"pair")],
+            Term::from(List::from(vec![binary(b"ok"), Term::from(1)]))
+        );
+        assert_eq!(map.map[&binary(b"raw")], binary(&[255]));
+
+        let options = JsonOptions::new()
+            .binary_repr(BinaryRepr::Base64)
+            .big_integer_repr(BigIntegerRepr::String)
+            .null_atom("undefined");
+        assert_eq!(binary(b"joe").to_json(&options), "am9l");
+        assert_eq!(
+            Term::from(BigInteger::from(u64::MAX)).to_json(&options),
+            u64::MAX.to_string().as_str()
+        );
+        assert_eq!(atom("undefined").to_json(&options), Value::Null);
+        assert_eq!(atom("nil").to_json(&options), "nil");
+    }
+
+    #[test]
+    fn invalid_markers_are_rejected() {
+        let options = JsonOptions::reversible();
+        assert_eq!(
+            Term::from_json(&serde_json::json!({"base64": "!"}), &options),
+            Err(JsonError::InvalidBase64("!".to_string()))
+        );
+        assert!(matches!(
+            Term::from_json(&serde_json::json!({"tuple": 1}), &options),
+            Err(JsonError::InvalidMarker {
+                marker: "tuple",
+                ..
+            })
+        ));
+    }
+}
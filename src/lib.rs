@@ -1,12 +1,15 @@
 //! Library for encoding/decoding Erlang External Term Format.
 //!
+//! The [`prelude`] re-exports the term types, the conversion traits, the [`tuple!`] macro and
+//! the slice codec functions, which are enough for most uses.
+//!
 //! # Examples
 //!
 //! Decodes an atom:
 //!
 //! ```
 //! use std::io::Cursor;
-//! use eetf::{Term, Atom};
+//! use eetf::prelude::*;
 //!
 //! let bytes = vec![131, 100, 0, 3, 102, 111, 111];
 //! let term = Term::decode(Cursor::new(&bytes)).unwrap();
@@ -16,7 +19,7 @@
 //! Encodes an atom:
 //!
 //! ```
-//! use eetf::{Term, Atom};
+//! use eetf::prelude::*;
 //!
 //! let mut buf = Vec::new();
 //! let term = Term::from(Atom::from("foo"));
@@ -28,28 +31,195 @@
 //!
 //! - [Erlang External Term Format](http://erlang.org/doc/apps/erts/erl_ext_dist.html)
 //!
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, string::ToString, vec, vec::Vec};
+use core::fmt;
+use core::hash::Hash;
+#[cfg(not(feature = "std"))]
+use hashbrown::{HashMap, HashSet};
 use num::bigint::BigInt;
-use std::collections::HashMap;
-use std::fmt;
-use std::hash::Hash;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
 use std::io;
 
+#[cfg(feature = "std")]
+mod arc_term;
+mod atom_map;
+#[cfg(feature = "std")]
+mod atom_table;
+mod canonical;
+#[cfg(feature = "std")]
 mod codec;
 mod codec_common;
+mod slice;
 
 #[cfg(feature = "tokio-async")]
 mod async_codec;
 
+#[cfg(any(test, feature = "proptest"))]
+pub mod arbitrary;
+#[cfg(feature = "std")]
+pub mod berp;
+#[cfg(feature = "std")]
+pub mod bert;
+#[cfg(feature = "std")]
+pub mod bert_rpc;
+#[cfg(feature = "std")]
 pub mod convert;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "std")]
+mod diff;
+pub mod dist;
+#[cfg(feature = "std")]
+pub mod elixir;
+#[cfg(feature = "std")]
+pub mod envelope;
+#[cfg(feature = "epmd")]
+pub mod epmd;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod fidelity;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+pub mod gen;
+#[cfg(feature = "tracing")]
+mod instrument;
+#[cfg(feature = "std")]
+mod iolist;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "rmpv")]
+pub mod msgpack;
+#[cfg(feature = "rustler")]
+pub mod nif;
+pub mod node_name;
+#[cfg(feature = "std")]
+pub mod packet;
+#[cfg(feature = "std")]
+mod patch;
+#[cfg(feature = "std")]
 pub mod pattern;
+pub mod prelude;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(feature = "std")]
+pub mod projection;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "std")]
+mod redact;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "std")]
 pub mod string_convert;
+#[cfg(feature = "std")]
+mod tagged;
+#[cfg(feature = "std")]
+pub mod tee;
+pub mod term_map;
+pub mod text;
+mod view;
+#[cfg(feature = "std")]
+pub mod writer;
+#[cfg(feature = "std")]
+mod zlib;
 
+#[cfg(feature = "std")]
+pub use crate::arc_term::{ArcNode, ArcTerm};
+#[cfg(feature = "tokio-async")]
+pub use crate::async_codec::{AsyncDecoder, AsyncEncoder, LocalAsyncDecoder, LocalAsyncEncoder};
+pub use crate::atom_map::{AtomMap, NonAtomKeyError};
+#[cfg(feature = "std")]
+pub use crate::atom_table::{AtomSet, AtomTable, StaticAtom};
+pub use crate::canonical::CanonicalizeOptions;
+#[cfg(feature = "std")]
+pub use crate::codec::BufReadSource;
+#[cfg(feature = "std")]
+pub use crate::codec::{encode_into, ElementSink, Encoder, IterSequence, ToTermSequence};
+#[cfg(feature = "std")]
+pub use crate::codec::{DecodeAs, Decoder};
 pub use crate::codec_common::DecodeError;
 pub use crate::codec_common::DecodeResult;
+pub use crate::codec_common::DecoderOptions;
 pub use crate::codec_common::EncodeError;
 pub use crate::codec_common::EncodeResult;
+pub use crate::codec_common::EncoderOptions;
+pub use crate::codec_common::TermSummary;
+pub use crate::codec_common::MAX_DECODE_DEPTH;
+pub use crate::codec_common::{EncodePath, EncodePathSegment};
+#[cfg(feature = "std")]
+pub use crate::convert::FromTerm;
+#[cfg(feature = "std")]
+pub use crate::convert::FromTermError;
+#[cfg(feature = "std")]
+pub use crate::convert::IntoTerm;
+#[cfg(feature = "std")]
+pub use crate::convert::IntoTermTuple;
+#[cfg(feature = "serde")]
+pub use crate::de::{from_bytes, from_term, DeserializeOptions};
+#[cfg(feature = "std")]
+pub use crate::diff::{MergeStrategy, TermChange, TermDiff};
+pub use crate::error::Error;
+#[cfg(feature = "std")]
+pub use crate::file::{read_term_file, write_term_file, WriteOptions};
+#[cfg(feature = "std")]
+pub use crate::iolist::{IolistError, IolistPathSegment};
+#[cfg(feature = "json")]
+pub use crate::json::JsonOptions;
+#[cfg(feature = "mmap")]
+pub use crate::mmap::{decode_file, decode_file_view, MappedTerm};
+#[cfg(feature = "rmpv")]
+pub use crate::msgpack::MsgpackOptions;
+pub use crate::node_name::NodeName;
+#[cfg(feature = "std")]
+pub use crate::patch::{patch_encoded, PatchError, PatchPathSegment};
+#[cfg(feature = "std")]
+pub use crate::redact::RedactPolicy;
+#[cfg(feature = "serde")]
+pub use crate::ser::{to_bytes, to_term, SerializeOptions};
+#[cfg(feature = "bytes")]
+pub use crate::slice::decode_from_bytes;
+#[cfg(feature = "std")]
+pub use crate::slice::decode_from_slice_with_options;
+pub use crate::slice::{
+    decode_frame, decode_frame_with_options, decode_from_slice, encode_to_vec, term_iter, TermIter,
+};
+#[cfg(feature = "std")]
+pub use crate::tagged::{TaggedPayload, TaggedTuple, TaggedTupleError};
+pub use crate::term_map::TermMap;
+pub use crate::text::{parse_term, ParseError};
+pub use crate::view::{decode_view, TermView};
+#[cfg(feature = "derive")]
+pub use eetf_derive::{FromTerm, IntoTerm};
 
 /// Term.
+///
+/// With the `serde` feature, terms and the types of their variants implement `Serialize` and
+/// `Deserialize` themselves, to be embedded in other formats (e.g., JSON configuration), where
+/// a term is an object naming its variant. This is unrelated to `eetf::to_term` and
+/// `eetf::from_term`, which convert Rust values into and from terms.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Term {
     Atom(Atom),
@@ -68,41 +238,159 @@ pub enum Term {
     ImproperList(ImproperList),
     Tuple(Tuple),
     Map(Map),
+    Raw(Raw),
 }
 impl Term {
     /// Decodes a term.
+    #[cfg(feature = "std")]
     pub fn decode<R: io::Read>(reader: R) -> DecodeResult {
         codec::Decoder::new(reader).decode()
     }
 
+    /// Decodes a term with `options`.
+    #[cfg(feature = "std")]
+    pub fn decode_with_options<R: io::Read>(reader: R, options: DecoderOptions) -> DecodeResult {
+        codec::Decoder::with_options(reader, options).decode()
+    }
+
     /// Encodes the term.
+    #[cfg(feature = "std")]
     pub fn encode<W: io::Write>(&self, writer: W) -> EncodeResult {
         codec::Encoder::new(writer).encode(self)
     }
 
+    /// Encodes the term compressed with zlib.
+    #[cfg(feature = "std")]
+    pub fn encode_compressed<W: io::Write>(&self, writer: W) -> EncodeResult {
+        codec::Encoder::new(writer).encode_compressed(self)
+    }
+
+    /// Returns the number of bytes which [`Term::encode`] writes, without storing them (e.g.,
+    /// to size the buffer of [`encode_into`]).
+    #[cfg(feature = "std")]
+    pub fn encoded_size(&self) -> Result<usize, EncodeError> {
+        codec::encoded_size(self)
+    }
+
+    /// Encodes the term asynchronously.
     #[cfg(feature = "tokio-async")]
-    pub async fn encode_async<W: tokio::io::AsyncRead + std::marker::Unpin + std::marker::Send>(
+    pub async fn encode_async<W: tokio::io::AsyncWrite + std::marker::Unpin + std::marker::Send>(
         &self,
-        reader: W,
+        writer: W,
     ) -> EncodeResult {
-        async_codec::AsyncDecoder::new(reader).encode(self).await
+        async_codec::AsyncEncoder::new(writer).encode(self).await
     }
 
+    /// Decodes a term asynchronously.
     #[cfg(feature = "tokio-async")]
-    pub async fn decode_async<W: tokio::io::AsyncRead + std::marker::Unpin + std::marker::Send>(
-        &self,
-        reader: W,
+    pub async fn decode_async<R: tokio::io::AsyncRead + std::marker::Unpin + std::marker::Send>(
+        reader: R,
     ) -> DecodeResult {
-        async_codec::AsyncDecoder::new(reader).encode(self).await
+        async_codec::AsyncDecoder::new(reader).decode().await
     }
 
-    pub fn as_match<'a, P>(&'a self, pattern: P) -> pattern::Result<P::Output>
+    /// Makes a binary term from `bytes` (e.g., a `Vec<u8>` which is moved into it, or, with the
+    /// `bytes` feature, a [`bytes::Bytes`] which clones of the term share).
+    pub fn binary_from(bytes: impl Into<Binary>) -> Self {
+        Term::Binary(bytes.into())
+    }
+
+    /// Makes an atom term with [`Atom::from_static`].
+    pub fn atom_static(name: &'static str) -> Self {
+        Term::Atom(Atom::from_static(name))
+    }
+
+    /// Returns the id of the term in `set`, if it is an atom of the set.
+    #[cfg(feature = "std")]
+    pub fn atom_id(&self, set: &AtomSet) -> Option<usize> {
+        match self {
+            Term::Atom(atom) => set.id(atom),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the atom which is the first element of a tuple (e.g., `"error"` for
+    /// `{error, Reason}`), as by [`TaggedTuple::parse`].
+    pub fn tag_name(&self) -> Option<&str> {
+        match self {
+            Term::Tuple(tuple) => match tuple.elements.first() {
+                Some(Term::Atom(tag)) => Some(tag.as_str()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the variant of the term (e.g., `"Atom"`).
+    pub fn variant_name(&self) -> &'static str {
+        match *self {
+            Term::Atom(_) => "Atom",
+            Term::FixInteger(_) => "FixInteger",
+            Term::BigInteger(_) => "BigInteger",
+            Term::Float(_) => "Float",
+            Term::Pid(_) => "Pid",
+            Term::Port(_) => "Port",
+            Term::Reference(_) => "Reference",
+            Term::ExternalFun(_) => "ExternalFun",
+            Term::InternalFun(_) => "InternalFun",
+            Term::Binary(_) => "Binary",
+            Term::BitBinary(_) => "BitBinary",
+            Term::ByteList(_) => "ByteList",
+            Term::List(_) => "List",
+            Term::ImproperList(_) => "ImproperList",
+            Term::Tuple(_) => "Tuple",
+            Term::Map(_) => "Map",
+            Term::Raw(_) => "Raw",
+        }
+    }
+
+    /// Returns the bytes of a string, whichever way it was encoded: as a [`ByteList`], a
+    /// [`Binary`], or a proper [`List`] of integers in `0..=255`.
+    ///
+    /// Only the bytes of a list are copied. Other terms (e.g., a list with an element out of
+    /// range) return `None`.
+    pub fn as_text_bytes(&self) -> Option<Cow<'_, [u8]>> {
+        match *self {
+            Term::ByteList(ref x) => Some(Cow::Borrowed(&x.bytes)),
+            Term::Binary(ref x) => Some(Cow::Borrowed(&x.bytes)),
+            Term::List(ref x) => x
+                .elements
+                .iter()
+                .map(|e| match *e {
+                    Term::FixInteger(FixInteger { value }) => u8::try_from(value).ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Cow::Owned),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if both terms are strings (see [`Term::as_text_bytes`]) of the same bytes,
+    /// e.g., `"ok"` received as a `STRING_EXT` and `<<"ok">>`.
+    ///
+    /// `==` still compares the terms structurally, so a byte list never equals a binary.
+    pub fn text_eq(&self, other: &Term) -> bool {
+        match (self.as_text_bytes(), other.as_text_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn as_match<'a, P>(&'a self, pattern: P) -> pattern::Result<'a, P::Output>
     where
         P: pattern::Pattern<'a>,
     {
         pattern.try_match(self)
     }
 }
+/// Compares a string term (see [`Term::as_text_bytes`]) with bytes.
+impl PartialEq<[u8]> for Term {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_text_bytes().is_some_and(|bytes| *bytes == *other)
+    }
+}
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -122,6 +410,7 @@ impl fmt::Display for Term {
             Term::ImproperList(ref x) => x.fmt(f),
             Term::Tuple(ref x) => x.fmt(f),
             Term::Map(ref x) => x.fmt(f),
+            Term::Raw(ref x) => x.fmt(f),
         }
     }
 }
@@ -212,9 +501,14 @@ impl From<Map> for Term {
         Term::Map(x)
     }
 }
+impl From<Raw> for Term {
+    fn from(x: Raw) -> Self {
+        Term::Raw(x)
+    }
+}
 impl From<bool> for Term {
     fn from(value: bool) -> Self {
-        return Term::from(Atom::from(value));
+        Term::from(Atom::from(value))
     }
 }
 
@@ -232,11 +526,91 @@ macro_rules! impl_from_integer_to_term {
 }
 impl_from_integer_to_term!(u8, i8, u16, i16, i32);
 
+// Wider integers become a BigInteger only if they don't fit into a FixInteger
+macro_rules! impl_from_wide_integer_to_term {
+    ( $($fromInt:ty),* ) => {
+        $( impl From<$fromInt> for Term
+        {
+            fn from(number:$fromInt) -> Term
+            {
+                match i32::try_from(number) {
+                    Ok(value) => Term::FixInteger(FixInteger::from(value)),
+                    Err(_) => Term::BigInteger(BigInteger::from(number)),
+                }
+            }
+        } )*
+    };
+}
+impl_from_wide_integer_to_term!(u32, i64, u64, isize, usize, i128, u128);
+
 /// Atom.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+///
+/// The name is reference counted, so clones (and atoms interned by an [`AtomTable`])
+/// share one allocation and compare by pointer first.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Eq, Clone)]
 pub struct Atom {
     /// The name of the atom.
-    pub name: String,
+    pub name: Arc<str>,
+}
+impl Atom {
+    /// Returns the name of the atom.
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// Makes an atom named `name`, which shares its name with the other atoms made from the
+    /// same static string, so that only the first one allocates.
+    ///
+    /// Without the `std` feature, the name is copied as by `Atom::from`.
+    pub fn from_static(name: &'static str) -> Self {
+        #[cfg(feature = "std")]
+        {
+            Atom {
+                name: atom_table::static_name(name),
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            Atom::from(name)
+        }
+    }
+}
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.name, &other.name) || self.name == other.name
+    }
+}
+impl Hash for Atom {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Atom {
+    /// Orders atoms by their names, as Erlang does.
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+impl core::borrow::Borrow<str> for Atom {
+    fn borrow(&self) -> &str {
+        &self.name
+    }
+}
+impl core::ops::Deref for Atom {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.name
+    }
 }
 impl fmt::Display for Atom {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -249,32 +623,32 @@ impl fmt::Display for Atom {
 }
 impl<'a> From<&'a str> for Atom {
     fn from(name: &'a str) -> Self {
-        Atom {
-            name: name.to_string(),
-        }
+        Atom { name: name.into() }
     }
 }
 impl From<String> for Atom {
     fn from(name: String) -> Self {
+        Atom { name: name.into() }
+    }
+}
+impl From<Arc<str>> for Atom {
+    fn from(name: Arc<str>) -> Self {
         Atom { name }
     }
 }
 impl From<bool> for Atom {
     fn from(boolean: bool) -> Self {
-        if boolean {
-            Atom {
-                name: "true".to_string(),
-            }
-        } else {
-            Atom {
-                name: "false".to_string(),
-            }
-        }
+        Atom::from(if boolean { "true" } else { "false" })
     }
 }
 
 /// Fixed width integer.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct FixInteger {
     /// The value of the integer
     pub value: i32,
@@ -319,7 +693,12 @@ impl From<i32> for FixInteger {
 }
 
 /// Multiple precision integer.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct BigInteger {
     /// The value of the integer
     pub value: BigInt,
@@ -399,23 +778,103 @@ impl From<usize> for BigInteger {
         }
     }
 }
-impl<'a> From<&'a FixInteger> for BigInteger {
+impl From<i128> for BigInteger {
+    fn from(value: i128) -> Self {
+        BigInteger {
+            value: BigInt::from(value),
+        }
+    }
+}
+impl From<u128> for BigInteger {
+    fn from(value: u128) -> Self {
+        BigInteger {
+            value: BigInt::from(value),
+        }
+    }
+}
+impl From<&FixInteger> for BigInteger {
     fn from(i: &FixInteger) -> Self {
         BigInteger {
             value: BigInt::from(i.value),
         }
     }
 }
+impl BigInteger {
+    /// Returns the value if it fits into an `i64`.
+    pub fn to_i64(&self) -> Option<i64> {
+        num::traits::ToPrimitive::to_i64(&self.value)
+    }
+
+    /// Returns the value if it fits into a `u64`.
+    pub fn to_u64(&self) -> Option<u64> {
+        num::traits::ToPrimitive::to_u64(&self.value)
+    }
+
+    /// Returns the value if it fits into an `i128`.
+    pub fn to_i128(&self) -> Option<i128> {
+        num::traits::ToPrimitive::to_i128(&self.value)
+    }
+
+    /// Returns the value if it fits into a `u128`.
+    pub fn to_u128(&self) -> Option<u128> {
+        num::traits::ToPrimitive::to_u128(&self.value)
+    }
+
+    /// Returns the nearest `f64`, which is infinite if the value is out of the range of `f64`.
+    pub fn to_f64_lossy(&self) -> f64 {
+        num::traits::ToPrimitive::to_f64(&self.value).unwrap_or(if self.is_negative() {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        })
+    }
+
+    /// Returns `true` if the value is less than zero.
+    pub fn is_negative(&self) -> bool {
+        self.value.sign() == num::bigint::Sign::Minus
+    }
+
+    /// Returns the number of bits of the magnitude of the value (zero for zero).
+    pub fn bit_len(&self) -> u64 {
+        self.value.bits()
+    }
+}
+impl TryFrom<&BigInteger> for FixInteger {
+    type Error = DecodeError;
+
+    /// Converts a value which fits into an `i32`.
+    fn try_from(x: &BigInteger) -> Result<Self, Self::Error> {
+        num::traits::ToPrimitive::to_i32(&x.value)
+            .map(FixInteger::from)
+            .ok_or_else(|| DecodeError::UnexpectedType {
+                found: Box::new(TermSummary::new(&Term::from(x.clone()))),
+                expected: "FixInteger".to_string(),
+            })
+    }
+}
 
 /// Floating point number
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "f64", try_from = "f64")
+)]
 #[derive(Debug, Clone)]
 pub struct Float {
     /// The value of the number
     pub value: f64,
 }
 impl fmt::Display for Float {
+    /// Prints the shortest digits which parse back into the value, with a fraction even if it
+    /// is zero (e.g., `1.0` and `1.0e300`), as Erlang does.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.value)
+        let digits = format!("{:?}", self.value);
+        match digits.find('e') {
+            Some(i) if !digits[..i].contains('.') => {
+                write!(f, "{}.0{}", &digits[..i], &digits[i..])
+            }
+            _ => f.write_str(&digits),
+        }
     }
 }
 impl TryFrom<f32> for Float {
@@ -442,19 +901,119 @@ impl TryFrom<f64> for Float {
         }
     }
 }
+impl TryFrom<f32> for Term {
+    type Error = DecodeError;
+
+    /// Widens `value` exactly into a [`Float`].
+    fn try_from(value: f32) -> Result<Self, Self::Error> {
+        Float::try_from(value).map(Term::from)
+    }
+}
+impl TryFrom<f64> for Term {
+    type Error = DecodeError;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Float::try_from(value).map(Term::from)
+    }
+}
+impl Float {
+    /// Returns the nearest `f32`, which is infinite if the value is out of the range of `f32`.
+    pub fn to_f32_lossy(&self) -> f32 {
+        self.value as f32
+    }
+
+    /// Returns the value as an `f32`, or `None` if it is out of the range of `f32`, or if it
+    /// is not exactly representable and `policy` is [`FloatPrecisionLoss::Error`].
+    ///
+    /// ```
+    /// use eetf::{Float, FloatPrecisionLoss};
+    ///
+    /// let x = Float::try_from(0.1).unwrap();
+    /// assert_eq!(x.to_f32(FloatPrecisionLoss::Error), None);
+    /// assert_eq!(x.to_f32(FloatPrecisionLoss::Nearest), Some(0.1));
+    /// assert_eq!(Float::try_from(0.5).unwrap().to_f32(FloatPrecisionLoss::Error), Some(0.5));
+    /// ```
+    pub fn to_f32(&self, policy: FloatPrecisionLoss) -> Option<f32> {
+        let nearest = self.to_f32_lossy();
+        if !nearest.is_finite() && self.value.is_finite() {
+            return None;
+        }
+        match policy {
+            FloatPrecisionLoss::Nearest => Some(nearest),
+            FloatPrecisionLoss::Error if f64::from(nearest) == self.value => Some(nearest),
+            FloatPrecisionLoss::Error => None,
+        }
+    }
+}
+
+/// What converting a [`Float`] into an `f32` does with a value which `f32` cannot represent
+/// exactly (see [`Float::to_f32`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloatPrecisionLoss {
+    /// Fail.
+    #[default]
+    Error,
+
+    /// Round to the nearest `f32`.
+    Nearest,
+}
 impl PartialEq for Float {
     fn eq(&self, other: &Self) -> bool {
         ordered_float::OrderedFloat(self.value) == ordered_float::OrderedFloat(other.value)
     }
 }
 impl Eq for Float {}
-impl std::hash::Hash for Float {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl core::hash::Hash for Float {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
         ordered_float::OrderedFloat(self.value).hash(state);
     }
 }
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Float {
+    /// Orders floats by their values, with `-0.0` equal to `0.0` (as by [`PartialEq`]).
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        ordered_float::OrderedFloat(self.value).cmp(&ordered_float::OrderedFloat(other.value))
+    }
+}
+impl From<Float> for f64 {
+    fn from(x: Float) -> Self {
+        x.value
+    }
+}
+
+/// Errors of the validating constructors of [`Pid`], [`Port`] and [`Reference`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IdentifierError {
+    #[error("invalid node name")]
+    Node(#[from] node_name::NodeNameError),
+
+    #[error("creation 0 is reserved for identifiers of unknown incarnations")]
+    ZeroCreation,
+
+    #[error("a reference ID has 1 to {} words, not {len}", Reference::MAX_ID_LEN)]
+    ReferenceIdLen { len: usize },
+}
+
+/// Fails unless `node` is a valid node name and `creation` is not 0.
+fn check_identifier(node: &str, creation: u32) -> Result<NodeName, IdentifierError> {
+    let node = NodeName::parse(node)?;
+    if creation == 0 {
+        return Err(IdentifierError::ZeroCreation);
+    }
+    Ok(node)
+}
 
 /// Process Identifier.
+///
+/// [`Pid::new`] takes any values, like the decoders. [`Pid::try_new`] checks them against the
+/// current format (`NEW_PID_EXT`), where the ID, serial and creation are 32 bit each.
+/// The encoders check that the creation fits the 8 bits of the old format (`PID_EXT`) when
+/// [`EncoderOptions::big_creation`] is off.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Pid {
     pub node: Atom,
@@ -465,15 +1024,30 @@ pub struct Pid {
 impl Pid {
     pub fn new<T>(node: T, id: u32, serial: u32, creation: u32) -> Self
     where
-        Atom: From<T>,
+        T: Into<NodeName>,
     {
         Pid {
-            node: Atom::from(node),
+            node: Atom::from(node.into()),
             id,
             serial,
             creation,
         }
     }
+
+    /// Makes a pid, checking that `node` is a valid node name and that `creation` is not 0
+    /// (which the VM uses for pids of unknown incarnations, e.g., from `list_to_pid/1`).
+    pub fn try_new<T>(node: T, id: u32, serial: u32, creation: u32) -> Result<Self, IdentifierError>
+    where
+        T: AsRef<str>,
+    {
+        let node = check_identifier(node.as_ref(), creation)?;
+        Ok(Pid::new(node, id, serial, creation))
+    }
+
+    /// Returns `true` if the pid belongs to the incarnation `creation` of `node`.
+    pub fn is_local(&self, node: &NodeName, creation: u32) -> bool {
+        self.node.as_str() == node.as_str() && self.creation == creation
+    }
 }
 impl fmt::Display for Pid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -493,12 +1067,43 @@ impl<'a> From<(&'a str, u32, u32)> for Pid {
 }
 
 /// Port.
+///
+/// [`Port::new`] takes any values, like the decoders. [`Port::try_new`] checks them against the
+/// current format (`V4_PORT_EXT`), where the ID is 64 bit and the creation 32 bit.
+/// The encoders need [`EncoderOptions::v4_nc`] for IDs over 32 bits.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Port {
     pub node: Atom,
     pub id: u64,
     pub creation: u32,
 }
+impl Port {
+    pub fn new<T>(node: T, id: u64, creation: u32) -> Self
+    where
+        T: Into<NodeName>,
+    {
+        Port {
+            node: Atom::from(node.into()),
+            id,
+            creation,
+        }
+    }
+
+    /// Makes a port, checking `node` and `creation` like [`Pid::try_new`].
+    pub fn try_new<T>(node: T, id: u64, creation: u32) -> Result<Self, IdentifierError>
+    where
+        T: AsRef<str>,
+    {
+        let node = check_identifier(node.as_ref(), creation)?;
+        Ok(Port::new(node, id, creation))
+    }
+
+    /// Returns `true` if the port belongs to the incarnation `creation` of `node`.
+    pub fn is_local(&self, node: &NodeName, creation: u32) -> bool {
+        self.node.as_str() == node.as_str() && self.creation == creation
+    }
+}
 impl fmt::Display for Port {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "#Port<{}.{}>", self.node, self.id)
@@ -515,12 +1120,69 @@ impl<'a> From<(&'a str, u32)> for Port {
 }
 
 /// Reference.
+///
+/// [`Reference::new`] takes any values, like the decoders. [`Reference::try_new`] checks them
+/// against the current format (`NEWER_REFERENCE_EXT`), where the ID has 1 to 5 words.
+/// The encoders need [`EncoderOptions::v4_nc`] for IDs of more than 3 words.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Reference {
     pub node: Atom,
     pub id: Vec<u32>,
     pub creation: u32,
 }
+impl Reference {
+    /// Maximum number of words of an ID.
+    pub const MAX_ID_LEN: usize = 5;
+
+    pub fn new<T>(node: T, id: Vec<u32>, creation: u32) -> Self
+    where
+        T: Into<NodeName>,
+    {
+        Reference {
+            node: Atom::from(node.into()),
+            id,
+            creation,
+        }
+    }
+
+    /// Makes a reference, checking `node` and `creation` like [`Pid::try_new`] and that the ID
+    /// has 1 to [`Reference::MAX_ID_LEN`] words.
+    pub fn try_new<T>(node: T, id: Vec<u32>, creation: u32) -> Result<Self, IdentifierError>
+    where
+        T: AsRef<str>,
+    {
+        let node = check_identifier(node.as_ref(), creation)?;
+        if id.is_empty() || id.len() > Self::MAX_ID_LEN {
+            return Err(IdentifierError::ReferenceIdLen { len: id.len() });
+        }
+        Ok(Reference::new(node, id, creation))
+    }
+
+    /// Makes a reference of `node` which no other call in the process makes (e.g., to tag a call).
+    ///
+    /// The ID has 3 words: 2 from a process-wide counter and 1 random word, which tells apart
+    /// the references of processes sharing `node` and `creation`.
+    #[cfg(feature = "std")]
+    pub fn generate<T>(node: T, creation: u32) -> Self
+    where
+        T: Into<NodeName>,
+    {
+        use std::hash::BuildHasher;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let random = std::collections::hash_map::RandomState::new().hash_one(count);
+        let id = vec![count as u32, (count >> 32) as u32, random as u32];
+        Reference::new(node, id, creation)
+    }
+
+    /// Returns `true` if the reference belongs to the incarnation `creation` of `node`.
+    pub fn is_local(&self, node: &NodeName, creation: u32) -> bool {
+        self.node.as_str() == node.as_str() && self.creation == creation
+    }
+}
 impl fmt::Display for Reference {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "#Ref<{}", self.node)?;
@@ -550,12 +1212,51 @@ impl<'a> From<(&'a str, Vec<u32>)> for Reference {
 }
 
 /// External Function.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ExternalFun {
     pub module: Atom,
     pub function: Atom,
     pub arity: u8,
 }
+impl ExternalFun {
+    pub fn new<M, F>(module: M, function: F, arity: u8) -> Self
+    where
+        M: Into<Atom>,
+        F: Into<Atom>,
+    {
+        ExternalFun {
+            module: module.into(),
+            function: function.into(),
+            arity,
+        }
+    }
+
+    /// Returns the module, the function and the arity.
+    pub fn mfa(&self) -> (&Atom, &Atom, u8) {
+        (&self.module, &self.function, self.arity)
+    }
+
+    /// Converts `term` into an external fun, or returns it back if it is not one.
+    ///
+    /// If `mfa_tuples` is true, `{Module, Function, Arity}` tuples (which stand for funs in,
+    /// e.g., `apply/3` and the start functions of child specs) are accepted too.
+    pub fn try_from_term(term: Term, mfa_tuples: bool) -> Result<Self, Term> {
+        match term {
+            Term::ExternalFun(x) => Ok(*x),
+            Term::Tuple(ref x) if mfa_tuples => match x.elements.as_slice() {
+                [Term::Atom(module), Term::Atom(function), Term::FixInteger(arity)] => {
+                    match u8::try_from(arity.value) {
+                        Ok(arity) => Ok(ExternalFun::new(module.clone(), function.clone(), arity)),
+                        Err(_) => Err(term),
+                    }
+                }
+                _ => Err(term),
+            },
+            term => Err(term),
+        }
+    }
+}
 impl fmt::Display for ExternalFun {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "fun {}:{}/{}", self.module, self.function, self.arity)
@@ -572,6 +1273,7 @@ impl<'a, 'b> From<(&'a str, &'b str, u8)> for ExternalFun {
 }
 
 /// Internal Function.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum InternalFun {
     /// Old representation.
@@ -594,6 +1296,33 @@ pub enum InternalFun {
         old_uniq: i32,
     },
 }
+impl InternalFun {
+    pub fn module(&self) -> &Atom {
+        match self {
+            InternalFun::Old { module, .. } | InternalFun::New { module, .. } => module,
+        }
+    }
+
+    /// Returns the arity, which the old representation does not record.
+    pub fn arity(&self) -> Option<u8> {
+        match *self {
+            InternalFun::Old { .. } => None,
+            InternalFun::New { arity, .. } => Some(arity),
+        }
+    }
+
+    pub fn pid(&self) -> &Pid {
+        match self {
+            InternalFun::Old { pid, .. } | InternalFun::New { pid, .. } => pid,
+        }
+    }
+
+    pub fn free_vars(&self) -> &[Term] {
+        match self {
+            InternalFun::Old { free_vars, .. } | InternalFun::New { free_vars, .. } => free_vars,
+        }
+    }
+}
 impl fmt::Display for InternalFun {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -617,10 +1346,90 @@ impl fmt::Display for InternalFun {
     }
 }
 
+/// Storage of the bytes of binaries and bit binaries.
+///
+/// With the `bytes` feature, this is [`bytes::Bytes`], which makes clones and
+/// [`Binary::slice`] share the bytes instead of copying them.
+#[cfg(not(feature = "bytes"))]
+pub type BinaryBytes = Vec<u8>;
+
+/// Storage of the bytes of binaries and bit binaries.
+///
+/// With the `bytes` feature, this is [`bytes::Bytes`], which makes clones and
+/// [`Binary::slice`] share the bytes instead of copying them.
+#[cfg(feature = "bytes")]
+pub type BinaryBytes = bytes::Bytes;
+
+/// Moves the bytes of a binary into a vector (without copying them unless they are shared).
+#[cfg(not(feature = "bytes"))]
+pub(crate) fn bytes_into_vec(bytes: BinaryBytes) -> Vec<u8> {
+    bytes
+}
+
+/// Moves the bytes of a binary into a vector (without copying them unless they are shared).
+#[cfg(feature = "bytes")]
+pub(crate) fn bytes_into_vec(bytes: BinaryBytes) -> Vec<u8> {
+    Vec::from(bytes)
+}
+
 /// Binary.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct Binary {
-    pub bytes: Vec<u8>,
+    pub bytes: BinaryBytes,
+}
+impl Binary {
+    /// Returns the sub-binary in `range`.
+    ///
+    /// With the `bytes` feature, the sub-binary shares the bytes of `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    #[doc(alias = "subslice")]
+    pub fn slice(&self, range: impl core::ops::RangeBounds<usize>) -> Self {
+        #[cfg(feature = "bytes")]
+        let bytes = self.bytes.slice(range);
+        #[cfg(not(feature = "bytes"))]
+        let bytes = self.bytes[(range.start_bound().cloned(), range.end_bound().cloned())].to_vec();
+        Binary { bytes }
+    }
+
+    /// Returns a view of the bytes in `range`, which borrows them with or without the `bytes`
+    /// feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn view(&self, range: impl core::ops::RangeBounds<usize>) -> BinarySlice<'_> {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        BinarySlice {
+            bytes: &self.bytes[bounds],
+        }
+    }
+
+    /// Splits the binary into the sub-binaries before and from `mid` (see [`Binary::slice`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid` is greater than the length of the binary.
+    pub fn split_at(&self, mid: usize) -> (Binary, Binary) {
+        (self.slice(..mid), self.slice(mid..))
+    }
+
+    pub fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.bytes.starts_with(prefix)
+    }
+
+    /// Returns the sub-binary after `prefix` (see [`Binary::slice`]), or `None` if the binary
+    /// does not start with it.
+    pub fn strip_prefix(&self, prefix: &[u8]) -> Option<Binary> {
+        self.starts_with(prefix).then(|| self.slice(prefix.len()..))
+    }
 }
 impl fmt::Display for Binary {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -638,20 +1447,110 @@ impl fmt::Display for Binary {
 impl<'a> From<&'a [u8]> for Binary {
     fn from(bytes: &'a [u8]) -> Self {
         Binary {
-            bytes: Vec::from(bytes),
+            bytes: BinaryBytes::from(bytes.to_vec()),
         }
     }
 }
 impl From<Vec<u8>> for Binary {
     fn from(bytes: Vec<u8>) -> Self {
+        Binary {
+            bytes: BinaryBytes::from(bytes),
+        }
+    }
+}
+#[cfg(feature = "bytes")]
+impl From<bytes::Bytes> for Binary {
+    fn from(bytes: bytes::Bytes) -> Self {
         Binary { bytes }
     }
 }
+impl AsRef<[u8]> for Binary {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Borrowed bytes of a binary (see [`Binary::view`]).
+///
+/// It compares equal to the binaries with the same bytes, and can be encoded with
+/// [`Encoder::encode_parts`] (as `slice.as_ref()`) without making it a [`Binary`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct BinarySlice<'a> {
+    pub bytes: &'a [u8],
+}
+impl<'a> BinarySlice<'a> {
+    /// Copies the bytes into a binary.
+    pub fn to_binary(&self) -> Binary {
+        Binary::from(self.bytes)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `mid` is greater than the length of the slice.
+    pub fn split_at(&self, mid: usize) -> (BinarySlice<'a>, BinarySlice<'a>) {
+        let (head, tail) = self.bytes.split_at(mid);
+        (BinarySlice { bytes: head }, BinarySlice { bytes: tail })
+    }
+
+    pub fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.bytes.starts_with(prefix)
+    }
+
+    pub fn strip_prefix(&self, prefix: &[u8]) -> Option<BinarySlice<'a>> {
+        self.bytes
+            .strip_prefix(prefix)
+            .map(|bytes| BinarySlice { bytes })
+    }
+}
+impl fmt::Display for BinarySlice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<<")?;
+        for (i, b) in self.bytes.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", b)?;
+        }
+        write!(f, ">>")
+    }
+}
+impl<'a> From<&'a Binary> for BinarySlice<'a> {
+    fn from(binary: &'a Binary) -> Self {
+        BinarySlice {
+            bytes: &binary.bytes,
+        }
+    }
+}
+impl<'a> From<BinarySlice<'a>> for Binary {
+    fn from(slice: BinarySlice<'a>) -> Self {
+        slice.to_binary()
+    }
+}
+impl AsRef<[u8]> for BinarySlice<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.bytes
+    }
+}
+impl PartialEq<Binary> for BinarySlice<'_> {
+    fn eq(&self, other: &Binary) -> bool {
+        self.bytes == &other.bytes[..]
+    }
+}
+impl PartialEq<BinarySlice<'_>> for Binary {
+    fn eq(&self, other: &BinarySlice<'_>) -> bool {
+        &self.bytes[..] == other.bytes
+    }
+}
 
 /// Bit string.
+///
+/// Its bits are numbered from the most significant bit of the first byte, and the last byte
+/// holds the last `tail_bits_size` bits in its least significant bits (e.g., `<<1:3>>` is `[1]`
+/// with 3 tail bits).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct BitBinary {
-    pub bytes: Vec<u8>,
+    pub bytes: BinaryBytes,
     pub tail_bits_size: u8,
 }
 impl fmt::Display for BitBinary {
@@ -684,12 +1583,164 @@ impl From<Binary> for BitBinary {
 }
 impl From<(Vec<u8>, u8)> for BitBinary {
     fn from((bytes, tail_bits_size): (Vec<u8>, u8)) -> Self {
+        BitBinary {
+            bytes: BinaryBytes::from(bytes),
+            tail_bits_size,
+        }
+    }
+}
+#[cfg(feature = "bytes")]
+impl From<(bytes::Bytes, u8)> for BitBinary {
+    fn from((bytes, tail_bits_size): (bytes::Bytes, u8)) -> Self {
         BitBinary {
             bytes,
             tail_bits_size,
         }
     }
 }
+impl AsRef<[u8]> for BitBinary {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+impl BitBinary {
+    /// Makes a bit string of `bits`.
+    pub fn from_bits<I: IntoIterator<Item = bool>>(bits: I) -> Self {
+        let mut bytes = Vec::new();
+        let mut tail_bits_size = 0;
+        for bit in bits {
+            push_bit(&mut bytes, &mut tail_bits_size, bit);
+        }
+        BitBinary::with_bits(bytes, tail_bits_size)
+    }
+
+    /// Makes a bit string of the first `bit_len` bits of `bytes`, or returns `None` if `bytes`
+    /// does not have exactly the bytes for them.
+    ///
+    /// This is the inverse of [`BitBinary::to_padded_bytes`].
+    pub fn from_padded_bytes(mut bytes: Vec<u8>, bit_len: usize) -> Option<Self> {
+        if bytes.len() != bit_len.div_ceil(8) {
+            return None;
+        }
+        let tail_bits_size = match bit_len % 8 {
+            0 if bit_len == 0 => 0,
+            0 => 8,
+            n => n as u8,
+        };
+        if let Some(last) = bytes.last_mut() {
+            *last >>= 8 - tail_bits_size;
+        }
+        Some(BitBinary::from((bytes, tail_bits_size)))
+    }
+
+    /// Returns the bytes of the bit string, whose last byte is padded with zero bits (as in
+    /// Erlang), and the number of bits.
+    pub fn to_padded_bytes(&self) -> (Vec<u8>, usize) {
+        let bit_len = self.bit_len();
+        let mut bytes = self.bytes[..bit_len.div_ceil(8)].to_vec();
+        if let Some(last) = bytes.last_mut() {
+            *last <<= 8 - self.last_byte_bits();
+        }
+        (bytes, bit_len)
+    }
+
+    pub fn bit_len(&self) -> usize {
+        match self.bytes.len() {
+            0 => 0,
+            len => (len - 1) * 8 + usize::from(self.tail_bits_size.min(8)),
+        }
+    }
+
+    pub fn get_bit(&self, index: usize) -> Option<bool> {
+        if index >= self.bit_len() {
+            return None;
+        }
+        let (byte, bit) = (index / 8, (index % 8) as u8);
+        let width = if byte == self.bytes.len() - 1 {
+            self.last_byte_bits()
+        } else {
+            8
+        };
+        Some((self.bytes[byte] >> (width - 1 - bit)) & 1 == 1)
+    }
+
+    pub fn iter_bits(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.bit_len()).map(move |i| self.get_bit(i).unwrap_or_default())
+    }
+
+    pub fn push_bit(&mut self, bit: bool) {
+        let (mut bytes, mut tail_bits_size) = self.take_bits();
+        push_bit(&mut bytes, &mut tail_bits_size, bit);
+        *self = BitBinary::with_bits(bytes, tail_bits_size);
+    }
+
+    /// Appends the bits of `other`, which need not start at a byte boundary.
+    pub fn append(&mut self, other: &BitBinary) {
+        let (mut bytes, mut tail_bits_size) = self.take_bits();
+        if tail_bits_size == 8 || bytes.is_empty() {
+            let other_len = other.bit_len().div_ceil(8);
+            bytes.extend_from_slice(&other.bytes[..other_len]);
+            if other_len > 0 {
+                tail_bits_size = other.last_byte_bits();
+            }
+        } else {
+            for bit in other.iter_bits() {
+                push_bit(&mut bytes, &mut tail_bits_size, bit);
+            }
+        }
+        *self = BitBinary::with_bits(bytes, tail_bits_size);
+    }
+
+    fn with_bits(bytes: Vec<u8>, tail_bits_size: u8) -> Self {
+        let tail_bits_size = if bytes.is_empty() { 0 } else { tail_bits_size };
+        BitBinary::from((bytes, tail_bits_size))
+    }
+
+    /// Returns the number of bits of the last byte (of the bytes holding bits).
+    fn last_byte_bits(&self) -> u8 {
+        match self.tail_bits_size {
+            1..=8 => self.tail_bits_size,
+            _ => 8,
+        }
+    }
+
+    /// Takes the bytes holding bits, and the number of bits of the last one (8 if there are none).
+    fn take_bits(&mut self) -> (Vec<u8>, u8) {
+        let len = self.bit_len().div_ceil(8);
+        let tail_bits_size = if len == 0 { 8 } else { self.last_byte_bits() };
+        let mut bytes = bytes_into_vec(core::mem::take(&mut self.bytes));
+        bytes.truncate(len);
+        (bytes, tail_bits_size)
+    }
+}
+/// Appends `bit` to `bytes`, whose last byte holds `tail_bits_size` bits.
+fn push_bit(bytes: &mut Vec<u8>, tail_bits_size: &mut u8, bit: bool) {
+    match bytes.last_mut() {
+        Some(last) if *tail_bits_size < 8 => {
+            *last = (*last << 1) | u8::from(bit);
+            *tail_bits_size += 1;
+        }
+        _ => {
+            bytes.push(u8::from(bit));
+            *tail_bits_size = 1;
+        }
+    }
+}
+impl TryFrom<BitBinary> for Binary {
+    type Error = BitBinary;
+
+    /// Converts a bit string whose bits fill its bytes into a binary.
+    fn try_from(bits: BitBinary) -> Result<Self, BitBinary> {
+        if !bits.bit_len().is_multiple_of(8) {
+            return Err(bits);
+        }
+        let len = bits.bit_len() / 8;
+        let mut bytes = bits.bytes;
+        // The last byte of a bit string with no tail bits holds no bits.
+        bytes.truncate(len);
+        Ok(Binary { bytes })
+    }
+}
 
 /// Erlang has a transport optimization for lists only containing u8 elements. \
 /// Since Strings in erlang are just lists with u8's they call this "STRING_EXT".
@@ -698,7 +1749,12 @@ impl From<(Vec<u8>, u8)> for BitBinary {
 ///
 /// See: https://erlang.org/doc/apps/erts/erl_ext_dist.html#STRING_EXT
 ///
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct ByteList {
     pub bytes: Vec<u8>,
 }
@@ -743,7 +1799,12 @@ impl<const N: usize> From<&[u8; N]> for ByteList {
 }
 
 /// List.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
 pub struct List {
     pub elements: Vec<Term>,
 }
@@ -794,6 +1855,7 @@ impl From<ByteList> for List {
 }
 
 /// Improper list.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ImproperList {
     pub elements: Vec<Term>,
@@ -823,7 +1885,12 @@ impl From<(Vec<Term>, Term)> for ImproperList {
 }
 
 /// Tuple.
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
 pub struct Tuple {
     pub elements: Vec<Term>,
 }
@@ -855,20 +1922,30 @@ impl From<Vec<Term>> for Tuple {
 #[macro_export]
 macro_rules! tuple {
     ($($term:expr),*) => {
-        Tuple::from(vec![$($term.into()),*])
+        $crate::Tuple::from(vec![$($term.into()),*])
     };
 }
 
 /// Map.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Map {
-    pub map: HashMap<Term, Term>,
+    pub map: TermMap,
 }
 impl Map {
     pub fn new() -> Self {
-        return Map {
-            map: HashMap::new(),
-        };
+        Map {
+            map: TermMap::new(),
+        }
+    }
+}
+impl Default for Map {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl fmt::Display for Map {
@@ -885,41 +1962,121 @@ impl fmt::Display for Map {
     }
 }
 impl Hash for Map {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        // Equal maps may iterate in different orders, so the entry hashes are combined commutatively.
+        let mut sum: u64 = 0;
         for (k, v) in self.map.iter() {
-            k.hash(state);
-            v.hash(state);
+            let mut hasher = EntryHasher::default();
+            k.hash(&mut hasher);
+            v.hash(&mut hasher);
+            sum = sum.wrapping_add(core::hash::Hasher::finish(&hasher));
+        }
+        state.write_usize(self.map.len());
+        state.write_u64(sum);
+    }
+}
+#[cfg(feature = "std")]
+type EntryHasher = std::collections::hash_map::DefaultHasher;
+
+/// FNV-1a, which hashes map entries deterministically without `std`.
+#[cfg(not(feature = "std"))]
+struct EntryHasher(u64);
+#[cfg(not(feature = "std"))]
+impl Default for EntryHasher {
+    fn default() -> Self {
+        EntryHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+#[cfg(not(feature = "std"))]
+impl core::hash::Hasher for EntryHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3);
         }
     }
 }
+
 impl<const N: usize> From<[(Term, Term); N]> for Map {
     fn from(from: [(Term, Term); N]) -> Self {
         Map {
-            map: HashMap::from(from),
+            map: TermMap::from(from),
         }
     }
 }
+impl From<TermMap> for Map {
+    fn from(map: TermMap) -> Self {
+        Map { map }
+    }
+}
 impl From<HashMap<Term, Term>> for Map {
     fn from(from_map: HashMap<Term, Term>) -> Self {
-        Map { map: from_map }
+        Map {
+            map: TermMap::from(from_map),
+        }
     }
 }
 impl From<HashMap<String, Term>> for Map {
     fn from(from_map: HashMap<String, Term>) -> Self {
-        let mut result_map = HashMap::<Term, Term>::new();
-        for (k, v) in from_map {
-            result_map.insert(Term::from(k), v);
+        Map {
+            map: from_map
+                .into_iter()
+                .map(|(k, v)| (Term::from(k), v))
+                .collect(),
         }
-        Map { map: result_map }
     }
 }
 impl From<HashMap<&str, Term>> for Map {
     fn from(from_map: HashMap<&str, Term>) -> Self {
-        let mut result_map = HashMap::<Term, Term>::new();
-        for (k, v) in from_map {
-            result_map.insert(Term::from(Atom::from(k)), v);
+        Map {
+            map: from_map
+                .into_iter()
+                .map(|(k, v)| (Term::from(Atom::from(k)), v))
+                .collect(),
+        }
+    }
+}
+
+/// Subterm kept in the external term format (from its tag on), which is encoded verbatim.
+///
+/// Decoders produce raw terms when asked to with [`DecoderOptions::raw_depth`], and they
+/// compare by their bytes (so a raw term never equals a decoded one).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Raw {
+    pub bytes: Vec<u8>,
+}
+impl Raw {
+    /// Decodes the subterm.
+    pub fn decode(&self) -> DecodeResult {
+        let mut decoder = slice::SliceDecoder::new(&self.bytes);
+        let tag = decoder.read_u8()?;
+        let term = decoder.decode_term_with_tag(tag)?;
+        decoder.finish()?;
+        Ok(term)
+    }
+}
+impl fmt::Display for Raw {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#Raw<")?;
+        for (i, b) in self.bytes.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", b)?;
         }
-        Map { map: result_map }
+        write!(f, ">")
+    }
+}
+impl From<Vec<u8>> for Raw {
+    fn from(bytes: Vec<u8>) -> Self {
+        Raw { bytes }
     }
 }
 
@@ -939,7 +2096,7 @@ mod tests {
             Term::from(Atom::from("bar")),
         ]));
         let (_, v) = t.as_match(("foo", any::<Atom>())).unwrap();
-        assert_eq!("bar", v.name);
+        assert_eq!("bar", v.as_str());
 
         let t = Term::from(Tuple::from(vec![
             Term::from(Atom::from("foo")),
@@ -951,4 +2108,53 @@ mod tests {
         let t = Term::from(FixInteger::from(8));
         t.as_match(U8).unwrap();
     }
+
+    #[test]
+    fn sub_binaries_are_encoded() {
+        let binary = Binary::from(&b"header:payload"[..]);
+        let payload = binary.slice(7..);
+        assert_eq!(payload.as_ref(), b"payload");
+        assert_eq!(binary.slice(..=5).as_ref(), b"header");
+
+        let mut buf = Vec::new();
+        Term::from(payload).encode(&mut buf).unwrap();
+        assert_eq!(buf, [131, 109, 0, 0, 0, 7, 112, 97, 121, 108, 111, 97, 100]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn binaries_share_their_bytes() {
+        let binary = Binary::from(vec![0; 1 << 20]);
+        let term = Term::from(Tuple::from(vec![Term::from(binary.clone())]));
+        let Term::Tuple(tuple) = term.clone() else {
+            unreachable!()
+        };
+        let Term::Binary(ref cloned) = tuple.elements[0] else {
+            unreachable!()
+        };
+        assert_eq!(cloned.bytes.as_ptr(), binary.bytes.as_ptr());
+        assert_eq!(
+            binary.slice(10..).bytes.as_ptr(),
+            binary.bytes[10..].as_ptr()
+        );
+
+        let bit_binary = BitBinary::from((bytes::Bytes::from_static(b"ab"), 3));
+        assert_eq!(bit_binary.clone().bytes.as_ptr(), bit_binary.bytes.as_ptr());
+    }
+
+    #[test]
+    fn equal_maps_have_equal_hashes() {
+        use std::hash::{BuildHasher, RandomState};
+
+        let entries = (0..100).map(|i| (Term::from(i), Term::from(-i)));
+        let mut small = HashMap::new();
+        small.extend(entries.clone());
+        let mut large = HashMap::with_capacity(1000);
+        large.extend(entries.rev());
+
+        let (small, large) = (Map::from(small), Map::from(large));
+        assert_eq!(small, large);
+        let state = RandomState::new();
+        assert_eq!(state.hash_one(&small), state.hash_one(&large));
+    }
 }
@@ -0,0 +1,305 @@
+//! Metrics hooks of the codecs.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::metrics::CodecMetrics;
+//! use eetf::{Atom, Decoder, Term};
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! #[derive(Default)]
+//! struct Counters {
+//!     terms: AtomicU64,
+//!     bytes: AtomicU64,
+//!     errors: AtomicU64,
+//! }
+//! impl CodecMetrics for Counters {
+//!     fn on_decode(&self, bytes: u64, _elapsed: Duration, ok: bool) {
+//!         self.terms.fetch_add(1, Ordering::Relaxed);
+//!         self.bytes.fetch_add(bytes, Ordering::Relaxed);
+//!         if !ok {
+//!             self.errors.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let counters = Arc::new(Counters::default());
+//! let bytes = [131, 100, 0, 2, b'o', b'k'];
+//! let term = Decoder::new(&bytes[..]).with_metrics(counters.clone()).decode().unwrap();
+//! assert_eq!(term, Term::from(Atom::from("ok")));
+//! assert_eq!(counters.bytes.load(Ordering::Relaxed), 6);
+//! ```
+use super::*;
+use std::time::{Duration, Instant};
+
+/// Callbacks which are called after each term a codec decodes or encodes.
+///
+/// Codecs without metrics (the default) do not measure anything.
+pub trait CodecMetrics: Send + Sync {
+    /// Called after a term of `bytes` bytes was decoded in `elapsed`, or failed to decode after
+    /// `bytes` bytes.
+    fn on_decode(&self, bytes: u64, elapsed: Duration, ok: bool) {
+        let _ = (bytes, elapsed, ok);
+    }
+
+    /// Called after a term of `bytes` bytes was encoded in `elapsed`, or failed to encode after
+    /// `bytes` bytes.
+    fn on_encode(&self, bytes: u64, elapsed: Duration, ok: bool) {
+        let _ = (bytes, elapsed, ok);
+    }
+}
+
+/// Observer of a decoded or encoded term, for its metrics and its `tracing` span.
+pub(crate) struct Observer {
+    metrics: Option<Arc<dyn CodecMetrics>>,
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+    start: Instant,
+}
+impl Observer {
+    /// Returns the observer of a decoded term, or `None` if nothing observes it.
+    pub(crate) fn decode(metrics: Option<&Arc<dyn CodecMetrics>>) -> Option<Self> {
+        #[cfg(feature = "tracing")]
+        let span = crate::instrument::decode_span();
+        #[cfg(feature = "tracing")]
+        let observed = metrics.is_some() || span.is_some();
+        #[cfg(not(feature = "tracing"))]
+        let observed = metrics.is_some();
+        observed.then(|| Observer {
+            metrics: metrics.cloned(),
+            #[cfg(feature = "tracing")]
+            span,
+            start: Instant::now(),
+        })
+    }
+
+    /// Returns the observer of an encoded term, or `None` if nothing observes it.
+    pub(crate) fn encode(
+        metrics: Option<&Arc<dyn CodecMetrics>>,
+        term: &Term,
+        compressed: bool,
+    ) -> Option<Self> {
+        #[cfg(feature = "tracing")]
+        let span = crate::instrument::encode_span(term, compressed);
+        #[cfg(feature = "tracing")]
+        let observed = metrics.is_some() || span.is_some();
+        #[cfg(not(feature = "tracing"))]
+        let observed = {
+            let _ = (term, compressed);
+            metrics.is_some()
+        };
+        observed.then(|| Observer {
+            metrics: metrics.cloned(),
+            #[cfg(feature = "tracing")]
+            span,
+            start: Instant::now(),
+        })
+    }
+
+    /// Runs `f` within the span.
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &self.span {
+            return span.in_scope(f);
+        }
+        f()
+    }
+
+    /// Polls `future` within the span.
+    #[cfg(feature = "tokio-async")]
+    pub(crate) async fn in_scope_async<F: std::future::Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &self.span {
+            use tracing::Instrument;
+            return future.instrument(span.clone()).await;
+        }
+        future.await
+    }
+
+    /// Records the outcome of decoding `bytes` bytes.
+    pub(crate) fn finish_decode(self, result: &DecodeResult, bytes: u64) {
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &self.span {
+            crate::instrument::record_decode(span, result, bytes, self.start);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.on_decode(bytes, self.start.elapsed(), result.is_ok());
+        }
+    }
+
+    /// Records the outcome of encoding `bytes` bytes.
+    pub(crate) fn finish_encode(self, result: &EncodeResult, bytes: u64) {
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &self.span {
+            crate::instrument::record_encode(span, result, bytes, self.start);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.on_encode(bytes, self.start.elapsed(), result.is_ok());
+        }
+    }
+}
+
+/// Reader or writer which counts the bytes passing through it.
+pub(crate) struct Counting<T> {
+    pub(crate) inner: T,
+    pub(crate) count: u64,
+}
+impl<T> Counting<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Counting { inner, count: 0 }
+    }
+}
+impl<T: io::Read> io::Read for Counting<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+impl<T: io::Write> io::Write for Counting<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+#[cfg(feature = "tokio-async")]
+impl<T: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for Counting<T> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = std::pin::Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.count += (buf.filled().len() - before) as u64;
+        poll
+    }
+}
+#[cfg(feature = "tokio-async")]
+impl<T: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for Counting<T> {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let poll = std::pin::Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let std::task::Poll::Ready(Ok(n)) = poll {
+            self.count += n as u64;
+        }
+        poll
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records the calls as `(decode, bytes, ok)`.
+    #[derive(Default)]
+    struct Calls(Mutex<Vec<(bool, u64, bool)>>);
+    impl CodecMetrics for Calls {
+        fn on_decode(&self, bytes: u64, _elapsed: Duration, ok: bool) {
+            self.0.lock().unwrap().push((true, bytes, ok));
+        }
+
+        fn on_encode(&self, bytes: u64, _elapsed: Duration, ok: bool) {
+            self.0.lock().unwrap().push((false, bytes, ok));
+        }
+    }
+    impl Calls {
+        fn take(&self) -> Vec<(bool, u64, bool)> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    fn term() -> Term {
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("ok")),
+            Term::from(Binary::from(vec![1, 2, 3])),
+        ]))
+    }
+
+    #[test]
+    fn sync_codec_reports_terms() {
+        let calls = Arc::new(Calls::default());
+        let mut bytes = Vec::new();
+        let encoder = Encoder::new(&mut bytes).with_metrics(calls.clone());
+        encoder.encode(&term()).unwrap();
+        let len = bytes.len() as u64;
+        assert_eq!(calls.take(), [(false, len, true)]);
+
+        let mut reader = &bytes[..];
+        let mut decoder = Decoder::new(&mut reader).with_metrics(calls.clone());
+        assert_eq!(decoder.decode_next().unwrap(), term());
+        assert!(decoder.decode_next().is_err());
+        assert_eq!(calls.take(), [(true, len, true), (true, 0, false)]);
+
+        let decoder = Decoder::new(&bytes[..10]).with_metrics(calls.clone());
+        assert!(decoder.decode().is_err());
+        assert_eq!(calls.take(), [(true, 10, false)]);
+
+        let nan = Term::Float(Float { value: f64::NAN });
+        let options = EncoderOptions {
+            new_floats: false,
+            ..EncoderOptions::default()
+        };
+        let encoder = Encoder::with_options(Vec::new(), options).with_metrics(calls.clone());
+        assert!(encoder.encode(&nan).is_err());
+        assert_eq!(calls.take(), [(false, 1, false)]);
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_codec_reports_terms() {
+        let calls = Arc::new(Calls::default());
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let mut bytes = Vec::new();
+                let encoder = AsyncEncoder::new(&mut bytes).with_metrics(calls.clone());
+                encoder.encode(&term()).await.unwrap();
+                let decoder = AsyncDecoder::new(&bytes[..]).with_metrics(calls.clone());
+                assert_eq!(decoder.decode().await.unwrap(), term());
+                let decoder = AsyncDecoder::new(&bytes[..10]).with_metrics(calls.clone());
+                assert!(decoder.decode().await.is_err());
+                let len = bytes.len() as u64;
+                let expected = [(false, len, true), (true, len, true), (true, 10, false)];
+                assert_eq!(calls.take(), expected);
+
+                // The bytes read past a compressed term count for the next term.
+                let mut bytes = Vec::new();
+                term().encode_compressed(&mut bytes).unwrap();
+                let compressed_len = bytes.len() as u64;
+                term().encode(&mut bytes).unwrap();
+                let mut decoder = AsyncDecoder::new(&bytes[..]).with_metrics(calls.clone());
+                assert_eq!(decoder.decode_next().await.unwrap(), term());
+                assert_eq!(decoder.position(), compressed_len);
+                assert_eq!(decoder.decode_next().await.unwrap(), term());
+                let expected = [(true, compressed_len, true), (true, len, true)];
+                assert_eq!(calls.take(), expected);
+            });
+    }
+}
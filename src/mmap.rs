@@ -0,0 +1,111 @@
+//! Decoding of memory-mapped files.
+//!
+//! The files are mapped with [memmap2](https://docs.rs/memmap2) and decoded like
+//! [`decode_from_slice`] and [`decode_view`], so the kernel pages the input in on demand
+//! instead of it being copied through a reader.
+//!
+//! A mapped file must not be modified while it is mapped: the decoded terms would be
+//! unspecified, and truncating the file can crash the process (e.g., with `SIGBUS`).
+use super::*;
+use crate::codec_common::*;
+use crate::zlib::{self, Zlib};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Decodes the term which occupies the whole of the file at `path`.
+///
+/// Compressed terms are inflated into memory.
+pub fn decode_file<P: AsRef<Path>>(path: P) -> DecodeResult {
+    decode_from_slice(&map(path.as_ref())?)
+}
+
+/// Decodes the term in the file at `path` into a view, and returns the result of `f` for it.
+///
+/// The view borrows from the mapped file, which is unmapped when `f` returns;
+/// use [`MappedTerm`] to keep the mapping around.
+pub fn decode_file_view<P, F, T>(path: P, f: F) -> Result<T, DecodeError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(TermView<'_>) -> T,
+{
+    let term = MappedTerm::open(path)?;
+    Ok(f(term.view()?))
+}
+
+/// Term file which is memory-mapped, so that views can borrow from it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use eetf::{MappedTerm, TermView};
+///
+/// let term = MappedTerm::open("dump.etf")?;
+/// if let TermView::Tuple(elements) = term.view()? {
+///     println!("{} elements", elements.len());
+/// }
+/// # Ok::<(), eetf::DecodeError>(())
+/// ```
+pub struct MappedTerm {
+    bytes: Bytes,
+}
+
+enum Bytes {
+    Mapped(Mmap),
+    /// Views cannot borrow from compressed terms, so these are inflated into memory.
+    Inflated(Vec<u8>),
+}
+
+impl MappedTerm {
+    /// Maps the file at `path`.
+    ///
+    /// Empty files are rejected with [`DecodeError::EmptyFile`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecodeError> {
+        let mmap = map(path.as_ref())?;
+        if mmap.len() < 6 || mmap[0] != VERSION || mmap[1] != COMPRESSED_TERM {
+            return Ok(MappedTerm {
+                bytes: Bytes::Mapped(mmap),
+            });
+        }
+        let uncompressed_size = u32::from_be_bytes([mmap[2], mmap[3], mmap[4], mmap[5]]) as usize;
+        let compressed = &mmap[6..];
+        let mut bytes = Vec::with_capacity(1 + uncompressed_size.min(compressed.len() * 8));
+        bytes.push(VERSION);
+        let mut zlib_decoder = zlib::Backend::slice_decoder(compressed)?;
+        zlib_decoder.read_to_end(&mut bytes)?;
+        Ok(MappedTerm {
+            bytes: Bytes::Inflated(bytes),
+        })
+    }
+
+    /// Returns the encoding of the term (inflated if the file holds a compressed term).
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.bytes {
+            Bytes::Mapped(ref x) => x,
+            Bytes::Inflated(ref x) => x,
+        }
+    }
+
+    /// Decodes the term into a view borrowing from the file.
+    pub fn view(&self) -> Result<TermView<'_>, DecodeError> {
+        decode_view(self.as_bytes())
+    }
+
+    /// Decodes the term into an owned term.
+    pub fn decode(&self) -> DecodeResult {
+        decode_from_slice(self.as_bytes())
+    }
+}
+
+fn map(path: &Path) -> Result<Mmap, DecodeError> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Err(DecodeError::EmptyFile {
+            path: path.to_path_buf(),
+        });
+    }
+    // SAFETY: The file must not be modified while it is mapped (see the module documentation).
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(mmap)
+}
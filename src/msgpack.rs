@@ -0,0 +1,405 @@
+//! Conversion between terms and MessagePack values (`rmpv::Value`).
+//!
+//! | Term         | Lossy mode                                      | Reversible mode                            |
+//! |--------------|-------------------------------------------------|--------------------------------------------|
+//! | `Atom`       | `Boolean` / `Nil` (for the null atom) or `Str`  | `Ext(EXT_ATOM, name)`                      |
+//! | `FixInteger` | `Integer`                                       | `Integer`                                  |
+//! | `BigInteger` | `Integer` if it fits, else `Ext(EXT_BIG_INTEGER, bytes)` | `Ext(EXT_BIG_INTEGER, bytes)`     |
+//! | `Float`      | `F64`                                           | `F64`                                      |
+//! | `Binary`     | `Bin`                                           | `Bin`                                      |
+//! | `ByteList`   | `Array` of integers                             | `Ext(EXT_BYTE_LIST, bytes)`                |
+//! | `List`       | `Array`                                         | `Array`                                    |
+//! | `Tuple`      | `Array`                                         | `Array` starting with `Ext(EXT_TUPLE, [])` |
+//! | `Map`        | `Map`                                           | `Map`                                      |
+//! | `ImproperList` | `Str` formatted by `Display`                  | `Array` of `Ext(EXT_IMPROPER_LIST, [])`, the elements and the tail |
+//! | `InternalFun`  | `Str` formatted by `Display`                  | `Array` of `Ext(EXT_FUN, fun without free variables)` and the free variables |
+//! | other terms  | `Str` formatted by `Display`                    | `Ext(EXT_TERM, external term format)`      |
+//!
+//! The nested terms of improper lists and funs are converted recursively, so
+//! that they keep their types. The payload of `EXT_FUN` is the external term
+//! format of the fun with its free variables removed.
+//!
+//! Big integers are encoded as big-endian two's complement bytes.
+//!
+//! In the other direction, `Str` becomes a UTF-8 `Binary`, `Boolean` and `Nil`
+//! become atoms, and all of the extension types above are recognized regardless
+//! of the mode.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::msgpack::MsgpackOptions;
+//! use eetf::{Atom, Term, Tuple};
+//!
+//! let term = Term::from(Tuple::from(vec![
+//!     Term::from(Atom::from("ok")),
+//!     Term::from(1),
+//! ]));
+//!
+//! let options = MsgpackOptions::reversible();
+//! let value = term.to_msgpack_value(&options).unwrap();
+//! assert_eq!(Term::from_msgpack_value(&value, &options).unwrap(), term);
+//!
+//! let value = term.to_msgpack_value(&MsgpackOptions::new()).unwrap();
+//! assert_eq!(value, rmpv::Value::Array(vec!["ok".into(), 1.into()]));
+//! ```
+use super::*;
+use num::traits::ToPrimitive;
+use rmpv::Value;
+
+/// Extension type of atoms (the payload is the UTF-8 name).
+pub const EXT_ATOM: i8 = 1;
+
+/// Extension type of big integers (the payload is big-endian two's complement bytes).
+pub const EXT_BIG_INTEGER: i8 = 2;
+
+/// Extension type of the tuple marker which is the first element of a tuple array.
+pub const EXT_TUPLE: i8 = 3;
+
+/// Extension type of byte lists (the payload is the bytes).
+pub const EXT_BYTE_LIST: i8 = 4;
+
+/// Extension type of terms without a MessagePack counterpart (the payload is the external term format).
+pub const EXT_TERM: i8 = 5;
+
+/// Extension type of the improper list marker which is the first element of an improper list array.
+pub const EXT_IMPROPER_LIST: i8 = 6;
+
+/// Extension type of the fun marker which is the first element of an internal fun array.
+pub const EXT_FUN: i8 = 7;
+
+/// Errors which can occur when converting between terms and MessagePack values.
+#[derive(Debug, thiserror::Error)]
+pub enum MsgpackError {
+    #[error("unknown MessagePack extension type {0}")]
+    UnknownExtType(i8),
+
+    #[error("invalid payload of MessagePack extension type {ext_type}: {reason}")]
+    InvalidExt { ext_type: i8, reason: String },
+
+    #[error("MessagePack value {0} has no term counterpart")]
+    Unsupported(Value),
+
+    #[error("failed to encode a term embedded in a MessagePack extension")]
+    Encode(#[from] EncodeError),
+
+    #[error("failed to decode a term embedded in a MessagePack extension")]
+    Decode(#[from] DecodeError),
+}
+
+/// Options for converting between terms and MessagePack values.
+#[derive(Debug, Clone)]
+pub struct MsgpackOptions {
+    reversible: bool,
+    null_atom: String,
+}
+impl MsgpackOptions {
+    /// Makes the default (human-readable and lossy) options.
+    pub fn new() -> Self {
+        MsgpackOptions {
+            reversible: false,
+            null_atom: "nil".to_string(),
+        }
+    }
+
+    /// Makes options with which every term can be reconstructed from its MessagePack value.
+    pub fn reversible() -> Self {
+        Self::new().set_reversible(true)
+    }
+
+    /// Sets whether extension types are used to keep the conversion reversible (default: `false`).
+    pub fn set_reversible(mut self, reversible: bool) -> Self {
+        self.reversible = reversible;
+        self
+    }
+
+    /// Sets the atom which corresponds to `Nil` (default: `nil`).
+    pub fn null_atom(mut self, name: &str) -> Self {
+        self.null_atom = name.to_string();
+        self
+    }
+}
+impl Default for MsgpackOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid_ext<E: fmt::Display>(ext_type: i8) -> impl FnOnce(E) -> MsgpackError {
+    move |e| MsgpackError::InvalidExt {
+        ext_type,
+        reason: e.to_string(),
+    }
+}
+
+impl Term {
+    /// Converts the term into a MessagePack value.
+    pub fn to_msgpack_value(&self, options: &MsgpackOptions) -> Result<Value, MsgpackError> {
+        let reversible = options.reversible;
+        Ok(match self {
+            Term::Atom(x) if reversible => Value::Ext(EXT_ATOM, x.name.as_bytes().to_vec()),
+            Term::Atom(x) if x.as_str() == "true" => Value::Boolean(true),
+            Term::Atom(x) if x.as_str() == "false" => Value::Boolean(false),
+            Term::Atom(x) if x.as_str() == options.null_atom => Value::Nil,
+            Term::Atom(x) => Value::from(x.as_str()),
+            Term::FixInteger(x) => Value::from(x.value),
+            Term::BigInteger(x) => match (reversible, x.value.to_i64(), x.value.to_u64()) {
+                (false, Some(v), _) => Value::from(v),
+                (false, None, Some(v)) => Value::from(v),
+                _ => Value::Ext(EXT_BIG_INTEGER, x.value.to_signed_bytes_be()),
+            },
+            Term::Float(x) => Value::F64(x.value),
+            Term::Binary(x) => Value::Binary(x.bytes.to_vec()),
+            Term::ByteList(x) if reversible => Value::Ext(EXT_BYTE_LIST, x.bytes.clone()),
+            Term::ByteList(x) => Value::Array(x.bytes.iter().map(|&b| Value::from(b)).collect()),
+            Term::List(x) => Value::Array(
+                x.elements
+                    .iter()
+                    .map(|e| e.to_msgpack_value(options))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Term::Tuple(x) => {
+                let marker = reversible.then(|| Ok(Value::Ext(EXT_TUPLE, Vec::new())));
+                Value::Array(
+                    marker
+                        .into_iter()
+                        .chain(x.elements.iter().map(|e| e.to_msgpack_value(options)))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            Term::Map(x) => Value::Map(
+                x.map
+                    .iter()
+                    .map(|(k, v)| Ok((k.to_msgpack_value(options)?, v.to_msgpack_value(options)?)))
+                    .collect::<Result<_, MsgpackError>>()?,
+            ),
+            Term::ImproperList(x) if reversible => {
+                let elements = x.elements.iter().chain(std::iter::once(&*x.last));
+                marked_array(Value::Ext(EXT_IMPROPER_LIST, Vec::new()), elements, options)?
+            }
+            Term::InternalFun(x) if reversible => {
+                let mut bare = (**x).clone();
+                let free_vars = std::mem::take(free_vars_mut(&mut bare));
+                let mut buf = Vec::new();
+                Term::from(bare).encode(&mut buf)?;
+                marked_array(Value::Ext(EXT_FUN, buf), free_vars.iter(), options)?
+            }
+            _ if reversible => {
+                let mut buf = Vec::new();
+                self.encode(&mut buf)?;
+                Value::Ext(EXT_TERM, buf)
+            }
+            _ => Value::from(self.to_string()),
+        })
+    }
+
+    /// Converts a MessagePack value into a term.
+    pub fn from_msgpack_value(
+        value: &Value,
+        options: &MsgpackOptions,
+    ) -> Result<Self, MsgpackError> {
+        Ok(match value {
+            Value::Nil => Term::from(Atom::from(options.null_atom.as_str())),
+            Value::Boolean(b) => Term::from(*b),
+            Value::Integer(n) => {
+                if let Some(v) = n.as_i64() {
+                    match i32::try_from(v) {
+                        Ok(v) => Term::from(v),
+                        Err(_) => Term::from(BigInteger::from(v)),
+                    }
+                } else {
+                    Term::from(BigInteger::from(n.as_u64().expect("never fails")))
+                }
+            }
+            Value::F32(f) => float(f64::from(*f), value)?,
+            Value::F64(f) => float(*f, value)?,
+            Value::String(s) => Term::from(Binary::from(s.as_bytes())),
+            Value::Binary(bytes) => Term::from(Binary::from(bytes.as_slice())),
+            Value::Array(elements) => match elements.split_first() {
+                Some((Value::Ext(EXT_TUPLE, marker), rest)) if marker.is_empty() => {
+                    Term::from(Tuple::from(elements_from_msgpack(rest, options)?))
+                }
+                Some((Value::Ext(EXT_IMPROPER_LIST, marker), rest))
+                    if marker.is_empty() && rest.len() >= 2 =>
+                {
+                    let mut elements = elements_from_msgpack(rest, options)?;
+                    let last = elements.pop().expect("never fails");
+                    Term::from(ImproperList::from((elements, last)))
+                }
+                Some((Value::Ext(EXT_FUN, fun), rest)) => {
+                    fun_from_msgpack(fun, elements_from_msgpack(rest, options)?)?
+                }
+                _ => Term::from(List::from(elements_from_msgpack(elements, options)?)),
+            },
+            Value::Map(entries) => Term::from(Map::from(
+                entries
+                    .iter()
+                    .map(|(k, v)| {
+                        Ok((
+                            Term::from_msgpack_value(k, options)?,
+                            Term::from_msgpack_value(v, options)?,
+                        ))
+                    })
+                    .collect::<Result<TermMap, MsgpackError>>()?,
+            )),
+            Value::Ext(ext_type, data) => ext_to_term(*ext_type, data)?,
+        })
+    }
+}
+
+fn marked_array<'a>(
+    marker: Value,
+    elements: impl Iterator<Item = &'a Term>,
+    options: &MsgpackOptions,
+) -> Result<Value, MsgpackError> {
+    std::iter::once(Ok(marker))
+        .chain(elements.map(|e| e.to_msgpack_value(options)))
+        .collect::<Result<_, _>>()
+        .map(Value::Array)
+}
+
+fn free_vars_mut(fun: &mut InternalFun) -> &mut Vec<Term> {
+    match fun {
+        InternalFun::Old { free_vars, .. } | InternalFun::New { free_vars, .. } => free_vars,
+    }
+}
+
+fn fun_from_msgpack(data: &[u8], vars: Vec<Term>) -> Result<Term, MsgpackError> {
+    match Term::decode(io::Cursor::new(data))? {
+        Term::InternalFun(mut fun) => {
+            *free_vars_mut(&mut fun) = vars;
+            Ok(Term::InternalFun(fun))
+        }
+        _ => Err(invalid_ext(EXT_FUN)("the payload is not an internal fun")),
+    }
+}
+
+fn float(f: f64, value: &Value) -> Result<Term, MsgpackError> {
+    Float::try_from(f)
+        .map(Term::from)
+        .map_err(|_| MsgpackError::Unsupported(value.clone()))
+}
+
+fn elements_from_msgpack(
+    elements: &[Value],
+    options: &MsgpackOptions,
+) -> Result<Vec<Term>, MsgpackError> {
+    elements
+        .iter()
+        .map(|e| Term::from_msgpack_value(e, options))
+        .collect()
+}
+
+fn ext_to_term(ext_type: i8, data: &[u8]) -> Result<Term, MsgpackError> {
+    Ok(match ext_type {
+        EXT_ATOM => {
+            let name = std::str::from_utf8(data).map_err(invalid_ext(ext_type))?;
+            Term::from(Atom::from(name))
+        }
+        EXT_BIG_INTEGER => {
+            if data.is_empty() {
+                return Err(invalid_ext(ext_type)("empty payload"));
+            }
+            Term::from(BigInteger {
+                value: BigInt::from_signed_bytes_be(data),
+            })
+        }
+        EXT_BYTE_LIST => Term::from(ByteList::from(data.to_vec())),
+        EXT_TERM => Term::decode(io::Cursor::new(data))?,
+        EXT_TUPLE => return Err(invalid_ext(ext_type)("tuple marker outside of an array")),
+        EXT_IMPROPER_LIST => {
+            return Err(invalid_ext(ext_type)(
+                "improper list marker outside of an array",
+            ))
+        }
+        EXT_FUN => return Err(invalid_ext(ext_type)("fun marker outside of an array")),
+        _ => return Err(MsgpackError::UnknownExtType(ext_type)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    proptest! {
+        #[test]
+        fn reversible_mode_round_trips_every_term(term in any::<Term>()) {
+            let options = MsgpackOptions::reversible();
+            let value = term.to_msgpack_value(&options).unwrap();
+
+            let mut buf = Vec::new();
+            rmpv::encode::write_value(&mut buf, &value).unwrap();
+            let value = rmpv::decode::read_value(&mut buf.as_slice()).unwrap();
+
+            prop_assert_eq!(Term::from_msgpack_value(&value, &options).unwrap(), term);
+        }
+    }
+
+    #[test]
+    fn reversible_mode_keeps_nested_lists() {
+        let options = MsgpackOptions::reversible();
+        let term = Term::from(ImproperList::from((
+            vec![Term::from(List::from(vec![Term::from(1)]))],
+            atom("t"),
+        )));
+        let value = term.to_msgpack_value(&options).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Ext(EXT_IMPROPER_LIST, vec![]),
+                Value::Array(vec![Value::from(1)]),
+                Value::Ext(EXT_ATOM, b"t".to_vec()),
+            ])
+        );
+        assert_eq!(Term::from_msgpack_value(&value, &options).unwrap(), term);
+    }
+
+    #[test]
+    fn lossy_mode_works() {
+        let options = MsgpackOptions::new();
+        let pid = Term::from(Pid::from(("node", 1, 0)));
+        let big = Term::from(BigInteger {
+            value: BigInt::from(u64::MAX) + 1,
+        });
+        let term = Term::from(Tuple::from(vec![
+            atom("true"),
+            atom("nil"),
+            atom("joe"),
+            Term::from(BigInteger::from(u64::MAX)),
+            big.clone(),
+            Term::from(Binary::from(vec![0, 255])),
+            Term::from(ByteList::from(vec![1, 2])),
+            pid.clone(),
+        ]));
+        let value = term.to_msgpack_value(&options).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Boolean(true),
+                Value::Nil,
+                Value::from("joe"),
+                Value::from(u64::MAX),
+                Value::Ext(EXT_BIG_INTEGER, vec![1, 0, 0, 0, 0, 0, 0, 0, 0]),
+                Value::Binary(vec![0, 255]),
+                Value::Array(vec![Value::from(1), Value::from(2)]),
+                Value::from(pid.to_string()),
+            ])
+        );
+
+        let back = Term::from_msgpack_value(&value, &options).unwrap();
+        let list: &List = crate::convert::TryAsRef::try_as_ref(&back).unwrap();
+        assert_eq!(list.elements[0], atom("true"));
+        assert_eq!(list.elements[2], Term::from(Binary::from("joe".as_bytes())));
+        assert_eq!(list.elements[4], big);
+
+        assert!(matches!(
+            Term::from_msgpack_value(&Value::Ext(42, vec![]), &options),
+            Err(MsgpackError::UnknownExtType(42))
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+//! Conversion between [`Term`] and [rustler](https://docs.rs/rustler) NIF terms.
+//!
+//! Atoms, integers, floats, binaries, lists, tuples and maps are converted element by
+//! element. Big integers, bit binaries, pids, ports, references and external funs go
+//! through the external term format (`term_to_binary/1` and `binary_to_term/1`) because
+//! the NIF API has no other way to build or inspect them.
+//!
+//! Local funs (`Term::InternalFun`) cannot cross the boundary in either direction:
+//! they refer to code loaded in a particular VM, and converting them fails with
+//! [`NifConversionError::InternalFun`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use eetf::Term;
+//!
+//! #[rustler::nif]
+//! fn echo<'a>(env: rustler::Env<'a>, term: rustler::Term<'a>) -> rustler::NifResult<Term> {
+//!     Term::from_nif_term(env, term).map_err(|e| rustler::Error::Term(Box::new(e.to_string())))
+//! }
+//! ```
+use super::*;
+use rustler::types::atom::Atom as NifAtom;
+use rustler::types::tuple;
+use rustler::{Encoder as _, NewBinary, TermType};
+
+/// Errors which can occur when converting a term to or from a NIF term.
+#[derive(Debug, thiserror::Error)]
+pub enum NifConversionError {
+    #[error("local funs cannot be converted between eetf and NIF terms")]
+    InternalFun,
+
+    #[error("the NIF term has an unknown type")]
+    UnknownType,
+
+    #[error("the NIF term could not be inspected as {expected}")]
+    Inspect { expected: &'static str },
+
+    #[error("the VM could not create the atom {name:?}")]
+    InvalidAtom { name: String },
+
+    #[error("the VM rejected the external term format of {value}")]
+    BinaryToTerm { value: Term },
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+impl Term {
+    /// Converts a NIF term to a term.
+    ///
+    /// `term` is copied into `env` first if it belongs to another environment (e.g., an
+    /// [`OwnedEnv`](rustler::OwnedEnv)).
+    pub fn from_nif_term<'a>(
+        env: rustler::Env<'a>,
+        term: rustler::Term<'a>,
+    ) -> Result<Self, NifConversionError> {
+        from_nif(term.in_env(env))
+    }
+
+    /// Converts the term to a NIF term.
+    pub fn to_nif_term<'a>(
+        &self,
+        env: rustler::Env<'a>,
+    ) -> Result<rustler::Term<'a>, NifConversionError> {
+        match self {
+            Term::Atom(x) => NifAtom::from_str(env, &x.name)
+                .map(|atom| atom.to_term(env))
+                .map_err(|_| NifConversionError::InvalidAtom {
+                    name: x.name.to_string(),
+                }),
+            Term::FixInteger(x) => Ok(x.value.encode(env)),
+            Term::Float(x) => Ok(x.value.encode(env)),
+            Term::Binary(x) => {
+                let mut binary = NewBinary::new(env, x.bytes.len());
+                binary.as_mut_slice().copy_from_slice(&x.bytes);
+                Ok(binary.into())
+            }
+            Term::ByteList(x) => Ok(x.bytes.encode(env)),
+            Term::List(x) => {
+                let nil = rustler::Term::list_new_empty(env);
+                prepend_all(env, &x.elements, nil)
+            }
+            Term::ImproperList(x) => {
+                let last = x.last.to_nif_term(env)?;
+                prepend_all(env, &x.elements, last)
+            }
+            Term::Tuple(x) => {
+                let elements = x
+                    .elements
+                    .iter()
+                    .map(|e| e.to_nif_term(env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(tuple::make_tuple(env, &elements))
+            }
+            Term::Map(x) => {
+                let mut keys = Vec::with_capacity(x.map.len());
+                let mut values = Vec::with_capacity(x.map.len());
+                for (k, v) in &x.map {
+                    keys.push(k.to_nif_term(env)?);
+                    values.push(v.to_nif_term(env)?);
+                }
+                rustler::Term::map_from_term_arrays(env, &keys, &values)
+                    .map_err(|_| inspect("a map"))
+            }
+            Term::InternalFun(_) => Err(NifConversionError::InternalFun),
+            Term::BigInteger(_)
+            | Term::Pid(_)
+            | Term::Port(_)
+            | Term::Reference(_)
+            | Term::ExternalFun(_)
+            | Term::BitBinary(_)
+            | Term::Raw(_) => {
+                let mut buf = Vec::new();
+                self.encode(&mut buf)?;
+                match env.binary_to_term(&buf) {
+                    Some((term, _)) => Ok(term),
+                    None => Err(NifConversionError::BinaryToTerm {
+                        value: self.clone(),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+/// Builds NIF terms with [`Term::to_nif_term`].
+///
+/// # Panics
+///
+/// Panics if the conversion fails (e.g., for local funs). Rustler turns a panic in a NIF
+/// into an exception, but callers which want to handle the error should use
+/// [`Term::to_nif_term`] instead.
+impl rustler::Encoder for Term {
+    fn encode<'a>(&self, env: rustler::Env<'a>) -> rustler::Term<'a> {
+        match self.to_nif_term(env) {
+            Ok(term) => term,
+            Err(e) => panic!("cannot convert {} to a NIF term: {}", self, e),
+        }
+    }
+}
+
+fn inspect(expected: &'static str) -> NifConversionError {
+    NifConversionError::Inspect { expected }
+}
+
+fn prepend_all<'a>(
+    env: rustler::Env<'a>,
+    elements: &[Term],
+    tail: rustler::Term<'a>,
+) -> Result<rustler::Term<'a>, NifConversionError> {
+    let mut list = tail;
+    for e in elements.iter().rev() {
+        list = list.list_prepend(e.to_nif_term(env)?);
+    }
+    Ok(list)
+}
+
+fn from_nif(term: rustler::Term) -> Result<Term, NifConversionError> {
+    match term.get_type() {
+        TermType::Atom => term
+            .atom_to_string()
+            .map(|name| Term::from(Atom::from(name)))
+            .map_err(|_| inspect("an atom")),
+        TermType::Integer => match term.decode::<i64>() {
+            Ok(value) => Ok(match i32::try_from(value) {
+                Ok(value) => Term::from(FixInteger::from(value)),
+                Err(_) => Term::from(BigInteger::from(value)),
+            }),
+            Err(_) => from_external_format(term),
+        },
+        TermType::Float => term
+            .decode::<f64>()
+            .map(|value| Term::Float(Float { value }))
+            .map_err(|_| inspect("a float")),
+        TermType::Binary => match term.decode::<rustler::Binary>() {
+            Ok(binary) => Ok(Term::from(Binary::from(binary.as_slice()))),
+            Err(_) => from_external_format(term),
+        },
+        TermType::List => {
+            let mut elements = Vec::new();
+            let mut rest = term;
+            while !rest.is_empty_list() {
+                if !rest.is_list() {
+                    let tail = from_nif(rest)?;
+                    return Ok(Term::from(ImproperList::from((elements, tail))));
+                }
+                let (head, tail) = rest.list_get_cell().map_err(|_| inspect("a list"))?;
+                elements.push(from_nif(head)?);
+                rest = tail;
+            }
+            Ok(Term::from(List::from(elements)))
+        }
+        TermType::Tuple => {
+            let elements = tuple::get_tuple(term).map_err(|_| inspect("a tuple"))?;
+            let elements = elements
+                .into_iter()
+                .map(from_nif)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Term::from(Tuple::from(elements)))
+        }
+        TermType::Map => {
+            let iter = rustler::MapIterator::new(term).ok_or_else(|| inspect("a map"))?;
+            let mut map = TermMap::new();
+            for (key, value) in iter {
+                map.insert(from_nif(key)?, from_nif(value)?);
+            }
+            Ok(Term::from(Map::from(map)))
+        }
+        TermType::Fun | TermType::Pid | TermType::Port | TermType::Ref => {
+            from_external_format(term)
+        }
+        TermType::Unknown => Err(NifConversionError::UnknownType),
+    }
+}
+
+fn from_external_format(term: rustler::Term) -> Result<Term, NifConversionError> {
+    let binary = term.to_binary();
+    match Term::decode(binary.as_slice())? {
+        Term::InternalFun(_) => Err(NifConversionError::InternalFun),
+        term => Ok(term),
+    }
+}
@@ -0,0 +1,200 @@
+//! Node names (i.e., `Alive@Host`).
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::NodeName;
+//!
+//! let name = NodeName::parse("app@db01.internal").unwrap();
+//! assert_eq!(name.alive(), "app");
+//! assert_eq!(name.host(), "db01.internal");
+//! assert!(name.is_long_name());
+//! assert!(NodeName::parse("a@b@c").is_err());
+//! ```
+use super::*;
+
+/// Errors which can occur when parsing a node name.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum NodeNameError {
+    #[error("node name {name:?} has no '@'")]
+    MissingAt { name: String },
+
+    #[error("node name {name:?} has more than one '@'")]
+    MultipleAt { name: String },
+
+    #[error("node name {name:?} has an empty part")]
+    EmptyPart { name: String },
+
+    #[error("node name {name:?} contains invalid character {c:?}")]
+    InvalidChar { name: String, c: char },
+}
+
+/// Node name.
+///
+/// Names made by [`NodeName::parse`] and [`TryFrom<&Atom>`] are validated. The `From`
+/// conversions (used by the constructors of pids, ports and references) are not,
+/// as node atoms in decoded terms are not either.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NodeName {
+    atom: Atom,
+}
+impl NodeName {
+    /// Parses and validates `Alive@Host`.
+    ///
+    /// Both parts must be non-empty; the alive part consists of ASCII alphanumerics, `_`
+    /// and `-`, and the host part additionally of `.`.
+    pub fn parse(name: &str) -> Result<Self, NodeNameError> {
+        let error_name = || name.to_owned();
+        let (alive, host) = name
+            .split_once('@')
+            .ok_or_else(|| NodeNameError::MissingAt { name: error_name() })?;
+        if host.contains('@') {
+            return Err(NodeNameError::MultipleAt { name: error_name() });
+        }
+        if alive.is_empty() || host.is_empty() {
+            return Err(NodeNameError::EmptyPart { name: error_name() });
+        }
+        let is_alive_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        let invalid = alive
+            .chars()
+            .find(|&c| !is_alive_char(c))
+            .or_else(|| host.chars().find(|&c| !is_alive_char(c) && c != '.'));
+        if let Some(c) = invalid {
+            return Err(NodeNameError::InvalidChar {
+                name: error_name(),
+                c,
+            });
+        }
+        Ok(NodeName {
+            atom: Atom::from(name),
+        })
+    }
+
+    /// Returns the name.
+    pub fn as_str(&self) -> &str {
+        &self.atom.name
+    }
+
+    /// Returns the part preceding `@` (i.e., the name registered in EPMD).
+    pub fn alive(&self) -> &str {
+        self.split().0
+    }
+
+    /// Returns the part following `@`.
+    pub fn host(&self) -> &str {
+        self.split().1
+    }
+
+    /// Returns `true` if the host is not qualified (i.e., the node uses `-sname`).
+    pub fn is_short_name(&self) -> bool {
+        !self.host().contains('.')
+    }
+
+    /// Returns `true` if the host is qualified (i.e., the node uses `-name`).
+    pub fn is_long_name(&self) -> bool {
+        !self.is_short_name()
+    }
+
+    fn split(&self) -> (&str, &str) {
+        self.as_str().split_once('@').unwrap_or((self.as_str(), ""))
+    }
+}
+impl fmt::Display for NodeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl From<Atom> for NodeName {
+    fn from(atom: Atom) -> Self {
+        NodeName { atom }
+    }
+}
+impl<'a> From<&'a str> for NodeName {
+    fn from(name: &'a str) -> Self {
+        NodeName::from(Atom::from(name))
+    }
+}
+impl From<String> for NodeName {
+    fn from(name: String) -> Self {
+        NodeName::from(Atom::from(name))
+    }
+}
+impl<'a> TryFrom<&'a Atom> for NodeName {
+    type Error = NodeNameError;
+
+    fn try_from(atom: &'a Atom) -> Result<Self, Self::Error> {
+        NodeName::parse(&atom.name)
+    }
+}
+impl core::str::FromStr for NodeName {
+    type Err = NodeNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NodeName::parse(s)
+    }
+}
+impl From<NodeName> for Atom {
+    fn from(name: NodeName) -> Self {
+        name.atom
+    }
+}
+impl AsRef<str> for NodeName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names_work() {
+        let name = NodeName::parse("app@localhost").unwrap();
+        assert_eq!((name.alive(), name.host()), ("app", "localhost"));
+        assert!(name.is_short_name());
+        assert!(!name.is_long_name());
+
+        let name: NodeName = "my_app-2@db01.internal".parse().unwrap();
+        assert_eq!((name.alive(), name.host()), ("my_app-2", "db01.internal"));
+        assert!(name.is_long_name());
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(matches!(
+            NodeName::parse("a@b@c"),
+            Err(NodeNameError::MultipleAt { .. })
+        ));
+        assert!(matches!(
+            NodeName::parse("app"),
+            Err(NodeNameError::MissingAt { .. })
+        ));
+        for name in ["@host", "app@", "@"] {
+            assert!(matches!(
+                NodeName::parse(name),
+                Err(NodeNameError::EmptyPart { .. })
+            ));
+        }
+        assert!(matches!(
+            NodeName::parse("a.b@host"),
+            Err(NodeNameError::InvalidChar { c: '.', .. })
+        ));
+        assert!(matches!(
+            NodeName::parse("app@ho st"),
+            Err(NodeNameError::InvalidChar { c: ' ', .. })
+        ));
+    }
+
+    #[test]
+    fn atom_round_trip_works() {
+        let atom = Atom::from("app@localhost");
+        let name = NodeName::try_from(&atom).unwrap();
+        assert_eq!(Atom::from(name), atom);
+        assert!(NodeName::try_from(&Atom::from("nonode")).is_err());
+
+        // Unvalidated names
+        let name = NodeName::from("nonode");
+        assert_eq!((name.alive(), name.host()), ("nonode", ""));
+    }
+}
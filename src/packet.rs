@@ -0,0 +1,677 @@
+//! `{packet, N}` framing of Erlang ports.
+//!
+//! A port opened with `open_port({spawn, Command}, [{packet, N}, binary])` prefixes
+//! each message with its `N` byte big-endian length. The program on the other end
+//! (e.g., a Rust helper reading stdin and writing stdout) does the same.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::packet::{PacketSize, PacketTransport};
+//! use eetf::{Atom, Term};
+//!
+//! let mut output = Vec::new();
+//! let mut transport = PacketTransport::new(&[][..], &mut output, PacketSize::Four);
+//! transport.send_term(&Term::from(Atom::from("ok"))).unwrap();
+//! assert_eq!(output, [0, 0, 0, 6, 131, 100, 0, 2, b'o', b'k']);
+//! ```
+//!
+//! With the `tokio-async` feature, a transport over an [`AsyncRead`](tokio::io::AsyncRead) and
+//! an [`AsyncWrite`](tokio::io::AsyncWrite) receives and sends terms with
+//! [`PacketTransport::recv_term_async`] and [`PacketTransport::send_term_async`].
+use super::*;
+use std::io::{Cursor, Read, Write};
+
+/// Size of the length prefix of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketSize {
+    One,
+    Two,
+    Four,
+}
+impl PacketSize {
+    /// Returns the number of bytes of the length prefix.
+    pub fn bytes(self) -> usize {
+        match self {
+            PacketSize::One => 1,
+            PacketSize::Two => 2,
+            PacketSize::Four => 4,
+        }
+    }
+
+    /// Returns the largest length which the prefix can represent.
+    pub fn max_len(self) -> usize {
+        match self {
+            PacketSize::One => u8::MAX as usize,
+            PacketSize::Two => u16::MAX as usize,
+            PacketSize::Four => u32::MAX as usize,
+        }
+    }
+}
+
+/// What a [`PacketTransport`] does after failing to receive a term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OnError {
+    /// Fails the later receives with [`DecodeError::Poisoned`], as the connection should be
+    /// dropped.
+    #[default]
+    Drop,
+    /// Consumes the rest of the frame whose term failed to decode (or which was too large),
+    /// so that the next frame can be received.
+    ///
+    /// I/O errors (e.g., a stream ending within a frame) still poison the transport.
+    SkipFrame,
+}
+
+/// Counts of the frames which a [`PacketTransport`] sent compressed (see
+/// [`PacketTransport::compress_above`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CompressionStats {
+    /// The number of frames whose term was sent compressed.
+    pub frames_compressed: u64,
+
+    /// The number of bytes by which the compressed frames are shorter than they would be
+    /// without compression.
+    pub bytes_saved: u64,
+}
+
+/// Transport of terms over a pair of `{packet, N}` framed streams.
+#[derive(Debug)]
+pub struct PacketTransport<R, W> {
+    reader: R,
+    writer: W,
+    packet_size: PacketSize,
+    max_frame_size: usize,
+    on_error: OnError,
+    poisoned: bool,
+    compress_above: Option<usize>,
+    compression_stats: CompressionStats,
+    encoder_options: EncoderOptions,
+    decoder_options: DecoderOptions,
+}
+impl<R, W> PacketTransport<R, W> {
+    /// Makes a new transport.
+    pub fn new(reader: R, writer: W, packet_size: PacketSize) -> Self {
+        PacketTransport {
+            reader,
+            writer,
+            packet_size,
+            max_frame_size: packet_size.max_len(),
+            on_error: OnError::default(),
+            poisoned: false,
+            compress_above: None,
+            compression_stats: CompressionStats::default(),
+            encoder_options: EncoderOptions::default(),
+            decoder_options: DecoderOptions::default(),
+        }
+    }
+
+    /// Sets the maximum length of the frames to be received and sent (the default is [`PacketSize::max_len`]).
+    ///
+    /// A received term which is compressed (`COMPRESSED_TERM`) may not inflate to more than the
+    /// maximum either.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max.min(self.packet_size.max_len());
+        self
+    }
+
+    /// Sends the terms whose encoding is longer than `threshold` bytes compressed (as
+    /// `term_to_binary(Term, [compressed])` does), unless that does not make them shorter.
+    ///
+    /// Compressed terms are received whether or not this is set.
+    pub fn compress_above(mut self, threshold: usize) -> Self {
+        self.compress_above = Some(threshold);
+        self
+    }
+
+    /// Sets the options with which the terms are encoded.
+    pub fn encoder_options(mut self, options: EncoderOptions) -> Self {
+        self.encoder_options = options;
+        self
+    }
+
+    /// Sets the options with which the terms are decoded.
+    ///
+    /// The maximum size of a compressed term is at most the maximum length of the frames,
+    /// whatever [`DecoderOptions::max_uncompressed_size`] says.
+    pub fn decoder_options(mut self, options: DecoderOptions) -> Self {
+        self.decoder_options = options;
+        self
+    }
+
+    /// Returns the counts of the frames sent compressed so far.
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.compression_stats
+    }
+
+    /// Sets what the transport does after failing to receive a term (the default is
+    /// [`OnError::Drop`]).
+    pub fn on_error(mut self, on_error: OnError) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// Returns `true` if the transport failed to receive a term and cannot receive any more.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Returns the reader and the writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+
+    /// Decodes the term of a received frame, poisoning the transport if that fails and
+    /// [`OnError::Drop`] is set.
+    fn decode_received(&mut self, frame: &[u8]) -> Result<Term, DecodeError> {
+        let max = self
+            .decoder_options
+            .max_uncompressed_size
+            .unwrap_or(usize::MAX);
+        let options = self
+            .decoder_options
+            .with_max_uncompressed_size(max.min(self.max_frame_size));
+        let result = decode_frame(frame, options);
+        self.poisoned = result.is_err() && self.on_error == OnError::Drop;
+        result
+    }
+
+    /// Encodes the frame of a term to be sent.
+    fn encode_sent(&mut self, term: &Term) -> Result<Vec<u8>, EncodeError> {
+        match self.compress_above {
+            Some(threshold) => self.encode_compressible_frame(term, threshold),
+            None => encode_frame(
+                term,
+                self.packet_size.bytes(),
+                self.max_frame_size,
+                self.encoder_options,
+            ),
+        }
+    }
+
+    /// Encodes a frame, whose term is compressed if it is longer than `threshold` bytes and
+    /// compressing it makes it shorter.
+    fn encode_compressible_frame(
+        &mut self,
+        term: &Term,
+        threshold: usize,
+    ) -> Result<Vec<u8>, EncodeError> {
+        let prefix_len = self.packet_size.bytes();
+        let mut encoded = Vec::new();
+        Encoder::with_options(&mut encoded, self.encoder_options).encode(term)?;
+        let mut frame = vec![0; prefix_len];
+        if encoded.len() > threshold {
+            // The body of a compressed term has no version number.
+            crate::codec::write_compressed(&mut frame, &encoded[1..])?;
+            let uncompressed_len = prefix_len + encoded.len();
+            if frame.len() < uncompressed_len {
+                self.compression_stats.frames_compressed += 1;
+                self.compression_stats.bytes_saved += (uncompressed_len - frame.len()) as u64;
+                return finish_frame(frame, prefix_len, self.max_frame_size);
+            }
+            frame.truncate(prefix_len);
+        }
+        frame.extend_from_slice(&encoded);
+        finish_frame(frame, prefix_len, self.max_frame_size)
+    }
+}
+impl<R: Read, W: Write> PacketTransport<R, W> {
+    /// Receives a term.
+    ///
+    /// Fails with [`DecodeError::EndOfStream`] if the stream ends before a frame,
+    /// and with an `UnexpectedEof` I/O error if it ends within one. Once the transport is
+    /// poisoned (see [`OnError`]), fails with [`DecodeError::Poisoned`].
+    pub fn recv_term(&mut self) -> Result<Term, DecodeError> {
+        if self.poisoned {
+            return Err(DecodeError::Poisoned);
+        }
+        // Until the frame is read in full, an error leaves the stream within it.
+        self.poisoned = true;
+        let Some(len) = read_frame_len(&mut self.reader, self.packet_size.bytes())? else {
+            self.poisoned = false;
+            return Err(DecodeError::EndOfStream);
+        };
+        if let Err(e) = check_frame_len(len, self.max_frame_size) {
+            if self.on_error == OnError::SkipFrame {
+                let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+                if skipped < len as u64 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                self.poisoned = false;
+            }
+            return Err(e);
+        }
+        let mut frame = vec![0; len];
+        self.reader.read_exact(&mut frame)?;
+        self.decode_received(&frame)
+    }
+
+    /// Sends a term.
+    pub fn send_term(&mut self, term: &Term) -> Result<(), EncodeError> {
+        let frame = self.encode_sent(term)?;
+        self.writer.write_all(&frame)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+#[cfg(feature = "tokio-async")]
+impl<R, W> PacketTransport<R, W>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    /// Receives a term from the async reader (see [`PacketTransport::recv_term`]).
+    pub async fn recv_term_async(&mut self) -> Result<Term, DecodeError> {
+        use tokio::io::AsyncReadExt;
+
+        if self.poisoned {
+            return Err(DecodeError::Poisoned);
+        }
+        self.poisoned = true;
+        let Some(len) = read_frame_len_async(&mut self.reader, self.packet_size.bytes()).await?
+        else {
+            self.poisoned = false;
+            return Err(DecodeError::EndOfStream);
+        };
+        if let Err(e) = check_frame_len(len, self.max_frame_size) {
+            if self.on_error == OnError::SkipFrame {
+                let skipped = tokio::io::copy(
+                    &mut (&mut self.reader).take(len as u64),
+                    &mut tokio::io::sink(),
+                )
+                .await?;
+                if skipped < len as u64 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                self.poisoned = false;
+            }
+            return Err(e);
+        }
+        let mut frame = vec![0; len];
+        self.reader.read_exact(&mut frame).await?;
+        self.decode_received(&frame)
+    }
+
+    /// Sends a term to the async writer (see [`PacketTransport::send_term`]).
+    pub async fn send_term_async(&mut self, term: &Term) -> Result<(), EncodeError> {
+        use tokio::io::AsyncWriteExt;
+
+        let frame = self.encode_sent(term)?;
+        self.writer.write_all(&frame).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Reads a frame whose length prefix is `prefix_len` bytes, returning `None` at the end of the stream.
+pub(crate) fn read_frame<R: Read>(
+    reader: &mut R,
+    prefix_len: usize,
+    max: usize,
+) -> Result<Option<Vec<u8>>, DecodeError> {
+    let Some(len) = read_frame_len(reader, prefix_len)? else {
+        return Ok(None);
+    };
+    check_frame_len(len, max)?;
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+/// Reads a length prefix of `prefix_len` bytes, returning `None` at the end of the stream.
+fn read_frame_len<R: Read>(
+    reader: &mut R,
+    prefix_len: usize,
+) -> Result<Option<usize>, DecodeError> {
+    let mut prefix = [0; 4];
+    let prefix = &mut prefix[4 - prefix_len..];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(
+        prefix
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | usize::from(b)),
+    ))
+}
+
+/// Reads a length prefix of `prefix_len` bytes from an async reader, returning `None` at the
+/// end of the stream.
+#[cfg(feature = "tokio-async")]
+pub(crate) async fn read_frame_len_async<R>(
+    reader: &mut R,
+    prefix_len: usize,
+) -> Result<Option<usize>, DecodeError>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut prefix = [0; 4];
+    let prefix = &mut prefix[4 - prefix_len..];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]).await? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            n => read += n,
+        }
+    }
+    Ok(Some(
+        prefix
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | usize::from(b)),
+    ))
+}
+
+fn check_frame_len(len: usize, max: usize) -> Result<(), DecodeError> {
+    if len > max {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(len, max, "received a frame larger than the maximum");
+        return Err(DecodeError::TooLargeFrame { len, max });
+    }
+    Ok(())
+}
+
+/// Decodes a term which must span the whole frame.
+pub(crate) fn decode_frame(frame: &[u8], options: DecoderOptions) -> Result<Term, DecodeError> {
+    let mut reader = Cursor::new(frame);
+    let term = Decoder::with_options(&mut reader, options).decode()?;
+    let count = frame.len() - reader.position() as usize;
+    if count != 0 {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(count, "received a frame with bytes following the term");
+        return Err(DecodeError::TrailingBytes { count });
+    }
+    Ok(term)
+}
+
+/// Encodes a term preceded by its `prefix_len` byte length.
+pub(crate) fn encode_frame(
+    term: &Term,
+    prefix_len: usize,
+    max: usize,
+    options: EncoderOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut frame = vec![0; prefix_len];
+    Encoder::with_options(&mut frame, options).encode(term)?;
+    finish_frame(frame, prefix_len, max)
+}
+
+/// Writes the length prefix of a frame, whose first `prefix_len` bytes are left for it.
+fn finish_frame(mut frame: Vec<u8>, prefix_len: usize, max: usize) -> Result<Vec<u8>, EncodeError> {
+    let len = frame.len() - prefix_len;
+    if len > max {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(len, max, "cannot send a frame larger than the maximum");
+        return Err(EncodeError::TooLargeFrame { len, max });
+    }
+    frame[..prefix_len].copy_from_slice(&(len as u32).to_be_bytes()[4 - prefix_len..]);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning at most one byte per `read` call.
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    #[test]
+    fn round_trip_works() {
+        for packet_size in [PacketSize::One, PacketSize::Two, PacketSize::Four] {
+            let mut pipe = Vec::new();
+            let mut sender = PacketTransport::new(&[][..], &mut pipe, packet_size);
+            sender.send_term(&atom("foo")).unwrap();
+            sender.send_term(&Term::from(1)).unwrap();
+            assert_eq!(pipe.len(), 2 * packet_size.bytes() + 7 + 3);
+
+            let mut receiver = PacketTransport::new(Trickle(&pipe), io::sink(), packet_size);
+            assert_eq!(receiver.recv_term().unwrap(), atom("foo"));
+            assert_eq!(receiver.recv_term().unwrap(), Term::from(1));
+            assert!(matches!(
+                receiver.recv_term(),
+                Err(DecodeError::EndOfStream)
+            ));
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let frames: [&[u8]; 2] = [&[0, 0], &[0, 0, 0, 5, 131, 100, 0]];
+        for frame in frames {
+            let mut transport = PacketTransport::new(frame, io::sink(), PacketSize::Four);
+            match transport.recv_term() {
+                Err(DecodeError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn too_large_frames_are_rejected() {
+        let input = [0, 0, 1, 0];
+        let mut transport =
+            PacketTransport::new(&input[..], Vec::new(), PacketSize::Four).max_frame_size(100);
+        assert!(matches!(
+            transport.recv_term(),
+            Err(DecodeError::TooLargeFrame { len: 256, max: 100 })
+        ));
+
+        let long = Term::from(Binary::from(vec![0; 300]));
+        assert!(matches!(
+            transport.send_term(&long),
+            Err(EncodeError::TooLargeFrame { max: 100, .. })
+        ));
+        let mut transport = PacketTransport::new(&[][..], Vec::new(), PacketSize::One);
+        assert!(matches!(
+            transport.send_term(&long),
+            Err(EncodeError::TooLargeFrame { max: 255, .. })
+        ));
+    }
+
+    #[test]
+    fn options_apply_to_frames() {
+        let term = Term::from(Tuple::from(vec![atom("ok"), Term::try_from(1.5).unwrap()]));
+        let mut output = Vec::new();
+        let mut transport = PacketTransport::new(&[][..], &mut output, PacketSize::Two)
+            .encoder_options(EncoderOptions::new().with_new_floats(false));
+        transport.send_term(&term).unwrap();
+        // FLOAT_EXT after the prefix, the version, the tuple header and the atom.
+        assert_eq!(output[2 + 1 + 2 + 5], 99);
+
+        let options = DecoderOptions::raw_depth(1).with_max_uncompressed_size(usize::MAX);
+        let mut transport = PacketTransport::new(&output[..], Vec::new(), PacketSize::Two)
+            .max_frame_size(100)
+            .decoder_options(options);
+        match transport.recv_term().unwrap() {
+            Term::Tuple(tuple) => {
+                assert_eq!(tuple.elements[0], atom("ok"));
+                assert!(matches!(tuple.elements[1], Term::Raw(ref raw) if raw.bytes[0] == 99));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn compressed_frames_round_trip() {
+        let map = Term::from(Map::from(
+            (0..1000)
+                .map(|i| (Term::from(i), atom("value")))
+                .collect::<HashMap<_, _>>(),
+        ));
+        let mut plain = Vec::new();
+        PacketTransport::new(&[][..], &mut plain, PacketSize::Four)
+            .send_term(&map)
+            .unwrap();
+
+        let mut pipe = Vec::new();
+        let mut sender =
+            PacketTransport::new(&[][..], &mut pipe, PacketSize::Four).compress_above(1024);
+        sender.send_term(&map).unwrap();
+        sender.send_term(&atom("small")).unwrap();
+        let stats = sender.compression_stats();
+        assert_eq!(stats.frames_compressed, 1);
+        assert_eq!(
+            pipe.len() as u64 + stats.bytes_saved,
+            plain.len() as u64 + 4 + 9
+        );
+        assert_eq!(pipe[4..6], [131, 80]);
+
+        let mut receiver = PacketTransport::new(&pipe[..], io::sink(), PacketSize::Four);
+        assert_eq!(receiver.recv_term().unwrap(), map);
+        assert_eq!(receiver.recv_term().unwrap(), atom("small"));
+        assert_eq!(receiver.compression_stats(), CompressionStats::default());
+    }
+
+    #[test]
+    fn too_large_compressed_frames_are_rejected() {
+        let mut pipe = Vec::new();
+        let mut sender =
+            PacketTransport::new(&[][..], &mut pipe, PacketSize::Four).compress_above(0);
+        sender
+            .send_term(&Term::from(Binary::from(vec![0; 10_000])))
+            .unwrap();
+        assert!(pipe.len() < 100);
+
+        let mut receiver =
+            PacketTransport::new(&pipe[..], io::sink(), PacketSize::Four).max_frame_size(1000);
+        assert!(matches!(
+            receiver.recv_term(),
+            Err(DecodeError::LimitExceeded { max: 1000, .. })
+        ));
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_compressed_frames_round_trip() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (client, server) = tokio::io::duplex(64);
+            let (client_reader, client_writer) = tokio::io::split(client);
+            let (server_reader, server_writer) = tokio::io::split(server);
+            let mut sender = PacketTransport::new(client_reader, client_writer, PacketSize::Four)
+                .compress_above(1024);
+            let mut receiver = PacketTransport::new(server_reader, server_writer, PacketSize::Four)
+                .max_frame_size(20_000)
+                .on_error(OnError::SkipFrame);
+
+            let map = Term::from(Map::from(
+                (0..1000)
+                    .map(|i| (Term::from(i), atom("value")))
+                    .collect::<HashMap<_, _>>(),
+            ));
+            // Inflates to more than the maximum length of the frames of the receiver.
+            let zeros = Term::from(Binary::from(vec![0; 50_000]));
+            let send = async {
+                sender.send_term_async(&map).await.unwrap();
+                sender.send_term_async(&atom("small")).await.unwrap();
+                sender.send_term_async(&zeros).await.unwrap();
+                sender.send_term_async(&atom("last")).await.unwrap();
+                sender
+            };
+            let recv = async {
+                let mut received = Vec::new();
+                for _ in 0..4 {
+                    received.push(receiver.recv_term_async().await);
+                }
+                received
+            };
+            let (sender, received) = tokio::join!(send, recv);
+
+            let stats = sender.compression_stats();
+            assert_eq!(stats.frames_compressed, 2);
+            assert!(stats.bytes_saved > 50_000, "{:?}", stats);
+            assert_eq!(received[0].as_ref().unwrap(), &map);
+            assert_eq!(received[1].as_ref().unwrap(), &atom("small"));
+            assert!(matches!(
+                received[2],
+                Err(DecodeError::LimitExceeded { max: 20_000, .. })
+            ));
+            assert_eq!(received[3].as_ref().unwrap(), &atom("last"));
+            assert_eq!(receiver.compression_stats(), CompressionStats::default());
+        });
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let input = [4, 131, 97, 1, 0];
+        let mut transport = PacketTransport::new(&input[..], io::sink(), PacketSize::One);
+        assert!(matches!(
+            transport.recv_term(),
+            Err(DecodeError::TrailingBytes { count: 1 })
+        ));
+    }
+
+    #[test]
+    fn frames_are_skipped_after_errors() {
+        let mut input = Vec::new();
+        let mut sender = PacketTransport::new(&[][..], &mut input, PacketSize::Two);
+        sender.send_term(&atom("first")).unwrap();
+        // A corrupt frame (with an unknown tag), and a too large one.
+        input.extend([0, 3, 131, 0, 0]);
+        input.extend([0, 200]);
+        input.extend([0; 200]);
+        let mut sender = PacketTransport::new(&[][..], &mut input, PacketSize::Two);
+        sender.send_term(&atom("last")).unwrap();
+
+        let mut transport = PacketTransport::new(Trickle(&input), io::sink(), PacketSize::Two)
+            .max_frame_size(100)
+            .on_error(OnError::SkipFrame);
+        assert_eq!(transport.recv_term().unwrap(), atom("first"));
+        assert!(matches!(
+            transport.recv_term(),
+            Err(DecodeError::UnknownTag { tag: 0 })
+        ));
+        assert!(!transport.is_poisoned());
+        assert!(matches!(
+            transport.recv_term(),
+            Err(DecodeError::TooLargeFrame { len: 200, .. })
+        ));
+        assert_eq!(transport.recv_term().unwrap(), atom("last"));
+        assert!(matches!(
+            transport.recv_term(),
+            Err(DecodeError::EndOfStream)
+        ));
+        assert!(!transport.is_poisoned());
+
+        let mut transport = PacketTransport::new(&input[..], io::sink(), PacketSize::Two);
+        assert_eq!(transport.recv_term().unwrap(), atom("first"));
+        assert!(transport.recv_term().is_err());
+        assert!(transport.is_poisoned());
+        assert!(matches!(transport.recv_term(), Err(DecodeError::Poisoned)));
+
+        // The end of the stream within a frame cannot be skipped.
+        let mut transport =
+            PacketTransport::new(&input[..input.len() - 2], io::sink(), PacketSize::Two)
+                .on_error(OnError::SkipFrame);
+        for _ in 0..3 {
+            let _ = transport.recv_term();
+        }
+        assert!(transport.recv_term().is_err());
+        assert!(transport.is_poisoned());
+    }
+}
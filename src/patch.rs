@@ -0,0 +1,219 @@
+//! Replacing a subterm of an encoded term without decoding the rest (see [`patch_encoded`]).
+use super::*;
+use crate::codec::Decoder;
+use crate::codec_common::*;
+use crate::slice::SliceDecoder;
+use crate::zlib::{self, Zlib};
+use std::io::Read;
+use std::ops::Range;
+
+/// A step in the path from an encoded term to the subterm which [`patch_encoded`] replaces.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchPathSegment {
+    TupleElement(usize),
+    ListElement(usize),
+    MapValue { key: Term },
+}
+impl fmt::Display for PatchPathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchPathSegment::TupleElement(i) => write!(f, "tuple[{}]", i),
+            PatchPathSegment::ListElement(i) => write!(f, "list[{}]", i),
+            PatchPathSegment::MapValue { ref key } => write!(f, "map value for key {}", key),
+        }
+    }
+}
+
+/// Error of [`patch_encoded`].
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("segment {index} of the path ({segment}) does not exist")]
+    NotFound {
+        index: usize,
+        segment: PatchPathSegment,
+    },
+
+    #[error("segment {index} of the path ({segment}) cannot step into a {found}")]
+    UnexpectedType {
+        index: usize,
+        segment: PatchPathSegment,
+        found: &'static str,
+    },
+
+    #[error("failed to decode the input")]
+    Decode(#[from] DecodeError),
+
+    #[error("failed to encode the new value")]
+    Encode(#[from] EncodeError),
+}
+
+/// Returns the encoded term `input` with its subterm at `path` replaced by `new_value`.
+///
+/// Only the tags and lengths of the terms on the way to the subterm are read (and the keys of the
+/// maps on it decoded): the bytes before and after the subterm are copied as they are, as the
+/// encoding has no offsets to fix up. A compressed input is decompressed, patched and compressed
+/// again.
+///
+/// An element of a list encoded as a `STRING_EXT` is replaced by encoding the whole list as a
+/// `LIST_EXT`.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{patch_encoded, PatchPathSegment, Term};
+///
+/// // {counter, 1}
+/// let input = [131, 104, 2, 119, 7, b'c', b'o', b'u', b'n', b't', b'e', b'r', 97, 1];
+/// let output = patch_encoded(&input, &[PatchPathSegment::TupleElement(1)], &Term::from(2)).unwrap();
+/// assert_eq!(output[..13], input[..13]);
+/// assert_eq!(output[13], 2);
+/// ```
+pub fn patch_encoded(
+    input: &[u8],
+    path: &[PatchPathSegment],
+    new_value: &Term,
+) -> Result<Vec<u8>, PatchError> {
+    SliceDecoder::new(input).read_version()?;
+    if input.get(1) == Some(&COMPRESSED_TERM) {
+        let body = decompress(&input[2..])?;
+        let mut patched = Vec::with_capacity(body.len());
+        patch_body(&body, path, new_value, &mut patched)?;
+        let mut output = Vec::new();
+        codec::write_compressed(&mut output, &patched)?;
+        return Ok(output);
+    }
+    let mut output = Vec::with_capacity(input.len());
+    output.push(VERSION);
+    patch_body(&input[1..], path, new_value, &mut output)?;
+    Ok(output)
+}
+
+/// Location of the replaced subterm in the body of the input.
+enum Target {
+    Term(Range<usize>),
+    /// An element of a `STRING_EXT`, whose range is given.
+    StringElement(Range<usize>, usize),
+}
+
+fn patch_body(
+    body: &[u8],
+    path: &[PatchPathSegment],
+    new_value: &Term,
+    output: &mut Vec<u8>,
+) -> Result<(), PatchError> {
+    let (range, replacement) = match locate(body, path)? {
+        Target::Term(range) => (range, encode_to_vec(new_value)?),
+        Target::StringElement(range, index) => {
+            let mut elements = body[range.start + 3..range.end]
+                .iter()
+                .map(|&b| Term::from(FixInteger::from(b)))
+                .collect::<Vec<_>>();
+            elements[index] = new_value.clone();
+            (range, encode_to_vec(&Term::from(List::from(elements)))?)
+        }
+    };
+    output.extend_from_slice(&body[..range.start]);
+    output.extend_from_slice(&replacement[1..]);
+    output.extend_from_slice(&body[range.end..]);
+    Ok(())
+}
+
+fn locate(body: &[u8], path: &[PatchPathSegment]) -> Result<Target, PatchError> {
+    let mut start = 0;
+    for (index, segment) in path.iter().enumerate() {
+        let not_found = || PatchError::NotFound {
+            index,
+            segment: segment.clone(),
+        };
+        let mut header = SliceDecoder::new(&body[start..]);
+        let tag = header.read_u8()?;
+        let len = match (segment, tag) {
+            (PatchPathSegment::TupleElement(_), SMALL_TUPLE_EXT) => header.read_u8()? as usize,
+            (PatchPathSegment::TupleElement(_), LARGE_TUPLE_EXT)
+            | (PatchPathSegment::ListElement(_), LIST_EXT)
+            | (PatchPathSegment::MapValue { .. }, MAP_EXT) => header.read_len_u32()?,
+            (PatchPathSegment::ListElement(_), NIL_EXT) => 0,
+            (PatchPathSegment::ListElement(i), STRING_EXT) => {
+                let len = header.read_u16()? as usize;
+                header.read_bytes(len)?;
+                if *i >= len {
+                    return Err(not_found());
+                }
+                if let Some(next) = path.get(index + 1) {
+                    return Err(PatchError::UnexpectedType {
+                        index: index + 1,
+                        segment: next.clone(),
+                        found: "FixInteger",
+                    });
+                }
+                return Ok(Target::StringElement(start..start + 3 + len, *i));
+            }
+            _ => {
+                return Err(PatchError::UnexpectedType {
+                    index,
+                    segment: segment.clone(),
+                    found: variant_name(&body[start..])?,
+                })
+            }
+        };
+        start = body.len() - header.remaining();
+        match segment {
+            PatchPathSegment::TupleElement(i) | PatchPathSegment::ListElement(i) => {
+                if *i >= len {
+                    return Err(not_found());
+                }
+                for _ in 0..*i {
+                    start += term_len(&body[start..])?;
+                }
+            }
+            PatchPathSegment::MapValue { key } => {
+                let mut found = false;
+                for _ in 0..len {
+                    let key_len = term_len(&body[start..])?;
+                    let mut decoder = SliceDecoder::new(&body[start..start + key_len]);
+                    let tag = decoder.read_u8()?;
+                    start += key_len;
+                    if decoder.decode_term_with_tag(tag)? == *key {
+                        found = true;
+                        break;
+                    }
+                    start += term_len(&body[start..])?;
+                }
+                if !found {
+                    return Err(not_found());
+                }
+            }
+        }
+    }
+    Ok(Target::Term(start..start + term_len(&body[start..])?))
+}
+
+/// Returns the length of the term at the start of `bytes`, which is skipped without decoding it.
+fn term_len(bytes: &[u8]) -> Result<usize, DecodeError> {
+    let mut decoder = Decoder::new(io::Cursor::new(bytes));
+    decoder.skip_subterm_fast()?;
+    // Seeking past the end of the input succeeds.
+    usize::try_from(decoder.position())
+        .ok()
+        .filter(|&len| len <= bytes.len())
+        .ok_or(DecodeError::UnexpectedEof)
+}
+
+fn variant_name(bytes: &[u8]) -> Result<&'static str, DecodeError> {
+    let mut decoder = SliceDecoder::new(bytes);
+    let tag = decoder.read_u8()?;
+    Ok(decoder.decode_term_with_tag(tag)?.variant_name())
+}
+
+/// Inflates the body of a `COMPRESSED_TERM` (following its tag).
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let size = SliceDecoder::new(bytes).read_len_u32()?;
+    let max = DecoderOptions::default().max_uncompressed_size;
+    zlib::check_declared_size(max, size)?;
+    let compressed = &bytes[4..];
+    let mut body = Vec::with_capacity(size.min(compressed.len() * 8));
+    let mut zlib_decoder = zlib::Limited::new(zlib::Backend::slice_decoder(compressed)?, max);
+    let result = zlib_decoder.read_to_end(&mut body);
+    zlib_decoder.check(result.map_err(DecodeError::from))?;
+    Ok(body)
+}
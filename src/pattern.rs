@@ -148,7 +148,7 @@ impl<'a> Pattern<'a> for &'static str {
     type Output = Self;
     fn try_match(&self, input: &'a Term) -> Result<'a, Self::Output> {
         let a: &Atom = input.try_as_ref().ok_or_else(|| self.unmatched(input))?;
-        (*self == a.name)
+        (*self == a.as_str())
             .as_option()
             .ok_or_else(|| self.unmatched(input))?;
         Ok(*self)
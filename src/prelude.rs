@@ -0,0 +1,33 @@
+//! The term types, traits, macros and functions which most users of the crate need.
+//!
+//! # Examples
+//!
+//! Replies to a `{call, Ref, Request}` message:
+//!
+//! ```
+//! use eetf::prelude::*;
+//!
+//! let request = tuple!(Atom::from("call"), 1, Atom::from("ping"));
+//! let bytes = encode_to_vec(&Term::from(request)).unwrap();
+//!
+//! let Term::Tuple(message) = decode_from_slice(&bytes).unwrap() else {
+//!     unreachable!()
+//! };
+//! let reply = tuple!(Atom::from("reply"), message.elements[1].clone(), Atom::from("pong"));
+//! let reply = encode_to_vec(&Term::from(reply)).unwrap();
+//! assert_eq!(term_iter(&reply).count(), 1);
+//! ```
+#[cfg(feature = "std")]
+pub use crate::convert::{AsOption, TryAsRef};
+pub use crate::tuple;
+pub use crate::{decode_from_slice, decode_view, encode_to_vec, term_iter, TermView};
+pub use crate::{
+    Atom, BigInteger, Binary, BitBinary, ByteList, ExternalFun, FixInteger, Float, ImproperList,
+    InternalFun, List, Map, Pid, Port, Raw, Reference, Term, Tuple,
+};
+pub use crate::{
+    DecodeError, DecodeResult, DecoderOptions, EncodeError, EncodeResult, EncoderOptions,
+};
+// Also the derive macros of the same names, with the `derive` feature.
+#[cfg(feature = "std")]
+pub use crate::{Decoder, Encoder, FromTerm, FromTermError, IntoTerm, IntoTermTuple};
@@ -0,0 +1,249 @@
+//! Tracking of the position of the stream decoders (see [`Decoder::position`]).
+use super::*;
+
+/// Callback of [`Decoder::with_progress`].
+pub(crate) struct Progress {
+    every: u64,
+    next: u64,
+    callback: Box<dyn FnMut(u64) + Send>,
+}
+impl Progress {
+    pub(crate) fn new(every: u64, callback: Box<dyn FnMut(u64) + Send>) -> Self {
+        assert!(every > 0, "the progress interval must not be zero");
+        Progress {
+            every,
+            next: every,
+            callback,
+        }
+    }
+}
+
+/// Timeout of the reads of an async decoder (see `AsyncDecoder::decode_with_timeout`).
+#[cfg(feature = "tokio-async")]
+pub(crate) struct Timeout {
+    /// The time for which a read may make no progress.
+    idle: Option<std::time::Duration>,
+    deadline: Option<tokio::time::Instant>,
+    started: tokio::time::Instant,
+    sleep: std::pin::Pin<Box<tokio::time::Sleep>>,
+}
+#[cfg(feature = "tokio-async")]
+impl Timeout {
+    pub(crate) fn new(
+        idle: Option<std::time::Duration>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Self {
+        let started = tokio::time::Instant::now();
+        let mut timeout = Timeout {
+            idle,
+            deadline,
+            started,
+            sleep: Box::pin(tokio::time::sleep_until(started)),
+        };
+        timeout.restart();
+        timeout
+    }
+
+    /// Restarts the idle timeout (which never ends after the deadline).
+    fn restart(&mut self) {
+        let idle = self.idle.map(|idle| tokio::time::Instant::now() + idle);
+        let at = match (idle, self.deadline) {
+            (Some(idle), Some(deadline)) => idle.min(deadline),
+            (idle, deadline) => idle.or(deadline).unwrap_or(self.started),
+        };
+        self.sleep.as_mut().reset(at);
+    }
+}
+
+/// Reader which counts the bytes read through it, calls the progress callback, and fails once
+/// more bytes than the limit are requested (or, for async reads, once the timeout expires).
+pub(crate) struct Tracked<R> {
+    pub(crate) inner: R,
+    pub(crate) position: u64,
+    pub(crate) limit: Option<u64>,
+    pub(crate) progress: Option<Progress>,
+    exceeded: bool,
+    #[cfg(feature = "tokio-async")]
+    pub(crate) timeout: Option<Timeout>,
+    /// The time from the start of the timeout to its expiry, once it expired.
+    #[cfg(feature = "tokio-async")]
+    pub(crate) timed_out: Option<std::time::Duration>,
+    /// Bytes which were read past the position and given back, which async reads return first
+    /// (see [`Tracked::unread`]).
+    #[cfg(feature = "tokio-async")]
+    pub(crate) unread: std::collections::VecDeque<u8>,
+}
+impl<R> Tracked<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Tracked {
+            inner,
+            position: 0,
+            limit: None,
+            progress: None,
+            exceeded: false,
+            #[cfg(feature = "tokio-async")]
+            timeout: None,
+            #[cfg(feature = "tokio-async")]
+            timed_out: None,
+            #[cfg(feature = "tokio-async")]
+            unread: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Gives back `bytes`, the last bytes read, which the next async reads return again.
+    #[cfg(feature = "tokio-async")]
+    pub(crate) fn unread(&mut self, bytes: &[u8]) {
+        self.position -= bytes.len() as u64;
+        for &byte in bytes.iter().rev() {
+            self.unread.push_front(byte);
+        }
+    }
+
+    /// Returns the number of bytes which may still be read, if there is a limit.
+    pub(crate) fn remaining(&self) -> Option<u64> {
+        self.limit.map(|limit| limit.saturating_sub(self.position))
+    }
+
+    /// Replaces the error of reading from the stream if it is the limit being exceeded (or the
+    /// timeout expiring).
+    pub(crate) fn check<T>(&self, result: Result<T, DecodeError>) -> Result<T, DecodeError> {
+        #[cfg(feature = "tokio-async")]
+        if let (Err(_), Some(elapsed)) = (&result, self.timed_out) {
+            return Err(DecodeError::Timeout {
+                bytes_consumed: self.position,
+                elapsed,
+            });
+        }
+        match self.limit {
+            Some(max) if self.exceeded => Err(DecodeError::LimitExceeded {
+                limit: "bytes_limit",
+                max: usize::try_from(max).unwrap_or(usize::MAX),
+            }),
+            _ => result,
+        }
+    }
+
+    /// Fails if `len` more bytes would exceed the limit.
+    fn reserve(&mut self, len: u64) -> io::Result<()> {
+        match self.remaining() {
+            Some(remaining) if len > remaining => {
+                self.exceeded = true;
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the decoder exceeds its bytes limit",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that `len` bytes were read, and calls the progress callback once if the position
+    /// passed one or more multiples of its interval.
+    fn advance(&mut self, len: u64) {
+        self.position += len;
+        if let Some(progress) = &mut self.progress {
+            if self.position >= progress.next {
+                progress.next = self.position - self.position % progress.every + progress.every;
+                (progress.callback)(self.position);
+            }
+        }
+    }
+}
+impl<R: io::Read> io::Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.reserve(1)?;
+        let len = self.remaining().map_or(buf.len(), |remaining| {
+            buf.len()
+                .min(usize::try_from(remaining).unwrap_or(usize::MAX))
+        });
+        let n = self.inner.read(&mut buf[..len])?;
+        self.advance(n as u64);
+        Ok(n)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.reserve(buf.len() as u64)?;
+        // Forwarded, so that the fast path of `BufReadSource` is kept.
+        self.inner.read_exact(buf)?;
+        self.advance(buf.len() as u64);
+        Ok(())
+    }
+}
+impl<R: io::Seek> Tracked<R> {
+    /// Seeks over the next `len` bytes, which count as read.
+    pub(crate) fn seek_forward(&mut self, len: u64) -> io::Result<()> {
+        self.reserve(len)?;
+        self.inner.seek_relative(len as i64)?;
+        self.advance(len);
+        Ok(())
+    }
+}
+#[cfg(feature = "tokio-async")]
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for Tracked<R> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        if buf.remaining() == 0 {
+            return std::task::Poll::Ready(Ok(()));
+        }
+        self.reserve(1)?;
+        let this = &mut *self;
+        if !this.unread.is_empty() {
+            let len = this.remaining().map_or(buf.remaining(), |remaining| {
+                buf.remaining()
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX))
+            });
+            let len = len.min(this.unread.len());
+            let (front, back) = this.unread.as_slices();
+            let split = len.min(front.len());
+            buf.put_slice(&front[..split]);
+            buf.put_slice(&back[..len - split]);
+            this.unread.drain(..len);
+            this.advance(len as u64);
+            return std::task::Poll::Ready(Ok(()));
+        }
+        let poll = match this.remaining() {
+            None => {
+                let before = buf.filled().len();
+                std::pin::Pin::new(&mut this.inner)
+                    .poll_read(cx, buf)
+                    .map_ok(|()| buf.filled().len() - before)
+            }
+            Some(remaining) => {
+                let len = buf
+                    .remaining()
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                let mut limited = tokio::io::ReadBuf::new(buf.initialize_unfilled_to(len));
+                let poll = std::pin::Pin::new(&mut this.inner).poll_read(cx, &mut limited);
+                let n = limited.filled().len();
+                buf.advance(n);
+                poll.map_ok(|()| n)
+            }
+        };
+        let n = match (poll, &mut this.timeout) {
+            (std::task::Poll::Ready(result), timeout) => {
+                let n = result?;
+                if let (Some(timeout), true) = (timeout, n > 0) {
+                    timeout.restart();
+                }
+                n
+            }
+            (std::task::Poll::Pending, Some(timeout)) => {
+                std::task::ready!(std::future::Future::poll(timeout.sleep.as_mut(), cx));
+                this.timed_out = Some(timeout.started.elapsed());
+                return std::task::Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the decoder timed out",
+                )));
+            }
+            (std::task::Poll::Pending, None) => return std::task::Poll::Pending,
+        };
+        self.advance(n as u64);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
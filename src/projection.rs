@@ -0,0 +1,144 @@
+//! Decoding of the parts of a term which are needed, skipping the others (see [`Projection`]).
+//!
+//! A [`Projection`] tells, for each element of a tuple or a list and for each value of a map,
+//! whether to decode it, to skip it, or to keep its encoding as a [`Raw`]. The skipped subterms
+//! are read and checked like by [`Decoder::skip_term`], but never buffered, so that a small
+//! field can be taken out of a message holding a large binary. The [`Projected`] result keeps
+//! the shape of the term, with placeholders where subterms were skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::projection::{decode_projected, Projected, Projection};
+//! use eetf::{Atom, Binary, Term, Tuple};
+//!
+//! let term = Term::from(Tuple::from(vec![
+//!     Term::from(Atom::from("upload")),
+//!     Term::from(Binary::from(vec![0; 1024])),
+//!     Term::from(7),
+//! ]));
+//! let mut bytes = Vec::new();
+//! term.encode(&mut bytes).unwrap();
+//!
+//! let projection = Projection::elements(Projection::Skip, [(2, Projection::Decode)]);
+//! let projected = decode_projected(&bytes[..], &projection).unwrap();
+//! assert_eq!(projected.get(2).and_then(Projected::as_term), Some(&Term::from(7)));
+//! assert!(matches!(projected.get(1), Some(Projected::Skipped { len: 1029, .. })));
+//! ```
+use super::*;
+use std::collections::BTreeMap;
+
+/// What to do with a subterm when decoding a projection of a term.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    /// Decodes the subterm into a [`Projected::Term`].
+    Decode,
+
+    /// Skips the subterm, which becomes a [`Projected::Skipped`].
+    Skip,
+
+    /// Keeps the encoding of the subterm as a [`Projected::Raw`].
+    CaptureRaw,
+
+    /// Projects the elements of a tuple or a list by their index, and the elements without a
+    /// projection (including the tail of an improper list) with `other`.
+    ///
+    /// A string (`STRING_EXT`) is decoded as a whole, since its elements are bytes.
+    Elements {
+        by_index: BTreeMap<usize, Projection>,
+        other: Box<Projection>,
+    },
+
+    /// Projects the values of a map by their key, and the values of the other keys with
+    /// `other`. The keys are always decoded.
+    Values {
+        by_key: HashMap<Term, Projection>,
+        other: Box<Projection>,
+    },
+}
+impl Projection {
+    /// Makes a projection of the elements of a tuple or a list.
+    pub fn elements(
+        other: Projection,
+        by_index: impl IntoIterator<Item = (usize, Projection)>,
+    ) -> Self {
+        Projection::Elements {
+            by_index: by_index.into_iter().collect(),
+            other: Box::new(other),
+        }
+    }
+
+    /// Makes a projection of the values of a map.
+    pub fn values<K: Into<Term>>(
+        other: Projection,
+        by_key: impl IntoIterator<Item = (K, Projection)>,
+    ) -> Self {
+        Projection::Values {
+            by_key: by_key.into_iter().map(|(k, p)| (k.into(), p)).collect(),
+            other: Box::new(other),
+        }
+    }
+}
+
+/// Term decoded according to a [`Projection`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projected {
+    Term(Term),
+    Raw(Raw),
+
+    /// Subterm which was skipped, whose encoding starts with `tag` and has `len` bytes (the tag
+    /// included).
+    Skipped {
+        tag: u8,
+        len: u64,
+    },
+
+    Tuple(Vec<Projected>),
+
+    /// List, whose `tail` is `None` if it is proper.
+    List {
+        elements: Vec<Projected>,
+        tail: Option<Box<Projected>>,
+    },
+
+    /// Map, with its entries in the order they are encoded in.
+    Map(Vec<(Term, Projected)>),
+}
+impl Projected {
+    /// Returns the element at `index` of a tuple or a list.
+    pub fn get(&self, index: usize) -> Option<&Projected> {
+        match self {
+            Projected::Tuple(elements) | Projected::List { elements, .. } => elements.get(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of `key` in a map.
+    pub fn value(&self, key: &Term) -> Option<&Projected> {
+        match self {
+            Projected::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the decoded term, if the subterm was decoded.
+    pub fn as_term(&self) -> Option<&Term> {
+        match self {
+            Projected::Term(term) => Some(term),
+            _ => None,
+        }
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, Projected::Skipped { .. })
+    }
+}
+
+/// Decodes the parts of the next term of `reader` selected by `projection` (see
+/// [`Decoder::decode_projected`]).
+pub fn decode_projected<R: io::Read>(
+    reader: R,
+    projection: &Projection,
+) -> Result<Projected, DecodeError> {
+    Decoder::new(reader).decode_projected(projection)
+}
@@ -0,0 +1,504 @@
+//! Pull-based decoding of a term as a sequence of events.
+//!
+//! [`TermReader`] is the reading counterpart of [`TermWriter`](crate::writer::TermWriter):
+//! it yields an [`Event`] for each value and container boundary without building a
+//! [`Term`], so that large terms can be processed in constant memory.
+//!
+//! # Examples
+//!
+//! Counts the rows of a list of tuples and sums their integers:
+//!
+//! ```
+//! use eetf::reader::{Event, TermReader};
+//!
+//! // [{a, 1}, {b, 2}]
+//! let bytes = [
+//!     131, 108, 0, 0, 0, 2, 104, 2, 100, 0, 1, 97, 97, 1, 104, 2, 100, 0, 1, 98, 97, 2, 106,
+//! ];
+//! let mut reader = TermReader::new(&bytes[..]);
+//! let (mut rows, mut sum) = (0, 0);
+//! while let Some(event) = reader.next().unwrap() {
+//!     match event {
+//!         Event::StartTuple(_) => rows += 1,
+//!         Event::Int(n) => sum += n,
+//!         _ => {}
+//!     }
+//! }
+//! assert_eq!((rows, sum), (2, 3));
+//! ```
+use super::*;
+use crate::codec::Decoder;
+use crate::codec_common::*;
+use crate::zlib::{self, Zlib};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::Read;
+
+/// Default maximum size of a [`Event::BinaryChunk`].
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Part of a term.
+///
+/// Containers start with a `Start*` event, followed by the events of their elements
+/// (keys and values alternating for maps), and end with [`Event::End`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    Atom(&'a str),
+    /// Small integer (`SMALL_INTEGER_EXT` or `INTEGER_EXT`).
+    Int(i64),
+    /// Big integer (`SMALL_BIG_EXT` or `LARGE_BIG_EXT`).
+    BigInt(BigInt),
+    Float(f64),
+    Pid(Pid),
+    Port(Port),
+    Reference(Reference),
+    ExternalFun(ExternalFun),
+    InternalFun(InternalFun),
+    BitBinary(BitBinary),
+    /// List of bytes (`STRING_EXT`).
+    ByteList(&'a [u8]),
+    /// Start of a binary of the given size, whose bytes follow in [`Event::BinaryChunk`]s.
+    StartBinary(usize),
+    BinaryChunk(&'a [u8]),
+    StartTuple(usize),
+    /// Start of a list with the given number of elements (excluding the tail).
+    StartList(usize),
+    /// Marks the tail of an improper list, the events of which follow.
+    Tail,
+    /// Start of a map with the given number of entries.
+    StartMap(usize),
+    End,
+}
+
+#[derive(Debug)]
+enum Container {
+    Binary,
+    Tuple,
+    List { tail: bool },
+    Map,
+}
+
+#[derive(Debug)]
+struct Frame {
+    container: Container,
+    /// The number of elements (or bytes, for binaries) left.
+    remaining: usize,
+}
+
+enum Source<R> {
+    Plain(R),
+    Compressed(zlib::Decoder<R>),
+    Poisoned,
+}
+impl<R: Read> Read for Source<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Plain(r) => r.read(buf),
+            Source::Compressed(r) => r.read(buf),
+            Source::Poisoned => Err(io::Error::other("the reader failed")),
+        }
+    }
+}
+
+/// Reader decoding a term as a sequence of [`Event`]s (see the [module documentation](self)).
+///
+/// Compressed terms are decompressed transparently.
+pub struct TermReader<R> {
+    source: Source<R>,
+    frames: Vec<Frame>,
+    buf: Vec<u8>,
+    /// Names of Latin-1 atoms, which need converting.
+    text: String,
+    /// A tag which has been read ahead.
+    pending_tag: Option<u8>,
+    chunk_size: usize,
+    started: bool,
+    done: bool,
+}
+impl<R: Read> TermReader<R> {
+    pub fn new(reader: R) -> Self {
+        TermReader {
+            source: Source::Plain(reader),
+            frames: Vec::new(),
+            buf: Vec::new(),
+            text: String::new(),
+            pending_tag: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Sets the maximum size of a [`Event::BinaryChunk`] (at least 1).
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the number of open containers (including binaries).
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the next event, or `None` once the term is complete.
+    ///
+    /// The input after the term is not read.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<Event<'_>>, DecodeError> {
+        if self.done {
+            return Ok(None);
+        }
+        if !self.started {
+            self.started = true;
+            let version = self.source.read_u8()?;
+            if version != VERSION {
+                return Err(DecodeError::UnsupportedVersion { version });
+            }
+            let mut tag = self.source.read_u8()?;
+            if tag == COMPRESSED_TERM {
+                let _uncompressed_size = self.source.read_u32::<BigEndian>()?;
+                self.source = match std::mem::replace(&mut self.source, Source::Poisoned) {
+                    Source::Plain(r) => Source::Compressed(zlib::Backend::decoder(r)?),
+                    _ => unreachable!(),
+                };
+                tag = self.source.read_u8()?;
+            }
+            return self.value(tag).map(Some);
+        }
+
+        let frame = self.frames.last_mut().expect("unreachable");
+        match frame.container {
+            Container::Binary if frame.remaining > 0 => {
+                let len = frame.remaining.min(self.chunk_size);
+                frame.remaining -= len;
+                self.buf.resize(len, 0);
+                self.source.read_exact(&mut self.buf)?;
+                Ok(Some(Event::BinaryChunk(&self.buf)))
+            }
+            Container::List { tail: false } if frame.remaining == 0 => {
+                let tag = self.source.read_u8()?;
+                if tag == NIL_EXT {
+                    return Ok(Some(self.end()));
+                }
+                frame.container = Container::List { tail: true };
+                frame.remaining = 1;
+                self.pending_tag = Some(tag);
+                Ok(Some(Event::Tail))
+            }
+            _ if frame.remaining == 0 => Ok(Some(self.end())),
+            _ => {
+                frame.remaining -= 1;
+                let tag = match self.pending_tag.take() {
+                    Some(tag) => tag,
+                    None => self.source.read_u8()?,
+                };
+                self.value(tag).map(Some)
+            }
+        }
+    }
+
+    /// Skips the rest of the innermost open container, including its [`Event::End`].
+    ///
+    /// Does nothing if no container is open.
+    pub fn skip_current(&mut self) -> Result<(), DecodeError> {
+        let depth = self.depth();
+        while self.depth() >= depth && depth > 0 {
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Event<'static> {
+        self.frames.pop();
+        self.done = self.frames.is_empty();
+        Event::End
+    }
+
+    fn open(&mut self, container: Container, remaining: usize) {
+        self.frames.push(Frame {
+            container,
+            remaining,
+        });
+    }
+
+    fn value(&mut self, tag: u8) -> Result<Event<'_>, DecodeError> {
+        let is_root = self.frames.is_empty();
+        let event = match tag {
+            SMALL_TUPLE_EXT | LARGE_TUPLE_EXT => {
+                let arity = if tag == SMALL_TUPLE_EXT {
+                    self.source.read_u8()? as usize
+                } else {
+                    self.source.read_u32::<BigEndian>()? as usize
+                };
+                self.open(Container::Tuple, arity);
+                return Ok(Event::StartTuple(arity));
+            }
+            NIL_EXT => {
+                self.open(Container::List { tail: true }, 0);
+                return Ok(Event::StartList(0));
+            }
+            LIST_EXT => {
+                let len = self.source.read_u32::<BigEndian>()? as usize;
+                self.open(Container::List { tail: false }, len);
+                return Ok(Event::StartList(len));
+            }
+            MAP_EXT => {
+                let len = self.source.read_u32::<BigEndian>()? as usize;
+                let remaining = len.checked_mul(2).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "too many map entries")
+                })?;
+                self.open(Container::Map, remaining);
+                return Ok(Event::StartMap(len));
+            }
+            BINARY_EXT => {
+                let size = self.source.read_u32::<BigEndian>()? as usize;
+                self.open(Container::Binary, size);
+                return Ok(Event::StartBinary(size));
+            }
+            SMALL_INTEGER_EXT => Event::Int(i64::from(self.source.read_u8()?)),
+            INTEGER_EXT => Event::Int(i64::from(self.source.read_i32::<BigEndian>()?)),
+            NEW_FLOAT_EXT => {
+                let value = self.source.read_f64::<BigEndian>()?;
+                Event::Float(Float::try_from(value)?.value)
+            }
+            STRING_EXT | ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => {
+                let len = match tag {
+                    SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => self.source.read_u8()? as usize,
+                    _ => self.source.read_u16::<BigEndian>()? as usize,
+                };
+                self.buf.resize(len, 0);
+                self.source.read_exact(&mut self.buf)?;
+                self.done = is_root;
+                return match tag {
+                    STRING_EXT => Ok(Event::ByteList(&self.buf)),
+                    ATOM_EXT | SMALL_ATOM_EXT if !self.buf.is_ascii() => {
+                        self.text.clear();
+                        self.text.extend(self.buf.iter().map(|&b| char::from(b)));
+                        Ok(Event::Atom(&self.text))
+                    }
+                    _ => match std::str::from_utf8(&self.buf) {
+                        Ok(name) => Ok(Event::Atom(name)),
+                        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+                    },
+                };
+            }
+            _ => match Decoder::new(&mut self.source).decode_term_with_tag(tag)? {
+                Term::BigInteger(x) => Event::BigInt(x.value),
+                Term::Float(x) => Event::Float(x.value),
+                Term::Pid(x) => Event::Pid(x),
+                Term::Port(x) => Event::Port(x),
+                Term::Reference(x) => Event::Reference(*x),
+                Term::ExternalFun(x) => Event::ExternalFun(*x),
+                Term::InternalFun(x) => Event::InternalFun(*x),
+                Term::BitBinary(x) => Event::BitBinary(x),
+                other => unreachable!("{} is read as an event", other),
+            },
+        };
+        self.done = is_root;
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Step {
+        Term(Term),
+        Binary(usize),
+        Tuple(usize),
+        List(usize),
+        Map(usize),
+        End,
+        Tail,
+    }
+
+    /// Decodes a term from events.
+    fn read_term<R: Read>(reader: &mut TermReader<R>) -> Term {
+        let step = match reader.next().unwrap().expect("the term ended early") {
+            Event::Atom(x) => Step::Term(Term::from(Atom::from(x))),
+            Event::Int(x) => Step::Term(Term::from(FixInteger::from(x as i32))),
+            Event::BigInt(value) => Step::Term(Term::from(BigInteger { value })),
+            Event::Float(value) => Step::Term(Term::Float(Float { value })),
+            Event::Pid(x) => Step::Term(Term::from(x)),
+            Event::Port(x) => Step::Term(Term::from(x)),
+            Event::Reference(x) => Step::Term(Term::from(x)),
+            Event::ExternalFun(x) => Step::Term(Term::from(x)),
+            Event::InternalFun(x) => Step::Term(Term::from(x)),
+            Event::BitBinary(x) => Step::Term(Term::from(x)),
+            Event::ByteList(x) => Step::Term(Term::from(ByteList::from(x.to_vec()))),
+            Event::StartBinary(size) => Step::Binary(size),
+            Event::StartTuple(arity) => Step::Tuple(arity),
+            Event::StartList(len) => Step::List(len),
+            Event::StartMap(len) => Step::Map(len),
+            Event::BinaryChunk(_) => panic!("unexpected chunk"),
+            Event::End => Step::End,
+            Event::Tail => Step::Tail,
+        };
+        let term = match step {
+            Step::Term(term) => return term,
+            Step::Binary(size) => {
+                let mut bytes = Vec::new();
+                while let Event::BinaryChunk(chunk) = reader.next().unwrap().unwrap() {
+                    bytes.extend_from_slice(chunk);
+                }
+                assert_eq!(bytes.len(), size);
+                return Term::from(Binary::from(bytes));
+            }
+            Step::Tuple(arity) => Term::from(Tuple::from(
+                (0..arity).map(|_| read_term(reader)).collect::<Vec<_>>(),
+            )),
+            Step::List(len) => {
+                let elements = (0..len).map(|_| read_term(reader)).collect::<Vec<_>>();
+                let depth = reader.depth();
+                match reader.next().unwrap().unwrap() {
+                    Event::End => return Term::from(List::from(elements)),
+                    Event::Tail => {
+                        let last = read_term(reader);
+                        assert_eq!(reader.depth(), depth);
+                        Term::from(ImproperList::from((elements, last)))
+                    }
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+            Step::Map(len) => Term::from(Map::from(
+                (0..len)
+                    .map(|_| (read_term(reader), read_term(reader)))
+                    .collect::<HashMap<_, _>>(),
+            )),
+            Step::End | Step::Tail => panic!("unexpected end"),
+        };
+        assert_eq!(reader.next().unwrap(), Some(Event::End));
+        term
+    }
+
+    fn fixtures() -> Vec<Term> {
+        let atom = |name: &str| Term::from(Atom::from(name));
+        let pid = Pid::new("node@host", 1, 2, 3);
+        vec![
+            atom("foo"),
+            atom("h\u{e9}llo"),
+            atom("\u{3b1}"),
+            Term::from(FixInteger::from(255)),
+            Term::from(FixInteger::from(-1)),
+            Term::from(BigInteger::from(u64::MAX)),
+            Term::from(Float::try_from(-1.5).unwrap()),
+            Term::from(pid.clone()),
+            Term::from(Port::new("node@host", 1 << 40, 7)),
+            Term::from(Reference::new("node@host", vec![1, 2, 3], 7)),
+            Term::from(ExternalFun::from(("lists", "map", 2))),
+            Term::from(InternalFun::New {
+                module: Atom::from("m"),
+                arity: 1,
+                pid,
+                free_vars: vec![atom("x")],
+                index: 2,
+                uniq: [3; 16],
+                old_index: 4,
+                old_uniq: 5,
+            }),
+            Term::from(Binary::from(Vec::new())),
+            Term::from(Binary::from(
+                (0..=255).cycle().take(1000).collect::<Vec<u8>>(),
+            )),
+            Term::from(BitBinary::from((vec![1, 0b101], 3))),
+            Term::from(ByteList::from(vec![1, 2, 3])),
+            Term::from(List::nil()),
+            Term::from(List::from(vec![atom("a"), Term::from(List::nil())])),
+            Term::from(ImproperList::from((
+                vec![atom("a")],
+                Term::from(Tuple::from(vec![atom("b")])),
+            ))),
+            Term::from(Tuple::from(Vec::new())),
+            Term::from(Tuple::from(vec![
+                Term::from(FixInteger::from(1 << 20));
+                300
+            ])),
+            Term::from(Map::from([
+                (atom("k"), Term::from(List::from(vec![atom("v")]))),
+                (Term::from(Binary::from(&b"l"[..])), atom("w")),
+            ])),
+        ]
+    }
+
+    #[test]
+    fn events_rebuild_the_decoded_term() {
+        for term in fixtures() {
+            let mut bytes = Vec::new();
+            term.encode(&mut bytes).unwrap();
+            let expected = Term::decode(&bytes[..]).unwrap();
+
+            let mut reader = TermReader::new(&bytes[..]).with_chunk_size(7);
+            assert_eq!(read_term(&mut reader), expected);
+            assert_eq!(reader.next().unwrap(), None);
+            assert_eq!(reader.depth(), 0);
+        }
+    }
+
+    #[test]
+    fn compressed_terms_are_read() {
+        // zlib-compressed "abc" (STRING_EXT)
+        let bytes = [
+            131, 80, 0, 0, 0, 23, 120, 156, 203, 102, 16, 97, 196, 2, 0, 12, 42, 0, 148,
+        ];
+        let mut reader = TermReader::new(&bytes[..]);
+        assert_eq!(read_term(&mut reader), Term::decode(&bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn binaries_are_chunked() {
+        let bytes = [131, 109, 0, 0, 0, 5, 1, 2, 3, 4, 5];
+        let mut reader = TermReader::new(&bytes[..]).with_chunk_size(2);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next().unwrap() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(
+            events,
+            [
+                "StartBinary(5)",
+                "BinaryChunk([1, 2])",
+                "BinaryChunk([3, 4])",
+                "BinaryChunk([5])",
+                "End"
+            ]
+        );
+    }
+
+    #[test]
+    fn subtrees_are_skipped() {
+        // {[{a}, <<"big">>], b}
+        let term = Term::from(Tuple::from(vec![
+            Term::from(List::from(vec![
+                Term::from(Tuple::from(vec![Term::from(Atom::from("a"))])),
+                Term::from(Binary::from(&b"big"[..])),
+            ])),
+            Term::from(Atom::from("b")),
+        ]));
+        let mut bytes = Vec::new();
+        term.encode(&mut bytes).unwrap();
+
+        let mut reader = TermReader::new(&bytes[..]).with_chunk_size(1);
+        assert_eq!(reader.next().unwrap(), Some(Event::StartTuple(2)));
+        assert_eq!(reader.next().unwrap(), Some(Event::StartList(2)));
+        assert_eq!(reader.depth(), 2);
+        reader.skip_current().unwrap();
+        assert_eq!(reader.depth(), 1);
+        assert_eq!(reader.next().unwrap(), Some(Event::Atom("b")));
+        assert_eq!(reader.next().unwrap(), Some(Event::End));
+        reader.skip_current().unwrap();
+        assert_eq!(reader.next().unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let mut reader = TermReader::new(&[131, 104, 2, 97][..]);
+        assert_eq!(reader.next().unwrap(), Some(Event::StartTuple(2)));
+        assert!(matches!(reader.next(), Err(DecodeError::Io(_))));
+
+        let mut reader = TermReader::new(&[131, 255][..]);
+        assert!(matches!(
+            reader.next(),
+            Err(DecodeError::UnknownTag { tag: 255 })
+        ));
+    }
+}
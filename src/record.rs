@@ -0,0 +1,259 @@
+//! Erlang record helpers.
+//!
+//! Records are tuples whose first element is the record name atom followed by
+//! the fields in declaration order.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::record::RecordDef;
+//! use eetf::{Atom, Term, Tuple};
+//!
+//! let def = RecordDef::new("user", ["id", "name"]);
+//! let term = Term::from(Tuple::from(vec![
+//!     Term::from(Atom::from("user")),
+//!     Term::from(1),
+//!     Term::from(Atom::from("joe")),
+//! ]));
+//! assert!(def.matches(&term));
+//! assert_eq!(def.get(&term, "name").unwrap(), &Term::from(Atom::from("joe")));
+//! ```
+use super::*;
+use crate::convert::TryAsRef;
+
+/// Errors which can occur when mapping a term to or from a record.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum RecordError {
+    #[error("{value} is not a '{record}' record")]
+    NotARecord { record: String, value: Term },
+
+    #[error("record '{record}' expects arity {expected}, got {actual}")]
+    ArityMismatch {
+        record: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("record '{record}' has no field {field}")]
+    UnknownField { record: String, field: Term },
+}
+
+/// Definition of an Erlang record (i.e., `-record(Name, {Field1, ...})`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordDef {
+    name: Atom,
+    fields: Vec<Atom>,
+    defaults: Vec<Term>,
+}
+impl RecordDef {
+    /// Makes a new record definition.
+    ///
+    /// The default value of every field is the atom `undefined`, as in Erlang.
+    pub fn new<I, T>(name: &str, fields: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Atom>,
+    {
+        let fields: Vec<Atom> = fields.into_iter().map(Into::into).collect();
+        let defaults = vec![Term::from(Atom::from("undefined")); fields.len()];
+        RecordDef {
+            name: Atom::from(name),
+            fields,
+            defaults,
+        }
+    }
+
+    /// Sets the default value of `field` which is used by `from_map`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the record has no such field.
+    pub fn default_value<T>(mut self, field: &str, value: T) -> Self
+    where
+        Term: From<T>,
+    {
+        let i = self
+            .field_position(field)
+            .unwrap_or_else(|| panic!("record '{}' has no field '{}'", self.name.name, field));
+        self.defaults[i] = Term::from(value);
+        self
+    }
+
+    /// Returns the record name.
+    pub fn name(&self) -> &Atom {
+        &self.name
+    }
+
+    /// Returns the field names in declaration order.
+    pub fn fields(&self) -> &[Atom] {
+        &self.fields
+    }
+
+    /// Returns the arity of the record tuple (the number of fields plus one for the tag).
+    pub fn arity(&self) -> usize {
+        self.fields.len() + 1
+    }
+
+    /// Returns the index of `field` in the record tuple elements.
+    ///
+    /// The tag occupies index `0`, so the first field is at index `1`.
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.field_position(field).map(|i| i + 1)
+    }
+
+    /// Returns `true` if `term` is a tuple tagged with the record name and of the right arity.
+    pub fn matches(&self, term: &Term) -> bool {
+        self.check(term).is_ok()
+    }
+
+    /// Returns the value of `field` in the record `term`.
+    pub fn get<'a>(&self, term: &'a Term, field: &str) -> Result<&'a Term, RecordError> {
+        let tuple = self.check(term)?;
+        let i = self
+            .field_index(field)
+            .ok_or_else(|| self.unknown_field(Term::from(Atom::from(field))))?;
+        Ok(&tuple.elements[i])
+    }
+
+    /// Converts the record `term` into a map keyed by field name atoms.
+    pub fn to_map(&self, term: &Term) -> Result<Map, RecordError> {
+        let tuple = self.check(term)?;
+        let map = self
+            .fields
+            .iter()
+            .zip(&tuple.elements[1..])
+            .map(|(k, v)| (Term::from(k.clone()), v.clone()))
+            .collect::<HashMap<_, _>>();
+        Ok(Map::from(map))
+    }
+
+    /// Builds a record tuple from a map keyed by field name atoms.
+    ///
+    /// Missing fields take their default values; keys which are not fields of the record are errors.
+    pub fn from_map(&self, map: &Map) -> Result<Tuple, RecordError> {
+        let mut elements = Vec::with_capacity(self.arity());
+        elements.push(Term::from(self.name.clone()));
+        elements.extend(self.defaults.iter().cloned());
+        for (k, v) in map.map.iter() {
+            let i = TryAsRef::<Atom>::try_as_ref(k)
+                .and_then(|a| self.field_index(&a.name))
+                .ok_or_else(|| self.unknown_field(k.clone()))?;
+            elements[i] = v.clone();
+        }
+        Ok(Tuple::from(elements))
+    }
+
+    fn field_position(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.as_str() == field)
+    }
+
+    fn check<'a>(&self, term: &'a Term) -> Result<&'a Tuple, RecordError> {
+        let not_a_record = || RecordError::NotARecord {
+            record: self.name.name.to_string(),
+            value: term.clone(),
+        };
+        let tuple: &Tuple = term.try_as_ref().ok_or_else(not_a_record)?;
+        let tag: Option<&Atom> = tuple.elements.first().and_then(|t| t.try_as_ref());
+        if tag != Some(&self.name) {
+            return Err(not_a_record());
+        }
+        if tuple.elements.len() != self.arity() {
+            return Err(RecordError::ArityMismatch {
+                record: self.name.name.to_string(),
+                expected: self.arity(),
+                actual: tuple.elements.len(),
+            });
+        }
+        Ok(tuple)
+    }
+
+    fn unknown_field(&self, field: Term) -> RecordError {
+        RecordError::UnknownField {
+            record: self.name.name.to_string(),
+            field,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_def() -> RecordDef {
+        RecordDef::new("user", ["id", "name", "email", "role"])
+            .default_value("role", Atom::from("guest"))
+    }
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    #[test]
+    fn record_to_map_and_back_works() {
+        let def = user_def();
+        assert_eq!(def.arity(), 5);
+        assert_eq!(def.field_index("id"), Some(1));
+        assert_eq!(def.field_index("role"), Some(4));
+        assert_eq!(def.field_index("age"), None);
+
+        let term = Term::from(Tuple::from(vec![
+            atom("user"),
+            Term::from(1),
+            atom("joe"),
+            Term::from(Binary::from("joe@example.com".as_bytes())),
+            atom("admin"),
+        ]));
+        assert!(def.matches(&term));
+        assert_eq!(def.get(&term, "name"), Ok(&atom("joe")));
+
+        let map = def.to_map(&term).unwrap();
+        assert_eq!(map.map.len(), 4);
+        assert_eq!(map.map.get(&atom("role")), Some(&atom("admin")));
+        assert_eq!(Term::from(def.from_map(&map).unwrap()), term);
+
+        let partial = Map::from([(atom("id"), Term::from(2))]);
+        assert_eq!(
+            def.from_map(&partial).unwrap(),
+            Tuple::from(vec![
+                atom("user"),
+                Term::from(2),
+                atom("undefined"),
+                atom("undefined"),
+                atom("guest"),
+            ])
+        );
+
+        let unknown = Map::from([(atom("age"), Term::from(2))]);
+        assert_eq!(
+            def.from_map(&unknown),
+            Err(RecordError::UnknownField {
+                record: "user".to_string(),
+                field: atom("age")
+            })
+        );
+    }
+
+    #[test]
+    fn arity_mismatch_is_reported() {
+        let def = user_def();
+        let term = Term::from(Tuple::from(vec![atom("user"), Term::from(1), atom("joe")]));
+        assert!(!def.matches(&term));
+
+        let e = def.to_map(&term).unwrap_err();
+        assert_eq!(
+            e,
+            RecordError::ArityMismatch {
+                record: "user".to_string(),
+                expected: 5,
+                actual: 3
+            }
+        );
+        assert_eq!(e.to_string(), "record 'user' expects arity 5, got 3");
+
+        let other = Term::from(Tuple::from(vec![atom("group"), Term::from(1)]));
+        assert!(matches!(
+            def.to_map(&other),
+            Err(RecordError::NotARecord { .. })
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+//! Redaction of terms for logging.
+use super::*;
+
+/// Rules of [`Term::redact`].
+///
+/// Redacted subterms are replaced with binaries which describe them (e.g.,
+/// `<<"...redacted 1048576 bytes">>`), so the result is still a term which can be displayed
+/// or converted like any other.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactPolicy {
+    /// Binaries (and bitstrings) longer than this many bytes are replaced.
+    pub max_binary_len: Option<usize>,
+
+    /// Lists longer than this many elements are cut, and end with an element which counts
+    /// the removed ones.
+    pub max_list_len: Option<usize>,
+
+    /// Values of the map keys (atoms or binaries) with these names are replaced, regardless of
+    /// ASCII case.
+    pub keys: Vec<String>,
+
+    /// Elements of the tuples tagged with an atom (e.g., records), given as the tag and the
+    /// (zero-based) index of the element, are replaced.
+    pub tuple_elements: Vec<(String, usize)>,
+
+    /// Subterms at these dot separated paths (e.g., `user.email` or `2.0`) are replaced.
+    ///
+    /// A segment is a map key (an atom, a binary or an integer) or the index of a tuple or
+    /// list element.
+    pub paths: Vec<String>,
+}
+impl RedactPolicy {
+    /// Makes a policy which redacts nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a policy which drops what usually should not be logged: binaries longer than 64
+    /// bytes, the values of keys such as `password` or `token`, and lists after 100 elements.
+    pub fn default_safe() -> Self {
+        RedactPolicy {
+            max_binary_len: Some(64),
+            max_list_len: Some(100),
+            keys: [
+                "password",
+                "passwd",
+                "secret",
+                "token",
+                "access_token",
+                "refresh_token",
+                "api_key",
+                "authorization",
+                "cookie",
+            ]
+            .map(String::from)
+            .to_vec(),
+            tuple_elements: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    fn redact(&self, term: &Term, path: &mut Vec<String>) -> Term {
+        let at = |p: &String| p.split('.').eq(path.iter().map(|s| s.as_str()));
+        if self.paths.iter().any(at) {
+            return placeholder("...redacted".to_owned());
+        }
+        match term {
+            Term::Binary(x) if self.binary_too_long(x.bytes.len()) => {
+                placeholder(format!("...redacted {} bytes", x.bytes.len()))
+            }
+            Term::BitBinary(x) if self.binary_too_long(x.bytes.len()) => {
+                placeholder(format!("...redacted {} bytes", x.bytes.len()))
+            }
+            Term::ByteList(x) if self.list_too_long(x.bytes.len()) => {
+                let elements = x.bytes.iter().map(|b| Term::from(i32::from(*b))).collect();
+                Term::from(List::from(self.redact_elements(elements, path)))
+            }
+            Term::List(x) => Term::from(List::from(self.redact_elements(x.elements.clone(), path))),
+            Term::ImproperList(x) => Term::from(ImproperList {
+                elements: self.redact_elements(x.elements.clone(), path),
+                last: Box::new(self.redact(&x.last, path)),
+            }),
+            Term::Tuple(x) => {
+                let tag = match x.elements.first() {
+                    Some(Term::Atom(tag)) => Some(tag.as_str()),
+                    _ => None,
+                };
+                let elements = x.elements.iter().enumerate().map(|(i, element)| {
+                    let redacted = self
+                        .tuple_elements
+                        .iter()
+                        .any(|(t, j)| Some(t.as_str()) == tag && *j == i);
+                    if redacted {
+                        placeholder("...redacted".to_owned())
+                    } else {
+                        self.redact_within(element, i.to_string(), path)
+                    }
+                });
+                Term::from(Tuple::from(elements.collect::<Vec<_>>()))
+            }
+            Term::Map(x) => {
+                let map = x.map.iter().map(|(k, v)| {
+                    let value = match key_name(k) {
+                        Some(name)
+                            if self.keys.iter().any(|key| key.eq_ignore_ascii_case(&name)) =>
+                        {
+                            placeholder("...redacted".to_owned())
+                        }
+                        Some(name) => self.redact_within(v, name, path),
+                        None => self.redact_within(v, k.to_string(), path),
+                    };
+                    (k.clone(), value)
+                });
+                Term::from(Map::from(map.collect::<TermMap>()))
+            }
+            Term::InternalFun(x) => {
+                let mut fun = x.clone();
+                match &mut *fun {
+                    InternalFun::Old { free_vars, .. } | InternalFun::New { free_vars, .. } => {
+                        for var in free_vars {
+                            *var = self.redact(var, path);
+                        }
+                    }
+                }
+                Term::InternalFun(fun)
+            }
+            _ => term.clone(),
+        }
+    }
+
+    fn redact_within(&self, term: &Term, segment: String, path: &mut Vec<String>) -> Term {
+        path.push(segment);
+        let term = self.redact(term, path);
+        path.pop();
+        term
+    }
+
+    fn redact_elements(&self, mut elements: Vec<Term>, path: &mut Vec<String>) -> Vec<Term> {
+        let removed = match self.max_list_len {
+            Some(max) if elements.len() > max => elements.len() - max,
+            _ => 0,
+        };
+        elements.truncate(elements.len() - removed);
+        for (i, element) in elements.iter_mut().enumerate() {
+            *element = self.redact_within(element, i.to_string(), path);
+        }
+        if removed > 0 {
+            elements.push(placeholder(format!("...redacted {} elements", removed)));
+        }
+        elements
+    }
+
+    fn binary_too_long(&self, len: usize) -> bool {
+        self.max_binary_len.is_some_and(|max| len > max)
+    }
+
+    fn list_too_long(&self, len: usize) -> bool {
+        self.max_list_len.is_some_and(|max| len > max)
+    }
+}
+
+/// Returns the name of a map key in a path, if it is an atom, a UTF-8 binary or an integer.
+pub(crate) fn key_name(key: &Term) -> Option<String> {
+    match key {
+        Term::Atom(atom) => Some(atom.as_str().to_owned()),
+        Term::Binary(binary) => std::str::from_utf8(&binary.bytes).ok().map(str::to_owned),
+        Term::FixInteger(n) => Some(n.value.to_string()),
+        _ => None,
+    }
+}
+
+fn placeholder(text: String) -> Term {
+    Term::from(Binary::from(text.into_bytes()))
+}
+
+impl Term {
+    /// Returns a copy of the term without the subterms selected by `policy` (e.g., for logging).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eetf::{Atom, Binary, Map, RedactPolicy, Term};
+    ///
+    /// let term = Term::from(Map::from([(
+    ///     Term::from(Atom::from("password")),
+    ///     Term::from(Binary::from(b"hunter2".to_vec())),
+    /// )]));
+    /// let redacted = Term::from(Map::from([(
+    ///     Term::from(Atom::from("password")),
+    ///     Term::from(Binary::from(b"...redacted".to_vec())),
+    /// )]));
+    /// assert_eq!(term.redact(&RedactPolicy::default_safe()), redacted);
+    /// ```
+    pub fn redact(&self, policy: &RedactPolicy) -> Term {
+        policy.redact(self, &mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn binary(bytes: &[u8]) -> Term {
+        Term::from(Binary::from(bytes.to_vec()))
+    }
+
+    fn map<const N: usize>(entries: [(Term, Term); N]) -> Term {
+        Term::from(Map::from(entries))
+    }
+
+    #[test]
+    fn default_safe_policy_works() {
+        let request = |password, body| {
+            Term::from(Tuple::from(vec![
+                atom("login"),
+                map([
+                    (atom("user"), binary(b"joe")),
+                    (binary(b"Password"), password),
+                ]),
+                body,
+            ]))
+        };
+        let term = request(binary(b"hunter2"), binary(&vec![0; 1 << 20]));
+        let expected = request(binary(b"...redacted"), binary(b"...redacted 1048576 bytes"));
+        assert_eq!(term.redact(&RedactPolicy::default_safe()), expected);
+        assert_eq!(term.redact(&RedactPolicy::new()), term);
+    }
+
+    #[test]
+    fn lists_are_cut() {
+        let policy = RedactPolicy {
+            max_list_len: Some(2),
+            ..RedactPolicy::new()
+        };
+        let term = Term::from(List::from(vec![
+            Term::from(1),
+            Term::from(2),
+            Term::from(3),
+        ]));
+        let expected = Term::from(List::from(vec![
+            Term::from(1),
+            Term::from(2),
+            binary(b"...redacted 1 elements"),
+        ]));
+        assert_eq!(term.redact(&policy), expected);
+        assert_eq!(
+            Term::from(ByteList::from(&[1, 2, 3])).redact(&policy),
+            expected
+        );
+        assert_eq!(
+            Term::from(ByteList::from("ab")).redact(&policy),
+            Term::from(ByteList::from("ab"))
+        );
+    }
+
+    #[test]
+    fn paths_and_tuple_elements_are_replaced() {
+        let policy = RedactPolicy {
+            tuple_elements: vec![("user".to_owned(), 2)],
+            paths: vec!["0.email".to_owned()],
+            ..RedactPolicy::new()
+        };
+        let user = |name, email| {
+            Term::from(List::from(vec![
+                map([(atom("email"), email)]),
+                Term::from(Tuple::from(vec![atom("user"), Term::from(1), name])),
+            ]))
+        };
+        let term = user(binary(b"joe"), binary(b"joe@example.com"));
+        let expected = user(binary(b"...redacted"), binary(b"...redacted"));
+        assert_eq!(term.redact(&policy), expected);
+    }
+}
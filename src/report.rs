@@ -0,0 +1,218 @@
+//! Anomalies of decodable terms, reported by [`Decoder::decode_with_report`].
+//!
+//! Some encodings are valid but suspicious: they use deprecated tags, repeat the keys of a map,
+//! encode integers with more bytes than needed, or hold atoms longer than the Erlang runtime
+//! accepts. Such terms are decoded as usual, and each anomaly is listed in the [`DecodeReport`]
+//! as a [`Warning`], so that an ingestion pipeline can accept and log them. With the
+//! [`strict`](DecoderOptions::strict) option, the kinds for which
+//! [`WarningKind::is_strict_error`] is `true` fail the decode with [`DecodeError::Rejected`].
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::report::WarningKind;
+//! use eetf::{Decoder, DecoderOptions, Term};
+//!
+//! // The integer 1 encoded as an `INTEGER_EXT`.
+//! let bytes = [131, 98, 0, 0, 0, 1];
+//! let (term, report) = Decoder::new(&bytes[..]).decode_with_report().unwrap();
+//! assert_eq!(term, Term::from(1));
+//! assert_eq!(report.warnings()[0].kind, WarningKind::NonMinimalInteger { tag: 98 });
+//!
+//! let options = DecoderOptions::new().with_strict(true);
+//! assert!(Decoder::with_options(&bytes[..], options).decode().is_err());
+//! ```
+use super::*;
+use codec_common::*;
+
+/// Longest atom (in characters) which the Erlang runtime accepts.
+const MAX_ATOM_CHARS: usize = 255;
+
+/// Warnings about the anomalies of a term decoded by [`Decoder::decode_with_report`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeReport {
+    warnings: Vec<Warning>,
+}
+impl DecodeReport {
+    /// Returns the warnings in the order of the input.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn into_warnings(self) -> Vec<Warning> {
+        self.warnings
+    }
+
+    /// Returns `true` if the term has no anomalies.
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+/// Anomaly of a subterm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+
+    /// Position of the decoder (see [`Decoder::position`]) at the tag of the subterm. Within a
+    /// compressed term, it is the offset in the inflated term (its tag being at offset 0).
+    pub offset: u64,
+
+    /// Steps from the decoded term to the subterm, outermost first.
+    pub path: Vec<ReportPathSegment>,
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        for (i, segment) in self.path.iter().enumerate() {
+            let separator = if i == 0 { " (at " } else { " → " };
+            write!(f, "{}{}", separator, segment)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Kind of a [`Warning`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningKind {
+    /// The subterm has a deprecated tag (`ATOM_EXT`, `SMALL_ATOM_EXT`, `REFERENCE_EXT` or
+    /// `FUN_EXT`).
+    ///
+    /// [`Encoder`] and Erlang/OTP before 26 encode ASCII atoms as `ATOM_EXT`, so the atom tags
+    /// are never errors.
+    DeprecatedTag { tag: u8 },
+
+    /// The map has the key more than once, so that only its last value is kept.
+    DuplicateMapKey { key: Term },
+
+    /// The integer has a tag for larger values (e.g., `INTEGER_EXT` for 1), or a big integer has
+    /// leading zero bytes.
+    NonMinimalInteger { tag: u8 },
+
+    /// The atom is longer than the 255 characters which the Erlang runtime accepts.
+    LongAtom { chars: usize },
+
+    /// The `STRING_EXT` holds bytes which are not printable Latin-1 characters, so it is likely
+    /// a list of small integers rather than a string.
+    ///
+    /// Erlang encodes every list of integers in `0..=255` as a `STRING_EXT`, so this kind is
+    /// never an error.
+    NonPrintableString,
+}
+impl WarningKind {
+    /// Returns `true` if the [`strict`](DecoderOptions::strict) option rejects this kind.
+    pub fn is_strict_error(&self) -> bool {
+        match *self {
+            WarningKind::DeprecatedTag { tag } => !matches!(tag, ATOM_EXT | SMALL_ATOM_EXT),
+            WarningKind::NonPrintableString => false,
+            _ => true,
+        }
+    }
+}
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WarningKind::DeprecatedTag { tag } => write!(f, "deprecated tag {}", tag),
+            WarningKind::DuplicateMapKey { ref key } => write!(f, "duplicate map key {}", key),
+            WarningKind::NonMinimalInteger { tag } => {
+                write!(f, "integer not minimally encoded with tag {}", tag)
+            }
+            WarningKind::LongAtom { chars } => write!(f, "atom of {} characters", chars),
+            WarningKind::NonPrintableString => write!(f, "non-printable STRING_EXT"),
+        }
+    }
+}
+
+/// A step in the [path](Warning::path) from a decoded term to the subterm of a warning.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportPathSegment {
+    ListElement(usize),
+    ListTail,
+    TupleElement(usize),
+    MapKey(usize),
+    MapValue { key: String },
+}
+impl fmt::Display for ReportPathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReportPathSegment::ListElement(i) => write!(f, "list[{}]", i),
+            ReportPathSegment::ListTail => write!(f, "list tail"),
+            ReportPathSegment::TupleElement(i) => write!(f, "tuple[{}]", i),
+            ReportPathSegment::MapKey(i) => write!(f, "map key[{}]", i),
+            ReportPathSegment::MapValue { ref key } => write!(f, "map value for key {}", key),
+        }
+    }
+}
+
+/// Collector of the warnings of a decoder.
+#[derive(Default)]
+pub(crate) struct Reporter {
+    warnings: Vec<Warning>,
+    path: Vec<ReportPathSegment>,
+    /// Offset of the position 0 of the reader of the decoder.
+    pub(crate) base: u64,
+    /// Offset of the tag of the subterm being decoded.
+    pub(crate) start: u64,
+}
+impl Reporter {
+    /// Moves the reporter to a decoder whose reader has its position 0 at offset `base`.
+    pub(crate) fn with_base(self, base: u64) -> Self {
+        Reporter { base, ..self }
+    }
+
+    pub(crate) fn enter(&mut self, segment: ReportPathSegment) {
+        self.path.push(segment);
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    /// Records a warning about the subterm at `offset`, or fails if `strict` rejects its kind.
+    pub(crate) fn warn(
+        &mut self,
+        offset: u64,
+        kind: WarningKind,
+        strict: bool,
+    ) -> Result<(), DecodeError> {
+        let warning = Warning {
+            kind,
+            offset,
+            path: self.path.clone(),
+        };
+        if strict && warning.kind.is_strict_error() {
+            return Err(DecodeError::Rejected { warning });
+        }
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> DecodeReport {
+        DecodeReport {
+            warnings: self.warnings,
+        }
+    }
+}
+
+pub(crate) fn is_deprecated_tag(tag: u8) -> bool {
+    matches!(tag, ATOM_EXT | SMALL_ATOM_EXT | REFERENCE_EXT | FUN_EXT)
+}
+
+/// Returns the number of characters of `name` if it is too long for an atom.
+pub(crate) fn long_atom_chars(name: &str) -> Option<usize> {
+    // An atom of at most 255 bytes has at most 255 characters.
+    if name.len() <= MAX_ATOM_CHARS {
+        return None;
+    }
+    Some(name.chars().count()).filter(|&chars| chars > MAX_ATOM_CHARS)
+}
+
+/// Returns `true` if `bytes` are printable Latin-1 characters (as by `io_lib:printable_list/1`).
+pub(crate) fn is_printable(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .all(|&b| matches!(b, 8..=13 | 27 | 32..=126 | 160..=255))
+}
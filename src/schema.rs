@@ -0,0 +1,420 @@
+//! Declarative validation of the shape of terms.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::schema::Shape;
+//! use eetf::{Atom, Binary, Map, Term, Tuple};
+//!
+//! let event = Shape::tuple([
+//!     Shape::atom("event"),
+//!     Shape::binary(),
+//!     Shape::map()
+//!         .required("ts", Shape::integer())
+//!         .required("payload", Shape::Any),
+//! ]);
+//!
+//! let term = Term::from(Tuple::from(vec![
+//!     Term::from(Atom::from("event")),
+//!     Term::from(Binary::from(b"click".to_vec())),
+//!     Term::from(Map::from([(Term::from(Atom::from("ts")), Term::from(1))])),
+//! ]));
+//! let error = event.validate(&term).unwrap_err();
+//! assert_eq!(
+//!     error.to_string(),
+//!     "expected a map with the key 'payload', found a map of 1 entries at tuple[2]"
+//! );
+//! ```
+use super::*;
+use num::traits::ToPrimitive;
+use std::ops::RangeInclusive;
+
+/// Expected shape of a term.
+///
+/// Shapes are built with the constructors below, which are shorter than the variants.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Any term.
+    Any,
+
+    /// The atom with the given name.
+    Atom(String),
+
+    /// An integer, in the range if there is one.
+    Integer(Option<RangeInclusive<i64>>),
+
+    /// A binary, whose size in bytes is in the range if there is one.
+    Binary(Option<RangeInclusive<usize>>),
+
+    /// A tuple whose elements have the given shapes.
+    Tuple(Vec<Shape>),
+
+    /// A proper list whose elements have the given shape.
+    List(Box<Shape>),
+
+    /// A map with the given keys (other keys are allowed).
+    Map(MapShape),
+
+    /// A term which has one of the given shapes.
+    Union(Vec<Shape>),
+}
+
+/// Keys of a map [`Shape`] and the shapes of their values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapShape {
+    pub required: Vec<(Term, Shape)>,
+    pub optional: Vec<(Term, Shape)>,
+}
+
+impl Shape {
+    pub fn atom(name: &str) -> Self {
+        Shape::Atom(name.to_owned())
+    }
+
+    pub fn integer() -> Self {
+        Shape::Integer(None)
+    }
+
+    pub fn integer_in(range: RangeInclusive<i64>) -> Self {
+        Shape::Integer(Some(range))
+    }
+
+    pub fn binary() -> Self {
+        Shape::Binary(None)
+    }
+
+    pub fn binary_len(range: RangeInclusive<usize>) -> Self {
+        Shape::Binary(Some(range))
+    }
+
+    pub fn tuple<I: IntoIterator<Item = Shape>>(elements: I) -> Self {
+        Shape::Tuple(elements.into_iter().collect())
+    }
+
+    pub fn list(element: Shape) -> Self {
+        Shape::List(Box::new(element))
+    }
+
+    /// Returns a map shape without keys, to which keys are added with
+    /// [`Shape::required`] and [`Shape::optional`].
+    pub fn map() -> Self {
+        Shape::Map(MapShape::default())
+    }
+
+    pub fn union<I: IntoIterator<Item = Shape>>(shapes: I) -> Self {
+        Shape::Union(shapes.into_iter().collect())
+    }
+
+    /// Adds the atom key `key` whose value must have the shape `value` to a map shape.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not a map shape.
+    pub fn required(self, key: &str, value: Shape) -> Self {
+        self.with_key(key, value, true)
+    }
+
+    /// Adds the atom key `key` whose value, if any, must have the shape `value` to a map shape.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is not a map shape.
+    pub fn optional(self, key: &str, value: Shape) -> Self {
+        self.with_key(key, value, false)
+    }
+
+    fn with_key(self, key: &str, value: Shape, required: bool) -> Self {
+        let Shape::Map(mut map) = self else {
+            panic!("{} is not a map shape", self);
+        };
+        let entry = (Term::from(Atom::from(key)), value);
+        if required {
+            map.required.push(entry);
+        } else {
+            map.optional.push(entry);
+        }
+        Shape::Map(map)
+    }
+
+    /// Checks that `term` has this shape.
+    pub fn validate(&self, term: &Term) -> Result<(), SchemaError> {
+        let mismatch = || SchemaError {
+            expected: self.to_string(),
+            found: describe(term),
+            path: SchemaPath::default(),
+        };
+        match (self, term) {
+            (Shape::Any, _) => Ok(()),
+            (Shape::Atom(name), Term::Atom(atom)) if atom.as_str() == name => Ok(()),
+            (Shape::Integer(range), Term::FixInteger(x)) => match range {
+                Some(range) if !range.contains(&i64::from(x.value)) => Err(mismatch()),
+                _ => Ok(()),
+            },
+            (Shape::Integer(range), Term::BigInteger(x)) => match (range, x.value.to_i64()) {
+                (None, _) => Ok(()),
+                (Some(range), Some(value)) if range.contains(&value) => Ok(()),
+                _ => Err(mismatch()),
+            },
+            (Shape::Binary(len), Term::Binary(x)) => match len {
+                Some(len) if !len.contains(&x.bytes.len()) => Err(mismatch()),
+                _ => Ok(()),
+            },
+            (Shape::Tuple(shapes), Term::Tuple(x)) if shapes.len() == x.elements.len() => {
+                for (i, (shape, element)) in shapes.iter().zip(&x.elements).enumerate() {
+                    shape
+                        .validate(element)
+                        .map_err(|e| e.within(SchemaPathSegment::TupleElement(i)))?;
+                }
+                Ok(())
+            }
+            (Shape::List(shape), Term::List(x)) => {
+                for (i, element) in x.elements.iter().enumerate() {
+                    shape
+                        .validate(element)
+                        .map_err(|e| e.within(SchemaPathSegment::ListElement(i)))?;
+                }
+                Ok(())
+            }
+            (Shape::List(shape), Term::ByteList(x)) => {
+                for (i, byte) in x.bytes.iter().enumerate() {
+                    shape
+                        .validate(&Term::from(i32::from(*byte)))
+                        .map_err(|e| e.within(SchemaPathSegment::ListElement(i)))?;
+                }
+                Ok(())
+            }
+            (Shape::Map(shape), Term::Map(x)) => {
+                for (key, value_shape) in &shape.required {
+                    let Some(value) = x.map.get(key) else {
+                        return Err(SchemaError {
+                            expected: format!("a map with the key {}", key),
+                            ..mismatch()
+                        });
+                    };
+                    value_shape
+                        .validate(value)
+                        .map_err(|e| e.within(SchemaPathSegment::MapValue { key: key.clone() }))?;
+                }
+                for (key, value_shape) in &shape.optional {
+                    if let Some(value) = x.map.get(key) {
+                        value_shape.validate(value).map_err(|e| {
+                            e.within(SchemaPathSegment::MapValue { key: key.clone() })
+                        })?;
+                    }
+                }
+                Ok(())
+            }
+            (Shape::Union(shapes), _) if shapes.iter().any(|s| s.validate(term).is_ok()) => Ok(()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Shape::Any => write!(f, "any term"),
+            Shape::Atom(name) => write!(f, "the atom {}", Atom::from(name.as_str())),
+            Shape::Integer(None) => write!(f, "an integer"),
+            Shape::Integer(Some(range)) => {
+                write!(f, "an integer in {}..={}", range.start(), range.end())
+            }
+            Shape::Binary(None) => write!(f, "a binary"),
+            Shape::Binary(Some(len)) => {
+                write!(f, "a binary of {}..={} bytes", len.start(), len.end())
+            }
+            Shape::Tuple(shapes) => write!(f, "a {}-tuple", shapes.len()),
+            Shape::List(_) => write!(f, "a list"),
+            Shape::Map(_) => write!(f, "a map"),
+            Shape::Union(shapes) => {
+                write!(f, "one of ")?;
+                for (i, shape) in shapes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " or ")?;
+                    }
+                    write!(f, "{}", shape)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Describes `term` for a [`SchemaError`].
+fn describe(term: &Term) -> String {
+    match term {
+        Term::Atom(x) => format!("the atom {}", x),
+        Term::FixInteger(x) => format!("the integer {}", x),
+        Term::BigInteger(x) => format!("the integer {}", x),
+        Term::Float(x) => format!("the float {}", x),
+        Term::Pid(_) => "a pid".to_owned(),
+        Term::Port(_) => "a port".to_owned(),
+        Term::Reference(_) => "a reference".to_owned(),
+        Term::ExternalFun(_) | Term::InternalFun(_) => "a fun".to_owned(),
+        Term::Binary(x) => format!("a binary of {} bytes", x.bytes.len()),
+        Term::BitBinary(_) => "a bitstring".to_owned(),
+        Term::ByteList(x) => format!("a list of {} elements", x.bytes.len()),
+        Term::List(x) => format!("a list of {} elements", x.elements.len()),
+        Term::ImproperList(_) => "an improper list".to_owned(),
+        Term::Tuple(x) => format!("a {}-tuple", x.elements.len()),
+        Term::Map(x) => format!("a map of {} entries", x.map.len()),
+        Term::Raw(_) => "a raw term".to_owned(),
+    }
+}
+
+/// Error returned when a term does not have the expected [`Shape`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("expected {expected}, found {found}{path}")]
+pub struct SchemaError {
+    /// The expected shape.
+    pub expected: String,
+
+    /// The mismatched term.
+    pub found: String,
+
+    /// The path from the validated term to the mismatched one.
+    pub path: SchemaPath,
+}
+impl SchemaError {
+    fn within(mut self, segment: SchemaPathSegment) -> Self {
+        self.path.segments.push(segment);
+        self
+    }
+}
+
+/// A step in the path from a validated term to the mismatched subterm.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaPathSegment {
+    TupleElement(usize),
+    ListElement(usize),
+    MapValue { key: Term },
+}
+impl fmt::Display for SchemaPathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemaPathSegment::TupleElement(i) => write!(f, "tuple[{}]", i),
+            SchemaPathSegment::ListElement(i) => write!(f, "list[{}]", i),
+            SchemaPathSegment::MapValue { ref key } => write!(f, "map value for key {}", key),
+        }
+    }
+}
+
+/// Path from a validated term to the mismatched subterm.
+///
+/// It is displayed outermost step first (e.g., ` at tuple[2] → map value for key 'ts'`),
+/// and is empty if the mismatched term is the validated term itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaPath {
+    /// The steps, innermost first.
+    pub segments: Vec<SchemaPathSegment>,
+}
+impl fmt::Display for SchemaPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().rev().enumerate() {
+            if i == 0 {
+                write!(f, " at {}", segment)?;
+            } else {
+                write!(f, " → {}", segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    fn event_shape() -> Shape {
+        Shape::tuple([
+            Shape::atom("event"),
+            Shape::binary_len(1..=16),
+            Shape::map()
+                .required("ts", Shape::integer_in(0..=i64::MAX))
+                .required("payload", Shape::Any)
+                .optional(
+                    "tags",
+                    Shape::list(Shape::union([Shape::atom("a"), Shape::binary()])),
+                ),
+        ])
+    }
+
+    fn event(fields: Vec<(Term, Term)>) -> Term {
+        Term::from(Tuple::from(vec![
+            atom("event"),
+            Term::from(Binary::from(b"click".to_vec())),
+            Term::from(Map::from(fields.into_iter().collect::<HashMap<_, _>>())),
+        ]))
+    }
+
+    #[test]
+    fn valid_terms_pass() {
+        let fields = vec![(atom("ts"), Term::from(1)), (atom("payload"), atom("x"))];
+        assert_eq!(event_shape().validate(&event(fields)), Ok(()));
+
+        let ts = Term::from(BigInteger::from(i64::MAX));
+        let fields = vec![(atom("ts"), ts), (atom("payload"), Term::from(List::nil()))];
+        assert_eq!(event_shape().validate(&event(fields)), Ok(()));
+    }
+
+    #[test]
+    fn nested_failures_have_paths() {
+        let fields = vec![(atom("ts"), atom("now")), (atom("payload"), atom("x"))];
+        let error = event_shape().validate(&event(fields)).unwrap_err();
+        assert_eq!(error.expected, "an integer in 0..=9223372036854775807");
+        assert_eq!(error.found, "the atom 'now'");
+        assert_eq!(
+            error.to_string(),
+            "expected an integer in 0..=9223372036854775807, found the atom 'now' \
+             at tuple[2] → map value for key 'ts'"
+        );
+
+        let error = event_shape().validate(&atom("event")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a 3-tuple, found the atom 'event'"
+        );
+
+        let term = Term::from(Tuple::from(vec![
+            atom("event"),
+            Term::from(Binary::from(Vec::new())),
+            Term::from(Map::new()),
+        ]));
+        let error = event_shape().validate(&term).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a binary of 1..=16 bytes, found a binary of 0 bytes at tuple[1]"
+        );
+    }
+
+    #[test]
+    fn optional_map_keys_are_checked_if_present() {
+        let mut fields = vec![(atom("ts"), Term::from(1)), (atom("payload"), atom("x"))];
+        fields.push((atom("tags"), Term::from(List::from(vec![atom("a")]))));
+        assert_eq!(event_shape().validate(&event(fields.clone())), Ok(()));
+
+        fields[2].1 = Term::from(List::from(vec![atom("a"), atom("b")]));
+        let error = event_shape().validate(&event(fields)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected one of the atom 'a' or a binary, found the atom 'b' \
+             at tuple[2] → map value for key 'tags' → list[1]"
+        );
+    }
+
+    #[test]
+    fn byte_lists_are_lists() {
+        let shape = Shape::list(Shape::integer_in(0..=127));
+        assert_eq!(shape.validate(&Term::from(ByteList::from("abc"))), Ok(()));
+        let error = shape
+            .validate(&Term::from(ByteList::from(&[1, 200])))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected an integer in 0..=127, found the integer 200 at list[1]"
+        );
+    }
+}
@@ -0,0 +1,626 @@
+//! Serialize Rust values into terms with serde.
+//!
+//! The default mapping is:
+//!
+//! | Rust (serde data model)   | Term                                     |
+//! |---------------------------|------------------------------------------|
+//! | `bool`                    | `true` / `false` atoms                   |
+//! | integers                  | `FixInteger`, or `BigInteger` if needed  |
+//! | floats                    | `Float` (non-finite values are errors)   |
+//! | `char`, strings           | UTF-8 `Binary`                           |
+//! | bytes                     | `Binary`                                 |
+//! | `None`, `()`, unit struct | the atom selected by [`UnitRepr`]        |
+//! | `Some(x)`, newtype struct | `x`                                      |
+//! | sequences                 | `List`                                   |
+//! | tuples, tuple structs     | `Tuple`                                  |
+//! | maps                      | `Map`                                    |
+//! | structs                   | `Map` keyed by field name atoms          |
+//! | unit variants             | the variant name atom                    |
+//! | newtype variants          | `{Variant, Value}`                       |
+//! | tuple variants            | `{Variant, Value1, ...}`                 |
+//! | struct variants           | `{Variant, #{field => Value, ...}}`      |
+//!
+//! The representations of enums and struct keys can be changed with
+//! [`EnumRepr`] and [`KeyRepr`].
+//!
+//! # Examples
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Point {
+//!     x: i32,
+//! }
+//!
+//! let term = eetf::to_term(&Point { x: 1 }).unwrap();
+//! assert_eq!(term.to_string(), "#{'x'=>1}");
+//! ```
+use super::*;
+use ::serde::ser::{self, Serialize};
+
+/// Errors which can occur when serializing a value.
+#[derive(Debug, thiserror::Error)]
+pub enum SerializeError {
+    #[error("{0}")]
+    Message(String),
+
+    #[error("failed to encode the serialized term")]
+    Encode(#[from] EncodeError),
+
+    #[error("tried to serialize non-finite float")]
+    NonFiniteFloat,
+}
+impl ser::Error for SerializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerializeError::Message(msg.to_string())
+    }
+}
+
+/// How `()`, unit structs, and `None` are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnitRepr {
+    /// The atom `undefined` (Erlang convention).
+    #[default]
+    Undefined,
+
+    /// The atom `nil` (Elixir convention).
+    Nil,
+}
+impl UnitRepr {
+    pub fn atom_name(self) -> &'static str {
+        match self {
+            UnitRepr::Undefined => "undefined",
+            UnitRepr::Nil => "nil",
+        }
+    }
+}
+
+/// How enum variants are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// Unit variants are bare atoms, other variants are `{Variant, Value...}` tuples.
+    #[default]
+    Atom,
+
+    /// Every variant is a tuple tagged with the variant name atom (e.g., `{Variant}`).
+    TaggedTuple,
+
+    /// Every variant is a single-entry map `#{Variant => Payload}`.
+    ///
+    /// The key follows [`KeyRepr`], a unit variant's payload is the unit atom,
+    /// and a tuple variant's payload is a tuple.
+    ExternallyTaggedMap,
+}
+
+/// How struct field names (and map enum tags) are represented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRepr {
+    /// Atoms (e.g., `#{name => ...}`).
+    #[default]
+    Atom,
+
+    /// UTF-8 binaries (e.g., `#{<<"name">> => ...}`).
+    Binary,
+}
+impl KeyRepr {
+    pub(crate) fn key(self, name: &str) -> Term {
+        match self {
+            KeyRepr::Atom => atom(name),
+            KeyRepr::Binary => binary(name.as_bytes()),
+        }
+    }
+
+    pub(crate) fn matches(self, term: &Term) -> bool {
+        matches!(
+            (self, term),
+            (KeyRepr::Atom, Term::Atom(_)) | (KeyRepr::Binary, Term::Binary(_))
+        )
+    }
+}
+
+/// Options for serialization.
+#[derive(Debug, Default, Clone)]
+pub struct SerializeOptions {
+    unit_repr: UnitRepr,
+    enum_repr: EnumRepr,
+    key_repr: KeyRepr,
+}
+impl SerializeOptions {
+    /// Makes the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how `()`, unit structs, and `None` are represented (default: [`UnitRepr::Undefined`]).
+    pub fn unit_repr(mut self, repr: UnitRepr) -> Self {
+        self.unit_repr = repr;
+        self
+    }
+
+    /// Sets how enum variants are represented (default: [`EnumRepr::Atom`]).
+    pub fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+
+    /// Sets how struct field names are represented (default: [`KeyRepr::Atom`]).
+    pub fn key_repr(mut self, repr: KeyRepr) -> Self {
+        self.key_repr = repr;
+        self
+    }
+}
+
+/// Serializes `value` into a term.
+pub fn to_term<T: Serialize + ?Sized>(value: &T) -> Result<Term, SerializeError> {
+    to_term_with_options(value, &SerializeOptions::default())
+}
+
+/// Serializes `value` into a term using `options`.
+pub fn to_term_with_options<T: Serialize + ?Sized>(
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<Term, SerializeError> {
+    value.serialize(Serializer { options })
+}
+
+/// Serializes `value` into the external term format.
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerializeError> {
+    to_bytes_with_options(value, &SerializeOptions::default())
+}
+
+/// Serializes `value` into the external term format using `options`.
+pub fn to_bytes_with_options<T: Serialize + ?Sized>(
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>, SerializeError> {
+    let mut buf = Vec::new();
+    to_term_with_options(value, options)?.encode(&mut buf)?;
+    Ok(buf)
+}
+
+/// Serializer whose output is a `Term`.
+#[derive(Debug, Clone, Copy)]
+pub struct Serializer<'a> {
+    options: &'a SerializeOptions,
+}
+impl<'a> Serializer<'a> {
+    pub fn new(options: &'a SerializeOptions) -> Self {
+        Serializer { options }
+    }
+
+    fn unit(&self) -> Term {
+        Term::from(Atom::from(self.options.unit_repr.atom_name()))
+    }
+
+    fn key(&self, name: &str) -> Term {
+        self.options.key_repr.key(name)
+    }
+
+    fn variant(&self, variant: &str, payload: Option<Term>) -> Term {
+        match (self.options.enum_repr, payload) {
+            (EnumRepr::Atom, None) => atom(variant),
+            (EnumRepr::Atom | EnumRepr::TaggedTuple, payload) => {
+                let elements = std::iter::once(atom(variant)).chain(payload);
+                Term::from(Tuple::from(elements.collect::<Vec<_>>()))
+            }
+            (EnumRepr::ExternallyTaggedMap, payload) => {
+                let value = payload.unwrap_or_else(|| self.unit());
+                Term::from(Map::from([(self.key(variant), value)]))
+            }
+        }
+    }
+
+    fn tuple_variant(&self, variant: &str, mut elements: Vec<Term>) -> Term {
+        match self.options.enum_repr {
+            EnumRepr::Atom | EnumRepr::TaggedTuple => {
+                elements.insert(0, atom(variant));
+                Term::from(Tuple::from(elements))
+            }
+            EnumRepr::ExternallyTaggedMap => {
+                self.variant(variant, Some(Term::from(Tuple::from(elements))))
+            }
+        }
+    }
+}
+
+fn atom(name: &str) -> Term {
+    Term::from(Atom::from(name))
+}
+
+fn binary(bytes: &[u8]) -> Term {
+    Term::from(Binary::from(bytes))
+}
+
+fn float(value: f64) -> Result<Term, SerializeError> {
+    Float::try_from(value)
+        .map(Term::from)
+        .map_err(|_| SerializeError::NonFiniteFloat)
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    type SerializeSeq = SerializeVec<'a>;
+    type SerializeTuple = SerializeVec<'a>;
+    type SerializeTupleStruct = SerializeVec<'a>;
+    type SerializeTupleVariant = SerializeVec<'a>;
+    type SerializeMap = SerializeMap<'a>;
+    type SerializeStruct = SerializeMap<'a>;
+    type SerializeStructVariant = SerializeMap<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Term, SerializeError> {
+        match i32::try_from(v) {
+            Ok(v) => Ok(Term::from(v)),
+            Err(_) => Ok(Term::from(BigInteger {
+                value: BigInt::from(v),
+            })),
+        }
+    }
+    fn serialize_u8(self, v: u8) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Term, SerializeError> {
+        Ok(Term::from(v))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Term, SerializeError> {
+        match i32::try_from(v) {
+            Ok(v) => Ok(Term::from(v)),
+            Err(_) => Ok(Term::from(BigInteger {
+                value: BigInt::from(v),
+            })),
+        }
+    }
+    fn serialize_f32(self, v: f32) -> Result<Term, SerializeError> {
+        float(f64::from(v))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Term, SerializeError> {
+        float(v)
+    }
+    fn serialize_char(self, v: char) -> Result<Term, SerializeError> {
+        Ok(binary(v.encode_utf8(&mut [0; 4]).as_bytes()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Term, SerializeError> {
+        Ok(binary(v.as_bytes()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Term, SerializeError> {
+        Ok(binary(v))
+    }
+    fn serialize_none(self) -> Result<Term, SerializeError> {
+        Ok(self.unit())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Term, SerializeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Term, SerializeError> {
+        Ok(self.unit())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Term, SerializeError> {
+        Ok(self.unit())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Term, SerializeError> {
+        Ok(self.variant(variant, None))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Term, SerializeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Term, SerializeError> {
+        let value = value.serialize(self)?;
+        Ok(self.variant(variant, Some(value)))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec<'a>, SerializeError> {
+        Ok(SerializeVec::new(self, len.unwrap_or(0), VecKind::List))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec<'a>, SerializeError> {
+        Ok(SerializeVec::new(self, len, VecKind::Tuple))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec<'a>, SerializeError> {
+        Ok(SerializeVec::new(self, len, VecKind::Tuple))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec<'a>, SerializeError> {
+        Ok(SerializeVec::new(self, len, VecKind::Variant(variant)))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap<'a>, SerializeError> {
+        Ok(SerializeMap::new(self, len.unwrap_or(0), None))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap<'a>, SerializeError> {
+        Ok(SerializeMap::new(self, len, None))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap<'a>, SerializeError> {
+        Ok(SerializeMap::new(self, len, Some(variant)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VecKind {
+    List,
+    Tuple,
+    Variant(&'static str),
+}
+
+#[doc(hidden)]
+pub struct SerializeVec<'a> {
+    serializer: Serializer<'a>,
+    elements: Vec<Term>,
+    kind: VecKind,
+}
+impl<'a> SerializeVec<'a> {
+    fn new(serializer: Serializer<'a>, len: usize, kind: VecKind) -> Self {
+        SerializeVec {
+            serializer,
+            elements: Vec::with_capacity(len),
+            kind,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.elements.push(value.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Term, SerializeError> {
+        Ok(match self.kind {
+            VecKind::List => Term::from(List::from(self.elements)),
+            VecKind::Tuple => Term::from(Tuple::from(self.elements)),
+            VecKind::Variant(variant) => self.serializer.tuple_variant(variant, self.elements),
+        })
+    }
+}
+impl<'a> ser::SerializeSeq for SerializeVec<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeTuple for SerializeVec<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeTupleStruct for SerializeVec<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeTupleVariant for SerializeVec<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+
+#[doc(hidden)]
+pub struct SerializeMap<'a> {
+    serializer: Serializer<'a>,
+    map: TermMap,
+    next_key: Option<Term>,
+    variant: Option<&'static str>,
+}
+impl<'a> SerializeMap<'a> {
+    fn new(serializer: Serializer<'a>, len: usize, variant: Option<&'static str>) -> Self {
+        SerializeMap {
+            serializer,
+            map: TermMap::with_capacity(len),
+            next_key: None,
+            variant,
+        }
+    }
+
+    fn field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        let value = value.serialize(self.serializer)?;
+        self.map.insert(self.serializer.key(key), value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Term, SerializeError> {
+        let map = Term::from(Map::from(self.map));
+        Ok(match self.variant {
+            None => map,
+            Some(variant) => self.serializer.variant(variant, Some(map)),
+        })
+    }
+}
+impl<'a> ser::SerializeMap for SerializeMap<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.next_key = Some(key.serialize(self.serializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self.next_key.take().ok_or_else(|| {
+            SerializeError::Message("serialize_value called before serialize_key".to_string())
+        })?;
+        let value = value.serialize(self.serializer)?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeStruct for SerializeMap<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeStructVariant for SerializeMap<'a> {
+    type Ok = Term;
+    type Error = SerializeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<Term, SerializeError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::serde::Serialize;
+
+    #[derive(Serialize)]
+    enum Role {
+        Guest,
+        Admin(u8),
+    }
+
+    fn atom(name: &str) -> Term {
+        Term::from(Atom::from(name))
+    }
+
+    #[test]
+    fn default_mapping_works() {
+        assert_eq!(to_term(&true).unwrap(), atom("true"));
+        assert_eq!(to_term(&()).unwrap(), atom("undefined"));
+        assert_eq!(
+            to_term_with_options(
+                &None::<i32>,
+                &SerializeOptions::new().unit_repr(UnitRepr::Nil)
+            )
+            .unwrap(),
+            atom("nil")
+        );
+        assert_eq!(
+            to_term(&u64::MAX).unwrap(),
+            Term::from(BigInteger::from(u64::MAX))
+        );
+        assert_eq!(
+            to_term(&"foo").unwrap(),
+            Term::from(Binary::from("foo".as_bytes()))
+        );
+        assert_eq!(
+            to_term(&Role::Admin(3)).unwrap(),
+            Term::from(Tuple::from(vec![atom("Admin"), Term::from(3)]))
+        );
+        assert_eq!(to_term(&Role::Guest).unwrap(), atom("Guest"));
+        assert!(to_term(&f64::NAN).is_err());
+    }
+
+    #[test]
+    fn golden_bytes_work() {
+        #[derive(Serialize)]
+        struct Point {
+            x: u8,
+        }
+        assert_eq!(
+            to_bytes(&Point { x: 1 }).unwrap(),
+            [131, 116, 0, 0, 0, 1, 100, 0, 1, 120, 97, 1]
+        );
+        assert_eq!(
+            to_bytes(&(1u8, "a")).unwrap(),
+            [131, 104, 2, 97, 1, 109, 0, 0, 0, 1, 97]
+        );
+        assert_eq!(to_bytes(&vec![1u8, 2]).unwrap(), [131, 107, 0, 2, 1, 2]);
+        assert_eq!(
+            to_bytes(&Role::Admin(3)).unwrap(),
+            [131, 104, 2, 100, 0, 5, 65, 100, 109, 105, 110, 97, 3]
+        );
+    }
+
+    #[test]
+    fn enum_repr_works() {
+        let options = SerializeOptions::new().enum_repr(EnumRepr::ExternallyTaggedMap);
+        assert_eq!(
+            to_term_with_options(&Role::Guest, &options).unwrap(),
+            Term::from(Map::from([(atom("Guest"), atom("undefined"))]))
+        );
+        let options = SerializeOptions::new().enum_repr(EnumRepr::TaggedTuple);
+        assert_eq!(
+            to_term_with_options(&Role::Guest, &options).unwrap(),
+            Term::from(Tuple::from(vec![atom("Guest")]))
+        );
+    }
+}
@@ -0,0 +1,1038 @@
+//! Slice-based codec, which needs only `alloc`.
+//!
+//! The output is the same as [`Term::encode`] with the default options, and the input
+//! is anything [`Term::decode`] accepts except distribution headers (and, without the
+//! `std` feature, compressed terms).
+use super::*;
+use crate::codec_common::*;
+use byteorder::{BigEndian, ByteOrder};
+
+/// Decodes a term which occupies the whole of `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Atom, Term};
+///
+/// let term = eetf::decode_from_slice(&[131, 100, 0, 3, 102, 111, 111]).unwrap();
+/// assert_eq!(term, Term::from(Atom::from("foo")));
+/// ```
+pub fn decode_from_slice(bytes: &[u8]) -> DecodeResult {
+    decode_observed(SliceDecoder::new(bytes))
+}
+
+/// Decodes a term which occupies the whole of `bytes` under the limits of `options`.
+///
+/// Raw subterms are not supported by the slice decoder, so `options.raw_depth` is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{DecodeError, DecoderOptions, Term};
+///
+/// let mut bytes = Vec::new();
+/// Term::from(eetf::Binary::from(vec![0; 1000]))
+///     .encode_compressed(&mut bytes)
+///     .unwrap();
+/// let options = DecoderOptions::new().with_max_uncompressed_size(100);
+/// assert!(matches!(
+///     eetf::decode_from_slice_with_options(&bytes, options),
+///     Err(DecodeError::LimitExceeded { .. })
+/// ));
+/// ```
+#[cfg(feature = "std")]
+pub fn decode_from_slice_with_options(bytes: &[u8], options: DecoderOptions) -> DecodeResult {
+    decode_frame_with_options(bytes, options)
+}
+
+/// Decodes the term of a frame handed over by a framing layer, which must hold exactly one term.
+///
+/// Fails with [`DecodeError::TrailingBytes`] if bytes follow the term, and with
+/// [`DecodeError::UnexpectedEof`] if the frame ends within it. The term may be compressed, in
+/// which case its inflated body must hold exactly the term too.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{DecodeError, Term};
+///
+/// assert_eq!(eetf::decode_frame(&[131, 97, 7]).unwrap(), Term::from(7));
+/// assert!(matches!(
+///     eetf::decode_frame(&[131, 97, 7, 0]),
+///     Err(DecodeError::TrailingBytes { count: 1 })
+/// ));
+/// assert!(matches!(
+///     eetf::decode_frame(&[131, 98, 0, 0]),
+///     Err(DecodeError::UnexpectedEof)
+/// ));
+/// ```
+pub fn decode_frame(frame: &[u8]) -> DecodeResult {
+    decode_observed(SliceDecoder::new(frame))
+}
+
+/// Decodes the term of a frame (see [`decode_frame`]) under `options`, which may tell that the
+/// term has no version number (see [`DecoderOptions::versionless`]).
+///
+/// Raw subterms are not supported by the slice decoder, so `options.raw_depth` is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{DecoderOptions, Term};
+///
+/// let options = DecoderOptions::new().with_versionless(true);
+/// let term = eetf::decode_frame_with_options(&[97, 7], options).unwrap();
+/// assert_eq!(term, Term::from(7));
+/// ```
+pub fn decode_frame_with_options(frame: &[u8], options: DecoderOptions) -> DecodeResult {
+    let mut decoder = SliceDecoder::new(frame);
+    #[cfg(feature = "std")]
+    {
+        decoder.max_uncompressed_size = options.max_uncompressed_size;
+    }
+    decoder.keep_offending_term = options.keep_offending_term;
+    decoder.versionless = options.versionless;
+    decode_observed(decoder)
+}
+
+fn decode_observed(mut decoder: SliceDecoder) -> DecodeResult {
+    #[cfg(feature = "tracing")]
+    if let Some(observer) = crate::metrics::Observer::decode(None) {
+        let result = observer.in_scope(|| decode_whole(&mut decoder));
+        observer.finish_decode(&result, decoder.pos as u64);
+        return result;
+    }
+    decode_whole(&mut decoder)
+}
+
+fn decode_whole(decoder: &mut SliceDecoder) -> DecodeResult {
+    let term = decode_versioned(decoder)?;
+    decoder.finish()?;
+    Ok(term)
+}
+
+fn decode_versioned(decoder: &mut SliceDecoder) -> DecodeResult {
+    if !decoder.versionless {
+        decoder.read_version()?;
+    }
+    match decoder.read_u8()? {
+        COMPRESSED_TERM => {
+            #[cfg(feature = "tracing")]
+            instrument::record_compressed();
+            decoder.decode_compressed_term()
+        }
+        tag => decoder.decode_term_with_tag(tag),
+    }
+}
+
+/// Returns an iterator over the terms encoded one after the other in `bytes`.
+///
+/// The iteration stops after the first error. A compressed term extends to the end of `bytes`,
+/// so it can only be the last term.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Atom, Term};
+///
+/// let bytes = [131, 97, 1, 131, 100, 0, 3, 102, 111, 111];
+/// let terms = eetf::term_iter(&bytes).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(terms, [Term::from(1), Term::from(Atom::from("foo"))]);
+/// ```
+pub fn term_iter(bytes: &[u8]) -> TermIter<'_> {
+    TermIter {
+        decoder: SliceDecoder::new(bytes),
+        failed: false,
+    }
+}
+
+/// Iterator over the terms of a slice (see [`term_iter`]).
+pub struct TermIter<'a> {
+    decoder: SliceDecoder<'a>,
+    failed: bool,
+}
+impl Iterator for TermIter<'_> {
+    type Item = DecodeResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.decoder.remaining() == 0 {
+            return None;
+        }
+        let result = decode_versioned(&mut self.decoder);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+impl fmt::Debug for TermIter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TermIter")
+            .field("remaining", &self.decoder.remaining())
+            .finish()
+    }
+}
+
+/// Decodes a term which occupies the whole of `bytes`, sharing `bytes` with the binaries.
+///
+/// The bytes of the binaries in the term refer to `bytes` instead of being copied (except
+/// in compressed terms, where they refer to the decompressed input instead).
+///
+/// # Examples
+///
+/// ```
+/// use bytes::Bytes;
+/// use eetf::Term;
+///
+/// let bytes = Bytes::from_static(&[131, 109, 0, 0, 0, 3, 102, 111, 111]);
+/// let Term::Binary(binary) = eetf::decode_from_bytes(&bytes).unwrap() else {
+///     unreachable!()
+/// };
+/// assert_eq!(binary.bytes.as_ptr(), bytes[6..].as_ptr());
+/// ```
+#[cfg(feature = "bytes")]
+pub fn decode_from_bytes(bytes: &bytes::Bytes) -> DecodeResult {
+    let mut decoder = SliceDecoder::new(bytes);
+    decoder.shared = Some(bytes);
+    decoder.read_version()?;
+    let term = match decoder.read_u8()? {
+        COMPRESSED_TERM => decoder.decode_compressed_term()?,
+        tag => decoder.decode_term_with_tag(tag)?,
+    };
+    decoder.finish()?;
+    Ok(term)
+}
+
+/// Encodes a term into a new vector.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Atom, Term};
+///
+/// let bytes = eetf::encode_to_vec(&Term::from(Atom::from("foo"))).unwrap();
+/// assert_eq!(bytes, [131, 100, 0, 3, 102, 111, 111]);
+/// ```
+pub fn encode_to_vec(term: &Term) -> Result<Vec<u8>, EncodeError> {
+    let mut encoder = SliceEncoder { buf: vec![VERSION] };
+    #[cfg(feature = "tracing")]
+    if let Some(observer) = crate::metrics::Observer::encode(None, term, false) {
+        let result = observer.in_scope(|| encoder.encode_term(term));
+        observer.finish_encode(&result, encoder.buf.len() as u64);
+        return result.map(|()| encoder.buf);
+    }
+    encoder.encode_term(term)?;
+    Ok(encoder.buf)
+}
+
+pub(crate) struct SliceDecoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    /// See [`DecoderOptions::keep_offending_term`].
+    keep_offending_term: bool,
+    /// See [`DecoderOptions::versionless`].
+    versionless: bool,
+    /// See [`DecoderOptions::max_uncompressed_size`].
+    #[cfg(feature = "std")]
+    max_uncompressed_size: Option<usize>,
+    /// The buffer of `bytes`, which binaries are sliced from.
+    #[cfg(feature = "bytes")]
+    shared: Option<&'a bytes::Bytes>,
+}
+impl<'a> SliceDecoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        SliceDecoder {
+            bytes,
+            pos: 0,
+            depth: 0,
+            keep_offending_term: false,
+            versionless: false,
+            #[cfg(feature = "std")]
+            max_uncompressed_size: None,
+            #[cfg(feature = "bytes")]
+            shared: None,
+        }
+    }
+    /// Enters a subterm, failing if it is nested too deeply.
+    pub(crate) fn enter(&mut self) -> Result<(), DecodeError> {
+        aux::check_depth(self.depth)?;
+        self.depth += 1;
+        Ok(())
+    }
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+    pub(crate) fn read_version(&mut self) -> Result<(), DecodeError> {
+        match self.read_u8()? {
+            VERSION => Ok(()),
+            version => Err(DecodeError::UnsupportedVersion { version }),
+        }
+    }
+    /// Fails if some input is left.
+    pub(crate) fn finish(&self) -> Result<(), DecodeError> {
+        match self.remaining() {
+            0 => Ok(()),
+            count => Err(DecodeError::TrailingBytes { count }),
+        }
+    }
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+    fn read_binary(&mut self, len: usize) -> Result<BinaryBytes, DecodeError> {
+        let bytes = self.read_bytes(len)?;
+        #[cfg(feature = "bytes")]
+        if let Some(shared) = self.shared {
+            return Ok(shared.slice_ref(bytes));
+        }
+        Ok(BinaryBytes::from(bytes.to_vec()))
+    }
+    pub(crate) fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+    pub(crate) fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(BigEndian::read_u16(self.read_bytes(2)?))
+    }
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(BigEndian::read_u32(self.read_bytes(4)?))
+    }
+    fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(BigEndian::read_u64(self.read_bytes(8)?))
+    }
+    pub(crate) fn read_len_u32(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.read_u32()? as usize)
+    }
+    #[cfg(feature = "std")]
+    fn decode_compressed_term(&mut self) -> DecodeResult {
+        use crate::zlib::{self, Zlib};
+        use std::io::Read;
+
+        let uncompressed_size = self.read_len_u32()?;
+        let max = self.max_uncompressed_size;
+        zlib::check_declared_size(max, uncompressed_size)?;
+        let compressed = self.read_bytes(self.remaining())?;
+        let mut buf = Vec::with_capacity(uncompressed_size.min(compressed.len() * 8));
+        let mut zlib_decoder = zlib::Limited::new(zlib::Backend::slice_decoder(compressed)?, max);
+        let result = zlib_decoder.read_to_end(&mut buf);
+        zlib_decoder.check(result.map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEof,
+            _ => DecodeError::from(e),
+        }))?;
+        #[cfg(feature = "bytes")]
+        if self.shared.is_some() {
+            let buf = bytes::Bytes::from(buf);
+            let mut decoder = SliceDecoder::new(&buf);
+            decoder.shared = Some(&buf);
+            return decoder.decode_compressed_body();
+        }
+        SliceDecoder::new(&buf).decode_compressed_body()
+    }
+    /// Decodes the inflated body of a compressed term, which must hold exactly the term.
+    #[cfg(feature = "std")]
+    fn decode_compressed_body(&mut self) -> DecodeResult {
+        let term = self.decode_term()?;
+        match self.remaining() {
+            0 => Ok(term),
+            count => Err(DecodeError::InvalidData {
+                message: format!("{} bytes follow the compressed term", count),
+            }),
+        }
+    }
+    #[cfg(not(feature = "std"))]
+    fn decode_compressed_term(&mut self) -> DecodeResult {
+        Err(DecodeError::CompressedTerm)
+    }
+    fn decode_term(&mut self) -> DecodeResult {
+        let tag = self.read_u8()?;
+        self.decode_term_with_tag(tag)
+    }
+    fn decode_atom(&mut self) -> Result<Atom, DecodeError> {
+        let keep = self.keep_offending_term;
+        let tag = self.read_u8()?;
+        aux::check_atom_tag(tag)?;
+        self.decode_term_with_tag(tag)
+            .and_then(|t| aux::term_into_atom(t, keep))
+    }
+    fn decode_terms(&mut self, count: usize) -> Result<Vec<Term>, DecodeError> {
+        // Every term takes at least one byte, which bounds the allocation.
+        let mut terms = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            terms.push(self.decode_term()?);
+        }
+        Ok(terms)
+    }
+    pub(crate) fn decode_term_with_tag(&mut self, tag: u8) -> DecodeResult {
+        self.enter()?;
+        let result = self.decode_subterm_with_tag(tag);
+        self.leave();
+        result
+    }
+    // Each tag is decoded by a method of its own, which keeps the frame of this recursive
+    // method small (the arms of a single large match all take stack space in debug builds).
+    fn decode_subterm_with_tag(&mut self, tag: u8) -> DecodeResult {
+        match tag {
+            NEW_FLOAT_EXT => self.decode_new_float_ext(),
+            FLOAT_EXT => self.decode_float_ext(),
+            BIT_BINARY_EXT => self.decode_bit_binary_ext(),
+            SMALL_INTEGER_EXT => self.decode_small_integer_ext(),
+            INTEGER_EXT => self.decode_integer_ext(),
+            SMALL_BIG_EXT => self.decode_small_big_ext(),
+            LARGE_BIG_EXT => self.decode_large_big_ext(),
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => {
+                self.decode_atom_ext(tag)
+            }
+            PID_EXT | NEW_PID_EXT => self.decode_pid(tag),
+            PORT_EXT | NEW_PORT_EXT | V4_PORT_EXT => self.decode_port(tag),
+            REFERENCE_EXT => self.decode_reference_ext(),
+            NEW_REFERENCE_EXT | NEWER_REFERENCE_EXT => self.decode_new_reference(tag),
+            SMALL_TUPLE_EXT => self.decode_small_tuple_ext(),
+            LARGE_TUPLE_EXT => self.decode_large_tuple_ext(),
+            NIL_EXT => Ok(Term::from(List::nil())),
+            STRING_EXT => self.decode_string_ext(),
+            LIST_EXT => self.decode_list_ext(),
+            BINARY_EXT => self.decode_binary_ext(),
+            MAP_EXT => self.decode_map_ext(),
+            EXPORT_EXT => self.decode_export_ext(),
+            FUN_EXT => self.decode_fun_ext(),
+            NEW_FUN_EXT => self.decode_new_fun_ext(),
+            _ => Err(DecodeError::UnknownTag { tag }),
+        }
+    }
+    fn decode_new_float_ext(&mut self) -> DecodeResult {
+        let value = f64::from_bits(self.read_u64()?);
+        Ok(Term::from(Float::try_from(value)?))
+    }
+    fn decode_float_ext(&mut self) -> DecodeResult {
+        let bytes = self.read_bytes(31)?;
+        let value = core::str::from_utf8(bytes)
+            .map_err(invalid_data)?
+            .trim_end_matches('\0')
+            .parse::<f32>()
+            .map_err(invalid_data)?;
+        Ok(Term::from(Float::try_from(value)?))
+    }
+    fn decode_bit_binary_ext(&mut self) -> DecodeResult {
+        let size = self.read_len_u32()?;
+        let tail_bits_size = self.read_u8()?;
+        aux::check_tail_bits_size(size, tail_bits_size)?;
+        let mut bytes = self.read_bytes(size)?.to_vec();
+        if let Some(last) = bytes.last_mut() {
+            *last >>= 8 - tail_bits_size;
+        }
+        Ok(Term::from(BitBinary::from((bytes, tail_bits_size))))
+    }
+    fn decode_pid(&mut self, tag: u8) -> DecodeResult {
+        let node = self.decode_atom()?;
+        let id = self.read_u32()?;
+        let serial = self.read_u32()?;
+        let creation = if tag == PID_EXT {
+            u32::from(self.read_u8()?)
+        } else {
+            self.read_u32()?
+        };
+        Ok(Term::from(Pid {
+            node,
+            id,
+            serial,
+            creation,
+        }))
+    }
+    fn decode_port(&mut self, tag: u8) -> DecodeResult {
+        let node = self.decode_atom()?;
+        let (id, creation) = match tag {
+            PORT_EXT => (u64::from(self.read_u32()?), u32::from(self.read_u8()?)),
+            NEW_PORT_EXT => (u64::from(self.read_u32()?), self.read_u32()?),
+            _ => (self.read_u64()?, self.read_u32()?),
+        };
+        Ok(Term::from(Port { node, id, creation }))
+    }
+    fn decode_reference_ext(&mut self) -> DecodeResult {
+        let node = self.decode_atom()?;
+        let id = vec![self.read_u32()?];
+        let creation = u32::from(self.read_u8()?);
+        Ok(Term::from(Reference { node, id, creation }))
+    }
+    fn decode_new_reference(&mut self, tag: u8) -> DecodeResult {
+        let id_count = self.read_u16()? as usize;
+        let node = self.decode_atom()?;
+        let creation = if tag == NEW_REFERENCE_EXT {
+            u32::from(self.read_u8()?)
+        } else {
+            self.read_u32()?
+        };
+        let id = (0..id_count)
+            .map(|_| self.read_u32())
+            .collect::<Result<_, _>>()?;
+        Ok(Term::from(Reference { node, id, creation }))
+    }
+    fn decode_small_integer_ext(&mut self) -> DecodeResult {
+        Ok(Term::from(FixInteger::from(i32::from(self.read_u8()?))))
+    }
+    fn decode_integer_ext(&mut self) -> DecodeResult {
+        Ok(Term::from(FixInteger::from(self.read_u32()? as i32)))
+    }
+    fn decode_small_big_ext(&mut self) -> DecodeResult {
+        let count = self.read_u8()? as usize;
+        self.decode_big(count)
+    }
+    fn decode_large_big_ext(&mut self) -> DecodeResult {
+        let count = self.read_len_u32()?;
+        self.decode_big(count)
+    }
+    fn decode_atom_ext(&mut self, tag: u8) -> DecodeResult {
+        let len = match tag {
+            ATOM_EXT | ATOM_UTF8_EXT => self.read_u16()? as usize,
+            _ => self.read_u8()? as usize,
+        };
+        let bytes = self.read_bytes(len)?;
+        if tag == ATOM_EXT || tag == SMALL_ATOM_EXT {
+            return Ok(Term::from(latin1_atom(bytes)));
+        }
+        let name = core::str::from_utf8(bytes).map_err(invalid_data)?;
+        Ok(Term::from(Atom::from(name)))
+    }
+    fn decode_small_tuple_ext(&mut self) -> DecodeResult {
+        let count = self.read_u8()? as usize;
+        Ok(Term::from(Tuple::from(self.decode_terms(count)?)))
+    }
+    fn decode_large_tuple_ext(&mut self) -> DecodeResult {
+        let count = self.read_len_u32()?;
+        Ok(Term::from(Tuple::from(self.decode_terms(count)?)))
+    }
+    fn decode_string_ext(&mut self) -> DecodeResult {
+        let len = self.read_u16()? as usize;
+        Ok(Term::from(ByteList::from(self.read_bytes(len)?.to_vec())))
+    }
+    fn decode_list_ext(&mut self) -> DecodeResult {
+        let count = self.read_len_u32()?;
+        let elements = self.decode_terms(count)?;
+        match self.decode_term()? {
+            Term::List(ref last) if last.is_nil() => Ok(Term::from(List::from(elements))),
+            last => Ok(Term::from(ImproperList::from((elements, last)))),
+        }
+    }
+    fn decode_binary_ext(&mut self) -> DecodeResult {
+        let size = self.read_len_u32()?;
+        Ok(Term::from(Binary {
+            bytes: self.read_binary(size)?,
+        }))
+    }
+    fn decode_map_ext(&mut self) -> DecodeResult {
+        let count = self.read_len_u32()?;
+        let mut map = TermMap::new();
+        for _ in 0..count {
+            let k = self.decode_term()?;
+            let v = self.decode_term()?;
+            map.insert(k, v);
+        }
+        Ok(Term::from(Map::from(map)))
+    }
+    fn decode_export_ext(&mut self) -> DecodeResult {
+        let keep = self.keep_offending_term;
+        let module = self.decode_atom()?;
+        let function = self.decode_atom()?;
+        let arity =
+            self.decode_term()
+                .and_then(|t| aux::term_into_ranged_integer(t, 0..0xFF, keep))? as u8;
+        Ok(Term::from(ExternalFun {
+            module,
+            function,
+            arity,
+        }))
+    }
+    fn decode_fun_ext(&mut self) -> DecodeResult {
+        let keep = self.keep_offending_term;
+        let num_free = self.read_len_u32()?;
+        let pid = self
+            .decode_term()
+            .and_then(|t| aux::term_into_pid(t, keep))?;
+        let module = self.decode_atom()?;
+        let index = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let uniq = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        Ok(Term::from(InternalFun::Old {
+            module,
+            pid,
+            free_vars: self.decode_terms(num_free)?,
+            index: index.value,
+            uniq: uniq.value,
+        }))
+    }
+    fn decode_new_fun_ext(&mut self) -> DecodeResult {
+        let keep = self.keep_offending_term;
+        let _size = self.read_u32()?;
+        let arity = self.read_u8()?;
+        let mut uniq = [0; 16];
+        uniq.copy_from_slice(self.read_bytes(16)?);
+        let index = self.read_u32()?;
+        let num_free = self.read_len_u32()?;
+        let module = self.decode_atom()?;
+        let old_index = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let old_uniq = self
+            .decode_term()
+            .and_then(|t| aux::term_into_fix_integer(t, keep))?;
+        let pid = self
+            .decode_term()
+            .and_then(|t| aux::term_into_pid(t, keep))?;
+        Ok(Term::from(InternalFun::New {
+            module,
+            arity,
+            pid,
+            free_vars: self.decode_terms(num_free)?,
+            index,
+            uniq,
+            old_index: old_index.value,
+            old_uniq: old_uniq.value,
+        }))
+    }
+    fn decode_big(&mut self, count: usize) -> DecodeResult {
+        let sign = match self.read_u8()? {
+            0 => num::bigint::Sign::Plus,
+            1 => num::bigint::Sign::Minus,
+            b => {
+                return Err(invalid_data(format!(
+                    "a sign value must be 0 or 1: value={}",
+                    b
+                )))
+            }
+        };
+        let value = BigInt::from_bytes_le(sign, self.read_bytes(count)?);
+        Ok(Term::from(BigInteger { value }))
+    }
+}
+
+struct SliceEncoder {
+    buf: Vec<u8>,
+}
+impl SliceEncoder {
+    fn write_u8(&mut self, n: u8) {
+        self.buf.push(n);
+    }
+    fn write_u16(&mut self, n: u16) {
+        self.buf.extend_from_slice(&n.to_be_bytes());
+    }
+    fn write_u32(&mut self, n: u32) {
+        self.buf.extend_from_slice(&n.to_be_bytes());
+    }
+    fn encode_term(&mut self, term: &Term) -> Result<(), EncodeError> {
+        match term {
+            Term::Atom(x) => self.encode_atom(x)?,
+            Term::FixInteger(x) => self.encode_fix_integer(x.value),
+            Term::BigInteger(x) => {
+                let (sign, bytes) = x.value.to_bytes_le();
+                if bytes.len() <= u8::MAX as usize {
+                    self.write_u8(SMALL_BIG_EXT);
+                    self.write_u8(bytes.len() as u8);
+                } else if bytes.len() <= u32::MAX as usize {
+                    self.write_u8(LARGE_BIG_EXT);
+                    self.write_u32(bytes.len() as u32);
+                } else {
+                    return Err(EncodeError::TooLargeInteger(
+                        x.clone(),
+                        EncodePath::default(),
+                    ));
+                }
+                self.write_u8(aux::sign_to_byte(sign));
+                self.buf.extend_from_slice(&bytes);
+            }
+            Term::Float(x) => {
+                self.write_u8(NEW_FLOAT_EXT);
+                self.buf.extend_from_slice(&x.value.to_be_bytes());
+            }
+            Term::Pid(x) => self.encode_pid(x)?,
+            Term::Port(x) => {
+                if x.id >> 32 == 0 {
+                    self.write_u8(NEW_PORT_EXT);
+                    self.encode_atom(&x.node)?;
+                    self.write_u32(x.id as u32);
+                } else {
+                    self.write_u8(V4_PORT_EXT);
+                    self.encode_atom(&x.node)?;
+                    self.buf.extend_from_slice(&x.id.to_be_bytes());
+                }
+                self.write_u32(x.creation);
+            }
+            Term::Reference(x) => {
+                if x.id.len() > u16::MAX as usize {
+                    return Err(EncodeError::TooLargeReferenceId(
+                        (**x).clone(),
+                        EncodePath::default(),
+                    ));
+                }
+                self.write_u8(NEWER_REFERENCE_EXT);
+                self.write_u16(x.id.len() as u16);
+                self.encode_atom(&x.node)?;
+                self.write_u32(x.creation);
+                for &n in &x.id {
+                    self.write_u32(n);
+                }
+            }
+            Term::ExternalFun(x) => {
+                self.write_u8(EXPORT_EXT);
+                self.encode_atom(&x.module)?;
+                self.encode_atom(&x.function)?;
+                self.encode_fix_integer(i32::from(x.arity));
+            }
+            Term::InternalFun(x) => self.encode_internal_fun(x)?,
+            Term::Binary(x) => {
+                self.write_u8(BINARY_EXT);
+                self.write_u32(x.bytes.len() as u32);
+                self.buf.extend_from_slice(&x.bytes);
+            }
+            Term::BitBinary(x) => {
+                self.write_u8(BIT_BINARY_EXT);
+                self.write_u32(x.bytes.len() as u32);
+                self.write_u8(x.tail_bits_size);
+                if let Some((&last, init)) = x.bytes.split_last() {
+                    self.buf.extend_from_slice(init);
+                    self.write_u8(last << (8 - x.tail_bits_size));
+                }
+            }
+            Term::ByteList(x) => {
+                self.write_u8(STRING_EXT);
+                self.write_u16(x.bytes.len() as u16);
+                self.buf.extend_from_slice(&x.bytes);
+            }
+            Term::Raw(x) => self.buf.extend_from_slice(&x.bytes),
+            Term::List(x) => {
+                let to_byte = |e: &Term| match e {
+                    Term::FixInteger(FixInteger { value }) => u8::try_from(*value).ok(),
+                    _ => None,
+                };
+                if !x.elements.is_empty()
+                    && x.elements.len() <= u16::MAX as usize
+                    && x.elements.iter().all(|e| to_byte(e).is_some())
+                {
+                    self.write_u8(STRING_EXT);
+                    self.write_u16(x.elements.len() as u16);
+                    for e in &x.elements {
+                        self.write_u8(to_byte(e).expect("unreachable"));
+                    }
+                } else {
+                    if !x.is_nil() {
+                        self.write_u8(LIST_EXT);
+                        self.write_u32(x.elements.len() as u32);
+                        for (i, e) in x.elements.iter().enumerate() {
+                            self.encode_term(e)
+                                .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
+                        }
+                    }
+                    self.write_u8(NIL_EXT);
+                }
+            }
+            Term::ImproperList(x) => {
+                self.write_u8(LIST_EXT);
+                self.write_u32(x.elements.len() as u32);
+                for (i, e) in x.elements.iter().enumerate() {
+                    self.encode_term(e)
+                        .map_err(|e| e.within(EncodePathSegment::ListElement(i)))?;
+                }
+                self.encode_term(&x.last)
+                    .map_err(|e| e.within(EncodePathSegment::ListTail))?;
+            }
+            Term::Tuple(x) => {
+                if x.elements.len() < 0x100 {
+                    self.write_u8(SMALL_TUPLE_EXT);
+                    self.write_u8(x.elements.len() as u8);
+                } else {
+                    self.write_u8(LARGE_TUPLE_EXT);
+                    self.write_u32(x.elements.len() as u32);
+                }
+                for (i, e) in x.elements.iter().enumerate() {
+                    self.encode_term(e)
+                        .map_err(|e| e.within(EncodePathSegment::TupleElement(i)))?;
+                }
+            }
+            Term::Map(x) => {
+                self.write_u8(MAP_EXT);
+                self.write_u32(x.map.len() as u32);
+                for (k, v) in &x.map {
+                    self.encode_term(k)
+                        .map_err(|e| e.within(EncodePathSegment::MapKey))?;
+                    self.encode_term(v).map_err(|e| {
+                        e.within(EncodePathSegment::MapValue { key: k.to_string() })
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn encode_atom(&mut self, x: &Atom) -> Result<(), EncodeError> {
+        if x.name.len() > 0xFFFF {
+            return Err(EncodeError::TooLongAtomName(
+                x.clone(),
+                EncodePath::default(),
+            ));
+        }
+        if x.name.is_ascii() {
+            self.write_u8(ATOM_EXT);
+        } else {
+            self.write_u8(ATOM_UTF8_EXT);
+        }
+        self.write_u16(x.name.len() as u16);
+        self.buf.extend_from_slice(x.name.as_bytes());
+        Ok(())
+    }
+    fn encode_fix_integer(&mut self, value: i32) {
+        match u8::try_from(value) {
+            Ok(n) => {
+                self.write_u8(SMALL_INTEGER_EXT);
+                self.write_u8(n);
+            }
+            Err(_) => {
+                self.write_u8(INTEGER_EXT);
+                self.write_u32(value as u32);
+            }
+        }
+    }
+    fn encode_pid(&mut self, x: &Pid) -> Result<(), EncodeError> {
+        self.write_u8(NEW_PID_EXT);
+        self.encode_atom(&x.node)?;
+        self.write_u32(x.id);
+        self.write_u32(x.serial);
+        self.write_u32(x.creation);
+        Ok(())
+    }
+    fn encode_internal_fun(&mut self, x: &InternalFun) -> Result<(), EncodeError> {
+        match x {
+            InternalFun::Old {
+                module,
+                pid,
+                free_vars,
+                index,
+                uniq,
+            } => {
+                self.write_u8(FUN_EXT);
+                self.write_u32(free_vars.len() as u32);
+                self.encode_pid(pid)?;
+                self.encode_atom(module)?;
+                self.encode_fix_integer(*index);
+                self.encode_fix_integer(*uniq);
+                for (i, v) in free_vars.iter().enumerate() {
+                    self.encode_term(v)
+                        .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
+                }
+            }
+            InternalFun::New {
+                module,
+                arity,
+                pid,
+                free_vars,
+                index,
+                uniq,
+                old_index,
+                old_uniq,
+            } => {
+                self.write_u8(NEW_FUN_EXT);
+                // The size (which includes itself) is patched once the fun is written.
+                let start = self.buf.len();
+                self.write_u32(0);
+                self.write_u8(*arity);
+                self.buf.extend_from_slice(uniq);
+                self.write_u32(*index);
+                self.write_u32(free_vars.len() as u32);
+                self.encode_atom(module)?;
+                self.encode_fix_integer(*old_index);
+                self.encode_fix_integer(*old_uniq);
+                self.encode_pid(pid)?;
+                for (i, v) in free_vars.iter().enumerate() {
+                    self.encode_term(v)
+                        .map_err(|e| e.within(EncodePathSegment::FreeVar(i)))?;
+                }
+                let size = (self.buf.len() - start) as u32;
+                BigEndian::write_u32(&mut self.buf[start..start + 4], size);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn invalid_data(e: impl ToString) -> DecodeError {
+    DecodeError::InvalidData {
+        message: e.to_string(),
+    }
+}
+
+fn latin1_atom(bytes: &[u8]) -> Atom {
+    Atom {
+        name: bytes
+            .iter()
+            .map(|&b| char::from(b))
+            .collect::<String>()
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms() -> Vec<Term> {
+        let atom = |name: &str| Term::from(Atom::from(name));
+        let pid = Pid::new("node@host", 1, 2, 3);
+        vec![
+            atom("foo"),
+            atom("h\u{e9}llo"),
+            Term::from(FixInteger::from(255)),
+            Term::from(FixInteger::from(-1)),
+            Term::from(BigInteger::from(u64::MAX)),
+            Term::from(BigInteger {
+                value: BigInt::from(-1) << 2048,
+            }),
+            Term::from(Float::try_from(-1.5).unwrap()),
+            Term::from(pid.clone()),
+            Term::from(Port::new("node@host", 1 << 40, 7)),
+            Term::from(Reference::new("node@host", vec![1, 2, 3], 7)),
+            Term::from(ExternalFun::from(("lists", "map", 2))),
+            Term::from(InternalFun::New {
+                module: Atom::from("m"),
+                arity: 1,
+                pid: pid.clone(),
+                free_vars: vec![atom("x")],
+                index: 2,
+                uniq: [3; 16],
+                old_index: 4,
+                old_uniq: 5,
+            }),
+            Term::from(InternalFun::Old {
+                module: Atom::from("m"),
+                pid,
+                free_vars: Vec::new(),
+                index: 1,
+                uniq: 2,
+            }),
+            Term::from(Binary::from(&b"bytes"[..])),
+            Term::from(BitBinary::from((vec![1, 0b101], 3))),
+            Term::from(ByteList::from(vec![1, 2, 3])),
+            Term::from(List::nil()),
+            Term::from(List::from(vec![atom("a"), Term::from(List::nil())])),
+            Term::from(ImproperList::from((vec![atom("a")], atom("b")))),
+            Term::from(Tuple::from(Vec::new())),
+            Term::from(Tuple::from(vec![Term::from(FixInteger::from(1)); 300])),
+            Term::from(Map::from([(atom("k"), atom("v")), (atom("l"), atom("w"))])),
+        ]
+    }
+
+    #[test]
+    fn slice_codec_agrees_with_io_codec() {
+        for term in terms() {
+            let mut expected = Vec::new();
+            term.encode(&mut expected).unwrap();
+            let actual = encode_to_vec(&term).unwrap();
+            if !matches!(term, Term::Map(_)) {
+                assert_eq!(actual, expected, "{}", term);
+            }
+            assert_eq!(decode_from_slice(&actual).unwrap(), term);
+            assert_eq!(
+                decode_from_slice(&expected).unwrap(),
+                Term::decode(&expected[..]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn old_formats_are_decoded() {
+        // FLOAT_EXT, SMALL_ATOM_EXT, PID_EXT and zlib-compressed STRING_EXT
+        let mut float = vec![131, 99];
+        float.extend_from_slice(b"1.50000000000000000000e+00\0\0\0\0\0");
+        #[rustfmt::skip]
+        let inputs: [&[u8]; 4] = [
+            &float,
+            &[131, 115, 3, 102, 111, 111],
+            &[131, 103, 115, 1, 110, 0, 0, 0, 1, 0, 0, 0, 2, 3],
+            &[131, 80, 0, 0, 0, 23, 120, 156, 203, 102, 16, 97, 196, 2, 0, 12, 42, 0, 148],
+        ];
+        for input in inputs {
+            assert_eq!(
+                decode_from_slice(input).unwrap(),
+                Term::decode(input).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn binaries_are_sliced_from_the_input() {
+        // {<<"foo">>, <<"bar">>}
+        let term = Term::from(Tuple::from(vec![
+            Term::from(Binary::from(&b"foo"[..])),
+            Term::from(Binary::from(&b"bar"[..])),
+        ]));
+        let input = bytes::Bytes::from(encode_to_vec(&term).unwrap());
+        let decoded = decode_from_bytes(&input).unwrap();
+        assert_eq!(decoded, term);
+        let Term::Tuple(tuple) = decoded else {
+            unreachable!()
+        };
+        let range = input.as_ptr_range();
+        for e in &tuple.elements {
+            let Term::Binary(binary) = e else {
+                unreachable!()
+            };
+            assert!(range.contains(&binary.bytes.as_ptr()));
+        }
+
+        // A compressed term cannot be shared, but is decoded anyway.
+        let input = bytes::Bytes::from_static(&[
+            131, 80, 0, 0, 0, 23, 120, 156, 203, 102, 16, 97, 196, 2, 0, 12, 42, 0, 148,
+        ]);
+        assert_eq!(
+            decode_from_bytes(&input).unwrap(),
+            decode_from_slice(&input).unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(matches!(
+            decode_from_slice(&[131, 100, 0, 3, 102, 111]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_from_slice(&[131, 106, 0]),
+            Err(DecodeError::TrailingBytes { count: 1 })
+        ));
+        assert!(matches!(
+            decode_from_slice(&[130, 106]),
+            Err(DecodeError::UnsupportedVersion { version: 130 })
+        ));
+        assert!(matches!(
+            decode_from_slice(&[131, 118, 0, 1, 0xFF]),
+            Err(DecodeError::InvalidData { .. })
+        ));
+        // A huge count must not allocate before the input runs out.
+        assert!(matches!(
+            decode_from_slice(&[131, 108, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn terms_are_iterated_until_an_error() {
+        let mut bytes = Vec::new();
+        for term in terms() {
+            bytes.extend(encode_to_vec(&term).unwrap());
+        }
+        let decoded = term_iter(&bytes).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(decoded, terms());
+
+        bytes.extend([131, 97]);
+        let mut iter = term_iter(&bytes).skip(terms().len());
+        assert!(matches!(iter.next(), Some(Err(DecodeError::UnexpectedEof))));
+        assert!(iter.next().is_none());
+        assert_eq!(term_iter(&[]).count(), 0);
+    }
+}
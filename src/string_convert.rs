@@ -1,25 +1,106 @@
-use crate::{Atom, ByteList};
+//! Conversions of strings and bytes into atoms, binaries and byte lists.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::string_convert::Convert;
+//! use eetf::IntoTermTuple;
+//!
+//! let key = String::from("user:1");
+//! let request = ("get".to_term_atom(), key.to_term_binary()).into_term_tuple();
+//! assert_eq!(request.to_string(), "{'get',<<117,115,101,114,58,49>>}");
+//! ```
+use crate::{Atom, Binary, ByteList, Term};
+use std::borrow::Cow;
 
-pub trait Convert {
+pub trait Convert: Sized {
     fn to_atom(self) -> Atom;
     fn to_byte_list(self) -> ByteList;
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self.to_byte_list().bytes)
+    }
+
+    fn to_term_atom(self) -> Term {
+        Term::from(self.to_atom())
+    }
+
+    fn to_term_binary(self) -> Term {
+        Term::from(self.to_binary())
+    }
+
+    fn to_term_bytelist(self) -> Term {
+        Term::from(self.to_byte_list())
+    }
 }
 impl Convert for String {
     fn to_atom(self) -> Atom {
-        return Atom::from(self);
+        Atom::from(self)
     }
 
     fn to_byte_list(self) -> ByteList {
-        return ByteList::from(self);
+        ByteList::from(self)
+    }
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self.into_bytes())
     }
 }
 impl Convert for &str {
     fn to_atom(self) -> Atom {
-        return Atom::from(self);
+        Atom::from(self)
+    }
+
+    fn to_byte_list(self) -> ByteList {
+        ByteList::from(self)
+    }
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self.as_bytes())
+    }
+}
+impl Convert for Cow<'_, str> {
+    fn to_atom(self) -> Atom {
+        match self {
+            Cow::Borrowed(s) => s.to_atom(),
+            Cow::Owned(s) => s.to_atom(),
+        }
+    }
+
+    fn to_byte_list(self) -> ByteList {
+        ByteList::from(self.into_owned())
+    }
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self.into_owned().into_bytes())
+    }
+}
+/// The bytes are the latin-1 name of the atom (as in `binary_to_atom(Bytes, latin1)`).
+impl Convert for Vec<u8> {
+    fn to_atom(self) -> Atom {
+        self.as_slice().to_atom()
     }
 
     fn to_byte_list(self) -> ByteList {
-        return ByteList::from(self);
+        ByteList::from(self)
+    }
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self)
+    }
+}
+/// The bytes are the latin-1 name of the atom (as in `binary_to_atom(Bytes, latin1)`).
+impl Convert for &[u8] {
+    fn to_atom(self) -> Atom {
+        Atom::from(self.iter().map(|&b| char::from(b)).collect::<String>())
+    }
+
+    fn to_byte_list(self) -> ByteList {
+        ByteList::from(self.to_vec())
+    }
+
+    fn to_binary(self) -> Binary {
+        Binary::from(self)
     }
 }
 
@@ -42,11 +123,32 @@ mod convert_test {
     #[test]
     fn string_should_convert_to_atom() {
         let a = "hello".to_atom();
-        assert_eq!(a.name, "hello");
+        assert_eq!(a.as_str(), "hello");
     }
     #[test]
     fn string_should_convert_to_byte_list() {
         let a = "hello".to_byte_list();
         assert_eq!(ByteList::from("hello"), a);
     }
+
+    #[test]
+    fn strings_should_convert_to_binary() {
+        let expected = Binary::from(&b"hello"[..]);
+        assert_eq!("hello".to_binary(), expected);
+        assert_eq!(String::from("hello").to_binary(), expected);
+        assert_eq!(Cow::Borrowed("hello").to_binary(), expected);
+    }
+
+    #[test]
+    fn bytes_should_convert_to_terms() {
+        assert_eq!(b"caf\xe9".to_vec().to_atom().as_str(), "café");
+        assert_eq!(
+            (&b"hello"[..]).to_term_binary(),
+            Term::from(Binary::from(&b"hello"[..]))
+        );
+        assert_eq!(
+            b"hello".to_vec().to_term_bytelist(),
+            Term::from(ByteList::from("hello"))
+        );
+    }
 }
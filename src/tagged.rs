@@ -0,0 +1,149 @@
+//! Tuples whose first element is an atom naming them (see [`TaggedTuple`]).
+use super::*;
+use crate::convert::{FromTerm, FromTermError, FromTermPathSegment};
+
+/// Tuple whose first element is an atom, the tag, such as `{user, Id, Name}` or
+/// `{error, Reason}`.
+///
+/// [`TaggedTuple::parse`] checks the tag of a term, and returns the other elements as a
+/// [`TaggedPayload`], which converts into a Rust tuple of [`FromTerm`] types.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::{Binary, TaggedTuple, TaggedTupleError, Term};
+///
+/// # fn main() -> Result<(), TaggedTupleError> {
+/// let elements = vec![Term::from(7), Term::from(Binary::from(&b"joe"[..]))];
+/// let term = Term::from(TaggedTuple::new("user", elements));
+/// assert_eq!(term.tag_name(), Some("user"));
+///
+/// let (id, name): (i64, Binary) = TaggedTuple::parse(&term, "user")?.try_into()?;
+/// assert_eq!((id, &name.bytes[..]), (7, &b"joe"[..]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedTuple {
+    pub tag: Atom,
+    /// The elements after the tag.
+    pub elements: Vec<Term>,
+}
+impl TaggedTuple {
+    pub fn new(tag: impl Into<Atom>, elements: Vec<Term>) -> Self {
+        TaggedTuple {
+            tag: tag.into(),
+            elements,
+        }
+    }
+
+    /// Returns the elements of `term` after its tag, if it is a tuple tagged with `expected_tag`.
+    pub fn parse<'a>(
+        term: &'a Term,
+        expected_tag: &str,
+    ) -> Result<TaggedPayload<'a>, TaggedTupleError> {
+        let elements = match term {
+            Term::Tuple(tuple) => &tuple.elements,
+            _ => {
+                return Err(TaggedTupleError::NotATuple {
+                    found: term.variant_name(),
+                })
+            }
+        };
+        match elements.split_first() {
+            Some((Term::Atom(tag), payload)) if tag.as_str() == expected_tag => {
+                Ok(TaggedPayload(payload))
+            }
+            first => Err(TaggedTupleError::WrongTag {
+                expected: expected_tag.to_owned(),
+                found: first.map(|(tag, _)| tag.clone()),
+            }),
+        }
+    }
+}
+impl From<TaggedTuple> for Tuple {
+    fn from(tagged: TaggedTuple) -> Self {
+        let mut elements = Vec::with_capacity(1 + tagged.elements.len());
+        elements.push(Term::Atom(tagged.tag));
+        elements.extend(tagged.elements);
+        Tuple::from(elements)
+    }
+}
+impl From<TaggedTuple> for Term {
+    fn from(tagged: TaggedTuple) -> Self {
+        Term::Tuple(Tuple::from(tagged))
+    }
+}
+
+/// Elements of a tagged tuple after its tag, returned by [`TaggedTuple::parse`].
+///
+/// It dereferences to the slice of the elements, and converts with `try_into` into a Rust tuple
+/// of as many [`FromTerm`] types (up to six).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaggedPayload<'a>(&'a [Term]);
+impl<'a> TaggedPayload<'a> {
+    pub fn as_slice(&self) -> &'a [Term] {
+        self.0
+    }
+}
+impl core::ops::Deref for TaggedPayload<'_> {
+    type Target = [Term];
+
+    fn deref(&self) -> &[Term] {
+        self.0
+    }
+}
+
+/// Error of parsing a tagged tuple, or of converting its payload.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TaggedTupleError {
+    #[error("expected a tagged tuple, found {found}")]
+    NotATuple { found: &'static str },
+
+    /// The first element is not the expected atom (`found` is `None` for the empty tuple).
+    #[error("expected a tuple tagged '{expected}', found {}", describe_tag(.found))]
+    WrongTag {
+        expected: String,
+        found: Option<Term>,
+    },
+
+    /// The tuple has a different number of elements after its tag than the Rust tuple.
+    #[error("expected {expected} elements after the tag, found {found}")]
+    Arity { expected: usize, found: usize },
+
+    #[error(transparent)]
+    Element(#[from] FromTermError),
+}
+
+fn describe_tag(found: &Option<Term>) -> String {
+    match found {
+        Some(tag) => format!("the tag {}", tag),
+        None => "the empty tuple".to_owned(),
+    }
+}
+
+macro_rules! impl_payload_conversions {
+    ($arity:expr; $($name:ident : $index:tt),*) => {
+        impl<'a, $($name: FromTerm),*> TryFrom<TaggedPayload<'a>> for ($($name,)*) {
+            type Error = TaggedTupleError;
+
+            fn try_from(payload: TaggedPayload<'a>) -> Result<Self, Self::Error> {
+                if payload.len() != $arity {
+                    return Err(TaggedTupleError::Arity {
+                        expected: $arity,
+                        found: payload.len(),
+                    });
+                }
+                // The path counts the tag as the element 0 of the tuple.
+                Ok(($($name::from_term(&payload[$index])
+                    .map_err(|e| e.within(FromTermPathSegment::TupleElement($index + 1)))?,)*))
+            }
+        }
+    };
+}
+impl_payload_conversions!(1; A: 0);
+impl_payload_conversions!(2; A: 0, B: 1);
+impl_payload_conversions!(3; A: 0, B: 1, C: 2);
+impl_payload_conversions!(4; A: 0, B: 1, C: 2, D: 3);
+impl_payload_conversions!(5; A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_payload_conversions!(6; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
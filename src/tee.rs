@@ -0,0 +1,298 @@
+//! Encoding of terms to two sinks at once, e.g., to a socket and to an audit log.
+//!
+//! [`Encoder::tee`] encodes each term once and writes its bytes to both writers of a
+//! [`TeeWriter`], one after the other. With the `tokio-async` feature, [`AsyncTeeEncoder`] writes
+//! each term to its primary writer, and lets its secondary writer lag behind through a bounded
+//! buffer, whose [`Overflow`] policy decides what happens once the secondary falls too far
+//! behind.
+//!
+//! A write error of either sink is a [`TeeError`] within the [`EncodeError::Io`], which tells
+//! which [`Sink`] failed (see [`TeeError::find`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::{Atom, Encoder, Term};
+//!
+//! let mut encoder = Encoder::tee(Vec::new(), Vec::new());
+//! encoder.encode_next(&Term::from(Atom::from("a"))).unwrap();
+//! let (socket, log) = encoder.into_inner().into_inner();
+//! assert_eq!(socket, [131, 100, 0, 1, 97]);
+//! assert_eq!(socket, log);
+//! ```
+use super::*;
+use codec_common::*;
+
+/// One of the two sinks of a tee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    Primary,
+    Secondary,
+}
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sink::Primary => write!(f, "primary"),
+            Sink::Secondary => write!(f, "secondary"),
+        }
+    }
+}
+
+/// Error of writing to a sink of a tee, within the [`io::Error`] returned by the tee.
+#[derive(Debug, thiserror::Error)]
+#[error("the {sink} sink of the tee failed")]
+pub struct TeeError {
+    pub sink: Sink,
+    #[source]
+    pub source: io::Error,
+}
+impl TeeError {
+    /// Returns the tee error within `error`, if it is an I/O error of a tee.
+    pub fn find(error: &EncodeError) -> Option<&TeeError> {
+        match error {
+            EncodeError::Io(e) => e.get_ref()?.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+fn sink_error(sink: Sink, source: io::Error) -> io::Error {
+    io::Error::new(source.kind(), TeeError { sink, source })
+}
+
+/// Writer which writes all of its bytes to two writers (see [`Encoder::tee`]).
+///
+/// Each write is written in full to the primary writer, then to the secondary one, so that both
+/// have the same bytes unless a write fails.
+#[derive(Debug)]
+pub struct TeeWriter<P, S> {
+    primary: P,
+    secondary: S,
+}
+impl<P, S> TeeWriter<P, S> {
+    pub fn new(primary: P, secondary: S) -> Self {
+        TeeWriter { primary, secondary }
+    }
+
+    pub fn get_ref(&self) -> (&P, &S) {
+        (&self.primary, &self.secondary)
+    }
+
+    pub fn get_mut(&mut self) -> (&mut P, &mut S) {
+        (&mut self.primary, &mut self.secondary)
+    }
+
+    pub fn into_inner(self) -> (P, S) {
+        (self.primary, self.secondary)
+    }
+}
+impl<P: io::Write, S: io::Write> io::Write for TeeWriter<P, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.primary
+            .write_all(buf)
+            .map_err(|e| sink_error(Sink::Primary, e))?;
+        self.secondary
+            .write_all(buf)
+            .map_err(|e| sink_error(Sink::Secondary, e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.primary
+            .flush()
+            .map_err(|e| sink_error(Sink::Primary, e))?;
+        self.secondary
+            .flush()
+            .map_err(|e| sink_error(Sink::Secondary, e))
+    }
+}
+
+/// What an [`AsyncTeeEncoder`] does with a term which does not fit in the buffer of its
+/// secondary writer.
+#[cfg(feature = "tokio-async")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Waits for the secondary writer to take enough of the buffer.
+    #[default]
+    Block,
+
+    /// Does not write the term to the secondary writer, and counts it as
+    /// [dropped](AsyncTeeEncoder::dropped).
+    Drop,
+
+    /// Fails with a [`TeeError`] of the secondary sink (after writing the term to the primary
+    /// writer).
+    Error,
+}
+
+/// Encoder of terms to a primary writer, whose secondary writer may lag behind (see
+/// [`tee`](crate::tee)).
+///
+/// Each term is encoded once into a buffer, written to the primary writer, and queued for the
+/// secondary writer. The queued terms are written to the secondary writer as far as it takes
+/// them without waiting, whenever a term is encoded, and in full by
+/// [`AsyncTeeEncoder::flush`]. The secondary writer never gets part of a term, unless a write
+/// fails.
+#[cfg(feature = "tokio-async")]
+pub struct AsyncTeeEncoder<P, S> {
+    primary: P,
+    secondary: S,
+    options: EncoderOptions,
+    /// The terms not yet written to the secondary writer, the first of which has `written`
+    /// bytes written.
+    queue: std::collections::VecDeque<Vec<u8>>,
+    written: usize,
+    /// The number of bytes of the queue not yet written.
+    queued: usize,
+    capacity: usize,
+    overflow: Overflow,
+    dropped: u64,
+}
+#[cfg(feature = "tokio-async")]
+impl<P, S> AsyncTeeEncoder<P, S>
+where
+    P: tokio::io::AsyncWrite + Unpin,
+    S: tokio::io::AsyncWrite + Unpin,
+{
+    /// Makes an encoder whose secondary writer lags behind by at most `capacity` bytes.
+    ///
+    /// With [`Overflow::Block`], a term larger than `capacity` is queued once the secondary
+    /// writer took all of the others.
+    pub fn new(primary: P, secondary: S, capacity: usize) -> Self {
+        AsyncTeeEncoder {
+            primary,
+            secondary,
+            options: EncoderOptions::default(),
+            queue: std::collections::VecDeque::new(),
+            written: 0,
+            queued: 0,
+            capacity,
+            overflow: Overflow::default(),
+            dropped: 0,
+        }
+    }
+
+    pub fn with_options(mut self, options: EncoderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Returns the number of terms which [`Overflow::Drop`] kept from the secondary writer.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the number of bytes queued for the secondary writer.
+    pub fn buffered(&self) -> usize {
+        self.queued
+    }
+
+    /// Encodes `term`, writes it to the primary writer, and queues it for the secondary one.
+    pub async fn encode(&mut self, term: &Term) -> EncodeResult {
+        use tokio::io::AsyncWriteExt;
+
+        let mut bytes = Vec::new();
+        Encoder::with_options(&mut bytes, self.options).encode(term)?;
+        self.primary
+            .write_all(&bytes)
+            .await
+            .map_err(|e| sink_error(Sink::Primary, e))?;
+        self.drain(false).await?;
+        if self.queued + bytes.len() > self.capacity {
+            match self.overflow {
+                Overflow::Block => {
+                    let until = self.capacity.saturating_sub(bytes.len());
+                    std::future::poll_fn(|cx| self.poll_drain(cx, until)).await?;
+                }
+                Overflow::Drop => {
+                    self.dropped += 1;
+                    return Ok(());
+                }
+                Overflow::Error => {
+                    let e = io::Error::other("the buffer of the secondary writer is full");
+                    return Err(sink_error(Sink::Secondary, e).into());
+                }
+            }
+        }
+        self.queued += bytes.len();
+        self.queue.push_back(bytes);
+        self.drain(false).await?;
+        Ok(())
+    }
+
+    /// Flushes the primary writer, and writes the queued terms to the secondary writer and
+    /// flushes it.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        self.primary
+            .flush()
+            .await
+            .map_err(|e| sink_error(Sink::Primary, e))?;
+        self.drain(true).await?;
+        self.secondary
+            .flush()
+            .await
+            .map_err(|e| sink_error(Sink::Secondary, e))
+    }
+
+    /// Returns the writers, without the terms still queued for the secondary one (see
+    /// [`AsyncTeeEncoder::flush`]).
+    pub fn into_inner(self) -> (P, S) {
+        (self.primary, self.secondary)
+    }
+
+    /// Writes the queued terms to the secondary writer, or only as many as it takes without
+    /// waiting unless `wait`.
+    async fn drain(&mut self, wait: bool) -> io::Result<()> {
+        std::future::poll_fn(|cx| match self.poll_drain(cx, 0) {
+            std::task::Poll::Pending if !wait => std::task::Poll::Ready(Ok(())),
+            poll => poll,
+        })
+        .await
+    }
+
+    /// Writes the queued terms to the secondary writer until at most `until` bytes are queued.
+    fn poll_drain(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        until: usize,
+    ) -> std::task::Poll<io::Result<()>> {
+        while self.queued > until {
+            let Some(front) = self.queue.front() else {
+                break;
+            };
+            let poll =
+                std::pin::Pin::new(&mut self.secondary).poll_write(cx, &front[self.written..]);
+            let n = match std::task::ready!(poll) {
+                Ok(0) => Err(io::Error::from(io::ErrorKind::WriteZero)),
+                result => result,
+            }
+            .map_err(|e| sink_error(Sink::Secondary, e))?;
+            self.written += n;
+            self.queued -= n;
+            if self.written == front.len() {
+                self.queue.pop_front();
+                self.written = 0;
+            }
+        }
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+impl<P: io::Write, S: io::Write> Encoder<TeeWriter<P, S>> {
+    /// Makes an encoder which writes each term to both `primary` and `secondary` (see
+    /// [`tee`](crate::tee)).
+    pub fn tee(primary: P, secondary: S) -> Self {
+        Encoder::new(TeeWriter::new(primary, secondary))
+    }
+}
@@ -0,0 +1,381 @@
+//! Entries of a [`Map`].
+use super::*;
+#[cfg(not(feature = "std"))]
+use hashbrown::hash_map;
+#[cfg(feature = "std")]
+use std::collections::hash_map;
+
+/// Number of entries up to which a map is a vector of entries (which is searched without hashing).
+const MAX_SMALL_LEN: usize = 16;
+
+/// Map from terms to terms with the API of a [`HashMap`].
+///
+/// Maps with up to 16 entries (e.g., Elixir structs) keep their entries in a vector,
+/// and larger maps switch to a `HashMap`. The order of iteration is unspecified like
+/// the one of a `HashMap`.
+#[derive(Clone)]
+pub struct TermMap {
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    Small(Vec<(Term, Term)>),
+    Large(HashMap<Term, Term>),
+}
+
+impl TermMap {
+    /// Makes an empty map.
+    pub fn new() -> Self {
+        TermMap {
+            repr: Repr::Small(Vec::new()),
+        }
+    }
+
+    /// Makes an empty map with room for `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let repr = if capacity <= MAX_SMALL_LEN {
+            Repr::Small(Vec::with_capacity(capacity))
+        } else {
+            Repr::Large(HashMap::with_capacity(capacity))
+        };
+        TermMap { repr }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        match self.repr {
+            Repr::Small(ref x) => x.len(),
+            Repr::Large(ref x) => x.len(),
+        }
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.repr = Repr::Small(Vec::new());
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &Term) -> Option<&Term> {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns the entry of `key`.
+    pub fn get_key_value(&self, key: &Term) -> Option<(&Term, &Term)> {
+        match self.repr {
+            Repr::Small(ref x) => x.iter().find(|(k, _)| k == key).map(|(k, v)| (k, v)),
+            Repr::Large(ref x) => x.get_key_value(key),
+        }
+    }
+
+    /// Returns the value of `key` mutably.
+    pub fn get_mut(&mut self, key: &Term) -> Option<&mut Term> {
+        match self.repr {
+            Repr::Small(ref mut x) => x.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            Repr::Large(ref mut x) => x.get_mut(key),
+        }
+    }
+
+    /// Returns `true` if the map has an entry for `key`.
+    pub fn contains_key(&self, key: &Term) -> bool {
+        self.get_key_value(key).is_some()
+    }
+
+    /// Sets the value of `key`, and returns the previous value if there is one.
+    pub fn insert(&mut self, key: Term, value: Term) -> Option<Term> {
+        match self.repr {
+            Repr::Small(ref mut x) => {
+                if let Some((_, v)) = x.iter_mut().find(|(k, _)| *k == key) {
+                    return Some(core::mem::replace(v, value));
+                }
+                if x.len() < MAX_SMALL_LEN {
+                    x.push((key, value));
+                    return None;
+                }
+                let mut map = x.drain(..).collect::<HashMap<_, _>>();
+                map.insert(key, value);
+                self.repr = Repr::Large(map);
+                None
+            }
+            Repr::Large(ref mut x) => x.insert(key, value),
+        }
+    }
+
+    /// Removes the entry of `key`, and returns its value if there is one.
+    pub fn remove(&mut self, key: &Term) -> Option<Term> {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes the entry of `key`, and returns it if there is one.
+    pub fn remove_entry(&mut self, key: &Term) -> Option<(Term, Term)> {
+        match self.repr {
+            Repr::Small(ref mut x) => {
+                let i = x.iter().position(|(k, _)| k == key)?;
+                Some(x.swap_remove(i))
+            }
+            Repr::Large(ref mut x) => x.remove_entry(key),
+        }
+    }
+
+    /// Keeps only the entries for which `f` returns `true`.
+    pub fn retain<F: FnMut(&Term, &mut Term) -> bool>(&mut self, mut f: F) {
+        match self.repr {
+            Repr::Small(ref mut x) => x.retain_mut(|(k, v)| f(k, v)),
+            Repr::Large(ref mut x) => x.retain(|k, v| f(k, v)),
+        }
+    }
+
+    /// Returns an iterator over the entries.
+    pub fn iter(&self) -> Iter<'_> {
+        let repr = match self.repr {
+            Repr::Small(ref x) => IterRepr::Small(x.iter()),
+            Repr::Large(ref x) => IterRepr::Large(x.iter()),
+        };
+        Iter { repr }
+    }
+
+    /// Returns an iterator over the keys.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &Term> + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Returns an iterator over the values.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &Term> + '_ {
+        self.iter().map(|(_, v)| v)
+    }
+}
+impl Default for TermMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for TermMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+impl PartialEq for TermMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+impl Eq for TermMap {}
+#[cfg(feature = "serde")]
+impl serde::Serialize for TermMap {
+    /// Serializes the entries as a sequence of `[key, value]` pairs, since the keys are terms.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TermMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<(Term, Term)>::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+impl core::ops::Index<&Term> for TermMap {
+    type Output = Term;
+    fn index(&self, key: &Term) -> &Term {
+        self.get(key).expect("no entry found for key")
+    }
+}
+impl FromIterator<(Term, Term)> for TermMap {
+    fn from_iter<I: IntoIterator<Item = (Term, Term)>>(iter: I) -> Self {
+        let mut map = TermMap::new();
+        map.extend(iter);
+        map
+    }
+}
+impl Extend<(Term, Term)> for TermMap {
+    fn extend<I: IntoIterator<Item = (Term, Term)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+impl From<HashMap<Term, Term>> for TermMap {
+    fn from(map: HashMap<Term, Term>) -> Self {
+        if map.len() <= MAX_SMALL_LEN {
+            TermMap {
+                repr: Repr::Small(map.into_iter().collect()),
+            }
+        } else {
+            TermMap {
+                repr: Repr::Large(map),
+            }
+        }
+    }
+}
+impl From<TermMap> for HashMap<Term, Term> {
+    fn from(map: TermMap) -> Self {
+        match map.repr {
+            Repr::Small(x) => x.into_iter().collect(),
+            Repr::Large(x) => x,
+        }
+    }
+}
+impl<const N: usize> From<[(Term, Term); N]> for TermMap {
+    fn from(entries: [(Term, Term); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+impl<'a> IntoIterator for &'a TermMap {
+    type Item = (&'a Term, &'a Term);
+    type IntoIter = Iter<'a>;
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+impl IntoIterator for TermMap {
+    type Item = (Term, Term);
+    type IntoIter = IntoIter;
+    fn into_iter(self) -> IntoIter {
+        let repr = match self.repr {
+            Repr::Small(x) => IntoIterRepr::Small(x.into_iter()),
+            Repr::Large(x) => IntoIterRepr::Large(x.into_iter()),
+        };
+        IntoIter { repr }
+    }
+}
+
+/// Iterator over the entries of a [`TermMap`].
+#[derive(Clone)]
+pub struct Iter<'a> {
+    repr: IterRepr<'a>,
+}
+
+#[derive(Clone)]
+enum IterRepr<'a> {
+    Small(core::slice::Iter<'a, (Term, Term)>),
+    Large(hash_map::Iter<'a, Term, Term>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Term, &'a Term);
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.repr {
+            IterRepr::Small(ref mut x) => x.next().map(|(k, v)| (k, v)),
+            IterRepr::Large(ref mut x) => x.next(),
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.repr {
+            IterRepr::Small(ref x) => x.size_hint(),
+            IterRepr::Large(ref x) => x.size_hint(),
+        }
+    }
+}
+impl ExactSizeIterator for Iter<'_> {}
+
+/// Owning iterator over the entries of a [`TermMap`].
+pub struct IntoIter {
+    repr: IntoIterRepr,
+}
+
+enum IntoIterRepr {
+    Small(alloc::vec::IntoIter<(Term, Term)>),
+    Large(hash_map::IntoIter<Term, Term>),
+}
+
+impl Iterator for IntoIter {
+    type Item = (Term, Term);
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.repr {
+            IntoIterRepr::Small(ref mut x) => x.next(),
+            IntoIterRepr::Large(ref mut x) => x.next(),
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.repr {
+            IntoIterRepr::Small(ref x) => x.size_hint(),
+            IntoIterRepr::Large(ref x) => x.size_hint(),
+        }
+    }
+}
+impl ExactSizeIterator for IntoIter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(i32, i32),
+        Remove(i32),
+        Get(i32),
+        Retain(i32),
+        Clear,
+    }
+
+    fn arb_op() -> impl Strategy<Value = Op> {
+        // Few distinct keys, so that the operations hit existing entries.
+        let key = 0..40i32;
+        prop_oneof![
+            8 => (key.clone(), any::<i32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+            3 => key.clone().prop_map(Op::Remove),
+            3 => key.prop_map(Op::Get),
+            1 => (1..5i32).prop_map(Op::Retain),
+            1 => Just(Op::Clear),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn behaves_like_a_hash_map(ops in proptest::collection::vec(arb_op(), 0..200)) {
+            let mut map = TermMap::new();
+            let mut model = HashMap::<Term, Term>::new();
+            for op in ops {
+                match op {
+                    Op::Insert(k, v) => {
+                        let (k, v) = (Term::from(k), Term::from(v));
+                        prop_assert_eq!(map.insert(k.clone(), v.clone()), model.insert(k, v));
+                    }
+                    Op::Remove(k) => {
+                        let k = Term::from(k);
+                        prop_assert_eq!(map.remove(&k), model.remove(&k));
+                    }
+                    Op::Get(k) => {
+                        let k = Term::from(k);
+                        prop_assert_eq!(map.get(&k), model.get(&k));
+                        prop_assert_eq!(map.contains_key(&k), model.contains_key(&k));
+                    }
+                    Op::Retain(n) => {
+                        let keep = |v: &Term| matches!(v, Term::FixInteger(x) if x.value % n == 0);
+                        map.retain(|_, v| keep(v));
+                        model.retain(|_, v| keep(v));
+                    }
+                    Op::Clear => {
+                        map.clear();
+                        model.clear();
+                    }
+                }
+                prop_assert_eq!(map.len(), model.len());
+                prop_assert_eq!(map.iter().len(), model.len());
+                prop_assert!(map.iter().all(|(k, v)| model.get(k) == Some(v)));
+            }
+            prop_assert_eq!(TermMap::from(model.clone()), map.clone());
+            prop_assert_eq!(HashMap::from(map), model);
+        }
+    }
+
+    #[test]
+    fn switches_to_a_hash_map_when_growing() {
+        let mut map = (0..MAX_SMALL_LEN as i32)
+            .map(|i| (Term::from(i), Term::from(i)))
+            .collect::<TermMap>();
+        assert!(matches!(map.repr, Repr::Small(_)));
+        map.insert(Term::from(0), Term::from(1));
+        assert!(matches!(map.repr, Repr::Small(_)));
+        map.insert(Term::from(-1), Term::from(-1));
+        assert!(matches!(map.repr, Repr::Large(_)));
+        assert_eq!(map.len(), MAX_SMALL_LEN + 1);
+        assert_eq!(map[&Term::from(0)], Term::from(1));
+    }
+}
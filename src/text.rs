@@ -0,0 +1,790 @@
+//! Parsing of terms from their text, as printed by their [`Display`](fmt::Display) (see
+//! [`parse_term`]).
+//!
+//! The text is Erlang's syntax for terms (e.g., `{ok, [1, 2], #{'a' => <<"b">>}}`), as
+//! `erl_parse` reads them, with the forms which Erlang prints for the terms which cannot be
+//! written in code:
+//!
+//! - pids are `<Node.ID.Serial>`, ports `#Port<Node.ID>`, and references
+//!   `#Ref<Node.Word.Word...>`. The node is either its quoted name (e.g.,
+//!   `<'foo@bar'.1.2>`, as these types print themselves), or a number, as Erlang prints it
+//!   (e.g., `<0.1.2>`). A number stands for the placeholder node `nonode@nohost`, since the text
+//!   does not tell the name of the node. Neither tells the creation, which is 0.
+//! - external funs are `fun Module:Function/Arity`.
+//! - internal funs (`#Fun<...>`) cannot be parsed, since their text lacks most of their fields,
+//!   and fail with [`ParseErrorKind::InternalFun`].
+//! - raw terms are `#Raw<Byte,...>`.
+//!
+//! The parsed terms are canonical (see [`Term::canonicalize`]): integers which fit an `i32` are
+//! [`FixInteger`]s, lists of bytes are [`ByteList`]s, and bit strings of whole bytes are
+//! [`Binary`]s. So the text of a canonical term parses back into it, unless the term holds an
+//! internal fun, or identifiers with a creation.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::{parse_term, Atom, Pid, Term, Tuple};
+//!
+//! let term = parse_term("{ok, <0.80.0>, 1.5}").unwrap();
+//! let pid = Pid::new("nonode@nohost", 80, 0, 0);
+//! let expected = Tuple::from(vec![
+//!     Term::from(Atom::from("ok")),
+//!     Term::from(pid),
+//!     Term::try_from(1.5).unwrap(),
+//! ]);
+//! assert_eq!(term, Term::from(expected));
+//! assert_eq!(term.to_string(), "{'ok',<'nonode@nohost'.80.0>,1.5}");
+//! assert_eq!(parse_term(&term.to_string()).unwrap(), term);
+//! ```
+use super::*;
+use num::traits::{Num, ToPrimitive};
+
+/// Node of the identifiers whose text has a node number rather than a name.
+pub const PLACEHOLDER_NODE: &str = "nonode@nohost";
+
+/// Error of [`parse_term`], at the byte `offset` of the text.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{kind} at offset {offset}")]
+pub struct ParseError {
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// What is wrong with the text of a term.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseErrorKind {
+    #[error("the text ends within the term")]
+    UnexpectedEnd,
+
+    #[error("expected {expected}, found {found:?}")]
+    Unexpected { expected: &'static str, found: char },
+
+    #[error("invalid escape sequence")]
+    InvalidEscape,
+
+    /// A number does not fit where it is (e.g., a byte of a binary, or the ID of a pid).
+    #[error("{value} is out of range")]
+    OutOfRange { value: String },
+
+    #[error("the float is not finite")]
+    NonFiniteFloat,
+
+    /// A segment of a binary other than the last one has a size other than 8 bits.
+    #[error("only the last segment of a binary may have a size other than 8")]
+    UnalignedSegment,
+
+    #[error("internal funs cannot be parsed")]
+    InternalFun,
+
+    #[error("the term is nested deeper than {max} levels")]
+    TooDeep { max: usize },
+
+    #[error("text follows the term")]
+    TrailingText,
+}
+
+/// Parses the text of a term (see [`text`](crate::text)).
+///
+/// The text may be followed by a dot, as by `io_lib:format("~p.", [Term])`, and its tokens may
+/// be separated by whitespace.
+pub fn parse_term(text: &str) -> Result<Term, ParseError> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        depth: 0,
+    };
+    let term = parser.term()?;
+    parser.skip_whitespace();
+    parser.eat('.');
+    parser.skip_whitespace();
+    if parser.pos != text.len() {
+        return Err(parser.error(ParseErrorKind::TrailingText));
+    }
+    Ok(term)
+}
+
+impl core::str::FromStr for Term {
+    type Err = ParseError;
+
+    /// Parses the text of a term (see [`parse_term`]).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_term(s)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+impl<'a> Parser<'a> {
+    fn term(&mut self) -> Result<Term, ParseError> {
+        self.skip_whitespace();
+        if self.depth >= MAX_DECODE_DEPTH {
+            let max = MAX_DECODE_DEPTH;
+            return Err(self.error(ParseErrorKind::TooDeep { max }));
+        }
+        self.depth += 1;
+        let result = self.subterm();
+        self.depth -= 1;
+        result
+    }
+
+    fn subterm(&mut self) -> Result<Term, ParseError> {
+        match self.peek() {
+            Some('[') => self.list(),
+            Some('{') => self.tuple(),
+            Some('#') => self.hashed(),
+            Some('<') if self.rest().starts_with("<<") => self.binary(),
+            Some('<') => self.pid().map(Term::from),
+            Some('\'') => self.quoted_atom().map(Term::from),
+            Some('"') => self.string(),
+            Some('$') => {
+                self.pos += 1;
+                let c = self.char_literal()?;
+                Ok(Term::from(FixInteger::from(c as i32)))
+            }
+            Some('-' | '0'..='9') => self.number(),
+            Some('a'..='z') => {
+                let name = self.unquoted_atom();
+                if name == "fun" {
+                    self.external_fun().map(Term::from)
+                } else {
+                    Ok(Term::from(Atom::from(name)))
+                }
+            }
+            _ => Err(self.unexpected("a term")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Term, ParseError> {
+        self.expect('[', "'['")?;
+        let mut elements = Vec::new();
+        let mut tail = None;
+        if !self.eat_token(']') {
+            loop {
+                elements.push(self.term()?);
+                if self.eat_token('|') {
+                    tail = Some(self.term()?);
+                    self.expect_token(']', "']'")?;
+                    break;
+                }
+                if self.eat_token(']') {
+                    break;
+                }
+                self.expect_token(',', "',', '|' or ']'")?;
+            }
+        }
+        match tail {
+            None => Ok(proper_list(elements)),
+            Some(Term::List(tail)) => {
+                elements.extend(tail.elements);
+                Ok(proper_list(elements))
+            }
+            Some(Term::ByteList(tail)) => {
+                elements.extend(tail.bytes.into_iter().map(|b| Term::from(i32::from(b))));
+                Ok(proper_list(elements))
+            }
+            Some(Term::ImproperList(tail)) => {
+                elements.extend(tail.elements);
+                Ok(Term::from(ImproperList::from((elements, *tail.last))))
+            }
+            Some(last) => Ok(Term::from(ImproperList::from((elements, last)))),
+        }
+    }
+
+    fn tuple(&mut self) -> Result<Term, ParseError> {
+        self.expect('{', "'{'")?;
+        let elements = self.sequence('}', |parser| parser.term())?;
+        Ok(Term::from(Tuple::from(elements)))
+    }
+
+    /// Parses a map, or one of the forms which Erlang prints for other terms (e.g., `#Ref<...>`).
+    fn hashed(&mut self) -> Result<Term, ParseError> {
+        let start = self.pos;
+        self.expect('#', "'#'")?;
+        if self.eat('{') {
+            let entries = self.sequence('}', |parser| {
+                let key = parser.term()?;
+                parser.skip_whitespace();
+                if !parser.rest().starts_with("=>") {
+                    return Err(parser.unexpected("'=>'"));
+                }
+                parser.pos += 2;
+                Ok((key, parser.term()?))
+            })?;
+            return Ok(Term::from(Map::from(
+                entries.into_iter().collect::<TermMap>(),
+            )));
+        }
+        let name = self.unquoted_identifier();
+        match name {
+            "Ref" => {
+                self.expect('<', "'<'")?;
+                let node = self.node()?;
+                let mut id = Vec::new();
+                while self.eat('.') {
+                    id.push(self.unsigned()?);
+                }
+                self.expect('>', "'>'")?;
+                Ok(Term::from(Reference::new(node, id, 0)))
+            }
+            "Port" => {
+                self.expect('<', "'<'")?;
+                let node = self.node()?;
+                self.expect('.', "'.'")?;
+                let id = self.unsigned()?;
+                self.expect('>', "'>'")?;
+                Ok(Term::from(Port::new(node, id, 0)))
+            }
+            "Raw" => {
+                self.expect('<', "'<'")?;
+                let bytes = self.sequence('>', |parser| {
+                    parser.skip_whitespace();
+                    parser.unsigned()
+                })?;
+                Ok(Term::from(Raw::from(bytes)))
+            }
+            "Fun" => Err(ParseError {
+                offset: start,
+                kind: ParseErrorKind::InternalFun,
+            }),
+            _ => {
+                self.pos = start + 1;
+                Err(self.unexpected("'{', 'Ref', 'Port' or 'Raw'"))
+            }
+        }
+    }
+
+    fn binary(&mut self) -> Result<Term, ParseError> {
+        self.pos += 2;
+        let mut bytes = Vec::new();
+        let mut tail_bits_size = 8;
+        self.skip_whitespace();
+        if self.rest().starts_with(">>") {
+            self.pos += 2;
+            return Ok(Term::from(Binary::from(bytes)));
+        }
+        loop {
+            self.skip_whitespace();
+            if tail_bits_size != 8 {
+                return Err(self.error(ParseErrorKind::UnalignedSegment));
+            }
+            if self.peek() == Some('"') {
+                let start = self.pos;
+                for c in self.quoted('"')? {
+                    bytes.push(u8::try_from(c).map_err(|_| ParseError {
+                        offset: start,
+                        kind: ParseErrorKind::OutOfRange {
+                            value: u32::from(c).to_string(),
+                        },
+                    })?);
+                }
+            } else {
+                let value: u8 = self.unsigned()?;
+                if self.eat_token(':') {
+                    self.skip_whitespace();
+                    let size: u8 = self.unsigned()?;
+                    if size == 0 || size > 8 || u16::from(value) >> size != 0 {
+                        let value = format!("{}:{}", value, size);
+                        return Err(self.error(ParseErrorKind::OutOfRange { value }));
+                    }
+                    tail_bits_size = size;
+                }
+                bytes.push(value);
+            }
+            self.skip_whitespace();
+            if self.rest().starts_with(">>") {
+                self.pos += 2;
+                break;
+            }
+            self.expect(',', "',' or '>>'")?;
+        }
+        if tail_bits_size == 8 {
+            Ok(Term::from(Binary::from(bytes)))
+        } else {
+            Ok(Term::from(BitBinary::from((bytes, tail_bits_size))))
+        }
+    }
+
+    fn pid(&mut self) -> Result<Pid, ParseError> {
+        self.expect('<', "'<'")?;
+        let node = self.node()?;
+        self.expect('.', "'.'")?;
+        let id = self.unsigned()?;
+        self.expect('.', "'.'")?;
+        let serial = self.unsigned()?;
+        self.expect('>', "'>'")?;
+        Ok(Pid::new(node, id, serial, 0))
+    }
+
+    /// Parses the node of an identifier, which is either a quoted atom or a node number.
+    fn node(&mut self) -> Result<Atom, ParseError> {
+        match self.peek() {
+            Some('\'') => self.quoted_atom(),
+            Some('0'..='9') => {
+                let _: u64 = self.unsigned()?;
+                Ok(Atom::from(PLACEHOLDER_NODE))
+            }
+            _ => Err(self.unexpected("a node")),
+        }
+    }
+
+    fn external_fun(&mut self) -> Result<ExternalFun, ParseError> {
+        let module = self.atom()?;
+        self.expect_token(':', "':'")?;
+        let function = self.atom()?;
+        self.expect_token('/', "'/'")?;
+        self.skip_whitespace();
+        let arity = self.unsigned()?;
+        Ok(ExternalFun::new(module, function, arity))
+    }
+
+    fn atom(&mut self) -> Result<Atom, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('\'') => self.quoted_atom(),
+            Some('a'..='z') => Ok(Atom::from(self.unquoted_atom())),
+            _ => Err(self.unexpected("an atom")),
+        }
+    }
+
+    fn quoted_atom(&mut self) -> Result<Atom, ParseError> {
+        let name = self.quoted('\'')?;
+        Ok(Atom::from(name.into_iter().collect::<String>()))
+    }
+
+    fn unquoted_atom(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '@'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn unquoted_identifier(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Parses a string, which is a list of its characters.
+    fn string(&mut self) -> Result<Term, ParseError> {
+        let chars = self.quoted('"')?;
+        let elements = chars.into_iter().map(|c| Term::from(c as i32)).collect();
+        Ok(proper_list(elements))
+    }
+
+    /// Parses the characters between two `quote`s, with their escape sequences.
+    fn quoted(&mut self, quote: char) -> Result<Vec<char>, ParseError> {
+        self.pos += quote.len_utf8();
+        let mut chars = Vec::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error(ParseErrorKind::UnexpectedEnd)),
+                Some(c) if c == quote => return Ok(chars),
+                Some('\\') => chars.push(self.escape()?),
+                Some(c) => chars.push(c),
+            }
+        }
+    }
+
+    fn char_literal(&mut self) -> Result<char, ParseError> {
+        match self.bump() {
+            None => Err(self.error(ParseErrorKind::UnexpectedEnd)),
+            Some('\\') => self.escape(),
+            Some(c) => Ok(c),
+        }
+    }
+
+    /// Parses an escape sequence of Erlang, after its backslash.
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos - 1;
+        let invalid = ParseError {
+            offset: start,
+            kind: ParseErrorKind::InvalidEscape,
+        };
+        let c = match self.bump() {
+            None => return Err(self.error(ParseErrorKind::UnexpectedEnd)),
+            Some('b') => '\u{8}',
+            Some('d') => '\u{7f}',
+            Some('e') => '\u{1b}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('s') => ' ',
+            Some('t') => '\t',
+            Some('v') => '\u{b}',
+            Some('^') => match self.bump() {
+                Some(c) if c.is_ascii() => char::from(c as u8 & 0x1f),
+                _ => return Err(invalid),
+            },
+            Some('x') => {
+                let digits = if self.eat('{') {
+                    let rest = self.rest();
+                    let len = rest.find('}').ok_or_else(|| invalid.clone())?;
+                    self.pos += len + 1;
+                    &rest[..len]
+                } else {
+                    let rest = self.rest();
+                    let digits = rest.get(..2).ok_or_else(|| invalid.clone())?;
+                    self.pos += 2;
+                    digits
+                };
+                let code = u32::from_str_radix(digits, 16).map_err(|_| invalid.clone())?;
+                char::from_u32(code).ok_or(invalid)?
+            }
+            Some(c @ '0'..='7') => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !('0'..='7').contains(&c))
+                    .unwrap_or(rest.len())
+                    .min(2);
+                self.pos += len;
+                let code = (c as u32 - '0' as u32) * 8u32.pow(len as u32)
+                    + u32::from_str_radix(&rest[..len], 8).unwrap_or(0);
+                char::from_u32(code).ok_or(invalid)?
+            }
+            Some(c) => c,
+        };
+        Ok(c)
+    }
+
+    /// Parses an integer (decimal, or `Base#Digits`) or a float.
+    fn number(&mut self) -> Result<Term, ParseError> {
+        let start = self.pos;
+        let negative = self.eat('-');
+        let digits = self.digits(10);
+        if digits.is_empty() {
+            return Err(self.unexpected("a digit"));
+        }
+        let mut radix = 10;
+        let mut digits = digits;
+        if self.peek() == Some('#') {
+            radix = digits
+                .parse::<u32>()
+                .ok()
+                .filter(|r| (2..=36).contains(r))
+                .ok_or_else(|| ParseError {
+                    offset: start,
+                    kind: ParseErrorKind::OutOfRange {
+                        value: digits.to_owned(),
+                    },
+                })?;
+            self.pos += 1;
+            digits = self.digits(radix);
+            if digits.is_empty() {
+                return Err(self.unexpected("a digit"));
+            }
+        } else if self.peek() == Some('.')
+            && self.rest()[1..].starts_with(|c: char| c.is_ascii_digit())
+        {
+            return self.float(start);
+        }
+        let magnitude = BigInt::from_str_radix(digits, radix).expect("digits of the radix");
+        let value = if negative { -magnitude } else { magnitude };
+        Ok(match value.to_i32() {
+            Some(value) => Term::from(value),
+            None => Term::from(BigInteger { value }),
+        })
+    }
+
+    /// Parses the fraction and exponent of a float whose integer part starts at `start`.
+    fn float(&mut self, start: usize) -> Result<Term, ParseError> {
+        self.pos += 1;
+        self.digits(10);
+        let rest = self.rest();
+        if rest.starts_with(['e', 'E']) {
+            let sign = usize::from(rest[1..].starts_with(['+', '-']));
+            if rest[1 + sign..].starts_with(|c: char| c.is_ascii_digit()) {
+                self.pos += 1 + sign;
+                self.digits(10);
+            }
+        }
+        let text = &self.text[start..self.pos];
+        let value = text.parse::<f64>().expect("a float literal");
+        Float::try_from(value)
+            .map(Term::from)
+            .map_err(|_| ParseError {
+                offset: start,
+                kind: ParseErrorKind::NonFiniteFloat,
+            })
+    }
+
+    /// Skips the digits of `radix`, returning them.
+    fn digits(&mut self, radix: u32) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn unsigned<T: TryFrom<u64>>(&mut self) -> Result<T, ParseError> {
+        let start = self.pos;
+        let digits = self.digits(10);
+        if digits.is_empty() {
+            return Err(self.unexpected("a digit"));
+        }
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| ParseError {
+                offset: start,
+                kind: ParseErrorKind::OutOfRange {
+                    value: digits.to_owned(),
+                },
+            })
+    }
+
+    /// Parses items separated by commas up to `close`, which follows the opening delimiter.
+    fn sequence<T>(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut items = Vec::new();
+        if self.eat_token(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat_token(close) {
+                return Ok(items);
+            }
+            self.expect_token(',', "',' or the end of the sequence")?;
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_token(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.eat(c)
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn expect_token(&mut self, c: char, expected: &'static str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        self.expect(c, expected)
+    }
+
+    /// Skips whitespace, and comments up to the end of their line.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with('%') {
+                return;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            kind,
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        match self.peek() {
+            None => self.error(ParseErrorKind::UnexpectedEnd),
+            Some(found) => self.error(ParseErrorKind::Unexpected { expected, found }),
+        }
+    }
+}
+
+/// Makes a proper list, which is a byte list if its elements are bytes (as it is encoded).
+fn proper_list(elements: Vec<Term>) -> Term {
+    let bytes = elements
+        .iter()
+        .map(|e| match *e {
+            Term::FixInteger(FixInteger { value }) => u8::try_from(value).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    match bytes {
+        Some(bytes) if !bytes.is_empty() => Term::from(ByteList::from(bytes)),
+        _ => Term::from(List::from(elements)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrary::{arb_leaf, arb_term_with, TermParams};
+    use proptest::prelude::*;
+
+    fn parse(text: &str) -> Term {
+        parse_term(text).unwrap_or_else(|e| panic!("{}: {}", text, e))
+    }
+
+    fn error(text: &str) -> ParseErrorKind {
+        parse_term(text).unwrap_err().kind
+    }
+
+    // The text tells neither the creation of identifiers nor most fields of internal funs.
+    fn arb_term() -> impl Strategy<Value = Term> {
+        let without_creation = |term: Term| match term {
+            Term::Pid(x) => Term::from(Pid { creation: 0, ..x }),
+            Term::Port(x) => Term::from(Port { creation: 0, ..x }),
+            Term::Reference(x) => Term::from(Reference { creation: 0, ..*x }),
+            term => term,
+        };
+        let leaf = prop_oneof![
+            arb_leaf().prop_map(without_creation),
+            "[a-z'\\\\ \n]{0,8}".prop_map(|name| Term::from(Atom::from(name))),
+            prop::collection::vec(any::<u8>(), 1..8).prop_map(|b| Term::from(Raw::from(b))),
+        ];
+        arb_term_with(TermParams::default(), leaf.boxed(), false)
+    }
+
+    proptest! {
+        #[test]
+        fn text_round_trips(term in arb_term()) {
+            let term = term.canonicalize(CanonicalizeOptions::default());
+            prop_assert_eq!(parse_term(&term.to_string()).unwrap(), term);
+        }
+    }
+
+    #[test]
+    fn identifiers_are_parsed() {
+        let node = Atom::from(PLACEHOLDER_NODE);
+        assert_eq!(
+            parse("<0.1.0>"),
+            Term::from(Pid::new(node.as_str(), 1, 0, 0))
+        );
+        assert_eq!(
+            parse("<'foo@bar'.4.5>"),
+            Term::from(Pid::new("foo@bar", 4, 5, 0))
+        );
+        assert_eq!(
+            parse("#Port<0.8>"),
+            Term::from(Port::new(node.as_str(), 8, 0))
+        );
+        assert_eq!(
+            parse("#Ref<0.3845196396.1291845633.142417>"),
+            Term::from(Reference::new(
+                node.as_str(),
+                vec![3845196396, 1291845633, 142417],
+                0
+            ))
+        );
+        assert_eq!(
+            parse("fun erlang:'+'/2"),
+            Term::from(ExternalFun::new("erlang", "+", 2))
+        );
+        assert_eq!(parse("#Raw<97,1>"), Term::from(Raw::from(vec![97, 1])));
+
+        assert_eq!(
+            parse_term("#Fun<erl_eval.6.39074546>"),
+            Err(ParseError {
+                offset: 0,
+                kind: ParseErrorKind::InternalFun
+            })
+        );
+        assert!(matches!(
+            error("<0.4294967296.0>"),
+            ParseErrorKind::OutOfRange { .. }
+        ));
+        assert!(matches!(
+            error("#Pid<0.1.0>"),
+            ParseErrorKind::Unexpected { .. }
+        ));
+    }
+
+    #[test]
+    fn erlang_syntax_is_parsed() {
+        let text = "{ok, [1, $a, 16#FF | tail], \"h\\x{e9}\", <<\"ab\", 3:2>>,\n\
+                    #{'k\\'1' => -7.5e-3, k2 => []}}. % comment";
+        let expected = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("ok")),
+            Term::from(ImproperList::from((
+                vec![Term::from(1), Term::from(97), Term::from(255)],
+                Term::from(Atom::from("tail")),
+            ))),
+            Term::from(ByteList::from(vec![b'h', 0xe9])),
+            Term::from(BitBinary::from((vec![b'a', b'b', 3], 2))),
+            Term::from(Map::from([
+                (
+                    Term::from(Atom::from("k'1")),
+                    Term::try_from(-7.5e-3).unwrap(),
+                ),
+                (Term::from(Atom::from("k2")), Term::from(List::nil())),
+            ])),
+        ]));
+        assert_eq!(parse(text), expected);
+        // Formatting the parsed text normalizes its whitespace.
+        assert_eq!(parse(&expected.to_string()), expected);
+        assert_eq!(parse(" { a ,[ 1 ] , 2.0 } ").to_string(), "{'a',[1],2.0}");
+
+        assert_eq!(
+            parse("[1 | [2, 3]]"),
+            Term::from(ByteList::from(vec![1, 2, 3]))
+        );
+        assert_eq!(parse("\"\""), Term::from(List::nil()));
+        assert_eq!(
+            parse("\"\\x{100}\""),
+            Term::from(List::from(vec![Term::from(256)]))
+        );
+        assert_eq!(
+            parse("-123456789012345678901234567890"),
+            Term::from(BigInteger {
+                value: "-123456789012345678901234567890".parse().unwrap()
+            })
+        );
+        assert_eq!(parse("1.0e300").to_string(), "1.0e300");
+        assert_eq!(error("1.0e999"), ParseErrorKind::NonFiniteFloat);
+        assert_eq!(error("<<1:3, 2>>"), ParseErrorKind::UnalignedSegment);
+        assert!(matches!(
+            error("<<256>>"),
+            ParseErrorKind::OutOfRange { .. }
+        ));
+        assert_eq!(error("{a, b"), ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error("a b"), ParseErrorKind::TrailingText);
+        assert_eq!(error("'\\x{110000}'"), ParseErrorKind::InvalidEscape);
+        assert_eq!(
+            error(&"[".repeat(MAX_DECODE_DEPTH + 1)),
+            ParseErrorKind::TooDeep {
+                max: MAX_DECODE_DEPTH
+            }
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Borrowed terms, which refer to the input they were decoded from.
+//!
+//! [`decode_view`] decodes like [`decode_from_slice`], but atoms, binaries and byte lists
+//! are sliced out of the input instead of being copied. This is cheaper when only a
+//! part of a term is needed (e.g., to route a message by one of its fields).
+use super::*;
+use crate::codec_common::*;
+use crate::slice::{invalid_data, SliceDecoder};
+#[cfg(not(feature = "std"))]
+use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+/// Term borrowing its atoms, binaries and byte lists from the input.
+///
+/// Numbers, bit binaries, pids, ports, references and funs are small or rare enough to
+/// be owned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TermView<'a> {
+    /// Atom (borrowed unless it is a Latin-1 atom with non-ASCII characters).
+    Atom(Cow<'a, str>),
+    FixInteger(FixInteger),
+    BigInteger(BigInteger),
+    Float(Float),
+    Pid(Pid),
+    Port(Port),
+    Reference(Box<Reference>),
+    ExternalFun(Box<ExternalFun>),
+    InternalFun(Box<InternalFun>),
+    Binary(&'a [u8]),
+    BitBinary(BitBinary),
+    ByteList(&'a [u8]),
+    List(Vec<TermView<'a>>),
+    ImproperList(Vec<TermView<'a>>, Box<TermView<'a>>),
+    Tuple(Vec<TermView<'a>>),
+    /// Map entries in the order of the input.
+    Map(Vec<(TermView<'a>, TermView<'a>)>),
+}
+impl<'a> TermView<'a> {
+    /// Copies the view into an owned term.
+    ///
+    /// As in [`decode_from_slice`], the last of duplicate map keys wins.
+    pub fn to_owned(&self) -> Term {
+        match self {
+            TermView::Atom(x) => Term::from(Atom::from(x.as_ref())),
+            TermView::FixInteger(x) => Term::from(x.clone()),
+            TermView::BigInteger(x) => Term::from(x.clone()),
+            TermView::Float(x) => Term::from(x.clone()),
+            TermView::Pid(x) => Term::from(x.clone()),
+            TermView::Port(x) => Term::from(x.clone()),
+            TermView::Reference(x) => Term::Reference(x.clone()),
+            TermView::ExternalFun(x) => Term::ExternalFun(x.clone()),
+            TermView::InternalFun(x) => Term::InternalFun(x.clone()),
+            TermView::Binary(x) => Term::from(Binary::from(*x)),
+            TermView::BitBinary(x) => Term::from(x.clone()),
+            TermView::ByteList(x) => Term::from(ByteList::from(x.to_vec())),
+            TermView::List(x) => Term::from(List::from(to_owned_all(x))),
+            TermView::ImproperList(x, last) => Term::from(ImproperList::from((
+                to_owned_all(x),
+                TermView::to_owned(last),
+            ))),
+            TermView::Tuple(x) => Term::from(Tuple::from(to_owned_all(x))),
+            TermView::Map(x) => Term::from(Map::from(
+                x.iter()
+                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .collect::<HashMap<_, _>>(),
+            )),
+        }
+    }
+}
+
+fn to_owned_all(views: &[TermView]) -> Vec<Term> {
+    views.iter().map(TermView::to_owned).collect()
+}
+
+/// Decodes a term which occupies the whole of `bytes` into a view borrowing from it.
+///
+/// Compressed terms are rejected with [`DecodeError::CompressedView`]; use
+/// [`decode_from_slice`] for them.
+///
+/// # Examples
+///
+/// ```
+/// use eetf::TermView;
+///
+/// // {foo, <<"bar">>}
+/// let bytes = [131, 104, 2, 100, 0, 3, 102, 111, 111, 109, 0, 0, 0, 3, 98, 97, 114];
+/// let view = eetf::decode_view(&bytes).unwrap();
+/// match view {
+///     TermView::Tuple(elements) => assert_eq!(elements[1], TermView::Binary(b"bar")),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn decode_view(bytes: &[u8]) -> Result<TermView<'_>, DecodeError> {
+    let mut decoder = SliceDecoder::new(bytes);
+    decoder.read_version()?;
+    let view = match decoder.read_u8()? {
+        COMPRESSED_TERM => return Err(DecodeError::CompressedView),
+        tag => decode_subview(&mut decoder, tag)?,
+    };
+    decoder.finish()?;
+    Ok(view)
+}
+
+fn decode<'a>(decoder: &mut SliceDecoder<'a>) -> Result<TermView<'a>, DecodeError> {
+    let tag = decoder.read_u8()?;
+    decode_subview(decoder, tag)
+}
+
+fn decode_subview<'a>(
+    decoder: &mut SliceDecoder<'a>,
+    tag: u8,
+) -> Result<TermView<'a>, DecodeError> {
+    decoder.enter()?;
+    let view = decode_with_tag(decoder, tag);
+    decoder.leave();
+    view
+}
+
+fn decode_all<'a>(
+    decoder: &mut SliceDecoder<'a>,
+    count: usize,
+) -> Result<Vec<TermView<'a>>, DecodeError> {
+    // Every term takes at least one byte, which bounds the allocation.
+    let mut views = Vec::with_capacity(count.min(decoder.remaining()));
+    for _ in 0..count {
+        views.push(decode(decoder)?);
+    }
+    Ok(views)
+}
+
+// As in the slice decoder, the tags are decoded by functions of their own to keep the
+// frame of this recursive function small.
+fn decode_with_tag<'a>(
+    decoder: &mut SliceDecoder<'a>,
+    tag: u8,
+) -> Result<TermView<'a>, DecodeError> {
+    match tag {
+        ATOM_EXT | SMALL_ATOM_EXT => decode_latin1_atom(decoder, tag),
+        ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => decode_utf8_atom(decoder, tag),
+        BINARY_EXT => decode_binary(decoder),
+        STRING_EXT => decode_byte_list(decoder),
+        SMALL_TUPLE_EXT | LARGE_TUPLE_EXT => decode_tuple(decoder, tag),
+        NIL_EXT => Ok(TermView::List(Vec::new())),
+        LIST_EXT => decode_list(decoder),
+        MAP_EXT => decode_map(decoder),
+        _ => decode_owned(decoder, tag),
+    }
+}
+
+fn decode_latin1_atom<'a>(
+    decoder: &mut SliceDecoder<'a>,
+    tag: u8,
+) -> Result<TermView<'a>, DecodeError> {
+    let len = if tag == ATOM_EXT {
+        decoder.read_u16()? as usize
+    } else {
+        decoder.read_u8()? as usize
+    };
+    let bytes = decoder.read_bytes(len)?;
+    Ok(TermView::Atom(if bytes.is_ascii() {
+        Cow::Borrowed(core::str::from_utf8(bytes).expect("unreachable"))
+    } else {
+        Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect())
+    }))
+}
+
+fn decode_utf8_atom<'a>(
+    decoder: &mut SliceDecoder<'a>,
+    tag: u8,
+) -> Result<TermView<'a>, DecodeError> {
+    let len = if tag == ATOM_UTF8_EXT {
+        decoder.read_u16()? as usize
+    } else {
+        decoder.read_u8()? as usize
+    };
+    let name = core::str::from_utf8(decoder.read_bytes(len)?).map_err(invalid_data)?;
+    Ok(TermView::Atom(Cow::Borrowed(name)))
+}
+
+fn decode_binary<'a>(decoder: &mut SliceDecoder<'a>) -> Result<TermView<'a>, DecodeError> {
+    let size = decoder.read_len_u32()?;
+    Ok(TermView::Binary(decoder.read_bytes(size)?))
+}
+
+fn decode_byte_list<'a>(decoder: &mut SliceDecoder<'a>) -> Result<TermView<'a>, DecodeError> {
+    let len = decoder.read_u16()? as usize;
+    Ok(TermView::ByteList(decoder.read_bytes(len)?))
+}
+
+fn decode_tuple<'a>(decoder: &mut SliceDecoder<'a>, tag: u8) -> Result<TermView<'a>, DecodeError> {
+    let count = if tag == SMALL_TUPLE_EXT {
+        decoder.read_u8()? as usize
+    } else {
+        decoder.read_len_u32()?
+    };
+    Ok(TermView::Tuple(decode_all(decoder, count)?))
+}
+
+fn decode_list<'a>(decoder: &mut SliceDecoder<'a>) -> Result<TermView<'a>, DecodeError> {
+    let count = decoder.read_len_u32()?;
+    let elements = decode_all(decoder, count)?;
+    match decode(decoder)? {
+        TermView::List(last) if last.is_empty() => Ok(TermView::List(elements)),
+        last => Ok(TermView::ImproperList(elements, Box::new(last))),
+    }
+}
+
+fn decode_map<'a>(decoder: &mut SliceDecoder<'a>) -> Result<TermView<'a>, DecodeError> {
+    let count = decoder.read_len_u32()?;
+    // Every entry takes at least two bytes.
+    let mut entries = Vec::with_capacity(count.min(decoder.remaining() / 2));
+    for _ in 0..count {
+        let k = decode(decoder)?;
+        let v = decode(decoder)?;
+        entries.push((k, v));
+    }
+    Ok(TermView::Map(entries))
+}
+
+/// Decodes the terms which views do not borrow from the input.
+fn decode_owned<'a>(decoder: &mut SliceDecoder<'a>, tag: u8) -> Result<TermView<'a>, DecodeError> {
+    match decoder.decode_term_with_tag(tag)? {
+        Term::FixInteger(x) => Ok(TermView::FixInteger(x)),
+        Term::BigInteger(x) => Ok(TermView::BigInteger(x)),
+        Term::Float(x) => Ok(TermView::Float(x)),
+        Term::Pid(x) => Ok(TermView::Pid(x)),
+        Term::Port(x) => Ok(TermView::Port(x)),
+        Term::Reference(x) => Ok(TermView::Reference(x)),
+        Term::ExternalFun(x) => Ok(TermView::ExternalFun(x)),
+        Term::InternalFun(x) => Ok(TermView::InternalFun(x)),
+        Term::BitBinary(x) => Ok(TermView::BitBinary(x)),
+        _ => unreachable!("tag {} is decoded as a view", tag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn view_agrees_with_owned_decoding(term in any::<Term>()) {
+            let bytes = encode_to_vec(&term).unwrap();
+            prop_assert_eq!(decode_view(&bytes).unwrap().to_owned(), Term::decode(&bytes[..]).unwrap());
+        }
+    }
+
+    #[test]
+    fn atoms_and_binaries_are_borrowed() {
+        // [foo, <<"bar">>, "baz"]
+        let bytes = encode_to_vec(&Term::from(List::from(vec![
+            Term::from(Atom::from("foo")),
+            Term::from(Binary::from(&b"bar"[..])),
+            Term::from(ByteList::from("baz")),
+        ])))
+        .unwrap();
+        let range = bytes.as_ptr_range();
+        let elements = match decode_view(&bytes).unwrap() {
+            TermView::List(elements) => elements,
+            other => panic!("unexpected view: {:?}", other),
+        };
+        match &elements[..] {
+            [TermView::Atom(Cow::Borrowed(atom)), TermView::Binary(binary), TermView::ByteList(chars)] =>
+            {
+                assert_eq!((*atom, *binary, *chars), ("foo", &b"bar"[..], &b"baz"[..]));
+                for slice in [atom.as_bytes(), binary, chars] {
+                    assert!(range.contains(&slice.as_ptr()));
+                }
+            }
+            other => panic!("unexpected elements: {:?}", other),
+        }
+
+        // A Latin-1 atom with non-ASCII characters has to be converted.
+        let view = decode_view(&[131, 100, 0, 2, 104, 0xE9]).unwrap();
+        assert_eq!(view, TermView::Atom(Cow::Owned(String::from("h\u{e9}"))));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(matches!(
+            decode_view(&[131, 80, 0, 0, 0, 1, 120, 156]),
+            Err(DecodeError::CompressedView)
+        ));
+        assert!(matches!(
+            decode_view(&[131, 109, 0, 0, 0, 3, 98]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            decode_view(&[131, 106, 106]),
+            Err(DecodeError::TrailingBytes { count: 1 })
+        ));
+        assert!(matches!(
+            decode_view(&[131, 116, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Err(DecodeError::UnexpectedEof)
+        ));
+    }
+}
@@ -0,0 +1,690 @@
+//! Push-style encoding of a term without building it.
+//!
+//! [`TermWriter`] (and [`AsyncTermWriter`] with the `tokio-async` feature) encodes a
+//! term from a sequence of calls such as [`begin_tuple`](TermWriter::begin_tuple),
+//! [`atom`](TermWriter::atom) and [`end_tuple`](TermWriter::end_tuple), so that large
+//! terms can be written without holding them in memory.
+//!
+//! Containers declare their length up front and the writer checks that exactly that many
+//! elements are written before they are closed. The exception is
+//! [`begin_list_unknown_len`](TermWriter::begin_list_unknown_len), whose elements are
+//! buffered until the list is closed, since the length precedes the elements.
+//!
+//! # Examples
+//!
+//! ```
+//! use eetf::writer::TermWriter;
+//! use eetf::Term;
+//!
+//! // [{row, 0}, {row, 1}]
+//! let mut writer = TermWriter::new(Vec::new());
+//! writer.begin_list(2).unwrap();
+//! for i in 0..2 {
+//!     writer.begin_tuple(2).unwrap();
+//!     writer.atom("row").unwrap();
+//!     writer.int(i).unwrap();
+//!     writer.end_tuple().unwrap();
+//! }
+//! writer.end_list().unwrap();
+//! let bytes = writer.finish().unwrap();
+//!
+//! assert_eq!(Term::decode(&bytes[..]).unwrap().to_string(), "[{'row',0},{'row',1}]");
+//! ```
+use super::*;
+use crate::codec::Encoder;
+use crate::codec_common::*;
+use crate::dist::DistFlags;
+
+/// The pending output is written once it reaches this size.
+const FLUSH_THRESHOLD: usize = 8 * 1024;
+
+/// Errors which can occur when writing a term.
+///
+/// The output is unusable after an error.
+#[derive(Debug, thiserror::Error)]
+pub enum TermWriterError {
+    #[error("the {container} was declared with {declared} elements, but {written} were written")]
+    ArityMismatch {
+        container: &'static str,
+        declared: usize,
+        written: usize,
+    },
+
+    #[error("a {container} cannot have {len} elements")]
+    TooLarge { container: &'static str, len: usize },
+
+    #[error("cannot close a {container} while {open}")]
+    UnexpectedEnd {
+        container: &'static str,
+        open: &'static str,
+    },
+
+    #[error("{value} is not a finite float")]
+    NonFiniteFloat { value: f64 },
+
+    #[error("the term is already complete")]
+    Complete,
+
+    #[error("the term is incomplete")]
+    Incomplete,
+
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+impl From<io::Error> for TermWriterError {
+    fn from(e: io::Error) -> Self {
+        TermWriterError::Encode(EncodeError::Io(e))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Tuple,
+    List,
+    UnknownLenList,
+    Map,
+}
+impl Container {
+    fn name(self) -> &'static str {
+        match self {
+            Container::Tuple => "tuple",
+            Container::List | Container::UnknownLenList => "list",
+            Container::Map => "map",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    container: Container,
+    /// Number of elements (keys and values for maps), or `usize::MAX` for unknown lengths.
+    declared: usize,
+    written: usize,
+}
+
+/// The state shared by the sync and async writers.
+#[derive(Debug)]
+struct State {
+    options: EncoderOptions,
+    frames: Vec<Frame>,
+    /// Bytes which are ready to be written.
+    out: Vec<u8>,
+    /// Elements of the open lists of unknown length, innermost last.
+    buffers: Vec<Vec<u8>>,
+    complete: bool,
+}
+impl State {
+    fn new(options: EncoderOptions) -> Self {
+        State {
+            options,
+            frames: Vec::new(),
+            out: vec![VERSION],
+            buffers: Vec::new(),
+            complete: false,
+        }
+    }
+
+    fn sink(&mut self) -> &mut Vec<u8> {
+        self.buffers.last_mut().unwrap_or(&mut self.out)
+    }
+
+    /// Accounts for a value about to be written.
+    fn value(&mut self) -> Result<(), TermWriterError> {
+        if self.complete {
+            return Err(TermWriterError::Complete);
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            if frame.written == frame.declared {
+                return Err(TermWriterError::ArityMismatch {
+                    container: frame.container.name(),
+                    declared: frame.declared,
+                    written: frame.written + 1,
+                });
+            }
+            frame.written += 1;
+        }
+        Ok(())
+    }
+
+    /// Marks the term as complete if the value just written is the top-level one.
+    fn value_done(&mut self) {
+        if self.frames.is_empty() {
+            self.complete = true;
+        }
+    }
+
+    fn encode(&mut self, term: &Term) -> Result<(), TermWriterError> {
+        self.value()?;
+        let options = self.options;
+        Encoder::with_options(self.sink(), options).encode_term(term)?;
+        self.value_done();
+        Ok(())
+    }
+
+    fn atom(&mut self, name: &str) -> Result<(), TermWriterError> {
+        self.encode(&Term::from(Atom::from(name)))
+    }
+
+    fn int(&mut self, value: i64) -> Result<(), TermWriterError> {
+        match i32::try_from(value) {
+            Ok(value) => self.encode(&Term::from(FixInteger::from(value))),
+            Err(_) => self.encode(&Term::from(BigInteger::from(value))),
+        }
+    }
+
+    fn float(&mut self, value: f64) -> Result<(), TermWriterError> {
+        let float =
+            Float::try_from(value).map_err(|_| TermWriterError::NonFiniteFloat { value })?;
+        self.encode(&Term::from(float))
+    }
+
+    fn binary(&mut self, bytes: &[u8]) -> Result<(), TermWriterError> {
+        if u32::try_from(bytes.len()).is_err() {
+            return Err(TermWriterError::TooLarge {
+                container: "binary",
+                len: bytes.len(),
+            });
+        }
+        self.value()?;
+        let sink = self.sink();
+        sink.push(BINARY_EXT);
+        sink.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        sink.extend_from_slice(bytes);
+        self.value_done();
+        Ok(())
+    }
+
+    fn begin(&mut self, container: Container, len: usize) -> Result<(), TermWriterError> {
+        let declared = if container == Container::Map {
+            len.checked_mul(2)
+        } else {
+            Some(len)
+        };
+        let declared = match declared {
+            Some(declared) if u32::try_from(len).is_ok() => declared,
+            _ => {
+                return Err(TermWriterError::TooLarge {
+                    container: container.name(),
+                    len,
+                })
+            }
+        };
+        if container == Container::Map && !self.options.map_tag {
+            return Err(EncodeError::UnsupportedByPeer {
+                value: Term::from(Map::from(HashMap::<Term, Term>::new())),
+                flag: DistFlags::MAP_TAG,
+            }
+            .into());
+        }
+        self.value()?;
+        let sink = self.sink();
+        match container {
+            Container::Tuple if len < 0x100 => {
+                sink.extend_from_slice(&[SMALL_TUPLE_EXT, len as u8])
+            }
+            Container::Tuple => {
+                sink.push(LARGE_TUPLE_EXT);
+                sink.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            Container::List if len == 0 => {}
+            Container::List => {
+                sink.push(LIST_EXT);
+                sink.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            Container::Map => {
+                sink.push(MAP_EXT);
+                sink.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            Container::UnknownLenList => unreachable!(),
+        }
+        self.frames.push(Frame {
+            container,
+            declared,
+            written: 0,
+        });
+        Ok(())
+    }
+
+    fn begin_list_unknown_len(&mut self) -> Result<(), TermWriterError> {
+        self.value()?;
+        self.buffers.push(Vec::new());
+        self.frames.push(Frame {
+            container: Container::UnknownLenList,
+            declared: usize::MAX,
+            written: 0,
+        });
+        Ok(())
+    }
+
+    fn end(&mut self, containers: &[Container]) -> Result<(), TermWriterError> {
+        let (container, declared, written) = match self.frames.last() {
+            Some(frame) if containers.contains(&frame.container) => {
+                (frame.container, frame.declared, frame.written)
+            }
+            other => {
+                return Err(TermWriterError::UnexpectedEnd {
+                    container: containers[0].name(),
+                    open: match other {
+                        Some(frame) => match frame.container {
+                            Container::Tuple => "a tuple is open",
+                            Container::List | Container::UnknownLenList => "a list is open",
+                            Container::Map => "a map is open",
+                        },
+                        None => "nothing is open",
+                    },
+                })
+            }
+        };
+        match container {
+            Container::UnknownLenList => {
+                let elements = self.buffers.pop().expect("unreachable");
+                if u32::try_from(written).is_err() {
+                    return Err(TermWriterError::TooLarge {
+                        container: "list",
+                        len: written,
+                    });
+                }
+                let written = written as u32;
+                let sink = self.sink();
+                if written != 0 {
+                    sink.push(LIST_EXT);
+                    sink.extend_from_slice(&written.to_be_bytes());
+                    sink.extend_from_slice(&elements);
+                }
+                sink.push(NIL_EXT);
+            }
+            _ if written != declared => {
+                return Err(TermWriterError::ArityMismatch {
+                    container: container.name(),
+                    declared,
+                    written,
+                })
+            }
+            Container::List => self.sink().push(NIL_EXT),
+            Container::Tuple | Container::Map => {}
+        }
+        self.frames.pop();
+        self.value_done();
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), TermWriterError> {
+        if self.complete {
+            Ok(())
+        } else {
+            Err(TermWriterError::Incomplete)
+        }
+    }
+}
+
+/// Writer encoding a term from a sequence of calls (see the [module documentation](self)).
+///
+/// The output is written in chunks, so `W` does not need to be buffered.
+#[derive(Debug)]
+pub struct TermWriter<W> {
+    writer: W,
+    state: State,
+}
+impl<W: io::Write> TermWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, EncoderOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: EncoderOptions) -> Self {
+        TermWriter {
+            writer,
+            state: State::new(options),
+        }
+    }
+
+    fn written(&mut self, result: Result<(), TermWriterError>) -> Result<(), TermWriterError> {
+        result?;
+        if self.state.out.len() >= FLUSH_THRESHOLD {
+            self.writer.write_all(&self.state.out)?;
+            self.state.out.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes a term as a value.
+    pub fn term(&mut self, term: &Term) -> Result<(), TermWriterError> {
+        let result = self.state.encode(term);
+        self.written(result)
+    }
+
+    /// Writes an atom.
+    pub fn atom(&mut self, name: &str) -> Result<(), TermWriterError> {
+        let result = self.state.atom(name);
+        self.written(result)
+    }
+
+    /// Writes an integer (as a [`BigInteger`] if it does not fit in an `i32`).
+    pub fn int(&mut self, value: i64) -> Result<(), TermWriterError> {
+        let result = self.state.int(value);
+        self.written(result)
+    }
+
+    /// Writes a float, which must be finite.
+    pub fn float(&mut self, value: f64) -> Result<(), TermWriterError> {
+        let result = self.state.float(value);
+        self.written(result)
+    }
+
+    /// Writes a binary.
+    pub fn binary(&mut self, bytes: &[u8]) -> Result<(), TermWriterError> {
+        let result = self.state.binary(bytes);
+        self.written(result)
+    }
+
+    /// Opens a tuple of `arity` elements.
+    pub fn begin_tuple(&mut self, arity: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::Tuple, arity);
+        self.written(result)
+    }
+
+    /// Closes the innermost tuple.
+    pub fn end_tuple(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.end(&[Container::Tuple]);
+        self.written(result)
+    }
+
+    /// Opens a proper list of `len` elements.
+    pub fn begin_list(&mut self, len: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::List, len);
+        self.written(result)
+    }
+
+    /// Opens a proper list whose elements are buffered in memory until it is closed.
+    pub fn begin_list_unknown_len(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.begin_list_unknown_len();
+        self.written(result)
+    }
+
+    /// Closes the innermost list.
+    pub fn end_list(&mut self) -> Result<(), TermWriterError> {
+        let result = self
+            .state
+            .end(&[Container::List, Container::UnknownLenList]);
+        self.written(result)
+    }
+
+    /// Opens a map of `len` entries, whose keys and values are written alternately.
+    pub fn begin_map(&mut self, len: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::Map, len);
+        self.written(result)
+    }
+
+    /// Closes the innermost map.
+    pub fn end_map(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.end(&[Container::Map]);
+        self.written(result)
+    }
+
+    /// Writes the rest of the output and returns the writer.
+    ///
+    /// Fails if the term is incomplete.
+    pub fn finish(mut self) -> Result<W, TermWriterError> {
+        self.state.finish()?;
+        self.writer.write_all(&self.state.out)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Async version of [`TermWriter`].
+#[cfg(feature = "tokio-async")]
+#[derive(Debug)]
+pub struct AsyncTermWriter<W> {
+    writer: W,
+    state: State,
+}
+#[cfg(feature = "tokio-async")]
+impl<W: tokio::io::AsyncWrite + std::marker::Unpin> AsyncTermWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_options(writer, EncoderOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: EncoderOptions) -> Self {
+        AsyncTermWriter {
+            writer,
+            state: State::new(options),
+        }
+    }
+
+    async fn written(
+        &mut self,
+        result: Result<(), TermWriterError>,
+    ) -> Result<(), TermWriterError> {
+        use tokio::io::AsyncWriteExt;
+
+        result?;
+        if self.state.out.len() >= FLUSH_THRESHOLD {
+            self.writer.write_all(&self.state.out).await?;
+            self.state.out.clear();
+        }
+        Ok(())
+    }
+
+    /// See [`TermWriter::term`].
+    pub async fn term(&mut self, term: &Term) -> Result<(), TermWriterError> {
+        let result = self.state.encode(term);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::atom`].
+    pub async fn atom(&mut self, name: &str) -> Result<(), TermWriterError> {
+        let result = self.state.atom(name);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::int`].
+    pub async fn int(&mut self, value: i64) -> Result<(), TermWriterError> {
+        let result = self.state.int(value);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::float`].
+    pub async fn float(&mut self, value: f64) -> Result<(), TermWriterError> {
+        let result = self.state.float(value);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::binary`].
+    pub async fn binary(&mut self, bytes: &[u8]) -> Result<(), TermWriterError> {
+        let result = self.state.binary(bytes);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::begin_tuple`].
+    pub async fn begin_tuple(&mut self, arity: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::Tuple, arity);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::end_tuple`].
+    pub async fn end_tuple(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.end(&[Container::Tuple]);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::begin_list`].
+    pub async fn begin_list(&mut self, len: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::List, len);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::begin_list_unknown_len`].
+    pub async fn begin_list_unknown_len(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.begin_list_unknown_len();
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::end_list`].
+    pub async fn end_list(&mut self) -> Result<(), TermWriterError> {
+        let result = self
+            .state
+            .end(&[Container::List, Container::UnknownLenList]);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::begin_map`].
+    pub async fn begin_map(&mut self, len: usize) -> Result<(), TermWriterError> {
+        let result = self.state.begin(Container::Map, len);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::end_map`].
+    pub async fn end_map(&mut self) -> Result<(), TermWriterError> {
+        let result = self.state.end(&[Container::Map]);
+        self.written(result).await
+    }
+
+    /// See [`TermWriter::finish`].
+    pub async fn finish(mut self) -> Result<W, TermWriterError> {
+        use tokio::io::AsyncWriteExt;
+
+        self.state.finish()?;
+        self.writer.write_all(&self.state.out).await?;
+        self.writer.flush().await?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_terms_are_written() {
+        const ROWS: usize = 100_000;
+
+        let mut writer = TermWriter::new(Vec::new());
+        writer.begin_tuple(3).unwrap();
+        writer.atom("result").unwrap();
+        writer.begin_list(ROWS).unwrap();
+        for i in 0..ROWS {
+            writer.begin_tuple(4).unwrap();
+            writer.atom("row").unwrap();
+            writer.int(i as i64 * 100_000).unwrap();
+            writer.binary(format!("name{}", i).as_bytes()).unwrap();
+            writer.float(i as f64 / 2.0).unwrap();
+            writer.end_tuple().unwrap();
+        }
+        writer.end_list().unwrap();
+        writer.begin_map(1).unwrap();
+        writer.atom("empty").unwrap();
+        writer.begin_list_unknown_len().unwrap();
+        writer.end_list().unwrap();
+        writer.end_map().unwrap();
+        writer.end_tuple().unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let rows = (0..ROWS).map(|i| {
+            Term::from(Tuple::from(vec![
+                Term::from(Atom::from("row")),
+                Term::from(i as i64 * 100_000),
+                Term::from(Binary::from(format!("name{}", i).into_bytes())),
+                Term::from(Float::try_from(i as f64 / 2.0).unwrap()),
+            ]))
+        });
+        let expected = Term::from(Tuple::from(vec![
+            Term::from(Atom::from("result")),
+            Term::from(List::from(rows.collect::<Vec<_>>())),
+            Term::from(Map::from([(
+                Term::from(Atom::from("empty")),
+                Term::from(List::nil()),
+            )])),
+        ]));
+        assert_eq!(Term::decode(&bytes[..]).unwrap(), expected);
+    }
+
+    #[test]
+    fn lists_of_unknown_length_are_buffered() {
+        let mut writer = TermWriter::new(Vec::new());
+        writer.begin_list_unknown_len().unwrap();
+        for i in 0..3 {
+            writer.begin_list_unknown_len().unwrap();
+            for _ in 0..i {
+                writer.atom("a").unwrap();
+            }
+            writer.end_list().unwrap();
+        }
+        writer.begin_list(0).unwrap();
+        writer.end_list().unwrap();
+        writer.end_list().unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(
+            Term::decode(&bytes[..]).unwrap().to_string(),
+            "[[],['a'],['a','a'],[]]"
+        );
+    }
+
+    #[test]
+    fn arity_mismatches_are_rejected() {
+        let mut writer = TermWriter::new(Vec::new());
+        writer.begin_tuple(2).unwrap();
+        writer.atom("a").unwrap();
+        assert_eq!(
+            writer.end_tuple().unwrap_err().to_string(),
+            "the tuple was declared with 2 elements, but 1 were written"
+        );
+
+        let mut writer = TermWriter::new(Vec::new());
+        writer.begin_map(1).unwrap();
+        writer.atom("k").unwrap();
+        writer.atom("v").unwrap();
+        assert!(matches!(
+            writer.atom("k"),
+            Err(TermWriterError::ArityMismatch {
+                container: "map",
+                declared: 2,
+                written: 3
+            })
+        ));
+
+        let mut writer = TermWriter::new(Vec::new());
+        writer.begin_list(1).unwrap();
+        assert_eq!(
+            writer.end_tuple().unwrap_err().to_string(),
+            "cannot close a tuple while a list is open"
+        );
+        writer.int(1).unwrap();
+        writer.end_list().unwrap();
+        assert!(matches!(writer.int(2), Err(TermWriterError::Complete)));
+
+        let mut writer = TermWriter::new(Vec::new());
+        assert!(matches!(
+            writer.end_list(),
+            Err(TermWriterError::UnexpectedEnd { .. })
+        ));
+        writer.begin_tuple(0).unwrap();
+        let writer = TermWriter::new(Vec::new());
+        assert!(matches!(writer.finish(), Err(TermWriterError::Incomplete)));
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[test]
+    fn async_writer_works() {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let mut writer = AsyncTermWriter::new(Vec::new());
+                writer.begin_tuple(2).await.unwrap();
+                writer.atom("ok").await.unwrap();
+                writer.begin_list_unknown_len().await.unwrap();
+                for i in 0..10_000 {
+                    writer.int(i).await.unwrap();
+                }
+                writer.end_list().await.unwrap();
+                writer.end_tuple().await.unwrap();
+                let bytes = writer.finish().await.unwrap();
+
+                let expected = Term::from(Tuple::from(vec![
+                    Term::from(Atom::from("ok")),
+                    Term::from(List::from((0..10_000).map(Term::from).collect::<Vec<_>>())),
+                ]));
+                assert_eq!(Term::decode(&bytes[..]).unwrap(), expected);
+            });
+    }
+}
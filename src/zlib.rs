@@ -0,0 +1,261 @@
+//! Zlib streams of compressed terms, which `libflate` inflates and deflates unless the `flate2`
+//! feature swaps it for `flate2`. The async decoders inflate with `flate2` either way, as its
+//! inflater can be fed the stream as it arrives.
+use crate::DecodeError;
+use std::io;
+
+/// Backend inflating and deflating zlib streams.
+pub(crate) trait Zlib {
+    type Decoder<R: io::Read>: io::Read;
+    type SliceDecoder<'a>: io::Read;
+    type Encoder<W: io::Write>: io::Write;
+
+    /// Makes a decoder of the stream at the start of `reader`, which reads no byte after it.
+    fn decoder<R: io::Read>(reader: R) -> io::Result<Self::Decoder<R>>;
+
+    /// Makes a decoder of the stream in `bytes`, which ends with it.
+    fn slice_decoder(bytes: &[u8]) -> io::Result<Self::SliceDecoder<'_>>;
+
+    fn encoder<W: io::Write>(writer: W) -> io::Result<Self::Encoder<W>>;
+
+    /// Writes the end of the stream.
+    fn finish<W: io::Write>(encoder: Self::Encoder<W>) -> io::Result<W>;
+}
+
+#[cfg(not(feature = "flate2"))]
+pub(crate) type Backend = Libflate;
+#[cfg(feature = "flate2")]
+pub(crate) type Backend = Flate2;
+
+/// Decoder of the backend, which can be named without bounding `R` (e.g., in fields).
+#[cfg(not(feature = "flate2"))]
+pub(crate) type Decoder<R> = LibflateDecoder<R>;
+#[cfg(feature = "flate2")]
+pub(crate) type Decoder<R> = Flate2Decoder<R>;
+
+#[cfg(not(feature = "flate2"))]
+pub(crate) struct Libflate;
+#[cfg(not(feature = "flate2"))]
+type LibflateDecoder<R> = libflate::zlib::Decoder<R>;
+#[cfg(not(feature = "flate2"))]
+impl Zlib for Libflate {
+    type Decoder<R: io::Read> = LibflateDecoder<R>;
+    type SliceDecoder<'a> = libflate::zlib::Decoder<&'a [u8]>;
+    type Encoder<W: io::Write> = libflate::zlib::Encoder<W>;
+
+    fn decoder<R: io::Read>(reader: R) -> io::Result<Self::Decoder<R>> {
+        libflate::zlib::Decoder::new(reader)
+    }
+
+    fn slice_decoder(bytes: &[u8]) -> io::Result<Self::SliceDecoder<'_>> {
+        libflate::zlib::Decoder::new(bytes)
+    }
+
+    fn encoder<W: io::Write>(writer: W) -> io::Result<Self::Encoder<W>> {
+        libflate::zlib::Encoder::new(writer)
+    }
+
+    fn finish<W: io::Write>(encoder: Self::Encoder<W>) -> io::Result<W> {
+        encoder.finish().into_result()
+    }
+}
+
+#[cfg(feature = "flate2")]
+pub(crate) struct Flate2;
+#[cfg(feature = "flate2")]
+type Flate2Decoder<R> = flate2::bufread::ZlibDecoder<ByteByByte<R>>;
+#[cfg(feature = "flate2")]
+impl Zlib for Flate2 {
+    type Decoder<R: io::Read> = Flate2Decoder<R>;
+    type SliceDecoder<'a> = flate2::bufread::ZlibDecoder<&'a [u8]>;
+    type Encoder<W: io::Write> = flate2::write::ZlibEncoder<W>;
+
+    fn decoder<R: io::Read>(reader: R) -> io::Result<Self::Decoder<R>> {
+        Ok(flate2::bufread::ZlibDecoder::new(ByteByByte {
+            inner: reader,
+            byte: None,
+        }))
+    }
+
+    fn slice_decoder(bytes: &[u8]) -> io::Result<Self::SliceDecoder<'_>> {
+        Ok(flate2::bufread::ZlibDecoder::new(bytes))
+    }
+
+    fn encoder<W: io::Write>(writer: W) -> io::Result<Self::Encoder<W>> {
+        Ok(flate2::write::ZlibEncoder::new(
+            writer,
+            flate2::Compression::default(),
+        ))
+    }
+
+    fn finish<W: io::Write>(encoder: Self::Encoder<W>) -> io::Result<W> {
+        encoder.finish()
+    }
+}
+
+/// Buffered reader whose buffer holds a single byte, so that a `flate2` decoder does not take
+/// the bytes after the end of its stream from the reader.
+#[cfg(feature = "flate2")]
+pub(crate) struct ByteByByte<R> {
+    inner: R,
+    byte: Option<u8>,
+}
+#[cfg(feature = "flate2")]
+impl<R: io::Read> io::Read for ByteByByte<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (self.byte.take(), buf.first_mut()) {
+            (Some(byte), Some(first)) => {
+                *first = byte;
+                Ok(1)
+            }
+            (byte, _) => {
+                self.byte = byte;
+                self.inner.read(buf)
+            }
+        }
+    }
+}
+#[cfg(feature = "flate2")]
+impl<R: io::Read> io::BufRead for ByteByByte<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.byte.is_none() {
+            let mut byte = [0];
+            loop {
+                match self.inner.read(&mut byte) {
+                    Ok(0) => return Ok(&[]),
+                    Ok(_) => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            self.byte = Some(byte[0]);
+        }
+        Ok(self.byte.as_slice())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 {
+            self.byte = None;
+        }
+    }
+}
+
+/// Reader of an inflated stream, which fails once the stream turns out to be longer than `max`
+/// bytes (i.e., [`DecoderOptions::max_uncompressed_size`](crate::DecoderOptions)).
+pub(crate) struct Limited<R> {
+    inner: R,
+    max: Option<usize>,
+    len: usize,
+    exceeded: bool,
+}
+impl<R: io::Read> Limited<R> {
+    pub(crate) fn new(inner: R, max: Option<usize>) -> Self {
+        Limited {
+            inner,
+            max,
+            len: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Replaces the error of reading from the stream if it is the limit being exceeded.
+    pub(crate) fn check<T>(&self, result: Result<T, DecodeError>) -> Result<T, DecodeError> {
+        match self.max {
+            Some(max) if self.exceeded => Err(limit_exceeded(max)),
+            _ => result,
+        }
+    }
+}
+impl<R: io::Read> io::Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(max) = self.max else {
+            return self.inner.read(buf);
+        };
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.len == max {
+            // Only a byte beyond the limit tells the end of the stream from a longer stream.
+            if self.inner.read(&mut [0])? == 0 {
+                return Ok(0);
+            }
+            self.exceeded = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the compressed term exceeds the maximum uncompressed size",
+            ));
+        }
+        let len = buf.len().min(max - self.len);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.len += n;
+        Ok(n)
+    }
+}
+
+/// Number of bytes which [`inflate_async`] reads and inflates at once.
+#[cfg(feature = "tokio-async")]
+const INFLATE_CHUNK_LEN: usize = 8 * 1024;
+
+/// Inflates the zlib stream at the start of `reader`, and fails once the stream turns out to be
+/// longer than `max` bytes.
+///
+/// The stream is read by blocks, and the bytes of the last block which follow the stream are
+/// given back to `reader`.
+#[cfg(feature = "tokio-async")]
+pub(crate) async fn inflate_async<R>(
+    reader: &mut crate::progress::Tracked<R>,
+    max: Option<usize>,
+) -> Result<Vec<u8>, DecodeError>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+
+    let mut inflater = flate2::Decompress::new(true);
+    let mut block = vec![0; INFLATE_CHUNK_LEN];
+    let mut chunk = vec![0; INFLATE_CHUNK_LEN];
+    let mut body = Vec::new();
+    loop {
+        let read = reader.read(&mut block).await?;
+        if read == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let mut input = &block[..read];
+        // A block may inflate to more than a chunk, which is taken chunk by chunk.
+        loop {
+            let (total_in, total_out) = (inflater.total_in(), inflater.total_out());
+            let status = inflater
+                .decompress(input, &mut chunk, flate2::FlushDecompress::None)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            input = &input[(inflater.total_in() - total_in) as usize..];
+            let len = (inflater.total_out() - total_out) as usize;
+            if let Some(max) = max.filter(|&max| body.len() + len > max) {
+                return Err(limit_exceeded(max));
+            }
+            body.extend_from_slice(&chunk[..len]);
+            if status == flate2::Status::StreamEnd {
+                reader.unread(input);
+                return Ok(body);
+            }
+            if input.is_empty() && len < chunk.len() {
+                break;
+            }
+        }
+    }
+}
+
+/// Fails if the uncompressed size which a compressed term declares is over the limit, so that
+/// the term is rejected before it is inflated.
+pub(crate) fn check_declared_size(max: Option<usize>, size: usize) -> Result<(), DecodeError> {
+    match max {
+        Some(max) if size > max => Err(limit_exceeded(max)),
+        _ => Ok(()),
+    }
+}
+
+fn limit_exceeded(max: usize) -> DecodeError {
+    DecodeError::LimitExceeded {
+        limit: "max_uncompressed_size",
+        max,
+    }
+}
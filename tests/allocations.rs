@@ -0,0 +1,248 @@
+//! Counts the allocations made by the decoder and the encoder.
+//!
+//! This is a separate test binary because it replaces the global allocator.
+use eetf::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::Cursor;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Per thread, so that the tests running concurrently do not count each other's allocations.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn decoding_allocates_the_owned_storage_only() {
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("call")),
+        Term::from(Atom::from("café")),
+        Term::from(Binary::from(&b"hello"[..])),
+        Term::from(ByteList::from(b"abc".to_vec())),
+        Term::from(Map::from([(Term::from(Atom::from("id")), Term::from(42))])),
+        Term::from(BigInteger::from(u64::MAX)),
+    ]));
+    // Latin-1 atoms (`ATOM_EXT`) are converted to UTF-8.
+    let options = EncoderOptions {
+        utf8_atoms: false,
+        ..EncoderOptions::default()
+    };
+    let mut bytes = Vec::new();
+    Encoder::with_options(&mut bytes, options)
+        .encode(&term)
+        .unwrap();
+    let message = [bytes.clone(), bytes].concat();
+
+    // The tuple, the three atoms, the binary, the byte list, the map and the digits of the integer.
+    let expected = 8;
+
+    let mut decoder = Decoder::with_capacity(Cursor::new(&message), 64);
+    let (decoded, n) = count_allocations(|| decoder.decode_next().unwrap());
+    assert_eq!(decoded, term);
+    assert_eq!(n, expected);
+
+    // The buffers of the decoder are reused.
+    let (decoded, n) = count_allocations(|| decoder.decode_next().unwrap());
+    assert_eq!(decoded, term);
+    assert_eq!(n, expected);
+
+    // A new decoder also grows its scratch buffer (for "call", then for the UTF-8 of "café").
+    let (decoded, n) = count_allocations(|| Term::decode(Cursor::new(&message)).unwrap());
+    assert_eq!(decoded, term);
+    assert!(n <= expected + 2, "{} allocations", n);
+}
+
+#[test]
+fn maps_are_decoded_into_hash_maps_without_intermediate_terms() {
+    use std::collections::HashMap;
+
+    let map = Map::from(
+        (0..1000)
+            .map(|i| {
+                let key = Binary::from(format!("key{}", i).into_bytes());
+                (Term::from(key), Term::from(i))
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+    let mut bytes = Vec::new();
+    Term::from(map).encode(&mut bytes).unwrap();
+
+    let (two_step, n_two_step) = count_allocations(|| {
+        let term = Term::decode(Cursor::new(&bytes)).unwrap();
+        HashMap::<String, i64>::from_term(&term).unwrap()
+    });
+    let (direct, n_direct) = count_allocations(|| {
+        Decoder::new(Cursor::new(&bytes))
+            .decode_as::<HashMap<String, i64>>()
+            .unwrap()
+    });
+    assert_eq!(direct, two_step);
+    assert!(n_direct < n_two_step, "{} >= {}", n_direct, n_two_step);
+}
+
+#[test]
+fn funs_are_encoded_without_buffering_their_free_variables() {
+    let fun = Term::from(InternalFun::New {
+        module: Atom::from("a"),
+        arity: 1,
+        pid: Pid::new("a@localhost", 1, 0, 1),
+        free_vars: vec![Term::from(Binary::from(vec![7; 10 << 20]))],
+        index: 0,
+        uniq: [0; 16],
+        old_index: 0,
+        old_uniq: 0,
+    });
+    let mut buf = Vec::with_capacity(11 << 20);
+    let ((), n) = count_allocations(|| fun.encode(&mut buf).unwrap());
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn encode_into_does_not_allocate() {
+    let big = |value: num::BigInt| Term::from(BigInteger { value });
+    let terms = [
+        Term::from(Atom::from("foo")),
+        Term::from(-1000),
+        big(num::BigInt::from(u64::MAX) * 3),
+        big(-num::BigInt::from(u64::MAX)),
+        big(num::BigInt::from(0)),
+        // LARGE_BIG_EXT
+        big(num::BigInt::from(1) << 4000),
+        Term::try_from(1.5).unwrap(),
+        Term::from(Binary::from(vec![0; 1000])),
+        Term::from(Tuple::from(vec![
+            Term::from(Pid::new("foo@bar", 1, 2, 3)),
+            Term::from(List::from(vec![Term::from(1), Term::from(Atom::from("a"))])),
+            Term::from(Map::from([(
+                Term::from(1),
+                Term::from(ByteList::from("abc")),
+            )])),
+        ])),
+    ];
+    let mut buf = vec![0; 4096];
+    for term in &terms {
+        let needed = term.encoded_size().unwrap();
+        let (len, n) = count_allocations(|| encode_into(term, &mut buf[..needed]).unwrap());
+        assert_eq!((len, n), (needed, 0), "{}", term);
+        assert_eq!(Term::decode(Cursor::new(&buf[..len])).unwrap(), *term);
+
+        let (result, n) = count_allocations(|| encode_into(term, &mut buf[..needed - 1]));
+        assert!(
+            matches!(result, Err(EncodeError::BufferTooSmall { needed: m, .. }) if m == needed),
+            "{:?}",
+            result
+        );
+        assert_eq!(n, 0, "{}", term);
+    }
+}
+
+#[test]
+fn encode_parts_does_not_copy_the_payload() {
+    // One payload is encoded into the messages of many subscribers.
+    let payload = vec![7; 1 << 20];
+    let prefix = Term::from(Atom::from("data"));
+    let mut buf = Vec::with_capacity((1 << 20) + 64);
+    let ((), n) = count_allocations(|| {
+        for _ in 0..100 {
+            buf.clear();
+            Encoder::new(&mut buf)
+                .encode_parts(&prefix, &payload)
+                .unwrap();
+        }
+    });
+    assert_eq!(n, 0);
+    let message = Term::from(Tuple::from(vec![prefix, Term::binary_from(payload)]));
+    assert_eq!(buf, encode_to_vec(&message).unwrap());
+}
+
+#[test]
+fn lists_are_encoded_from_iterators_without_collecting_them() {
+    let mut sink = std::io::sink();
+    let ((), n) = count_allocations(|| {
+        Encoder::new(&mut sink)
+            .encode_list_from_iter(0..1_000_000)
+            .unwrap()
+    });
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn sequences_are_encoded_without_collecting_terms() {
+    let slice = (0..10_000).collect::<Vec<i64>>();
+    let deque = slice
+        .iter()
+        .copied()
+        .collect::<std::collections::VecDeque<_>>();
+    let mut expected = Vec::new();
+    Term::from(List::from(
+        slice.iter().map(|&i| Term::from(i)).collect::<Vec<_>>(),
+    ))
+    .encode(&mut expected)
+    .unwrap();
+
+    let mut buf = Vec::with_capacity(expected.len());
+    let ((), n) = count_allocations(|| {
+        Encoder::new(&mut buf)
+            .encode_sequence_as_list(&slice[..])
+            .unwrap()
+    });
+    assert_eq!(n, 0);
+    assert_eq!(buf, expected);
+
+    buf.clear();
+    let ((), n) = count_allocations(|| {
+        Encoder::new(&mut buf)
+            .encode_sequence_as_list(&deque)
+            .unwrap()
+    });
+    assert_eq!(n, 0);
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn static_atoms_allocate_once() {
+    static_atoms! {
+        NOPROC = "noproc";
+    }
+    let ((), n) = count_allocations(|| {
+        Atom::from_static("reply");
+        assert_eq!(*NOPROC, Atom::from_static("noproc"));
+    });
+    assert!(n > 0);
+    let ((), n) = count_allocations(|| {
+        for _ in 0..1000 {
+            let reply = Term::atom_static("reply");
+            assert!(matches!(reply, Term::Atom(ref atom) if atom.as_str() == "reply"));
+            assert_eq!(Term::from(&NOPROC), Term::atom_static("noproc"));
+        }
+    });
+    assert_eq!(n, 0);
+}
@@ -0,0 +1,113 @@
+//! Runs the `etfcat` binary.
+#![cfg(feature = "cli")]
+use assert_cmd::cargo::cargo_bin_cmd;
+use eetf::*;
+
+fn config() -> Term {
+    Term::from(Map::from([(
+        Term::from(Atom::from("http")),
+        Term::from(Map::from([(
+            Term::from(Atom::from("port")),
+            Term::from(8080),
+        )])),
+    )]))
+}
+
+fn input() -> Vec<u8> {
+    let mut bytes = encode_to_vec(&config()).unwrap();
+    bytes.extend(encode_to_vec(&Term::from(Atom::from("ok"))).unwrap());
+    bytes
+}
+
+#[test]
+fn prints_every_term() {
+    cargo_bin_cmd!("etfcat")
+        .write_stdin(input())
+        .assert()
+        .success()
+        .stdout("#{'http'=>#{'port'=>8080}}\n'ok'\n");
+
+    let binary = Term::from(Binary::from(vec![1; 40]));
+    let long = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(List::from(vec![binary.clone()])),
+    ]));
+    let expected = format!("{{\n    'ok',\n    [\n        {}\n    ]\n}}\n", binary);
+    cargo_bin_cmd!("etfcat")
+        .arg("--pretty")
+        .write_stdin(encode_to_vec(&long).unwrap())
+        .assert()
+        .success()
+        .stdout(expected);
+
+    cargo_bin_cmd!("etfcat")
+        .arg("--json")
+        .write_stdin(input())
+        .assert()
+        .success()
+        .stdout("{\"http\":{\"port\":8080}}\n\"ok\"\n");
+
+    cargo_bin_cmd!("etfcat")
+        .arg("--stats")
+        .write_stdin(input())
+        .assert()
+        .success()
+        .stdout(concat!(
+            "depth: 3, atoms: 2, largest binary: 0 bytes\n",
+            "depth: 1, atoms: 1, largest binary: 0 bytes\n"
+        ));
+}
+
+#[test]
+fn extracts_subterms() {
+    cargo_bin_cmd!("etfcat")
+        .args(["--path", "http.port"])
+        .write_stdin(encode_to_vec(&config()).unwrap())
+        .assert()
+        .success()
+        .stdout("8080\n");
+
+    cargo_bin_cmd!("etfcat")
+        .args(["--path", "http.host"])
+        .write_stdin(encode_to_vec(&config()).unwrap())
+        .assert()
+        .code(1)
+        .stderr("etfcat: -: path http.host is not found in the term at byte 0\n");
+}
+
+#[test]
+fn recompresses_terms() {
+    let output = cargo_bin_cmd!("etfcat")
+        .arg("--recompress")
+        .write_stdin(input())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout[..2], [131, 80]);
+
+    cargo_bin_cmd!("etfcat")
+        .arg("--decompress")
+        .write_stdin(output.stdout)
+        .assert()
+        .success()
+        .stdout(input());
+}
+
+#[test]
+fn distinguishes_decode_errors_from_io_errors() {
+    let mut truncated = input();
+    truncated.pop();
+    cargo_bin_cmd!("etfcat")
+        .write_stdin(truncated)
+        .assert()
+        .code(65)
+        .stdout("#{'http'=>#{'port'=>8080}}\n")
+        .stderr("etfcat: -: failed to decode the term at byte 30: I/O error\n");
+
+    let path = std::env::temp_dir().join(format!("eetf-etfcat-{}.etf", std::process::id()));
+    let output = cargo_bin_cmd!("etfcat").arg(&path).output().unwrap();
+    assert_eq!(output.status.code(), Some(74));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let prefix = format!("etfcat: failed to read {}: ", path.display());
+    assert!(stderr.starts_with(&prefix), "{}", stderr);
+}
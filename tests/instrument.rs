@@ -0,0 +1,204 @@
+//! Checks the `tracing` spans of the codecs.
+#![cfg(feature = "tracing")]
+use eetf::*;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Name and fields of a closed span, or of an event.
+type Record_ = (String, Vec<(String, String)>);
+
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        let value = format!("{:?}", value);
+        match self.0.iter_mut().find(|(name, _)| name == field.name()) {
+            Some(entry) => entry.1 = value,
+            None => self.0.push((field.name().to_owned(), value)),
+        }
+    }
+}
+
+/// Collects the closed spans and the events.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<Record_>>>);
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        ctx.span(id).unwrap().extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        values.record(span.extensions_mut().get_mut::<Fields>().unwrap());
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let name = event.metadata().name().to_owned();
+        self.0.lock().unwrap().push((name, fields.0));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).unwrap();
+        let fields = span.extensions_mut().remove::<Fields>().unwrap();
+        let name = span.name().to_owned();
+        self.0.lock().unwrap().push((name, fields.0));
+    }
+}
+
+/// Runs `f`, and returns the spans and the events it emitted.
+fn capture(f: impl FnOnce()) -> Vec<Record_> {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    let records = capture.0.lock().unwrap().clone();
+    records
+        .into_iter()
+        .map(|(name, fields)| {
+            // The elapsed time varies.
+            let fields = fields.into_iter().filter(|(k, _)| k != "elapsed_us");
+            (name, fields.collect())
+        })
+        .collect()
+}
+
+fn span(name: &str, fields: &[(&str, &str)]) -> Record_ {
+    let mut fields: Vec<_> = fields
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    fields.sort();
+    (name.to_owned(), fields)
+}
+
+fn sorted(records: Vec<Record_>) -> Vec<Record_> {
+    records
+        .into_iter()
+        .map(|(name, mut fields)| {
+            fields.sort();
+            (name, fields)
+        })
+        .collect()
+}
+
+fn field<'a>(record: &'a Record_, name: &str) -> &'a str {
+    let (_, value) = record.1.iter().find(|(k, _)| k == name).unwrap();
+    value
+}
+
+fn term() -> Term {
+    Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(Binary::from(vec![1, 2, 3])),
+    ]))
+}
+
+#[test]
+fn decoding_emits_a_span() {
+    let bytes = encode_to_vec(&term()).unwrap();
+    let records = capture(|| {
+        assert_eq!(Decoder::new(&bytes[..]).decode().unwrap(), term());
+        assert_eq!(decode_from_slice(&bytes).unwrap(), term());
+    });
+    let expected = span(
+        "eetf::decode",
+        &[
+            ("bytes", "16"),
+            ("compressed", "false"),
+            ("variant", "\"Tuple\""),
+        ],
+    );
+    assert_eq!(sorted(records), [expected.clone(), expected]);
+
+    let mut compressed = Vec::new();
+    term().encode_compressed(&mut compressed).unwrap();
+    let records = capture(|| {
+        Decoder::new(&compressed[..]).decode().unwrap();
+    });
+    assert_eq!(field(&records[0], "compressed"), "true");
+
+    let records = capture(|| {
+        assert!(Decoder::new(&bytes[..10]).decode().is_err());
+    });
+    assert_eq!(records.len(), 2);
+    assert_eq!(field(&records[0], "message"), "failed to decode a term");
+    assert_eq!(field(&records[0], "offset"), "10");
+    assert_eq!(field(&records[0], "error"), "I/O error");
+    assert_eq!(field(&records[1], "bytes"), "10");
+}
+
+#[test]
+fn encoding_emits_a_span() {
+    let records = capture(|| {
+        encode_to_vec(&term()).unwrap();
+        term().encode(Vec::new()).unwrap();
+    });
+    let expected = span(
+        "eetf::encode",
+        &[
+            ("bytes", "16"),
+            ("compressed", "false"),
+            ("variant", "\"Tuple\""),
+        ],
+    );
+    assert_eq!(sorted(records), [expected.clone(), expected]);
+
+    let nan = Term::from(List::from(vec![Term::Float(Float { value: f64::NAN })]));
+    let records = capture(|| {
+        let options = EncoderOptions {
+            new_floats: false,
+            ..EncoderOptions::default()
+        };
+        let encoder = Encoder::with_options(Vec::new(), options);
+        assert!(encoder.encode(&nan).is_err());
+    });
+    assert_eq!(field(&records[0], "message"), "failed to encode a term");
+    assert_eq!(field(&records[0], "path"), " at list[0]");
+    assert_eq!(field(&records[1], "variant"), "\"List\"");
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_decoding_emits_a_span() {
+    let bytes = encode_to_vec(&term()).unwrap();
+    let records = capture(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let decoded = AsyncDecoder::new(&bytes[..]).decode().await.unwrap();
+                assert_eq!(decoded, term());
+                let mut encoded = Vec::new();
+                AsyncEncoder::new(&mut encoded)
+                    .encode(&term())
+                    .await
+                    .unwrap();
+            });
+    });
+    let decoded = span(
+        "eetf::decode",
+        &[
+            ("bytes", "16"),
+            ("compressed", "false"),
+            ("variant", "\"Tuple\""),
+        ],
+    );
+    let encoded = span(
+        "eetf::encode",
+        &[
+            ("bytes", "16"),
+            ("compressed", "false"),
+            ("variant", "\"Tuple\""),
+        ],
+    );
+    assert_eq!(sorted(records), [decoded, encoded]);
+}
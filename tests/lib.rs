@@ -1,6 +1,7 @@
 extern crate eetf;
 extern crate num;
 
+use eetf::convert::FromTermPathSegment;
 use eetf::*;
 use std::io::Cursor;
 
@@ -28,6 +29,10 @@ fn atom_test() {
         Ok(Atom::from("foo")),
         decode(&[131, 119, 3, 102, 111, 111]).try_into()
     ); // SMALL_ATOM_UTF8_EXT
+    assert_eq!(
+        Ok(Atom::from("h\u{e9}llo")),
+        decode(&[131, 100, 0, 5, 104, 233, 108, 108, 111]).try_into()
+    ); // ATOM_EXT (Latin-1)
 
     // Encode
     assert_eq!(
@@ -36,6 +41,68 @@ fn atom_test() {
     );
 }
 
+#[test]
+fn chunked_name_test() {
+    fn with_header(tag: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![131, tag];
+        encoded.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+        encoded.extend_from_slice(bytes);
+        encoded
+    }
+    fn error_message(bytes: &[u8]) -> String {
+        match Decoder::new(Cursor::new(bytes)).decode() {
+            Err(DecodeError::Io(e)) => e.to_string(),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    // The characters of the names are split across the chunks in which they are read.
+    let name = "\u{20ac}".repeat(700);
+    let utf8 = with_header(118, name.as_bytes());
+    assert_eq!(decode(&utf8), Term::from(Atom::from(name.as_str())));
+    let latin1 = with_header(100, &[0xe9; 1500]);
+    let expected = "\u{e9}".repeat(1500);
+    assert_eq!(decode(&latin1), Term::from(Atom::from(expected.as_str())));
+    let string = with_header(107, &[7; 3000]);
+    assert_eq!(decode(&string), Term::from(ByteList::from(vec![7; 3000])));
+
+    // An invalid sequence fails before the rest of the name is read.
+    let mut invalid = utf8.clone();
+    invalid[4 + 1500] = 0xff;
+    assert_eq!(
+        error_message(&invalid[..4 + 2050]),
+        "invalid utf-8 sequence of 1 bytes from index 1500"
+    );
+    let incomplete = with_header(118, &name.as_bytes()[..2099]);
+    assert_eq!(
+        error_message(&incomplete),
+        "incomplete utf-8 byte sequence from index 2097"
+    );
+
+    // A name which is declared but never delivered is an error.
+    let mut truncated = vec![131, 118, 0xea, 0x60];
+    truncated.extend_from_slice(b"truncated");
+    assert!(matches!(
+        Decoder::new(Cursor::new(&truncated)).decode(),
+        Err(DecodeError::Io(ref e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let decoded = AsyncDecoder::new(&utf8[..]).decode().await.unwrap();
+            assert_eq!(decoded, Term::from(Atom::from(name.as_str())));
+            let decoded = AsyncDecoder::new(&string[..]).decode().await.unwrap();
+            assert_eq!(decoded, Term::from(ByteList::from(vec![7; 3000])));
+            let error = AsyncDecoder::new(&invalid[..]).decode().await.unwrap_err();
+            assert!(matches!(error, DecodeError::Io(ref e) if e.kind() == std::io::ErrorKind::InvalidData));
+            let error = AsyncDecoder::new(&truncated[..]).decode().await.unwrap_err();
+            assert!(matches!(error, DecodeError::Io(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+        });
+}
+
 #[test]
 fn integer_test() {
     // Display
@@ -96,9 +163,74 @@ fn integer_test() {
 }
 
 #[test]
+fn big_integer_conversion_test() {
+    // Accessors at the boundaries
+    let max_i64 = BigInteger::from(i64::MAX);
+    assert_eq!(max_i64.to_i64(), Some(i64::MAX));
+    assert_eq!(max_i64.to_u64(), Some(i64::MAX as u64));
+    let above_i64 = BigInteger::from(i64::MAX as u64 + 1);
+    assert_eq!(above_i64.to_i64(), None);
+    assert_eq!(above_i64.to_u64(), Some(i64::MAX as u64 + 1));
+    let min_i64 = BigInteger::from(i64::MIN);
+    assert_eq!(min_i64.to_i64(), Some(i64::MIN));
+    assert_eq!(min_i64.to_u64(), None);
+    let above_u64 = BigInteger::from(u64::MAX as u128 + 1);
+    assert_eq!(above_u64.to_u64(), None);
+    assert_eq!(above_u64.to_i128(), Some(u64::MAX as i128 + 1));
+    assert_eq!(BigInteger::from(i128::MIN).to_i128(), Some(i128::MIN));
+    assert_eq!(BigInteger::from(i128::MIN).to_u128(), None);
+    assert_eq!(BigInteger::from(u128::MAX).to_i128(), None);
+    assert_eq!(BigInteger::from(u128::MAX).to_u128(), Some(u128::MAX));
+
+    // Sign, size and floats
+    assert!(BigInteger::from(-1).is_negative());
+    assert!(!BigInteger::from(0).is_negative());
+    assert_eq!(BigInteger::from(0).bit_len(), 0);
+    assert_eq!(BigInteger::from(-255).bit_len(), 8);
+    assert_eq!(BigInteger::from(u128::MAX).bit_len(), 128);
+    assert_eq!(
+        BigInteger::from(-1i64 << 53).to_f64_lossy(),
+        -(2f64.powi(53))
+    );
+    let huge = BigInteger {
+        value: num::BigInt::from(1) << 1100,
+    };
+    assert_eq!(huge.to_f64_lossy(), f64::INFINITY);
+    assert_eq!(
+        BigInteger { value: -huge.value }.to_f64_lossy(),
+        f64::NEG_INFINITY
+    );
+
+    // FixInteger
+    assert_eq!(
+        FixInteger::try_from(&BigInteger::from(i32::MIN)).unwrap(),
+        FixInteger::from(i32::MIN)
+    );
+    assert!(FixInteger::try_from(&BigInteger::from(i32::MAX as i64 + 1)).is_err());
+    assert!(FixInteger::try_from(&BigInteger::from(i32::MIN as i64 - 1)).is_err());
+
+    // Terms
+    assert_eq!(Term::from(-5i128), Term::from(FixInteger::from(-5)));
+    assert_eq!(
+        Term::from(u128::MAX),
+        Term::from(BigInteger::from(u128::MAX))
+    );
+    assert_eq!(
+        Term::from(BigInteger::from(i128::MIN)).try_into(),
+        Ok(BigInteger::from(i128::MIN))
+    );
+    assert_eq!(i128::from_term(&Term::from(i128::MIN)), Ok(i128::MIN));
+    assert_eq!(
+        u128::from_term(&Term::from(-1)).unwrap_err().to_string(),
+        "expected u128, found -1 (out of range)"
+    );
+}
+
+#[test]
+#[allow(clippy::assertions_on_constants, clippy::legacy_numeric_constants)]
 fn float_test() {
     // Display
-    assert_eq!("123", Float::try_from(123.0).unwrap().to_string());
+    assert_eq!("123.0", Float::try_from(123.0).unwrap().to_string());
     assert_eq!("123.4", Float::try_from(123.4).unwrap().to_string());
     assert_eq!("-123.4", Float::try_from(-123.4).unwrap().to_string());
 
@@ -143,6 +275,54 @@ fn float_test() {
     }
 }
 
+#[test]
+fn f32_conversion_test() {
+    use eetf::convert::NonFiniteFloatError;
+
+    // Widening is exact.
+    for value in [f32::MAX, f32::MIN_POSITIVE, -f32::EPSILON, 1.0 / 3.0, 0.1] {
+        let term = Term::try_from(value).unwrap();
+        assert_eq!(term, Term::from(Float::try_from(f64::from(value)).unwrap()));
+        assert_eq!(f32::from_term(&term), Ok(value));
+        let decoded = decode(&encode(term));
+        assert_eq!(f32::from_term(&decoded), Ok(value));
+    }
+    assert!(Term::try_from(f32::NAN).is_err());
+    assert!(Term::try_from(f32::INFINITY).is_err());
+
+    // Narrowing
+    let tenth = Float::try_from(0.1).unwrap();
+    assert_eq!(tenth.to_f32(FloatPrecisionLoss::Error), None);
+    assert_eq!(tenth.to_f32(FloatPrecisionLoss::Nearest), Some(0.1f32));
+    assert_eq!(tenth.to_f32_lossy(), 0.1f32);
+    let above_max = Float::try_from(f64::from(f32::MAX) * 2.0).unwrap();
+    assert_eq!(above_max.to_f32(FloatPrecisionLoss::Nearest), None);
+    assert_eq!(above_max.to_f32_lossy(), f32::INFINITY);
+    let tiny = Float::try_from(f64::from(f32::MIN_POSITIVE) / 3.0).unwrap();
+    assert_eq!(tiny.to_f32(FloatPrecisionLoss::Error), None);
+    assert!(tiny.to_f32(FloatPrecisionLoss::Nearest).is_some());
+    assert_eq!(
+        f32::from_term(&Term::from(tenth)).unwrap_err().to_string(),
+        "expected f32, found 0.1 (precision loss)"
+    );
+    assert!(f32::from_term(&Term::from(1)).is_err());
+
+    // Batches
+    let values = [0.5, -1.25, 1e300];
+    let list = List::from_f64_slice(&values).unwrap();
+    assert_eq!(list.try_to_f64_vec(), Ok(values.to_vec()));
+    assert_eq!(
+        List::from_f64_slice(&[0.5, 1.0, f64::INFINITY]),
+        Err(NonFiniteFloatError {
+            index: 2,
+            value: f64::INFINITY
+        })
+    );
+    let mixed = List::from(vec![Term::try_from(0.5).unwrap(), Term::from(1)]);
+    let e = mixed.try_to_f64_vec().unwrap_err();
+    assert_eq!(e.path, vec![FromTermPathSegment::ListElement(1)]);
+}
+
 #[test]
 fn pid_test() {
     // Display
@@ -238,6 +418,76 @@ fn reference_test() {
     );
 }
 
+#[test]
+fn identifier_constructors_test() {
+    let node = node_name::NodeName::parse("a@b").unwrap();
+
+    // Pid
+    let pid = Pid::try_new("a@b", u32::MAX, u32::MAX, u32::MAX).unwrap();
+    assert_eq!(pid, Pid::new("a@b", u32::MAX, u32::MAX, u32::MAX));
+    assert!(pid.is_local(&node, u32::MAX));
+    assert!(!pid.is_local(&node, 1));
+    assert!(!pid.is_local(&node_name::NodeName::parse("a@c").unwrap(), u32::MAX));
+    assert_eq!(
+        Pid::try_new("a@b", 0, 0, 0),
+        Err(IdentifierError::ZeroCreation)
+    );
+    assert!(matches!(
+        Pid::try_new("ab", 0, 0, 1),
+        Err(IdentifierError::Node(_))
+    ));
+
+    // Port
+    let port = Port::try_new("a@b", u64::MAX, 1).unwrap();
+    assert!(port.is_local(&node, 1));
+    assert_eq!(
+        Port::try_new("a@b", 0, 0),
+        Err(IdentifierError::ZeroCreation)
+    );
+
+    // Reference
+    assert!(Reference::try_new("a@b", vec![0], 1).is_ok());
+    assert!(Reference::try_new("a@b", vec![0; 5], 1).is_ok());
+    assert_eq!(
+        Reference::try_new("a@b", vec![], 1),
+        Err(IdentifierError::ReferenceIdLen { len: 0 })
+    );
+    assert_eq!(
+        Reference::try_new("a@b", vec![0; 6], 1),
+        Err(IdentifierError::ReferenceIdLen { len: 6 })
+    );
+    assert_eq!(
+        Reference::try_new("a@b", vec![0; 6], 1)
+            .unwrap_err()
+            .to_string(),
+        "a reference ID has 1 to 5 words, not 6"
+    );
+}
+
+#[test]
+fn reference_generate_test() {
+    let threads = (0..8)
+        .map(|_| {
+            std::thread::spawn(|| {
+                (0..1000)
+                    .map(|_| Reference::generate("a@b", 1))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut references = std::collections::HashSet::new();
+    for thread in threads {
+        for reference in thread.join().unwrap() {
+            assert_eq!(reference.id.len(), 3);
+            assert!(reference.is_local(&node_name::NodeName::from("a@b"), 1));
+            assert!(references.insert(reference));
+        }
+    }
+    assert_eq!(references.len(), 8000);
+    let reference = Term::from(Reference::generate("a@b", 1));
+    assert_eq!(decode(&encode(reference.clone())), reference);
+}
+
 #[test]
 fn external_fun_test() {
     // Display
@@ -257,6 +507,25 @@ fn external_fun_test() {
         vec![131, 113, 100, 0, 3, 102, 111, 111, 100, 0, 3, 98, 97, 114, 97, 3],
         encode(Term::from(ExternalFun::from(("foo", "bar", 3))))
     );
+
+    // MFA
+    let fun = ExternalFun::new("foo", "bar", 3);
+    assert_eq!(fun, ExternalFun::from(("foo", "bar", 3)));
+    assert_eq!(fun.mfa(), (&Atom::from("foo"), &Atom::from("bar"), 3));
+    let mfa = |arity| {
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("foo")),
+            Term::from(Atom::from("bar")),
+            Term::from(arity),
+        ]))
+    };
+    assert_eq!(ExternalFun::try_from_term(mfa(3), true), Ok(fun.clone()));
+    assert_eq!(ExternalFun::try_from_term(mfa(3), false), Err(mfa(3)));
+    assert_eq!(ExternalFun::try_from_term(mfa(256), true), Err(mfa(256)));
+    assert_eq!(
+        ExternalFun::try_from_term(Term::from(fun.clone()), false),
+        Ok(fun)
+    );
 }
 
 #[test]
@@ -283,7 +552,47 @@ fn internal_fun_test() {
     assert_eq!(Ok(term.clone()), decode(&bytes).try_into());
 
     // Encode
-    assert_eq!(Vec::from(&bytes[..]), encode(Term::from(term)));
+    assert_eq!(Vec::from(&bytes[..]), encode(Term::from(term.clone())));
+
+    // Display
+    assert_eq!(
+        term.to_string(),
+        "#Fun<'a'.0.153176882388067055964866530197859352843>"
+    );
+
+    // Accessors
+    assert_eq!(term.module(), &Atom::from("a"));
+    assert_eq!(term.arity(), Some(1));
+    assert_eq!(term.pid(), &Pid::from(("nonode@nohost", 36, 0)));
+    assert_eq!(term.free_vars(), [Term::from(FixInteger::from(10))]);
+
+    // Large free variables (here a 10 MB binary) are written after the size without buffering.
+    let mut term = term;
+    let binary = vec![7; 10 << 20];
+    if let InternalFun::New {
+        ref mut free_vars, ..
+    } = term
+    {
+        free_vars.push(Term::from(Binary::from(binary.clone())));
+    }
+    let term = Term::from(term);
+    let mut expected = Vec::from(&bytes[..]);
+    expected[2..6].copy_from_slice(&(71 + 5 + binary.len() as u32).to_be_bytes());
+    expected[27..31].copy_from_slice(&2u32.to_be_bytes());
+    expected.push(109);
+    expected.extend_from_slice(&(binary.len() as u32).to_be_bytes());
+    expected.extend_from_slice(&binary);
+    assert!(encode(term.clone()) == expected);
+    #[cfg(feature = "tokio-async")]
+    {
+        let mut buf = Vec::new();
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(term.encode_async(&mut buf))
+            .unwrap();
+        assert!(buf == expected);
+    }
 }
 
 #[test]
@@ -304,6 +613,611 @@ fn binary_test() {
     );
 }
 
+#[test]
+fn malformed_input_test() {
+    let rejected = |bytes: &[u8]| {
+        #[cfg(feature = "tokio-async")]
+        assert!(tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(AsyncDecoder::new(bytes).decode())
+            .is_err());
+        Term::decode(Cursor::new(bytes)).is_err() && decode_from_slice(bytes).is_err()
+    };
+    // Huge counts and sizes must not allocate before the input runs out.
+    assert!(rejected(&[131, 105, 0x24, 0, 0, 0, 3, 0, 0, 0]));
+    assert!(rejected(&[131, 108, 0xFF, 0xFF, 0xFF, 0xFF, 97, 1]));
+    assert!(rejected(&[131, 109, 0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3]));
+    assert!(rejected(&[131, 111, 0xFF, 0xFF, 0xFF, 0xFF, 0, 1, 2]));
+    // A non-empty bit binary with no bits in its last byte.
+    assert!(rejected(&[131, 77, 0, 0, 0, 2, 0, 0, 98]));
+    // An empty bit binary with bits in its (absent) last byte.
+    assert!(rejected(&[131, 77, 0, 0, 0, 0, 3]));
+    // The node of a pid must be an atom, not another pid (which recursed without bound).
+    let mut pids = vec![131];
+    pids.extend([88; 100_000]);
+    assert!(rejected(&pids));
+    // A distribution header outside of a distribution message.
+    assert!(matches!(
+        Term::decode(Cursor::new(&[131, 68, 0][..])),
+        Err(DecodeError::UnknownTag { tag: 68 })
+    ));
+}
+
+#[test]
+fn nesting_depth_test() {
+    let nested = |depth: usize| {
+        let mut bytes = vec![131];
+        bytes.extend([104, 1].repeat(depth - 1));
+        bytes.push(106);
+        bytes
+    };
+    let too_deep = |r: Result<Term, DecodeError>| {
+        matches!(
+            r,
+            Err(DecodeError::TooDeep {
+                max: MAX_DECODE_DEPTH
+            })
+        )
+    };
+
+    let bytes = nested(MAX_DECODE_DEPTH);
+    let term = decode(&bytes);
+    assert_eq!(decode_from_slice(&bytes).unwrap(), term);
+    assert_eq!(decode_view(&bytes).unwrap().to_owned(), term);
+    assert_eq!(encode(term), bytes);
+
+    let bytes = nested(MAX_DECODE_DEPTH + 1);
+    assert!(too_deep(Term::decode(Cursor::new(&bytes))));
+    assert!(too_deep(decode_from_slice(&bytes)));
+    assert!(too_deep(decode_view(&bytes).map(|v| v.to_owned())));
+    assert!(too_deep(
+        Decoder::with_options(Cursor::new(&bytes), DecoderOptions::raw_depth(1)).decode()
+    ));
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            assert!(too_deep(AsyncDecoder::new(&bytes[..]).decode().await));
+            let options = DecoderOptions::raw_depth(1);
+            let decoder = AsyncDecoder::with_options(&bytes[..], options);
+            assert!(too_deep(decoder.decode().await));
+        });
+}
+
+#[test]
+fn encode_into_test() {
+    let terms = [
+        Term::from(Atom::from("foo")),
+        Term::from(-1000),
+        Term::from(BigInteger {
+            value: num::BigInt::from(u64::MAX) * 3,
+        }),
+        Term::try_from(1.5).unwrap(),
+        // Not compressed, however compressible.
+        Term::from(Binary::from(vec![0; 1000])),
+        Term::from(Tuple::from(vec![
+            Term::from(Pid::new("foo@bar", 1, 2, 3)),
+            Term::from(List::from(vec![Term::from(1), Term::from(Atom::from("a"))])),
+            Term::from(Map::from([(
+                Term::from(1),
+                Term::from(ByteList::from("abc")),
+            )])),
+        ])),
+    ];
+    for term in terms {
+        let expected = encode(term.clone());
+        let needed = term.encoded_size().unwrap();
+        assert_eq!(needed, expected.len());
+
+        let mut exact = vec![0; needed];
+        assert_eq!(encode_into(&term, &mut exact).unwrap(), needed);
+        assert_eq!(exact, expected);
+
+        let mut short = vec![0; needed - 1];
+        let error = encode_into(&term, &mut short).unwrap_err();
+        assert!(
+            matches!(error, EncodeError::BufferTooSmall { needed: n, available } if n == needed && available == needed - 1),
+            "{:?}",
+            error
+        );
+
+        let mut oversized = vec![0xff; needed + 10];
+        assert_eq!(encode_into(&term, &mut oversized).unwrap(), needed);
+        assert_eq!(oversized[..needed], expected);
+        assert_eq!(oversized[needed..], [0xff; 10]);
+    }
+    assert!(matches!(
+        encode_into(&Term::from(Atom::from("foo")), &mut []),
+        Err(EncodeError::BufferTooSmall {
+            needed: 7,
+            available: 0
+        })
+    ));
+}
+
+#[test]
+fn decode_frame_test() {
+    use std::io::Write;
+
+    let terms = [
+        Term::from(Atom::from("foo")),
+        Term::from(7),
+        Term::from(-300),
+        Term::try_from(1.5).unwrap(),
+        Term::from(Binary::from(&b"bar"[..])),
+        Term::from(ByteList::from("baz")),
+        Term::from(List::from(vec![
+            Term::from(Atom::from("a")),
+            Term::from(-1),
+        ])),
+        Term::from(Tuple::from(vec![
+            Term::from(1),
+            Term::from(Atom::from("b")),
+        ])),
+        Term::from(Map::from([(Term::from(1), Term::from(2))])),
+        Term::from(Pid::new("foo@bar", 1, 2, 3)),
+    ];
+    let versionless = DecoderOptions::new().with_versionless(true);
+    for term in terms {
+        let frame = encode(term.clone());
+        assert_eq!(decode_frame(&frame).unwrap(), term);
+        assert_eq!(
+            decode_frame_with_options(&frame[1..], versionless).unwrap(),
+            term
+        );
+
+        let mut trailing = frame.clone();
+        trailing.extend([0, 0]);
+        assert!(matches!(
+            decode_frame(&trailing),
+            Err(DecodeError::TrailingBytes { count: 2 })
+        ));
+        for len in 0..frame.len() {
+            assert!(
+                matches!(decode_frame(&frame[..len]), Err(DecodeError::UnexpectedEof)),
+                "{} {:?}",
+                term,
+                &frame[..len]
+            );
+        }
+        // The version number is not optional unless the options say so.
+        assert!(matches!(
+            decode_frame(&frame[1..]),
+            Err(DecodeError::UnsupportedVersion { .. })
+        ));
+    }
+
+    // Compressed frames, whose inflated body must hold exactly the term.
+    let term = Term::from(Binary::from(vec![0; 1000]));
+    let mut frame = Vec::new();
+    term.encode_compressed(&mut frame).unwrap();
+    assert_eq!(decode_frame(&frame).unwrap(), term);
+    assert_eq!(
+        decode_frame_with_options(&frame[1..], versionless).unwrap(),
+        term
+    );
+    assert!(matches!(
+        decode_frame(&frame[..frame.len() - 4]),
+        Err(DecodeError::UnexpectedEof)
+    ));
+    let mut body = encode(term.clone());
+    body.push(0);
+    let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+    compressor.write_all(&body[1..]).unwrap();
+    let mut frame = vec![131, 80];
+    frame.extend_from_slice(&((body.len() - 1) as u32).to_be_bytes());
+    frame.extend(compressor.finish().into_result().unwrap());
+    assert!(matches!(
+        decode_frame(&frame),
+        Err(DecodeError::InvalidData { .. })
+    ));
+}
+
+#[test]
+fn encode_error_path_test() {
+    let nested = |offender: Term| {
+        let mut list = vec![Term::from(0); 1022];
+        list[1021] = offender;
+        Term::from(Tuple::from(vec![
+            Term::from(0),
+            Term::from(1),
+            Term::from(2),
+            Term::from(Map::from([(
+                Term::from(Atom::from("labels")),
+                Term::from(List::from(list)),
+            )])),
+        ]))
+    };
+    let path = " at tuple[3] → map value for key 'labels' → list[1021]";
+
+    let term = nested(Term::from(Atom::from("a".repeat(0x10000))));
+    let error = term.encode(Vec::new()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("too long atom name: 65536 bytes{}", path)
+    );
+    assert!(matches!(
+        error,
+        EncodeError::TooLongAtomName(_, EncodePath { ref segments }) if segments[0] == EncodePathSegment::ListElement(1021)
+    ));
+    assert_eq!(
+        encode_to_vec(&term).unwrap_err().to_string(),
+        error.to_string()
+    );
+    #[cfg(feature = "tokio-async")]
+    {
+        let async_error = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(term.encode_async(Vec::new()))
+            .unwrap_err();
+        assert_eq!(async_error.to_string(), error.to_string());
+    }
+
+    let reference = Reference::from(("a@localhost", vec![0; 0x10000]));
+    let term = nested(Term::from(reference));
+    let error = term.encode(Vec::new()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "too large reference ID: 262144 bytes required to encode{}",
+            path
+        )
+    );
+    assert_eq!(
+        encode_to_vec(&term).unwrap_err().to_string(),
+        error.to_string()
+    );
+
+    // Other steps.
+    let atom = Term::from(Atom::from("a".repeat(0x10000)));
+    let term = Term::from(ImproperList::from((
+        vec![Term::from(1)],
+        Term::from(Map::from([(atom, Term::from(1))])),
+    )));
+    assert_eq!(
+        encode_to_vec(&term).unwrap_err().to_string(),
+        "too long atom name: 65536 bytes at list tail → map key"
+    );
+    let error = Term::from(Atom::from("a".repeat(0x10000)))
+        .encode(Vec::new())
+        .unwrap_err();
+    assert_eq!(error.to_string(), "too long atom name: 65536 bytes");
+}
+
+#[test]
+fn binary_from_test() {
+    // The vector is moved into the term.
+    let bytes = vec![1, 2, 3];
+    let ptr = bytes.as_ptr();
+    let Term::Binary(binary) = Term::binary_from(bytes) else {
+        unreachable!()
+    };
+    assert_eq!(binary.bytes.as_ptr(), ptr);
+
+    // Clones of the messages share the payload.
+    #[cfg(feature = "bytes")]
+    {
+        let payload = bytes::Bytes::from(vec![7; 1 << 20]);
+        let messages = (0..100)
+            .map(|i| {
+                Term::from(Tuple::from(vec![
+                    Term::from(i),
+                    Term::binary_from(payload.clone()),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        for message in messages.iter().cloned() {
+            let Term::Tuple(tuple) = message else {
+                unreachable!()
+            };
+            let Term::Binary(ref binary) = tuple.elements[1] else {
+                unreachable!()
+            };
+            assert_eq!(binary.bytes.as_ptr(), payload.as_ptr());
+        }
+    }
+
+    // Encoder::encode_parts
+    let mut buf = Vec::new();
+    Encoder::new(&mut buf)
+        .encode_parts(&Term::from(Atom::from("data")), &[1, 2, 3])
+        .unwrap();
+    assert_eq!(
+        buf,
+        encode(Term::from(Tuple::from(vec![
+            Term::from(Atom::from("data")),
+            Term::binary_from(vec![1, 2, 3]),
+        ])))
+    );
+}
+
+#[test]
+fn sub_binary_test() {
+    // <<"GET /index.html">>
+    let mut bytes = vec![131, 109, 0, 0, 0, 15];
+    bytes.extend_from_slice(b"GET /index.html");
+    let Term::Binary(request) = decode(&bytes) else {
+        unreachable!()
+    };
+
+    let path = request.strip_prefix(b"GET ").unwrap();
+    assert_eq!(path, Binary::from(&b"/index.html"[..]));
+    assert_eq!(request.strip_prefix(b"POST "), None);
+    let (method, rest) = request.split_at(3);
+    assert_eq!(method, Binary::from(&b"GET"[..]));
+    assert!(rest.starts_with(b" /"));
+
+    let view = request.view(4..);
+    assert_eq!(view, path);
+    assert_eq!(path, view);
+    assert_eq!(view.strip_prefix(b"/").unwrap().as_ref(), b"index.html");
+    assert_eq!(view.split_at(6).1.to_string(), "<<46,104,116,109,108>>");
+    assert_eq!(Binary::from(view), path);
+
+    let mut expected = vec![131, 104, 2, 100, 0, 3, 103, 101, 116, 109, 0, 0, 0, 11];
+    expected.extend_from_slice(b"/index.html");
+    let get = Term::from(Atom::from("get"));
+    let term = Term::from(Tuple::from(vec![get.clone(), Term::from(path.clone())]));
+    assert_eq!(encode(term), expected);
+    let mut buf = Vec::new();
+    Encoder::new(&mut buf)
+        .encode_parts(&get, view.as_ref())
+        .unwrap();
+    assert_eq!(buf, expected);
+
+    // The sub-binaries of a binary decoded from `Bytes` point into the input.
+    #[cfg(feature = "bytes")]
+    {
+        let input = bytes::Bytes::from(bytes);
+        let Term::Binary(request) = decode_from_bytes(&input).unwrap() else {
+            unreachable!()
+        };
+        let path = request.strip_prefix(b"GET ").unwrap();
+        assert_eq!(path.bytes.as_ptr(), input[10..].as_ptr());
+        let (_, rest) = request.split_at(3);
+        assert_eq!(rest.bytes.as_ptr(), input[9..].as_ptr());
+    }
+}
+
+#[test]
+fn decode_binary_into_test() {
+    use std::io::Write;
+
+    struct FailingSink(usize);
+    impl Write for FailingSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 < buf.len() {
+                return Err(std::io::Error::other("disk full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let bytes = (0..32 << 20).map(|i| i as u8).collect::<Vec<_>>();
+    let binary = encode(Term::from(Binary::from(bytes.clone())));
+    let mut message = binary.clone();
+    message.extend(encode(Term::from(ByteList::from(b"abc".to_vec()))));
+    message.extend(encode(Term::from(List::nil())));
+    message.extend(encode(Term::from(Atom::from("eof"))));
+    message.extend(encode(Term::from(1)));
+
+    let mut decoder = Decoder::new(Cursor::new(&message));
+    let mut sink = Vec::new();
+    assert_eq!(
+        decoder.decode_binary_into(&mut sink).unwrap(),
+        bytes.len() as u64
+    );
+    assert_eq!(sink, bytes);
+    let mut sink = Vec::new();
+    assert_eq!(decoder.decode_binary_into(&mut sink).unwrap(), 3);
+    assert_eq!(sink, b"abc");
+    assert_eq!(decoder.decode_binary_into(&mut sink).unwrap(), 0);
+    // A term of another type is consumed, so the decoder may be reset to go on.
+    assert!(matches!(
+        decoder.decode_binary_into(&mut sink),
+        Err(DecodeError::UnexpectedType { ref found, .. }) if found.type_name == "Atom"
+    ));
+    assert!(decoder.is_poisoned());
+    decoder.reset();
+    assert_eq!(decoder.decode_next().unwrap(), Term::from(1));
+
+    // Errors of the sink are propagated.
+    let mut decoder = Decoder::new(Cursor::new(&binary));
+    let error = decoder
+        .decode_binary_into(&mut FailingSink(1 << 20))
+        .unwrap_err();
+    assert!(matches!(error, DecodeError::Io(ref e) if e.to_string() == "disk full"));
+
+    // Truncated binaries are reported.
+    let mut decoder = Decoder::new(Cursor::new(&binary[..1000]));
+    let error = decoder.decode_binary_into(&mut Vec::new()).unwrap_err();
+    assert!(
+        matches!(error, DecodeError::Io(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+    );
+
+    // Compressed binaries are decompressed on the fly.
+    let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+    compressor.write_all(&binary[1..]).unwrap();
+    let mut compressed = vec![131, 80];
+    compressed.extend_from_slice(&(binary.len() as u32 - 1).to_be_bytes());
+    compressed.extend(compressor.finish().into_result().unwrap());
+    let mut sink = Vec::new();
+    Decoder::new(Cursor::new(&compressed))
+        .decode_binary_into(&mut sink)
+        .unwrap();
+    assert_eq!(sink, bytes);
+
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut decoder = AsyncDecoder::new(&message[..]);
+            let mut sink = Vec::new();
+            assert_eq!(
+                decoder.decode_binary_into(&mut sink).await.unwrap(),
+                bytes.len() as u64
+            );
+            assert_eq!(sink, bytes);
+            sink.clear();
+            assert_eq!(decoder.decode_binary_into(&mut sink).await.unwrap(), 3);
+            assert_eq!(decoder.decode_binary_into(&mut sink).await.unwrap(), 0);
+            assert!(matches!(
+                decoder.decode_binary_into(&mut sink).await,
+                Err(DecodeError::UnexpectedType { ref found, .. }) if found.type_name == "Atom"
+            ));
+
+            let mut decoder = AsyncDecoder::new(&binary[..]);
+            let mut sink = tokio::io::BufWriter::with_capacity(1024, FailingAsyncSink);
+            let error = decoder.decode_binary_into(&mut sink).await.unwrap_err();
+            assert!(matches!(error, DecodeError::Io(ref e) if e.to_string() == "disk full"));
+        });
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_compressed_term_test() {
+    // Reader which counts the reads of its inner reader.
+    struct CountingReads<'a> {
+        inner: &'a [u8],
+        reads: usize,
+    }
+    impl tokio::io::AsyncRead for CountingReads<'_> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.reads += 1;
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    // Pseudo-random bytes, which hardly compress.
+    let mut state = 1u32;
+    let noise = (0..200_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect::<Vec<_>>();
+    let term = Term::from(Binary::from(noise));
+    let mut bytes = Vec::new();
+    term.encode_compressed(&mut bytes).unwrap();
+    let compressed_len = bytes.len();
+    Term::from(1).encode(&mut bytes).unwrap();
+
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut reader = CountingReads {
+                inner: &bytes,
+                reads: 0,
+            };
+            let mut decoder = AsyncDecoder::new(&mut reader);
+            assert_eq!(decoder.decode_next().await.unwrap(), term);
+            assert_eq!(decoder.position(), compressed_len as u64);
+            assert_eq!(decoder.decode_next().await.unwrap(), Term::from(1));
+            assert_eq!(decoder.position(), bytes.len() as u64);
+            // The stream is read by blocks rather than byte by byte.
+            assert!(reader.reads < compressed_len / 1000, "{}", reader.reads);
+
+            // A reset discards the bytes read past the compressed term.
+            let mut decoder = AsyncDecoder::new(&bytes[..]);
+            assert_eq!(decoder.decode_next().await.unwrap(), term);
+            decoder.reset();
+            assert!(decoder.decode_next().await.is_err());
+        });
+}
+
+#[cfg(feature = "tokio-async")]
+struct FailingAsyncSink;
+#[cfg(feature = "tokio-async")]
+impl tokio::io::AsyncWrite for FailingAsyncSink {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+        _: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::task::Poll::Ready(Err(std::io::Error::other("disk full")))
+    }
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn decode_file_test() {
+    use std::io::Write;
+
+    let term = Term::from(List::from(
+        (0..100_000)
+            .map(|i| {
+                Term::from(Tuple::from(vec![
+                    Term::from(Atom::from("entry")),
+                    Term::from(i),
+                    Term::from(Binary::from(vec![i as u8; 64])),
+                    Term::from(ByteList::from(b"abc".to_vec())),
+                ]))
+            })
+            .collect::<Vec<_>>(),
+    ));
+    let bytes = encode(term.clone());
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("eetf-decode-file-{}.etf", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+    assert_eq!(eetf::decode_file(&path).unwrap(), term);
+    assert_eq!(
+        eetf::decode_file_view(&path, |view| view.to_owned()).unwrap(),
+        term
+    );
+    let mapped = MappedTerm::open(&path).unwrap();
+    assert_eq!(mapped.as_bytes(), bytes);
+    assert!(matches!(mapped.view().unwrap(), TermView::List(x) if x.len() == 100_000));
+
+    // Compressed terms.
+    let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+    compressor.write_all(&bytes[1..]).unwrap();
+    let mut compressed = vec![131, 80];
+    compressed.extend_from_slice(&(bytes.len() as u32 - 1).to_be_bytes());
+    compressed.extend(compressor.finish().into_result().unwrap());
+    std::fs::write(&path, &compressed).unwrap();
+    assert_eq!(eetf::decode_file(&path).unwrap(), term);
+    assert_eq!(
+        eetf::decode_file_view(&path, |view| view.to_owned()).unwrap(),
+        term
+    );
+    assert_eq!(MappedTerm::open(&path).unwrap().as_bytes(), bytes);
+
+    // Empty files.
+    std::fs::write(&path, []).unwrap();
+    let error = eetf::decode_file(&path).unwrap_err();
+    assert!(matches!(error, DecodeError::EmptyFile { path: ref p } if *p == path));
+    assert!(matches!(
+        MappedTerm::open(&path),
+        Err(DecodeError::EmptyFile { .. })
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
 #[test]
 fn bit_binary_test() {
     // Display
@@ -325,6 +1239,70 @@ fn bit_binary_test() {
         vec![131, 77, 0, 0, 0, 3, 5, 1, 2, 24],
         encode(Term::from(BitBinary::from((vec![1, 2, 3], 5))))
     );
+
+    // Bits
+    let bits = BitBinary::from((vec![1, 2, 3], 5));
+    assert_eq!(bits.bit_len(), 21);
+    assert_eq!(bits.get_bit(7), Some(true));
+    assert_eq!(bits.get_bit(20), Some(true));
+    assert_eq!(bits.get_bit(19), Some(true));
+    assert_eq!(bits.get_bit(18), Some(false));
+    assert_eq!(bits.get_bit(21), None);
+    let collected = bits.iter_bits().collect::<Vec<_>>();
+    assert_eq!(collected.len(), 21);
+    assert_eq!(BitBinary::from_bits(collected), bits);
+    assert_eq!(bits.to_padded_bytes(), (vec![1, 2, 24], 21));
+    assert_eq!(
+        BitBinary::from_padded_bytes(vec![1, 2, 24], 21),
+        Some(bits.clone())
+    );
+    assert_eq!(BitBinary::from_padded_bytes(vec![1, 2], 21), None);
+    assert_eq!(BitBinary::from((vec![1, 2, 3], 0)).bit_len(), 16);
+
+    // Empty bit strings
+    let empty = BitBinary::from_bits([]);
+    assert_eq!(empty, BitBinary::from((vec![], 0)));
+    assert_eq!(empty.bit_len(), 0);
+    assert_eq!(empty.get_bit(0), None);
+    assert_eq!(empty.to_padded_bytes(), (vec![], 0));
+    let mut appended = empty.clone();
+    appended.append(&empty);
+    assert_eq!(appended, empty);
+    assert_eq!(Binary::try_from(empty.clone()), Ok(Binary::from(vec![])));
+
+    // Unaligned appends
+    let mut bits = BitBinary::from_bits([true, false, true]);
+    bits.append(&BitBinary::from((vec![0xFF, 1], 2)));
+    assert_eq!(bits.bit_len(), 13);
+    assert_eq!(bits, BitBinary::from((vec![0b1011_1111, 0b11101], 5)));
+    bits.push_bit(true);
+    bits.append(&BitBinary::from(Binary::from(vec![0x0F, 0xF0])));
+    let bytes = vec![0b1011_1111, 0b1110_1100, 0b0011_1111, 0b1100_0000];
+    assert_eq!(bits.to_padded_bytes(), (bytes, 30));
+    let mut aligned = BitBinary::from(Binary::from(vec![7]));
+    aligned.append(&BitBinary::from((vec![1], 1)));
+    assert_eq!(aligned, BitBinary::from((vec![7, 1], 1)));
+
+    // Binaries
+    assert_eq!(
+        Binary::try_from(BitBinary::from_bits([true; 16])),
+        Ok(Binary::from(vec![0xFF, 0xFF]))
+    );
+    assert_eq!(
+        Binary::try_from(BitBinary::from((vec![1, 2, 3], 0))),
+        Ok(Binary::from(vec![1, 2]))
+    );
+    assert!(Binary::try_from(BitBinary::from((vec![1], 1))).is_err());
+
+    // Round trips
+    for bits in [
+        BitBinary::from_bits([true]),
+        BitBinary::from_bits((0..100).map(|i| i % 3 == 0)),
+        bits,
+    ] {
+        let bytes = encode(Term::from(bits.clone()));
+        assert_eq!(Ok(bits), decode(&bytes).try_into());
+    }
 }
 
 
@@ -399,6 +1377,11 @@ fn list_test() {
         vec![131, 108, 0, 0, 0, 1, 100, 0, 1, 97, 106],
         encode(Term::from(List::from(vec![Term::from(Atom::from("a"))])))
     );
+    // Negative integers are not bytes.
+    assert_eq!(
+        vec![131, 108, 0, 0, 0, 1, 98, 255, 255, 255, 255, 106],
+        encode(Term::from(List::from(vec![Term::from(-1)])))
+    );
 }
 
 #[test]
@@ -534,6 +1517,3109 @@ fn compressed_term_test() {
         ])
         .try_into()
     );
+
+    // Encode
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(Binary::from(vec![0; 1000])),
+    ]));
+    let mut buf = Vec::new();
+    term.encode_compressed(&mut buf).unwrap();
+    assert_eq!(&buf[..2], [131, 80]);
+    assert_eq!(
+        u32::from_be_bytes(buf[2..6].try_into().unwrap()) as usize,
+        term.encoded_size().unwrap() - 1
+    );
+    assert!(buf.len() < 100);
+    assert_eq!(decode(&buf), term);
+
+    // A compressed term ends where its zlib stream ends.
+    buf.extend(encode(Term::from(Atom::from("next"))));
+    let mut reader = Cursor::new(&buf);
+    assert_eq!(Decoder::new(&mut reader).decode().unwrap(), term);
+    assert_eq!(
+        Decoder::new(&mut reader).decode().unwrap(),
+        Term::from(Atom::from("next"))
+    );
+}
+
+#[test]
+fn max_uncompressed_size_test() {
+    use std::io::Write;
+
+    let limited = DecoderOptions::new().with_max_uncompressed_size(16 << 20);
+    fn is_limit_exceeded<T>(result: Result<T, DecodeError>) -> bool {
+        matches!(
+            result,
+            Err(DecodeError::LimitExceeded {
+                limit: "max_uncompressed_size",
+                ..
+            })
+        )
+    }
+    let compress = |bytes: &[u8], declared_size: u32| {
+        let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+        compressor.write_all(&bytes[1..]).unwrap();
+        let mut compressed = vec![131, 80];
+        compressed.extend_from_slice(&declared_size.to_be_bytes());
+        compressed.extend(compressor.finish().into_result().unwrap());
+        compressed
+    };
+
+    // A term declaring 2 GB is rejected before it is inflated.
+    let binary = encode(Term::from(Binary::from(vec![0; 1000])));
+    let compressed = compress(&binary, 2 << 30);
+    assert!(is_limit_exceeded(Term::decode_with_options(
+        Cursor::new(&compressed),
+        limited
+    )));
+    assert!(is_limit_exceeded(decode_from_slice_with_options(
+        &compressed,
+        limited
+    )));
+    assert_eq!(
+        Term::decode(Cursor::new(&compressed)).unwrap(),
+        decode(&binary)
+    );
+
+    // A term declaring less than it inflates to is rejected once the limit is reached.
+    let binary = encode(Term::from(Binary::from(vec![0; 1 << 20])));
+    let compressed = compress(&binary, 100);
+    let options = DecoderOptions::new().with_max_uncompressed_size(64 << 10);
+    let mut reader = Cursor::new(&compressed);
+    assert!(is_limit_exceeded(Term::decode_with_options(
+        &mut reader,
+        options
+    )));
+    // libflate inflates a whole deflate block at once, and the 1 MB binary is a single block.
+    #[cfg(feature = "flate2")]
+    assert!((reader.position() as usize) < compressed.len() / 2);
+    let mut decoder = Decoder::with_options(Cursor::new(&compressed), options);
+    assert!(is_limit_exceeded(decoder.skip_term()));
+    let mut decoder = Decoder::with_options(Cursor::new(&compressed), options);
+    let mut sink = Vec::new();
+    assert!(is_limit_exceeded(decoder.decode_binary_into(&mut sink)));
+    assert!(is_limit_exceeded(decode_from_slice_with_options(
+        &compressed,
+        options
+    )));
+
+    // A term inflating to exactly the limit is decoded.
+    let options = DecoderOptions::new().with_max_uncompressed_size(binary.len() - 1);
+    let term = Term::decode_with_options(Cursor::new(&compressed), options).unwrap();
+    assert_eq!(term, decode(&binary));
+    let term = decode_from_slice_with_options(&compressed, options).unwrap();
+    assert_eq!(term, decode(&binary));
+}
+
+#[test]
+fn std_traits_test() {
+    use std::collections::BTreeMap;
+
+    assert_eq!(List::default(), List::nil());
+    assert_eq!(Tuple::default(), Tuple::nil());
+    assert!(Binary::default().bytes.is_empty());
+    assert!(ByteList::default().bytes.is_empty());
+
+    let mut counts = BTreeMap::new();
+    for name in ["b", "a", "c", "a"] {
+        *counts.entry(Atom::from(name)).or_insert(0) += 1;
+    }
+    let names = counts.keys().map(|a| a.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "c"]);
+    assert!(FixInteger::from(-1) < FixInteger::from(1));
+    assert!(Binary::from(&b"ab"[..]) < Binary::from(&b"b"[..]));
+    assert!(Float::try_from(-0.5).unwrap() < Float::try_from(0.0).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_json_test() {
+    let pid = Pid::new("foo@bar", 1, 2, 3);
+    let json = serde_json::to_string(&pid).unwrap();
+    assert_eq!(json, r#"{"node":"foo@bar","id":1,"serial":2,"creation":3}"#);
+    assert_eq!(serde_json::from_str::<Pid>(&json).unwrap(), pid);
+
+    let tuple = Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(pid),
+        Term::try_from(1.5).unwrap(),
+        Term::from(Map::from([(
+            Term::from(1),
+            Term::from(Binary::from(&b"a"[..])),
+        )])),
+    ]);
+    let json = serde_json::to_string(&tuple).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"[{"Atom":"ok"},{"Pid":{"node":"foo@bar","id":1,"serial":2,"creation":3}},"#,
+            r#"{"Float":1.5},{"Map":[[{"FixInteger":1},{"Binary":[97]}]]}]"#
+        )
+    );
+    assert_eq!(serde_json::from_str::<Tuple>(&json).unwrap(), tuple);
+
+    assert!(serde_json::from_str::<Float>("1e999").is_err());
+}
+
+#[test]
+fn text_eq_test() {
+    let byte_list = Term::from(ByteList::from("ok"));
+    let binary = Term::from(Binary::from(&b"ok"[..]));
+    let list = Term::from(List::from(vec![Term::from(111), Term::from(107)]));
+    for a in [&byte_list, &binary, &list] {
+        for b in [&byte_list, &binary, &list] {
+            assert!(a.text_eq(b), "{} {}", a, b);
+        }
+        assert_eq!(a.as_text_bytes().unwrap().as_ref(), b"ok");
+        assert_eq!(*a, b"ok"[..]);
+        assert_ne!(*a, b"ko"[..]);
+    }
+    assert_ne!(byte_list, binary);
+    assert_ne!(binary, list);
+    assert_ne!(list, byte_list);
+
+    let empty = [
+        Term::from(ByteList::from("")),
+        Term::from(Binary::from(&b""[..])),
+        Term::from(List::nil()),
+    ];
+    assert!(empty.iter().all(|t| t.text_eq(&empty[0]) && *t == b""[..]));
+
+    let out_of_range = Term::from(List::from(vec![Term::from(111), Term::from(256)]));
+    assert_eq!(out_of_range.as_text_bytes(), None);
+    assert!(!out_of_range.text_eq(&byte_list));
+    assert_ne!(out_of_range, b"o\0"[..]);
+    let improper = Term::from(ImproperList::from((vec![Term::from(111)], Term::from(107))));
+    assert_eq!(improper.as_text_bytes(), None);
+    assert!(!Term::from(Atom::from("ok")).text_eq(&byte_list));
+}
+
+#[test]
+fn iolist_test() {
+    // The results of `iolist_to_binary/1` in Erlang.
+    let iolists: [(&str, &[u8]); 12] = [
+        ("<<\"abc\">>", b"abc"),
+        ("[]", b""),
+        ("\"abc\"", b"abc"),
+        ("[97, <<\"bc\">>, \"de\"]", b"abcde"),
+        ("[[[[]]], [1, [2, <<3>>]]]", &[1, 2, 3]),
+        ("[0, 255, <<>>, \"\"]", &[0, 255]),
+        ("[<<\"a\">> | <<\"b\">>]", b"ab"),
+        ("[[97 | <<\"b\">>], 99 | <<\"d\">>]", b"abcd"),
+        ("[\"ab\", [] | <<\"c\">>]", b"abc"),
+        ("[[] | <<>>]", b""),
+        ("[<<\"a\">>, [\"b\" | <<\"c\">>] | []]", b"abc"),
+        (
+            "[1, [2, [3 | <<4, 5>>] | <<6>>] | <<7>>]",
+            &[1, 2, 3, 4, 5, 6, 7],
+        ),
+    ];
+    for (text, bytes) in iolists {
+        let term = parse_term(text).unwrap();
+        assert!(term.is_iolist(), "{}", text);
+        assert_eq!(term.iolist_to_bytes().unwrap(), bytes, "{}", text);
+    }
+
+    // The terms for which `iolist_to_binary/1` fails with `badarg`.
+    use IolistPathSegment::{ListElement, ListTail};
+    let not_iolists: [(&str, &str, &[IolistPathSegment]); 9] = [
+        ("ok", "'ok'", &[]),
+        ("1", "1", &[]),
+        ("<<1:4>>", "<<1:4>>", &[]),
+        ("[256]", "256", &[ListElement(0)]),
+        ("[1, -1]", "-1", &[ListElement(1)]),
+        (
+            "[<<\"a\">>, [{x}]]",
+            "{'x'}",
+            &[ListElement(0), ListElement(1)],
+        ),
+        ("[1 | 2]", "2", &[ListTail]),
+        ("[[1 | ok]]", "'ok'", &[ListTail, ListElement(0)]),
+        ("[<<1, 2:4>>]", "<<1,2:4>>", &[ListElement(0)]),
+    ];
+    for (text, found, path) in not_iolists {
+        let term = parse_term(text).unwrap();
+        assert!(!term.is_iolist(), "{}", text);
+        let e = term.iolist_to_bytes().unwrap_err();
+        assert_eq!((e.found.preview.as_str(), &e.path[..]), (found, path));
+    }
+    let e = parse_term("[[1 | ok]]")
+        .unwrap()
+        .iolist_to_bytes()
+        .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "'ok' is not allowed in an iolist at tail of list, in element 0 of list"
+    );
+
+    // Deep iolists are flattened without recursion.
+    let mut deep = Term::from(Binary::from(&b"x"[..]));
+    for _ in 0..10_000 {
+        deep = Term::from(List::from(vec![deep, Term::from(121)]));
+    }
+    let bytes = deep.iolist_to_bytes().unwrap();
+    assert_eq!(bytes.len(), 10_001);
+    assert_eq!(&bytes[..3], b"xyy");
+}
+
+#[test]
+fn into_term_test() {
+    assert_eq!(Term::from(Atom::from("a")), Atom::from("a").into_term());
+    assert_eq!(
+        Term::from(List::from(vec![Term::from(1), Term::from(2)])),
+        vec![1, 2].into_term()
+    );
+    assert_eq!(
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("ok")),
+            Term::from(List::from(vec![Term::from(true)]))
+        ])),
+        (Atom::from("ok"), vec![true]).into_term()
+    );
+
+    let map = std::collections::HashMap::from([(Atom::from("a"), 1)]);
+    assert_eq!(
+        Term::from(Map::from([(Term::from(Atom::from("a")), Term::from(1))])),
+        map.into_term()
+    );
+}
+
+#[test]
+fn into_term_tuple_test() {
+    use eetf::string_convert::Convert;
+
+    let from = Pid::new("client@localhost", 85, 0, 1);
+    let tag = Reference::new("client@localhost", vec![1, 2, 3], 1);
+    let key = String::from("user:1");
+    let request = (
+        "$gen_call".to_term_atom(),
+        (from.clone(), tag.clone()).into_term_tuple(),
+        (
+            "get".to_term_atom(),
+            key.to_term_binary(),
+            "none".to_term_bytelist(),
+            5000,
+        )
+            .into_term_tuple(),
+    )
+        .into_term_tuple();
+
+    let expected = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("$gen_call")),
+        Term::from(Tuple::from(vec![Term::from(from), Term::from(tag)])),
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("get")),
+            Term::from(Binary::from(&b"user:1"[..])),
+            Term::from(ByteList::from("none")),
+            Term::from(5000),
+        ])),
+    ]));
+    assert_eq!(request, expected);
+}
+
+#[test]
+fn from_term_test() {
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(List::from(vec![Term::from(1), Term::from(2)])),
+    ]));
+    assert_eq!(
+        Ok((Atom::from("ok"), vec![1u8, 2])),
+        <(Atom, Vec<u8>)>::from_term(&term)
+    );
+    assert_eq!(
+        Ok(vec![1i64, 2]),
+        Vec::from_term(&Term::from(ByteList::from(vec![1, 2])))
+    );
+    assert_eq!(
+        Ok("foo".to_string()),
+        String::from_term(&Term::from(Binary::from("foo".as_bytes())))
+    );
+    assert_eq!(
+        Ok(u64::MAX),
+        u64::from_term(&Term::from(BigInteger::from(u64::MAX)))
+    );
+
+    // Errors carry the path and the expected/actual type names
+    let term = Term::from(List::from(vec![
+        Term::from(Atom::from("a")),
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("b")),
+            Term::from(1),
+        ])),
+    ]));
+    let e = <Vec<(Atom, Atom)>>::from_term(&term).unwrap_err();
+    assert_eq!(e.expected, "Tuple of arity 2");
+    assert_eq!(e.actual, "Atom");
+    assert_eq!(e.path, vec![FromTermPathSegment::ListElement(0)]);
+
+    let e = <Vec<(Atom, Atom)>>::from_term(&Term::from(List::from(vec![Term::from(Tuple::from(
+        vec![Term::from(Atom::from("b")), Term::from(1)],
+    ))])))
+    .unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "expected Atom, found FixInteger at element 1 of tuple, in element 0 of list"
+    );
+
+    let e = u8::from_term(&Term::from(300)).unwrap_err();
+    assert_eq!(e.to_string(), "expected u8, found 300 (out of range)");
+}
+
+#[test]
+fn list_conversion_test() {
+    let atom = |name: &str| Term::from(Atom::from(name));
+    let pair = |k: &str, v: Term| Term::from(Tuple::from(vec![atom(k), v]));
+
+    let list = List::from(vec![Term::from(1), Term::from(2), atom("three")]);
+    let e = list.try_map::<i32>().unwrap_err();
+    assert_eq!(e.path, vec![FromTermPathSegment::ListElement(2)]);
+    assert_eq!(
+        e.to_string(),
+        "expected i32, found Atom at element 2 of list"
+    );
+    assert_eq!(list.first_as::<i32>(), Ok(Some(1)));
+    assert!(list.first_as::<Atom>().is_err());
+    assert_eq!(List::nil().first_as::<i32>(), Ok(None));
+
+    let options = List::from(vec![
+        pair("timeout", Term::from(5000)),
+        atom("debug"),
+        pair("name", atom("worker")),
+    ]);
+    let groups = options.partition_by_variant();
+    assert_eq!(groups["Atom"], [&atom("debug")]);
+    assert_eq!(
+        groups["Tuple"],
+        [
+            &pair("timeout", Term::from(5000)),
+            &pair("name", atom("worker"))
+        ]
+    );
+
+    let pairs = options.pairs().collect::<Vec<_>>();
+    assert_eq!(pairs[0], Ok((&atom("timeout"), &Term::from(5000))));
+    assert_eq!(
+        pairs[1].clone().unwrap_err().to_string(),
+        "expected Tuple of arity 2, found Atom at element 1 of list"
+    );
+    let triple = List::from(vec![Term::from(Tuple::from(vec![
+        atom("a"),
+        atom("b"),
+        atom("c"),
+    ]))]);
+    let e = triple.pairs().next().unwrap().unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "expected Tuple of arity 2, found Tuple of arity 3 at element 0 of list"
+    );
+}
+
+#[test]
+fn distribution_message_test() {
+    use eetf::dist::AtomCache;
+
+    let atom = |name: &str| Term::from(Atom::from(name));
+    let pid = |node: &str, id| {
+        Term::from(Pid {
+            node: Atom::from(node),
+            id,
+            serial: 0,
+            creation: 1700000000,
+        })
+    };
+    let mut cache = AtomCache::new();
+
+    // The messages are assembled by hand following the distribution header specification.
+
+    // REG_SEND {6, <a@localhost.85.0>, '', net_kernel} with payload {ping},
+    // creating three cache entries (in segments 0, 1 and 0).
+    #[rustfmt::skip]
+    let bytes = [
+        131, 68, 3,
+        0x98, 0x08, // flags: new/0, new/1, new/0, short atoms
+        12, 11, b'a', b'@', b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't',
+        200, 0,
+        3, 10, b'n', b'e', b't', b'_', b'k', b'e', b'r', b'n', b'e', b'l',
+        104, 4, 97, 6, 88, 82, 0, 0, 0, 0, 85, 0, 0, 0, 0, 101, 83, 241, 0, 82, 1, 82, 2,
+        104, 1, 119, 4, b'p', b'i', b'n', b'g',
+    ];
+    let message = Decoder::new(Cursor::new(&bytes))
+        .decode_distribution_message(&mut cache)
+        .unwrap();
+    assert!(!message.header.long_atoms);
+    let refs = &message.header.atom_cache_refs;
+    assert_eq!(
+        refs.iter().map(|r| r.cache_index).collect::<Vec<_>>(),
+        [12, 456, 3]
+    );
+    assert!(refs.iter().all(|r| r.new_entry));
+    assert_eq!(
+        message.control,
+        Term::from(Tuple::from(vec![
+            Term::from(6),
+            pid("a@localhost", 85),
+            atom(""),
+            atom("net_kernel"),
+        ]))
+    );
+    assert_eq!(
+        message.payload,
+        Some(Term::from(Tuple::from(vec![atom("ping")])))
+    );
+    assert_eq!(cache.get(456), Some(&Atom::from("")));
+
+    // SEND {2, '', <a@localhost.86.0>} with payload pong, reusing two cache entries.
+    #[rustfmt::skip]
+    let bytes = [
+        131, 68, 2,
+        0x00, 0x00, // flags: old/0, old/0, short atoms
+        12,
+        3,
+        104, 3, 97, 2, 119, 0, 88, 82, 0, 0, 0, 0, 86, 0, 0, 0, 0, 101, 83, 241, 0,
+        82, 1,
+    ];
+    let message = Decoder::new(Cursor::new(&bytes))
+        .decode_distribution_message(&mut cache)
+        .unwrap();
+    assert!(message.header.atom_cache_refs.iter().all(|r| !r.new_entry));
+    assert_eq!(
+        message.control,
+        Term::from(Tuple::from(vec![
+            Term::from(2),
+            atom(""),
+            pid("a@localhost", 86)
+        ]))
+    );
+    assert_eq!(message.payload, Some(atom("net_kernel")));
+
+    // LINK {1, <node.1.0>, <node.2.0>} without payload, using long atoms (segment 2).
+    #[rustfmt::skip]
+    let bytes = [
+        131, 68, 1,
+        0x1A, // flags: new/2, long atoms
+        7, 0, 4, b'n', b'o', b'd', b'e',
+        104, 3, 97, 1,
+        88, 82, 0, 0, 0, 0, 1, 0, 0, 0, 0, 101, 83, 241, 0,
+        88, 82, 0, 0, 0, 0, 2, 0, 0, 0, 0, 101, 83, 241, 0,
+    ];
+    let message = Decoder::new(Cursor::new(&bytes))
+        .decode_distribution_message(&mut cache)
+        .unwrap();
+    assert!(message.header.long_atoms);
+    assert_eq!(message.header.atom_cache_refs[0].cache_index, 519);
+    assert_eq!(
+        message.control,
+        Term::from(Tuple::from(vec![
+            Term::from(1),
+            pid("node", 1),
+            pid("node", 2)
+        ]))
+    );
+    assert_eq!(message.payload, None);
+
+    // No atom cache references; the terms may carry a version number.
+    let bytes = [131, 68, 0, 131, 104, 1, 97, 1, 131, 97, 2];
+    let message = Decoder::new(Cursor::new(&bytes))
+        .decode_distribution_message(&mut cache)
+        .unwrap();
+    assert!(message.header.atom_cache_refs.is_empty());
+    assert_eq!(
+        message.control,
+        Term::from(Tuple::from(vec![Term::from(1)]))
+    );
+    assert_eq!(message.payload, Some(Term::from(2)));
+
+    // Errors
+    let mut empty = AtomCache::new();
+    let bytes = [131, 68, 1, 0x00, 12, 104, 1, 82, 0];
+    assert!(matches!(
+        Decoder::new(Cursor::new(&bytes)).decode_distribution_message(&mut empty),
+        Err(DecodeError::EmptyAtomCacheEntry { index: 12 })
+    ));
+    let bytes = [131, 68, 0, 104, 1, 82, 0];
+    assert!(matches!(
+        Decoder::new(Cursor::new(&bytes)).decode_distribution_message(&mut empty),
+        Err(DecodeError::UnknownAtomCacheRef { index: 0 })
+    ));
+    assert!(matches!(
+        Decoder::new(Cursor::new(&[131, 97, 1])).decode_distribution_message(&mut empty),
+        Err(DecodeError::UnexpectedTag { tag: 97, .. })
+    ));
+    // Outside of a distribution message, there is no atom cache to refer to.
+    assert!(matches!(
+        Term::decode(&[131, 82, 0][..]),
+        Err(DecodeError::UnknownAtomCacheRef { index: 0 })
+    ));
+    #[cfg(feature = "tokio-async")]
+    assert!(matches!(
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(AsyncDecoder::new(&[131, 82, 0][..]).decode()),
+        Err(DecodeError::UnknownAtomCacheRef { index: 0 })
+    ));
+}
+
+#[test]
+fn distribution_message_encode_test() {
+    use eetf::dist::{AtomCache, MAX_ATOM_CACHE_REFS};
+
+    fn round_trip(
+        control: &Term,
+        payload: Option<&Term>,
+        sender: &mut AtomCache,
+        receiver: &mut AtomCache,
+    ) -> eetf::dist::DistMessage {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf)
+            .encode_distribution_message(control, payload, sender)
+            .unwrap();
+        let message = Decoder::new(Cursor::new(&buf))
+            .decode_distribution_message(receiver)
+            .unwrap();
+        assert_eq!(&message.control, control);
+        assert_eq!(message.payload.as_ref(), payload);
+        assert_eq!(sender, receiver);
+        message
+    }
+
+    let atom = |name: &str| Term::from(Atom::from(name));
+    let pid = Term::from(Pid::new("a@localhost", 85, 0, 1));
+    let control = Term::from(Tuple::from(vec![
+        Term::from(6),
+        pid.clone(),
+        atom(""),
+        atom("net_kernel"),
+    ]));
+    let payload = Term::from(Tuple::from(vec![atom("ping"), pid]));
+    let mut sender = AtomCache::new();
+    let mut receiver = AtomCache::new();
+
+    // The first message populates the cache, and the second one reuses it.
+    let message = round_trip(&control, Some(&payload), &mut sender, &mut receiver);
+    assert_eq!(message.header.atom_cache_refs.len(), 4);
+    assert!(message.header.atom_cache_refs.iter().all(|r| r.new_entry));
+    let message = round_trip(&control, Some(&payload), &mut sender, &mut receiver);
+    assert!(message.header.atom_cache_refs.iter().all(|r| !r.new_entry));
+    let message = round_trip(&atom("net_kernel"), None, &mut sender, &mut receiver);
+    assert!(!message.header.atom_cache_refs[0].new_entry);
+
+    // Long atoms
+    let long = atom(&"x".repeat(300));
+    let message = round_trip(&long, Some(&atom("ping")), &mut sender, &mut receiver);
+    assert!(message.header.long_atoms);
+
+    // At most 255 atoms are cached; the rest are encoded inline.
+    let many = Term::from(List::from(
+        (0..400)
+            .map(|i| atom(&format!("atom_{}", i)))
+            .collect::<Vec<_>>(),
+    ));
+    let message = round_trip(&many, None, &mut sender, &mut receiver);
+    assert!(message.header.atom_cache_refs.len() <= MAX_ATOM_CACHE_REFS);
+    assert!(message.header.atom_cache_refs.len() > MAX_ATOM_CACHE_REFS / 2);
+
+    // Colliding atoms evict each other.
+    let name = |i: usize| format!("a{}", i);
+    let slot = |i: usize| AtomCache::slot_for(&Atom::from(name(i)));
+    let (a, b) = (0..)
+        .flat_map(|i| (0..i).map(move |j| (j, i)))
+        .find(|&(j, i)| slot(j) == slot(i))
+        .unwrap();
+    let (a_name, b_name) = (name(a), name(b));
+    let (a_name, b_name) = (a_name.as_str(), b_name.as_str());
+    let (a, b) = (atom(a_name), atom(b_name));
+    let both = Term::from(Tuple::from(vec![a.clone(), b.clone()]));
+    let message = round_trip(&both, None, &mut sender, &mut receiver);
+    assert_eq!(message.header.atom_cache_refs.len(), 1);
+    let message = round_trip(&b, None, &mut sender, &mut receiver);
+    assert!(message.header.atom_cache_refs[0].new_entry);
+    let message = round_trip(&a, None, &mut sender, &mut receiver);
+    assert!(message.header.atom_cache_refs[0].new_entry);
+    let index = message.header.atom_cache_refs[0].cache_index;
+    assert_eq!(receiver.get(index).map(|x| x.as_str()), Some(a_name));
+
+    // Atoms in funs
+    let fun = Term::from(InternalFun::New {
+        module: Atom::from("net_kernel"),
+        arity: 1,
+        pid: Pid::new("a@localhost", 85, 0, 1),
+        free_vars: vec![atom("ping"), atom("fun_var")],
+        index: 0,
+        uniq: [0; 16],
+        old_index: 0,
+        old_uniq: 0,
+    });
+    round_trip(&fun, Some(&atom("fun_var")), &mut sender, &mut receiver);
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_distribution_message_encode_test() {
+    use eetf::dist::AtomCache;
+
+    let control = Term::from(Tuple::from(vec![
+        Term::from(2),
+        Term::from(Atom::from("")),
+        Term::from(Pid::new("a@localhost", 86, 0, 1)),
+    ]));
+    let payload = Term::from(Atom::from("pong"));
+    let mut sender = AtomCache::new();
+    let mut buf = Vec::new();
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(AsyncEncoder::new(&mut buf).encode_distribution_message(
+            &control,
+            Some(&payload),
+            &mut sender,
+        ))
+        .unwrap();
+
+    let mut receiver = AtomCache::new();
+    let message = Decoder::new(Cursor::new(&buf))
+        .decode_distribution_message(&mut receiver)
+        .unwrap();
+    assert_eq!(message.control, control);
+    assert_eq!(message.payload, Some(payload));
+    assert_eq!(sender, receiver);
+}
+
+#[test]
+fn borrowed_io_test() {
+    let terms = [Term::from(Atom::from("first")), Term::from(2)];
+    let mut writer = Vec::new();
+    for term in &terms {
+        Encoder::new(&mut writer).encode(term).unwrap();
+    }
+    let mut reader = Cursor::new(&writer);
+    assert_eq!(Decoder::new(&mut reader).decode().unwrap(), terms[0]);
+    assert_eq!(Decoder::new(&mut reader).decode().unwrap(), terms[1]);
+    assert_eq!(reader.position() as usize, writer.len());
+
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let (client, server) = tokio::io::duplex(64);
+            let (mut client_reader, mut client_writer) = tokio::io::split(client);
+            let (mut server_reader, mut server_writer) = tokio::io::split(server);
+            for term in &terms {
+                AsyncEncoder::new(&mut client_writer)
+                    .encode(term)
+                    .await
+                    .unwrap();
+            }
+            // The halves stay with their owners, and are only borrowed by the codecs.
+            for term in &terms {
+                let decoded = AsyncDecoder::new(&mut server_reader).decode().await;
+                assert_eq!(decoded.unwrap(), *term);
+                AsyncEncoder::new(&mut server_writer)
+                    .encode(term)
+                    .await
+                    .unwrap();
+            }
+            for term in &terms {
+                let decoded = AsyncDecoder::new(&mut client_reader).decode().await;
+                assert_eq!(decoded.unwrap(), *term);
+            }
+        });
+}
+
+#[test]
+fn buffered_encoder_test() {
+    use std::io::Write;
+
+    #[derive(Debug, Default)]
+    struct WriteCounting {
+        bytes: Vec<u8>,
+        writes: usize,
+    }
+    impl Write for WriteCounting {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.bytes.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let term = Term::from(List::from((0..10_000).map(Term::from).collect::<Vec<_>>()));
+    let mut unbuffered = WriteCounting::default();
+    Encoder::new(&mut unbuffered).encode(&term).unwrap();
+    assert!(unbuffered.writes > 10_000);
+
+    let mut encoder = Encoder::buffered(WriteCounting::default(), 8 * 1024);
+    encoder.encode_next(&term).unwrap();
+    encoder.encode_next(&term).unwrap();
+    encoder.flush().unwrap();
+    let buffered = encoder.into_inner().into_inner().unwrap();
+    assert!(buffered.writes < 20);
+    let twice = [unbuffered.bytes.clone(), unbuffered.bytes].concat();
+    assert_eq!(buffered.bytes, twice);
+}
+
+#[test]
+fn encode_from_iter_test() {
+    // The elements are made while they are encoded.
+    let mut bytes = Vec::new();
+    Encoder::new(&mut bytes)
+        .encode_list_from_iter((0..1_000_000).map(|i| i * 3))
+        .unwrap();
+    let decoded: List = decode(&bytes).try_into().unwrap();
+    assert_eq!(decoded.elements.len(), 1_000_000);
+    assert!(decoded
+        .elements
+        .iter()
+        .enumerate()
+        .all(|(i, e)| *e == Term::from(i as i32 * 3)));
+
+    let mut empty = Vec::new();
+    Encoder::new(&mut empty)
+        .encode_list_from_iter(Vec::<Term>::new())
+        .unwrap();
+    assert_eq!(empty, encode(Term::from(List::nil())));
+
+    // Without a length
+    let atoms = || {
+        (0..1000)
+            .filter(|i| i % 3 == 0)
+            .map(|i| Atom::from(format!("a{}", i)))
+    };
+    let mut buffered = Vec::new();
+    Encoder::new(&mut buffered)
+        .encode_list_from_buffered_iter(atoms())
+        .unwrap();
+    let expected = List::from(atoms().map(Term::from).collect::<Vec<_>>());
+    assert_eq!(decode(&buffered), Term::from(expected));
+
+    // Maps
+    let entries = [("a", 1), ("b", 2), ("c", 3)];
+    let map = Map::from(
+        entries
+            .iter()
+            .map(|&(k, v)| (Term::from(Atom::from(k)), Term::from(v)))
+            .collect::<std::collections::HashMap<_, _>>(),
+    );
+    let mut bytes = Vec::new();
+    Encoder::new(&mut bytes)
+        .encode_map_from_iter(entries.iter().map(|&(k, v)| (Atom::from(k), v)))
+        .unwrap();
+    assert_eq!(decode(&bytes), Term::from(map.clone()));
+    let options = EncoderOptions {
+        deterministic: true,
+        ..EncoderOptions::default()
+    };
+    let mut bytes = Vec::new();
+    Encoder::with_options(&mut bytes, options)
+        .encode_map_from_iter(entries.iter().rev().map(|&(k, v)| (Atom::from(k), v)))
+        .unwrap();
+    let mut expected = Vec::new();
+    Encoder::with_options(&mut expected, options)
+        .encode(&Term::from(map))
+        .unwrap();
+    assert_eq!(bytes, expected);
+
+    // An iterator whose length is wrong
+    struct Lying(std::ops::Range<i32>, usize);
+    impl Iterator for Lying {
+        type Item = i32;
+        fn next(&mut self) -> Option<i32> {
+            self.0.next()
+        }
+    }
+    impl ExactSizeIterator for Lying {
+        fn len(&self) -> usize {
+            self.1
+        }
+    }
+    for (range, len) in [(0..3, 4), (0..3, 2)] {
+        match Encoder::new(Vec::new()).encode_list_from_iter(Lying(range, len)) {
+            Err(EncodeError::IteratorLengthMismatch { len: n }) => assert_eq!(n, len),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut bytes = Vec::new();
+            let mut encoder = AsyncEncoder::new(&mut bytes);
+            encoder.encode_list_from_iter(0..1000).await.unwrap();
+            encoder
+                .encode_list_from_buffered_iter((0..1000).filter(|i| i % 2 == 0))
+                .await
+                .unwrap();
+            encoder
+                .encode_map_from_iter([(1, Atom::from("one"))])
+                .await
+                .unwrap();
+            let mut decoder = Decoder::new(&bytes[..]);
+            let list = List::from((0..1000).map(Term::from).collect::<Vec<_>>());
+            assert_eq!(decoder.decode_next().unwrap(), Term::from(list));
+            let list: List = decoder.decode_next().unwrap().try_into().unwrap();
+            assert_eq!(list.elements.len(), 500);
+            let map = Map::from([(Term::from(1), Term::from(Atom::from("one")))]);
+            assert_eq!(decoder.decode_next().unwrap(), Term::from(map));
+        });
+}
+
+#[test]
+fn encode_sequence_test() {
+    fn encode_sequence<S: ToTermSequence + ?Sized>(sequence: &S) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = Vec::new();
+        Encoder::new(&mut bytes).encode_sequence_as_list(sequence)?;
+        Ok(bytes)
+    }
+
+    let queue = std::collections::VecDeque::from([
+        (Atom::from("a"), Term::from(1)),
+        (Atom::from("b"), Term::from(Binary::from(&b"x"[..]))),
+    ]);
+    let expected = List::from(
+        queue
+            .iter()
+            .cloned()
+            .map(IntoTerm::into_term)
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        encode_sequence(&queue).unwrap(),
+        encode(Term::from(expected))
+    );
+
+    let numbers: &[i64] = &[1, -2, i64::MAX];
+    let expected = List::from(numbers.iter().map(|&i| Term::from(i)).collect::<Vec<_>>());
+    assert_eq!(
+        encode_sequence(numbers).unwrap(),
+        encode(Term::from(expected.clone()))
+    );
+    assert_eq!(
+        encode_sequence(&expected).unwrap(),
+        encode(Term::from(expected))
+    );
+    assert_eq!(
+        encode_sequence(&Vec::<i64>::new()).unwrap(),
+        encode(Term::from(List::nil()))
+    );
+
+    // Improper lists
+    let improper = ImproperList::from((vec![Term::from(1)], Term::from(Atom::from("tail"))));
+    assert_eq!(
+        encode_sequence(&improper).unwrap(),
+        encode(Term::from(improper))
+    );
+
+    // A sequence whose count is wrong
+    struct Lying(usize);
+    impl ToTermSequence for Lying {
+        fn element_count(&self) -> usize {
+            self.0
+        }
+        fn encode_elements(&self, sink: &mut dyn ElementSink) -> EncodeResult {
+            (0..3).try_for_each(|i| sink.encode_element(&Term::from(i)))
+        }
+    }
+    for len in [2, 4] {
+        match encode_sequence(&Lying(len)) {
+            Err(EncodeError::IteratorLengthMismatch { len: n }) => assert_eq!(n, len),
+            other => panic!("{:?}", other),
+        }
+    }
+}
+
+#[test]
+fn buf_read_decode_test() {
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("event")),
+        Term::from(Binary::from(vec![7; 100])),
+        Term::from(List::from(vec![
+            Term::from(1 << 20),
+            Term::from(Float::try_from(2.5).unwrap()),
+        ])),
+        Term::from(BigInteger {
+            value: num::BigInt::from(1) << 100,
+        }),
+    ]));
+    let mut bytes = encode_to_vec(&term).unwrap();
+    term.encode_compressed(&mut bytes).unwrap();
+    // Small buffers make the fields and payloads straddle their ends.
+    for capacity in [1, 2, 3, 5, 8, 64, 1024] {
+        let reader = std::io::BufReader::with_capacity(capacity, &bytes[..]);
+        let mut decoder = Decoder::from_buf_read(reader);
+        assert_eq!(decoder.decode_next().unwrap(), term);
+        assert_eq!(decoder.decode_next().unwrap(), term);
+    }
+
+    for len in 0..bytes.len() / 2 {
+        let reader = std::io::BufReader::with_capacity(4, &bytes[..len]);
+        let error = Decoder::from_buf_read(reader).decode().unwrap_err();
+        let expected = Decoder::new(&bytes[..len]).decode().unwrap_err();
+        assert_eq!(error.to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn skip_term_test() {
+    let terms = [
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("event")),
+            Term::from(Binary::from(vec![7; 100_000])),
+            Term::from(ByteList::from("abc")),
+        ])),
+        Term::from(Map::from([(
+            Term::from(Pid::new("a@localhost", 1, 2, 3)),
+            Term::from(List::from(vec![Term::from(BigInteger {
+                value: num::BigInt::from(1) << 100,
+            })])),
+        )])),
+        Term::from(ImproperList {
+            elements: vec![Term::from(ExternalFun::from(("lists", "map", 2)))],
+            last: Box::new(Term::from(Reference::from(("a@localhost", vec![1, 2, 3])))),
+        }),
+    ];
+    let mut bytes = Vec::new();
+    for term in &terms {
+        term.encode(&mut bytes).unwrap();
+        term.encode_compressed(&mut bytes).unwrap();
+    }
+    let last = Term::from(Atom::from("last"));
+    last.encode(&mut bytes).unwrap();
+
+    let mut reader = Cursor::new(&bytes);
+    let mut decoder = Decoder::new(&mut reader);
+    for _ in 0..terms.len() * 2 {
+        decoder.skip_term().unwrap();
+    }
+    assert_eq!(decoder.decode_next().unwrap(), last);
+
+    let mut decoder = Decoder::new(Cursor::new(&bytes));
+    for _ in 0..terms.len() * 2 {
+        decoder.skip_term_fast().unwrap();
+    }
+    assert_eq!(decoder.decode_next().unwrap(), last);
+
+    let error = Decoder::new(&bytes[..100]).skip_term().unwrap_err();
+    assert!(matches!(error, DecodeError::Io(_)), "{:?}", error);
+}
+
+#[test]
+fn decoder_poisoning_test() {
+    // A term with an unknown tag between two valid terms.
+    let mut bytes = encode(Term::from(1));
+    bytes.extend([131, 104, 2, 97, 1, 0]);
+    bytes.extend(encode(Term::from(2)));
+    let mut decoder = Decoder::new(Cursor::new(&bytes));
+    assert_eq!(decoder.decode_next().unwrap(), Term::from(1));
+    assert!(!decoder.is_poisoned());
+    assert!(matches!(
+        decoder.decode_next(),
+        Err(DecodeError::UnknownTag { tag: 0 })
+    ));
+    assert!(decoder.is_poisoned());
+    assert!(matches!(decoder.decode_next(), Err(DecodeError::Poisoned)));
+    assert!(matches!(decoder.skip_term(), Err(DecodeError::Poisoned)));
+
+    // The unknown tag was the last byte of its term, so the next term follows.
+    decoder.reset();
+    assert!(!decoder.is_poisoned());
+    assert_eq!(decoder.decode_next().unwrap(), Term::from(2));
+}
+
+#[test]
+fn decode_report_test() {
+    use eetf::report::{ReportPathSegment, WarningKind};
+
+    let report = |bytes: &[u8]| Decoder::new(bytes).decode_with_report().unwrap().1;
+    let strict = |bytes: &[u8]| {
+        Decoder::with_options(bytes, DecoderOptions::new().with_strict(true)).decode()
+    };
+
+    // A clean term has no warnings, and strict mode accepts it.
+    let clean = [131, 104, 2, 119, 2, b'o', b'k', 97, 1];
+    assert!(report(&clean).is_clean());
+    assert!(strict(&clean).is_ok());
+
+    // {ok, 'abc'} with the atom as an ATOM_EXT.
+    let atom_ext = [131, 104, 2, 119, 2, b'o', b'k', 100, 0, 3, b'a', b'b', b'c'];
+    // A REFERENCE_EXT.
+    let reference_ext = [131, 101, 119, 1, b'a', 0, 0, 0, 7, 1];
+    // #{1 => a, 1 => b}
+    let duplicate_key = [
+        131, 116, 0, 0, 0, 2, 97, 1, 119, 1, b'a', 97, 1, 119, 1, b'b',
+    ];
+    // [1] with the element as an INTEGER_EXT, 1 as a SMALL_BIG_EXT, and 256 as a SMALL_BIG_EXT
+    // with a leading zero byte.
+    let integer_ext = [131, 108, 0, 0, 0, 1, 98, 0, 0, 0, 1, 106];
+    let small_big = [131, 110, 1, 0, 1];
+    let zero_byte = [131, 110, 5, 0, 0, 0, 0, 128, 0];
+    // An atom of 256 characters, as an ATOM_UTF8_EXT.
+    let mut long_atom = vec![131, 118, 1, 0];
+    long_atom.extend([b'x'; 256]);
+    // A STRING_EXT of control characters.
+    let string_ext = [131, 107, 0, 3, 1, 2, 3];
+
+    let cases: Vec<(&[u8], WarningKind, u64, Vec<ReportPathSegment>)> = vec![
+        (
+            &atom_ext,
+            WarningKind::DeprecatedTag { tag: 100 },
+            7,
+            vec![ReportPathSegment::TupleElement(1)],
+        ),
+        (
+            &reference_ext,
+            WarningKind::DeprecatedTag { tag: 101 },
+            1,
+            vec![],
+        ),
+        (
+            &duplicate_key,
+            WarningKind::DuplicateMapKey { key: Term::from(1) },
+            11,
+            vec![ReportPathSegment::MapKey(1)],
+        ),
+        (
+            &integer_ext,
+            WarningKind::NonMinimalInteger { tag: 98 },
+            6,
+            vec![ReportPathSegment::ListElement(0)],
+        ),
+        (
+            &small_big,
+            WarningKind::NonMinimalInteger { tag: 110 },
+            1,
+            vec![],
+        ),
+        (
+            &zero_byte,
+            WarningKind::NonMinimalInteger { tag: 110 },
+            1,
+            vec![],
+        ),
+        (&long_atom, WarningKind::LongAtom { chars: 256 }, 1, vec![]),
+        (&string_ext, WarningKind::NonPrintableString, 1, vec![]),
+    ];
+    for (bytes, kind, offset, path) in cases {
+        let warnings = report(bytes).into_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].kind, kind);
+        assert_eq!(warnings[0].offset, offset);
+        assert_eq!(warnings[0].path, path);
+
+        // Strict mode rejects all but the atom tags and the non-printable strings.
+        match strict(bytes) {
+            Err(DecodeError::Rejected { warning }) => assert_eq!(warning, warnings[0]),
+            Ok(term) => {
+                assert!(!kind.is_strict_error());
+                assert_eq!(term, decode(bytes));
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // The paths go through nested terms: #{k => [1, 1, ..., 1]} with the last 1 of the list
+    // as an INTEGER_EXT.
+    let mut bytes = vec![131, 116, 0, 0, 0, 1, 119, 1, b'k', 108, 0, 0, 0, 100];
+    bytes.extend([97, 1].repeat(99));
+    let last = bytes.len();
+    bytes.extend([98, 0, 0, 0, 1, 106]);
+    let warnings = report(&bytes).into_warnings();
+    assert_eq!(warnings[0].kind, WarningKind::NonMinimalInteger { tag: 98 });
+    assert_eq!(
+        warnings[0].path,
+        [
+            ReportPathSegment::MapValue {
+                key: "'k'".to_owned()
+            },
+            ReportPathSegment::ListElement(99)
+        ]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        format!(
+            "integer not minimally encoded with tag 98 at offset {} (at map value for key 'k' → list[99])",
+            last
+        )
+    );
+
+    // The offsets within a compressed term are those in its inflated body. The body of
+    // `atom_ext` is compressed as a stored deflate block.
+    let body = &atom_ext[1..];
+    let mut compressed = vec![131, 80, 0, 0, 0, body.len() as u8, 0x78, 0x01, 1];
+    compressed.extend((body.len() as u16).to_le_bytes());
+    compressed.extend((!(body.len() as u16)).to_le_bytes());
+    compressed.extend(body);
+    let (a, b) = body.iter().fold((1u32, 0u32), |(a, b), &x| {
+        let a = (a + u32::from(x)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    compressed.extend(((b << 16) | a).to_be_bytes());
+    let warnings = report(&compressed).into_warnings();
+    assert_eq!(warnings[0].kind, WarningKind::DeprecatedTag { tag: 100 });
+    assert_eq!(warnings[0].offset, 6);
+}
+
+#[test]
+fn decoder_position_test() {
+    use std::sync::{Arc, Mutex};
+
+    // A list of 1000 integers, whose fields are read a few bytes at a time.
+    let term = Term::from(List::from((1000..2000).map(Term::from).collect::<Vec<_>>()));
+    let bytes = encode_to_vec(&term).unwrap();
+    assert_eq!(bytes.len(), 5007);
+
+    let positions = Arc::new(Mutex::new(Vec::new()));
+    let sink = positions.clone();
+    let mut decoder =
+        Decoder::new(&bytes[..]).with_progress(100, move |p| sink.lock().unwrap().push(p));
+    assert_eq!(decoder.position(), 0);
+    assert_eq!(decoder.decode_next().unwrap(), term);
+    assert_eq!(decoder.position(), bytes.len() as u64);
+    let positions = positions.lock().unwrap();
+    assert_eq!(positions.len(), 50);
+    assert!(positions
+        .iter()
+        .enumerate()
+        .all(|(i, &p)| p / 100 == i as u64 + 1));
+
+    // Compressed terms count their compressed bytes.
+    let mut compressed = Vec::new();
+    term.encode_compressed(&mut compressed).unwrap();
+    let mut input = compressed.clone();
+    input.extend_from_slice(&bytes);
+    let mut decoder = Decoder::new(&input[..]);
+    assert_eq!(decoder.decode_next().unwrap(), term);
+    assert_eq!(decoder.position(), compressed.len() as u64);
+    decoder.skip_term().unwrap();
+    assert_eq!(decoder.position(), input.len() as u64);
+
+    // Seeking over a binary counts its bytes too.
+    let binary = encode_to_vec(&Term::from(Binary::from(vec![0; 100_000]))).unwrap();
+    let mut decoder = Decoder::new(Cursor::new(&binary));
+    decoder.skip_term_fast().unwrap();
+    assert_eq!(decoder.position(), binary.len() as u64);
+
+    // Limits
+    let len = bytes.len() as u64;
+    let mut decoder = Decoder::new(&bytes[..]).with_bytes_limit(len);
+    assert_eq!(decoder.bytes_limit(), Some(len));
+    assert_eq!(decoder.bytes_remaining(), Some(len));
+    assert_eq!(decoder.decode_next().unwrap(), term);
+    assert_eq!(decoder.bytes_remaining(), Some(0));
+    assert_eq!(Decoder::new(&bytes[..]).bytes_limit(), None);
+    match Decoder::new(&bytes[..]).with_bytes_limit(len - 1).decode() {
+        Err(DecodeError::LimitExceeded { limit, max }) => {
+            assert_eq!((limit, max), ("bytes_limit", bytes.len() - 1));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let calls = Arc::new(Mutex::new(0));
+            let sink = calls.clone();
+            let decoder = AsyncDecoder::new(&bytes[..])
+                .with_progress(100, move |_| *sink.lock().unwrap() += 1);
+            assert_eq!(decoder.decode().await.unwrap(), term);
+            assert_eq!(*calls.lock().unwrap(), 50);
+
+            let mut decoder = AsyncDecoder::new(&binary[..]);
+            assert_eq!(
+                decoder.decode_binary_into(&mut Vec::new()).await.unwrap(),
+                100_000
+            );
+            assert_eq!(decoder.position(), binary.len() as u64);
+
+            let decoder = AsyncDecoder::new(&bytes[..]).with_bytes_limit(len - 1);
+            assert_eq!(decoder.bytes_remaining(), Some(len - 1));
+            assert!(matches!(
+                decoder.decode().await,
+                Err(DecodeError::LimitExceeded { .. })
+            ));
+        });
+}
+
+#[test]
+fn skip_term_fast_seeks_over_binaries() {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    struct Counting<R> {
+        inner: R,
+        read: u64,
+    }
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n as u64;
+            Ok(n)
+        }
+    }
+    impl<R: Seek> Seek for Counting<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    // `{<<0, 0, ...>>, ok}` with a 100 MB binary (left as a hole of the file), then `next`.
+    let len: u32 = 100 << 20;
+    let path = std::env::temp_dir().join(format!("eetf-skip-{}", std::process::id()));
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(&[131, 104, 2, 109]).unwrap();
+    file.write_all(&len.to_be_bytes()).unwrap();
+    file.set_len(8 + u64::from(len)).unwrap();
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(&[119, 2, b'o', b'k']).unwrap();
+    let next = Term::from(Atom::from("next"));
+    next.encode(&mut file).unwrap();
+    drop(file);
+
+    let mut reader = Counting {
+        inner: std::fs::File::open(&path).unwrap(),
+        read: 0,
+    };
+    let mut decoder = Decoder::new(&mut reader);
+    decoder.skip_term_fast().unwrap();
+    assert_eq!(decoder.decode_next().unwrap(), next);
+    assert!(reader.read < 100, "{} bytes were read", reader.read);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn decode_projected_skips_without_buffering() {
+    use eetf::projection::{decode_projected, Projected, Projection};
+    use std::io::Read;
+
+    struct Counting<R> {
+        inner: R,
+        read: u64,
+        largest_buf: usize,
+    }
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n as u64;
+            self.largest_buf = self.largest_buf.max(buf.len());
+            Ok(n)
+        }
+    }
+
+    // `{ok, <<0, 0, ...>>, 7}` with a 10 MB binary, which is never held in memory.
+    let len: u32 = 10 << 20;
+    let mut head = vec![131, 104, 3, 119, 2, b'o', b'k', 109];
+    head.extend_from_slice(&len.to_be_bytes());
+    let mut reader = Counting {
+        inner: Cursor::new(head)
+            .chain(std::io::repeat(0).take(u64::from(len)))
+            .chain(Cursor::new(vec![97, 7])),
+        read: 0,
+        largest_buf: 0,
+    };
+    let projection = Projection::elements(Projection::Skip, [(2, Projection::Decode)]);
+    let projected = decode_projected(&mut reader, &projection).unwrap();
+    assert_eq!(
+        projected,
+        Projected::Tuple(vec![
+            Projected::Skipped { tag: 119, len: 4 },
+            Projected::Skipped {
+                tag: 109,
+                len: 5 + u64::from(len)
+            },
+            Projected::Term(Term::from(7)),
+        ])
+    );
+    assert_eq!(reader.read, 14 + u64::from(len));
+    assert!(reader.largest_buf < 1 << 20, "{}", reader.largest_buf);
+
+    // Maps, raw captures, and projections which do not fit the term.
+    let term = Term::from(Map::from([
+        (Term::from(Atom::from("id")), Term::from(1)),
+        (Term::from(Atom::from("n")), Term::from(2)),
+    ]));
+    let bytes = encode(term);
+    let projection = Projection::values(
+        Projection::CaptureRaw,
+        [(Atom::from("id"), Projection::Decode)],
+    );
+    let projected = decode_projected(&bytes[..], &projection).unwrap();
+    let id = Term::from(Atom::from("id"));
+    let n = Term::from(Atom::from("n"));
+    assert_eq!(projected.value(&id), Some(&Projected::Term(Term::from(1))));
+    assert_eq!(
+        projected.value(&n),
+        Some(&Projected::Raw(Raw::from(vec![97, 2])))
+    );
+    let projection = Projection::elements(Projection::Decode, []);
+    assert!(matches!(
+        decode_projected(&bytes[..], &projection),
+        Err(DecodeError::UnexpectedType { .. })
+    ));
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn local_async_codec_test() {
+    use std::cell::RefCell;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    // Neither the reader nor the writer is `Send`.
+    struct Shared(Rc<RefCell<Cursor<Vec<u8>>>>);
+    impl AsyncRead for Shared {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut *self.0.borrow_mut()).poll_read(cx, buf)
+        }
+    }
+    impl AsyncWrite for Shared {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Pin::new(&mut *self.0.borrow_mut()).poll_write(cx, buf)
+        }
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut *self.0.borrow_mut()).poll_flush(cx)
+        }
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut *self.0.borrow_mut()).poll_shutdown(cx)
+        }
+    }
+
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(List::from(vec![Term::from(Map::from([(
+            Term::from(1),
+            Term::from(Binary::from(vec![1, 2, 3])),
+        )]))])),
+    ]));
+    let stream = Rc::new(RefCell::new(Cursor::new(Vec::new())));
+    let local = tokio::task::LocalSet::new();
+    let decoded = local.block_on(
+        &tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap(),
+        async {
+            let encoder = LocalAsyncEncoder::new(Shared(stream.clone()));
+            let sent = term.clone();
+            let encode = async move { encoder.encode(&sent).await };
+            tokio::task::spawn_local(encode).await.unwrap().unwrap();
+            stream.borrow_mut().set_position(0);
+            let decode = LocalAsyncDecoder::new(Shared(stream.clone())).decode();
+            tokio::task::spawn_local(decode).await.unwrap()
+        },
+    );
+    assert_eq!(decoded.unwrap(), term);
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_chunked_encode_test() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+
+    let bytes = (0..4 << 20).map(|i| i as u8).collect::<Vec<_>>();
+    let term = Term::from(List::from(vec![
+        Term::from(Binary::from(bytes.clone())),
+        Term::from(BitBinary::from((bytes.clone(), 3))),
+        Term::from(BigInteger {
+            value: num::BigInt::from_bytes_le(num::bigint::Sign::Plus, &bytes),
+        }),
+    ]));
+    let expected = encode(term.clone());
+    let options = EncoderOptions {
+        chunk_size: 64 * 1024,
+        ..EncoderOptions::default()
+    };
+    let chunks = 3 * bytes.len() / options.chunk_size;
+
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            // Counts how often the encoder gives way to other tasks.
+            let ticks = Arc::new(AtomicUsize::new(0));
+            let done = Arc::new(AtomicBool::new(false));
+            let ticker = tokio::spawn({
+                let (ticks, done) = (ticks.clone(), done.clone());
+                async move {
+                    while !done.load(Ordering::SeqCst) {
+                        ticks.fetch_add(1, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                    }
+                }
+            });
+
+            // A writer which is always ready.
+            AsyncEncoder::with_options(tokio::io::sink(), options)
+                .encode(&term)
+                .await
+                .unwrap();
+            assert!(ticks.load(Ordering::SeqCst) >= chunks - 3);
+
+            // A writer which is drained slowly.
+            let (writer, mut reader) = tokio::io::duplex(16 * 1024);
+            let drain = tokio::spawn(async move {
+                let mut received = Vec::new();
+                let mut buf = [0; 4096];
+                loop {
+                    let n = reader.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break received;
+                    }
+                    received.extend_from_slice(&buf[..n]);
+                    tokio::task::yield_now().await;
+                }
+            });
+            let before = ticks.load(Ordering::SeqCst);
+            AsyncEncoder::with_options(writer, options)
+                .encode(&term)
+                .await
+                .unwrap();
+            assert!(ticks.load(Ordering::SeqCst) - before >= chunks - 3);
+            assert_eq!(drain.await.unwrap(), expected);
+
+            done.store(true, Ordering::SeqCst);
+            ticker.await.unwrap();
+        });
+}
+
+#[test]
+fn tagged_tuple_test() {
+    let user = Term::from(TaggedTuple::new(
+        "user",
+        vec![Term::from(7), Term::from(Binary::from(&b"joe"[..]))],
+    ));
+    assert_eq!(user.to_string(), "{'user',7,<<106,111,101>>}");
+    assert_eq!(user.tag_name(), Some("user"));
+    assert_eq!(
+        Term::from(Tuple::from(vec![Term::from(1)])).tag_name(),
+        None
+    );
+    assert_eq!(Term::from(Atom::from("user")).tag_name(), None);
+
+    let parse = |term: &Term| -> Result<(i64, Binary), TaggedTupleError> {
+        let (id, name) = TaggedTuple::parse(term, "user")?.try_into()?;
+        Ok((id, name))
+    };
+    assert_eq!(parse(&user).unwrap(), (7, Binary::from(&b"joe"[..])));
+    let payload = TaggedTuple::parse(&user, "user").unwrap();
+    assert_eq!(payload.len(), 2);
+    assert_eq!(payload[0], Term::from(7));
+
+    // Not a tuple.
+    assert_eq!(
+        parse(&Term::from(1)).unwrap_err(),
+        TaggedTupleError::NotATuple {
+            found: "FixInteger"
+        }
+    );
+
+    // Another tag, a tag which is not an atom, and no tag.
+    let error = Term::from(TaggedTuple::new(
+        "error",
+        vec![Term::from(1), Term::from(2)],
+    ));
+    let e = parse(&error).unwrap_err();
+    assert_eq!(
+        e,
+        TaggedTupleError::WrongTag {
+            expected: "user".to_owned(),
+            found: Some(Term::from(Atom::from("error")))
+        }
+    );
+    assert_eq!(
+        e.to_string(),
+        "expected a tuple tagged 'user', found the tag 'error'"
+    );
+    assert!(matches!(
+        parse(&Term::from(Tuple::from(vec![Term::from(1), Term::from(2)]))),
+        Err(TaggedTupleError::WrongTag {
+            found: Some(Term::FixInteger(_)),
+            ..
+        })
+    ));
+    let e = parse(&Term::from(Tuple::from(vec![]))).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "expected a tuple tagged 'user', found the empty tuple"
+    );
+
+    // Too few or too many elements.
+    let short = Term::from(TaggedTuple::new("user", vec![Term::from(7)]));
+    assert_eq!(
+        parse(&short).unwrap_err(),
+        TaggedTupleError::Arity {
+            expected: 2,
+            found: 1
+        }
+    );
+    let long = Term::from(TaggedTuple::new("user", vec![Term::from(7); 3]));
+    assert!(matches!(
+        parse(&long),
+        Err(TaggedTupleError::Arity {
+            expected: 2,
+            found: 3
+        })
+    ));
+
+    // An element of the wrong type, whose index counts the tag.
+    let bad = Term::from(TaggedTuple::new("user", vec![Term::from(7), Term::from(8)]));
+    match parse(&bad).unwrap_err() {
+        TaggedTupleError::Element(e) => {
+            assert_eq!(e.path, [FromTermPathSegment::TupleElement(2)]);
+        }
+        e => panic!("{:?}", e),
+    }
+}
+
+#[test]
+fn atom_map_test() {
+    let map = Map::from([
+        (Term::from(Atom::from("id")), Term::from(1)),
+        (
+            Term::from(Atom::from("name")),
+            Term::from(Binary::from(b"joe".to_vec())),
+        ),
+    ]);
+
+    // Round trips between `Map` and `AtomMap`.
+    let mut atom_map = AtomMap::try_from(map.clone()).unwrap();
+    assert_eq!(atom_map.len(), 2);
+    assert_eq!(atom_map.get("id"), Some(&Term::from(1)));
+    assert!(atom_map.get("email").is_none());
+    assert_eq!(Map::from(atom_map.clone()), map);
+    assert_eq!(atom_map.insert("id", 2), Some(Term::from(1)));
+    assert_eq!(
+        atom_map.insert(Atom::from("email"), Term::from(Atom::from("none"))),
+        None
+    );
+    assert_eq!(
+        Term::from(atom_map),
+        Term::from(Map::from([
+            (Term::from(Atom::from("id")), Term::from(2)),
+            (
+                Term::from(Atom::from("name")),
+                Term::from(Binary::from(b"joe".to_vec()))
+            ),
+            (
+                Term::from(Atom::from("email")),
+                Term::from(Atom::from("none"))
+            ),
+        ]))
+    );
+
+    // A key which is not an atom is reported, with the map.
+    let mut mixed = map.clone();
+    mixed.map.insert(Term::from(3), Term::from(4));
+    let e = AtomMap::try_from(mixed.clone()).unwrap_err();
+    assert_eq!(e.key, Term::from(3));
+    assert_eq!(e.map, mixed);
+    assert_eq!(e.to_string(), "the map key 3 is not an atom");
+
+    // Decoding.
+    let bytes = encode(Term::from(map.clone()));
+    let decoded: AtomMap = Decoder::new(&bytes[..]).decode_as().unwrap();
+    assert_eq!(Map::from(decoded), map);
+    let mut compressed = Vec::new();
+    Term::from(map.clone())
+        .encode_compressed(&mut compressed)
+        .unwrap();
+    let decoded: AtomMap = Decoder::new(&compressed[..]).decode_as().unwrap();
+    assert_eq!(Map::from(decoded), map);
+    let decoded: Term = Decoder::new(&bytes[..]).decode_as().unwrap();
+    assert_eq!(decoded, Term::from(map.clone()));
+
+    // A map with a non-atom key and a non-map are read whole, so that the next term follows.
+    let mut bytes = encode(Term::from(mixed));
+    bytes.extend(encode(Term::from(1)));
+    bytes.extend(encode(Term::from(map.clone())));
+    let mut decoder = Decoder::new(&bytes[..]);
+    match decoder.decode_as::<AtomMap>() {
+        Err(DecodeError::UnexpectedType { found, expected }) => {
+            assert_eq!(found.preview, "3");
+            assert_eq!(expected, "atom");
+        }
+        other => panic!("{:?}", other),
+    }
+    decoder.reset();
+    match decoder.decode_as::<AtomMap>() {
+        Err(DecodeError::UnexpectedType { found, expected }) => {
+            assert_eq!(found.preview, "1");
+            assert_eq!(expected, "AtomMap");
+        }
+        other => panic!("{:?}", other),
+    }
+    decoder.reset();
+    assert_eq!(Map::from(decoder.decode_as::<AtomMap>().unwrap()), map);
+
+    // The values below the raw depth are kept raw.
+    let bytes = encode(Term::from(map));
+    let decoded: AtomMap = Decoder::with_options(&bytes[..], DecoderOptions::raw_depth(1))
+        .decode_as()
+        .unwrap();
+    assert!(matches!(decoded.get("id"), Some(Term::Raw(_))));
+}
+
+#[test]
+fn decode_map_with_test() {
+    use std::collections::HashMap;
+
+    let map = Map::from(
+        (0..100)
+            .map(|i| {
+                let key = Binary::from(format!("key{}", i).into_bytes());
+                (Term::from(key), Term::from(i * 1000))
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+    let bytes = encode(Term::from(map.clone()));
+
+    // Same map as the two-step decoding.
+    let decoded: HashMap<String, i64> = Decoder::new(&bytes[..]).decode_as().unwrap();
+    let term = Term::decode(&bytes[..]).unwrap();
+    assert_eq!(decoded, HashMap::<String, i64>::from_term(&term).unwrap());
+    assert_eq!(decoded.len(), 100);
+    assert_eq!(decoded["key7"], 7000);
+
+    // The entries are given in the order they are encoded.
+    let mut keys = Vec::new();
+    Decoder::new(&bytes[..])
+        .decode_map_with(|k, _| {
+            keys.push(k);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(keys, map.map.keys().cloned().collect::<Vec<_>>());
+
+    // Compressed terms and the options which need the whole term also work.
+    let mut compressed = Vec::new();
+    Term::from(map.clone())
+        .encode_compressed(&mut compressed)
+        .unwrap();
+    let from_compressed: HashMap<String, i64> = Decoder::new(&compressed[..]).decode_as().unwrap();
+    assert_eq!(from_compressed, decoded);
+    let strict: HashMap<String, i64> =
+        Decoder::with_options(&bytes[..], DecoderOptions::default().with_strict(true))
+            .decode_as()
+            .unwrap();
+    assert_eq!(strict, decoded);
+
+    // A value which does not convert and a non-map are read whole, so that the next term follows.
+    let mut bad = map.clone();
+    bad.map.insert(
+        Term::from(Binary::from(&b"bad"[..])),
+        Term::from(Atom::from("x")),
+    );
+    let mut bytes = encode(Term::from(bad));
+    bytes.extend(encode(Term::from(1)));
+    bytes.extend(encode(Term::from(map)));
+    let mut decoder = Decoder::new(&bytes[..]);
+    match decoder.decode_as::<HashMap<String, i64>>() {
+        Err(DecodeError::UnexpectedType { found, expected }) => {
+            assert_eq!(found.preview, "'x'");
+            assert_eq!(expected, "i64");
+        }
+        other => panic!("{:?}", other),
+    }
+    decoder.reset();
+    match decoder.decode_map_with(|_, _| Ok(())) {
+        Err(DecodeError::UnexpectedType { found, expected }) => {
+            assert_eq!(found.preview, "1");
+            assert_eq!(expected, "Map");
+        }
+        other => panic!("{:?}", other),
+    }
+    decoder.reset();
+    assert_eq!(
+        decoder.decode_as::<HashMap<String, i64>>().unwrap(),
+        decoded
+    );
+}
+
+#[test]
+fn unexpected_type_summary_test() {
+    // A binary of 10 MiB where a map is expected.
+    let huge = Term::from(Binary::from(vec![7; 10 << 20]));
+    let bytes = encode(huge.clone());
+    let error = Decoder::new(&bytes[..]).decode_as::<AtomMap>().unwrap_err();
+    let message = error.to_string();
+    assert!(message.len() < 100, "{}", message);
+    assert_eq!(
+        message,
+        "<<7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,… is not a AtomMap"
+    );
+    match error {
+        DecodeError::UnexpectedType { found, expected } => {
+            assert_eq!(expected, "AtomMap");
+            assert_eq!(found.type_name, "Binary");
+            assert_eq!(found.size, Some(bytes.len()));
+            assert_eq!(found.preview.chars().count(), 64);
+            assert!(found.truncated);
+            assert_eq!(found.value(), None);
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // The option keeps the term, e.g., of a FUN_EXT whose pid is a binary.
+    let bytes = [&[131, 117, 0, 0, 0, 0][..], &bytes[1..]].concat();
+    let options = DecoderOptions::new().with_keep_offending_term(true);
+    for result in [
+        Decoder::with_options(&bytes[..], options).decode(),
+        decode_from_slice_with_options(&bytes, options),
+    ] {
+        match result {
+            Err(DecodeError::UnexpectedType { found, expected }) => {
+                assert_eq!(expected, "Pid");
+                assert_eq!(found.value(), Some(&huge));
+                assert_eq!(found.into_value(), Some(huge.clone()));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+    #[cfg(feature = "tokio-async")]
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let kept = runtime.block_on(AsyncDecoder::with_options(&bytes[..], options).decode());
+        assert!(
+            matches!(kept, Err(DecodeError::UnexpectedType { found, .. }) if found.value() == Some(&huge))
+        );
+        let summarized = runtime.block_on(AsyncDecoder::new(&bytes[..]).decode());
+        assert!(
+            matches!(summarized, Err(DecodeError::UnexpectedType { found, .. }) if found.value().is_none())
+        );
+    }
+
+    // Short terms are previewed whole.
+    let summary = TermSummary::new(&Term::from(Atom::from("ok")));
+    assert_eq!(
+        (summary.preview.as_str(), summary.truncated),
+        ("'ok'", false)
+    );
+    assert_eq!(summary.to_string(), "'ok'");
+}
+
+#[test]
+fn options_builder_test() {
+    let options = EncoderOptions::new()
+        .with_new_floats(false)
+        .with_utf8_atoms(false)
+        .with_map_tag(false)
+        .with_big_creation(false)
+        .with_v4_nc(false)
+        .with_export_ptr_tag(false)
+        .with_bit_binaries(false)
+        .with_new_fun_tags(false)
+        .with_deterministic(true)
+        .with_chunk_size(16)
+        .with_compressed(true)
+        .with_string_ext(false);
+    assert_eq!(
+        options,
+        EncoderOptions {
+            deterministic: true,
+            chunk_size: 16,
+            compressed: true,
+            string_ext: false,
+            ..EncoderOptions::for_peer(eetf::dist::DistFlags::empty())
+        }
+    );
+
+    let options = DecoderOptions::new()
+        .with_raw_depth(1)
+        .with_max_uncompressed_size(10)
+        .with_strict(true)
+        .with_safe(true)
+        .with_keep_offending_term(true)
+        .with_versionless(true);
+    assert_eq!(
+        options,
+        DecoderOptions {
+            raw_depth: Some(1),
+            max_uncompressed_size: Some(10),
+            strict: true,
+            safe: true,
+            keep_offending_term: true,
+            versionless: true,
+        }
+    );
+    assert_eq!(
+        DecoderOptions::raw_depth(1),
+        DecoderOptions::new().with_raw_depth(1)
+    );
+}
+
+#[test]
+fn decoder_options_behavior_test() {
+    // Decodes `bytes` with the sync decoder and (with the feature) the async one.
+    let decode = |bytes: &[u8], options: DecoderOptions, table: Option<&AtomTable>| {
+        let mut results = Vec::new();
+        let decoder = Decoder::with_options(bytes, options);
+        results.push(match table {
+            Some(table) => decoder.with_atom_table(table.clone()).decode(),
+            None => decoder.decode(),
+        });
+        #[cfg(feature = "tokio-async")]
+        {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let decoder = AsyncDecoder::with_options(bytes, options);
+            results.push(runtime.block_on(match table {
+                Some(table) => decoder.with_atom_table(table.clone()).decode(),
+                None => decoder.decode(),
+            }));
+        }
+        results
+    };
+    let compress = |term: &Term| {
+        let mut bytes = Vec::new();
+        term.encode_compressed(&mut bytes).unwrap();
+        bytes
+    };
+
+    // raw_depth, also within a compressed term: {route, [1, 2]}.
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("route")),
+        Term::from(ByteList::from(vec![1, 2])),
+    ]));
+    let raw = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("route")),
+        Term::from(Raw::from(vec![107, 0, 2, 1, 2])),
+    ]));
+    let mut bytes = Vec::new();
+    term.encode(&mut bytes).unwrap();
+    for bytes in [bytes, compress(&term)] {
+        for result in decode(&bytes, DecoderOptions::raw_depth(1), None) {
+            assert_eq!(result.unwrap(), raw);
+        }
+        for result in decode(&bytes, DecoderOptions::new(), None) {
+            assert_eq!(result.unwrap(), term);
+        }
+    }
+
+    // max_uncompressed_size, checked against the declared size and against the inflated bytes.
+    let binary = Term::from(Binary::from(vec![0; 1000]));
+    let bytes = compress(&binary);
+    let mut understated = bytes.clone();
+    understated[2..6].copy_from_slice(&50u32.to_be_bytes());
+    for bytes in [&bytes, &understated] {
+        let options = DecoderOptions::new().with_max_uncompressed_size(100);
+        for result in decode(bytes, options, None) {
+            assert!(
+                matches!(
+                    result,
+                    Err(DecodeError::LimitExceeded {
+                        limit: "max_uncompressed_size",
+                        max: 100
+                    })
+                ),
+                "{:?}",
+                result
+            );
+        }
+    }
+    let options = DecoderOptions::new().with_max_uncompressed_size(1006);
+    for result in decode(&bytes, options, None) {
+        assert_eq!(result.unwrap(), binary);
+    }
+
+    // strict: 1 as an INTEGER_EXT, which the async decoders do not check.
+    let bytes = [131, 98, 0, 0, 0, 1];
+    let results = decode(&bytes, DecoderOptions::new().with_strict(true), None);
+    assert!(
+        matches!(results[0], Err(DecodeError::Rejected { .. })),
+        "{:?}",
+        results[0]
+    );
+    #[cfg(feature = "tokio-async")]
+    assert!(
+        matches!(
+            results[1],
+            Err(DecodeError::UnsupportedOption { option: "strict" })
+        ),
+        "{:?}",
+        results[1]
+    );
+    for result in decode(&bytes, DecoderOptions::new(), None) {
+        assert_eq!(result.unwrap(), Term::from(1));
+    }
+
+    // safe: {ok, nope}, with only `ok` in the table, which is not added to.
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(Atom::from("nope")),
+    ]));
+    let mut bytes = Vec::new();
+    term.encode(&mut bytes).unwrap();
+    let table = AtomTable::new();
+    table.intern("ok");
+    let safe = DecoderOptions::new().with_safe(true);
+    for result in decode(&bytes, safe, Some(&table)) {
+        assert!(
+            matches!(&result, Err(DecodeError::UnknownAtom { name }) if name == "nope"),
+            "{:?}",
+            result
+        );
+    }
+    for result in decode(&[131, 119, 2, b'o', b'k'], safe, Some(&table)) {
+        assert_eq!(result.unwrap(), Term::from(Atom::from("ok")));
+    }
+    assert_eq!(table.len(), 1);
+    for result in decode(&[131, 119, 2, b'o', b'k'], safe, None) {
+        assert!(
+            matches!(&result, Err(DecodeError::UnknownAtom { name }) if name == "ok"),
+            "{:?}",
+            result
+        );
+    }
+    for result in decode(&bytes, DecoderOptions::new(), Some(&table)) {
+        assert_eq!(result.unwrap(), term);
+    }
+    assert_eq!(table.len(), 2);
+}
+
+#[test]
+fn encoder_options_behavior_test() {
+    // Encodes `term` with the sync encoder and checks that (with the feature) the async one
+    // writes the same bytes.
+    let encode = |term: &Term, options: EncoderOptions| {
+        let mut bytes = Vec::new();
+        Encoder::with_options(&mut bytes, options)
+            .encode(term)
+            .unwrap();
+        #[cfg(feature = "tokio-async")]
+        {
+            let mut async_bytes = Vec::new();
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(AsyncEncoder::with_options(&mut async_bytes, options).encode(term))
+                .unwrap();
+            assert_eq!(async_bytes, bytes);
+        }
+        bytes
+    };
+    let options = EncoderOptions::new();
+
+    let float = Term::try_from(1.5).unwrap();
+    assert_eq!(encode(&float, options)[1], 70);
+    assert_eq!(encode(&float, options.with_new_floats(false))[1], 99);
+
+    let atom = Term::from(Atom::from("é"));
+    assert_eq!(encode(&atom, options), [131, 118, 0, 2, 0xC3, 0xA9]);
+    assert_eq!(
+        encode(&atom, options.with_utf8_atoms(false)),
+        [131, 100, 0, 1, 0xE9]
+    );
+
+    // The entries are written in the order of their keys.
+    let map = |keys: [&str; 2]| {
+        Term::from(Map::from(
+            keys.iter()
+                .map(|&key| (Term::from(Atom::from(key)), Term::from(0)))
+                .collect::<eetf::term_map::TermMap>(),
+        ))
+    };
+    let deterministic = options.with_deterministic(true);
+    assert_eq!(
+        encode(&map(["b", "a"]), deterministic),
+        encode(&map(["a", "b"]), deterministic)
+    );
+
+    let bytes = encode(&map(["a", "b"]), options.with_compressed(true));
+    assert_eq!(bytes[1], 80);
+    assert_eq!(Decoder::new(&bytes[..]).decode().unwrap(), map(["a", "b"]));
+
+    let list = Term::from(List::from(vec![Term::from(1), Term::from(2)]));
+    let byte_list = Term::from(ByteList::from(vec![1, 2]));
+    for term in [&list, &byte_list] {
+        assert_eq!(encode(term, options), [131, 107, 0, 2, 1, 2]);
+        assert_eq!(
+            encode(term, options.with_string_ext(false)),
+            [131, 108, 0, 0, 0, 2, 97, 1, 97, 2, 106]
+        );
+    }
+    assert_eq!(
+        encode(
+            &Term::from(ByteList::from(vec![])),
+            options.with_string_ext(false)
+        ),
+        [131, 106]
+    );
+}
+
+#[test]
+fn tee_test() {
+    use eetf::tee::{Sink, TeeError};
+
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("audit")),
+        Term::from(Binary::from(vec![7; 100])),
+    ]));
+    let mut encoder = Encoder::tee(Vec::new(), Vec::new());
+    encoder.encode_next(&term).unwrap();
+    encoder.encode_next(&term).unwrap();
+    let (primary, secondary) = encoder.into_inner().into_inner();
+    assert_eq!(
+        primary,
+        [encode(term.clone()), encode(term.clone())].concat()
+    );
+    assert_eq!(primary, secondary);
+
+    // The error tells which sink failed.
+    let mut full = [0; 10];
+    let e = Encoder::tee(Vec::new(), &mut full[..])
+        .encode(&term)
+        .unwrap_err();
+    let tee_error = TeeError::find(&e).unwrap();
+    assert_eq!(tee_error.sink, Sink::Secondary);
+    assert_eq!(tee_error.source.kind(), std::io::ErrorKind::WriteZero);
+    assert!(TeeError::find(&EncodeError::TooLargeBinary { len: 0 }).is_none());
+}
+
+#[cfg(feature = "tokio-async")]
+#[tokio::test]
+async fn async_tee_test() {
+    use eetf::tee::{AsyncTeeEncoder, Overflow, Sink, TeeError};
+    use tokio::io::AsyncReadExt;
+
+    let term = Term::from(Binary::from(vec![7; 94]));
+    let bytes = encode(term.clone());
+    assert_eq!(bytes.len(), 100);
+
+    // The secondary writer takes 150 bytes, which nobody reads, and lags by up to 200 bytes, so
+    // the fourth term does not fit.
+    for overflow in [Overflow::Drop, Overflow::Error] {
+        let (_reader, secondary) = tokio::io::duplex(150);
+        let mut encoder = AsyncTeeEncoder::new(Vec::new(), secondary, 200).with_overflow(overflow);
+        for _ in 0..3 {
+            encoder.encode(&term).await.unwrap();
+        }
+        assert_eq!(encoder.buffered(), 150);
+        let result = encoder.encode(&term).await;
+        match overflow {
+            Overflow::Drop => {
+                result.unwrap();
+                assert_eq!(encoder.dropped(), 1);
+            }
+            _ => assert_eq!(
+                TeeError::find(&result.unwrap_err()).unwrap().sink,
+                Sink::Secondary
+            ),
+        }
+        assert_eq!(encoder.buffered(), 150);
+        let (primary, _) = encoder.into_inner();
+        assert_eq!(primary, bytes.repeat(4));
+    }
+
+    // With `Overflow::Block`, the encoder waits for the slow reader of the secondary writer.
+    let (mut reader, secondary) = tokio::io::duplex(10);
+    let read = tokio::spawn(async move {
+        let mut log = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            tokio::task::yield_now().await;
+            match reader.read(&mut buf).await.unwrap() {
+                0 => return log,
+                n => log.extend_from_slice(&buf[..n]),
+            }
+        }
+    });
+    let mut encoder = AsyncTeeEncoder::new(Vec::new(), secondary, 150);
+    for _ in 0..20 {
+        encoder.encode(&term).await.unwrap();
+        assert!(encoder.buffered() <= 150);
+    }
+    encoder.flush().await.unwrap();
+    assert_eq!(encoder.buffered(), 0);
+    assert_eq!(encoder.dropped(), 0);
+    let (primary, secondary) = encoder.into_inner();
+    drop(secondary);
+    assert_eq!(primary, bytes.repeat(20));
+    assert_eq!(read.await.unwrap(), primary);
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_decode_timeout_test() {
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    let term = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("foo")),
+        Term::from(Binary::from(vec![7; 100])),
+    ]));
+    let bytes = encode(term.clone());
+    let half = bytes.len() / 2;
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+        .block_on(async {
+            // The peer stalls after half of the term.
+            let (mut peer, stream) = tokio::io::duplex(1024);
+            peer.write_all(&bytes[..half]).await.unwrap();
+            let mut decoder = AsyncDecoder::new(stream);
+            assert!(!decoder.is_poisoned());
+            let started = tokio::time::Instant::now();
+            match decoder.decode_with_timeout(Duration::from_secs(1)).await {
+                Err(DecodeError::Timeout {
+                    bytes_consumed,
+                    elapsed,
+                }) => {
+                    assert_eq!(bytes_consumed, half as u64);
+                    assert_eq!(elapsed, Duration::from_secs(1));
+                }
+                result => panic!("{:?}", result),
+            }
+            assert_eq!(started.elapsed(), Duration::from_secs(1));
+            assert!(decoder.is_poisoned());
+            assert_eq!(decoder.position(), half as u64);
+
+            // The idle timeout restarts whenever bytes arrive.
+            let (mut peer, stream) = tokio::io::duplex(1024);
+            let mut decoder = AsyncDecoder::new(stream);
+            let trickle = async {
+                for byte in &bytes {
+                    peer.write_all(&[*byte]).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+            };
+            let (decoded, ()) =
+                tokio::join!(decoder.decode_with_timeout(Duration::from_secs(1)), trickle);
+            assert_eq!(decoded.unwrap(), term);
+            assert!(!decoder.is_poisoned());
+
+            // The deadline does not.
+            let (mut peer, stream) = tokio::io::duplex(1024);
+            let mut decoder = AsyncDecoder::new(stream);
+            let trickle = async {
+                for byte in &bytes {
+                    peer.write_all(&[*byte]).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+            };
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+            let (decoded, ()) = tokio::join!(decoder.decode_with_deadline(deadline), trickle);
+            assert!(matches!(
+                decoded,
+                Err(DecodeError::Timeout {
+                    bytes_consumed: 6 | 7,
+                    ..
+                })
+            ));
+
+            // A dropped decode leaves the decoder poisoned within the term.
+            let (mut peer, stream) = tokio::io::duplex(1024);
+            peer.write_all(&bytes[..half]).await.unwrap();
+            let mut decoder = AsyncDecoder::new(stream);
+            let result = tokio::time::timeout(Duration::from_secs(1), decoder.decode_next()).await;
+            assert!(result.is_err());
+            assert!(decoder.is_poisoned());
+            assert_eq!(decoder.position(), half as u64);
+            assert!(matches!(
+                decoder.decode_next().await,
+                Err(DecodeError::Poisoned)
+            ));
+            assert_eq!(decoder.position(), half as u64);
+        });
+}
+
+#[cfg(feature = "tokio-async")]
+#[test]
+fn async_decode_yields_test() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // 100k subterms, which are all in memory.
+    let term = Term::from(List::from(
+        (0..50_000)
+            .map(|i| Term::from(Tuple::from(vec![Term::from(i)])))
+            .collect::<Vec<_>>(),
+    ));
+    let bytes = encode(term.clone());
+
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            // Counts how often the decoder gives way to other tasks.
+            let ticks = Arc::new(AtomicUsize::new(0));
+            let done = Arc::new(AtomicBool::new(false));
+            let ticker = tokio::spawn({
+                let (ticks, done) = (ticks.clone(), done.clone());
+                async move {
+                    while !done.load(Ordering::SeqCst) {
+                        ticks.fetch_add(1, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                    }
+                }
+            });
+            tokio::task::yield_now().await;
+
+            let before = ticks.load(Ordering::SeqCst);
+            let decoded = AsyncDecoder::new(&bytes[..])
+                .with_yield_interval(Some(1000))
+                .decode()
+                .await
+                .unwrap();
+            assert_eq!(decoded, term);
+            assert!(ticks.load(Ordering::SeqCst) - before >= 100 - 3);
+
+            // Yielding can be turned off.
+            let before = ticks.load(Ordering::SeqCst);
+            let decoded = AsyncDecoder::new(&bytes[..])
+                .with_yield_interval(None)
+                .decode()
+                .await
+                .unwrap();
+            assert_eq!(decoded, term);
+            assert_eq!(ticks.load(Ordering::SeqCst), before);
+
+            done.store(true, Ordering::SeqCst);
+            ticker.await.unwrap();
+        });
+}
+
+#[test]
+fn control_message_test() {
+    use eetf::dist::ControlMessage;
+
+    let from = Pid::new("a@localhost", 1, 0, 1);
+    let to = Pid::new("b@localhost", 2, 0, 1);
+    let reference = Reference::from(("a@localhost", vec![1, 2, 3]));
+    let messages = [
+        ControlMessage::Link {
+            from: from.clone(),
+            to: to.clone(),
+        },
+        ControlMessage::Send { to: to.clone() },
+        ControlMessage::RegSend {
+            from: from.clone(),
+            to_name: Atom::from("logger"),
+        },
+        ControlMessage::MonitorPExit {
+            from_proc: Term::from(Atom::from("logger")),
+            to: to.clone(),
+            reference,
+            reason: Term::from(Atom::from("normal")),
+        },
+        ControlMessage::UnlinkId {
+            id: Term::from(7),
+            from,
+            to,
+        },
+    ];
+    for message in messages {
+        assert_eq!(ControlMessage::from_term(message.to_term()), message);
+    }
+    assert_eq!(
+        ControlMessage::Send {
+            to: Pid::new("b@localhost", 2, 0, 1)
+        }
+        .to_term()
+        .to_string(),
+        "{2,'',<'b@localhost'.2.0>}"
+    );
+
+    // Unknown operations and malformed messages
+    let unknown = Term::from(Tuple::from(vec![Term::from(99), Term::from(1)]));
+    assert_eq!(
+        ControlMessage::from_term(unknown.clone()),
+        ControlMessage::Other(unknown)
+    );
+    let malformed = Term::from(Tuple::from(vec![Term::from(1), Term::from(1)]));
+    assert_eq!(
+        ControlMessage::from_term(malformed.clone()),
+        ControlMessage::Other(malformed)
+    );
+}
+
+#[cfg(feature = "distribution")]
+#[test]
+fn node_test() {
+    use eetf::dist::node::Node;
+    use eetf::dist::{ControlMessage, DistFlags};
+    use tokio::io::AsyncReadExt;
+
+    let a = Node::new("a@localhost", "secret").creation(1);
+    let b = Node::new("b@localhost", "secret").creation(2);
+    let message = Term::from(Tuple::from(vec![
+        Term::from(Atom::from("hello")),
+        Term::from(Pid::new("a@localhost", 5, 0, 1)),
+    ]));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        for b in [
+            b.clone(),
+            b.flags(DistFlags::default() - DistFlags::DIST_HDR_ATOM_CACHE),
+        ] {
+            let (a_stream, b_stream) = tokio::io::duplex(4096);
+            let (a_conn, b_conn) = tokio::join!(a.connect(a_stream), b.accept(b_stream));
+            let (mut a_conn, mut b_conn) = (a_conn.unwrap(), b_conn.unwrap());
+
+            // Repeated messages reuse the atom cache (if it is enabled).
+            for _ in 0..2 {
+                a_conn.send_reg("logger", &message).await.unwrap();
+                let (control, payload) = b_conn.recv().await.unwrap();
+                assert_eq!(
+                    control,
+                    ControlMessage::RegSend {
+                        from: a.pid(0, 0),
+                        to_name: Atom::from("logger"),
+                    }
+                );
+                assert_eq!(payload, Some(message.clone()));
+            }
+
+            b_conn
+                .send(a.pid(5, 0), &Term::from(Atom::from("world")))
+                .await
+                .unwrap();
+            let (control, payload) = a_conn.recv().await.unwrap();
+            assert_eq!(control, ControlMessage::Send { to: a.pid(5, 0) });
+            assert_eq!(payload, Some(Term::from(Atom::from("world"))));
+
+            // Ticks are skipped and answered.
+            a_conn.tick().await.unwrap();
+            a_conn.send_reg("logger", &message).await.unwrap();
+            let (_, payload) = b_conn.recv().await.unwrap();
+            assert_eq!(payload, Some(message.clone()));
+            let mut len = [0xFF; 4];
+            a_conn.into_stream().read_exact(&mut len).await.unwrap();
+            assert_eq!(len, [0; 4]);
+        }
+    });
+}
+
+#[test]
+fn encoder_options_test() {
+    use eetf::dist::DistFlags;
+
+    // Encodes with both encoders, which must agree.
+    fn encode_for(flags: DistFlags, term: impl Into<Term>) -> Result<Vec<u8>, EncodeError> {
+        let term = term.into();
+        let options = EncoderOptions::for_peer(flags);
+        let mut buf = Vec::new();
+        let result = Encoder::with_options(&mut buf, options)
+            .encode(&term)
+            .map(|()| buf);
+        #[cfg(feature = "tokio-async")]
+        {
+            let mut async_buf = Vec::new();
+            let async_result = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(AsyncEncoder::with_options(&mut async_buf, options).encode(&term))
+                .map(|()| async_buf);
+            assert_eq!(format!("{:?}", async_result), format!("{:?}", result));
+        }
+        result
+    }
+    let all = DistFlags::all();
+
+    // Floats
+    assert_eq!(
+        encode_for(all, Float::try_from(1.5).unwrap()).unwrap()[1],
+        70
+    );
+    let buf = encode_for(all - DistFlags::NEW_FLOATS, Float::try_from(1.5).unwrap()).unwrap();
+    assert_eq!(buf[1], 99);
+    assert_eq!(&buf[2..28], b"1.50000000000000000000e+00");
+    assert_eq!(buf.len(), 2 + 31);
+    let infinity = Term::from(Float {
+        value: f64::INFINITY,
+    });
+    assert_eq!(encode_for(all, infinity.clone()).unwrap()[1], 70);
+    assert!(matches!(
+        encode_for(all - DistFlags::NEW_FLOATS, infinity),
+        Err(EncodeError::NonFiniteFloat(..))
+    ));
+
+    // Atoms
+    assert_eq!(encode_for(all, Atom::from("é")).unwrap()[1], 118);
+    let no_utf8 = all - DistFlags::UTF8_ATOMS;
+    assert_eq!(
+        encode_for(no_utf8, Atom::from("é")).unwrap(),
+        [131, 100, 0, 1, 0xE9]
+    );
+    assert!(matches!(
+        encode_for(no_utf8, Atom::from("λ")),
+        Err(EncodeError::UnsupportedByPeer {
+            flag: DistFlags::UTF8_ATOMS,
+            ..
+        })
+    ));
+
+    // Pids, ports and references
+    let pid = Pid::new("a", 1, 0, 2);
+    assert_eq!(encode_for(all, pid.clone()).unwrap()[1], 88);
+    let no_big_creation = all - DistFlags::BIG_CREATION;
+    assert_eq!(
+        encode_for(no_big_creation, pid).unwrap(),
+        [131, 103, 100, 0, 1, b'a', 0, 0, 0, 1, 0, 0, 0, 0, 2]
+    );
+    assert!(matches!(
+        encode_for(no_big_creation, Pid::new("a", 1, 0, 1700000000)),
+        Err(EncodeError::ValueOutOfRangeForProfile {
+            field: "creation",
+            profile: DistFlags::BIG_CREATION,
+            ..
+        })
+    ));
+
+    // The fields of the older encodings are narrower.
+    let old = all - DistFlags::V4_NC - DistFlags::BIG_CREATION;
+    type MakeTerm = fn(u64) -> Term;
+    let cases: [(&str, u64, MakeTerm); 6] = [
+        ("creation", 3, |n| Term::from(Pid::new("a", 1, 0, n as u32))),
+        ("pid ID", (1 << 15) - 1, |n| {
+            Term::from(Pid::new("a", n as u32, 0, 1))
+        }),
+        ("pid serial", (1 << 13) - 1, |n| {
+            Term::from(Pid::new("a", 1, n as u32, 1))
+        }),
+        ("port ID", (1 << 28) - 1, |n| {
+            Term::from(Port::new("a", n, 1))
+        }),
+        ("first word of reference ID", (1 << 18) - 1, |n| {
+            Term::from(Reference::new("a", vec![n as u32, 0, 0], 1))
+        }),
+        ("number of words of reference ID", 3, |n| {
+            Term::from(Reference::new("a", vec![0; n as usize], 1))
+        }),
+    ];
+    for (name, max, make) in cases {
+        assert!(encode_for(old, make(max)).is_ok(), "{}", name);
+        match encode_for(old, make(max + 1)) {
+            Err(EncodeError::ValueOutOfRangeForProfile {
+                field,
+                value,
+                max: m,
+                ..
+            }) => assert_eq!((field, value, m), (name, max + 1, max)),
+            other => panic!("{}: {:?}", name, other),
+        }
+        let term = make(max + 1);
+        assert_eq!(decode(&encode_for(all, term.clone()).unwrap()), term);
+    }
+    // The async encoder checks the same ranges.
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let options = EncoderOptions::for_peer(old);
+            for (name, max, make) in cases {
+                let mut buf = Vec::new();
+                let encoder = AsyncEncoder::with_options(&mut buf, options);
+                assert!(encoder.encode(&make(max)).await.is_ok(), "{}", name);
+                let encoder = AsyncEncoder::with_options(&mut buf, options);
+                match encoder.encode(&make(max + 1)).await {
+                    Err(EncodeError::ValueOutOfRangeForProfile {
+                        field,
+                        value,
+                        max: m,
+                        profile,
+                    }) => {
+                        assert_eq!((field, value, m), (name, max + 1, max));
+                        assert!(!old.contains(profile), "{}", name);
+                    }
+                    other => panic!("{}: {:?}", name, other),
+                }
+            }
+        });
+    let port = Port {
+        node: Atom::from("a"),
+        id: 1 << 40,
+        creation: 2,
+    };
+    assert_eq!(encode_for(all, port.clone()).unwrap()[1], 120);
+    assert!(matches!(
+        encode_for(all - DistFlags::V4_NC, port),
+        Err(EncodeError::UnsupportedByPeer {
+            flag: DistFlags::V4_NC,
+            ..
+        })
+    ));
+    assert_eq!(
+        encode_for(no_big_creation, Reference::from(("a", vec![1, 2, 3]))).unwrap()[1],
+        114
+    );
+    assert!(matches!(
+        encode_for(no_big_creation, Reference::from(("a", vec![0; 0x10000]))),
+        Err(EncodeError::TooLargeReferenceId(..))
+    ));
+
+    // Terms without older tags
+    let map = Map::from([(Term::from(1), Term::from(2))]);
+    assert!(matches!(
+        encode_for(all - DistFlags::MAP_TAG, map),
+        Err(EncodeError::UnsupportedByPeer {
+            flag: DistFlags::MAP_TAG,
+            ..
+        })
+    ));
+    let bits = BitBinary::from((vec![1], 3));
+    assert!(encode_for(all - DistFlags::BIT_BINARIES, bits).is_err());
+    let export = ExternalFun::from(("lists", "map", 2));
+    assert!(encode_for(all - DistFlags::EXPORT_PTR_TAG, export).is_err());
+    let fun = InternalFun::New {
+        module: Atom::from("a"),
+        arity: 1,
+        pid: Pid::new("a", 1, 0, 2),
+        free_vars: vec![Term::from(Float::try_from(1.5).unwrap())],
+        index: 0,
+        uniq: [0; 16],
+        old_index: 0,
+        old_uniq: 0,
+    };
+    let buf = encode_for(
+        all - DistFlags::NEW_FLOATS - DistFlags::BIG_CREATION,
+        fun.clone(),
+    )
+    .unwrap();
+    assert_eq!(buf[1], 112);
+    assert_eq!(
+        u32::from_be_bytes(buf[2..6].try_into().unwrap()) as usize,
+        buf.len() - 2
+    );
+    assert!(matches!(
+        encode_for(all - DistFlags::NEW_FUN_TAGS, fun),
+        Err(EncodeError::UnsupportedByPeer {
+            flag: DistFlags::NEW_FUN_TAGS,
+            ..
+        })
+    ));
+
+    // The default options allow every tag.
+    assert_eq!(EncoderOptions::default(), EncoderOptions::for_peer(all));
+    assert_eq!(
+        EncoderOptions::for_peer(DistFlags::mandatory()),
+        EncoderOptions::default()
+    );
+}
+
+#[test]
+fn raw_depth_test() {
+    use std::io::Write;
+
+    let payload = Term::from(Tuple::from(vec![
+        Term::from(Binary::from(vec![7; 1000])),
+        Term::from(Map::from([(Term::from(Atom::from("k")), Term::from(-300))])),
+        Term::from(Pid::new("a@localhost", 1, 2, 3)),
+        Term::from(Reference::from(("a@localhost", vec![1, 2, 3]))),
+        Term::from(ExternalFun::from(("lists", "map", 2))),
+        Term::from(BigInteger {
+            value: num::BigInt::from(1) << 100,
+        }),
+        Term::from(Float::try_from(1.5).unwrap()),
+        Term::from(List::from(vec![Term::from(1), Term::from(1000)])),
+        Term::from(ImproperList::from((vec![Term::from(1)], Term::from(2)))),
+        Term::from(Port::new("a@localhost", 4, 5)),
+        Term::from(InternalFun::Old {
+            module: Atom::from("m"),
+            pid: Pid::new("a@localhost", 1, 2, 3),
+            free_vars: vec![Term::from(6)],
+            index: 7,
+            uniq: 8,
+        }),
+        Term::from(InternalFun::New {
+            module: Atom::from("m"),
+            arity: 1,
+            pid: Pid::new("a@localhost", 1, 2, 3),
+            free_vars: vec![Term::from(Binary::from(vec![9]))],
+            index: 10,
+            uniq: [11; 16],
+            old_index: 12,
+            old_uniq: 13,
+        }),
+    ]));
+    let route = Term::from(Atom::from("route"));
+    let bytes = encode(Term::from(Tuple::from(vec![
+        route.clone(),
+        payload.clone(),
+    ])));
+
+    let term =
+        Term::decode_with_options(Cursor::new(&bytes), DecoderOptions::raw_depth(1)).unwrap();
+    let Term::Tuple(tuple) = &term else {
+        panic!("{}", term)
+    };
+    assert_eq!(tuple.elements[0], route);
+    let Term::Raw(raw) = &tuple.elements[1] else {
+        panic!("{}", term)
+    };
+    assert_eq!(raw.bytes, bytes[11..]);
+    assert_eq!(raw.decode().unwrap(), payload);
+    assert_eq!(encode(term.clone()), bytes);
+
+    // Compressed terms are re-encoded uncompressed.
+    let mut compressor = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+    compressor.write_all(&bytes[1..]).unwrap();
+    let mut compressed = vec![131, 80];
+    compressed.extend_from_slice(&(bytes.len() as u32 - 1).to_be_bytes());
+    compressed.extend(compressor.finish().into_result().unwrap());
+    let options = DecoderOptions::raw_depth(1);
+    assert_eq!(
+        Term::decode_with_options(Cursor::new(&compressed), options).unwrap(),
+        term
+    );
+
+    // The async decoders capture the same bytes.
+    #[cfg(feature = "tokio-async")]
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(async {
+            for depth in 0..4 {
+                let options = DecoderOptions::raw_depth(depth);
+                for bytes in [&bytes, &compressed] {
+                    // Deeper than 2, the fields of the funs are raw too, which fails both.
+                    let decoder = AsyncDecoder::with_options(&bytes[..], options);
+                    assert_eq!(
+                        format!("{:?}", decoder.decode().await),
+                        format!(
+                            "{:?}",
+                            Term::decode_with_options(Cursor::new(bytes), options)
+                        ),
+                        "{}",
+                        depth
+                    );
+                }
+            }
+            let truncated = &bytes[..bytes.len() - 1];
+            let decoder = AsyncDecoder::with_options(truncated, DecoderOptions::raw_depth(1));
+            assert!(decoder.decode().await.is_err());
+        });
+
+    // Atoms are decoded at any depth.
+    let options = DecoderOptions::raw_depth(0);
+    let atom = encode(route.clone());
+    assert_eq!(
+        Term::decode_with_options(Cursor::new(&atom), options).unwrap(),
+        route
+    );
+    let Term::Raw(raw) = Term::decode_with_options(Cursor::new(&bytes), options).unwrap() else {
+        panic!()
+    };
+    assert_eq!(raw.bytes, bytes[1..]);
+
+    // Atom cache references are resolved in the raw terms.
+    #[rustfmt::skip]
+    let message = [
+        131, 68, 1,
+        0x08, // flags: new/0, short atoms
+        12, 11, b'a', b'@', b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't',
+        104, 2, 97, 6, 88, 82, 0, 0, 0, 0, 85, 0, 0, 0, 0, 0, 0, 0, 1,
+    ];
+    let message = Decoder::with_options(Cursor::new(&message), DecoderOptions::raw_depth(1))
+        .decode_distribution_message(&mut dist::AtomCache::new())
+        .unwrap();
+    let Term::Tuple(control) = message.control else {
+        panic!()
+    };
+    let Term::Raw(raw) = &control.elements[1] else {
+        panic!()
+    };
+    assert_eq!(
+        raw.decode().unwrap(),
+        Term::from(Pid::new("a@localhost", 85, 0, 1))
+    );
+}
+
+#[test]
+fn atom_table_test() {
+    let keys = (0..10)
+        .map(|i| Atom::from(format!("key{}", i)))
+        .collect::<Vec<_>>();
+    let maps = (0..1000)
+        .map(|i| {
+            Term::from(Map::from(
+                keys.iter()
+                    .map(|k| (Term::from(k.clone()), Term::from(i)))
+                    .collect::<std::collections::HashMap<_, _>>(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    let bytes = encode(Term::from(List::from(maps.clone())));
+    let names = |term: &Term| {
+        let Term::List(list) = term else {
+            panic!("{}", term)
+        };
+        list.elements
+            .iter()
+            .map(|m| {
+                let Term::Map(m) = m else { panic!("{}", m) };
+                let (Term::Atom(key), _) =
+                    m.map.get_key_value(&Term::from(keys[3].clone())).unwrap()
+                else {
+                    panic!("{}", m)
+                };
+                key.name.clone()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let table = AtomTable::new();
+    let term = Decoder::new(Cursor::new(&bytes))
+        .with_atom_table(table.clone())
+        .decode()
+        .unwrap();
+    assert_eq!(term, Term::from(List::from(maps.clone())));
+    assert_eq!(table.len(), keys.len());
+    let key3 = names(&term);
+    assert!(key3
+        .iter()
+        .all(|name| std::sync::Arc::ptr_eq(name, &key3[0])));
+
+    // Decoders can share a table.
+    #[cfg(feature = "tokio-async")]
+    {
+        let term = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(
+                AsyncDecoder::new(Cursor::new(&bytes))
+                    .with_atom_table(table.clone())
+                    .decode(),
+            )
+            .unwrap();
+        assert_eq!(term, Term::from(List::from(maps)));
+        assert_eq!(table.len(), keys.len());
+        assert!(std::sync::Arc::ptr_eq(&names(&term)[0], &key3[0]));
+    }
+}
+
+#[test]
+fn static_atom_test() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    static_atoms! {
+        OK = "ok";
+        pub ERROR = "error";
+    }
+    let hash = |atom: &Atom| {
+        let mut hasher = DefaultHasher::new();
+        atom.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let ok = Atom::from_static("ok");
+    assert!(std::sync::Arc::ptr_eq(
+        &ok.name,
+        &Atom::from_static("ok").name
+    ));
+    assert_eq!(ok, Atom::from("ok"));
+    assert_eq!(Atom::from("ok"), ok);
+    assert_eq!(hash(&ok), hash(&Atom::from("ok")));
+    assert_ne!(ok, Atom::from_static("error"));
+
+    assert_eq!(*OK, Atom::from("ok"));
+    assert!(OK == Atom::from("ok") && Atom::from("error") == ERROR);
+    assert_eq!(hash(&OK), hash(&Atom::from("ok")));
+    assert_eq!(OK.to_string(), "'ok'");
+    assert_eq!(Term::from(&ERROR), Term::atom_static("error"));
+    assert_eq!(Term::atom_static("error"), Term::from(Atom::from("error")));
+    let map = Map::from([(Term::from(Atom::from("ok")), Term::from(1))]);
+    assert_eq!(map.map.get(&Term::from(&OK)), Some(&Term::from(1)));
+}
+
+#[test]
+fn atom_set_test() {
+    #[derive(Debug, PartialEq)]
+    enum Outcome {
+        Ok,
+        Error,
+        NoReply,
+    }
+    let set = AtomSet::new(&AtomTable::new(), ["ok", "error", "noreply"]);
+    let outcome = |term: &Term| match term.atom_id(&set)? {
+        0 => Some(Outcome::Ok),
+        1 => Some(Outcome::Error),
+        2 => Some(Outcome::NoReply),
+        _ => unreachable!(),
+    };
+
+    let terms = ["error", "ok", "other", "noreply"]
+        .map(|name| Term::from(Atom::from(name)))
+        .to_vec();
+    let bytes = encode(Term::from(List::from(terms.clone())));
+    let term = Decoder::new(Cursor::new(&bytes))
+        .with_atom_table(set.table().clone())
+        .decode()
+        .unwrap();
+    let Term::List(list) = term else {
+        panic!("{}", term)
+    };
+    let outcomes = list.elements.iter().map(outcome).collect::<Vec<_>>();
+    assert_eq!(
+        outcomes,
+        [
+            Some(Outcome::Error),
+            Some(Outcome::Ok),
+            None,
+            Some(Outcome::NoReply)
+        ]
+    );
+
+    // Atoms not decoded with the table of the set are looked up by name.
+    assert_eq!(terms.iter().map(outcome).collect::<Vec<_>>(), outcomes);
+    assert_eq!(Term::from(1).atom_id(&set), None);
+    assert_eq!(set.atom(2), Some(&Atom::from("noreply")));
+    assert_eq!(set.atom(3), None);
+}
+
+#[test]
+fn shared_atom_table_test() {
+    let table = AtomTable::new();
+    let threads = (0..8)
+        .map(|t| {
+            let table = table.clone();
+            std::thread::spawn(move || {
+                let atoms = (0..50)
+                    .map(|i| format!("key{}", i))
+                    .chain((0..10).map(|i| format!("thread{}_{}", t, i)))
+                    .map(|name| Term::from(Atom::from(name)))
+                    .collect::<Vec<_>>();
+                let bytes = encode(Term::from(List::from(atoms)));
+                let mut key0 = None;
+                for _ in 0..100 {
+                    let term = Decoder::new(&bytes[..])
+                        .with_atom_table(table.clone())
+                        .decode()
+                        .unwrap();
+                    let Term::List(list) = term else { panic!() };
+                    let Term::Atom(ref atom) = list.elements[0] else {
+                        panic!()
+                    };
+                    key0 = Some(atom.name.clone());
+                }
+                key0.unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    let key0 = threads
+        .into_iter()
+        .map(|t| t.join().unwrap())
+        .collect::<Vec<_>>();
+    assert!(key0
+        .iter()
+        .all(|name| std::sync::Arc::ptr_eq(name, &key0[0])));
+    assert_eq!(table.len(), 50 + 8 * 10);
+    let key_bytes = (0..50).map(|i| format!("key{}", i).len()).sum::<usize>();
+    assert_eq!(table.bytes(), key_bytes + 8 * 10 * "thread0_0".len());
+}
+
+#[test]
+fn wire_layout_test() {
+    use eetf::fidelity::Fallback;
+
+    let reencode = |bytes: &[u8], change: fn(Term) -> Term| {
+        let (term, layout) = Decoder::new(bytes).decode_with_layout().unwrap();
+        let term = change(term);
+        let mut buf = Vec::new();
+        let mut fallbacks = Vec::new();
+        Encoder::new(&mut buf)
+            .encode_with_layout(&term, &layout, |f| fallbacks.push(f))
+            .unwrap();
+        assert_eq!(decode(&buf), term);
+        (buf, fallbacks)
+    };
+
+    let mut float_ext = vec![131, 99];
+    float_ext.extend_from_slice(b"1.50000000000000000000e+00");
+    float_ext.resize(2 + 31, 0);
+    // A map whose keys are in the reverse of the order of `TermMap`.
+    let mut map = vec![131, 116, 0, 0, 0, 20];
+    for i in (0..20).rev() {
+        map.extend_from_slice(&[97, i, 100, 0, 1, 97]);
+    }
+    let corpus: Vec<Vec<u8>> = vec![
+        // PID_EXT, PORT_EXT, REFERENCE_EXT and NEW_REFERENCE_EXT
+        vec![131, 103, 100, 0, 1, 97, 0, 0, 0, 1, 0, 0, 0, 0, 1],
+        vec![131, 102, 100, 0, 1, 97, 0, 0, 0, 5, 2],
+        vec![131, 101, 100, 0, 1, 97, 0, 0, 0, 7, 1],
+        vec![131, 114, 0, 2, 100, 0, 1, 97, 3, 0, 0, 0, 1, 0, 0, 0, 2],
+        // SMALL_ATOM_EXT, and ATOM_EXT in latin-1
+        vec![131, 115, 3, 102, 111, 111],
+        vec![131, 100, 0, 2, 104, 233],
+        // INTEGER_EXT of 1
+        vec![131, 98, 0, 0, 0, 1],
+        // LIST_EXT of small integers, and LARGE_TUPLE_EXT of arity 1
+        vec![131, 108, 0, 0, 0, 2, 97, 1, 97, 2, 106],
+        vec![131, 105, 0, 0, 0, 1, 97, 1],
+        // {Pid, [1 | 2], <<0>>}
+        vec![
+            131, 104, 3, 103, 100, 0, 1, 97, 0, 0, 0, 1, 0, 0, 0, 0, 1, 108, 0, 0, 0, 1, 97, 1, 97,
+            2, 109, 0, 0, 0, 1, 0,
+        ],
+        float_ext,
+        map,
+    ];
+    for bytes in &corpus {
+        assert_ne!(encode(decode(bytes)), *bytes);
+        assert_eq!(reencode(bytes, |t| t), (bytes.clone(), vec![]));
+    }
+
+    // A creation which does not fit PID_EXT.
+    let (buf, fallbacks) = reencode(&corpus[0], |_| Term::from(Pid::new("a", 1, 0, 300)));
+    assert_eq!(buf, encode(decode(&buf)));
+    assert_eq!(
+        fallbacks,
+        [Fallback::Tag {
+            tag: 103,
+            variant: "Pid"
+        }]
+    );
+
+    // The rest of a term which does not match the layout is encoded as usual.
+    let tuple = [131, 105, 0, 0, 0, 2, 98, 0, 0, 0, 1, 97, 2];
+    let (buf, fallbacks) = reencode(&tuple, |_| {
+        Term::from(Tuple::from(vec![
+            Term::from(Atom::from("a")),
+            Term::from(256),
+        ]))
+    });
+    assert_eq!(buf, [131, 105, 0, 0, 0, 2, 100, 0, 1, 97, 98, 0, 0, 1, 0]);
+    assert_eq!(
+        fallbacks,
+        [Fallback::Mismatch {
+            tag: Some(98),
+            variant: "Atom"
+        }]
+    );
+
+    let mut compressed = Vec::new();
+    decode(&corpus[9])
+        .encode_compressed(&mut compressed)
+        .unwrap();
+    let (buf, fallbacks) = reencode(&compressed, |t| t);
+    assert_eq!(buf, compressed);
+    assert_eq!(fallbacks, [Fallback::Recompressed]);
+}
+
+#[test]
+fn patch_encoded_test() {
+    use PatchPathSegment::*;
+
+    let term = |counter: i32| {
+        Term::from(Tuple::from(vec![
+            Term::from(Binary::from(vec![1; 1000])),
+            Term::from(Tuple::from(vec![
+                Term::from(Atom::from("stats")),
+                Term::from(Map::from([(
+                    Term::from(Atom::from("counter")),
+                    Term::from(List::from(vec![
+                        Term::from(Float::try_from(3.5).unwrap()),
+                        Term::from(counter),
+                    ])),
+                )])),
+            ])),
+            Term::from(ByteList::from(vec![1, 2, 3])),
+        ]))
+    };
+    let counter = [
+        TupleElement(1),
+        TupleElement(1),
+        MapValue {
+            key: Term::from(Atom::from("counter")),
+        },
+        ListElement(1),
+    ];
+
+    // Only the bytes of the integer change.
+    let input = encode(term(7));
+    let output = patch_encoded(&input, &counter, &Term::from(8)).unwrap();
+    assert_eq!(decode(&output), term(8));
+    assert_eq!(output.len(), input.len());
+    let changed = (0..input.len())
+        .filter(|&i| input[i] != output[i])
+        .collect::<Vec<_>>();
+    assert_eq!(changed.len(), 1);
+
+    // The new value may have another length.
+    let output = patch_encoded(&input, &counter, &Term::from(1_000_000)).unwrap();
+    assert_eq!(decode(&output), term(1_000_000));
+    assert_eq!(output.len(), input.len() + 3);
+    assert_eq!(input[..changed[0] - 1], output[..changed[0] - 1]);
+    assert_eq!(input[changed[0] + 1..], output[changed[0] + 4..]);
+
+    let output =
+        patch_encoded(&input, &[TupleElement(2), ListElement(1)], &Term::from(-1)).unwrap();
+    let Term::Tuple(tuple) = decode(&output) else {
+        panic!()
+    };
+    assert_eq!(
+        tuple.elements[2],
+        Term::from(List::from(vec![
+            Term::from(1),
+            Term::from(-1),
+            Term::from(3)
+        ]))
+    );
+    assert_eq!(
+        decode(&patch_encoded(&input, &[], &Term::from(0)).unwrap()),
+        Term::from(0)
+    );
+
+    // Compressed terms are compressed again.
+    let mut compressed = Vec::new();
+    term(7).encode_compressed(&mut compressed).unwrap();
+    let output = patch_encoded(&compressed, &counter, &Term::from(8)).unwrap();
+    assert_eq!(output[1], 80);
+    assert_eq!(decode(&output), term(8));
+
+    // Paths which do not exist.
+    assert!(matches!(
+        patch_encoded(&input, &[TupleElement(3)], &Term::from(0)),
+        Err(PatchError::NotFound { index: 0, .. })
+    ));
+    let missing_key = [
+        TupleElement(1),
+        TupleElement(1),
+        MapValue {
+            key: Term::from(Atom::from("other")),
+        },
+    ];
+    assert!(matches!(
+        patch_encoded(&input, &missing_key, &Term::from(0)),
+        Err(PatchError::NotFound { index: 2, .. })
+    ));
+    let err =
+        patch_encoded(&input, &[TupleElement(0), ListElement(0)], &Term::from(0)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "segment 1 of the path (list[0]) cannot step into a Binary"
+    );
+    assert!(matches!(
+        patch_encoded(&input[..changed[0]], &counter, &Term::from(0)),
+        Err(PatchError::Decode(_))
+    ));
 }
 
 fn encode(term: Term) -> Vec<u8> {
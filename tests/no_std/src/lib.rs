@@ -0,0 +1,48 @@
+//! Uses the term types and the slice codec of eetf under `#![no_std]`.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use eetf::{Atom, DecodeError, EncodeError, FixInteger, Map, Term, Tuple};
+
+/// Builds `{reading, Sensor, Value, #{unit => celsius}}`.
+pub fn reading(sensor: &str, value: i32) -> Term {
+    let unit = Map::from([(
+        Term::from(Atom::from("unit")),
+        Term::from(Atom::from("celsius")),
+    )]);
+    Term::from(Tuple::from(vec![
+        Term::from(Atom::from("reading")),
+        Term::from(Atom::from(sensor)),
+        Term::from(FixInteger::from(value)),
+        Term::from(unit),
+    ]))
+}
+
+/// Errors of [`round_trip`].
+#[derive(Debug)]
+pub enum Error {
+    Decode(DecodeError),
+    Encode(EncodeError),
+}
+
+/// Decodes `bytes` and encodes the term again.
+pub fn round_trip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let term = eetf::decode_from_slice(bytes).map_err(Error::Decode)?;
+    eetf::encode_to_vec(&term).map_err(Error::Encode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_works() {
+        let bytes = eetf::encode_to_vec(&reading("t1", 21)).unwrap();
+        assert_eq!(round_trip(&bytes).unwrap(), bytes);
+        assert_eq!(eetf::decode_from_slice(&bytes).unwrap(), reading("t1", 21));
+        assert!(matches!(round_trip(&bytes[..5]), Err(Error::Decode(_))));
+    }
+}
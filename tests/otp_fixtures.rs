@@ -0,0 +1,142 @@
+//! Interop tests against fixtures produced by a real OTP release.
+//!
+//! `tests/fixtures/otp.etf` is generated by `tests/fixtures/gen_fixtures.escript` and
+//! committed, and the tests fail without it. Run `EETF_REGEN_FIXTURES=1 cargo test` with
+//! `escript` on the `PATH` to regenerate it before the tests run.
+extern crate eetf;
+
+use eetf::*;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+struct Fixture {
+    name: String,
+    exact: bool,
+    bytes: Vec<u8>,
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Parses a manifest (a list of `{Name, Exact, Bin}` tuples).
+fn parse_manifest(bytes: &[u8]) -> Vec<Fixture> {
+    let manifest = Term::decode(Cursor::new(bytes)).expect("the manifest is not a term");
+    let entries = match manifest {
+        Term::List(x) => x.elements,
+        other => panic!("the manifest is not a list: {}", other),
+    };
+    entries
+        .into_iter()
+        .map(|entry| match entry {
+            Term::Tuple(Tuple { elements }) => match <[Term; 3]>::try_from(elements) {
+                Ok([Term::Binary(name), Term::Atom(exact), Term::Binary(bytes)]) => Fixture {
+                    name: String::from_utf8(name.bytes.to_vec())
+                        .expect("a fixture name is not UTF-8"),
+                    exact: exact.as_str() == "true",
+                    bytes: bytes.bytes.to_vec(),
+                },
+                Ok(elements) => panic!("malformed manifest entry: {:?}", elements),
+                Err(elements) => panic!("malformed manifest entry: {:?}", elements),
+            },
+            other => panic!("malformed manifest entry: {}", other),
+        })
+        .collect()
+}
+
+/// Decodes, re-encodes and decodes the fixture again.
+fn check_fixture(fixture: &Fixture) -> Result<(), String> {
+    let term = Term::decode(Cursor::new(&fixture.bytes)).map_err(|e| format!("decode: {}", e))?;
+    let mut buf = Vec::new();
+    term.encode(&mut buf)
+        .map_err(|e| format!("encode: {}", e))?;
+    if fixture.exact && buf != fixture.bytes {
+        return Err(format!(
+            "re-encoded bytes differ:\n  otp:  {:?}\n  eetf: {:?}",
+            fixture.bytes, buf
+        ));
+    }
+    let decoded = Term::decode(Cursor::new(&buf)).map_err(|e| format!("re-decode: {}", e))?;
+    if decoded != term {
+        return Err(format!("re-decoded term differs: {} != {}", decoded, term));
+    }
+
+    // Every fixture but the compressed ones is reproduced with its layout.
+    let (term, layout) = Decoder::new(Cursor::new(&fixture.bytes))
+        .decode_with_layout()
+        .map_err(|e| format!("decode with layout: {}", e))?;
+    let mut buf = Vec::new();
+    let mut fallbacks = Vec::new();
+    Encoder::new(&mut buf)
+        .encode_with_layout(&term, &layout, |f| fallbacks.push(f))
+        .map_err(|e| format!("encode with layout: {}", e))?;
+    if !layout.is_compressed() && (buf != fixture.bytes || !fallbacks.is_empty()) {
+        return Err(format!(
+            "bytes re-encoded with the layout differ ({:?}):\n  otp:  {:?}\n  eetf: {:?}",
+            fallbacks, fixture.bytes, buf
+        ));
+    }
+    Ok(())
+}
+
+fn regenerate(output: &Path) {
+    let script = fixtures_dir().join("gen_fixtures.escript");
+    let status = std::process::Command::new("escript")
+        .arg(&script)
+        .arg(output)
+        .status()
+        .unwrap_or_else(|e| panic!("cannot regenerate the fixtures (escript: {})", e));
+    assert!(status.success(), "{} failed: {}", script.display(), status);
+}
+
+#[test]
+fn otp_fixtures_test() {
+    let path = fixtures_dir().join("otp.etf");
+    if std::env::var_os("EETF_REGEN_FIXTURES").is_some() {
+        regenerate(&path);
+    }
+
+    let bytes = std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {}; run `EETF_REGEN_FIXTURES=1 cargo test` with OTP installed and commit it",
+            path.display(),
+            e
+        )
+    });
+    let failures = parse_manifest(&bytes)
+        .iter()
+        .filter_map(|f| check_fixture(f).err().map(|e| format!("{}: {}", f.name, e)))
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn manifest_test() {
+    let entry = |name: &str, exact: bool, bytes: &[u8]| {
+        Term::from(Tuple::from(vec![
+            Term::from(Binary::from(name.as_bytes())),
+            Term::from(Atom::from(if exact { "true" } else { "false" })),
+            Term::from(Binary::from(bytes)),
+        ]))
+    };
+    let manifest = Term::from(List::from(vec![
+        // foo as ATOM_EXT
+        entry("atom", true, &[131, 100, 0, 3, 102, 111, 111]),
+        // foo as SMALL_ATOM_UTF8_EXT
+        entry("small_atom_utf8", false, &[131, 119, 3, 102, 111, 111]),
+        entry("small_atom_utf8_exact", true, &[131, 119, 3, 102, 111, 111]),
+    ]));
+    let mut buf = Vec::new();
+    manifest.encode(&mut buf).unwrap();
+
+    let fixtures = parse_manifest(&buf);
+    assert_eq!(
+        fixtures.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+        ["atom", "small_atom_utf8", "small_atom_utf8_exact"]
+    );
+    assert_eq!(check_fixture(&fixtures[0]), Ok(()));
+    assert_eq!(check_fixture(&fixtures[1]), Ok(()));
+    assert!(check_fixture(&fixtures[2])
+        .unwrap_err()
+        .starts_with("re-encoded bytes differ"));
+}
@@ -0,0 +1,43 @@
+//! The prelude alone is enough for the usual decode, match, reply and encode flow.
+use eetf::prelude::*;
+
+#[test]
+fn prelude_suffices_for_a_request_reply_flow() {
+    let request = tuple!(Atom::from("get"), 7, Binary::from(&b"key"[..]));
+    let bytes = encode_to_vec(&Term::from(request)).unwrap();
+
+    let term = decode_from_slice(&bytes).unwrap();
+    let Term::Tuple(Tuple { elements }) = term else {
+        panic!("{}", term)
+    };
+    let [Term::Atom(command), Term::FixInteger(id), Term::Binary(key)] = &elements[..] else {
+        panic!("{:?}", elements)
+    };
+    assert_eq!((command.as_str(), id.value), ("get", 7));
+
+    let reply = tuple!(
+        Atom::from("ok"),
+        id.clone(),
+        List::from(vec![Term::from(key.clone())])
+    );
+    let mut bytes = encode_to_vec(&Term::from(reply.clone())).unwrap();
+    Term::from(Atom::from("done")).encode(&mut bytes).unwrap();
+    let terms = term_iter(&bytes)
+        .collect::<Result<Vec<_>, DecodeError>>()
+        .unwrap();
+    assert_eq!(terms, [Term::from(reply), Term::from(Atom::from("done"))]);
+}
+
+#[test]
+fn prelude_has_the_conversion_traits() {
+    let term = (Atom::from("point"), 1, 2).into_term();
+    let (name, x, y) = <(Atom, i32, i32)>::from_term(&term).unwrap();
+    assert_eq!((name.as_str(), x + y), ("point", 3));
+    assert!(i32::from_term(&Term::from(Atom::from("x"))).is_err());
+
+    let mut bytes = Vec::new();
+    Encoder::new(&mut bytes).encode(&term).unwrap();
+    assert_eq!(Decoder::new(&bytes[..]).decode().unwrap(), term);
+    let view = decode_view(&bytes).unwrap();
+    assert!(matches!(view, TermView::Tuple(_)));
+}
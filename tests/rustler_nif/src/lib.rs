@@ -0,0 +1,13 @@
+//! A NIF used to test the conversion between `eetf::Term` and NIF terms.
+//!
+//! Build it with `cargo build` and run `escript roundtrip.escript` in this directory.
+use eetf::Term;
+use rustler::{Env, NifResult};
+
+/// Converts `term` to `eetf::Term` and back.
+#[rustler::nif]
+fn roundtrip<'a>(env: Env<'a>, term: rustler::Term<'a>) -> NifResult<Term> {
+    Term::from_nif_term(env, term).map_err(|e| rustler::Error::Term(Box::new(e.to_string())))
+}
+
+rustler::init!("eetf_rustler_nif");
@@ -0,0 +1 @@
+//! Round-trip tests of the eetf codec on wasm32-unknown-unknown (see `tests/`).
@@ -0,0 +1,55 @@
+//! Round-trips terms through the sync codec on wasm32.
+//!
+//! Runs under Node.js by default; set `WASM_BINDGEN_USE_BROWSER=1` to use a headless browser.
+#![cfg(target_arch = "wasm32")]
+
+use eetf::*;
+use std::collections::HashMap;
+use std::io::Cursor;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn round_trip(term: Term) {
+    let mut buf = Vec::new();
+    term.encode(&mut buf).unwrap();
+    assert_eq!(Term::decode(Cursor::new(&buf)).unwrap(), term);
+}
+
+#[wasm_bindgen_test]
+fn scalars_round_trip() {
+    round_trip(Term::from(Atom::from("foo")));
+    round_trip(Term::from(Atom::from("こんにちは")));
+    round_trip(Term::from(FixInteger::from(-1)));
+    round_trip(Term::from(BigInteger::from(u64::MAX)));
+    round_trip(Term::from(Float::try_from(1.5).unwrap()));
+    round_trip(Term::from(Binary::from(&b"bytes"[..])));
+    round_trip(Term::from(BitBinary::from((vec![0b101], 3))));
+    round_trip(Term::from(Pid::new("node@host", 1, 2, 3)));
+}
+
+#[wasm_bindgen_test]
+fn containers_round_trip() {
+    let map = HashMap::from([(
+        Term::from(Atom::from("key")),
+        Term::from(List::from(vec![Term::from(Atom::from("a"))])),
+    )]);
+    round_trip(Term::from(Tuple::from(vec![
+        Term::from(Atom::from("ok")),
+        Term::from(Map::from(map)),
+        Term::from(ImproperList::from((
+            vec![Term::from(Atom::from("a"))],
+            Term::from(Atom::from("b")),
+        ))),
+    ])));
+}
+
+#[wasm_bindgen_test]
+fn compressed_terms_are_decoded() {
+    // term_to_binary(lists:duplicate(20, 1), [compressed])
+    let bytes = [
+        131, 80, 0, 0, 0, 23, 120, 156, 203, 102, 16, 97, 196, 2, 0, 12, 42, 0, 148,
+    ];
+    assert_eq!(
+        Term::decode(Cursor::new(&bytes)).unwrap(),
+        Term::from(ByteList::from(vec![1; 20]))
+    );
+}